@@ -266,6 +266,58 @@ pub unsafe extern "C" fn graph_name(graph: *const ()) -> *const c_char {
     with_unchecked(graph, |graph: &Graph| new_c_str(graph.name().to_string()))
 }
 
+/// # Safety
+///
+/// Expects `graph` to be a valid pointer to a graph.
+#[no_mangle]
+pub unsafe extern "C" fn graph_canonical_name(graph: *const ()) -> *const c_char {
+    with_unchecked(graph, |graph: &Graph| new_c_str(graph.canonical_name()))
+}
+
+/// Returns the number of inputs of the graph.
+///
+/// # Safety
+///
+/// Expects `graph` to be a valid pointer to a graph.
+#[no_mangle]
+pub unsafe extern "C" fn graph_input_count(graph: *const ()) -> usize {
+    with_unchecked(graph, |graph: &Graph| graph.input_layout().fields().len())
+}
+
+/// Returns the name of the input of the graph at `index`, in declaration order, or null
+/// if there is no such input.
+///
+/// # Safety
+///
+/// Expects `graph` to be a valid pointer to a graph.
+#[no_mangle]
+pub unsafe extern "C" fn graph_input_name(graph: *const (), index: usize) -> *const c_char {
+    with_unchecked(graph, |graph: &Graph| {
+        if let Some(name) = graph.input_names().nth(index) {
+            new_c_str(name.to_string())
+        } else {
+            std::ptr::null()
+        }
+    })
+}
+
+/// Returns the layout of the input of the graph at `index`, in declaration order, or null
+/// if there is no such input. The layout lives as long as the graph.
+///
+/// # Safety
+///
+/// Expects `graph` to be a valid pointer to a graph.
+#[no_mangle]
+pub unsafe extern "C" fn graph_input_layout_at(graph: *const (), index: usize) -> *const () {
+    with_unchecked(graph, |graph: &Graph| {
+        if let Some((_, layout)) = graph.input_layout().fields().get(index) {
+            layout as *const Layout as *const ()
+        } else {
+            std::ptr::null()
+        }
+    })
+}
+
 /// # Safety
 ///
 /// Expects `graph` to be a valid pointer to a graph and `key` to be a valid pointer
@@ -281,6 +333,26 @@ pub unsafe extern "C" fn graph_get_metadata(graph: *const (), key: *const c_char
     })
 }
 
+/// Returns the serialized JSON metadata value under `key`, or null if there is none.
+///
+/// # Safety
+///
+/// Expects `graph` to be a valid pointer to a graph and `key` to be a valid pointer
+/// to a C-style string.
+#[no_mangle]
+pub unsafe extern "C" fn graph_get_metadata_json_value(
+    graph: *const (),
+    key: *const c_char,
+) -> *const c_char {
+    with_unchecked(graph, |graph: &Graph| {
+        if let Some(value) = graph.metadata_json().get(&*from_c_str(key)) {
+            new_c_str(value.to_string())
+        } else {
+            std::ptr::null()
+        }
+    })
+}
+
 /// # Safety
 ///
 /// Expects `graph` to be a valid pointer to a graph.
@@ -302,6 +374,22 @@ pub unsafe extern "C" fn graph_load(bytes: *const u8, len: usize) -> Outcome {
     })
 }
 
+/// Loads a graph from a dump in memory, such as a memory-mapped file, building its
+/// resources directly from `bytes` when possible, instead of copying them first. This is
+/// only possible for resources stored uncompressed in the dump. Dumps created by jyafn
+/// compress resources, which are then decompressed into a buffer, as in `graph_load`.
+///
+/// # Safety
+///
+/// Expects `bytes` to point to the beginning of a valid byte slice in memory with the size
+/// of _at least_ `len`. The slice must not be modified until this function returns. The
+/// returned graph does not refer to `bytes`, so the slice may be freed (or unmapped) as
+/// soon as this function returns.
+#[no_mangle]
+pub unsafe extern "C" fn graph_load_borrowed(bytes: *const u8, len: usize) -> Outcome {
+    try_panic_to_outcome(|| Graph::load_from_slice(std::slice::from_raw_parts(bytes, len)))
+}
+
 /// # Safety
 ///
 /// Expects the `graph` parameter to be a valid pointer to a graph.
@@ -322,6 +410,14 @@ pub unsafe extern "C" fn graph_to_json(graph: *const ()) -> *const c_char {
     with_unchecked(graph, |graph: &Graph| new_c_str(graph.to_json()))
 }
 
+/// # Safety
+///
+/// Expects `graph` to be a valid pointer to a graph.
+#[no_mangle]
+pub unsafe extern "C" fn graph_to_dot(graph: *const ()) -> *const c_char {
+    with_unchecked(graph, |graph: &Graph| new_c_str(graph.to_dot()))
+}
+
 /// # Safety
 ///
 /// Expects `graph` to be a valid pointer to a graph.
@@ -332,6 +428,14 @@ pub unsafe extern "C" fn graph_render(graph: *const ()) -> Outcome {
     })
 }
 
+/// # Safety
+///
+/// Expects `graph` to be a valid pointer to a graph.
+#[no_mangle]
+pub unsafe extern "C" fn graph_verify(graph: *const ()) -> Outcome {
+    try_with(graph, |graph: &Graph| graph.verify())
+}
+
 /// # Safety
 ///
 /// Expects `graph` to be a valid pointer to a graph.
@@ -340,6 +444,17 @@ pub unsafe extern "C" fn graph_compile(graph: *const ()) -> Outcome {
     try_with(graph, |graph: &Graph| graph.compile())
 }
 
+/// # Safety
+///
+/// Expects `graph` to be a valid pointer to a graph and `entry` to be a pointer to a
+/// C-style string.
+#[no_mangle]
+pub unsafe extern "C" fn graph_compile_named(graph: *const (), entry: *const c_char) -> Outcome {
+    try_with(graph, |graph: &Graph| {
+        graph.compile_named(&from_c_str(entry))
+    })
+}
+
 /// # Safety
 ///
 /// Expects `graph` to be a valid pointer to a graph.
@@ -418,6 +533,14 @@ pub unsafe extern "C" fn layout_is_scalar(layout: *const ()) -> bool {
     with_unchecked(layout, |layout: &Layout| matches!(layout, Layout::Scalar))
 }
 
+/// # Safety
+///
+/// Expects the `layout` parameter to be a valid pointer to a layout.
+#[no_mangle]
+pub unsafe extern "C" fn layout_is_int(layout: *const ()) -> bool {
+    with_unchecked(layout, |layout: &Layout| matches!(layout, Layout::Int))
+}
+
 /// # Safety
 ///
 /// Expects the `layout` parameter to be a valid pointer to a layout.
@@ -636,6 +759,14 @@ pub unsafe extern "C" fn function_name(func: *const ()) -> *const c_char {
     })
 }
 
+/// # Safety
+///
+/// Expects the `func` parameter to be a valid pointer to a jyafn function.
+#[no_mangle]
+pub unsafe extern "C" fn function_entry(func: *const ()) -> *const c_char {
+    with_unchecked(func, |func: &Function| new_c_str(func.entry().to_string()))
+}
+
 /// # Safety
 ///
 /// Expects the `func` parameter to be a valid pointer to a jyafn function.
@@ -672,6 +803,26 @@ pub unsafe extern "C" fn function_output_layout(func: *const ()) -> *const () {
     })
 }
 
+/// # Safety
+///
+/// Expects the `func` parameter to be a valid pointer to a jyafn function.
+#[no_mangle]
+pub unsafe extern "C" fn function_input_schema(func: *const ()) -> *const c_char {
+    with_unchecked(func, |func: &Function| {
+        new_c_str(func.input_schema_json().to_string())
+    })
+}
+
+/// # Safety
+///
+/// Expects the `func` parameter to be a valid pointer to a jyafn function.
+#[no_mangle]
+pub unsafe extern "C" fn function_output_schema(func: *const ()) -> *const c_char {
+    with_unchecked(func, |func: &Function| {
+        new_c_str(func.output_schema_json().to_string())
+    })
+}
+
 /// # Safety
 ///
 /// Expects the `func` parameter to be a valid pointer to a jyafn function.
@@ -766,27 +917,35 @@ pub unsafe extern "C" fn function_call_raw(
     input: *const u8,
     output: *mut u8,
 ) -> Outcome {
-    with_unchecked(func, |func: &Function| {
-        let outcome = std::panic::catch_unwind(|| {
-            let input = std::slice::from_raw_parts(input, func.input_size().in_bytes());
-            let output = std::slice::from_raw_parts_mut(output, func.output_size().in_bytes());
-
-            let fn_err = func.call_raw(input, output);
-            if !fn_err.is_null() {
-                let fn_err = Box::from_raw(fn_err).take();
-                return Outcome::from_result(Result::<(), Error>::Err(rust::Error::StatusRaised(
-                    fn_err,
-                )));
-            }
+    try_with(func, |func: &Function| {
+        let input = std::slice::from_raw_parts(input, func.input_size().in_bytes());
+        let output = std::slice::from_raw_parts_mut(output, func.output_size().in_bytes());
+        func.try_call_raw(input, output)
+    })
+}
 
-            Outcome::from_result(Result::<(), Error>::Ok(()))
-        });
-        match outcome {
-            Ok(status) => status,
-            Err(_le_oops) => Outcome::from_result(Result::<(), Error>::Err(
-                "function raw call panicked (see stderr)".to_string().into(),
-            )),
-        }
+/// Calls the function on the raw `input`, of `input_len` bytes, writing the result to
+/// `output`, of `output_len` bytes. Unlike `function_call_raw`, this returns an error if
+/// the buffers are not of the sizes given by `function_input_size` and
+/// `function_output_size`.
+///
+/// # Safety
+///
+/// Expects the `func` parameter to be a valid pointer to a jyafn function, `input` to be
+/// a valid pointer to a slice of at least `input_len` bytes and `output` to be a valid
+/// pointer to a slice of at least `output_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn function_try_call_raw(
+    func: *const (),
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_len: usize,
+) -> Outcome {
+    try_with(func, |func: &Function| {
+        let input = std::slice::from_raw_parts(input, input_len);
+        let output = std::slice::from_raw_parts_mut(output, output_len);
+        func.try_call_raw(input, output)
     })
 }
 
@@ -826,6 +985,61 @@ pub unsafe extern "C" fn function_eval_json(func: *const (), input: *mut c_char)
     })
 }
 
+/// Evaluates the function on the raw `input` and returns the output as a JSON C-style
+/// string, decoded according to the output layout. This is `function_eval_raw` for
+/// callers who have binary input but would rather not decode the output themselves.
+///
+/// # Safety
+///
+/// Expects
+/// 1. the `func` parameter to be a valid pointer to a jyafn function
+/// 2. the `input` paramenter to be a valid pointer to a slice of size _at least_ the
+///    function input size (given by `function_input_size`).
+#[no_mangle]
+pub unsafe extern "C" fn function_eval_to_json(func: *const (), input: *const u8) -> Outcome {
+    try_with(func, |func: &Function| {
+        let input = std::slice::from_raw_parts(input, func.input_size().in_bytes());
+        let output_value: serde_json::Value =
+            func.eval_raw_with_decoder(input, rust::layout::ZeroDecoder::new())?;
+        let output_str = serde_json::to_string(&output_value).expect("can serialize");
+
+        Ok(new_c_str(output_str))
+    })
+}
+
+/// Evaluates the function on the JSON in `input`, writing the JSON of the output to the
+/// buffer `output`, of `output_len` bytes, and the number of bytes written to `written`.
+/// The output is not NUL-terminated. Unlike `function_eval_json`, no intermediate values
+/// are built and nothing is allocated for the output. This returns an error if the
+/// output does not fit in the buffer.
+///
+/// # Safety
+///
+/// Expects the `func` parameter to be a valid pointer to a jyafn function, `input` to be
+/// a pointer to a C-style string, `output` to be a valid pointer to a buffer of at least
+/// `output_len` bytes and `written` to be a valid pointer to a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn function_eval_json_into(
+    func: *const (),
+    input: *const c_char,
+    output: *mut u8,
+    output_len: usize,
+    written: *mut usize,
+) -> Outcome {
+    try_with(func, |func: &Function| {
+        let input = CStr::from_ptr(input).to_str().map_err(|e| e.to_string())?;
+        let mut output = std::io::Cursor::new(std::slice::from_raw_parts_mut(output, output_len));
+        let result = func.eval_json_streaming(input.trim(), &mut output);
+        if result.is_err() && output.position() as usize == output_len {
+            return Err(format!("output does not fit in buffer of {output_len} bytes").into());
+        }
+        result?;
+        *written = output.position() as usize;
+
+        Ok(())
+    })
+}
+
 /// # Safety
 ///
 /// Expects the `func` parameter to be a valid pointer to a jyafn function. The pointer
@@ -835,29 +1049,39 @@ pub unsafe extern "C" fn function_drop(func: *mut ()) {
     let _ = Box::from_raw(func as *mut Function);
 }
 
-// #[no_mangle]
-// pub extern "C" fn pfunc_inscribe(
-//     name: *const c_char,
-//     fn_ptr: *const (),
-//     signature: *const u8,
-//     signature_len: usize,
-//     returns: u8,
-// ) -> Outcome {
-//     unsafe {
-//         try_panic_to_outcome(|| {
-//             from_ptr_result((|| {
-//                 let name_cstr = CStr::from_ptr(name);
-//                 let name_str = name_cstr.to_string_lossy();
-//                 let signature = std::slice::from_raw_parts(signature, signature_len)
-//                     .iter()
-//                     .copied()
-//                     .map(rust::Type::try_from)
-//                     .collect::<Result<Vec<_>, _>>()?;
-//                 let returns: rust::Type = returns.try_into()?;
-
-//                 rust::pfunc::inscribe(&name_str, fn_ptr, &signature, returns)
-//                     .map(|_| std::ptr::null::<()>())
-//             })())
-//         })
-//     }
-// }
+/// Inscribes a new pure function under `name`, so that graphs can call it. The
+/// signature is a list of `signature_len` type ids (see `Type`) and `returns` is the type
+/// id of the return value. Scalars are passed as `double` and all other types as
+/// `int64_t`.
+///
+/// # Safety
+///
+/// Expects `name` to be a valid pointer to a C-style string, `signature` to point to
+/// `signature_len` bytes and `fn_ptr` to be a pointer to a function obeying the given
+/// signature that lives for the rest of the process. The function must also uphold all
+/// the requirements of a pure function: same outputs for the same inputs and never
+/// panicking or unwinding.
+#[no_mangle]
+pub unsafe extern "C" fn pfunc_inscribe(
+    name: *const c_char,
+    fn_ptr: *const (),
+    signature: *const u8,
+    signature_len: usize,
+    returns: u8,
+) -> Outcome {
+    try_panic_to_outcome(|| {
+        let name = from_c_str(name);
+        let signature = if signature_len == 0 {
+            vec![]
+        } else {
+            std::slice::from_raw_parts(signature, signature_len)
+                .iter()
+                .copied()
+                .map(rust::Type::try_from)
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let returns = rust::Type::try_from(returns)?;
+
+        rust::pfunc::inscribe(&name, fn_ptr, &signature, returns)
+    })
+}
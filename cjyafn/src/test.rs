@@ -1,6 +1,360 @@
 use super::*;
+use rust::layout::RefValue;
+use std::collections::HashMap;
 
 #[test]
 fn oops_test() {
     println!("{:?}", rust::utils::parse_datetime as *const ())
 }
+
+#[test]
+fn test_function_schema() {
+    let mut graph = Graph::new();
+    let RefValue::Scalar(x) = graph.input("x".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let RefValue::List(ys) =
+        graph.input("ys".to_string(), Layout::List(Box::new(Layout::Scalar), 3))
+    else {
+        unreachable!()
+    };
+    let RefValue::Scalar(y) = ys[0] else {
+        unreachable!()
+    };
+    graph.input(
+        "at".to_string(),
+        Layout::DateTime(rust::layout::ISOFORMAT.to_string()),
+    );
+    let sum = graph.insert(rust::op::Add, vec![x, y]).unwrap();
+    graph.output(RefValue::Scalar(sum), Layout::Scalar).unwrap();
+
+    let mut dumped = std::io::Cursor::new(vec![]);
+    graph.dump(&mut dumped).unwrap();
+    let dumped = dumped.into_inner();
+
+    unsafe {
+        let func = outcome_consume_ok(function_load(dumped.as_ptr(), dumped.len()));
+
+        let input_schema = function_input_schema(func);
+        let input: serde_json::Value = serde_json::from_str(&from_c_str(input_schema)).unwrap();
+        free_str(input_schema);
+        assert_eq!(input["type"], "object");
+        assert_eq!(input["required"], serde_json::json!(["x", "ys", "at"]));
+        assert_eq!(input["properties"]["x"]["type"], "number");
+        assert_eq!(input["properties"]["ys"]["type"], "array");
+        assert_eq!(input["properties"]["ys"]["items"]["type"], "number");
+        assert_eq!(input["properties"]["ys"]["minItems"], 3);
+        assert_eq!(input["properties"]["at"]["type"], "string");
+
+        let output_schema = function_output_schema(func);
+        let output: serde_json::Value = serde_json::from_str(&from_c_str(output_schema)).unwrap();
+        free_str(output_schema);
+        assert_eq!(output["type"], "number");
+
+        function_drop(func);
+    }
+}
+
+#[test]
+fn test_pfunc_inscribe() {
+    extern "C" fn double(x: f64) -> f64 {
+        2.0 * x
+    }
+
+    unsafe fn inscribe(name: &str, signature: &[u8], returns: u8) -> Result<(), String> {
+        let name = CString::new(name).unwrap();
+        let outcome = pfunc_inscribe(
+            name.as_ptr(),
+            double as *const (),
+            signature.as_ptr(),
+            signature.len(),
+            returns,
+        );
+        if outcome_is_ok(outcome) {
+            outcome_consume_ok(outcome);
+            Ok(())
+        } else {
+            let err = outcome_consume_err(outcome);
+            let message = from_c_str(err).into_owned();
+            free_str(err);
+            Err(message)
+        }
+    }
+
+    unsafe {
+        inscribe("test_double", &[0], 0).unwrap();
+        let err = inscribe("test_double", &[0], 0).unwrap_err();
+        assert!(err.contains("already inscribed"), "{err}");
+        let err = inscribe("sqrt", &[0], 0).unwrap_err();
+        assert!(err.contains("builtin"), "{err}");
+        let err = inscribe("test_ptr", &[3], 0).unwrap_err();
+        assert!(err.contains("cannot take or return"), "{err}");
+        let err = inscribe("test_bad_type", &[42], 0).unwrap_err();
+        assert!(err.contains("not a valid type id"), "{err}");
+    }
+
+    let mut graph = Graph::new();
+    let RefValue::Scalar(x) = graph.input("x".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let doubled = graph
+        .insert(rust::op::Call("test_double".to_string()), vec![x])
+        .unwrap();
+    graph
+        .output(RefValue::Scalar(doubled), Layout::Scalar)
+        .unwrap();
+
+    let func = graph.compile().unwrap();
+    let output: f64 = func.eval(&serde_json::json!({"x": 2.5})).unwrap();
+    assert_eq!(output, 5.0);
+}
+
+#[test]
+fn test_graph_load_borrowed() {
+    let mut graph = Graph::new();
+    let RefValue::Scalar(x) = graph.input("x".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let doubled = graph.insert(rust::op::Add, vec![x, x]).unwrap();
+    graph
+        .output(RefValue::Scalar(doubled), Layout::Scalar)
+        .unwrap();
+
+    let path = std::env::temp_dir().join(format!("cjyafn-test-{}.jyafn", std::process::id()));
+    let mut file = std::fs::File::create(&path).unwrap();
+    graph.dump(&mut file).unwrap();
+    drop(file);
+    let file = std::fs::File::open(&path).unwrap();
+    let len = file.metadata().unwrap().len() as usize;
+
+    unsafe {
+        use std::os::fd::AsRawFd;
+
+        let mapped = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        );
+        assert_ne!(mapped, libc::MAP_FAILED);
+        let first = outcome_consume_ok(graph_load_borrowed(mapped as *const u8, len));
+        let second = outcome_consume_ok(graph_load_borrowed(mapped as *const u8, len));
+        libc::munmap(mapped, len);
+        drop(file);
+        std::fs::remove_file(&path).unwrap();
+
+        for graph in [first, second] {
+            let func = with_unchecked(graph, |graph: &Graph| graph.compile()).unwrap();
+            let output: f64 = func.eval(&serde_json::json!({"x": 2.5})).unwrap();
+            assert_eq!(output, 5.0);
+            graph_drop(graph);
+        }
+    }
+}
+
+#[test]
+fn test_graph_get_metadata_json_value() {
+    let mut graph = Graph::new();
+    graph.metadata_json_mut().insert(
+        "features".to_string(),
+        serde_json::json!({"names": ["a", "b"]}),
+    );
+    let graph = Box::leak(Box::new(graph)) as *mut Graph as *mut ();
+
+    unsafe {
+        let key = CString::new("features").unwrap();
+        let value = graph_get_metadata_json_value(graph, key.as_ptr());
+        let parsed: serde_json::Value = serde_json::from_str(&from_c_str(value)).unwrap();
+        free_str(value);
+        assert_eq!(parsed, serde_json::json!({"names": ["a", "b"]}));
+
+        let key = CString::new("missing").unwrap();
+        assert!(graph_get_metadata_json_value(graph, key.as_ptr()).is_null());
+
+        graph_drop(graph);
+    }
+}
+
+#[test]
+fn test_function_eval_json_into() {
+    let mut graph = Graph::new();
+    let RefValue::Scalar(x) = graph.input("x".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let RefValue::Scalar(y) = graph.input("y".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let sum = graph.insert(rust::op::Add, vec![x, y]).unwrap();
+    graph
+        .output(
+            RefValue::Struct(HashMap::from([
+                ("sum".to_string(), RefValue::Scalar(sum)),
+                ("x".to_string(), RefValue::Scalar(x)),
+            ])),
+            Layout::Struct(rust::layout::Struct(vec![
+                ("sum".to_string(), Layout::Scalar),
+                ("x".to_string(), Layout::Scalar),
+            ])),
+        )
+        .unwrap();
+
+    let mut dumped = std::io::Cursor::new(vec![]);
+    graph.dump(&mut dumped).unwrap();
+    let dumped = dumped.into_inner();
+    let input = CString::new(r#"{"x": 1.5, "y": 2}"#).unwrap();
+
+    unsafe {
+        let func = outcome_consume_ok(function_load(dumped.as_ptr(), dumped.len()));
+
+        let expected = outcome_consume_ok(function_eval_json(func, input.as_ptr() as *mut _));
+        let expected = from_c_str(*(expected as *const *const c_char));
+
+        let mut buffer = [0u8; 64];
+        let mut written = 0;
+        let outcome = function_eval_json_into(
+            func,
+            input.as_ptr(),
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut written,
+        );
+        outcome_consume_ok(outcome);
+        assert_eq!(std::str::from_utf8(&buffer[..written]).unwrap(), expected);
+
+        let outcome = function_eval_json_into(
+            func,
+            input.as_ptr(),
+            buffer.as_mut_ptr(),
+            written - 1,
+            &mut written,
+        );
+        assert!(!outcome_is_ok(outcome));
+        let err = outcome_consume_err(outcome);
+        assert!(from_c_str(err).contains("does not fit"));
+
+        function_drop(func);
+    }
+}
+
+#[test]
+fn test_function_eval_to_json() {
+    let mut graph = Graph::new();
+    let RefValue::Scalar(x) = graph.input("x".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let RefValue::Scalar(y) = graph.input("y".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let sum = graph.insert(rust::op::Add, vec![x, y]).unwrap();
+    let positive = graph.insert(rust::op::Gt, vec![sum, 0.0.into()]).unwrap();
+    graph
+        .output(
+            RefValue::Struct(HashMap::from([
+                ("sum".to_string(), RefValue::Scalar(sum)),
+                ("positive".to_string(), RefValue::Bool(positive)),
+                (
+                    "xs".to_string(),
+                    RefValue::List(vec![RefValue::Scalar(x), RefValue::Scalar(x)]),
+                ),
+            ])),
+            Layout::Struct(rust::layout::Struct(vec![
+                ("sum".to_string(), Layout::Scalar),
+                ("positive".to_string(), Layout::Bool),
+                ("xs".to_string(), Layout::List(Box::new(Layout::Scalar), 2)),
+            ])),
+        )
+        .unwrap();
+
+    let mut dumped = std::io::Cursor::new(vec![]);
+    graph.dump(&mut dumped).unwrap();
+    let dumped = dumped.into_inner();
+    let input = CString::new(r#"{"x": 1.5, "y": 2}"#).unwrap();
+    let raw_input = [1.5f64, 2.0]
+        .iter()
+        .flat_map(|v| v.to_ne_bytes())
+        .collect::<Vec<_>>();
+
+    unsafe {
+        let func = outcome_consume_ok(function_load(dumped.as_ptr(), dumped.len()));
+
+        let expected = outcome_consume_ok(function_eval_json(func, input.as_ptr() as *mut _));
+        let expected = from_c_str(*(expected as *const *const c_char));
+
+        let output = outcome_consume_ok(function_eval_to_json(func, raw_input.as_ptr()));
+        let output = from_c_str(*(output as *const *const c_char));
+        assert_eq!(output, expected);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+            serde_json::json!({"sum": 3.5, "positive": true, "xs": [1.5, 1.5]})
+        );
+
+        function_drop(func);
+    }
+}
+
+#[test]
+fn test_function_try_call_raw() {
+    let mut graph = Graph::new();
+    let RefValue::Scalar(x) = graph.input("x".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let double = graph.insert(rust::op::Add, vec![x, x]).unwrap();
+    graph
+        .output(RefValue::Scalar(double), Layout::Scalar)
+        .unwrap();
+
+    let mut dumped = std::io::Cursor::new(vec![]);
+    graph.dump(&mut dumped).unwrap();
+    let dumped = dumped.into_inner();
+
+    unsafe {
+        let func = outcome_consume_ok(function_load(dumped.as_ptr(), dumped.len()));
+        let input = 1.5f64.to_ne_bytes();
+        let mut output = [0u8; 8];
+
+        let outcome = function_call_raw(func, input.as_ptr(), output.as_mut_ptr());
+        outcome_consume_ok(outcome);
+        assert_eq!(f64::from_ne_bytes(output), 3.0);
+
+        let outcome = function_try_call_raw(func, input.as_ptr(), 4, output.as_mut_ptr(), 8);
+        assert!(!outcome_is_ok(outcome));
+        let err = from_c_str(outcome_consume_err(outcome));
+        assert!(
+            err.contains("expected buffer of 8 bytes, got 4 bytes"),
+            "{err}"
+        );
+
+        let outcome = function_try_call_raw(func, input.as_ptr(), 8, output.as_mut_ptr(), 0);
+        assert!(!outcome_is_ok(outcome));
+        let err = from_c_str(outcome_consume_err(outcome));
+        assert!(err.contains("checking output"), "{err}");
+
+        function_drop(func);
+    }
+}
+
+#[test]
+fn test_graph_inputs() {
+    let mut graph = Graph::new();
+    graph.input("y".to_string(), Layout::Scalar);
+    graph.input("x".to_string(), Layout::Bool);
+
+    unsafe {
+        let graph = &graph as *const Graph as *const ();
+        assert_eq!(graph_input_count(graph), 2);
+
+        let name = graph_input_name(graph, 0);
+        assert_eq!(from_c_str(name), "y");
+        free_str(name);
+        let name = graph_input_name(graph, 1);
+        assert_eq!(from_c_str(name), "x");
+        free_str(name);
+        assert!(graph_input_name(graph, 2).is_null());
+
+        assert!(layout_is_scalar(graph_input_layout_at(graph, 0)));
+        assert!(layout_is_bool(graph_input_layout_at(graph, 1)));
+        assert!(graph_input_layout_at(graph, 2).is_null());
+    }
+}
@@ -0,0 +1,126 @@
+//! Derive macros for `jyafn`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Type};
+
+/// Derives `jyafn::layout::Decode` for a struct with named fields, so that it can be
+/// decoded from a `Layout::Struct`. Fields are matched to the fields of the layout by
+/// name, so their order does not matter. Fields of the layout that are absent in the
+/// struct are skipped. Decoding panics if a field of the struct is absent in the layout.
+///
+/// Each field must itself implement `Decode`. This covers scalars (`f64`, `f32` and the
+/// integer types), `bool`, `String` for symbols, `chrono::DateTime<Utc>` for datetimes,
+/// `Vec<T>` for lists and other structs deriving `JyafnDecode`.
+///
+/// # Usage
+///
+/// ```ignore
+/// #[derive(JyafnDecode)]
+/// struct Prediction {
+///     score: f64,
+///     is_fraud: bool,
+///     at: chrono::DateTime<chrono::Utc>,
+///     history: Vec<f64>,
+/// }
+///
+/// let prediction: Prediction = func.eval(&input)?;
+/// ```
+#[proc_macro_derive(JyafnDecode)]
+pub fn derive_decode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_decode(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_decode(input: &DeriveInput) -> Result<TokenStream2, syn::Error> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "`JyafnDecode` can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "`JyafnDecode` can only be derived for structs with named fields",
+        ));
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut declarations = vec![];
+    let mut arms = vec![];
+    let mut initializers = vec![];
+
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("fields are named");
+        let ty = &field.ty;
+        check_decodable(ty)?;
+
+        let field_name = ident.to_string();
+        let local = format_ident!("__field_{}", ident);
+        declarations.push(quote! { let mut #local = None; });
+        arms.push(quote_spanned! {ty.span()=>
+            #field_name => {
+                #local = Some(<#ty as ::jyafn::layout::Decode>::build(field, symbols, visitor))
+            }
+        });
+        initializers.push(quote! {
+            #ident: #local.unwrap_or_else(|| {
+                panic!("Missing field {:?} in layout for {}", #field_name, stringify!(#name))
+            })
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::jyafn::layout::Decode for #name #ty_generics #where_clause {
+            fn build(
+                layout: &::jyafn::layout::Layout,
+                symbols: &dyn ::jyafn::layout::Sym,
+                visitor: &mut ::jyafn::layout::Visitor,
+            ) -> Self {
+                let ::jyafn::layout::Layout::Struct(fields) = layout else {
+                    panic!("Bad layout for {}: {layout:?}", stringify!(#name))
+                };
+                #(#declarations)*
+
+                for (name, field) in &fields.0 {
+                    match name.as_str() {
+                        #(#arms)*
+                        _ => visitor.skip(field.size().in_slots()),
+                    }
+                }
+
+                #name { #(#initializers),* }
+            }
+        }
+    })
+}
+
+/// Rejects the field types that can never be decoded from a layout, with an error
+/// pointing to the offending type. All other types are checked by the compiler through
+/// the `Decode` bound.
+fn check_decodable(ty: &Type) -> Result<(), syn::Error> {
+    let kind = match ty {
+        Type::Path(_) | Type::Tuple(_) => return Ok(()),
+        Type::Paren(paren) => return check_decodable(&paren.elem),
+        Type::Group(group) => return check_decodable(&group.elem),
+        Type::Reference(_) => "references",
+        Type::Ptr(_) => "raw pointers",
+        Type::Slice(_) => "slices",
+        Type::Array(_) => "arrays (use `Vec<_>` for lists)",
+        Type::BareFn(_) => "function pointers",
+        Type::TraitObject(_) | Type::ImplTrait(_) => "trait objects",
+        Type::Never(_) => "the never type",
+        _ => "this type",
+    };
+
+    Err(syn::Error::new(
+        ty.span(),
+        format!("field type cannot be mapped to any jyafn layout: {kind} cannot be decoded"),
+    ))
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, TimeZone, Utc};
+use jyafn::layout::{Layout, Struct, ISOFORMAT};
+use jyafn::Graph;
+use jyafn_derive::JyafnDecode;
+use serde_derive::Serialize;
+
+#[derive(Debug, PartialEq, Serialize, JyafnDecode)]
+struct Location {
+    lat: f64,
+    lon: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, JyafnDecode)]
+struct Hotel {
+    name: String,
+    score: f64,
+    stars: i64,
+    is_open: bool,
+    #[serde(serialize_with = "serialize_datetime")]
+    updated_at: DateTime<Utc>,
+    location: Location,
+    prices: Vec<f64>,
+}
+
+fn serialize_datetime<S: serde::Serializer>(
+    datetime: &DateTime<Utc>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&datetime.format(ISOFORMAT).to_string())
+}
+
+fn hotel_layout() -> Layout {
+    let field = |name: &str, layout: Layout| (name.to_string(), layout);
+    Layout::Struct(Struct(vec![
+        // In a different order than the struct, with an extra field.
+        field("score", Layout::Scalar),
+        field("name", Layout::Symbol),
+        field("stars", Layout::Int),
+        field("extra", Layout::List(Box::new(Layout::Scalar), 2)),
+        field("is_open", Layout::Bool),
+        field("updated_at", Layout::DateTime(ISOFORMAT.to_string())),
+        field(
+            "location",
+            Layout::Struct(Struct(vec![
+                field("lon", Layout::Scalar),
+                field("lat", Layout::Scalar),
+            ])),
+        ),
+        field("prices", Layout::List(Box::new(Layout::Scalar), 3)),
+    ]))
+}
+
+#[test]
+fn test_round_trip() {
+    let mut graph = Graph::new();
+    let hotel = graph.input("hotel".to_string(), hotel_layout());
+    graph.output(hotel, hotel_layout()).unwrap();
+    let func = graph.compile().unwrap();
+
+    let hotel = Hotel {
+        name: "Hotel California".to_string(),
+        score: 9.5,
+        stars: 5,
+        is_open: true,
+        updated_at: Utc.with_ymd_and_hms(2024, 5, 17, 12, 30, 0).unwrap(),
+        location: Location {
+            lat: 34.05,
+            lon: -118.25,
+        },
+        prices: vec![100.0, 150.5, 200.0],
+    };
+    let mut input = serde_json::to_value(&hotel).unwrap();
+    input["extra"] = serde_json::json!([1.0, 2.0]);
+
+    let decoded: Hotel = func.eval(&serde_json::json!({ "hotel": input })).unwrap();
+    assert_eq!(decoded, hotel);
+}
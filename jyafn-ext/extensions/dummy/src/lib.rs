@@ -1,10 +1,15 @@
 //! This crate implements the `dummy` extension for jyafn. This extension is intended for
 //! testing and debugging purposes.
 //!
-//! The only resource declared by this extension is the `Dummy` resource, with three methods:
+//! The only resource declared by this extension is the `Dummy` resource, with five methods:
 //! ```
 //! // Gets the divison of `x` by the number supplied in the resource creation.
 //! get(x: scalar) -> scalar;
+//! // Gets the division of the sum of a list of any length by the same number.
+//! sum(x: [scalar; dynamic]) -> scalar;
+//! // Gets the `k` greatest elements of a list, in decreasing order. The length of the
+//! // output is only known at call time.
+//! top_k(x: [scalar; 4], k: scalar) -> [scalar; max 4];
 //! // Always errors.
 //! err(x: scalar) -> scalar;
 //! // Always panics.
@@ -41,6 +46,39 @@ impl Dummy {
 
     jyafn_ext::method!(get);
 
+    fn sum(
+        &self,
+        input: jyafn_ext::Input,
+        mut output: jyafn_ext::OutputBuilder,
+    ) -> Result<(), String> {
+        let sum = (0..input.len()).map(|i| input.get_f64(i)).sum::<f64>();
+        output.push_f64(self.do_get(sum));
+        Ok(())
+    }
+
+    jyafn_ext::method!(sum);
+
+    fn top_k(
+        &self,
+        input: jyafn_ext::Input,
+        mut output: jyafn_ext::OutputBuilder,
+    ) -> Result<(), String> {
+        let mut reader = jyafn_ext::InputReader::new(input);
+        let mut x = reader.read_n_f64(4);
+        let k = reader.read_f64();
+        if k.is_nan() || k < 0.0 {
+            return Err(format!("k must be non-negative, got {k}"));
+        }
+
+        x.sort_by(|a, b| b.total_cmp(a));
+        x.truncate(k as usize);
+        output.copy_from_f64(&x);
+        output.finish_with_len(x.len());
+        Ok(())
+    }
+
+    jyafn_ext::method!(top_k);
+
     fn err(&self, _: jyafn_ext::Input, _: jyafn_ext::OutputBuilder) -> Result<(), String> {
         Err("oops! wrooong!!".to_string())
     }
@@ -73,6 +111,8 @@ impl Resource for Dummy {
         jyafn_ext::declare_methods! {
             match method:
                 get(x: scalar) -> scalar;
+                sum(x: [scalar; dynamic]) -> scalar;
+                top_k(x: [scalar; 4], k: scalar) -> [scalar; max 4];
                 err(x: scalar) -> scalar;
                 panic(x: scalar) -> scalar;
         }
@@ -93,4 +133,78 @@ mod test {
             println!("{:?}", CString::from_raw(ptr));
         }
     }
+
+    #[test]
+    fn test_get() {
+        let dummy = Dummy::from_bytes(b"2.0").unwrap_or_else(|err| panic!("{}", err.to_string()));
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&dummy, "get"),
+            jyafn_ext::serde_json::json!(0.5)
+        );
+        assert_eq!(
+            jyafn_ext::test::call_method(&dummy, "get", &[3.0f64.to_bits()]),
+            Ok(jyafn_ext::serde_json::json!(1.5))
+        );
+    }
+
+    #[test]
+    fn test_sum() {
+        let dummy = Dummy { number: 2.0 };
+        let declared = dummy.get_method("sum").unwrap();
+        assert_eq!(declared.dynamic_inputs, vec!["x".to_string()]);
+        assert!(dummy.get_method("get").unwrap().dynamic_inputs.is_empty());
+
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&dummy, "sum"),
+            jyafn_ext::serde_json::json!(0.0)
+        );
+        let input = [1.0f64, 2.0, 3.0].map(f64::to_bits);
+        assert_eq!(
+            jyafn_ext::test::call_method(&dummy, "sum", &input),
+            Ok(jyafn_ext::serde_json::json!(3.0))
+        );
+    }
+
+    #[test]
+    fn test_top_k() {
+        let dummy = Dummy { number: 2.0 };
+        let declared = dummy.get_method("top_k").unwrap();
+        assert!(declared.dynamic_output);
+        assert_eq!(
+            declared.output_layout,
+            jyafn_ext::dynamic_output_layout(jyafn_ext::Layout::Scalar, 4)
+        );
+        assert!(!dummy.get_method("get").unwrap().dynamic_output);
+
+        let input = [3.0f64, 1.0, 4.0, 2.0, 2.0].map(f64::to_bits);
+        assert_eq!(
+            jyafn_ext::test::call_method(&dummy, "top_k", &input),
+            Ok(jyafn_ext::serde_json::json!({
+                "len": 2,
+                "values": [4.0, 3.0, 0.0, 0.0],
+            }))
+        );
+        let input = [3.0f64, 1.0, 4.0, 2.0, 10.0].map(f64::to_bits);
+        assert_eq!(
+            jyafn_ext::test::call_method(&dummy, "top_k", &input),
+            Ok(jyafn_ext::serde_json::json!({
+                "len": 4,
+                "values": [4.0, 3.0, 2.0, 1.0],
+            }))
+        );
+    }
+
+    #[test]
+    fn test_err_and_panic() {
+        let dummy = Dummy { number: 2.0 };
+        let input = [1.0f64.to_bits()];
+        assert_eq!(
+            jyafn_ext::test::call_method(&dummy, "err", &input),
+            Err("oops! wrooong!!".to_string())
+        );
+        assert!(jyafn_ext::test::call_method(&dummy, "panic", &input)
+            .unwrap_err()
+            .contains("panicked"));
+        assert!(jyafn_ext::test::call_method(&dummy, "nope", &input).is_err());
+    }
 }
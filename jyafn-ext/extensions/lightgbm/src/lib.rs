@@ -84,3 +84,42 @@ impl Lightgbm {
 
     jyafn_ext::method!(num_classes);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jyafn_ext::serde_json::json;
+    use lightgbm3::Dataset;
+
+    fn train() -> Lightgbm {
+        let xs = [0.1, 0.9, 0.2, 0.8, 0.3, 0.7, 0.7, 0.3, 0.8, 0.2, 0.9, 0.1];
+        let labels = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        let dataset = Dataset::from_slice(&xs, &labels, 2, true).unwrap();
+        let booster = Booster::train(
+            dataset,
+            &json!({ "num_iterations": 3, "objective": "binary", "min_data_in_leaf": 1 }),
+        )
+        .unwrap();
+
+        Lightgbm { booster }
+    }
+
+    #[test]
+    fn test_methods() {
+        let model = train();
+
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&model, "num_features"),
+            json!(2.0)
+        );
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&model, "num_classes"),
+            json!(1.0)
+        );
+
+        let prediction = jyafn_ext::test::exercise_resource(&model, "predict");
+        let prediction = prediction.as_array().expect("prediction is a list");
+        assert_eq!(prediction.len(), 1);
+        assert!((0.0..=1.0).contains(&prediction[0].as_f64().unwrap()));
+    }
+}
@@ -0,0 +1,114 @@
+"""
+Generates the ONNX models used in the tests of this extension. The protobuf messages are
+encoded by hand, so that no dependencies are needed.
+
+Usage: python3 make_fixtures.py
+"""
+
+import os
+import struct
+
+
+def varint(value: int) -> bytes:
+    out = bytearray()
+    while True:
+        byte = value & 0x7F
+        value >>= 7
+        if value:
+            out.append(byte | 0x80)
+        else:
+            out.append(byte)
+            return bytes(out)
+
+
+def int_field(number: int, value: int) -> bytes:
+    return varint(number << 3) + varint(value)
+
+
+def bytes_field(number: int, value: bytes | str) -> bytes:
+    if isinstance(value, str):
+        value = value.encode()
+    return varint(number << 3 | 2) + varint(len(value)) + value
+
+
+FLOAT = 1
+
+
+def tensor_info(name: str, dims: list[int | str]) -> bytes:
+    """A `ValueInfoProto` for a float tensor. String dimensions are symbolic."""
+    shape = b"".join(
+        bytes_field(1, bytes_field(2, dim) if isinstance(dim, str) else int_field(1, dim))
+        for dim in dims
+    )
+    tensor_type = int_field(1, FLOAT) + bytes_field(2, shape)
+    return bytes_field(1, name) + bytes_field(2, bytes_field(1, tensor_type))
+
+
+def float_tensor(name: str, dims: list[int], values: list[float]) -> bytes:
+    """A `TensorProto` with float data."""
+    return (
+        b"".join(int_field(1, dim) for dim in dims)
+        + int_field(2, FLOAT)
+        + bytes_field(8, name)
+        + bytes_field(9, struct.pack(f"<{len(values)}f", *values))
+    )
+
+
+def node(op_type: str, inputs: list[str], outputs: list[str]) -> bytes:
+    return (
+        b"".join(bytes_field(1, i) for i in inputs)
+        + b"".join(bytes_field(2, o) for o in outputs)
+        + bytes_field(4, op_type)
+    )
+
+
+def model(
+    nodes: list[bytes],
+    inputs: list[bytes],
+    outputs: list[bytes],
+    initializers: list[bytes] = [],
+) -> bytes:
+    graph = (
+        b"".join(bytes_field(1, n) for n in nodes)
+        + bytes_field(2, "fixture")
+        + b"".join(bytes_field(5, i) for i in initializers)
+        + b"".join(bytes_field(11, i) for i in inputs)
+        + b"".join(bytes_field(12, o) for o in outputs)
+    )
+    opset = bytes_field(1, "") + int_field(2, 13)
+    return (
+        int_field(1, 8)  # ir_version
+        + bytes_field(2, "jyafn")  # producer_name
+        + bytes_field(7, graph)
+        + bytes_field(8, opset)
+    )
+
+
+FIXTURES = {
+    # y = 2 * x + 1, with a dynamic batch dimension.
+    "affine.onnx": model(
+        nodes=[
+            node("Mul", ["x", "two"], ["doubled"]),
+            node("Add", ["doubled", "one"], ["y"]),
+        ],
+        inputs=[tensor_info("x", ["batch", 3])],
+        outputs=[tensor_info("y", ["batch", 3])],
+        initializers=[
+            float_tensor("two", [1], [2.0]),
+            float_tensor("one", [1], [1.0]),
+        ],
+    ),
+    # z = a + b, with two named inputs.
+    "add.onnx": model(
+        nodes=[node("Add", ["a", "b"], ["z"])],
+        inputs=[tensor_info("a", [1, 2]), tensor_info("b", [1, 2])],
+        outputs=[tensor_info("z", [1, 2])],
+    ),
+}
+
+
+if __name__ == "__main__":
+    here = os.path.dirname(os.path.abspath(__file__))
+    for name, data in FIXTURES.items():
+        with open(os.path.join(here, name), "wb") as f:
+            f.write(data)
@@ -0,0 +1,275 @@
+//! This crate implements the `onnx` extension for jyafn. It exposes a minimal API of
+//! ONNX Runtime for evaluating models in runtime.
+//!
+//! The only resource declared by this extension is the `Onnx` resource, with three methods:
+//! ```
+//! // Runs the model, given the values of its input tensor, returning the values of its
+//! // first output tensor.
+//! run(inputs: [scalar; n_inputs]) -> [scalar; n_outputs];
+//! // The number of input values of this model.
+//! num_inputs() -> scalar;
+//! // The number of output values of this model.
+//! num_outputs() -> scalar;
+//! ```
+//!
+//! If the model has more than one input tensor, `run` takes a struct with one field per
+//! input tensor instead, named after the tensor, e.g.
+//! `run(a: [scalar; n_a], b: [scalar; n_b]) -> [scalar; n_outputs]`.
+//!
+//! Tensors are flattened in row-major order. Symbolic dimensions, such as the batch
+//! size, are taken to be `1`. Only `float` and `double` tensors are supported.
+
+use jyafn_ext::{Input, Layout, Method, OutputBuilder, Resource, Struct};
+use ort::{Session, Tensor, TensorElementType, ValueType};
+
+jyafn_ext::extension! {
+    Onnx
+}
+
+/// The shape and type of an input or output tensor of the model.
+#[derive(Debug)]
+struct TensorSpec {
+    name: String,
+    shape: Vec<i64>,
+    ty: TensorElementType,
+}
+
+impl TensorSpec {
+    fn new(name: &str, value_type: &ValueType) -> Result<TensorSpec, String> {
+        let ValueType::Tensor { ty, dimensions } = value_type else {
+            return Err(format!("{name:?} is not a tensor"));
+        };
+        if !matches!(ty, TensorElementType::Float32 | TensorElementType::Float64) {
+            return Err(format!(
+                "tensor {name:?} has unsupported element type {ty:?}"
+            ));
+        }
+
+        Ok(TensorSpec {
+            name: name.to_string(),
+            // Symbolic dimensions (e.g., the batch size) are represented as `-1`.
+            shape: dimensions.iter().map(|&dim| dim.max(1)).collect(),
+            ty: *ty,
+        })
+    }
+
+    /// The number of values in this tensor.
+    fn size(&self) -> usize {
+        self.shape.iter().product::<i64>() as usize
+    }
+
+    fn layout(&self) -> Layout {
+        Layout::List(Box::new(Layout::Scalar), self.size())
+    }
+}
+
+struct Onnx {
+    bytes: Vec<u8>,
+    session: Session,
+    inputs: Vec<TensorSpec>,
+    output: TensorSpec,
+}
+
+impl Resource for Onnx {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, impl ToString> {
+        let session = Session::builder()
+            .and_then(|builder| builder.commit_from_memory(bytes))
+            .map_err(|err| format!("failed to load ONNX model: {err}"))?;
+        let inputs = session
+            .inputs
+            .iter()
+            .map(|input| TensorSpec::new(&input.name, &input.input_type))
+            .collect::<Result<Vec<_>, _>>()?;
+        let output = session
+            .outputs
+            .first()
+            .map(|output| TensorSpec::new(&output.name, &output.output_type))
+            .ok_or_else(|| "ONNX model has no outputs".to_string())??;
+
+        if inputs.is_empty() {
+            return Err("ONNX model has no inputs".to_string());
+        }
+
+        Ok(Onnx {
+            bytes: bytes.to_vec(),
+            session,
+            inputs,
+            output,
+        })
+    }
+
+    fn dump(&self) -> Result<Vec<u8>, impl ToString> {
+        Ok::<_, String>(self.bytes.clone())
+    }
+
+    /// We cannot know the size of the session... ;(
+    fn size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn get_method(&self, method: &str) -> Option<Method> {
+        let n_inputs = self.num_input_values();
+        let n_outputs = self.output.size();
+
+        if method == "run" && self.inputs.len() > 1 {
+            // One field per named input. This cannot be expressed with `declare_methods!`.
+            return Some(Method::new(
+                jyafn_ext::get_method_ptr!(run),
+                Struct(
+                    self.inputs
+                        .iter()
+                        .map(|input| (input.name.clone(), input.layout()))
+                        .collect(),
+                ),
+                self.output.layout(),
+            ));
+        }
+
+        jyafn_ext::declare_methods! {
+            match method:
+                run(inputs: [scalar; n_inputs]) -> [scalar; n_outputs];
+                num_inputs() -> scalar;
+                num_outputs() -> scalar;
+        }
+    }
+}
+
+impl Onnx {
+    /// The total number of values in the input tensors of the model.
+    fn num_input_values(&self) -> usize {
+        self.inputs.iter().map(TensorSpec::size).sum()
+    }
+
+    fn run(&self, input: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
+        let values = input.as_f64_slice();
+        let mut offset = 0;
+        let mut session_inputs = Vec::with_capacity(self.inputs.len());
+
+        for spec in &self.inputs {
+            let data = &values[offset..offset + spec.size()];
+            offset += spec.size();
+            let tensor = match spec.ty {
+                TensorElementType::Float32 => Tensor::from_array((
+                    spec.shape.clone(),
+                    data.iter().map(|&x| x as f32).collect::<Vec<_>>(),
+                ))
+                .map(Tensor::into_dyn),
+                _ => Tensor::from_array((spec.shape.clone(), data.to_vec())).map(Tensor::into_dyn),
+            }
+            .map_err(|err| format!("failed to create tensor {:?}: {err}", spec.name))?;
+            session_inputs.push((spec.name.as_str(), tensor));
+        }
+
+        let outputs = self
+            .session
+            .run(session_inputs)
+            .map_err(|err| err.to_string())?;
+        let output = &outputs[self.output.name.as_str()];
+        match self.output.ty {
+            TensorElementType::Float32 => {
+                let (_, data) = output
+                    .try_extract_raw_tensor::<f32>()
+                    .map_err(|err| err.to_string())?;
+                for &x in data.iter().take(self.output.size()) {
+                    output_builder.push_f64(x as f64);
+                }
+            }
+            _ => {
+                let (_, data) = output
+                    .try_extract_raw_tensor::<f64>()
+                    .map_err(|err| err.to_string())?;
+                output_builder.copy_from_f64(&data[..data.len().min(self.output.size())]);
+            }
+        }
+
+        Ok(())
+    }
+
+    jyafn_ext::method!(run);
+
+    fn num_inputs(&self, _: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
+        output_builder.push_f64(self.num_input_values() as f64);
+        Ok(())
+    }
+
+    jyafn_ext::method!(num_inputs);
+
+    fn num_outputs(&self, _: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
+        output_builder.push_f64(self.output.size() as f64);
+        Ok(())
+    }
+
+    jyafn_ext::method!(num_outputs);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jyafn_ext::serde_json::json;
+
+    /// Computes `2 * x + 1` for a tensor `x` of shape `[batch, 3]`.
+    const AFFINE: &[u8] = include_bytes!("../fixtures/affine.onnx");
+    /// Computes `a + b` for two tensors of shape `[1, 2]`.
+    const ADD: &[u8] = include_bytes!("../fixtures/add.onnx");
+
+    fn load(bytes: &[u8]) -> Onnx {
+        Onnx::from_bytes(bytes).unwrap_or_else(|err| panic!("{}", err.to_string()))
+    }
+
+    #[test]
+    fn test_single_input() {
+        let model = load(AFFINE);
+
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&model, "num_inputs"),
+            json!(3.0)
+        );
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&model, "num_outputs"),
+            json!(3.0)
+        );
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&model, "run"),
+            json!([3.0, 3.0, 3.0])
+        );
+        let input = [1.0f64, -1.0, 0.5].map(f64::to_bits);
+        assert_eq!(
+            jyafn_ext::test::call_method(&model, "run", &input),
+            Ok(json!([3.0, -1.0, 2.0]))
+        );
+
+        let Ok(dumped) = model.dump() else {
+            panic!("failed to dump model");
+        };
+        assert_eq!(dumped, AFFINE);
+    }
+
+    #[test]
+    fn test_multiple_inputs() {
+        let model = load(ADD);
+
+        let method = model.get_method("run").unwrap();
+        assert_eq!(
+            method.input_layout,
+            Struct(vec![
+                ("a".to_string(), jyafn_ext::layout!([scalar; 2])),
+                ("b".to_string(), jyafn_ext::layout!([scalar; 2])),
+            ])
+        );
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&model, "num_inputs"),
+            json!(4.0)
+        );
+
+        let input = [1.0f64, 2.0, 10.0, 20.0].map(f64::to_bits);
+        assert_eq!(
+            jyafn_ext::test::call_method(&model, "run", &input),
+            Ok(json!([11.0, 22.0]))
+        );
+    }
+
+    #[test]
+    fn test_bad_model() {
+        assert!(Onnx::from_bytes(b"not a model").is_err());
+    }
+}
@@ -18,6 +18,25 @@ fn size_of_model(model: &ppca::PPCAModel) -> usize {
     model.mean().len() + model.transform().len() + 1
 }
 
+/// The full covariance matrix of the extrapolated values of a sample. This is the
+/// smoothed covariance with the rows and columns of the observed values zeroed out, since
+/// these are known exactly. We don't use `ppca::InferredMasked::extrapolated_covariance`
+/// because it panics on partially observed samples.
+fn extrapolated_covariance(
+    inferred: &ppca::InferredMasked,
+    model: &ppca::PPCAModel,
+    sample: &ppca::MaskedSample,
+) -> DMatrix<f64> {
+    let mut covariance = inferred.smoothed_covariance(model);
+
+    for idx in (0..model.output_size()).filter(|&idx| sample.is_set(idx)) {
+        covariance.row_mut(idx).fill(0.0);
+        covariance.column_mut(idx).fill(0.0);
+    }
+
+    covariance
+}
+
 struct PPCAModel {
     model: ppca::PPCAModel,
 }
@@ -32,6 +51,10 @@ impl Resource for PPCAModel {
         bincode::serialize(&self.model)
     }
 
+    fn dump_to(&self, writer: &mut dyn std::io::Write) -> Result<(), impl ToString> {
+        bincode::serialize_into(writer, &self.model)
+    }
+
     /// We cannot know the size of this model... ;(
     fn size(&self) -> usize {
         size_of_model(&self.model)
@@ -57,6 +80,11 @@ impl Resource for PPCAModel {
                     state: [scalar; self.model.state_size()],
                     covariance: [[scalar; self.model.state_size()]; self.model.state_size()]
                 ) -> [scalar; self.model.output_size()];
+                extrapolated_covariance(
+                    sample: [scalar; self.model.output_size()],
+                    state: [scalar; self.model.state_size()],
+                    covariance: [[scalar; self.model.state_size()]; self.model.state_size()]
+                ) -> [[scalar; self.model.output_size()]; self.model.output_size()];
                 smooth(sample: [scalar; self.model.output_size()])
                     -> [scalar; self.model.output_size()];
                 smoothed(
@@ -67,6 +95,10 @@ impl Resource for PPCAModel {
                     state: [scalar; self.model.state_size()],
                     covariance: [[scalar; self.model.state_size()]; self.model.state_size()]
                 ) -> [scalar; self.model.output_size()];
+                smoothed_covariance(
+                    state: [scalar; self.model.state_size()],
+                    covariance: [[scalar; self.model.state_size()]; self.model.state_size()]
+                ) -> [[scalar; self.model.output_size()]; self.model.output_size()];
         }
     }
 }
@@ -137,6 +169,24 @@ impl PPCAModel {
 
     jyafn_ext::method!(extrapolated_covariance_diagonal);
 
+    fn extrapolated_covariance(
+        &self,
+        input: Input,
+        mut output_builder: OutputBuilder,
+    ) -> Result<(), String> {
+        let mut reader = InputReader::new(input);
+        let sample = self.read_sample(&mut reader);
+        let inferred = self.read_inferred(&mut reader);
+        output_builder.copy_from_f64(
+            extrapolated_covariance(&inferred, &self.model, &sample)
+                .data
+                .as_vec(),
+        );
+        Ok(())
+    }
+
+    jyafn_ext::method!(extrapolated_covariance);
+
     fn smooth(&self, input: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
         let mut reader = InputReader::new(input);
         let sample = self.read_sample(&mut reader);
@@ -174,6 +224,19 @@ impl PPCAModel {
 
     jyafn_ext::method!(smoothed_covariance_diagonal);
 
+    fn smoothed_covariance(
+        &self,
+        input: Input,
+        mut output_builder: OutputBuilder,
+    ) -> Result<(), String> {
+        let mut reader = InputReader::new(input);
+        let inferred = self.read_inferred(&mut reader);
+        output_builder.copy_from_f64(inferred.smoothed_covariance(&self.model).data.as_vec());
+        Ok(())
+    }
+
+    jyafn_ext::method!(smoothed_covariance);
+
     fn infer(&self, input: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
         let sample = ppca::MaskedSample::mask_non_finite(input.as_f64_slice().to_owned().into());
         let inferred = self.model.infer_one(&sample);
@@ -199,6 +262,10 @@ impl Resource for PPCAMix {
         bincode::serialize(&self.model)
     }
 
+    fn dump_to(&self, writer: &mut dyn std::io::Write) -> Result<(), impl ToString> {
+        bincode::serialize_into(writer, &self.model)
+    }
+
     /// We cannot know the size of this model... ;(
     fn size(&self) -> usize {
         self.model.models().len()
@@ -234,6 +301,12 @@ impl Resource for PPCAMix {
                         state: [[scalar; state_size]; self.model.models().len()],
                         covariance: [[[scalar; state_size]; state_size]; self.model.models().len()]
                     ) -> [scalar; self.model.output_size()];
+                    extrapolated_covariance(
+                        sample: [scalar; self.model.output_size()],
+                        log_posterior: [scalar; self.model.models().len()],
+                        state: [[scalar; state_size]; self.model.models().len()],
+                        covariance: [[[scalar; state_size]; state_size]; self.model.models().len()]
+                    ) -> [[scalar; self.model.output_size()]; self.model.output_size()];
                     smooth(sample: [scalar; self.model.output_size()])
                         -> [scalar; self.model.output_size()];
                     smoothed(
@@ -248,6 +321,12 @@ impl Resource for PPCAMix {
                         state: [[scalar; state_size]; self.model.models().len()],
                         covariance: [[[scalar; state_size]; state_size]; self.model.models().len()]
                     ) -> [scalar; self.model.output_size()];
+                    smoothed_covariance(
+                        sample: [scalar; self.model.output_size()],
+                        log_posterior: [scalar; self.model.models().len()],
+                        state: [[scalar; state_size]; self.model.models().len()],
+                        covariance: [[[scalar; state_size]; state_size]; self.model.models().len()]
+                    ) -> [[scalar; self.model.output_size()]; self.model.output_size()];
             }
         } else {
             jyafn_ext::declare_methods! {
@@ -356,6 +435,37 @@ impl PPCAMix {
 
     jyafn_ext::method!(extrapolated_covariance_diagonal);
 
+    /// The full covariance matrix of the extrapolated values. This is computed directly
+    /// from the sub-models (see [`extrapolated_covariance`]) instead of using
+    /// `ppca::InferredMaskedMix::extrapolated_covariance`, which uses the _smoothed_
+    /// covariances of the sub-models.
+    fn extrapolated_covariance(
+        &self,
+        input: Input,
+        mut output_builder: OutputBuilder,
+    ) -> Result<(), String> {
+        let mut reader = InputReader::new(input);
+        let sample = self.read_sample(&mut reader);
+        let inferred = self.read_inferred(&mut reader);
+        let mean = inferred.extrapolated(&self.model, &sample);
+        let covariance = inferred
+            .sub_states()
+            .iter()
+            .zip(&inferred.posterior())
+            .zip(self.model.models())
+            .map(|((inferred, &weight), ppca)| {
+                let deviation = inferred.extrapolated(ppca, &sample) - &mean;
+                weight
+                    * (extrapolated_covariance(inferred, ppca, &sample)
+                        + &deviation * deviation.transpose())
+            })
+            .sum::<DMatrix<f64>>();
+        output_builder.copy_from_f64(covariance.data.as_vec());
+        Ok(())
+    }
+
+    jyafn_ext::method!(extrapolated_covariance);
+
     fn smooth(&self, input: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
         let mut reader = InputReader::new(input);
         let sample = self.read_sample(&mut reader);
@@ -368,9 +478,9 @@ impl PPCAMix {
 
     fn smoothed(&self, input: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
         let mut reader = InputReader::new(input);
-        let sample = self.read_sample(&mut reader);
+        let _sample = self.read_sample(&mut reader);
         let inferred = self.read_inferred(&mut reader);
-        output_builder.copy_from_f64(inferred.extrapolated(&self.model, &sample).data.as_vec());
+        output_builder.copy_from_f64(inferred.smoothed(&self.model).data.as_vec());
         Ok(())
     }
 
@@ -382,11 +492,11 @@ impl PPCAMix {
         mut output_builder: OutputBuilder,
     ) -> Result<(), String> {
         let mut reader = InputReader::new(input);
-        let sample = self.read_sample(&mut reader);
+        let _sample = self.read_sample(&mut reader);
         let inferred = self.read_inferred(&mut reader);
         output_builder.copy_from_f64(
             inferred
-                .extrapolated_covariance_diagonal(&self.model, &sample)
+                .smoothed_covariance_diagonal(&self.model)
                 .data
                 .as_vec(),
         );
@@ -395,6 +505,20 @@ impl PPCAMix {
 
     jyafn_ext::method!(smoothed_covariance_diagonal);
 
+    fn smoothed_covariance(
+        &self,
+        input: Input,
+        mut output_builder: OutputBuilder,
+    ) -> Result<(), String> {
+        let mut reader = InputReader::new(input);
+        let _sample = self.read_sample(&mut reader);
+        let inferred = self.read_inferred(&mut reader);
+        output_builder.copy_from_f64(inferred.smoothed_covariance(&self.model).data.as_vec());
+        Ok(())
+    }
+
+    jyafn_ext::method!(smoothed_covariance);
+
     fn infer(&self, input: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
         let sample = ppca::MaskedSample::mask_non_finite(input.as_f64_slice().to_owned().into());
         let inferred = self.model.infer_one(&sample);
@@ -414,3 +538,100 @@ impl PPCAMix {
 
     jyafn_ext::method!(infer);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Calls a method directly on `input`, returning `output_size` output values.
+    fn call<R>(
+        method: fn(&R, Input, OutputBuilder) -> Result<(), String>,
+        resource: &R,
+        input: &[f64],
+        output_size: usize,
+    ) -> Vec<f64> {
+        let mut output = vec![0.0; output_size];
+        unsafe {
+            method(
+                resource,
+                Input::new(input.as_ptr() as *const u8, input.len()),
+                OutputBuilder::new(output.as_mut_ptr() as *mut u8, output_size),
+            )
+            .unwrap();
+        }
+        output
+    }
+
+    fn assert_diagonal_matches(full: &[f64], diagonal: &[f64]) {
+        let size = diagonal.len();
+        assert_eq!(full.len(), size * size);
+        for (i, &expected) in diagonal.iter().enumerate() {
+            let got = full[i * size + i];
+            assert!((got - expected).abs() < 1e-10, "{got} != {expected}");
+        }
+    }
+
+    fn create_model(shift: f64) -> ppca::PPCAModel {
+        ppca::PPCAModel::new(
+            0.5,
+            DMatrix::from_row_slice(3, 2, &[1.0, shift, 0.5, -1.0, -0.2, 2.0]),
+            DVector::from_column_slice(&[shift, 1.0, -1.0]),
+        )
+    }
+
+    #[test]
+    fn test_model_full_covariance_matches_diagonal() {
+        let model = PPCAModel {
+            model: create_model(0.0),
+        };
+        let sample = [1.0, f64::NAN, f64::NAN];
+        let inferred = call(PPCAModel::infer, &model, &sample, 2 + 4);
+        let input = sample.iter().chain(&inferred).copied().collect::<Vec<_>>();
+
+        let full = call(PPCAModel::extrapolated_covariance, &model, &input, 9);
+        assert_diagonal_matches(
+            &full,
+            &call(
+                PPCAModel::extrapolated_covariance_diagonal,
+                &model,
+                &input,
+                3,
+            ),
+        );
+        // The observed value has no uncertainty:
+        assert!(full[..3].iter().all(|&c| c == 0.0));
+
+        let full = call(PPCAModel::smoothed_covariance, &model, &inferred, 9);
+        assert_diagonal_matches(
+            &full,
+            &call(
+                PPCAModel::smoothed_covariance_diagonal,
+                &model,
+                &inferred,
+                3,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_mix_full_covariance_matches_diagonal() {
+        let mix = PPCAMix {
+            model: ppca::PPCAMix::new(
+                vec![create_model(0.0), create_model(1.0)],
+                DVector::from_column_slice(&[0.3f64.ln(), 0.7f64.ln()]),
+            ),
+        };
+        let sample = [1.0, f64::NAN, f64::NAN];
+        let inferred = call(PPCAMix::infer, &mix, &sample, 2 + 2 * 2 + 2 * 4);
+        let input = sample.iter().chain(&inferred).copied().collect::<Vec<_>>();
+
+        assert_diagonal_matches(
+            &call(PPCAMix::extrapolated_covariance, &mix, &input, 9),
+            &call(PPCAMix::extrapolated_covariance_diagonal, &mix, &input, 3),
+        );
+        assert_diagonal_matches(
+            &call(PPCAMix::smoothed_covariance, &mix, &input, 9),
+            &call(PPCAMix::smoothed_covariance_diagonal, &mix, &input, 3),
+        );
+    }
+}
@@ -0,0 +1,238 @@
+"""
+Generates the TFLite models used in the tests of this extension. The flatbuffers are
+encoded by hand, so that no dependencies are needed. Only the parts of the TFLite schema
+used here are supported.
+
+Usage: python3 make_fixtures.py
+"""
+
+import os
+import struct
+
+# Sizes and `struct` formats of the scalar types of the schema.
+SCALARS = {
+    "ubyte": (1, "<B"),
+    "byte": (1, "<b"),
+    "uint": (4, "<I"),
+    "int": (4, "<i"),
+    "float": (4, "<f"),
+    "long": (8, "<q"),
+}
+
+FLOAT32 = 0
+UINT8 = 3
+
+DEQUANTIZE = 6
+FULLY_CONNECTED = 9
+
+FULLY_CONNECTED_OPTIONS = 8
+
+
+class Table:
+    """A table with `(slot, type, value)` fields. Tables, vectors and strings are
+    referenced with the type `"offset"`."""
+
+    def __init__(self, *fields):
+        self.fields = fields
+
+
+class Vector:
+    """A vector of scalars of the given type or of `"offset"`s."""
+
+    def __init__(self, ty, items, align=1):
+        self.ty = ty
+        self.items = items
+        self.align = align
+
+
+class String:
+    def __init__(self, value):
+        self.value = value.encode()
+
+
+class Builder:
+    """Lays out objects front to back, so that every offset points forward."""
+
+    def __init__(self):
+        self.buf = bytearray()
+        self.pending = []
+
+    def align(self, alignment, extra=0):
+        while (len(self.buf) + extra) % alignment:
+            self.buf.append(0)
+
+    def write(self, ty, value):
+        if ty == "offset":
+            self.pending.append((len(self.buf), value))
+            self.buf += b"\0\0\0\0"
+        else:
+            self.buf += struct.pack(SCALARS[ty][1], value)
+
+    def place(self, obj):
+        if isinstance(obj, Table):
+            return self.place_table(obj)
+        elif isinstance(obj, Vector):
+            size = 4 if obj.ty == "offset" else SCALARS[obj.ty][0]
+            self.align(max(4, size, obj.align), extra=4)
+            pos = len(self.buf)
+            self.write("uint", len(obj.items))
+            for item in obj.items:
+                self.write(obj.ty, item)
+            return pos
+        else:
+            self.align(4)
+            pos = len(self.buf)
+            self.write("uint", len(obj.value))
+            self.buf += obj.value + b"\0"
+            return pos
+
+    def place_table(self, table):
+        # Biggest fields first, so that all of them are aligned.
+        fields = sorted(
+            table.fields,
+            key=lambda field: -(4 if field[1] == "offset" else SCALARS[field[1]][0]),
+        )
+        offsets = {}
+        size = 4
+        for slot, ty, _ in fields:
+            field_size = 4 if ty == "offset" else SCALARS[ty][0]
+            size += -size % field_size
+            offsets[slot] = size
+            size += field_size
+        n_slots = max(offsets, default=-1) + 1
+
+        self.align(2)
+        vtable = len(self.buf)
+        self.buf += struct.pack("<HH", 4 + 2 * n_slots, size)
+        for slot in range(n_slots):
+            self.buf += struct.pack("<H", offsets.get(slot, 0))
+
+        self.align(4)
+        pos = len(self.buf)
+        self.write("int", pos - vtable)
+        for slot, ty, value in fields:
+            while len(self.buf) - pos < offsets[slot]:
+                self.buf.append(0)
+            self.write(ty, value)
+        return pos
+
+    def finish(self, root):
+        self.buf += b"\0\0\0\0TFL3"
+        self.pending.append((0, root))
+        while self.pending:
+            fixup, obj = self.pending.pop(0)
+            pos = self.place(obj)
+            struct.pack_into("<I", self.buf, fixup, pos - fixup)
+        return bytes(self.buf)
+
+
+def ints(items):
+    return Vector("int", items)
+
+
+def tensor(name, shape, ty, buffer=0, quantization=None):
+    fields = [
+        (0, "offset", ints(shape)),
+        (1, "byte", ty),
+        (2, "uint", buffer),
+        (3, "offset", String(name)),
+    ]
+    if quantization is not None:
+        scale, zero_point = quantization
+        fields.append(
+            (
+                4,
+                "offset",
+                Table(
+                    (2, "offset", Vector("float", [scale])),
+                    (3, "offset", Vector("long", [zero_point])),
+                ),
+            )
+        )
+    return Table(*fields)
+
+
+def buffer(values=None):
+    if values is None:
+        return Table()
+    data = struct.pack(f"<{len(values)}f", *values)
+    return Table((0, "offset", Vector("ubyte", data, align=16)))
+
+
+def operator(opcode_index, inputs, outputs, options=None):
+    fields = [
+        (0, "uint", opcode_index),
+        (1, "offset", ints(inputs)),
+        (2, "offset", ints(outputs)),
+    ]
+    if options is not None:
+        options_type, options_table = options
+        fields += [(3, "ubyte", options_type), (4, "offset", options_table)]
+    return Table(*fields)
+
+
+def model(builtin_code, tensors, inputs, outputs, operators, buffers):
+    operator_code = Table(
+        (0, "byte", builtin_code),
+        (2, "int", 1),
+        (3, "int", builtin_code),
+    )
+    subgraph = Table(
+        (0, "offset", Vector("offset", tensors)),
+        (1, "offset", ints(inputs)),
+        (2, "offset", ints(outputs)),
+        (3, "offset", Vector("offset", operators)),
+        (4, "offset", String("main")),
+    )
+    return Builder().finish(
+        Table(
+            (0, "uint", 3),
+            (1, "offset", Vector("offset", [operator_code])),
+            (2, "offset", Vector("offset", [subgraph])),
+            (3, "offset", String("jyafn")),
+            (4, "offset", Vector("offset", buffers)),
+        )
+    )
+
+
+FIXTURES = {
+    # y = W x + b, with W = [[1, 2, 3], [-1, 0, 1]] and b = [0.5, -0.5].
+    "affine.tflite": model(
+        FULLY_CONNECTED,
+        tensors=[
+            tensor("x", [1, 3], FLOAT32),
+            tensor("weights", [2, 3], FLOAT32, buffer=1),
+            tensor("bias", [2], FLOAT32, buffer=2),
+            tensor("y", [1, 2], FLOAT32),
+        ],
+        inputs=[0],
+        outputs=[3],
+        operators=[
+            operator(0, [0, 1, 2], [3], (FULLY_CONNECTED_OPTIONS, Table())),
+        ],
+        buffers=[
+            buffer(),
+            buffer([1.0, 2.0, 3.0, -1.0, 0.0, 1.0]),
+            buffer([0.5, -0.5]),
+        ],
+    ),
+    # y = 0.5 * (x - 128), where x is quantized as `uint8`.
+    "dequantize.tflite": model(
+        DEQUANTIZE,
+        tensors=[
+            tensor("x", [1, 4], UINT8, quantization=(0.5, 128)),
+            tensor("y", [1, 4], FLOAT32),
+        ],
+        inputs=[0],
+        outputs=[1],
+        operators=[operator(0, [0], [1])],
+        buffers=[buffer()],
+    ),
+}
+
+
+if __name__ == "__main__":
+    here = os.path.dirname(os.path.abspath(__file__))
+    for name, data in FIXTURES.items():
+        with open(os.path.join(here, name), "wb") as f:
+            f.write(data)
@@ -0,0 +1,361 @@
+//! This crate implements the `tensorflow-lite` extension for jyafn. It exposes a minimal
+//! API of the TensorFlow Lite interpreter for evaluating models in runtime.
+//!
+//! The only resource declared by this extension is the `TfLite` resource, with three
+//! methods:
+//! ```
+//! // Runs the model, given the values of its input tensor, returning the values of its
+//! // output tensor.
+//! invoke(x: [scalar; n_inputs]) -> [scalar; n_outputs];
+//! // The number of input values of this model.
+//! num_inputs() -> scalar;
+//! // The number of output values of this model.
+//! num_outputs() -> scalar;
+//! ```
+//!
+//! Only models with a single input tensor and a single output tensor are supported.
+//! Tensors are flattened in row-major order. Tensors may be `float32`, `uint8` or `int8`.
+//! Quantized (integer) tensors are transparently quantized on input and dequantized on
+//! output, using the per-tensor quantization parameters declared in the model.
+
+use jyafn_ext::{Input, Method, OutputBuilder, Resource};
+use std::sync::Mutex;
+use tflite::context::ElementKind;
+use tflite::model::stl::vector::VectorSlice;
+use tflite::model::Model;
+use tflite::ops::builtin::BuiltinOpResolver;
+use tflite::{FlatBufferModel, Interpreter, InterpreterBuilder};
+
+jyafn_ext::extension! {
+    TfLite
+}
+
+/// The quantization parameters of a tensor: a real value `x` is stored as
+/// `round(x / scale) + zero_point`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Quantization {
+    scale: f32,
+    zero_point: i64,
+}
+
+impl Quantization {
+    /// Reads the quantization parameters of a tensor of the main subgraph of a model.
+    /// The interpreter API does not expose these, so they are read from the model itself.
+    /// Returns `None` if the tensor is not quantized. Only per-tensor quantization is
+    /// supported: for per-axis quantization, the first parameters are used.
+    fn of(model: &Model, tensor: usize) -> Option<Quantization> {
+        if model.subgraphs.size() == 0 || tensor >= model.subgraphs[0].tensors.size() {
+            return None;
+        }
+
+        let quantization = &model.subgraphs[0].tensors[tensor].quantization;
+        if !quantization.is_valid() {
+            return None;
+        }
+
+        Some(Quantization {
+            scale: *quantization.scale.as_slice().first()?,
+            zero_point: quantization
+                .zero_point
+                .as_slice()
+                .first()
+                .copied()
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// How the values of a tensor are represented in memory.
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    Float32,
+    UInt8(Quantization),
+    Int8(Quantization),
+}
+
+impl Encoding {
+    /// Converts a real value to the integer that represents it in a quantized tensor.
+    fn quantize(q: Quantization, x: f64, min: f64, max: f64) -> f64 {
+        ((x / q.scale as f64).round() + q.zero_point as f64).clamp(min, max)
+    }
+
+    /// Converts the integer in a quantized tensor to the real value it represents.
+    fn dequantize(q: Quantization, x: f64) -> f64 {
+        (x - q.zero_point as f64) * q.scale as f64
+    }
+}
+
+/// The shape and encoding of the input or the output tensor of the model.
+#[derive(Debug)]
+struct TensorSpec {
+    index: i32,
+    size: usize,
+    encoding: Encoding,
+}
+
+impl TensorSpec {
+    fn new(
+        model: &Model,
+        interpreter: &Interpreter<'static, BuiltinOpResolver>,
+        index: i32,
+    ) -> Result<TensorSpec, String> {
+        let info = interpreter
+            .tensor_info(index)
+            .ok_or_else(|| format!("tensor {index} not found in model"))?;
+        let quantization = || {
+            Quantization::of(model, index as usize)
+                .ok_or_else(|| format!("tensor {:?} is not quantized", info.name))
+        };
+        let encoding = match info.element_kind {
+            ElementKind::kTfLiteFloat32 => Encoding::Float32,
+            ElementKind::kTfLiteUInt8 => Encoding::UInt8(quantization()?),
+            ElementKind::kTfLiteInt8 => Encoding::Int8(quantization()?),
+            kind => {
+                return Err(format!(
+                    "tensor {:?} has unsupported element type {kind:?}",
+                    info.name
+                ))
+            }
+        };
+
+        Ok(TensorSpec {
+            index,
+            size: info.dims.iter().product(),
+            encoding,
+        })
+    }
+}
+
+struct TfLite {
+    bytes: Vec<u8>,
+    interpreter: Mutex<Interpreter<'static, BuiltinOpResolver>>,
+    input: TensorSpec,
+    output: TensorSpec,
+}
+
+// The interpreter holds raw pointers, but it is only ever accessed behind the mutex.
+unsafe impl Send for TfLite {}
+unsafe impl Sync for TfLite {}
+
+impl Resource for TfLite {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, impl ToString> {
+        let model = FlatBufferModel::build_from_buffer(bytes.to_vec())
+            .map_err(|err| format!("failed to load TFLite model: {err}"))?;
+        let mut interpreter = InterpreterBuilder::new(model, BuiltinOpResolver::default())
+            .and_then(|builder| builder.build())
+            .map_err(|err| format!("failed to build TFLite interpreter: {err}"))?;
+        interpreter
+            .allocate_tensors()
+            .map_err(|err| format!("failed to allocate tensors: {err}"))?;
+
+        let (&[input], &[output]) = (interpreter.inputs(), interpreter.outputs()) else {
+            return Err(format!(
+                "only models with a single input and a single output are supported, got {} \
+                inputs and {} outputs",
+                interpreter.inputs().len(),
+                interpreter.outputs().len()
+            ));
+        };
+        let schema = Model::from_buffer(bytes)
+            .ok_or_else(|| "failed to read TFLite model schema".to_string())?;
+        let input = TensorSpec::new(&schema, &interpreter, input)?;
+        let output = TensorSpec::new(&schema, &interpreter, output)?;
+
+        Ok(TfLite {
+            bytes: bytes.to_vec(),
+            interpreter: Mutex::new(interpreter),
+            input,
+            output,
+        })
+    }
+
+    fn dump(&self) -> Result<Vec<u8>, impl ToString> {
+        Ok::<_, String>(self.bytes.clone())
+    }
+
+    /// We cannot know the size of the interpreter... ;(
+    fn size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn get_method(&self, method: &str) -> Option<Method> {
+        let n_inputs = self.input.size;
+        let n_outputs = self.output.size;
+
+        jyafn_ext::declare_methods! {
+            match method:
+                invoke(x: [scalar; n_inputs]) -> [scalar; n_outputs];
+                num_inputs() -> scalar;
+                num_outputs() -> scalar;
+        }
+    }
+}
+
+impl TfLite {
+    fn invoke(&self, input: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
+        let values = input.as_f64_slice();
+        let mut interpreter = self
+            .interpreter
+            .lock()
+            .map_err(|_| "interpreter lock poisoned".to_string())?;
+
+        let index = self.input.index;
+        match self.input.encoding {
+            Encoding::Float32 => {
+                let data = interpreter
+                    .tensor_data_mut::<f32>(index)
+                    .map_err(|err| err.to_string())?;
+                for (slot, &x) in data.iter_mut().zip(values) {
+                    *slot = x as f32;
+                }
+            }
+            Encoding::UInt8(q) => {
+                let data = interpreter
+                    .tensor_buffer_mut(index)
+                    .ok_or_else(|| "input tensor has no buffer".to_string())?;
+                for (slot, &x) in data.iter_mut().zip(values) {
+                    *slot = Encoding::quantize(q, x, u8::MIN as f64, u8::MAX as f64) as u8;
+                }
+            }
+            Encoding::Int8(q) => {
+                let data = interpreter
+                    .tensor_buffer_mut(index)
+                    .ok_or_else(|| "input tensor has no buffer".to_string())?;
+                for (slot, &x) in data.iter_mut().zip(values) {
+                    *slot = Encoding::quantize(q, x, i8::MIN as f64, i8::MAX as f64) as i8 as u8;
+                }
+            }
+        }
+
+        interpreter.invoke().map_err(|err| err.to_string())?;
+
+        let index = self.output.index;
+        match self.output.encoding {
+            Encoding::Float32 => {
+                let data = interpreter
+                    .tensor_data::<f32>(index)
+                    .map_err(|err| err.to_string())?;
+                for &x in data.iter().take(self.output.size) {
+                    output_builder.push_f64(x as f64);
+                }
+            }
+            Encoding::UInt8(q) => {
+                let data = interpreter
+                    .tensor_buffer(index)
+                    .ok_or_else(|| "output tensor has no buffer".to_string())?;
+                for &x in data.iter().take(self.output.size) {
+                    output_builder.push_f64(Encoding::dequantize(q, x as f64));
+                }
+            }
+            Encoding::Int8(q) => {
+                let data = interpreter
+                    .tensor_buffer(index)
+                    .ok_or_else(|| "output tensor has no buffer".to_string())?;
+                for &x in data.iter().take(self.output.size) {
+                    output_builder.push_f64(Encoding::dequantize(q, x as i8 as f64));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    jyafn_ext::method!(invoke);
+
+    fn num_inputs(&self, _: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
+        output_builder.push_f64(self.input.size as f64);
+        Ok(())
+    }
+
+    jyafn_ext::method!(num_inputs);
+
+    fn num_outputs(&self, _: Input, mut output_builder: OutputBuilder) -> Result<(), String> {
+        output_builder.push_f64(self.output.size as f64);
+        Ok(())
+    }
+
+    jyafn_ext::method!(num_outputs);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jyafn_ext::serde_json::json;
+
+    /// Computes `W x + b` for a tensor `x` of shape `[1, 3]`, with
+    /// `W = [[1, 2, 3], [-1, 0, 1]]` and `b = [0.5, -0.5]`.
+    const AFFINE: &[u8] = include_bytes!("../fixtures/affine.tflite");
+    /// Dequantizes a `uint8` tensor of shape `[1, 4]` with scale `0.5` and zero point
+    /// `128`.
+    const DEQUANTIZE: &[u8] = include_bytes!("../fixtures/dequantize.tflite");
+
+    fn load(bytes: &[u8]) -> TfLite {
+        TfLite::from_bytes(bytes).unwrap_or_else(|err| panic!("{}", err.to_string()))
+    }
+
+    #[test]
+    fn test_float() {
+        let model = load(AFFINE);
+
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&model, "num_inputs"),
+            json!(3.0)
+        );
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&model, "num_outputs"),
+            json!(2.0)
+        );
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&model, "invoke"),
+            json!([6.5, -0.5])
+        );
+        let input = [1.0f64, -1.0, 0.5].map(f64::to_bits);
+        assert_eq!(
+            jyafn_ext::test::call_method(&model, "invoke", &input),
+            Ok(json!([0.5, -1.0]))
+        );
+
+        let Ok(dumped) = model.dump() else {
+            panic!("failed to dump model");
+        };
+        assert_eq!(dumped, AFFINE);
+    }
+
+    #[test]
+    fn test_quantized() {
+        let model = load(DEQUANTIZE);
+
+        assert_eq!(
+            jyafn_ext::test::exercise_resource(&model, "invoke"),
+            json!([1.0, 1.0, 1.0, 1.0])
+        );
+        // Values are rounded to the scale and clamped to the range of `uint8`.
+        let input = [1.0f64, -2.0, 0.3, 100.0].map(f64::to_bits);
+        assert_eq!(
+            jyafn_ext::test::call_method(&model, "invoke", &input),
+            Ok(json!([1.0, -2.0, 0.5, 63.5]))
+        );
+    }
+
+    #[test]
+    fn test_quantization() {
+        let model = Model::from_buffer(DEQUANTIZE).unwrap();
+        assert_eq!(
+            Quantization::of(&model, 0),
+            Some(Quantization {
+                scale: 0.5,
+                zero_point: 128
+            })
+        );
+        assert_eq!(Quantization::of(&model, 1), None);
+        assert_eq!(Quantization::of(&model, 2), None);
+
+        let model = Model::from_buffer(AFFINE).unwrap();
+        assert_eq!(Quantization::of(&model, 0), None);
+    }
+
+    #[test]
+    fn test_bad_model() {
+        assert!(TfLite::from_bytes(b"not a model").is_err());
+    }
+}
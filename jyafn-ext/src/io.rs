@@ -19,6 +19,17 @@ impl<'a> Input<'a> {
         Self(std::slice::from_raw_parts(input as *const u64, n_slots))
     }
 
+    /// The number of slots in this input. For methods with an input list of dynamic
+    /// length, this is how the length of the list can be known.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether this input has no slots at all.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     /// Gets the data at index `idx` as an `f64`.
     pub fn get_f64(&self, idx: usize) -> f64 {
         f64::from_ne_bytes(self.0[idx].to_ne_bytes())
@@ -100,6 +111,15 @@ impl<'a> InputReader<'a> {
         read
     }
 
+    /// Reads `n` bytes, packed as in `Layout::Bytes`, advancing the reader by as many
+    /// positions as needed to hold them.
+    pub fn read_bytes(&mut self, n: usize) -> Vec<u8> {
+        (0..n.div_ceil(8))
+            .flat_map(|_| self.read_u64().to_ne_bytes())
+            .take(n)
+            .collect()
+    }
+
     /// Iterates through `n` floats, advancing the reader.
     pub fn iter_n_f64(&'a mut self, n: usize) -> impl 'a + Iterator<Item = f64> {
         (0..n).map(|_| self.read_f64())
@@ -157,14 +177,20 @@ impl<'a> InputReader<'a> {
 pub struct OutputBuilder<'a> {
     position: usize,
     slice: &'a mut [MaybeUninit<u64>],
+    /// Whether the first slot of the output is the length of a dynamic output.
+    len_prefix: bool,
 }
 
 impl<'a> Drop for OutputBuilder<'a> {
     fn drop(&mut self) {
-        // This prevents any uninitialized memory from ever being read.
-        while self.position < self.slice.len() {
-            self.push_u64(0)
+        // Methods usually write the whole output, so there is nothing left to do.
+        if self.is_fully_written() {
+            return;
         }
+
+        // This prevents any uninitialized memory from ever being read.
+        self.slice[self.position..].fill(MaybeUninit::new(0));
+        self.position = self.slice.len();
     }
 }
 
@@ -179,9 +205,46 @@ impl<'a> OutputBuilder<'a> {
         Self {
             position: 0,
             slice: std::slice::from_raw_parts_mut(output as *mut MaybeUninit<u64>, n_slots),
+            len_prefix: false,
         }
     }
 
+    /// Creates a new output for a method with a dynamic output. The first slot is
+    /// reserved for the length of the output, which is zero until
+    /// [`OutputBuilder::finish_with_len`] is called. Pushed values start at the second
+    /// slot.
+    ///
+    /// # Safety
+    ///
+    /// Make sure that `output` points to a slice with _memory size_ of `8 * n_slots` at
+    /// least, with `n_slots` at least one. Failing to do so, writes to bad memory may
+    /// occur.
+    pub unsafe fn new_dynamic(output: *mut u8, n_slots: usize) -> Self {
+        let mut builder = Self::new(output, n_slots);
+        builder.push_u64(0);
+        builder.len_prefix = true;
+        builder
+    }
+
+    /// Records the number of elements actually written to a dynamic output, finishing
+    /// it. The slots that were not written are zero-filled. Methods with a dynamic output
+    /// that never call this function return an empty list.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the output is not dynamic, that is, if the method was not
+    /// declared with a dynamic output in [`declare_methods`](crate::declare_methods).
+    pub fn finish_with_len(self, len: usize) {
+        assert!(self.len_prefix, "output is not dynamic");
+        self.slice[0].write(len as u64);
+    }
+
+    /// Whether all the slots of the output have been written. Unwritten slots are
+    /// zero-filled when the builder is dropped.
+    pub fn is_fully_written(&self) -> bool {
+        self.position == self.slice.len()
+    }
+
     pub fn push_f64(&mut self, val: f64) {
         self.slice[self.position].write(u64::from_ne_bytes(val.to_ne_bytes()));
         self.position += 1;
@@ -202,6 +265,16 @@ impl<'a> OutputBuilder<'a> {
         self.position += 1;
     }
 
+    /// Pushes bytes, packed as in `Layout::Bytes`, zeroing the padding at the end of the
+    /// last position.
+    pub fn push_bytes(&mut self, src: &[u8]) {
+        for chunk in src.chunks(8) {
+            let mut slot = [0; 8];
+            slot[..chunk.len()].copy_from_slice(chunk);
+            self.push_u64(u64::from_ne_bytes(slot));
+        }
+    }
+
     pub fn copy_from_f64(&mut self, src: &[f64]) {
         for &val in src {
             self.push_f64(val);
@@ -226,3 +299,44 @@ impl<'a> OutputBuilder<'a> {
         }
     }
 }
+
+/// The callback supplied by jyafn to write binary data incrementally. It receives the
+/// context pointer supplied together with it and a chunk of data with its length and
+/// returns zero on success.
+pub type WriteCallback = unsafe extern "C" fn(*mut (), *const u8, usize) -> i32;
+
+/// A writer that forwards everything written to it to a [`WriteCallback`] supplied by
+/// jyafn.
+#[derive(Debug)]
+pub struct CallbackWriter {
+    context: *mut (),
+    callback: WriteCallback,
+}
+
+impl CallbackWriter {
+    /// Creates a new callback writer.
+    ///
+    /// # Safety
+    ///
+    /// Make sure that `context` and `callback` are the ones supplied by jyafn and that
+    /// the writer doesn't outlive the call in which they were supplied.
+    pub unsafe fn new(context: *mut (), callback: WriteCallback) -> Self {
+        CallbackWriter { context, callback }
+    }
+}
+
+impl std::io::Write for CallbackWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // Safety: guaranteed by the contract of `CallbackWriter::new`.
+        let status = unsafe { (self.callback)(self.context, buf.as_ptr(), buf.len()) };
+        if status == 0 {
+            Ok(buf.len())
+        } else {
+            Err(std::io::Error::other("jyafn failed to write dumped data"))
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
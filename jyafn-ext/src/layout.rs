@@ -16,7 +16,7 @@ pub enum Layout {
     /// An empty value.
     #[default]
     Unit,
-    /// A floating point number. Jyafn does not support integers directly.
+    /// A floating point number.
     Scalar,
     /// A boolean. Can be either true or false. This is represented as u64 1 or 0
     /// respectively. All other values are invalid.
@@ -30,6 +30,11 @@ pub enum Layout {
     Struct(Struct),
     /// A layout repeated a given number of times.
     List(Box<Layout>, usize),
+    /// A signed 64-bit integer.
+    Int,
+    /// A fixed number of opaque bytes, packed into as many slots as needed to hold
+    /// them. The padding at the end of the last slot is zeroed.
+    Bytes(usize),
 }
 
 impl From<Struct> for Layout {
@@ -49,6 +54,9 @@ impl From<Struct> for Layout {
 ///     y: datetime "%Y-%m-%d"
 /// })
 /// ```
+///
+/// Opaque binary blobs give their size in bytes, as in `layout!(bytes 32)`. Inside structs
+/// and lists, they need to be wrapped in parentheses, as in `layout!({ x: (bytes 32) })`.
 #[macro_export]
 macro_rules! layout {
     ({$($key:tt : $ty:tt),*}) => {
@@ -60,6 +68,9 @@ macro_rules! layout {
     (scalar) => {
         $crate::Layout::Scalar
     };
+    (int) => {
+        $crate::Layout::Int
+    };
     (bool) => {
         $crate::Layout::Bool
     };
@@ -72,6 +83,12 @@ macro_rules! layout {
     (symbol) => {
         $crate::Layout::Symbol
     };
+    (bytes $n_bytes:expr) => {
+        $crate::Layout::Bytes($n_bytes)
+    };
+    ((bytes $n_bytes:expr)) => {
+        $crate::Layout::Bytes($n_bytes)
+    };
     ([$element:tt; $size:expr]) => {
         $crate::Layout::List(Box::new($crate::layout!($element)), $size)
     }
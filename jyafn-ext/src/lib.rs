@@ -5,6 +5,7 @@ mod io;
 mod layout;
 mod outcome;
 mod resource;
+pub mod test;
 
 /// Reexporting from the `paste` crate. This is neeede because we have to programatically
 /// generate new identifiers to be used as symbols in the final shared object.
@@ -12,10 +13,10 @@ pub use paste::paste;
 /// We need JSON support to zip JSON values around the FFI boundary.
 pub use serde_json;
 
-pub use io::{Input, OutputBuilder, InputReader};
+pub use io::{CallbackWriter, Input, InputReader, OutputBuilder, WriteCallback};
 pub use layout::{Layout, Struct, ISOFORMAT};
 pub use outcome::Outcome;
-pub use resource::{Method, Resource};
+pub use resource::{dynamic_output_layout, Method, Resource};
 
 /// Generates the boilerplate code for a `jyafn` extension.
 ///
@@ -173,6 +174,7 @@ macro_rules! extension {
                         stringify!($ty): {
                             "fn_from_bytes": stringify!($ty).to_string() + "_from_bytes",
                             "fn_dump": stringify!($ty).to_string() + "_dump",
+                            "fn_dump_to": stringify!($ty).to_string() + "_dump_to",
                             "fn_size": stringify!($ty).to_string() + "_size",
                             "fn_get_method_def": stringify!($ty).to_string() + "_get_method",
                             "fn_drop": stringify!($ty).to_string() + "_drop"
@@ -244,6 +246,26 @@ macro_rules! resource {
                 })
             }
 
+            #[no_mangle]
+            pub unsafe extern "C" fn [<$ty _dump_to>](
+                raw: *mut $ty,
+                context: *mut (),
+                callback: $crate::WriteCallback,
+            ) -> *mut $crate::Outcome {
+                std::panic::catch_unwind(|| {
+                    let mut writer = $crate::CallbackWriter::new(context, callback);
+                    let outcome = (&*raw).dump_to(&mut writer).map_err(|err| err.to_string());
+                    let boxed = Box::new($crate::Outcome::from(outcome));
+                    Box::leak(boxed) as *mut _
+                }).unwrap_or_else(|_| {
+                    eprintln!(
+                        "calling `dump_to` on resource {:?} panicked. Will return null. See stderr.",
+                        stringify!($ty)
+                    );
+                    std::ptr::null_mut()
+                })
+            }
+
             #[no_mangle]
             pub unsafe extern "C" fn [<$ty _from_bytes>](
                 bytes_ptr: *const u8,
@@ -329,12 +351,20 @@ macro_rules! resource {
 /// }
 ///
 /// ```
+///
+/// This generates two raw methods: one for when the method is declared with a fixed
+/// output and another for when it is declared with a dynamic output in
+/// [`declare_methods`]. The right one is picked by the declaration.
 #[macro_export]
 macro_rules! method {
     ($safe_interface:ident) => {
+        $crate::method!(@raw raw_method__, new, $safe_interface);
+        $crate::method!(@raw raw_dynamic_method__, new_dynamic, $safe_interface);
+    };
+    (@raw $prefix:ident, $new:ident, $safe_interface:ident) => {
         $crate::paste! {
             #[allow(non_snake_case)]
-            pub unsafe extern "C" fn [<raw_method__ $safe_interface>](
+            pub unsafe extern "C" fn [<$prefix $safe_interface>](
                 resource_ptr: *const (),
                 input_ptr: *const u8,
                 input_slots: u64,
@@ -352,7 +382,7 @@ macro_rules! method {
                         Self::$safe_interface(
                             resource,
                             $crate::Input::new(input_ptr, input_slots as usize),
-                            $crate::OutputBuilder::new(output_ptr, output_slots as usize),
+                            $crate::OutputBuilder::$new(output_ptr, output_slots as usize),
                         )
                     }
                 }) {
@@ -378,7 +408,10 @@ macro_rules! method {
 macro_rules! get_method_ptr {
     ($safe_interface:ident) => {
         $crate::paste!(Self::[<raw_method__ $safe_interface>]) as usize
-    }
+    };
+    (dynamic $safe_interface:ident) => {
+        $crate::paste!(Self::[<raw_dynamic_method__ $safe_interface>]) as usize
+    };
 }
 
 /// This macro provides a standard implementation for the [`Resource::get_method`]
@@ -397,10 +430,47 @@ macro_rules! get_method_ptr {
 ///                 // Use the layout notation to declare the method (an yes, you can use
 ///                 // `self` anywhere in the declaration)
 ///                 foo_method(x: scalar, y: [datetime; self.size]) -> [datetime; self.size];
+///                 // Lists in the input may have a `dynamic` length.
+///                 bar_method(x: scalar, y: [scalar; dynamic]) -> scalar;
+///                 // Lists in the output may have a `max` length.
+///                 baz_method(x: [scalar; self.size]) -> [scalar; max self.size];
 ///         }
 ///     }
 /// }
 /// ```
+///
+/// # Dynamic lists
+///
+/// Input fields declared as `[<layout>; dynamic]` are lists whose length is decided at
+/// each call site, when the graph is built, instead of by the resource. Their length is
+/// not validated by jyafn: the method receives however many slots the call supplied and
+/// can find out how many through [`Input::len`]. Since only the total number of slots is
+/// known, a method should have at most one dynamic list. Dynamic lists can only be
+/// top-level fields of the input and never appear in the output.
+///
+/// In the ABI, dynamic lists are declared with length zero in the input layout of the
+/// [`Method`] and listed by name in [`Method::dynamic_inputs`]. Older versions of jyafn
+/// ignore this field and will only accept calls with empty lists, instead of calling the
+/// method with an input of the wrong size. Methods without dynamic lists are declared
+/// exactly as before.
+///
+/// # Dynamic outputs
+///
+/// An output declared as `[<layout>; max <size>]` is a list of at most `size` elements,
+/// whose actual length is decided by the method at each call, such as a top-k selection
+/// where `k` is an input. The method writes the elements it has to its [`OutputBuilder`]
+/// and then calls [`OutputBuilder::finish_with_len`] with the number of elements
+/// written. In jyafn, the output of the method is the struct
+/// `{ len: int, values: [<layout>; <size>] }`, where the elements of `values` past `len`
+/// are zeroed.
+///
+/// In the ABI, the output layout of the [`Method`] is this same struct, created by
+/// [`dynamic_output_layout`], and [`Method::dynamic_output`] is set. The method gets an
+/// output buffer of the size of the whole struct: its first slot is the length, as a
+/// 64-bit integer, followed by the slots of the elements. After each call, jyafn checks
+/// that the length is not greater than `size`, raising an error otherwise. Older
+/// versions of jyafn ignore [`Method::dynamic_output`], but still allocate the right
+/// buffer and read the same struct, only without the check.
 #[macro_export]
 macro_rules! declare_methods {
     ($( $safe_interface:ident ($($key:tt : $ty:tt),*) -> $output:tt; )*) => {
@@ -411,13 +481,76 @@ macro_rules! declare_methods {
     ( match $method:ident : $( $safe_interface:ident ($($key:tt : $ty:tt),*) -> $output:tt; )*) => {
         Some(match $method {
             $(
-                stringify!($safe_interface) => $crate::Method {
-                    fn_ptr: $crate::get_method_ptr!($safe_interface),
-                    input_layout: $crate::r#struct!($($key : $ty),*),
-                    output_layout: $crate::layout!($output),
-                },
+                stringify!($safe_interface) => $crate::Method::new(
+                    $crate::method_output!(fn_ptr $safe_interface -> $output),
+                    $crate::Struct(vec![$(
+                        $crate::method_input_field!($key : $ty)
+                    ),*]),
+                    $crate::method_output!(layout $output),
+                )
+                .with_dynamic_inputs({
+                    let names: Vec<Option<String>> = vec![$(
+                        $crate::dynamic_input_name!($key : $ty)
+                    ),*];
+                    names.into_iter().flatten().collect()
+                })
+                .with_dynamic_output($crate::method_output!(is_dynamic $output)),
             )*
             _ => return None,
         })
     };
 }
+
+/// Gets the function pointer, the output layout or whether the output is dynamic for a
+/// method in [`declare_methods`], where lists of `max` length are dynamic outputs.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! method_output {
+    (fn_ptr $safe_interface:ident -> [$element:tt; max $size:expr]) => {
+        $crate::get_method_ptr!(dynamic $safe_interface)
+    };
+    (fn_ptr $safe_interface:ident -> $output:tt) => {
+        $crate::get_method_ptr!($safe_interface)
+    };
+    (layout [$element:tt; max $size:expr]) => {
+        $crate::dynamic_output_layout($crate::layout!($element), $size)
+    };
+    (layout $output:tt) => {
+        $crate::layout!($output)
+    };
+    (is_dynamic [$element:tt; max $size:expr]) => {
+        true
+    };
+    (is_dynamic $output:tt) => {
+        false
+    };
+}
+
+/// Builds a field of the input layout of a method in [`declare_methods`], where lists of
+/// `dynamic` length are declared with length zero.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! method_input_field {
+    ($key:tt : [$element:tt; dynamic]) => {
+        $crate::struct_field!($key : [$element; 0])
+    };
+    ($key:tt : $ty:tt) => {
+        $crate::struct_field!($key : $ty)
+    };
+}
+
+/// Gets the name of an input field of a method in [`declare_methods`], if it is a list
+/// of `dynamic` length.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! dynamic_input_name {
+    ($key:literal : [$element:tt; dynamic]) => {
+        Some($key.to_string())
+    };
+    ($key:ident : [$element:tt; dynamic]) => {
+        Some(stringify!($key).to_string())
+    };
+    ($key:tt : $ty:tt) => {
+        None
+    };
+}
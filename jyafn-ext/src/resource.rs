@@ -15,6 +15,15 @@ pub trait Resource: 'static + Sized + Send + Sync {
     fn from_bytes(bytes: &[u8]) -> Result<Self, impl ToString>;
     /// Dumps this resource as binary data.
     fn dump(&self) -> Result<Vec<u8>, impl ToString>;
+    /// Writes the binary data of this resource, the same as returned by
+    /// `Resource::dump`, into the supplied writer.
+    ///
+    /// Override this method if the resource can be written incrementally, so that big
+    /// resources don't need to be entirely buffered in memory when graphs are dumped.
+    fn dump_to(&self, writer: &mut dyn std::io::Write) -> Result<(), impl ToString> {
+        let dump = self.dump().map_err(|err| err.to_string())?;
+        writer.write_all(&dump).map_err(|err| err.to_string())
+    }
     /// The ammount of heap used by this storage.
     fn size(&self) -> usize;
     /// Gets information on a method name for this resource, if it exists.
@@ -22,8 +31,10 @@ pub trait Resource: 'static + Sized + Send + Sync {
 }
 
 /// A description on the method signature, to guide jyafn to generate the correct method
-/// call on this resource.
+/// call on this resource. Create it with [`Method::new`], since new fields may be added
+/// in the future.
 #[derive(Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Method {
     /// The function pointer to be used in the jyafn code.
     pub fn_ptr: usize,
@@ -31,4 +42,57 @@ pub struct Method {
     pub input_layout: Struct,
     /// The layout of the output parameters.
     pub output_layout: Layout,
+    /// The names of the input fields that are lists of dynamic length. These are
+    /// declared with length zero in `input_layout` and take the length of the list
+    /// supplied in each call to the method. See [`declare_methods`](crate::declare_methods)
+    /// for the implications.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dynamic_inputs: Vec<String>,
+    /// Whether the output is a list whose length is only known at call time. The output
+    /// layout of such methods is created by [`dynamic_output_layout`]. See
+    /// [`declare_methods`](crate::declare_methods) for the implications.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dynamic_output: bool,
+}
+
+impl Method {
+    /// Creates a method with inputs and output of fixed sizes.
+    pub fn new(fn_ptr: usize, input_layout: Struct, output_layout: Layout) -> Method {
+        Method {
+            fn_ptr,
+            input_layout,
+            output_layout,
+            dynamic_inputs: vec![],
+            dynamic_output: false,
+        }
+    }
+
+    /// Sets the names of the input fields that are lists of dynamic length. See
+    /// [`Method::dynamic_inputs`].
+    pub fn with_dynamic_inputs(mut self, dynamic_inputs: Vec<String>) -> Method {
+        self.dynamic_inputs = dynamic_inputs;
+        self
+    }
+
+    /// Sets whether the output is a list whose length is only known at call time. See
+    /// [`Method::dynamic_output`].
+    pub fn with_dynamic_output(mut self, dynamic_output: bool) -> Method {
+        self.dynamic_output = dynamic_output;
+        self
+    }
+}
+
+/// The output layout of a method whose output is a list of at most `max_len` elements of
+/// the given layout, but whose actual length is only known at call time. This is the
+/// struct `{ len: int, values: [<element>; <max_len>] }`.
+pub fn dynamic_output_layout(element: Layout, max_len: usize) -> Layout {
+    Layout::Struct(Struct(vec![
+        ("len".to_string(), Layout::Int),
+        (
+            "values".to_string(),
+            Layout::List(Box::new(element), max_len),
+        ),
+    ]))
 }
@@ -0,0 +1,193 @@
+//! A harness for unit-testing resources in-process, without building the extension
+//! shared object and loading it through jyafn.
+//!
+//! # Usage
+//!
+//! In the tests of an extension:
+//! ```ignore
+//! let resource = MyResource::from_bytes(b"...").unwrap();
+//! let output = jyafn_ext::test::exercise_resource(&resource, "get");
+//! assert_eq!(output, jyafn_ext::serde_json::json!(1.0));
+//! ```
+
+use serde_json::Value;
+use std::ffi::CString;
+
+use super::{Layout, Resource, Struct};
+
+/// The signature of the raw methods generated by [`method!`](crate::method).
+type RawMethod = unsafe extern "C" fn(*const (), *const u8, u64, *mut u8, u64) -> *mut u8;
+
+/// Calls a method of a resource on a raw input, just like jyafn code would, and decodes
+/// the output according to the output layout declared for the method. Datetimes are
+/// decoded as timestamps in microseconds, symbols as their ids and bytes as arrays of
+/// integers.
+///
+/// This returns an error if the method is not declared, if the input does not have the
+/// size of the declared input layout or if the method errors or panics. For methods with
+/// lists of dynamic length, the input may have any number of extra slots. For methods
+/// with a dynamic output, this also returns an error if the method writes a length
+/// greater than the maximum length of the output, just like jyafn.
+pub fn call_method<R: Resource>(
+    resource: &R,
+    method: &str,
+    input: &[u64],
+) -> Result<Value, String> {
+    let Some(declared) = resource.get_method(method) else {
+        return Err(format!("resource has no method {method:?}"));
+    };
+
+    let input_slots = struct_slots(&declared.input_layout);
+    if declared.dynamic_inputs.is_empty() && input.len() != input_slots {
+        return Err(format!(
+            "method {method:?} takes {input_slots} input slots, but got {}",
+            input.len()
+        ));
+    }
+    if input.len() < input_slots {
+        return Err(format!(
+            "method {method:?} takes at least {input_slots} input slots, but got {}",
+            input.len()
+        ));
+    }
+
+    let mut output = vec![0u64; layout_slots(&declared.output_layout)];
+
+    // Safety: the function pointer was created by `method!` and the buffers have exactly
+    // the sizes declared in the method layouts.
+    let err = unsafe {
+        let raw_method: RawMethod = std::mem::transmute(declared.fn_ptr);
+        raw_method(
+            resource as *const R as *const (),
+            input.as_ptr() as *const u8,
+            input.len() as u64,
+            output.as_mut_ptr() as *mut u8,
+            output.len() as u64,
+        )
+    };
+
+    if !err.is_null() {
+        // Safety: errors are created by `method!` with `CString::into_raw`.
+        let err = unsafe { CString::from_raw(err as *mut _) };
+        return Err(err.to_string_lossy().into_owned());
+    }
+
+    if declared.dynamic_output {
+        let max_len = match &declared.output_layout {
+            Layout::Struct(fields) => match fields.0.last() {
+                Some((_, Layout::List(_, max_len))) => *max_len,
+                _ => 0,
+            },
+            _ => 0,
+        };
+        if output[0] > max_len as u64 {
+            return Err(format!(
+                "method {method:?} returned more than {max_len} elements"
+            ));
+        }
+    }
+
+    let mut slots = output.into_iter();
+    decode(&declared.output_layout, &mut slots)
+}
+
+/// Creates a sample input for a given input layout. Scalars are set to `1.0`, integers to
+/// `1`, booleans to `true`, datetimes to the Unix epoch and symbols to the id `0`. Lists
+/// of dynamic length are left empty.
+pub fn sample_input(layout: &Struct) -> Vec<u64> {
+    let mut input = vec![];
+    for (_, field) in &layout.0 {
+        encode_sample(field, &mut input);
+    }
+
+    input
+}
+
+/// Calls a method of a resource on a [`sample_input`], returning the decoded output.
+///
+/// # Panics
+///
+/// This function panics if the method cannot be called, errors or panics, making it
+/// suitable to be used directly in unit tests of extensions.
+pub fn exercise_resource<R: Resource>(resource: &R, method: &str) -> Value {
+    let Some(declared) = resource.get_method(method) else {
+        panic!("resource has no method {method:?}");
+    };
+    let input = sample_input(&declared.input_layout);
+
+    call_method(resource, method, &input)
+        .unwrap_or_else(|err| panic!("calling method {method:?} failed: {err}"))
+}
+
+fn struct_slots(layout: &Struct) -> usize {
+    layout.0.iter().map(|(_, field)| layout_slots(field)).sum()
+}
+
+fn layout_slots(layout: &Layout) -> usize {
+    match layout {
+        Layout::Unit => 0,
+        Layout::Scalar | Layout::Int | Layout::Bool | Layout::DateTime(_) | Layout::Symbol => 1,
+        Layout::Struct(fields) => struct_slots(fields),
+        Layout::List(element, size) => layout_slots(element) * size,
+        Layout::Bytes(n_bytes) => n_bytes.div_ceil(8),
+    }
+}
+
+fn encode_sample(layout: &Layout, input: &mut Vec<u64>) {
+    match layout {
+        Layout::Unit => {}
+        Layout::Scalar => input.push(1.0f64.to_bits()),
+        Layout::Int | Layout::Bool => input.push(1),
+        Layout::DateTime(_) | Layout::Symbol => input.push(0),
+        Layout::Struct(fields) => {
+            for (_, field) in &fields.0 {
+                encode_sample(field, input);
+            }
+        }
+        Layout::List(element, size) => {
+            for _ in 0..*size {
+                encode_sample(element, input);
+            }
+        }
+        Layout::Bytes(n_bytes) => input.resize(input.len() + n_bytes.div_ceil(8), 0),
+    }
+}
+
+fn decode(layout: &Layout, slots: &mut impl Iterator<Item = u64>) -> Result<Value, String> {
+    fn next(slots: &mut impl Iterator<Item = u64>) -> u64 {
+        slots.next().expect("output has the size of the layout")
+    }
+
+    Ok(match layout {
+        Layout::Unit => Value::Null,
+        Layout::Scalar => serde_json::Number::from_f64(f64::from_bits(next(slots)))
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        Layout::Bool => match next(slots) {
+            0 => Value::Bool(false),
+            1 => Value::Bool(true),
+            other => return Err(format!("method returned invalid bool {other}")),
+        },
+        Layout::Int | Layout::DateTime(_) => Value::from(next(slots) as i64),
+        Layout::Symbol => Value::from(next(slots)),
+        Layout::Struct(fields) => Value::Object(
+            fields
+                .0
+                .iter()
+                .map(|(name, field)| Ok((name.clone(), decode(field, slots)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        Layout::List(element, size) => Value::Array(
+            (0..*size)
+                .map(|_| decode(element, slots))
+                .collect::<Result<_, _>>()?,
+        ),
+        Layout::Bytes(n_bytes) => Value::Array(
+            (0..n_bytes.div_ceil(8))
+                .flat_map(|_| next(slots).to_ne_bytes())
+                .take(*n_bytes)
+                .map(Value::from)
+                .collect(),
+        ),
+    })
+}
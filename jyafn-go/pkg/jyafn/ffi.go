@@ -58,15 +58,22 @@ type ffiType struct {
 	formatDatetime func(int64, string) OutcomePtr
 	consumeI64Ptr  func(uintptr) int64
 
-	graphName            func(GraphPtr) AllocatedStr
-	graphGetMetadata     func(GraphPtr, string) AllocatedStr
-	graphGetMetadataJson func(GraphPtr) AllocatedStr
-	graphLoad            func([]byte, uintptr) OutcomePtr
-	graphToJson          func(GraphPtr) AllocatedStr
-	graphRender          func(GraphPtr) AllocatedStr
-	graphCompile         func(GraphPtr) OutcomePtr
-	graphClone           func(GraphPtr) GraphPtr
-	graphDrop            func(GraphPtr)
+	graphName                 func(GraphPtr) AllocatedStr
+	graphCanonicalName        func(GraphPtr) AllocatedStr
+	graphInputCount           func(GraphPtr) uintptr
+	graphInputName            func(GraphPtr, uintptr) AllocatedStr
+	graphInputLayoutAt        func(GraphPtr, uintptr) LayoutPtr
+	graphGetMetadata          func(GraphPtr, string) AllocatedStr
+	graphGetMetadataJson      func(GraphPtr) AllocatedStr
+	graphGetMetadataJsonValue func(GraphPtr, string) AllocatedStr
+	graphLoad                 func([]byte, uintptr) OutcomePtr
+	graphToJson               func(GraphPtr) AllocatedStr
+	graphToDot                func(GraphPtr) AllocatedStr
+	graphRender               func(GraphPtr) AllocatedStr
+	graphVerify               func(GraphPtr) OutcomePtr
+	graphCompile              func(GraphPtr) OutcomePtr
+	graphClone                func(GraphPtr) GraphPtr
+	graphDrop                 func(GraphPtr)
 
 	layoutToString       func(LayoutPtr) AllocatedStr
 	layoutToJson         func(LayoutPtr) AllocatedStr
@@ -74,6 +81,7 @@ type ffiType struct {
 	layoutSize           func(LayoutPtr) uintptr
 	layoutIsUnit         func(LayoutPtr) bool
 	layoutIsScalar       func(LayoutPtr) bool
+	layoutIsInt          func(LayoutPtr) bool
 	layoutIsBool         func(LayoutPtr) bool
 	layoutIsDatetime     func(LayoutPtr) bool
 	layoutIsSymbol       func(LayoutPtr) bool
@@ -169,11 +177,18 @@ func init() {
 	register(&ffi.consumeI64Ptr, "consume_i64_ptr")
 
 	register(&ffi.graphName, "graph_name")
+	register(&ffi.graphCanonicalName, "graph_canonical_name")
+	register(&ffi.graphInputCount, "graph_input_count")
+	register(&ffi.graphInputName, "graph_input_name")
+	register(&ffi.graphInputLayoutAt, "graph_input_layout_at")
 	register(&ffi.graphGetMetadata, "graph_get_metadata")
 	register(&ffi.graphGetMetadataJson, "graph_get_metadata_json")
+	register(&ffi.graphGetMetadataJsonValue, "graph_get_metadata_json_value")
 	register(&ffi.graphLoad, "graph_load")
 	register(&ffi.graphToJson, "graph_to_json")
+	register(&ffi.graphToDot, "graph_to_dot")
 	register(&ffi.graphRender, "graph_render")
+	register(&ffi.graphVerify, "graph_verify")
 	register(&ffi.graphCompile, "graph_compile")
 	register(&ffi.graphClone, "graph_clone")
 	register(&ffi.graphDrop, "graph_drop")
@@ -184,6 +199,7 @@ func init() {
 	register(&ffi.layoutSize, "layout_size")
 	register(&ffi.layoutIsUnit, "layout_is_unit")
 	register(&ffi.layoutIsScalar, "layout_is_scalar")
+	register(&ffi.layoutIsInt, "layout_is_int")
 	register(&ffi.layoutIsBool, "layout_is_bool")
 	register(&ffi.layoutIsDatetime, "layout_is_datetime")
 	register(&ffi.layoutIsSymbol, "layout_is_symbol")
@@ -36,6 +36,44 @@ func (g *Graph) Name() string {
 	return ffi.transmuteAsStr(name)
 }
 
+// CanonicalName returns a name derived only from the contents of the graph. Graphs with
+// the same structure have the same canonical name, regardless of their names and
+// metadata.
+func (g *Graph) CanonicalName() string {
+	g.panicOnClosed()
+	name := ffi.graphCanonicalName(g.ptr)
+	defer ffi.freeStr(name)
+	return ffi.transmuteAsStr(name)
+}
+
+// InputCount returns the number of inputs of the graph.
+func (g *Graph) InputCount() uint {
+	g.panicOnClosed()
+	return uint(ffi.graphInputCount(g.ptr))
+}
+
+// InputName returns the name of the input at the given index, in declaration order.
+func (g *Graph) InputName(index uint) string {
+	g.panicOnClosed()
+	name := ffi.graphInputName(g.ptr, uintptr(index))
+	if name == 0 {
+		panic("called InputName on a Graph out of bounds")
+	}
+	defer ffi.freeStr(name)
+	return ffi.transmuteAsStr(name)
+}
+
+// InputLayout returns the layout of the input at the given index, in declaration order.
+// The layout is only valid while the graph is not closed.
+func (g *Graph) InputLayout(index uint) Layout {
+	g.panicOnClosed()
+	ptr := ffi.graphInputLayoutAt(g.ptr, uintptr(index))
+	if uintptr(ptr) == 0 {
+		panic("called InputLayout on a Graph out of bounds")
+	}
+	return Layout{ptr: ptr}
+}
+
 func (g *Graph) GetMetadata(key string) string {
 	g.panicOnClosed()
 	value := ffi.graphGetMetadata(g.ptr, key)
@@ -46,6 +84,18 @@ func (g *Graph) GetMetadata(key string) string {
 	return ffi.transmuteAsStr(value)
 }
 
+// GetMetadataJSONValue returns the serialized JSON metadata value under the given key, or
+// an empty string if there is none.
+func (g *Graph) GetMetadataJSONValue(key string) string {
+	g.panicOnClosed()
+	value := ffi.graphGetMetadataJsonValue(g.ptr, key)
+	if value == 0 {
+		return ""
+	}
+	defer ffi.freeStr(value)
+	return ffi.transmuteAsStr(value)
+}
+
 func (g *Graph) GetMetadataJSON() string {
 	g.panicOnClosed()
 	value := ffi.graphGetMetadataJson(g.ptr)
@@ -60,6 +110,13 @@ func (g *Graph) ToJSON() string {
 	return ffi.transmuteAsStr(value)
 }
 
+func (g *Graph) ToDot() string {
+	g.panicOnClosed()
+	value := ffi.graphToDot(g.ptr)
+	defer ffi.freeStr(value)
+	return ffi.transmuteAsStr(value)
+}
+
 func (g *Graph) Render() string {
 	g.panicOnClosed()
 	rendered := ffi.graphRender(g.ptr)
@@ -67,6 +124,13 @@ func (g *Graph) Render() string {
 	return ffi.transmuteAsStr(rendered)
 }
 
+// Verify checks that the graph would compile, without invoking the toolchain.
+func (g *Graph) Verify() error {
+	g.panicOnClosed()
+	_, err := ffi.graphVerify(g.ptr).get()
+	return err
+}
+
 func (g *Graph) Compile() (*Function, error) {
 	g.panicOnClosed()
 	ptr, err := ffi.graphCompile(g.ptr).get()
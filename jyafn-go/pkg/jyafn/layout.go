@@ -85,6 +85,10 @@ func (l Layout) IsScalar() bool {
 	return ffi.layoutIsScalar(l.ptr)
 }
 
+func (l Layout) IsInt() bool {
+	return ffi.layoutIsInt(l.ptr)
+}
+
 func (l Layout) IsBool() bool {
 	return ffi.layoutIsBool(l.ptr)
 }
@@ -6,6 +6,7 @@ import types
 import typing
 import json
 import itertools
+import contextlib
 import numpy as np
 import datetime as pydatetime
 
@@ -218,6 +219,11 @@ class GraphFactory:
         g.set_metadata("jyafn.mem_size_estimate", str(g.get_size()))
         if self.original.__doc__ is not None:
             g.set_metadata("jyafn.doc", self.original.__doc__)
+        try:
+            g.set_source(inspect.getsource(self.original))
+        except (OSError, TypeError):
+            # Source is not available (e.g., defined in the REPL).
+            pass
 
         if self.debug:
             print(g.render())
@@ -310,6 +316,30 @@ def func(*args, metadata: dict = {}, debug: bool = False) -> fn.Function:
     return inner(args[0]) if len(args) == 1 else inner
 
 
+@contextlib.contextmanager
+def scope() -> typing.Iterator[None]:
+    """
+    A context manager that undoes, on exit, the changes made to the process-global state
+    of JYAFN inside of it. Currently, this means that pure functions inscribed with
+    `fn.inscribe_pfunc` inside the scope by the current thread are removed. This is
+    useful to isolate tests from one another. Scopes can be nested.
+
+    Example:
+    ```
+    with fn.scope():
+        fn.inscribe_pfunc("double", double, ["scalar"], "scalar")
+        ...
+
+    # `double` is not inscribed anymore.
+    ```
+    """
+    snapshot = fn.snapshot_pfuncs()
+    try:
+        yield
+    finally:
+        fn.restore_pfuncs(snapshot)
+
+
 ANONYMOUS_COUNTER: dict[str, int] = {}
 
 
@@ -504,6 +534,17 @@ class scalar(BaseAnnotation):
         return fn.Layout.scalar()
 
 
+class integer(BaseAnnotation):
+    """
+    Annotates the `int` layout. This is not called `int` so as not to shadow the Python
+    builtin.
+    """
+
+    @classmethod
+    def make_layout(cls, args: tuple[Any, ...]) -> fn.Layout:
+        return fn.Layout.int()
+
+
 class bool(BaseAnnotation):
     """Annotates the `bool` layout."""
 
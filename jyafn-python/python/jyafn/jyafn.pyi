@@ -3,7 +3,8 @@
 ##
 
 from __future__ import annotations
-from typing import Any, Callable, Optional
+from typing import Any, Callable, Iterable, Optional
+import ctypes
 
 class Graph:
     """
@@ -17,10 +18,31 @@ class Graph:
 
     def __enter__(self) -> Graph: ...
     def __exit__(self, exc_type, exc_val, exc_tb) -> None: ...
-    def __call__(self, *args: Any, **kwds: Any) -> Any:
-        """Calls this graph as a subgraph of another graph."""
+    def __call__(self, *args: Any, inline: bool = False, **kwds: Any) -> Any:
+        """
+        Calls this graph as a subgraph of another graph. If `inline` is set, the nodes of
+        this graph are copied into the other graph instead of being called in runtime,
+        which lets them be optimized together. The result is the same either way.
+        """
+    @property
+    def source(self) -> str | None:
+        """The source code that generated the graph of this function, if any."""
     def get_size(self) -> int:
         """Gets the total in-memory size of the current graph"""
+    def size_breakdown(self) -> dict[str, int]:
+        """
+        Breaks down the in-memory size of the current graph, in bytes, by component:
+        `nodes`, `mappings`, `resources`, `subgraphs`, `symbols` and `other`. Mappings and
+        resources shared with subgraphs are counted only once.
+        """
+    def diff(self, other: Graph) -> dict[str, Any]:
+        """
+        Describes what changed from this graph to `other`: `added_inputs`,
+        `removed_inputs`, `changed_inputs` (input name to `before` and `after` layouts),
+        `moved_inputs` (input name to `before` and `after` positions in the input layout),
+        `output_layout` and `node_count` (`before` and `after`, or `None` if unchanged),
+        and `added_metadata`, `removed_metadata` and `changed_metadata` keys.
+        """
     def dump(self) -> bytes:
         """Dumps the graph as a binary data format."""
     def write(self, path: str) -> None:
@@ -33,6 +55,30 @@ class Graph:
         Creates a JSON representation of the graph. This JSON representation does not
         take _mappings_ into account, so it cannot be used to recreate the graph later.
         """
+    def to_dot(self) -> str:
+        """
+        Renders the graph in the GraphViz DOT language, for debugging. Nodes that are
+        removed when compiling, since they do not contribute to the output, are dashed.
+        """
+    @property
+    def canonical_name(self) -> str:
+        """
+        A name derived only from the contents of this graph. Graphs built independently
+        with the same structure have the same canonical name, regardless of their names
+        and metadata. The contents of mappings and resources are not taken into account.
+        """
+    @property
+    def symbols(self) -> list[str]:
+        """
+        Returns the symbols defined in this graph, ordered by their ids. Inputs may
+        define extra symbols at runtime, so this list is not exhaustive.
+        """
+    @property
+    def inputs(self) -> list[tuple[str, Layout]]:
+        """
+        Returns the names and the layouts of the inputs of this graph, in the order they
+        were declared.
+        """
     @property
     def input_layout(self) -> Layout:
         """
@@ -49,20 +95,135 @@ class Graph:
         of the real deal, so mutating the returned dictionary has ho effect on the graph.
         Use `Graph.set_metadata` to add new keys.
         """
+    @property
+    def nodes(self) -> list[NodeInfo]:
+        """
+        Returns a read-only snapshot of the nodes of this graph, in insertion order. The
+        position of each node in the list is its id.
+        """
     def set_metadata(self, key: str, value: str) -> None:
         """
         Sets a metadata key to the given value. Metadata can be anything you find useful.
         Avoid using the `jyafn.` prefix for your keys because JYAFN uses some keys by
         default.
         """
+    @property
+    def source(self) -> str | None:
+        """
+        The source code that generated this graph, if it was set with
+        `Graph.set_source`. The source code is saved with the graph when it is dumped.
+        """
+    def set_source(self, source: str) -> None:
+        """
+        Annotates this graph with the source code that generated it. The source code has
+        no effect on the compilation of the graph, but is kept when the graph is dumped,
+        for auditing purposes. Sources larger than 1MiB are rejected.
+        """
+    def constrain_input(
+        self, name: str, min: float | None = None, max: float | None = None
+    ) -> None:
+        """
+        Constrains all the scalar values of the input `name` to be within `min` and
+        `max`, inclusive. The compiled function checks the constraint before any other
+        computation, raising an error naming the input if it is not satisfied. NaNs
+        never satisfy a constraint. The constraints are stored in the graph metadata,
+        under the key `jyafn.input_constraints`.
+        """
+    def rename_input(self, old: str, new: str) -> None:
+        """
+        Renames the input `old` to `new`, keeping its position in the input layout.
+        Input constraints and pinnable inputs are renamed along.
+        """
+    def bind_input(self, name: str, value: Any) -> None:
+        """
+        Specializes this graph for a fixed value of the input `name`. The input is
+        removed from the input layout and replaced by constants encoded from `value`,
+        which must fit the layout of the input.
+        """
+    def rename_output_field(self, old: str, new: str) -> None:
+        """
+        Renames the field `old` of the output struct to `new`, keeping its position.
+        """
+    def prune_unused_inputs(self) -> list[str]:
+        """
+        Removes the inputs of this graph that feed nothing reachable from the output,
+        returning their names. Inputs used only by assertions and constrained inputs
+        are kept.
+        """
+    def pipe(self, next: Graph) -> Graph:
+        """
+        Creates a new graph with the same inputs as this graph, feeding the output of
+        this graph into `next` and returning the output of `next`. The output of this
+        graph must be a struct containing all the inputs of `next`, which are matched by
+        name. Extra fields are ignored.
+        """
     def render(self) -> str:
         """Renders the QBE IR code associated with this graph."""
+    def render_ir(self, annotated: bool = True) -> str:
+        """
+        Renders the QBE IR code associated with this graph. If `annotated`, the
+        instructions computing each node are preceded by a QBE comment with the id of the
+        node and the name of its op, as in `# n3: Abs`. Node ids are those of the graph
+        after optimization.
+        """
     def render_assembly(self) -> str:
         """Renders the assembly code associated with this graph."""
-    def compile(self) -> Function:
+    def verify(self) -> None:
         """
-        Compiles the graph into a JYAFN function.
+        Checks that this graph would compile, raising the same error compilation would
+        otherwise. This type-checks the graph and looks for unconditional errors without
+        invoking QBE, the assembler or the linker.
         """
+    def compile(
+        self, cache: CompileCache | None = None, pin: dict[str, Any] | None = None
+    ) -> Function:
+        """
+        Compiles the graph into a JYAFN function. If a `cache` is supplied, the assembly
+        of the parts of the graph that did not change since a previous compilation using
+        the same cache is reused.
+
+        The values in `pin` are pinned to the corresponding pinnable inputs (see
+        `input`): the compiled function is specialized for them and does not take these
+        inputs anymore.
+        """
+
+class CompileCache:
+    """
+    A cache reusing the assembly generated for each region of a graph, that is, the
+    graph itself and each of its subgraphs. Regions are keyed by their content, so
+    changing a single node only invalidates the region containing it. Use it with
+    `Graph.compile` to speed up repeated compilations of slightly changed graphs.
+    """
+
+    def __init__(self) -> None: ...
+    def __len__(self) -> int:
+        """The number of regions stored in the cache."""
+    @property
+    def hits(self) -> int:
+        """The number of regions whose assembly was reused so far."""
+    @property
+    def misses(self) -> int:
+        """The number of regions whose assembly had to be generated so far."""
+    def clear(self) -> None:
+        """Removes all regions from the cache."""
+
+class NodeInfo:
+    """
+    A read-only snapshot of a node of a `fn.Graph`, as returned by `Graph.nodes`.
+    """
+
+    @property
+    def id(self) -> int:
+        """The id of this node in the graph."""
+    @property
+    def op(self) -> str:
+        """The name of the operation of this node, e.g., `"Add"`."""
+    @property
+    def args(self) -> list[Ref]:
+        """The arguments of the operation of this node."""
+    @property
+    def type(self) -> Type:
+        """The type of the output of this node."""
 
 class Ref:
     """
@@ -85,7 +246,11 @@ class Ref:
     def __rtruediv__(self, other: Any) -> Ref: ...
     def __floordiv__(self, other: Any) -> Ref: ...
     def __rfloordiv__(self, other: Any) -> Ref: ...
-    def __mod__(self, other: Any) -> Ref: ...
+    def __mod__(self, other: Any) -> Ref:
+        """
+        The remainder of the floored division, which has the sign of `other`, just like in
+        Python. Together with `//`, this keeps `a == (a // b) * b + a % b`.
+        """
     def __rmod__(self, other: Any) -> Ref: ...
     def __pow__(self, other: Any) -> Ref: ...
     def __rpow__(self, other: Any) -> Ref: ...
@@ -102,6 +267,16 @@ class Ref:
     def __rand__(self, other: Any) -> Ref: ...
     def __or__(self, other: Any) -> Ref: ...
     def __ror__(self, other: Any) -> Ref: ...
+    def not_(self) -> Ref:
+        """
+        Inverts this boolean reference. This is the same as `~self`. Note that `-self`
+        does not work on booleans.
+        """
+    def __array_ufunc__(self, ufunc: Any, method: str, *inputs: Any, **kwargs: Any) -> Any:
+        """
+        Makes numpy ufuncs called directly on refs (e.g., `np.exp(x)` or
+        `np.maximum(x, y)`) insert the corresponding operations in the current graph.
+        """
     def choose(self, if_true: Any, if_false: Any) -> Any:
         """
         Since `__bool__` doesn't work on refs, we need to use other alternatives. This
@@ -117,7 +292,42 @@ class Ref:
     def to_float(self) -> Ref:
         """
         Transform this boolean reference into a scalar. This is basically
-        `self.choose(1.0, 0.0)`.
+        `self.choose(1.0, 0.0)`. Int references are converted to the nearest scalar.
+        """
+    def to_int(self) -> Ref:
+        """
+        Transforms this scalar reference into an int, truncating towards zero. NaNs and
+        scalars out of the range of a 64-bit integer give unspecified results.
+        """
+    def min(self, other: Any) -> Ref:
+        """
+        The minimum of this scalar reference and `other`. If one of them is NaN, the
+        other is returned.
+        """
+    def max(self, other: Any) -> Ref:
+        """
+        The maximum of this scalar reference and `other`. If one of them is NaN, the
+        other is returned.
+        """
+    def isnan(self) -> Ref:
+        """
+        Whether this scalar reference is NaN. `isnan(NaN)` is `True`. This composes with
+        `choose` to replace NaNs by a default.
+        """
+    def isfinite(self) -> Ref:
+        """
+        Whether this scalar reference is neither infinite nor NaN. `isfinite(inf)` is
+        `False`.
+        """
+    def div_or(self, den: Any, default: Any) -> Ref:
+        """
+        Divides this scalar reference by `den`, unless `den` is zero (either `0.0` or
+        `-0.0`), in which case the result is `default`.
+        """
+    def clamp(self, min: Any, max: Any) -> Ref:
+        """
+        Bounds this scalar reference to be between `min` and `max`. If `min > max`, the
+        result is `max`. NaNs are passed through unchanged.
         """
     def conjugate(self) -> Ref:
         """This is only a noop to make numpy happy. Jyafn has no complex type."""
@@ -150,6 +360,14 @@ class Ref:
         """Same as `Ref.acosh`. Used for compatibility with `numpy`."""
     def arctanh(self) -> Ref:
         """Same as `Ref.atanh`. Used for compatibility with `numpy`."""
+    def erf(self) -> Ref: ...
+    def erfc(self) -> Ref: ...
+    def norm_cdf(self) -> Ref:
+        """The CDF of the standard normal distribution."""
+    def norm_pdf(self) -> Ref:
+        """The PDF of the standard normal distribution."""
+    def norm_inv(self) -> Ref:
+        """The quantile function (inverse CDF) of the standard normal distribution."""
     def timestamp(self) -> Ref:
         """
         Transforms this datetime ref into a scalar, containing the Unix epoch in
@@ -219,6 +437,15 @@ class Function:
     @property
     def output_layout(self) -> Layout:
         """Returns the output layout of this function."""
+    def input_schema_json(self) -> str:
+        """
+        Returns a JSON Schema, as a JSON string, of the values accepted as input by this
+        function. Datetimes are strings, described by their expected format.
+        """
+    def output_schema_json(self) -> str:
+        """
+        Returns a JSON Schema, as a JSON string, of the values returned by this function.
+        """
     @property
     def fn_ptr(self) -> int:
         """The raw function pointer associated with this function."""
@@ -262,20 +489,85 @@ class Function:
         a copy of the computational graph by using `Function.get_graph` and then use
         `Graph.set_metadata` on that.
         """
+    @property
+    def opt_stats(self) -> dict[str, int] | None:
+        """
+        Returns statistics on the optimizations performed when this function was
+        compiled: the number of nodes before and after optimization
+        (`nodes_before`, `nodes_after`), how many were merged into an identical node
+        (`subexpressions_merged`), simplified by constant evaluation (`const_folded`) or
+        removed for being unused (`unreachable_removed`) and how many
+        unused mappings, resources and subgraphs were dropped (`mappings_pruned`,
+        `resources_pruned`, `subgraphs_pruned`).
+        """
+    @property
+    def symbols(self) -> list[str]:
+        """
+        Returns the symbols known to this function, ordered by their ids. Inputs may
+        define extra symbols at runtime, so this list is not exhaustive.
+        """
+    def error_names(self) -> list[str]:
+        """
+        Returns the messages of the errors declared in this function, e.g., by
+        assertions, ordered by their ids. When one of these errors is raised, the
+        exception has an `error_id` attribute with its index in this list. The function
+        may raise other errors, so this list is not exhaustive.
+        """
+    def symbol_index(self, name: str) -> int | None:
+        """
+        Returns the id with which the symbol `name` is encoded at runtime, or `None` if
+        the symbol is not known to this function. This never adds new symbols.
+        """
     def get_size(self) -> int:
         """Gets the total in-memory size of the current graph"""
+    def warm_up(self, dry_run: bool = True) -> None:
+        """
+        Prepares this function to be called, so that the first call is not slower than
+        the rest. If `dry_run` is set, the function is also called once on an input of all
+        zeros, ignoring the result and any error raised. Call this at startup in every
+        thread that will use this function, since the buffers are allocated per thread.
+        """
     def eval_raw(self, args: bytes) -> bytes:
         """
         Evaluates the function on a _raw_ buffer of data and returns the resulting buffer
         of _raw_ data. Although this is perfectly safe, it it very error-prone. So, just
         use this if you really, really know what you are doing.
         """
-    def eval(self, args: dict[str, Any]) -> Any:
+    def eval_raw_batch(self, args: bytes, n: int) -> bytes:
+        """
+        Evaluates the function on `n` _raw_ buffers of data laid out contiguously in
+        `args` (e.g., `array.tobytes()` of a numpy array with one row per input) and
+        returns the `n` resulting buffers, also laid out contiguously. If a row fails, the
+        error names the index of the row. Just like `eval_raw`, this is very error-prone.
+        """
+    def eval_batch(self, args: Iterable[dict[str, Any]]) -> list[Any]:
+        """
+        Runs this function on each of the given inputs and returns the list of results.
+        This is equivalent to calling `eval` in a loop, but cheaper. If a row fails, the
+        error names the index of the row.
+        """
+    def eval(
+        self, args: dict[str, Any], select: list[str] | None = None, numpy: bool = False
+    ) -> Any:
         """
         Runs this function on the given pythonized and returns the pythonized result back.
         This is very similar to `__call__`, but all arguments are passed on a single
         parameter. Under the hood, this is the function invoked by `__call__`, with some
         cosmetics applied.
+
+        List fields accept any iterable, including iterators and generators, which are
+        consumed lazily, without building an intermediate list. A `TypeError` is raised
+        if they yield the wrong number of items. Lists of scalars also accept one-dimensional
+        `float64` numpy arrays (or anything else exposing a buffer of doubles), which are
+        copied without converting each element. A `ValueError` is raised if the array is
+        not of the right shape.
+
+        If `numpy` is set, lists of scalars in the output are returned as `float64` numpy
+        arrays instead of Python lists.
+
+        If `select` is given, only the values at these paths of the output are converted
+        to Python and the result is a dictionary mapping each path to its value. Paths are
+        dot-separated field names or indices, e.g., `"heads.score"` or `"items.0"`.
         """
     def eval_json(self, args: str) -> str:
         """
@@ -291,7 +583,36 @@ class IndexedList:
     information on the usage of this class.
     """
 
-    def __getitem__(self, idx: Ref) -> Any: ...
+    def __getitem__(self, idx: Ref) -> Any:
+        """
+        Gets the element at `idx`. Like in Python lists, negative indices count from the
+        end, i.e., `-1` is the last element. This is the same as `get` with
+        `mode="negative_wrap"`.
+        """
+    def get(self, idx: Ref, mode: str = "strict") -> Any:
+        """
+        Gets the element at `idx`, interpreting the index according to `mode`:
+        - `"strict"`: the index is an offset from the start of the list.
+        - `"negative_wrap"`: negative indices count from the end, i.e., `-1` is the last
+            element (same as `[]`).
+        - `"modulo"`: the index wraps around the list, for cyclic access.
+
+        Fractional indices are truncated towards zero. Indices still out of bounds raise
+        an error.
+        """
+
+class MapRef:
+    """
+    A map from the input of a graph. See `fn.Layout.map_of` for more information. Maps
+    can only be read, by key.
+    """
+
+    def get(self, key: Ref | str) -> tuple[Ref, Any]:
+        """
+        Gets the value of the symbol `key`, as a tuple of a boolean, telling whether the
+        key is in the map, and the value, which is zeroed if the key is not present. This
+        takes time proportional to the number of entries in the map.
+        """
 
 def read_metadata(file: str, initialize: bool = True) -> Graph:
     """
@@ -339,11 +660,15 @@ def make(obj: Any) -> Ref:
     `fn.Ref`. Else, returns the passed object.
     """
 
-def input(name: str, layout: Layout) -> Any:
+def input(name: str, layout: Layout, pinnable: bool = False) -> Any:
     """
     Inserts a new field with a given name an a given layout (i.e., type) into the current
     graph.
     This is to be used with an `fn.Graph` as a with-as context manager.
+
+    Pinnable inputs are constant for the lifetime of a served function, but may vary
+    across deployments (e.g., feature flags). Their values can be pinned when compiling
+    the graph with `Graph.compile(pin=...)`.
     """
 
 def ret(val: Any) -> None:
@@ -356,10 +681,137 @@ def assert_(condition: Any, message: str) -> Ref:
     """
     Creates a new assertion of a given condition in the current graph. Assertions
     guarantee that a certain condition is met before computations can continue. If it is
-    not met, the function will finish with the supplied error message.
+    not met, the function will finish with the supplied error message, followed by the
+    `file:line` of the Python code that created the assertion.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+def feature_hash(value: Any, k: int, seed: int = 0) -> Ref:
+    """
+    Computes the bucket of `value` in `[0, k)` using the "hashing trick". The value can
+    be anything that can be depythonized into a graph value (symbols, scalars, tuples,
+    etc.). Buckets are deterministic for a given `seed`.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+def min(a: Any, b: Any) -> Ref:
+    """
+    The minimum of two scalars. If one of them is NaN, the other is returned, the same
+    as `numpy.fmin`.
+    """
+
+def max(a: Any, b: Any) -> Ref:
+    """
+    The maximum of two scalars. If one of them is NaN, the other is returned, the same
+    as `numpy.fmax`.
+    """
+
+def sigmoid(x: Any) -> Ref:
+    """
+    The logistic function of a scalar, `1 / (1 + exp(-x))`.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+def relu(x: Any) -> Ref:
+    """
+    The maximum of a scalar and zero. Unlike `max`, NaN is returned if `x` is NaN.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+def softmax(values: list[Any]) -> tuple[Ref, ...]:
+    """
+    The softmax of a non-empty list of scalars, `exp(x) / sum(exp(x))`, computed with a
+    single node. The maximum of the values is subtracted before exponentiation, so
+    large values don't overflow.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+def coalesce(candidates: list[tuple[Any, Any]], default: Any) -> Any:
+    """
+    Selects the value of the first `(present, value)` pair in `candidates` whose
+    `present` flag is true at runtime, or `default` if none is. All values must have the
+    same layout as `default`. This is useful to build fallback chains over optional
+    lookups.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+def select(index: Any, values: list[Any]) -> Ref:
+    """
+    Selects the element of `values` at position `index`, which may be an int or a float
+    (truncated). Evaluation fails if the index is out of range. All values must have the
+    same type. Values that are only used by one of the alternatives are only computed
+    when that alternative is selected, but do not rely on that: anything shared with the
+    rest of the graph is always computed.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+def where(mask: list[Any], values: list[Any], default: Any) -> tuple[Ref, ...]:
+    """
+    Returns, for each position, the element of `values` if the element of `mask` is true,
+    or `default` otherwise. The mask and the values must have the same length and all
+    values must have the same type as `default`. Use this instead of one `choose` per
+    element.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+def sum(values: list[Any]) -> Ref:
+    """
+    Sums a list of scalars with a single node, instead of a chain of additions. The
+    values are added pairwise, so the result may differ in the last bits from adding them
+    one after the other.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+def mean(values: list[Any]) -> Ref:
+    """
+    The mean of a non-empty list of scalars. See `sum`.
     This is to be used with an `fn.Graph` as a with-as context manager.
     """
 
+def dot(a: list[Any], b: list[Any]) -> Ref:
+    """
+    The dot product of two lists of scalars of the same length. See `sum`.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+def inscribe_pfunc(
+    name: str, func: ctypes._CFuncPtr, signature: list[str], returns: str
+) -> None:
+    """
+    Inscribes a new pure function under the given name, to be called with `call_pfunc`.
+    `func` is a ctypes foreign function (e.g., created with `ctypes.CFUNCTYPE`) taking
+    and returning the types named in `signature` and `returns`, which can be
+    `"scalar"`, `"bool"`, `"symbol"`, `"datetime"` or `"int"`. Scalars are passed as
+    `ctypes.c_double` and all other types as `ctypes.c_int64`; the argument and return
+    types of `func` are checked against these. `func` is kept alive for the rest of the
+    process. The function must be pure and never fail.
+
+    See also: `fn.scope`
+    """
+
+def call_pfunc(name: str, *args: Any) -> Ref | tuple[Ref, ...]:
+    """
+    Calls the pure function of the given name, builtin or inscribed, on the arguments.
+    Multi-output pure functions return a tuple.
+    This is to be used with an `fn.Graph` as a with-as context manager.
+    """
+
+class PFuncSnapshot:
+    """A snapshot of the inscribed pure functions. See `snapshot_pfuncs`."""
+
+def snapshot_pfuncs() -> PFuncSnapshot:
+    """
+    Takes a snapshot of the pure functions inscribed by the current thread, to be
+    restored with `restore_pfuncs`. Prefer using `fn.scope`.
+    """
+
+def restore_pfuncs(snapshot: PFuncSnapshot) -> None:
+    """
+    Removes all pure functions inscribed by the current thread since the snapshot was
+    taken. Pure functions inscribed by other threads are kept. Snapshots should be
+    restored in the reverse order they were taken. Prefer using `fn.scope`.
+    """
+
 class Layout:
     """
     A JYAFN layout. A layout bridges the world of binary data that the raw JYAFN function
@@ -388,16 +840,26 @@ class Layout:
         """Whether this layout is of the flavor "unit"."""
     def is_scalar(self) -> bool:
         """Whether this layout is of the flavor "scalar"."""
+    def is_int(self) -> bool:
+        """Whether this layout is of the flavor "int"."""
     def is_bool(self) -> bool:
         """Whether this layout is of the flavor "bool"."""
     def is_datetime(self) -> bool:
         """Whether this layout is of the flavor "datetime"."""
     def is_symbol(self) -> bool:
         """Whether this layout is of the flavor "symbol"."""
+    def is_enum(self) -> bool:
+        """Whether this layout is of the flavor "enum"."""
     def is_struct(self) -> bool:
         """Whether this layout is of the flavor "struct"."""
     def is_tuple(self) -> bool:
         """Whether this layout is of the flavor "tuple"."""
+    def is_optional(self) -> bool:
+        """Whether this layout is of the flavor "optional"."""
+    def is_bytes(self) -> bool:
+        """Whether this layout is of the flavor "bytes"."""
+    def is_map(self) -> bool:
+        """Whether this layout is of the flavor "map"."""
     def struct_keys(self) -> Optional[list[str]]:
         """
         Returns the field names of this struct layout, if it is of flavor "struct", else
@@ -410,6 +872,9 @@ class Layout:
     def scalar() -> Layout:
         """Returns a new layout of flavor "scalar"."""
     @staticmethod
+    def int() -> Layout:
+        """Returns a new layout of flavor "int"."""
+    @staticmethod
     def bool() -> Layout:
         """Returns a new layout of flavor "bool"."""
     @staticmethod
@@ -419,12 +884,45 @@ class Layout:
     def symbol() -> Layout:
         """Returns a new layout of flavor "symbol"."""
     @staticmethod
+    def enum_of(variants: list[str]) -> Layout:
+        """
+        Returns a new layout of flavor "enum", for strings restricted to the given
+        variants. Encoding any other string is an error. In graphs, values of this flavor
+        are the integer index of the string in `variants`.
+        """
+    @staticmethod
     def list_of(ty: Layout, size: int) -> Layout:
         """
         Returns a new layout of flavor "list" of elements of a given layout with length
         given by `size`.
         """
     @staticmethod
+    def bytes(n_bytes: int) -> Layout:
+        """
+        Returns a new layout of flavor "bytes", for opaque binary blobs of exactly
+        `n_bytes` bytes. Values are encoded from any bytes-like object and decoded to
+        `bytes`. In graphs, values of this flavor are a list of the integers holding 8
+        bytes each, in native byte order.
+        """
+    @staticmethod
+    def optional(inner: Layout) -> Layout:
+        """
+        Returns a new layout of flavor "optional", for values of the given layout that
+        may be absent. Absent values are encoded from and decoded to `None`. Inside
+        structs, missing fields of this flavor are also treated as absent. In graphs,
+        values of this flavor are a tuple of a boolean, telling whether the value is
+        present, and the value itself.
+        """
+    @staticmethod
+    def map_of(value: Layout) -> Layout:
+        """
+        Returns a new layout of flavor "map", for dictionaries with any string keys and
+        values of the given layout. Keys are only known when a value is encoded, so maps
+        can only be used in inputs. They cannot be returned, pinned or used in mappings
+        and the values of a map cannot contain maps. In graphs, values of this flavor
+        are a `fn.MapRef`.
+        """
+    @staticmethod
     def struct_of(fields: dict[str, Layout]) -> Layout:
         """
         Returns a new layout of flavor "struct", with the fields given by the supplied
@@ -500,9 +998,36 @@ def rgamma(x: Any) -> Ref: ...
 def digamma(x: Any) -> Ref: ...
 def erf(x: Any) -> Ref: ...
 def erfc(x: Any) -> Ref: ...
-def norm(x: Any) -> Ref: ...
-def norm_inv(x: Any) -> Ref: ...
+def norm(x: Any) -> Ref:
+    """The CDF of the standard normal distribution. Same as `norm_cdf(x)`."""
+def norm_cdf(x: Any) -> Ref:
+    """The CDF of the standard normal distribution."""
+def norm_pdf(x: Any) -> Ref:
+    """The PDF of the standard normal distribution."""
+def norm_inv(x: Any) -> Ref:
+    """The quantile function (inverse CDF) of the standard normal distribution."""
 def riemann_zeta(x: Any) -> Ref: ...
+def vector_norm(x: Any, p: float = 2) -> Ref:
+    """
+    Computes the vector norm of order `p` of a sequence of scalars, where `p` can be 1, 2
+    or `math.inf`. Other orders raise a `ValueError`. This is not `norm`, which is the CDF
+    of the standard normal distribution.
+    """
+def norm_l1(x: Any) -> Ref:
+    """
+    Computes the sum of the absolute values of a sequence of scalars. Same as
+    `vector_norm(x, 1)`.
+    """
+def norm_l2(x: Any) -> Ref:
+    """
+    Computes the Euclidean norm of a sequence of scalars, in a way that does not overflow
+    for large components. Same as `vector_norm(x, 2)`.
+    """
+def norm_inf(x: Any) -> Ref:
+    """
+    Computes the maximum of the absolute values of a sequence of scalars. This is NaN if
+    any of the values is NaN. Same as `vector_norm(x, math.inf)`.
+    """
 def is_nan(x: Any) -> Ref: ...
 def is_finite(x: Any) -> Ref: ...
 def is_infinite(x: Any) -> Ref: ...
@@ -510,6 +1035,9 @@ def powf(base: Any, exponent: Any) -> Ref:
     """Same as `fn.pow`"""
 
 def rem(x: Any, mod: Any) -> Ref: ...
+def log(x: Any, base: Any) -> Ref:
+    """The logarithm of `x` in the given `base`. See `ln` for the natural logarithm."""
+
 def beta(x: Any, y: Any) -> Ref: ...
 def logbeta(x: Any, y: Any) -> Ref: ...
 def gammainc(x: Any, y: Any) -> Ref: ...
@@ -518,6 +1046,18 @@ def gammac_inv(x: Any, y: Any) -> Ref: ...
 def besselj(x: Any, y: Any) -> Ref: ...
 def bessely(x: Any, y: Any) -> Ref: ...
 def besseli(x: Any, y: Any) -> Ref: ...
+def hypot(x: Any, y: Any) -> Ref:
+    """Computes `sqrt(x**2 + y**2)` without overflowing for large `x` or `y`."""
+
+def sincos(x: Any) -> tuple[Ref, Ref]:
+    """Computes `(sin(x), cos(x))` with a single function call."""
+
+def modf(x: Any) -> tuple[Ref, Ref]:
+    """
+    Splits `x` into its fractional and integral parts, `(frac, int)`, both with the same
+    sign as `x`.
+    """
+
 def timestamp(x: Any) -> Ref:
     """
     Creates a scalar ref, which contains a Unix timestamp in seconds, from this datetime ref.
@@ -568,9 +1108,18 @@ class LazyResourceCall:
     A lazy (a.k.a deferred) method call to a resource.
     """
 
-    def __call__(self, **kwds: Any) -> Any:
+    @property
+    def input_layout(self) -> Layout:
+        """The input layout of the method. Its fields are the arguments of the method."""
+    @property
+    def output_layout(self) -> Layout:
+        """The output layout of the method."""
+    def __call__(self, *args: Any, **kwds: Any) -> Any:
         """
         Actually builds the JAYFN resource method call and binds it to the current graph.
+        Arguments are matched to the fields of `input_layout`: positional arguments in
+        order and keyword arguments by name. Missing, extra or repeated arguments raise a
+        `TypeError`.
         """
 
 class Extension:
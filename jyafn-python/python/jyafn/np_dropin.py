@@ -280,6 +280,12 @@ class linalg:
             )
         )
 
+    @staticmethod
+    def norm(x: np.ndarray, ord=None):
+        if len(x.shape) != 1:
+            raise Exception(f"Only vector norms are supported, got shape {x.shape}")
+        return fn.vector_norm(x.tolist(), 2 if ord is None else ord)
+
 
 DROP_IN: dict[np.ufunc, np.ufunc] = {
     np.equal: equal,
@@ -315,4 +321,5 @@ DROP_IN: dict[np.ufunc, np.ufunc] = {
     np.linalg.det: linalg.det,
     np.linalg.cholesky: linalg.cholesky,
     np.linalg.solve: linalg.solve,
+    np.linalg.norm: linalg.norm,
 }
@@ -83,6 +83,14 @@ impl Function {
         Layout(self.inner().output_layout().clone())
     }
 
+    fn input_schema_json(&self) -> String {
+        self.inner().input_schema_json().to_string()
+    }
+
+    fn output_schema_json(&self) -> String {
+        self.inner().output_schema_json().to_string()
+    }
+
     #[getter]
     fn fn_ptr(&self) -> usize {
         self.inner().fn_ptr() as *const () as usize
@@ -151,6 +159,37 @@ impl Function {
         self.inner().graph().metadata().clone()
     }
 
+    #[getter]
+    fn opt_stats(&self) -> Option<HashMap<String, usize>> {
+        self.inner().opt_stats().map(|stats| {
+            serde_json::from_value(serde_json::to_value(stats).expect("can always serialize"))
+                .expect("stats are a flat map of counts")
+        })
+    }
+
+    #[getter]
+    fn source(&self) -> Option<&str> {
+        self.inner().graph().source()
+    }
+
+    #[getter]
+    fn symbols(&self) -> Vec<String> {
+        self.inner().graph().symbols().as_vec()
+    }
+
+    fn error_names(&self) -> Vec<String> {
+        self.inner().graph().errors().to_vec()
+    }
+
+    fn symbol_index(&self, name: &str) -> Option<u64> {
+        self.inner().graph().symbols().lookup(name)
+    }
+
+    #[pyo3(signature = (dry_run=true))]
+    fn warm_up(&self, dry_run: bool) {
+        self.inner().warm_up(dry_run)
+    }
+
     fn eval_raw(&self, args: &[u8]) -> PyResult<Vec<u8>> {
         Ok(self
             .inner()
@@ -159,15 +198,67 @@ impl Function {
             .map(|o| o.into_vec())?)
     }
 
-    fn eval(&self, val: &Bound<'_, PyAny>) -> PyResult<PyObject> {
-        let outcome = self.inner().eval_with_decoder(
-            &crate::layout::Obj(val.clone()),
-            crate::layout::PyDecoder(val.py()),
-        );
+    fn eval_raw_batch<'py>(
+        &self,
+        py: Python<'py>,
+        args: &[u8],
+        n: usize,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let outputs = self.inner().eval_raw_batch(args, n).map_err(ToPyErr)?;
+        Ok(PyBytes::new_bound(py, &outputs))
+    }
+
+    fn eval_batch(&self, vals: &Bound<'_, PyAny>) -> PyResult<Vec<PyObject>> {
+        let py = vals.py();
+        let vals = vals
+            .iter()?
+            .map(|val| Ok(crate::layout::Obj(val?)))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        Ok(self
+            .inner()
+            .eval_batch_with_decoder(&vals, crate::layout::PyDecoder::new(py))
+            .map_err(ToPyErr)?)
+    }
+
+    #[pyo3(signature = (val, select=None, numpy=false))]
+    fn eval(
+        &self,
+        val: &Bound<'_, PyAny>,
+        select: Option<Vec<String>>,
+        numpy: bool,
+    ) -> PyResult<PyObject> {
+        let py = val.py();
+        let decoder = if numpy {
+            crate::layout::PyDecoder::with_numpy(py)?
+        } else {
+            crate::layout::PyDecoder::new(py)
+        };
+        let outcome = if let Some(select) = select {
+            let paths = select
+                .iter()
+                .map(|path| rust::layout::OutputPath::parse(path))
+                .collect::<Vec<_>>();
+            let decoder =
+                rust::layout::SelectDecoder::new(self.inner().output_layout(), &paths, decoder)
+                    .map_err(ToPyErr)?;
+            self.inner()
+                .eval_with_decoder(&crate::layout::Obj(val.clone()), decoder)
+                .map(|selected| {
+                    let dict = PyDict::new_bound(py);
+                    for (path, value) in select.into_iter().zip(selected) {
+                        dict.set_item(path, value).expect("can always set item");
+                    }
+                    dict.to_object(py)
+                })
+        } else {
+            self.inner()
+                .eval_with_decoder(&crate::layout::Obj(val.clone()), decoder)
+        };
 
         if let Err(rust::Error::EncodeError(inner)) = &outcome {
             if let Some(err) = inner.downcast_ref::<PyErr>() {
-                return Err(err.clone_ref(val.py()));
+                return Err(err.clone_ref(py));
             }
         }
 
@@ -201,7 +292,7 @@ impl Function {
             }
         }
 
-        self.eval(&kwargs)
+        self.eval(&kwargs, None, false)
     }
 
     #[pyo3(signature = (json, pretty=None))]
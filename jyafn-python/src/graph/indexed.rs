@@ -61,11 +61,28 @@ impl IndexedList {
     }
 
     fn __getitem__(&self, py: Python, idx: Ref) -> PyResult<Py<PyAny>> {
+        self.get(py, idx, "negative_wrap")
+    }
+
+    #[pyo3(signature = (idx, mode="strict"))]
+    fn get(&self, py: Python, idx: Ref, mode: &str) -> PyResult<Py<PyAny>> {
+        let mode = match mode {
+            "strict" => rust::op::IndexMode::Strict,
+            "negative_wrap" => rust::op::IndexMode::NegativeWrap,
+            "modulo" => rust::op::IndexMode::Modulo,
+            mode => {
+                return Err(exceptions::PyValueError::new_err(format!(
+                    "unsupported index mode {mode:?}: expected \"strict\", \"negative_wrap\" \
+                    or \"modulo\""
+                )))
+            }
+        };
+
         let indexed = try_with_current(|g| {
             let indexed = self
                 .lists
                 .iter()
-                .map(|list| list.get(g, idx.0))
+                .map(|list| list.get_with_mode(g, idx.0, mode))
                 .collect::<Result<Vec<_>, _>>()
                 .map_err(ToPyErr)?;
 
@@ -0,0 +1,29 @@
+use pyo3::prelude::*;
+
+use crate::const_from_py;
+
+use super::{pythonize_ref_value, try_with_current, Ref, ToPyErr};
+
+/// A map from the input of the graph, which can only be read by key.
+#[pyclass(module = "jyafn")]
+#[derive(Clone)]
+pub struct MapRef(pub(crate) rust::layout::RefValue);
+
+#[pymethods]
+impl MapRef {
+    fn __repr__(&self) -> String {
+        format!("MapRef({})", self.0)
+    }
+
+    fn get(&self, py: Python, key: &Bound<PyAny>) -> PyResult<PyObject> {
+        let value = try_with_current(|g| {
+            let key = match key.extract::<Ref>() {
+                Ok(key) => key,
+                Err(_) => const_from_py(g, key)?,
+            };
+            Ok(g.map_get(&self.0, key.0).map_err(ToPyErr)?)
+        })?;
+
+        pythonize_ref_value(py, value)
+    }
+}
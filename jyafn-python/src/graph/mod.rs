@@ -1,17 +1,20 @@
 mod indexed;
+mod map_ref;
 mod r#ref;
 
 pub use indexed::IndexedList;
+pub use map_ref::MapRef;
 pub use r#ref::{make, Ref};
 
 use pyo3::exceptions;
 use pyo3::prelude::*;
 use pyo3::types::{PyBytes, PyDict, PyTuple};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
-use super::layout::Layout;
-use super::{depythonize_ref_value, pythonize_ref_value, Function, ToPyErr};
+use super::layout::{Layout, Obj};
+use super::{depythonize_ref_value, pythonize_ref_value, Function, ToPyErr, Type};
 
 thread_local! {
     pub static CONTEXT: RefCell<Vec<Graph>> =
@@ -75,10 +78,11 @@ impl Graph {
         });
     }
 
-    #[pyo3(signature = (*args, **kwargs))]
+    #[pyo3(signature = (*args, inline = false, **kwargs))]
     fn __call__(
         &self,
         args: &Bound<'_, PyTuple>,
+        inline: bool,
         kwargs: Option<&Bound<'_, PyDict>>,
     ) -> PyResult<PyObject> {
         if Arc::ptr_eq(&self.0, &current_graph()?.0) {
@@ -111,8 +115,13 @@ impl Graph {
             }
 
             let kwargs_ref = depythonize_ref_value(g, &kwargs)?;
-            let graph_id = g.insert_subgraph(graph.clone());
-            let output = g.call_graph(graph_id, kwargs_ref).map_err(ToPyErr)?;
+            let graph_id = g.insert_subgraph(graph.clone()).map_err(ToPyErr)?;
+            let output = if inline {
+                g.inline_subgraph(graph_id, kwargs_ref)
+            } else {
+                g.call_graph(graph_id, kwargs_ref)
+            }
+            .map_err(ToPyErr)?;
 
             pythonize_ref_value(args.py(), output)
         })
@@ -122,6 +131,34 @@ impl Graph {
         get_size::GetSize::get_size(&*self.0.lock().expect("poisoned"))
     }
 
+    fn size_breakdown(&self) -> HashMap<&'static str, usize> {
+        let breakdown = self.0.lock().expect("poisoned").size_breakdown();
+        HashMap::from([
+            ("nodes", breakdown.nodes),
+            ("mappings", breakdown.mappings),
+            ("resources", breakdown.resources),
+            ("subgraphs", breakdown.subgraphs),
+            ("symbols", breakdown.symbols),
+            ("other", breakdown.other),
+        ])
+    }
+
+    fn diff(&self, py: Python, other: &Graph) -> PyResult<PyObject> {
+        let diff = if Arc::ptr_eq(&self.0, &other.0) {
+            rust::GraphDiff::default()
+        } else {
+            let graph = self.0.lock().expect("poisoned");
+            let other = other.0.lock().expect("poisoned");
+            graph.diff(&other)
+        };
+
+        let json = serde_json::to_string(&diff).expect("can always serialize");
+        Ok(py
+            .import_bound("json")?
+            .call_method1("loads", (json,))?
+            .into())
+    }
+
     pub fn __getstate__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
         self.dump(py)
     }
@@ -163,11 +200,25 @@ impl Graph {
         self.0.lock().expect("poisoned").to_json()
     }
 
+    pub fn to_dot(&self) -> String {
+        self.0.lock().expect("poisoned").to_dot()
+    }
+
     #[getter]
     pub fn name(&self) -> String {
         self.0.lock().expect("poisoned").name().to_string()
     }
 
+    #[getter]
+    pub fn canonical_name(&self) -> String {
+        self.0.lock().expect("poisoned").canonical_name()
+    }
+
+    #[getter]
+    pub fn symbols(&self) -> Vec<String> {
+        self.0.lock().expect("poisoned").symbols().as_vec()
+    }
+
     #[getter]
     pub fn input_layout(&self) -> Layout {
         Layout(rust::layout::Layout::Struct(
@@ -175,6 +226,18 @@ impl Graph {
         ))
     }
 
+    #[getter]
+    pub fn inputs(&self) -> Vec<(String, Layout)> {
+        self.0
+            .lock()
+            .expect("poisoned")
+            .input_layout()
+            .fields()
+            .iter()
+            .map(|(name, layout)| (name.clone(), Layout(layout.clone())))
+            .collect()
+    }
+
     #[getter]
     pub fn output_layout(&self) -> Layout {
         Layout(self.0.lock().expect("poisoned").output_layout().clone())
@@ -191,6 +254,24 @@ impl Graph {
         Ok(dict)
     }
 
+    #[getter]
+    pub fn nodes(&self) -> Vec<NodeInfo> {
+        self.0
+            .lock()
+            .expect("poisoned")
+            .nodes()
+            .iter()
+            .enumerate()
+            .map(|(id, node)| NodeInfo {
+                id,
+                op: node.op_name().to_string(),
+                op_debug: format!("{:?}", node.op()),
+                args: node.args().iter().copied().map(Ref).collect(),
+                ty: Type(node.ty()),
+            })
+            .collect()
+    }
+
     pub fn set_metadata(&self, key: String, value: String) {
         self.0
             .lock()
@@ -199,6 +280,89 @@ impl Graph {
             .insert(key, value);
     }
 
+    #[getter]
+    pub fn source(&self) -> Option<String> {
+        self.0
+            .lock()
+            .expect("poisoned")
+            .source()
+            .map(str::to_string)
+    }
+
+    pub fn set_source(&self, source: String) -> PyResult<()> {
+        Ok(self
+            .0
+            .lock()
+            .expect("poisoned")
+            .set_source(source)
+            .map_err(ToPyErr)?)
+    }
+
+    #[pyo3(signature = (name, min=None, max=None))]
+    pub fn constrain_input(&self, name: &str, min: Option<f64>, max: Option<f64>) -> PyResult<()> {
+        Ok(self
+            .0
+            .lock()
+            .expect("poisoned")
+            .constrain_input(name, min, max)
+            .map_err(ToPyErr)?)
+    }
+
+    fn rename_input(&self, old: &str, new: &str) -> PyResult<()> {
+        Ok(self
+            .0
+            .lock()
+            .expect("poisoned")
+            .rename_input(old, new)
+            .map_err(ToPyErr)?)
+    }
+
+    fn bind_input(&self, name: &str, value: &Bound<PyAny>) -> PyResult<()> {
+        Ok(self
+            .0
+            .lock()
+            .expect("poisoned")
+            .bind_input(name, &Obj(value.clone()))
+            .map_err(ToPyErr)?)
+    }
+
+    fn rename_output_field(&self, old: &str, new: &str) -> PyResult<()> {
+        Ok(self
+            .0
+            .lock()
+            .expect("poisoned")
+            .rename_output_field(old, new)
+            .map_err(ToPyErr)?)
+    }
+
+    fn prune_unused_inputs(&self) -> Vec<String> {
+        self.0.lock().expect("poisoned").prune_unused_inputs()
+    }
+
+    /// Creates a new graph feeding the output of this graph into `next`. The output of
+    /// this graph must be a struct containing all the inputs of `next`, matched by name.
+    fn pipe(&self, next: &Graph) -> PyResult<Graph> {
+        let this = self.0.lock().expect("poisoned").clone();
+        let next = next.0.lock().expect("poisoned").clone();
+        let mut piped = rust::Graph::new();
+
+        let input = rust::layout::RefValue::Struct(
+            this.input_layout()
+                .0
+                .iter()
+                .map(|(name, layout)| (name.clone(), piped.input(name.clone(), layout.clone())))
+                .collect(),
+        );
+        let graph_id = piped.insert_subgraph(this).map_err(ToPyErr)?;
+        let output = piped.call_graph(graph_id, input).map_err(ToPyErr)?;
+        let output = piped.pipe(&next, output).map_err(ToPyErr)?;
+        piped
+            .output(output, next.output_layout().clone())
+            .map_err(ToPyErr)?;
+
+        Ok(Graph(Arc::new(Mutex::new(piped))))
+    }
+
     fn render(&self) -> PyResult<String> {
         Ok(self
             .0
@@ -209,6 +373,16 @@ impl Graph {
             .to_string())
     }
 
+    #[pyo3(signature = (annotated=true))]
+    fn render_ir(&self, annotated: bool) -> PyResult<String> {
+        let graph = self.0.lock().expect("poisoned");
+        if annotated {
+            Ok(graph.render_annotated().map_err(ToPyErr)?)
+        } else {
+            Ok(graph.render().map_err(ToPyErr)?.to_string())
+        }
+    }
+
     fn render_assembly(&self) -> PyResult<String> {
         Ok(self
             .0
@@ -218,16 +392,110 @@ impl Graph {
             .map_err(ToPyErr)?)
     }
 
-    fn compile(&self) -> PyResult<Function> {
+    fn verify(&self) -> PyResult<()> {
+        Ok(self.0.lock().expect("poisoned").verify().map_err(ToPyErr)?)
+    }
+
+    #[pyo3(signature = (cache=None, pin=None))]
+    fn compile(
+        &self,
+        cache: Option<&CompileCache>,
+        pin: Option<&Bound<PyDict>>,
+    ) -> PyResult<Function> {
+        let options = rust::CompileOptions {
+            cache: cache.map(|cache| cache.0.clone()),
+            ..rust::CompileOptions::default()
+        };
+        let graph = self.0.lock().expect("poisoned");
+
+        let inner = if let Some(pin) = pin {
+            let values = pin
+                .iter()
+                .map(|(name, value)| Ok((name.extract::<String>()?, Obj(value))))
+                .collect::<PyResult<Vec<_>>>()?;
+            graph
+                .pin(values.iter().map(|(name, value)| (name.as_str(), value)))
+                .and_then(|pinned| pinned.compile_with("run", &options))
+        } else {
+            graph.compile_with("run", &options)
+        };
+
         Ok(Function {
-            inner: Some(
-                self.0
-                    .lock()
-                    .expect("poisoned")
-                    .compile()
-                    .map_err(ToPyErr)?,
-            ),
+            inner: Some(inner.map_err(ToPyErr)?),
             original: None,
         })
     }
 }
+
+/// A cache reusing the assembly of the regions of graphs that did not change between
+/// compilations.
+#[pyclass(module = "jyafn", frozen)]
+#[derive(Clone, Default)]
+pub struct CompileCache(Arc<rust::CompileCache>);
+
+#[pymethods]
+impl CompileCache {
+    #[new]
+    fn new() -> CompileCache {
+        CompileCache::default()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CompileCache(len={}, hits={}, misses={})",
+            self.0.len(),
+            self.0.hits(),
+            self.0.misses()
+        )
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    #[getter]
+    fn hits(&self) -> usize {
+        self.0.hits()
+    }
+
+    #[getter]
+    fn misses(&self) -> usize {
+        self.0.misses()
+    }
+
+    fn clear(&self) {
+        self.0.clear()
+    }
+}
+
+/// A read-only snapshot of a node of a graph.
+#[pyclass(module = "jyafn", frozen)]
+#[derive(Clone)]
+pub struct NodeInfo {
+    #[pyo3(get)]
+    id: usize,
+    #[pyo3(get)]
+    op: String,
+    op_debug: String,
+    #[pyo3(get)]
+    args: Vec<Ref>,
+    #[pyo3(get, name = "type")]
+    ty: Type,
+}
+
+#[pymethods]
+impl NodeInfo {
+    fn __repr__(&self) -> String {
+        format!(
+            "NodeInfo(id={}, op={}, args=[{}], type={})",
+            self.id,
+            self.op_debug,
+            self.args
+                .iter()
+                .map(|arg| arg.0.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.ty.0,
+        )
+    }
+}
@@ -1,5 +1,6 @@
 use pyo3::exceptions;
 use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyInt, PyTuple};
 
 use crate::r#const;
 
@@ -21,6 +22,124 @@ impl Ref {
     }
 }
 
+impl Ref {
+    /// Inserts the jyafn equivalent of the numpy ufunc of the given name, called on the
+    /// supplied arguments. Returns `None` if there is no such equivalent.
+    fn apply_ufunc(name: &str, args: &[rust::Ref]) -> Option<PyResult<Ref>> {
+        let call =
+            |pfunc: &str| insert_in_current(rust::op::Call(pfunc.to_string()), args.to_vec());
+        let choose_gt = |a: rust::Ref, b: rust::Ref, if_gt: rust::Ref, if_le: rust::Ref| {
+            let gt = insert_in_current(rust::op::Gt, vec![a, b])?;
+            insert_in_current(rust::op::Choose, vec![gt.0, if_gt, if_le])
+        };
+
+        Some(match (name, args) {
+            // Unary:
+            ("positive", &[a]) => Ok(Ref(a)),
+            ("negative", &[a]) => insert_in_current(rust::op::Neg, vec![a]),
+            ("absolute" | "fabs", &[a]) => insert_in_current(rust::op::Abs, vec![a]),
+            ("square", &[a]) => insert_in_current(rust::op::Mul, vec![a, a]),
+            ("logical_not", &[a]) => insert_in_current(rust::op::Not, vec![a]),
+            ("floor", &[a]) => insert_in_current(rust::op::Floor, vec![a]),
+            ("ceil", &[a]) => insert_in_current(rust::op::Ceil, vec![a]),
+            ("trunc", &[a]) => insert_in_current(rust::op::Trunc, vec![a]),
+            ("rint", &[a]) => insert_in_current(rust::op::Round, vec![a]),
+            ("sqrt" | "exp" | "sin" | "cos" | "tan" | "sinh" | "cosh" | "tanh", &[_]) => call(name),
+            ("log", &[_]) => call("ln"),
+            ("expm1", &[_]) => call("exp_m1"),
+            ("log1p", &[_]) => call("ln_1p"),
+            ("arcsin", &[_]) => call("asin"),
+            ("arccos", &[_]) => call("acos"),
+            ("arctan", &[_]) => call("atan"),
+            ("arcsinh", &[_]) => call("asinh"),
+            ("arccosh", &[_]) => call("acosh"),
+            ("arctanh", &[_]) => call("atanh"),
+            ("isnan", &[a]) => insert_in_current(rust::op::IsNan, vec![a]),
+            ("isfinite", &[a]) => insert_in_current(rust::op::IsFinite, vec![a]),
+            ("isinf", &[_]) => call("is_infinite"),
+            // Binary:
+            ("add", &[a, b]) => insert_in_current(rust::op::Add, vec![a, b]),
+            ("subtract", &[a, b]) => insert_in_current(rust::op::Sub, vec![a, b]),
+            ("multiply", &[a, b]) => insert_in_current(rust::op::Mul, vec![a, b]),
+            ("divide" | "true_divide", &[a, b]) => insert_in_current(rust::op::Div, vec![a, b]),
+            ("floor_divide", &[a, b]) => floor_div(a, b),
+            ("remainder" | "mod", &[a, b]) => insert_in_current(rust::op::FloorRem, vec![a, b]),
+            ("power", &[_, _]) => call("powf"),
+            ("arctan2", &[_, _]) => call("atan2"),
+            ("hypot", &[_, _]) => call("hypot"),
+            ("maximum", &[a, b]) => choose_gt(a, b, a, b).and_then(|max| nan_if_any(a, b, max)),
+            ("minimum", &[a, b]) => choose_gt(a, b, b, a).and_then(|min| nan_if_any(a, b, min)),
+            ("fmax", &[a, b]) => insert_in_current(rust::op::Max, vec![a, b]),
+            ("fmin", &[a, b]) => insert_in_current(rust::op::Min, vec![a, b]),
+            ("equal", &[a, b]) => insert_in_current(rust::op::Eq(None), vec![a, b]),
+            ("not_equal", &[a, b]) => insert_in_current(rust::op::Eq(None), vec![a, b])
+                .and_then(|eq| insert_in_current(rust::op::Not, vec![eq.0])),
+            ("less", &[a, b]) => insert_in_current(rust::op::Lt, vec![a, b]),
+            ("less_equal", &[a, b]) => insert_in_current(rust::op::Le, vec![a, b]),
+            ("greater", &[a, b]) => insert_in_current(rust::op::Gt, vec![a, b]),
+            ("greater_equal", &[a, b]) => insert_in_current(rust::op::Ge, vec![a, b]),
+            ("logical_and", &[a, b]) => insert_in_current(rust::op::And, vec![a, b]),
+            ("logical_or", &[a, b]) => insert_in_current(rust::op::Or, vec![a, b]),
+            _ => return None,
+        })
+    }
+}
+
+/// Inserts `isnan(a) | isnan(b) ? nan : otherwise`, so that NaNs propagate the same way
+/// as in numpy's `maximum` and `minimum`, whichever the operand they are in.
+fn nan_if_any(a: rust::Ref, b: rust::Ref, otherwise: Ref) -> PyResult<Ref> {
+    let a_is_nan = insert_in_current(rust::op::IsNan, vec![a])?;
+    let b_is_nan = insert_in_current(rust::op::IsNan, vec![b])?;
+    let any_is_nan = insert_in_current(rust::op::Or, vec![a_is_nan.0, b_is_nan.0])?;
+    insert_in_current(
+        rust::op::Choose,
+        vec![any_is_nan.0, rust::Ref::from(f64::NAN), otherwise.0],
+    )
+}
+
+/// Inserts `a // b`, computed from `a % b` the same way as Python does, so that
+/// `a == (a // b) * b + a % b` holds for the floored remainder of [`rust::op::FloorRem`].
+fn floor_div(a: rust::Ref, b: rust::Ref) -> PyResult<Ref> {
+    let rem = insert_in_current(rust::op::FloorRem, vec![a, b])?;
+    let multiple = insert_in_current(rust::op::Sub, vec![a, rem.0])?;
+    let divided = insert_in_current(rust::op::Div, vec![multiple.0, b])?;
+    // `divided` is already very close to an integer.
+    insert_in_current(rust::op::Round, vec![divided.0])
+}
+
+impl Ref {
+    /// Inserts `self op other` (or `other op self`, if `reflected`). The integer version
+    /// of the operation is used if both operands are integers. A Python `int` operand is
+    /// taken as an integer if `self` is an integer.
+    fn arithmetic<F: rust::Op, I: rust::Op>(
+        &self,
+        other: &Bound<PyAny>,
+        float_op: F,
+        int_op: I,
+        reflected: bool,
+    ) -> PyResult<Ref> {
+        let is_int = |r: &Ref| try_with_current(|g| Ok(g.type_of(r.0) == rust::Type::Int));
+        let self_is_int = is_int(self)?;
+        let other = match other.extract::<i64>() {
+            Ok(int) if self_is_int && !other.is_instance_of::<PyBool>() => {
+                Ref(rust::Ref::from(int))
+            }
+            _ => Ref::make(other)?,
+        };
+        let args = if reflected {
+            vec![other.0, self.0]
+        } else {
+            vec![self.0, other.0]
+        };
+
+        if self_is_int && is_int(&other)? {
+            insert_in_current(int_op, args)
+        } else {
+            insert_in_current(float_op, args)
+        }
+    }
+}
+
 #[pyfunction]
 pub fn make(obj: &Bound<PyAny>) -> PyResult<Ref> {
     Ref::make(obj)
@@ -47,33 +166,27 @@ impl Ref {
     }
 
     fn __add__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
-        let other = Ref::make(other)?;
-        insert_in_current(rust::op::Add, vec![self.0, other.0])
+        self.arithmetic(other, rust::op::Add, rust::op::IntAdd, false)
     }
 
     fn __radd__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
-        let other = Ref::make(other)?;
-        insert_in_current(rust::op::Add, vec![other.0, self.0])
+        self.arithmetic(other, rust::op::Add, rust::op::IntAdd, true)
     }
 
     fn __sub__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
-        let other = Ref::make(other)?;
-        insert_in_current(rust::op::Sub, vec![self.0, other.0])
+        self.arithmetic(other, rust::op::Sub, rust::op::IntSub, false)
     }
 
     fn __rsub__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
-        let other = Ref::make(other)?;
-        insert_in_current(rust::op::Sub, vec![other.0, self.0])
+        self.arithmetic(other, rust::op::Sub, rust::op::IntSub, true)
     }
 
     fn __mul__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
-        let other = Ref::make(other)?;
-        insert_in_current(rust::op::Mul, vec![self.0, other.0])
+        self.arithmetic(other, rust::op::Mul, rust::op::IntMul, false)
     }
 
     fn __rmul__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
-        let other = Ref::make(other)?;
-        insert_in_current(rust::op::Mul, vec![other.0, self.0])
+        self.arithmetic(other, rust::op::Mul, rust::op::IntMul, true)
     }
 
     fn __truediv__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
@@ -88,28 +201,36 @@ impl Ref {
 
     fn __floordiv__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
         let other = Ref::make(other)?;
-        let divided = insert_in_current(rust::op::Div, vec![self.0, other.0])?;
-        insert_in_current(rust::op::Call("floor".to_string()), vec![divided.0])
+        floor_div(self.0, other.0)
     }
 
     fn __rfloordiv__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
         let other = Ref::make(other)?;
-        let divided = insert_in_current(rust::op::Div, vec![other.0, self.0])?;
-        insert_in_current(rust::op::Call("floor".to_string()), vec![divided.0])
+        floor_div(other.0, self.0)
     }
 
     fn __mod__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
         let other = Ref::make(other)?;
-        insert_in_current(rust::op::Rem, vec![self.0, other.0])
+        insert_in_current(rust::op::FloorRem, vec![self.0, other.0])
     }
 
     fn __rmod__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
         let other = Ref::make(other)?;
-        insert_in_current(rust::op::Rem, vec![other.0, self.0])
+        insert_in_current(rust::op::FloorRem, vec![other.0, self.0])
     }
 
     fn __neg__(&self) -> PyResult<Ref> {
-        insert_in_current(rust::op::Neg, vec![self.0])
+        try_with_current(|g| {
+            if g.type_of(self.0) == rust::Type::Bool {
+                return Err(exceptions::PyTypeError::new_err(
+                    "cannot negate a bool: use ~ / .not_() to invert a bool",
+                ));
+            }
+
+            Ok(Ref(g
+                .insert(rust::op::Neg, vec![self.0])
+                .map_err(ToPyErr)?))
+        })
     }
 
     fn __pos__(&self) -> Ref {
@@ -117,6 +238,13 @@ impl Ref {
     }
 
     fn __pow__(&self, exponent: &Bound<PyAny>, _modulo: &Bound<PyAny>) -> PyResult<Ref> {
+        // Integer literals are cheaper to compute with repeated multiplication.
+        if exponent.is_instance_of::<PyInt>() && !exponent.is_instance_of::<PyBool>() {
+            if let Ok(exponent) = exponent.extract::<i32>() {
+                return insert_in_current(rust::op::Powi(exponent), vec![self.0]);
+            }
+        }
+
         let exponent = Ref::make(exponent)?;
         insert_in_current(rust::op::Call("powf".to_string()), vec![self.0, exponent.0])
     }
@@ -131,19 +259,19 @@ impl Ref {
     }
 
     fn __floor__(&self) -> PyResult<Ref> {
-        insert_in_current(rust::op::Call("floor".to_string()), vec![self.0])
+        insert_in_current(rust::op::Floor, vec![self.0])
     }
 
     fn __ceil__(&self) -> PyResult<Ref> {
-        insert_in_current(rust::op::Call("ceil".to_string()), vec![self.0])
+        insert_in_current(rust::op::Ceil, vec![self.0])
     }
 
     fn __trunc__(&self) -> PyResult<Ref> {
-        insert_in_current(rust::op::Call("trunc".to_string()), vec![self.0])
+        insert_in_current(rust::op::Trunc, vec![self.0])
     }
 
     fn __round__(&self) -> PyResult<Ref> {
-        insert_in_current(rust::op::Call("round".to_string()), vec![self.0])
+        insert_in_current(rust::op::Round, vec![self.0])
     }
 
     fn __eq__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
@@ -183,6 +311,10 @@ impl Ref {
         insert_in_current(rust::op::Not, vec![self.0])
     }
 
+    fn not_(&self) -> PyResult<Ref> {
+        insert_in_current(rust::op::Not, vec![self.0])
+    }
+
     fn __and__(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
         let other = Ref::make(other)?;
         insert_in_current(rust::op::And, vec![self.0, other.0])
@@ -244,7 +376,106 @@ impl Ref {
     }
 
     fn to_float(&self) -> PyResult<Ref> {
-        insert_in_current(rust::op::ToFloat, vec![self.0])
+        try_with_current(|g| {
+            let inserted = if g.type_of(self.0) == rust::Type::Int {
+                g.insert(rust::op::IntToFloat, vec![self.0])
+            } else {
+                g.insert(rust::op::ToFloat, vec![self.0])
+            };
+
+            Ok(Ref(inserted.map_err(ToPyErr)?))
+        })
+    }
+
+    fn to_int(&self) -> PyResult<Ref> {
+        insert_in_current(rust::op::ToInt, vec![self.0])
+    }
+
+    fn min(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
+        let other = Ref::make(other)?;
+        insert_in_current(rust::op::Min, vec![self.0, other.0])
+    }
+
+    fn max(&self, other: &Bound<PyAny>) -> PyResult<Ref> {
+        let other = Ref::make(other)?;
+        insert_in_current(rust::op::Max, vec![self.0, other.0])
+    }
+
+    fn isnan(&self) -> PyResult<Ref> {
+        insert_in_current(rust::op::IsNan, vec![self.0])
+    }
+
+    fn isfinite(&self) -> PyResult<Ref> {
+        insert_in_current(rust::op::IsFinite, vec![self.0])
+    }
+
+    fn div_or(&self, den: &Bound<PyAny>, default: &Bound<PyAny>) -> PyResult<Ref> {
+        let den = Ref::make(den)?;
+        let default = Ref::make(default)?;
+        insert_in_current(rust::op::DivOr, vec![self.0, den.0, default.0])
+    }
+
+    fn clamp(&self, min: &Bound<PyAny>, max: &Bound<PyAny>) -> PyResult<Ref> {
+        let min = Ref::make(min)?;
+        let max = Ref::make(max)?;
+        insert_in_current(rust::op::Clamp, vec![self.0, min.0, max.0])
+    }
+
+    /// Dispatches numpy ufuncs called directly on refs (e.g., `np.exp(x)`) to the
+    /// corresponding jyafn operations. Ufuncs without a jyafn equivalent, with arrays as
+    /// arguments or called with methods other than `__call__` fall back to the numpy
+    /// object implementation, which calls the methods of `Ref` element-wise.
+    #[pyo3(signature = (ufunc, method, *inputs, **kwargs))]
+    fn __array_ufunc__(
+        &self,
+        ufunc: &Bound<PyAny>,
+        method: &str,
+        inputs: &Bound<PyTuple>,
+        kwargs: Option<&Bound<PyDict>>,
+    ) -> PyResult<PyObject> {
+        let py = ufunc.py();
+        let name = ufunc.getattr("__name__")?.extract::<String>()?;
+        let numpy = py.import_bound("numpy")?;
+        let ndarray = numpy.getattr("ndarray")?;
+
+        let has_kwargs = kwargs.map(|kwargs| !kwargs.is_empty()).unwrap_or(false);
+        let has_arrays = inputs
+            .iter()
+            .map(|input| input.is_instance(&ndarray))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_iter()
+            .any(|is_array| is_array);
+
+        if method == "__call__" && !has_kwargs && !has_arrays {
+            let args = inputs
+                .iter()
+                .map(|input| Ok(Ref::make(&input)?.0))
+                .collect::<PyResult<Vec<_>>>()?;
+
+            if let Some(applied) = Ref::apply_ufunc(&name, &args) {
+                return Ok(applied?.into_py(py));
+            }
+        }
+
+        // Fallback: wrapping refs in object arrays disables this method and makes numpy
+        // use its object implementation.
+        let object_kwargs = PyDict::new_bound(py);
+        object_kwargs.set_item("dtype", numpy.getattr("object_")?)?;
+        let inputs = inputs
+            .iter()
+            .map(|input| {
+                if input.is_instance_of::<Ref>() {
+                    numpy.call_method("array", (input,), Some(&object_kwargs))
+                } else {
+                    Ok(input)
+                }
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        Ok(ufunc
+            .getattr(method)?
+            .call(PyTuple::new_bound(py, inputs), kwargs)?
+            .unbind())
     }
 
     /// This is a noop for numpy. Since jyafn has no complex type, nothing needs to be done.
@@ -256,19 +487,19 @@ impl Ref {
     // functionalities.
 
     fn floor(&self) -> PyResult<Ref> {
-        insert_in_current(rust::op::Call("floot".to_string()), vec![self.0])
+        insert_in_current(rust::op::Floor, vec![self.0])
     }
 
     fn round(&self) -> PyResult<Ref> {
-        insert_in_current(rust::op::Call("round".to_string()), vec![self.0])
+        insert_in_current(rust::op::Round, vec![self.0])
     }
 
     fn trunc(&self) -> PyResult<Ref> {
-        insert_in_current(rust::op::Call("trunc".to_string()), vec![self.0])
+        insert_in_current(rust::op::Trunc, vec![self.0])
     }
 
     fn ceil(&self) -> PyResult<Ref> {
-        insert_in_current(rust::op::Call("ceil".to_string()), vec![self.0])
+        insert_in_current(rust::op::Ceil, vec![self.0])
     }
 
     fn sqrt(&self) -> PyResult<Ref> {
@@ -388,6 +619,26 @@ impl Ref {
         insert_in_current(rust::op::Call("atanh".to_string()), vec![self.0])
     }
 
+    fn erf(&self) -> PyResult<Ref> {
+        insert_in_current(rust::op::Call("erf".to_string()), vec![self.0])
+    }
+
+    fn erfc(&self) -> PyResult<Ref> {
+        insert_in_current(rust::op::Call("erfc".to_string()), vec![self.0])
+    }
+
+    fn norm_cdf(&self) -> PyResult<Ref> {
+        insert_in_current(rust::op::Call("norm_cdf".to_string()), vec![self.0])
+    }
+
+    fn norm_pdf(&self) -> PyResult<Ref> {
+        insert_in_current(rust::op::Call("norm_pdf".to_string()), vec![self.0])
+    }
+
+    fn norm_inv(&self) -> PyResult<Ref> {
+        insert_in_current(rust::op::Call("norm_inv".to_string()), vec![self.0])
+    }
+
     // Datetime functions:
 
     fn timestamp(&self) -> PyResult<Ref> {
@@ -1,3 +1,4 @@
+use pyo3::buffer::PyBuffer;
 use pyo3::exceptions;
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
@@ -31,6 +32,17 @@ impl<'py> Encode for Obj<'py> {
                     )));
                 }
             }
+            RustLayout::Int => {
+                if let Ok(int) = self.0.extract::<i64>() {
+                    visitor.push_int(int);
+                } else {
+                    return Err(exceptions::PyTypeError::new_err(format!(
+                        "expected {layout}, got value {}, of type {}",
+                        self.0,
+                        self.0.get_type(),
+                    )));
+                }
+            }
             RustLayout::Bool => {
                 if let Ok(float) = self.0.extract::<bool>() {
                     visitor.push_int(float as i64);
@@ -64,9 +76,67 @@ impl<'py> Encode for Obj<'py> {
                 let index = symbols.find(&e);
                 visitor.push_int(index as i64);
             }
+            RustLayout::Enum(variants) => {
+                let e = self.0.extract::<String>()?;
+                let Some(index) = variants.iter().position(|variant| *variant == e) else {
+                    return Err(exceptions::PyValueError::new_err(format!(
+                        "{e:?} is not one of the variants of {layout}"
+                    )));
+                };
+                visitor.push_int(index as i64);
+            }
+            RustLayout::Bytes(n_bytes) => {
+                let Ok(bytes) = self.0.extract::<Vec<u8>>() else {
+                    return Err(exceptions::PyTypeError::new_err(format!(
+                        "expected {layout}, got value {}, of type {}",
+                        self.0,
+                        self.0.get_type()
+                    )));
+                };
+                if bytes.len() != *n_bytes {
+                    return Err(exceptions::PyValueError::new_err(format!(
+                        "expected {n_bytes} bytes, got {} bytes",
+                        bytes.len()
+                    )));
+                }
+                visitor.push_bytes(&bytes);
+            }
+            RustLayout::Map(value) => {
+                let Ok(dict) = self.0.downcast::<PyDict>() else {
+                    return Err(exceptions::PyTypeError::new_err(format!(
+                        "expected {layout}, got value {}, of type {}",
+                        self.0,
+                        self.0.get_type()
+                    )));
+                };
+                if value.contains_map() {
+                    return Err(exceptions::PyTypeError::new_err(format!(
+                        "map values cannot contain maps, got {value}"
+                    )));
+                }
+
+                let entry_size = RustLayout::Symbol.size() + value.size();
+                let mut entries = vec![];
+                for (key, item) in dict.iter() {
+                    let mut entry = Visitor::new(entry_size);
+                    entry.push_uint(symbols.find(&key.extract::<String>()?));
+                    Obj(item).visit(value, symbols, &mut entry)?;
+                    entries.extend_from_slice(entry.buffer());
+                }
+                visitor.push_map(dict.len(), &entries);
+            }
+            RustLayout::Optional(inner) if self.0.is_none() => visit_absent(inner, visitor),
+            RustLayout::Optional(inner) => {
+                visitor.push_int(1);
+                self.visit(inner, symbols, visitor)?;
+            }
             RustLayout::Struct(fields) => {
                 for (name, field) in &fields.0 {
                     let Ok(item) = self.0.get_item(name) else {
+                        if let RustLayout::Optional(inner) = field {
+                            visit_absent(inner, visitor);
+                            continue;
+                        }
                         return Err(exceptions::PyTypeError::new_err(format!(
                             "missing field {name:?} in {}",
                             self.0
@@ -87,18 +157,51 @@ impl<'py> Encode for Obj<'py> {
                 }
             }
             RustLayout::List(element, size) => {
-                let mut n_items = 0;
-                for item in self.0.iter()? {
-                    let item = item?;
+                // Arrays of `float64`, such as numpy arrays, are copied in one go, without
+                // converting each element to a Python float.
+                let buffer = (**element == RustLayout::Scalar)
+                    .then(|| PyBuffer::<f64>::get_bound(&self.0).ok())
+                    .flatten();
+                if let Some(buffer) = buffer {
+                    if buffer.dimensions() != 1 || buffer.item_count() != *size {
+                        let shape = match buffer.shape() {
+                            [dim] => format!("{dim},"),
+                            dims => dims
+                                .iter()
+                                .map(usize::to_string)
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        };
+                        return Err(exceptions::PyValueError::new_err(format!(
+                            "expected array of shape ({size},), got array of shape ({shape})",
+                        )));
+                    }
 
-                    Obj(item).visit(element, symbols, visitor)?;
+                    for value in buffer.to_vec(self.0.py())? {
+                        visitor.push(value);
+                    }
 
-                    n_items += 1;
+                    return Ok(());
                 }
 
-                if n_items != *size {
+                // Items are encoded as they are consumed, so that iterators and generators
+                // need not be materialized. At most one item is consumed past the expected
+                // size, so that infinite iterators fail instead of hanging.
+                let mut items = self.0.iter()?;
+
+                for n_items in 0..*size {
+                    let Some(item) = items.next() else {
+                        return Err(exceptions::PyTypeError::new_err(format!(
+                            "expected array of size {size}, got array of size {n_items}",
+                        )));
+                    };
+
+                    Obj(item?).visit(element, symbols, visitor)?;
+                }
+
+                if items.next().transpose()?.is_some() {
                     return Err(exceptions::PyTypeError::new_err(format!(
-                        "expected array of size {size}, got array of size {n_items}",
+                        "expected array of size {size}, got more than {size} items",
                     )));
                 }
             }
@@ -114,7 +217,37 @@ impl<'py> Encode for Obj<'py> {
     }
 }
 
-pub struct PyDecoder<'py>(pub Python<'py>);
+/// Encodes an absent optional value with the given inner layout.
+fn visit_absent(inner: &RustLayout, visitor: &mut Visitor) {
+    for _ in 0..=inner.size().in_slots() {
+        visitor.push_int(0);
+    }
+}
+
+/// Builds Python objects out of the output of a function.
+pub struct PyDecoder<'py> {
+    py: Python<'py>,
+    /// `numpy.frombuffer`, if lists of scalars are to be built as numpy arrays.
+    frombuffer: Option<Bound<'py, PyAny>>,
+}
+
+impl<'py> PyDecoder<'py> {
+    /// Builds lists as Python lists.
+    pub fn new(py: Python<'py>) -> PyDecoder<'py> {
+        PyDecoder {
+            py,
+            frombuffer: None,
+        }
+    }
+
+    /// Builds lists of scalars as numpy `float64` arrays, copying them in one go.
+    pub fn with_numpy(py: Python<'py>) -> PyResult<PyDecoder<'py>> {
+        Ok(PyDecoder {
+            py,
+            frombuffer: Some(py.import_bound("numpy")?.getattr("frombuffer")?),
+        })
+    }
+}
 
 impl<'py> Decoder for PyDecoder<'py> {
     type Target = PyObject;
@@ -125,41 +258,90 @@ impl<'py> Decoder for PyDecoder<'py> {
         visitor: &mut Visitor,
     ) -> Self::Target {
         match layout {
-            RustLayout::Unit => ().to_object(self.0),
-            RustLayout::Scalar => visitor.pop().to_object(self.0),
-            RustLayout::Bool => (visitor.pop_int() != 0).to_object(self.0),
+            RustLayout::Unit => ().to_object(self.py),
+            RustLayout::Scalar => visitor.pop().to_object(self.py),
+            RustLayout::Int => visitor.pop_int().to_object(self.py),
+            RustLayout::Bool => (visitor.pop_int() != 0).to_object(self.py),
             RustLayout::DateTime(format) => chrono::DateTime::<chrono::Utc>::from(
                 rust::utils::Timestamp::from(visitor.pop_int()),
             )
             .format(format)
             .to_string()
-            .to_object(self.0),
-            RustLayout::Symbol => symbols.get(visitor.pop_uint()).unwrap().to_object(self.0),
+            .to_object(self.py),
+            RustLayout::Symbol => symbols.get(visitor.pop_uint()).unwrap().to_object(self.py),
             RustLayout::Struct(fields) => {
-                let dict = pyo3::types::PyDict::new_bound(self.0);
+                let dict = pyo3::types::PyDict::new_bound(self.py);
 
                 for (name, field) in &fields.0 {
                     dict.set_item(name, self.build(field, symbols, visitor))
                         .unwrap();
                 }
 
-                dict.to_object(self.0)
+                dict.to_object(self.py)
             }
             RustLayout::Tuple(fields) => {
                 let tuple = pyo3::types::PyTuple::new_bound(
-                    self.0,
+                    self.py,
                     fields
                         .iter()
                         .map(|field| self.build(field, symbols, visitor)),
                 );
 
-                tuple.to_object(self.0)
+                tuple.to_object(self.py)
+            }
+            RustLayout::List(element, size)
+                if **element == RustLayout::Scalar && self.frombuffer.is_some() =>
+            {
+                let bytes = (0..*size)
+                    .flat_map(|_| visitor.pop().to_ne_bytes())
+                    .collect::<Vec<_>>();
+                // A bytearray, so that the array is writable.
+                let buffer = pyo3::types::PyByteArray::new_bound(self.py, &bytes);
+                self.frombuffer
+                    .as_ref()
+                    .expect("checked in guard")
+                    .call1((buffer, "float64"))
+                    .expect("can build numpy array from buffer")
+                    .unbind()
             }
             RustLayout::List(element, size) => pyo3::types::PyList::new_bound(
-                self.0,
+                self.py,
                 (0..*size).map(|_| self.build(element, symbols, visitor)),
             )
-            .to_object(self.0),
+            .to_object(self.py),
+            RustLayout::Optional(inner) => {
+                if visitor.pop_int() != 0 {
+                    self.build(inner, symbols, visitor)
+                } else {
+                    visitor.skip(inner.size().in_slots());
+                    ().to_object(self.py)
+                }
+            }
+            // Functions validate enums before decoding them, so this is never `None` for
+            // their outputs.
+            RustLayout::Enum(variants) => {
+                variants.get(visitor.pop_uint() as usize).to_object(self.py)
+            }
+            RustLayout::Map(value) => {
+                let dict = pyo3::types::PyDict::new_bound(self.py);
+                let (n_entries, start) = visitor.pop_map();
+                let end = visitor.position();
+                let entry_size = 1 + value.size().in_slots();
+
+                for i in 0..n_entries {
+                    visitor.seek(start + i * entry_size);
+                    let key = symbols.get(visitor.pop_uint()).unwrap();
+                    dict.set_item(key, self.build(value, symbols, visitor))
+                        .unwrap();
+                }
+                visitor.seek(end);
+
+                dict.to_object(self.py)
+            }
+            RustLayout::Bytes(n_bytes) => {
+                pyo3::types::PyBytes::new_bound(self.py, visitor.pop_bytes(*n_bytes))
+                    .to_object(self.py)
+            }
         }
     }
 }
@@ -217,6 +399,10 @@ impl Layout {
         self.0 == rust::layout::Layout::Scalar
     }
 
+    fn is_int(&self) -> bool {
+        matches!(&self.0, rust::layout::Layout::Int)
+    }
+
     fn is_bool(&self) -> bool {
         matches!(&self.0, rust::layout::Layout::Bool)
     }
@@ -229,6 +415,10 @@ impl Layout {
         matches!(&self.0, rust::layout::Layout::Symbol)
     }
 
+    fn is_enum(&self) -> bool {
+        matches!(&self.0, rust::layout::Layout::Enum(_))
+    }
+
     fn is_struct(&self) -> bool {
         matches!(&self.0, rust::layout::Layout::Struct(_))
     }
@@ -237,6 +427,18 @@ impl Layout {
         matches!(&self.0, rust::layout::Layout::Tuple(_))
     }
 
+    fn is_optional(&self) -> bool {
+        matches!(&self.0, rust::layout::Layout::Optional(_))
+    }
+
+    fn is_bytes(&self) -> bool {
+        matches!(&self.0, rust::layout::Layout::Bytes(_))
+    }
+
+    fn is_map(&self) -> bool {
+        matches!(&self.0, rust::layout::Layout::Map(_))
+    }
+
     fn struct_keys(&self, py: Python) -> PyResult<PyObject> {
         let rust::layout::Layout::Struct(s) = &self.0 else {
             return Ok(pyo3::types::PyNone::get_bound(py).to_object(py));
@@ -257,6 +459,11 @@ impl Layout {
         Layout(rust::layout::Layout::Scalar)
     }
 
+    #[staticmethod]
+    fn int() -> Layout {
+        Layout(rust::layout::Layout::Int)
+    }
+
     #[staticmethod]
     fn bool() -> Layout {
         Layout(rust::layout::Layout::Bool)
@@ -275,6 +482,11 @@ impl Layout {
         Layout(rust::layout::Layout::Symbol)
     }
 
+    #[staticmethod]
+    fn enum_of(variants: Vec<String>) -> Layout {
+        Layout(rust::layout::Layout::Enum(variants))
+    }
+
     #[staticmethod]
     fn list_of(element: &Layout, size: usize) -> Layout {
         Layout(rust::layout::Layout::List(
@@ -283,6 +495,21 @@ impl Layout {
         ))
     }
 
+    #[staticmethod]
+    fn bytes(n_bytes: usize) -> Layout {
+        Layout(rust::layout::Layout::Bytes(n_bytes))
+    }
+
+    #[staticmethod]
+    fn optional(inner: &Layout) -> Layout {
+        Layout(rust::layout::Layout::Optional(Box::new(inner.0.clone())))
+    }
+
+    #[staticmethod]
+    fn map_of(value: &Layout) -> Layout {
+        Layout(rust::layout::Layout::Map(Box::new(value.0.clone())))
+    }
+
     #[staticmethod]
     fn struct_of(fields: &Bound<'_, PyDict>) -> PyResult<Layout> {
         let fields = fields
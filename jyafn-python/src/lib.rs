@@ -15,7 +15,7 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use function::Function;
-use graph::{Graph, IndexedList, Ref};
+use graph::{CompileCache, Graph, IndexedList, MapRef, NodeInfo, Ref};
 use layout::Layout;
 
 #[pymodule]
@@ -25,6 +25,9 @@ fn jyafn(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Type>()?;
     m.add_class::<Function>()?;
     m.add_class::<IndexedList>()?;
+    m.add_class::<MapRef>()?;
+    m.add_class::<NodeInfo>()?;
+    m.add_class::<CompileCache>()?;
     m.add_function(wrap_pyfunction!(__get_version, m)?)?;
     m.add_function(wrap_pyfunction!(read_metadata, m)?)?;
     m.add_function(wrap_pyfunction!(read_graph, m)?)?;
@@ -35,6 +38,18 @@ fn jyafn(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(input, m)?)?;
     m.add_function(wrap_pyfunction!(ret, m)?)?;
     m.add_function(wrap_pyfunction!(assert_, m)?)?;
+    m.add_function(wrap_pyfunction!(feature_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(coalesce, m)?)?;
+    m.add_function(wrap_pyfunction!(select, m)?)?;
+    m.add_function(wrap_pyfunction!(masked_select, m)?)?;
+    m.add_function(wrap_pyfunction!(sum, m)?)?;
+    m.add_function(wrap_pyfunction!(mean, m)?)?;
+    m.add_function(wrap_pyfunction!(dot, m)?)?;
+    m.add_function(wrap_pyfunction!(min, m)?)?;
+    m.add_function(wrap_pyfunction!(max, m)?)?;
+    m.add_function(wrap_pyfunction!(sigmoid, m)?)?;
+    m.add_function(wrap_pyfunction!(relu, m)?)?;
+    m.add_function(wrap_pyfunction!(softmax, m)?)?;
     m.add_function(wrap_pyfunction!(layout::symbol_hash, m)?)?;
 
     m.add_class::<layout::Layout>()?;
@@ -62,7 +77,16 @@ pub struct ToPyErr(pub rust::Error);
 
 impl From<ToPyErr> for PyErr {
     fn from(err: ToPyErr) -> PyErr {
-        exceptions::PyException::new_err(err.0.to_string())
+        let py_err = exceptions::PyException::new_err(err.0.to_string());
+        if let Some(error_id) = err.0.error_id() {
+            Python::with_gil(|py| {
+                py_err
+                    .value_bound(py)
+                    .setattr("error_id", error_id)
+                    .expect("can set attribute on exception")
+            });
+        }
+        py_err
     }
 }
 
@@ -111,6 +135,7 @@ fn value_from_ref(g: &rust::Graph, scalar: Ref) -> PyResult<rust::layout::RefVal
         rust::Type::Bool => rust::layout::RefValue::Bool(scalar.0),
         rust::Type::DateTime => rust::layout::RefValue::DateTime(scalar.0),
         rust::Type::Symbol => rust::layout::RefValue::Symbol(scalar.0),
+        rust::Type::Int => rust::layout::RefValue::Int(scalar.0),
         _ => {
             return Err(exceptions::PyException::new_err(format!(
                 "cannot make RefValue out of {:?}",
@@ -127,6 +152,7 @@ fn pythonize_ref_value(py: Python, val: rust::layout::RefValue) -> PyResult<PyOb
         rust::layout::RefValue::Bool(s) => Ref(s).into_py(py),
         rust::layout::RefValue::DateTime(s) => Ref(s).into_py(py),
         rust::layout::RefValue::Symbol(e) => Ref(e).into_py(py),
+        rust::layout::RefValue::Int(s) => Ref(s).into_py(py),
         rust::layout::RefValue::Struct(fields) => {
             let dict = PyDict::new_bound(py);
             for (name, val) in fields {
@@ -152,6 +178,13 @@ fn pythonize_ref_value(py: Python, val: rust::layout::RefValue) -> PyResult<PyOb
         )
         .unbind()
         .into(),
+        rust::layout::RefValue::Optional(present, value) => PyTuple::new_bound(
+            py,
+            [Ref(present).into_py(py), pythonize_ref_value(py, *value)?],
+        )
+        .unbind()
+        .into(),
+        map @ rust::layout::RefValue::Map(..) => MapRef(map).into_py(py),
     })
 }
 
@@ -171,6 +204,10 @@ fn depythonize_ref_value(
             return value_from_ref(g, scalar);
         }
 
+        if let Ok(map) = obj.extract::<MapRef>() {
+            return Ok(map.0);
+        }
+
         if let Ok(dict) = obj.downcast::<PyDict>() {
             let vals = dict
                 .iter()
@@ -244,15 +281,16 @@ fn putative_layout(obj: &Bound<PyAny>) -> PyResult<Layout> {
 }
 
 #[pyfunction]
-#[pyo3(signature = (name, layout=None))]
-fn input(py: Python, name: String, layout: Option<Layout>) -> PyResult<PyObject> {
-    if let Some(layout) = layout {
-        graph::try_with_current(|g| pythonize_ref_value(py, g.input(name, layout.0)))
-    } else {
-        graph::try_with_current(|g| {
-            pythonize_ref_value(py, g.input(name, rust::layout::Layout::Scalar))
-        })
-    }
+#[pyo3(signature = (name, layout=None, pinnable=false))]
+fn input(py: Python, name: String, layout: Option<Layout>, pinnable: bool) -> PyResult<PyObject> {
+    let layout = layout.map_or(rust::layout::Layout::Scalar, |layout| layout.0);
+    graph::try_with_current(|g| {
+        if pinnable {
+            pythonize_ref_value(py, g.pinnable_input(name, layout))
+        } else {
+            pythonize_ref_value(py, g.input(name, layout))
+        }
+    })
 }
 
 #[pyfunction]
@@ -264,7 +302,170 @@ fn ret(val: &Bound<PyAny>, layout: Layout) -> PyResult<()> {
 }
 
 #[pyfunction]
-fn assert_(r#ref: &Bound<PyAny>, error_msg: String) -> PyResult<Ref> {
+fn assert_(py: Python, r#ref: &Bound<PyAny>, error_msg: String) -> PyResult<Ref> {
     let r#ref = Ref::make(r#ref)?;
-    graph::try_with_current(|g| Ok(Ref(g.assert(r#ref.0, error_msg).map_err(ToPyErr)?)))
+    let origin = caller_location(py);
+    graph::try_with_current(|g| {
+        let asserted = match origin {
+            Some(origin) => g.assert_with_origin(r#ref.0, error_msg, origin),
+            None => g.assert(r#ref.0, error_msg),
+        };
+        Ok(Ref(asserted.map_err(ToPyErr)?))
+    })
+}
+
+/// The `file:line` of the Python code calling into this module, if it can be found.
+fn caller_location(py: Python) -> Option<String> {
+    let frame = py
+        .import_bound("sys")
+        .and_then(|sys| sys.getattr("_getframe")?.call1((0,)))
+        .ok()?;
+    let filename = frame
+        .getattr("f_code")
+        .and_then(|code| code.getattr("co_filename"))
+        .ok()?;
+    let line = frame.getattr("f_lineno").ok()?;
+    Some(format!("{filename}:{line}"))
+}
+
+#[pyfunction]
+fn min(a: &Bound<PyAny>, b: &Bound<PyAny>) -> PyResult<Ref> {
+    let a = Ref::make(a)?;
+    let b = Ref::make(b)?;
+    graph::insert_in_current(rust::op::Min, vec![a.0, b.0])
+}
+
+#[pyfunction]
+fn max(a: &Bound<PyAny>, b: &Bound<PyAny>) -> PyResult<Ref> {
+    let a = Ref::make(a)?;
+    let b = Ref::make(b)?;
+    graph::insert_in_current(rust::op::Max, vec![a.0, b.0])
+}
+
+#[pyfunction]
+fn sigmoid(x: &Bound<PyAny>) -> PyResult<Ref> {
+    let x = Ref::make(x)?;
+    graph::insert_in_current(rust::op::Sigmoid, vec![x.0])
+}
+
+#[pyfunction]
+fn relu(x: &Bound<PyAny>) -> PyResult<Ref> {
+    let x = Ref::make(x)?;
+    graph::insert_in_current(rust::op::Relu, vec![x.0])
+}
+
+#[pyfunction]
+fn softmax<'py>(py: Python<'py>, values: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyTuple>> {
+    let values = make_refs(values)?;
+    let normalized = graph::try_with_current(|g| Ok(g.softmax(values).map_err(ToPyErr)?))?;
+
+    Ok(PyTuple::new_bound(
+        py,
+        normalized.into_iter().map(|r| Ref(r).into_py(py)),
+    ))
+}
+
+#[pyfunction]
+fn coalesce(candidates: &Bound<PyAny>, default: &Bound<PyAny>) -> PyResult<PyObject> {
+    graph::try_with_current(|g| {
+        let candidates = candidates
+            .iter()?
+            .map(|candidate| {
+                let (present, value) = candidate?.extract::<(Bound<PyAny>, Bound<PyAny>)>()?;
+                let present = match present.extract::<Ref>() {
+                    Ok(present) => present,
+                    Err(_) => const_from_py(g, &present)?,
+                };
+                Ok((present.0, depythonize_ref_value(g, &value)?))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let default_value = depythonize_ref_value(g, default)?;
+        let coalesced = g.coalesce(candidates, default_value).map_err(ToPyErr)?;
+        pythonize_ref_value(default.py(), coalesced)
+    })
+}
+
+#[pyfunction]
+fn select(index: &Bound<PyAny>, values: &Bound<PyAny>) -> PyResult<Ref> {
+    let index = Ref::make(index)?;
+    let values = values
+        .iter()?
+        .map(|value| Ok(Ref::make(&value?)?.0))
+        .collect::<PyResult<Vec<_>>>()?;
+    graph::try_with_current(|g| Ok(Ref(g.select(index.0, values).map_err(ToPyErr)?)))
+}
+
+#[pyfunction]
+#[pyo3(name = "where")]
+fn masked_select<'py>(
+    py: Python<'py>,
+    mask: &Bound<'py, PyAny>,
+    values: &Bound<'py, PyAny>,
+    default: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let mask = make_refs(mask)?;
+    let values = make_refs(values)?;
+    let default = Ref::make(default)?;
+    let selected = graph::try_with_current(|g| {
+        Ok(g.masked_select(mask, values, default.0).map_err(ToPyErr)?)
+    })?;
+
+    Ok(PyTuple::new_bound(
+        py,
+        selected.into_iter().map(|r| Ref(r).into_py(py)),
+    ))
+}
+
+/// Makes refs out of every element of an iterable.
+fn make_refs(values: &Bound<PyAny>) -> PyResult<Vec<rust::Ref>> {
+    values
+        .iter()?
+        .map(|value| Ok(Ref::make(&value?)?.0))
+        .collect()
+}
+
+#[pyfunction]
+fn sum(values: &Bound<PyAny>) -> PyResult<Ref> {
+    let values = make_refs(values)?;
+    graph::try_with_current(|g| {
+        Ok(Ref(g
+            .reduce(rust::op::ReduceKind::Sum, values)
+            .map_err(ToPyErr)?))
+    })
+}
+
+#[pyfunction]
+fn mean(values: &Bound<PyAny>) -> PyResult<Ref> {
+    let values = make_refs(values)?;
+    if values.is_empty() {
+        return Err(exceptions::PyValueError::new_err(
+            "cannot take the mean of no values",
+        ));
+    }
+
+    let n = values.len() as f64;
+    graph::try_with_current(|g| {
+        let sum = g
+            .reduce(rust::op::ReduceKind::Sum, values)
+            .map_err(ToPyErr)?;
+        Ok(Ref(g
+            .insert(rust::op::Div, vec![sum, n.into()])
+            .map_err(ToPyErr)?))
+    })
+}
+
+#[pyfunction]
+fn dot(a: &Bound<PyAny>, b: &Bound<PyAny>) -> PyResult<Ref> {
+    let a = make_refs(a)?;
+    let b = make_refs(b)?;
+    graph::try_with_current(|g| Ok(Ref(g.dot(a, b).map_err(ToPyErr)?)))
+}
+
+#[pyfunction]
+#[pyo3(signature = (value, k, seed=0))]
+fn feature_hash(value: &Bound<PyAny>, k: u64, seed: u64) -> PyResult<Ref> {
+    graph::try_with_current(|g| {
+        let value = depythonize_ref_value(g, value)?;
+        Ok(Ref(g.feature_hash(value, k, seed).map_err(ToPyErr)?))
+    })
 }
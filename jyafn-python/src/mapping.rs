@@ -5,6 +5,36 @@ use pyo3::types::PyDict;
 use super::layout::Obj;
 use super::{depythonize_ref_value, graph, pythonize_ref_value, Layout, ToPyErr};
 
+/// An error while inserting a mapping, either raised by Python or by the storage.
+#[derive(Debug)]
+struct InsertError(PyErr);
+
+impl std::fmt::Display for InsertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for InsertError {}
+
+impl From<PyErr> for InsertError {
+    fn from(err: PyErr) -> InsertError {
+        InsertError(err)
+    }
+}
+
+impl From<rust::Error> for InsertError {
+    fn from(err: rust::Error) -> InsertError {
+        InsertError(ToPyErr(err).into())
+    }
+}
+
+impl From<InsertError> for PyErr {
+    fn from(err: InsertError) -> PyErr {
+        err.0
+    }
+}
+
 #[pyclass(module = "jyafn")]
 pub struct LazyMapping {
     is_consumed: bool,
@@ -23,7 +53,8 @@ impl LazyMapping {
                     self.key_layout.clone(),
                     self.value_layout.clone(),
                     rust::mapping::HashMapStorage,
-                    dict.iter().map(|(k, v)| (Obj(k), Obj(v))).map(Ok),
+                    dict.iter()
+                        .map(|(k, v)| Ok::<_, InsertError>((Obj(k), Obj(v)))),
                 )?;
             } else {
                 if self.is_consumed {
@@ -39,6 +70,7 @@ impl LazyMapping {
                         i.extract::<(Bound<PyAny>, Bound<PyAny>)>()
                             .map(|(k, v)| (Obj(k), Obj(v)))
                     })
+                    .map_err(InsertError)
                 });
 
                 g.insert_mapping(
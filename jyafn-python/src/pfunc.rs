@@ -1,5 +1,8 @@
-use super::{graph, Ref};
+use super::{graph, Ref, ToPyErr};
 
+use pyo3::types::PyTuple;
+
+use pyo3::exceptions;
 use pyo3::prelude::*;
 
 pub fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
@@ -28,13 +31,30 @@ pub fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
         )* }
     }
 
+    macro_rules! pfunc1_to2s {
+        ($($f:ident),*) => { $(
+            #[pyfunction]
+            fn $f(x: &Bound<PyAny>) -> PyResult<(Ref, Ref)> {
+                let x = Ref::make(x)?;
+                graph::try_with_current(|g| {
+                    let outputs = g
+                        .call_pfunc(stringify!($f), vec![x.0])
+                        .map_err(ToPyErr)?;
+                    Ok((Ref(outputs[0]), Ref(outputs[1])))
+                })
+            }
+
+            m.add_function(wrap_pyfunction!($f, m)?)?;
+        )* }
+    }
+
     pfunc1s! {
         // f64 -> f64
         floor, ceil, round, trunc,
         sqrt, exp, ln, ln_1p, exp_m1,
         sin, cos, tan, asin, acos, atan, sinh, cosh, tanh, asinh, acosh, atanh,
         gamma, loggamma, factorial, rgamma, digamma,
-        erf, erfc, norm, norm_inv,
+        erf, erfc, norm, norm_cdf, norm_pdf, norm_inv,
         riemann_zeta,
 
         // dt -> f64
@@ -51,13 +71,28 @@ pub fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
 
     pfunc2s! {
         // f64, f64 -> f64
-        powf, rem, atan2,
+        powf, rem, atan2, hypot, log,
         beta, logbeta, gammainc, gammac, gammac_inv,
         besselj, bessely, besseli
     }
 
+    pfunc1_to2s! {
+        // f64 -> (f64, f64)
+        sincos, modf
+    }
+
     // Misc that can't be solved with macros.
     m.add_function(wrap_pyfunction!(pow, m)?)?;
+    m.add_function(wrap_pyfunction!(vector_norm, m)?)?;
+    m.add_function(wrap_pyfunction!(norm_l1, m)?)?;
+    m.add_function(wrap_pyfunction!(norm_l2, m)?)?;
+    m.add_function(wrap_pyfunction!(norm_inf, m)?)?;
+
+    m.add_class::<PFuncSnapshot>()?;
+    m.add_function(wrap_pyfunction!(inscribe_pfunc, m)?)?;
+    m.add_function(wrap_pyfunction!(call_pfunc, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot_pfuncs, m)?)?;
+    m.add_function(wrap_pyfunction!(restore_pfuncs, m)?)?;
 
     Ok(())
 }
@@ -68,3 +103,170 @@ fn pow(base: &Bound<PyAny>, exponent: &Bound<PyAny>) -> PyResult<Ref> {
     let exponent = Ref::make(exponent)?;
     graph::insert_in_current(rust::op::Call("powf".to_string()), vec![base.0, exponent.0])
 }
+
+fn insert_norm(x: &Bound<PyAny>, norm: rust::Norm) -> PyResult<Ref> {
+    let list = x
+        .iter()?
+        .map(|item| Ok(Ref::make(&item?)?.0))
+        .collect::<PyResult<Vec<_>>>()?;
+    graph::try_with_current(|g| Ok(Ref(g.norm(&list, norm).map_err(ToPyErr)?)))
+}
+
+/// The vector norm of order `p` of `x`, which can be 1, 2 or infinity. This is not
+/// `norm`, which is the CDF of the standard normal distribution.
+#[pyfunction]
+#[pyo3(signature = (x, p=2.0))]
+fn vector_norm(x: &Bound<PyAny>, p: f64) -> PyResult<Ref> {
+    let norm = match p {
+        1.0 => rust::Norm::L1,
+        2.0 => rust::Norm::L2,
+        f64::INFINITY => rust::Norm::Inf,
+        p => {
+            return Err(exceptions::PyValueError::new_err(format!(
+                "unsupported norm order {p}: expected 1, 2 or inf"
+            )))
+        }
+    };
+
+    insert_norm(x, norm)
+}
+
+#[pyfunction]
+fn norm_l1(x: &Bound<PyAny>) -> PyResult<Ref> {
+    vector_norm(x, 1.0)
+}
+
+#[pyfunction]
+fn norm_l2(x: &Bound<PyAny>) -> PyResult<Ref> {
+    vector_norm(x, 2.0)
+}
+
+#[pyfunction]
+fn norm_inf(x: &Bound<PyAny>) -> PyResult<Ref> {
+    vector_norm(x, f64::INFINITY)
+}
+
+/// Parses the name of a type as it is displayed, e.g. `scalar` or `int`.
+fn type_from_name(name: &str) -> PyResult<rust::Type> {
+    match name {
+        "scalar" => Ok(rust::Type::Float),
+        "bool" => Ok(rust::Type::Bool),
+        "symbol" => Ok(rust::Type::Symbol),
+        "datetime" => Ok(rust::Type::DateTime),
+        "int" => Ok(rust::Type::Int),
+        _ => Err(exceptions::PyValueError::new_err(format!(
+            "{name:?} is not a valid pure function type"
+        ))),
+    }
+}
+
+/// The ctypes type of the C argument or return value for a pure function type.
+fn ctype_of<'py>(ctypes: &Bound<'py, PyModule>, ty: rust::Type) -> PyResult<Bound<'py, PyAny>> {
+    match ty {
+        rust::Type::Float => ctypes.getattr("c_double"),
+        _ => ctypes.getattr("c_int64"),
+    }
+}
+
+/// Finds the address of a ctypes foreign function, such as one created with
+/// `ctypes.CFUNCTYPE`, checking that its argument and return types are the C types of
+/// `signature` and `returns`. The foreign function is kept alive for the rest of the
+/// process, since compiled functions may call it at any time.
+fn foreign_function_ptr(
+    func: &Bound<PyAny>,
+    signature: &[rust::Type],
+    returns: rust::Type,
+) -> PyResult<*const ()> {
+    let py = func.py();
+    let ctypes = py.import_bound("ctypes")?;
+    if !func.is_instance(&ctypes.getattr("_CFuncPtr")?)? {
+        return Err(exceptions::PyTypeError::new_err(
+            "pure function must be a ctypes foreign function, e.g. created with \
+            ctypes.CFUNCTYPE",
+        ));
+    }
+
+    let expected = signature
+        .iter()
+        .map(|&ty| ctype_of(&ctypes, ty))
+        .collect::<PyResult<Vec<_>>>()?;
+    let argtypes = func.getattr("argtypes")?;
+    let restype = func.getattr("restype")?;
+    let argtypes_match = !argtypes.is_none()
+        && argtypes.len()? == expected.len()
+        && argtypes
+            .iter()?
+            .zip(&expected)
+            .map(|(argtype, expected)| Ok(argtype?.is(expected)))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_iter()
+            .all(|matches| matches);
+    if !argtypes_match || !restype.is(&ctype_of(&ctypes, returns)?) {
+        return Err(exceptions::PyTypeError::new_err(format!(
+            "foreign function with argument types {argtypes} and return type {restype} \
+            does not match the pure function signature, which expects ctypes.c_double \
+            for scalars and ctypes.c_int64 for everything else"
+        )));
+    }
+
+    let address = ctypes
+        .getattr("cast")?
+        .call1((func, ctypes.getattr("c_void_p")?))?
+        .getattr("value")?
+        .extract::<usize>()?;
+    std::mem::forget(func.clone().unbind());
+
+    Ok(address as *const ())
+}
+
+#[pyfunction]
+fn inscribe_pfunc(
+    name: &str,
+    func: &Bound<PyAny>,
+    signature: Vec<String>,
+    returns: &str,
+) -> PyResult<()> {
+    let signature = signature
+        .iter()
+        .map(|ty| type_from_name(ty))
+        .collect::<PyResult<Vec<_>>>()?;
+    let returns = type_from_name(returns)?;
+    let fn_ptr = foreign_function_ptr(func, &signature, returns)?;
+
+    // Safety: the foreign function has the right C signature and is kept alive. That it
+    // is pure is part of the contract passed on to the caller, as documented in the stubs.
+    unsafe { rust::pfunc::inscribe(name, fn_ptr, &signature, returns) }
+        .map_err(|err| ToPyErr(err).into())
+}
+
+#[pyfunction]
+#[pyo3(signature = (name, *args))]
+fn call_pfunc(py: Python, name: &str, args: &Bound<PyTuple>) -> PyResult<PyObject> {
+    let args = args
+        .iter()
+        .map(|arg| Ok(Ref::make(&arg)?.0))
+        .collect::<PyResult<Vec<_>>>()?;
+    let outputs = graph::try_with_current(|g| Ok(g.call_pfunc(name, args).map_err(ToPyErr)?))?;
+
+    if let [output] = outputs.as_slice() {
+        Ok(Ref(*output).into_py(py))
+    } else {
+        let outputs = outputs.into_iter().map(|r| Ref(r).into_py(py));
+        Ok(PyTuple::new_bound(py, outputs).into_py(py))
+    }
+}
+
+/// A snapshot of the inscribed pure functions.
+#[pyclass(module = "jyafn")]
+#[derive(Clone, Copy)]
+pub struct PFuncSnapshot(rust::pfunc::Snapshot);
+
+#[pyfunction]
+fn snapshot_pfuncs() -> PFuncSnapshot {
+    PFuncSnapshot(rust::pfunc::snapshot())
+}
+
+#[pyfunction]
+fn restore_pfuncs(snapshot: PFuncSnapshot) {
+    rust::pfunc::restore(snapshot.0)
+}
@@ -1,10 +1,10 @@
+use pyo3::exceptions;
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
-use std::collections::HashMap;
+use pyo3::types::{PyDict, PyTuple};
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 
-use super::{depythonize_ref_value, graph, pythonize_ref_value, ToPyErr};
+use super::{depythonize_ref_value, graph, layout::Layout, pythonize_ref_value, ToPyErr};
 
 type PinnedResource = Pin<Box<dyn rust::resource::Resource>>;
 
@@ -65,20 +65,120 @@ impl LazyResourceCall {
 
         g.insert_resource_boxed(self.name.clone(), resource);
     }
+
+    /// Gets the method being called, either from the resource, if it was not inserted in
+    /// a graph yet, or from the current graph.
+    fn method(&self) -> PyResult<rust::resource::ResourceMethod> {
+        if let Some(resource) = &*self.resource.lock().expect("poisoned") {
+            return resource.get_method(&self.method_name).ok_or_else(|| {
+                exceptions::PyAttributeError::new_err(format!(
+                    "resource {} has no method {}",
+                    self.name, self.method_name
+                ))
+            });
+        }
+
+        graph::try_with_current(|g| {
+            Ok(g.resource_method(&self.name, &self.method_name)
+                .map_err(ToPyErr)?)
+        })
+    }
+
+    /// Matches positional and keyword arguments to the fields of the input layout of the
+    /// method, by position and by name, respectively.
+    fn match_arguments<'py>(
+        &self,
+        input_layout: &rust::layout::Struct,
+        args: &Bound<'py, PyTuple>,
+        kwargs: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let fields = input_layout.fields();
+        let matched = PyDict::new_bound(args.py());
+
+        if args.len() > fields.len() {
+            return Err(exceptions::PyTypeError::new_err(format!(
+                "method {} of {} takes {} arguments but {} were given",
+                self.method_name,
+                self.name,
+                fields.len(),
+                args.len()
+            )));
+        }
+
+        for (item, (name, _)) in args.iter().zip(fields) {
+            matched.set_item(name, item)?;
+        }
+
+        for (key, value) in kwargs.into_iter().flat_map(|kwargs| kwargs.iter()) {
+            let key = key.extract::<String>()?;
+            if !fields.iter().any(|(name, _)| *name == key) {
+                return Err(exceptions::PyTypeError::new_err(format!(
+                    "method {} of {} got an unexpected argument {key:?}",
+                    self.method_name, self.name
+                )));
+            }
+            if matched.contains(&key)? {
+                return Err(exceptions::PyTypeError::new_err(format!(
+                    "method {} of {} got multiple values for argument {key:?}",
+                    self.method_name, self.name
+                )));
+            }
+            matched.set_item(key, value)?;
+        }
+
+        let missing = fields
+            .iter()
+            .map(|(name, _)| Ok((name, matched.contains(name)?)))
+            .filter_map(|item| match item {
+                Ok((name, false)) => Some(Ok(format!("{name:?}"))),
+                Ok((_, true)) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        if !missing.is_empty() {
+            return Err(exceptions::PyTypeError::new_err(format!(
+                "method {} of {} is missing arguments {}",
+                self.method_name,
+                self.name,
+                missing.join(", ")
+            )));
+        }
+
+        Ok(matched)
+    }
 }
 
 #[pymethods]
 impl LazyResourceCall {
-    #[pyo3(signature = (**kwargs))]
-    fn __call__(&self, py: Python, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<PyObject> {
+    /// The input layout of the method. Its fields are the arguments of the method.
+    #[getter]
+    fn input_layout(&self) -> PyResult<Layout> {
+        Ok(Layout(rust::layout::Layout::Struct(
+            self.method()?.input_layout().clone(),
+        )))
+    }
+
+    /// The output layout of the method.
+    #[getter]
+    fn output_layout(&self) -> PyResult<Layout> {
+        Ok(Layout(self.method()?.output_layout().clone()))
+    }
+
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyObject> {
         graph::try_with_current(|g| {
             self.init(g);
 
-            let input = if let Some(kwargs) = kwargs {
-                depythonize_ref_value(g, kwargs)?
-            } else {
-                rust::layout::RefValue::Struct(HashMap::new())
-            };
+            let method = g
+                .resource_method(&self.name, &self.method_name)
+                .map_err(ToPyErr)?;
+            let arguments = self.match_arguments(method.input_layout(), args, kwargs)?;
+            let input = depythonize_ref_value(g, &arguments)?;
             let output = g
                 .call_resource(&self.name, &self.method_name, input)
                 .map_err(|e| ToPyErr(e.to_string().into()))?;
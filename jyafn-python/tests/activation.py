@@ -0,0 +1,28 @@
+import jyafn as fn
+import math
+
+
+@fn.func
+def activations(x: fn.scalar) -> fn.tensor[3]:
+    return [fn.sigmoid(x), fn.relu(x), fn.relu(-x)]
+
+
+assert activations(0.0) == [0.5, 0.0, 0.0]
+assert activations(2.0) == [1.0 / (1.0 + math.exp(-2.0)), 2.0, 0.0]
+assert activations(-1000.0)[0] == 0.0
+
+
+@fn.func
+def probs(x: fn.tensor[3]) -> fn.tensor[3]:
+    return fn.softmax(x)
+
+
+def reference(x):
+    exps = [math.exp(v - max(x)) for v in x]
+    return [e / sum(exps) for e in exps]
+
+
+for x in [[1.0, 2.0, 3.0], [0.0, 0.0, 0.0], [1000.0, 1001.0, -1000.0]]:
+    result = probs(x)
+    assert abs(sum(result) - 1.0) < 1e-12
+    assert all(abs(a - b) < 1e-12 for a, b in zip(result, reference(x)))
@@ -0,0 +1,20 @@
+import jyafn as fn
+
+
+@fn.func
+def checked(x: fn.scalar) -> fn.scalar:
+    fn.assert_(x > 0.0, "x must be positive")
+    return x
+
+
+with open(__file__) as source:
+    line = next(
+        i for i, text in enumerate(source, 1) if "fn.assert_(x > 0.0" in text
+    )
+
+try:
+    checked(-1.0)
+except Exception as e:
+    assert f"x must be positive (at {__file__}:{line})" in str(e), e
+else:
+    raise Exception("should raise")
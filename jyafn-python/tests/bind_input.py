@@ -0,0 +1,23 @@
+import jyafn as fn
+
+
+@fn.graph
+def affine(x: fn.scalar, weight: fn.scalar, bias: fn.scalar) -> fn.scalar:
+    return x * weight + bias
+
+
+g = affine.build()
+g.bind_input("weight", 2.0)
+assert [name for name, _ in g.inputs] == ["x", "bias"], g.inputs
+
+# The weight is no longer required:
+f = g.compile()
+assert f(3.0, 1.0) == 7.0
+
+try:
+    g.bind_input("x", "three")
+except Exception:
+    pass
+else:
+    raise AssertionError("binding a string to a scalar input should fail")
+assert [name for name, _ in g.inputs] == ["x", "bias"], g.inputs
@@ -0,0 +1,20 @@
+import jyafn as fn
+
+
+def build(c: float) -> fn.Graph:
+    with fn.Graph() as g:
+        a = fn.input("a", fn.Layout.scalar())
+        b = fn.input("b", fn.Layout.scalar())
+        fn.ret((a + b) * c, fn.Layout.scalar())
+
+    return g
+
+
+g1 = build(2.0)
+g2 = build(2.0)
+g2.set_metadata("origin", "elsewhere")
+
+assert g1.name != g2.name
+assert g1.canonical_name == g2.canonical_name
+assert g1.canonical_name == fn.Graph.load(g1.dump()).canonical_name
+assert g1.canonical_name != build(3.0).canonical_name
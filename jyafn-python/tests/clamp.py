@@ -0,0 +1,14 @@
+import math
+
+import jyafn as fn
+
+
+@fn.func
+def clamp(x: fn.scalar) -> fn.scalar:
+    return x.clamp(0.0, 1.0)
+
+
+assert clamp(-3.0) == 0.0
+assert clamp(0.5) == 0.5
+assert clamp(3.0) == 1.0
+assert math.isnan(clamp(float("nan")))
@@ -0,0 +1,14 @@
+import jyafn as fn
+
+
+@fn.func
+def first_present(
+    has_a: fn.bool, a: fn.scalar, has_b: fn.bool, b: fn.scalar, has_c: fn.bool
+) -> fn.scalar:
+    return fn.coalesce([(has_a, a), (has_b, b), (has_c, 3.0)], default=-1.0)
+
+
+assert first_present(True, 1.0, True, 2.0, True) == 1.0
+assert first_present(False, 1.0, True, 2.0, True) == 2.0
+assert first_present(False, 1.0, False, 2.0, True) == 3.0
+assert first_present(False, 1.0, False, 2.0, False) == -1.0
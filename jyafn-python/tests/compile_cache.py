@@ -0,0 +1,24 @@
+import jyafn as fn
+
+
+@fn.graph
+def scale(x: fn.scalar):
+    return 2.0 * x
+
+
+def offset_by(k: float) -> fn.Graph:
+    @fn.graph
+    def offset(a: fn.scalar):
+        return scale(a) + k
+
+    return offset.build()
+
+
+cache = fn.CompileCache()
+assert offset_by(1.0).compile(cache=cache)(2.0) == 5.0
+assert (cache.hits, cache.misses) == (0, 2)
+
+# Only the region of the outer graph changes; the subgraph is reused.
+assert offset_by(3.0).compile(cache=cache)(2.0) == 7.0
+assert (cache.hits, cache.misses) == (1, 3)
+assert len(cache) == 3
@@ -0,0 +1,35 @@
+import jyafn as fn
+
+
+@fn.graph
+def factory(age: fn.scalar, weights: fn.tensor[2]) -> fn.scalar:
+    return age + weights[0] + weights[1]
+
+
+g = factory.build()
+g.constrain_input("age", min=0, max=150)
+g.constrain_input("weights", min=0.0)
+assert "jyafn.input_constraints" in g.metadata
+
+f = fn.Graph.load(g.dump()).compile()
+assert f(30.0, [1.0, 2.0]) == 33.0
+
+for args, field in [
+    ((-1.0, [1.0, 2.0]), "age"),
+    ((151.0, [1.0, 2.0]), "age"),
+    ((float("nan"), [1.0, 2.0]), "age"),
+    ((30.0, [1.0, -2.0]), "weights"),
+]:
+    try:
+        f(*args)
+    except Exception as e:
+        assert f"input `{field}` is out of range" in str(e), e
+    else:
+        raise AssertionError(f"expected {args} to be rejected")
+
+try:
+    g.constrain_input("height", max=3.0)
+except Exception:
+    pass
+else:
+    raise AssertionError("expected unknown input to be rejected")
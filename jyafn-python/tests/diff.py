@@ -0,0 +1,44 @@
+import jyafn as fn
+
+with fn.Graph() as g:
+    a = fn.input("a")
+    b = fn.input("b")
+    fn.ret(a + b, fn.Layout.scalar())
+
+with fn.Graph() as other:
+    a = fn.input("a")
+    b = fn.input("b")
+    fn.input("c")
+    fn.ret(a + b, fn.Layout.scalar())
+
+no_change = {
+    "added_inputs": [],
+    "removed_inputs": [],
+    "changed_inputs": {},
+    "moved_inputs": {},
+    "output_layout": None,
+    "node_count": None,
+    "added_metadata": [],
+    "removed_metadata": [],
+    "changed_metadata": [],
+}
+assert g.diff(g) == no_change
+
+# The only change is the extra input.
+diff = g.diff(other)
+assert diff == {**no_change, "added_inputs": ["c"]}
+assert other.diff(g)["removed_inputs"] == ["c"]
+
+with fn.Graph() as swapped:
+    b = fn.input("b")
+    a = fn.input("a")
+    fn.ret(a + b, fn.Layout.scalar())
+
+# Reordering the inputs changes the input layout.
+assert g.diff(swapped) == {
+    **no_change,
+    "moved_inputs": {
+        "a": {"before": 0, "after": 1},
+        "b": {"before": 1, "after": 0},
+    },
+}
@@ -0,0 +1,11 @@
+import jyafn as fn
+
+
+@fn.func
+def ratio(num: fn.scalar, den: fn.scalar) -> fn.scalar:
+    return num.div_or(den, -1.0)
+
+
+assert ratio(6.0, 3.0) == 2.0
+assert ratio(6.0, 0.0) == -1.0
+assert ratio(6.0, -0.0) == -1.0
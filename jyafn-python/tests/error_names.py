@@ -0,0 +1,20 @@
+import jyafn as fn
+
+
+@fn.func
+def checked(x: fn.scalar) -> fn.scalar:
+    fn.assert_(x > 0.0, "x must be positive")
+    fn.assert_(x < 10.0, "x must be small")
+    return x
+
+
+assert checked.error_names() == ["x must be positive", "x must be small"]
+assert checked(1.0) == 1.0
+
+for value, expected in [(-1.0, "x must be positive"), (11.0, "x must be small")]:
+    try:
+        checked(value)
+    except Exception as e:
+        assert checked.error_names()[e.error_id] == expected, e
+    else:
+        raise Exception("should raise")
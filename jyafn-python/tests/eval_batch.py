@@ -0,0 +1,22 @@
+import numpy as np
+
+import jyafn as fn
+
+
+@fn.func
+def double(x: fn.scalar) -> fn.scalar:
+    fn.assert_(x >= 0.0, "x must be positive")
+    return x + x
+
+
+assert double.eval_batch([{"x": 1.0}, {"x": 2.5}]) == [2.0, 5.0]
+
+try:
+    double.eval_batch([{"x": 1.0}, {"x": -2.5}])
+except Exception as e:
+    assert "row 1" in str(e)
+else:
+    raise AssertionError("negative row should fail")
+
+raw = double.eval_raw_batch(np.array([1.0, 2.5, 0.0]).tobytes(), 3)
+assert list(np.frombuffer(raw)) == [2.0, 5.0, 0.0]
@@ -0,0 +1,31 @@
+import jyafn as fn
+
+
+@fn.func
+def bucket(x: fn.scalar, s: fn.symbol) -> fn.scalar:
+    return fn.feature_hash((x, s), 16, seed=42)
+
+
+counts = [0] * 16
+for i in range(10_000):
+    b = bucket(i, "foo")
+    assert b == bucket(i, "foo")
+    assert b == int(b)
+    assert 0 <= b < 16
+    counts[int(b)] += 1
+
+print(counts)
+assert all(400 < count < 850 for count in counts)
+
+
+@fn.func
+def const_bucket() -> fn.scalar:
+    return fn.feature_hash("foo", 16)
+
+
+@fn.func
+def symbol_bucket(s: fn.symbol) -> fn.scalar:
+    return fn.feature_hash(s, 16)
+
+
+assert const_bucket() == symbol_bucket("foo")
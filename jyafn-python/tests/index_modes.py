@@ -0,0 +1,41 @@
+import jyafn as fn
+
+
+@fn.func
+def negative(idx: fn.scalar, foo: fn.scalar, bar: fn.scalar, baz: fn.scalar):
+    return fn.index([foo, bar, baz]).get(idx, mode="negative_wrap")
+
+
+@fn.func
+def cyclic(idx: fn.scalar, foo: fn.scalar, bar: fn.scalar, baz: fn.scalar):
+    return fn.index([foo, bar, baz]).get(idx, mode="modulo")
+
+
+assert negative(-1, 1, 2, 3) == 3
+assert negative(0, 1, 2, 3) == 1
+assert cyclic(4, 1, 2, 3) == 2
+assert cyclic(-1, 1, 2, 3) == 3
+
+try:
+    negative(-4, 1, 2, 3)
+except Exception:
+    pass
+else:
+    assert False, "index should be out of bounds"
+
+
+@fn.func
+def last(idx: fn.scalar, foo: fn.scalar, bar: fn.scalar, baz: fn.scalar):
+    return fn.index([foo, bar, baz])[idx]
+
+
+assert last(-1, 1, 2, 3) == 3
+assert last(-3, 1, 2, 3) == 1
+
+for idx in [-4, 3]:
+    try:
+        last(idx, 1, 2, 3)
+    except Exception:
+        pass
+    else:
+        assert False, "index should be out of bounds"
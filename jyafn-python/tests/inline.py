@@ -0,0 +1,27 @@
+import jyafn as fn
+
+
+@fn.graph
+def simple(a: fn.scalar, b: fn.scalar):
+    fn.assert_(a >= 0.0, "a must be non-negative")
+    return 2.0 * a + b
+
+
+@fn.func
+def call_simple(a: fn.scalar, b: fn.scalar):
+    return simple(a, b)
+
+
+@fn.func
+def inline_simple(a: fn.scalar, b: fn.scalar):
+    return simple(a, b, inline=True)
+
+
+for a, b in [(2.0, 3.0), (0.0, -1.0), (1.5, 0.5)]:
+    assert call_simple(a, b) == inline_simple(a, b) == 2.0 * a + b
+
+try:
+    inline_simple(-1.0, 0.0)
+    assert False, "expected an error"
+except Exception as e:
+    assert "a must be non-negative" in str(e)
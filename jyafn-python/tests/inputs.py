@@ -0,0 +1,18 @@
+import jyafn as fn
+
+
+@fn.graph
+def scored(weight: fn.scalar, count: fn.integer, values: fn.list[fn.scalar, 3]):
+    return weight * count + values[0]
+
+
+inputs = scored.build().inputs
+names = [name for name, _ in inputs]
+assert names == ["weight", "count", "values"], names
+
+layouts = [layout.to_json() for _, layout in inputs]
+assert layouts == [
+    fn.Layout.scalar().to_json(),
+    fn.integer.make_layout(()).to_json(),
+    fn.Layout.list_of(fn.Layout.scalar(), 3).to_json(),
+], layouts
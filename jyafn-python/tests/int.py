@@ -0,0 +1,33 @@
+import jyafn as fn
+
+
+@fn.func
+def arithmetic(i: fn.integer, x: fn.scalar) -> fn.tuple[fn.integer, fn.scalar]:
+    truncated = x.to_int()
+    return (i * truncated + 1, (i - truncated).to_float())
+
+
+assert arithmetic(2, 3.7) == (7, -1.0)
+assert arithmetic(-1, -3.7) == (4, 2.0)
+
+
+@fn.func
+def index(i: fn.integer, foo: fn.scalar, bar: fn.scalar, baz: fn.scalar) -> fn.scalar:
+    return fn.index([foo, bar, baz])[i]
+
+
+assert index(1, 10.0, 20.0, 30.0) == 20.0
+
+try:
+    index(3, 10.0, 20.0, 30.0)
+except Exception:
+    pass
+else:
+    raise AssertionError("index out of bounds should raise")
+
+try:
+    arithmetic(1.5, 0.0)
+except TypeError:
+    pass
+else:
+    raise AssertionError("ints should not accept floats")
@@ -0,0 +1,22 @@
+import math
+import jyafn as fn
+
+
+@fn.func
+def special(x: fn.scalar) -> fn.tuple[fn.bool, fn.bool]:
+    return (x.isnan(), x.isfinite())
+
+
+assert special(1.5) == (False, True)
+assert special(math.nan) == (True, False)
+assert special(math.inf) == (False, False)
+assert special(-math.inf) == (False, False)
+
+
+@fn.func
+def nan_to_zero(x: fn.scalar) -> fn.scalar:
+    return x.isnan().choose(0.0, x)
+
+
+assert nan_to_zero(math.nan) == 0.0
+assert nan_to_zero(2.0) == 2.0
@@ -0,0 +1,23 @@
+import itertools
+import jyafn as fn
+
+
+@fn.func
+def total(xs: fn.tensor[1000]) -> fn.scalar:
+    return fn.norm_l1(xs)
+
+
+assert total(float(i) for i in range(1000)) == 499500.0
+assert total(iter([1.0] * 1000)) == 1000.0
+
+for wrong in [
+    (float(i) for i in range(999)),
+    (float(i) for i in range(1001)),
+    itertools.count(),
+]:
+    try:
+        total(wrong)
+    except TypeError as e:
+        assert "expected array of size 1000" in str(e), e
+    else:
+        raise AssertionError("expected a length error")
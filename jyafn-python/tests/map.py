@@ -0,0 +1,22 @@
+import jyafn as fn
+
+
+with fn.Graph() as g:
+    features = fn.input("features", fn.Layout.map_of(fn.Layout.scalar()))
+    key = fn.input("key", fn.Layout.symbol())
+    has_a, a = features.get("a")
+    has_key, value = features.get(key)
+    fn.ret(
+        (has_a.choose(a, 0.0), (has_key, value)),
+        fn.Layout.tuple_of(
+            (fn.Layout.scalar(), fn.Layout.optional(fn.Layout.scalar()))
+        ),
+    )
+
+assert str(g.input_layout) == "{ features: map[scalar], key: symbol }"
+
+f = g.compile()
+assert f(features={}, key="a") == (0.0, None)
+assert f(features={"a": 1.5}, key="b") == (1.5, None)
+assert f(features={"a": 1.5, "b": 2.5, "c": 3.5}, key="c") == (1.5, 3.5)
+assert f.eval({"features": {"b": -1.0}, "key": "b"}) == (0.0, -1.0)
@@ -0,0 +1,16 @@
+import math
+
+import jyafn as fn
+
+
+@fn.func
+def min_max(a: fn.scalar, b: fn.scalar) -> fn.tuple[fn.scalar, fn.scalar, fn.scalar]:
+    return fn.min(a, b), fn.max(a, b), a.max(0.0)
+
+
+assert min_max(1.0, 2.0) == (1.0, 2.0, 1.0)
+assert min_max(-1.0, -2.0) == (-2.0, -1.0, 0.0)
+assert min_max(float("nan"), 2.0) == (2.0, 2.0, 0.0)
+
+lower, upper, floored = min_max(float("nan"), float("nan"))
+assert math.isnan(lower) and math.isnan(upper)
@@ -0,0 +1,17 @@
+import jyafn as fn
+
+
+@fn.func
+def modulo(a: fn.scalar, b: fn.scalar) -> fn.scalar:
+    return a % b
+
+
+@fn.func
+def floor_division(a: fn.scalar, b: fn.scalar) -> fn.scalar:
+    return a // b
+
+
+for a, b in [(7.0, 3.0), (-7.0, 3.0), (7.0, -3.0), (-7.0, -3.0), (-7.5, 2.0), (1.0, 0.1)]:
+    assert modulo(a=a, b=b) == a % b, (a, b)
+    assert floor_division(a=a, b=b) == a // b, (a, b)
+    assert floor_division(a=a, b=b) * b + modulo(a=a, b=b) == a, (a, b)
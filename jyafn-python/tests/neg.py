@@ -0,0 +1,29 @@
+import jyafn as fn
+
+
+@fn.func
+def neg(a: fn.scalar) -> fn.scalar:
+    return -a
+
+
+assert neg(2.5) == -2.5
+
+
+@fn.func
+def not_(a: fn.scalar) -> fn.scalar:
+    return (a > 0.0).not_().choose(1.0, 0.0)
+
+
+assert not_(1.0) == 0.0
+assert not_(-1.0) == 1.0
+
+try:
+
+    @fn.func
+    def neg_bool(a: fn.scalar) -> fn.scalar:
+        return (-(a > 0.0)).choose(1.0, 0.0)
+
+except TypeError as e:
+    assert ".not_()" in str(e)
+else:
+    raise Exception("should fail")
@@ -0,0 +1,14 @@
+import jyafn as fn
+
+
+@fn.graph
+def g(x: fn.scalar, y: fn.scalar) -> fn.scalar:
+    return fn.sqrt(x * y + 1.0) > x
+
+
+nodes = g.build().nodes
+assert [node.op for node in nodes] == ["Mul", "Add", "Call", "Gt"], nodes
+assert [node.id for node in nodes] == [0, 1, 2, 3]
+assert str(nodes[0].type) == "scalar"
+assert str(nodes[3].type) == "bool"
+assert len(nodes[1].args) == 2
@@ -0,0 +1,68 @@
+import math
+
+import numpy as np
+import jyafn as fn
+
+
+@fn.func
+def hypot(x: fn.scalar, y: fn.scalar) -> fn.scalar:
+    return fn.hypot(x, y)
+
+
+assert hypot(3.0, 4.0) == np.hypot(3.0, 4.0)
+assert hypot(3e300, 4e300) == np.hypot(3e300, 4e300)
+
+
+@fn.func
+def norms(v: fn.tensor[3]):
+    return [fn.norm_l1(v), fn.norm_l2(v), fn.norm_inf(v), np.linalg.norm(v)]
+
+
+for v in [[3.0, -4.0, 12.0], [3e300, -4e300, 12e300], [1e-300, 0.0, -1e-300]]:
+    expected = [
+        np.linalg.norm(v, 1),
+        np.linalg.norm(v),
+        np.linalg.norm(v, np.inf),
+        np.linalg.norm(v),
+    ]
+    assert np.allclose(norms(v), expected, rtol=1e-12, atol=0.0), (v, norms(v))
+
+
+@fn.func
+def normal_cdf(x: fn.scalar) -> fn.scalar:
+    return fn.norm(x)
+
+
+assert normal_cdf(0.0) == 0.5
+
+
+@fn.func
+def orders(v: fn.tensor[3]):
+    return [
+        fn.vector_norm(v),
+        fn.vector_norm(v, 1),
+        fn.vector_norm(v, 2),
+        fn.vector_norm(v, math.inf),
+    ]
+
+
+for v in [[3.0, -4.0, 12.0], [3e300, -4e300, 12e300], [0.0, 0.0, 0.0]]:
+    expected = [
+        np.linalg.norm(v),
+        np.linalg.norm(v, 1),
+        np.linalg.norm(v, 2),
+        np.linalg.norm(v, np.inf),
+    ]
+    assert np.allclose(orders(v), expected, rtol=1e-12, atol=0.0), (v, orders(v))
+
+
+try:
+
+    @fn.func
+    def bad_order(v: fn.tensor[3]) -> fn.scalar:
+        return fn.vector_norm(v, 3)
+
+except ValueError as e:
+    assert "unsupported norm order" in str(e), e
+else:
+    raise AssertionError("expected a norm of order 3 to be rejected")
@@ -0,0 +1,29 @@
+import math
+from statistics import NormalDist
+
+import jyafn as fn
+
+
+@fn.func
+def normal(
+    x: fn.scalar,
+) -> fn.tuple[fn.scalar, fn.scalar, fn.scalar, fn.scalar, fn.scalar]:
+    return x.erf(), x.erfc(), fn.norm_cdf(x), fn.norm_pdf(x), fn.norm(x)
+
+
+for x in [-30.0, -5.0, -1.0, 0.0, 0.5, 3.0, 8.0]:
+    erf, erfc, cdf, pdf, norm = normal(x)
+    assert math.isclose(erf, math.erf(x), rel_tol=1e-12)
+    assert math.isclose(erfc, math.erfc(x), rel_tol=1e-12)
+    assert math.isclose(cdf, 0.5 * math.erfc(-x / math.sqrt(2)), rel_tol=1e-12)
+    assert math.isclose(pdf, NormalDist().pdf(x), rel_tol=1e-12)
+    assert cdf == norm
+
+
+@fn.func
+def quantile(p: fn.scalar) -> fn.scalar:
+    return p.norm_inv()
+
+
+for p in [1e-300, 1e-10, 0.025, 0.5, 0.975]:
+    assert math.isclose(quantile(p), NormalDist().inv_cdf(p), rel_tol=1e-12, abs_tol=1e-15)
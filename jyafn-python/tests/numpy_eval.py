@@ -0,0 +1,47 @@
+import time
+import numpy as np
+import jyafn as fn
+
+N = 1000
+
+
+@fn.func
+def scale(x: fn.list[fn.scalar, N]) -> fn.list[fn.scalar, N]:
+    return [2.0 * xi + 1.0 for xi in x]
+
+
+features = np.random.default_rng(0).normal(size=N)
+
+from_list = scale.eval({"x": features.tolist()})
+from_numpy = scale.eval({"x": features}, numpy=True)
+assert isinstance(from_list, list)
+assert isinstance(from_numpy, np.ndarray)
+assert from_numpy.dtype == np.float64
+assert from_numpy.tolist() == from_list
+assert scale(features) == from_list
+
+try:
+    scale.eval({"x": features[:-1]})
+except ValueError:
+    pass
+else:
+    raise Exception("should raise")
+
+try:
+    scale.eval({"x": features.reshape(10, 100)})
+except ValueError:
+    pass
+else:
+    raise Exception("should raise")
+
+
+def timed(f, n=200):
+    start = time.perf_counter()
+    for _ in range(n):
+        f()
+    return time.perf_counter() - start
+
+
+list_time = timed(lambda: scale.eval({"x": features.tolist()}))
+numpy_time = timed(lambda: scale.eval({"x": features}, numpy=True))
+assert numpy_time < list_time, (numpy_time, list_time)
@@ -0,0 +1,17 @@
+import jyafn as fn
+
+
+@fn.func
+def folded(a: fn.scalar) -> fn.scalar:
+    ten = (fn.const(2.0) + fn.const(3.0)) * fn.const(2.0)
+    unused = a * a
+    return a + ten
+
+
+assert folded(1.0) == 11.0
+
+stats = folded.opt_stats
+assert stats["nodes_before"] == 4
+assert stats["const_folded"] == 2
+assert stats["unreachable_removed"] == 3
+assert stats["nodes_after"] == 1
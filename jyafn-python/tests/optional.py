@@ -0,0 +1,15 @@
+import jyafn as fn
+
+
+with fn.Graph() as g:
+    present, x = fn.input("x", fn.Layout.optional(fn.Layout.scalar()))
+    y = fn.input("y", fn.Layout.scalar())
+    total = present.choose(x + y, y)
+    fn.ret((present, total), fn.Layout.optional(fn.Layout.scalar()))
+
+assert str(g.input_layout) == "{ x: ?scalar, y: scalar }"
+
+f = g.compile()
+assert f(x=1.0, y=2.0) == 3.0
+assert f(x=None, y=2.0) is None
+assert f.eval({"y": 2.0}) is None
@@ -0,0 +1,22 @@
+import jyafn as fn
+
+
+with fn.Graph() as g:
+    flag = fn.input("flag", fn.Layout.bool(), pinnable=True)
+    x = fn.input("x", fn.Layout.scalar())
+    fn.ret(flag.choose(x * 2.0, x + 1.0), fn.Layout.scalar())
+
+
+unpinned = g.compile()
+assert unpinned(flag=False, x=3.0) == 4.0
+
+pinned = g.compile(pin={"flag": True})
+assert pinned(x=3.0) == 6.0
+assert "flag" not in str(pinned.input_layout)
+
+try:
+    g.compile(pin={"x": 1.0})
+except Exception as e:
+    assert "not pinnable" in str(e)
+else:
+    raise Exception("should fail")
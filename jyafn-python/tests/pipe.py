@@ -0,0 +1,15 @@
+import jyafn as fn
+
+
+@fn.graph
+def normalize(x: fn.scalar, w: fn.scalar):
+    return {"z": x / 10.0, "w": w, "raw": x}
+
+
+@fn.graph
+def score(w: fn.scalar, z: fn.scalar):
+    return w * z
+
+
+pipeline = normalize.build().pipe(score.build())
+assert pipeline.compile()(5.0, 3.0) == 1.5
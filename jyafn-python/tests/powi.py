@@ -0,0 +1,20 @@
+import jyafn as fn
+import math
+
+exponents = [0, 1, 2, 3, 10, -1, -2]
+
+with fn.Graph() as g:
+    x = fn.input("x", fn.Layout.scalar())
+    fn.ret([x**n for n in exponents], fn.Layout.list_of(fn.Layout.scalar(), len(exponents)))
+
+# Integer literals use `Powi` instead of calling `powf`.
+assert '"Powi"' in g.to_json()
+assert "powf" not in g.to_json()
+
+f = g.compile()
+for x in [1.5, -0.75, 2.0, 0.0]:
+    for n, got in zip(exponents, f(x=x)):
+        if x == 0.0 and n < 0:
+            assert got == math.inf
+        else:
+            assert math.isclose(got, math.pow(x, n), rel_tol=1e-12), (x, n, got)
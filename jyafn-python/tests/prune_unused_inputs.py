@@ -0,0 +1,23 @@
+import jyafn as fn
+
+
+with fn.Graph() as g:
+    a = fn.input("a", fn.Layout.scalar())
+    dead = fn.input("dead", fn.Layout.scalar())
+    b = fn.input("b", fn.Layout.scalar())
+    asserted = fn.input("asserted", fn.Layout.scalar())
+    fn.assert_(asserted >= 0.0, "must be positive")
+
+    # A diamond where the right branch is dead.
+    top = a * b
+    left = top + 1.0
+    right = top + dead
+    fn.ret(left * top, fn.Layout.scalar())
+
+
+assert g.prune_unused_inputs() == ["dead"]
+assert g.prune_unused_inputs() == []
+assert "dead" not in str(g.input_layout)
+
+f = g.compile()
+assert f(a=2.0, b=3.0, asserted=1.0) == 42.0
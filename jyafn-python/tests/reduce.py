@@ -0,0 +1,50 @@
+import numpy as np
+import jyafn as fn
+
+
+@fn.func
+def reductions(x: fn.tensor[9], y: fn.tensor[9]) -> fn.tensor[3]:
+    return [fn.sum(x), fn.mean(x), fn.dot(x, y)]
+
+
+rng = np.random.default_rng(42)
+for scale in [1.0, 1e-300, 1e300]:
+    x = rng.normal(size=9) * scale
+    y = rng.normal(size=9)
+    expected = [np.sum(x), np.mean(x), np.dot(x, y)]
+    result = reductions(x.tolist(), y.tolist())
+    assert np.allclose(result, expected, rtol=1e-12, atol=0.0), (result, expected)
+
+assert reductions([1.0] * 9, [2.0] * 9) == [9.0, 1.0, 18.0]
+
+
+@fn.func
+def with_consts(x: fn.tensor[2]) -> fn.tensor[3]:
+    return [fn.sum([x[0], 1.5, x[1]]), fn.mean([x[0], 2.0]), fn.dot(x, [3.0, -1.0])]
+
+
+assert with_consts([1.0, 2.0]) == [4.5, 1.5, 1.0]
+
+
+try:
+
+    @fn.func
+    def empty_mean(x: fn.tensor[2]) -> fn.scalar:
+        return fn.mean([])
+
+except ValueError:
+    pass
+else:
+    raise AssertionError("expected the mean of no values to be rejected")
+
+
+try:
+
+    @fn.func
+    def mismatched_dot(x: fn.tensor[2], y: fn.tensor[3]) -> fn.scalar:
+        return fn.dot(x, y)
+
+except Exception as e:
+    assert "lengths 2 and 3" in str(e), e
+else:
+    raise AssertionError("expected a dot product of lists of different lengths to fail")
@@ -0,0 +1,28 @@
+import jyafn as fn
+
+
+@fn.graph
+def factory(a: fn.scalar, b: fn.scalar):
+    return {"sum": a + b, "diff": a - b}
+
+
+g = factory.build()
+g.rename_input("a", "x")
+g.rename_output_field("sum", "total")
+
+try:
+    g.rename_input("b", "x")
+except Exception as e:
+    assert "already exists" in str(e)
+else:
+    raise Exception("should fail")
+
+try:
+    g.rename_output_field("missing", "other")
+except Exception as e:
+    assert "no output field" in str(e)
+else:
+    raise Exception("should fail")
+
+f = fn.Graph.load(g.dump()).compile()
+assert f(x=3.0, b=1.0) == {"total": 4.0, "diff": 2.0}
@@ -0,0 +1,16 @@
+import jyafn as fn
+
+
+@fn.graph
+def absolute(a: fn.scalar) -> fn.scalar:
+    return abs(a)
+
+
+g = absolute.build()
+
+annotated = g.render_ir()
+assert "# n0: Abs" in annotated, annotated
+
+plain = g.render_ir(annotated=False)
+assert "#" not in plain, plain
+assert plain == g.render()
@@ -77,3 +77,31 @@ def with_resources(x: fn.scalar) -> fn.scalar:
 serialized = with_resources.write("with_resources.jyafn")
 deserialized = fn.read_fn("with_resources.jyafn")
 assert deserialized(2.5) == 1.0
+
+
+resource_type = fn.ResourceType.from_json('{"type": "Dummy"}')
+resource = resource_type.load("my_resource", b"1.0")
+assert resource.top_k.input_layout.struct_keys() == ["x", "k"]
+
+
+@fn.func
+def top_k(x: fn.list[fn.scalar, 4]) -> fn.scalar:
+    # Keyword arguments are matched by name, in any order.
+    top = resource.top_k(k=2.0, x=x)
+
+    for call in [
+        lambda: resource.top_k(x=x),
+        lambda: resource.top_k(x=x, k=2.0, y=1.0),
+        lambda: resource.top_k(x, x=x, k=2.0),
+    ]:
+        try:
+            call()
+        except TypeError:
+            traceback.print_exc()
+        else:
+            raise Exception("should raise")
+
+    return top["values"][0] + top["values"][1]
+
+
+assert top_k([3.0, 1.0, 4.0, 2.0]) == 7.0
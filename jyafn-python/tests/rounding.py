@@ -0,0 +1,27 @@
+import jyafn as fn
+import math
+
+
+@fn.func
+def rounded(x: fn.scalar) -> fn.tensor[4]:
+    return [math.floor(x), math.ceil(x), round(x), math.trunc(x)]
+
+
+@fn.func
+def methods(x: fn.scalar) -> fn.tensor[4]:
+    return [x.floor(), x.ceil(), x.round(), x.trunc()]
+
+
+for x in [0.0, 0.5, 1.5, 2.5, -0.5, -1.5, -2.5, -0.25, -3.7, 3.7, 1e300, -1e300]:
+    expected = [math.floor(x), math.ceil(x), round(x), math.trunc(x)]
+    assert rounded(x) == expected, (x, rounded(x), expected)
+    assert methods(x) == expected, (x, methods(x), expected)
+
+
+@fn.func
+def floor_div(a: fn.scalar, b: fn.scalar) -> fn.scalar:
+    return a // b
+
+
+assert floor_div(7.0, 2.0) == 3.0
+assert floor_div(-7.0, 2.0) == -4.0
@@ -0,0 +1,20 @@
+import jyafn as fn
+import json
+
+
+@fn.func
+def total(prices: fn.list[fn.scalar, 2], at: fn.datetime) -> fn.scalar:
+    return prices[0] + prices[1]
+
+
+schema = json.loads(total.input_schema_json())
+assert schema["type"] == "object"
+assert schema["required"] == ["prices", "at"]
+assert schema["properties"]["prices"] == {
+    "type": "array",
+    "items": {"type": "number"},
+    "minItems": 2,
+    "maxItems": 2,
+}
+assert schema["properties"]["at"]["type"] == "string"
+assert json.loads(total.output_schema_json()) == {"type": "number"}
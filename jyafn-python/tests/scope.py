@@ -0,0 +1,56 @@
+import ctypes
+import jyafn as fn
+
+
+@ctypes.CFUNCTYPE(ctypes.c_double, ctypes.c_double)
+def double(x):
+    return 2.0 * x
+
+
+with fn.scope():
+    fn.inscribe_pfunc("test_double", double, ["scalar"], "scalar")
+
+    with fn.Graph() as g:
+        x = fn.input("x")
+        fn.ret(fn.call_pfunc("test_double", x) + 1.0, fn.Layout.scalar())
+
+    func = g.compile()
+    assert func(x=3.0) == 7.0
+
+    with fn.scope():
+        fn.inscribe_pfunc("test_triple", double, ["scalar"], "scalar")
+
+    # The inner scope only removed what was inscribed in it.
+    with fn.Graph():
+        fn.call_pfunc("test_double", 1.0)
+        try:
+            fn.call_pfunc("test_triple", 1.0)
+            assert False, "test_triple should not be inscribed"
+        except Exception as e:
+            assert "no pure function" in str(e), e
+
+# Gone after the scope, so it can be inscribed again in another one.
+with fn.Graph():
+    try:
+        fn.call_pfunc("test_double", 1.0)
+        assert False, "test_double should not be inscribed"
+    except Exception as e:
+        assert "no pure function" in str(e), e
+
+# Already compiled functions keep working.
+assert func(x=3.0) == 7.0
+
+with fn.scope():
+    fn.inscribe_pfunc("test_double", double, ["scalar"], "scalar")
+
+# Foreign functions must match the signature.
+for func, signature in [
+    (double, ["int"]),
+    (double, ["scalar", "scalar"]),
+    (ctypes.cast(double, ctypes.c_void_p).value, ["scalar"]),
+]:
+    try:
+        fn.inscribe_pfunc("test_mismatch", func, signature, "scalar")
+        assert False, "signature should not match"
+    except TypeError:
+        pass
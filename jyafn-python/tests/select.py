@@ -0,0 +1,21 @@
+import jyafn as fn
+
+
+@fn.func
+def two_heads(
+    x: fn.scalar,
+) -> fn.struct[{"score": fn.scalar, "embedding": fn.list[fn.scalar, 2]}]:
+    return {"score": x + 1.0, "embedding": [x, 2.0 * x]}
+
+
+assert two_heads.eval({"x": 2.0}, select=["score"]) == {"score": 3.0}
+assert two_heads.eval({"x": 2.0}, select=["embedding.1", "score"]) == {
+    "embedding.1": 4.0,
+    "score": 3.0,
+}
+
+try:
+    two_heads.eval({"x": 2.0}, select=["logits"])
+    raise AssertionError("unknown path should fail")
+except Exception as e:
+    assert "logits" in str(e)
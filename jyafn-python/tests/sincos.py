@@ -0,0 +1,23 @@
+import math
+
+import jyafn as fn
+
+
+@fn.func
+def sincos(x: fn.scalar) -> fn.tuple[fn.scalar, fn.scalar, fn.scalar, fn.scalar]:
+    sin, cos = fn.sincos(x)
+    return sin, cos, fn.sin(x), fn.cos(x)
+
+
+for x in [0.0, 1.25, -2.5]:
+    sin, cos, expected_sin, expected_cos = sincos(x)
+    assert math.isclose(sin, expected_sin, abs_tol=1e-12)
+    assert math.isclose(cos, expected_cos, abs_tol=1e-12)
+
+
+@fn.func
+def modf(x: fn.scalar) -> fn.tuple[fn.scalar, fn.scalar]:
+    return fn.modf(x)
+
+
+assert modf(-2.5) == (-0.5, -2.0)
@@ -0,0 +1,16 @@
+import jyafn as fn
+
+
+@fn.func
+def two_x_plus_y(x: fn.scalar, y: fn.scalar) -> fn.scalar:
+    return 2.0 * x + y
+
+
+assert "return 2.0 * x + y" in two_x_plus_y.source
+
+g = fn.Graph.load(two_x_plus_y.get_graph().dump())
+assert g.source == two_x_plus_y.source
+assert g.compile()(2.0, 1.0) == 5.0
+
+g.set_source("hello")
+assert fn.Graph.load(g.dump()).source == "hello"
@@ -0,0 +1,20 @@
+import jyafn as fn
+
+
+with fn.Graph() as g:
+    x = fn.input("x", fn.Layout.symbol())
+    is_known = (x == fn.const("apple")) | (x == fn.const("banana"))
+    fn.ret(is_known.choose(fn.const("fruit"), fn.const("other")), fn.Layout.symbol())
+
+assert sorted(g.symbols) == ["apple", "banana", "fruit", "other"]
+assert g.symbols == sorted(g.symbols, key=fn.symbol_hash)
+
+f = g.compile()
+assert f.symbols == g.symbols
+assert f.symbol_index("apple") == fn.symbol_hash("apple")
+assert f.symbol_index("cherry") is None
+assert "cherry" not in f.symbols
+
+assert f(x="apple") == "fruit"
+assert f(x="cherry") == "other"
+assert "cherry" not in f.symbols
@@ -0,0 +1,65 @@
+import jyafn as fn
+import numpy as np
+import math
+
+
+@fn.func
+def exp(x: fn.scalar) -> fn.scalar:
+    return np.exp(x)
+
+
+assert '"exp"' in exp.get_graph().to_json()
+assert exp(1.0) == math.exp(1.0)
+
+
+@fn.func
+def maximum(a: fn.scalar, b: fn.scalar) -> fn.scalar:
+    return np.maximum(a, b)
+
+
+assert "Choose" in maximum.get_graph().to_json()
+assert maximum(1.0, 2.0) == 2.0
+assert maximum(3.0, 2.0) == 3.0
+# NaNs propagate from either operand, like in numpy:
+assert math.isnan(maximum(math.nan, 1.0))
+assert math.isnan(maximum(1.0, math.nan))
+
+
+@fn.func
+def minimum(a: fn.scalar, b: fn.scalar) -> fn.scalar:
+    return np.minimum(a, b)
+
+
+assert minimum(1.0, 2.0) == 1.0
+assert minimum(3.0, 2.0) == 2.0
+assert math.isnan(minimum(math.nan, 1.0))
+assert math.isnan(minimum(1.0, math.nan))
+
+
+@fn.func
+def fmax(a: fn.scalar, b: fn.scalar) -> fn.scalar:
+    return np.fmax(a, b)
+
+
+# Unlike `maximum`, `fmax` ignores NaNs:
+assert fmax(math.nan, 1.0) == np.fmax(math.nan, 1.0) == 1.0
+assert fmax(1.0, math.nan) == np.fmax(1.0, math.nan) == 1.0
+assert math.isnan(np.maximum(math.nan, 1.0))
+
+
+@fn.func
+def arctan2(y: fn.scalar, x: fn.scalar) -> fn.scalar:
+    return np.arctan2(y, x) + np.power(x, 2.0)
+
+
+assert '"atan2"' in arctan2.get_graph().to_json()
+assert arctan2(1.0, 2.0) == math.atan2(1.0, 2.0) + 4.0
+
+
+@fn.func
+def mixed(x: fn.scalar) -> fn.tensor[3]:
+    # Arrays fall back to numpy broadcasting:
+    return np.add(x, np.array([1.0, 2.0, 3.0]))
+
+
+assert list(mixed(1.0)) == [2.0, 3.0, 4.0]
@@ -0,0 +1,21 @@
+import jyafn as fn
+
+
+with fn.Graph() as g:
+    a = fn.input("a", fn.Layout.scalar())
+    fn.ret(a + 1.0, fn.Layout.scalar())
+
+g.verify()
+
+
+with fn.Graph() as failing:
+    a = fn.input("a", fn.Layout.scalar())
+    fn.assert_(False, "never")
+    fn.ret(a + 1.0, fn.Layout.scalar())
+
+try:
+    failing.verify()
+except Exception as e:
+    assert "illegal" in str(e).lower(), e
+else:
+    raise Exception("should fail")
@@ -0,0 +1,19 @@
+import jyafn as fn
+
+
+@fn.func
+def clip_negative(xs: fn.list[fn.scalar, 4]) -> fn.list[fn.scalar, 4]:
+    return fn.where([x >= 0.0 for x in xs], xs, 0.0)
+
+
+assert clip_negative([1.0, -2.0, 3.0, -4.0]) == [1.0, 0.0, 3.0, 0.0]
+assert clip_negative([-1.0, 2.0, -3.0, 4.0]) == [0.0, 2.0, 0.0, 4.0]
+
+
+@fn.func
+def mixed(x: fn.scalar) -> fn.list[fn.scalar, 3]:
+    return fn.where([True, x > 0.0, False], [1.0, x, 3.0], -1.0)
+
+
+assert mixed(2.0) == [1.0, 2.0, -1.0]
+assert mixed(-2.0) == [1.0, -1.0, -1.0]
@@ -0,0 +1,23 @@
+//! The harness shared by the benchmarks.
+
+use std::time::{Duration, Instant};
+
+/// The number of rows each benchmark evaluates per run.
+pub const N_ROWS: usize = 10_000;
+/// The number of times each benchmark is run.
+pub const N_RUNS: usize = 20;
+
+/// Runs `f` a few times and reports the best time per row.
+pub fn bench(name: &str, mut f: impl FnMut()) {
+    let mut best = Duration::MAX;
+    for _ in 0..N_RUNS {
+        let start = Instant::now();
+        f();
+        best = best.min(start.elapsed());
+    }
+
+    println!(
+        "{name:<20} {:>10.1} ns/row",
+        best.as_nanos() as f64 / N_ROWS as f64
+    );
+}
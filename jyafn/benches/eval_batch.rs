@@ -0,0 +1,62 @@
+//! Compares evaluating many inputs with [`Function::eval_batch`] against calling
+//! [`Function::eval`] in a loop. Run with `cargo bench -p jyafn --bench eval_batch`.
+
+mod common;
+
+use byte_slice_cast::*;
+
+use jyafn::layout::{Layout, RefValue};
+use jyafn::{op, Function, Graph};
+
+use common::{bench, N_ROWS};
+
+fn compile() -> Function {
+    let mut g = Graph::new();
+    let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let RefValue::Scalar(y) = g.input("y".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let prod = g.insert(op::Mul, vec![x, y]).unwrap();
+    let sum = g.insert(op::Add, vec![prod, x]).unwrap();
+    g.output(RefValue::Scalar(sum), Layout::Scalar).unwrap();
+    g.compile().unwrap()
+}
+
+fn main() {
+    let func = compile();
+    let inputs = (0..N_ROWS)
+        .map(|i| serde_json::json!({ "x": i as f64, "y": 0.5 }))
+        .collect::<Vec<_>>();
+    let raw_inputs = (0..N_ROWS)
+        .flat_map(|i| [i as f64, 0.5])
+        .collect::<Vec<_>>();
+    let raw_inputs = raw_inputs.as_byte_slice();
+    let input_size = func.input_size().in_bytes();
+
+    bench("eval loop", || {
+        let outputs = inputs
+            .iter()
+            .map(|input| func.eval(input))
+            .collect::<Result<Vec<f64>, _>>()
+            .unwrap();
+        assert_eq!(outputs.len(), N_ROWS);
+    });
+    bench("eval_batch", || {
+        let outputs: Vec<f64> = func.eval_batch(&inputs).unwrap();
+        assert_eq!(outputs.len(), N_ROWS);
+    });
+    bench("eval_raw loop", || {
+        let outputs = raw_inputs
+            .chunks_exact(input_size)
+            .map(|input| func.eval_raw(input))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(outputs.len(), N_ROWS);
+    });
+    bench("eval_raw_batch", || {
+        let outputs = func.eval_raw_batch(raw_inputs, N_ROWS).unwrap();
+        assert_eq!(outputs.len(), N_ROWS * func.output_size().in_bytes());
+    });
+}
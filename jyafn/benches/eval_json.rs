@@ -0,0 +1,62 @@
+//! Compares evaluating JSON text with [`Function::eval_json_streaming`] against parsing it
+//! into a [`serde_json::Value`], evaluating and serializing the output back. Run with
+//! `cargo bench -p jyafn --bench eval_json`.
+
+mod common;
+
+use jyafn::layout::{Layout, RefValue, Struct};
+use jyafn::{op, Function, Graph};
+
+use common::{bench, N_ROWS};
+
+fn compile() -> Function {
+    let mut g = Graph::new();
+    let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+        unreachable!()
+    };
+    let RefValue::List(ys) = g.input("ys".to_string(), Layout::List(Box::new(Layout::Scalar), 8))
+    else {
+        unreachable!()
+    };
+    let mut outputs = vec![];
+    for y in ys {
+        let RefValue::Scalar(y) = y else {
+            unreachable!()
+        };
+        let prod = g.insert(op::Mul, vec![x, y]).unwrap();
+        outputs.push(RefValue::Scalar(prod));
+    }
+    g.output(
+        RefValue::Struct([("products".to_string(), RefValue::List(outputs))].into()),
+        Layout::Struct(Struct(vec![(
+            "products".to_string(),
+            Layout::List(Box::new(Layout::Scalar), 8),
+        )])),
+    )
+    .unwrap();
+    g.compile().unwrap()
+}
+
+fn main() {
+    let func = compile();
+    let inputs = (0..N_ROWS)
+        .map(|i| format!(r#"{{"x": {i}, "ys": [1, 2, 3, 4, 5, 6, 7, 8.5]}}"#))
+        .collect::<Vec<_>>();
+
+    bench("eval via Value", || {
+        for input in &inputs {
+            let input: serde_json::Value = serde_json::from_str(input).unwrap();
+            let output: serde_json::Value = func.eval(&input).unwrap();
+            let output = serde_json::to_string(&output).unwrap();
+            assert!(!output.is_empty());
+        }
+    });
+    let mut output = Vec::with_capacity(1024);
+    bench("eval_json_streaming", || {
+        for input in &inputs {
+            output.clear();
+            func.eval_json_streaming(input, &mut output).unwrap();
+            assert!(!output.is_empty());
+        }
+    });
+}
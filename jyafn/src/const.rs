@@ -39,3 +39,14 @@ impl Const for bool {
         }
     }
 }
+
+#[typetag::serde]
+impl Const for i64 {
+    fn annotate(&self) -> Type {
+        Type::Int
+    }
+
+    fn render(&self) -> u64 {
+        *self as u64
+    }
+}
@@ -33,6 +33,11 @@ pub struct RawResource(pub(crate) *mut ());
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Dumped(pub(crate) *mut ());
 
+/// The callback passed to extensions to write binary data incrementally. It receives
+/// an opaque context pointer, supplied together with the callback, and a chunk of data
+/// with its length. It returns zero on success and non-zero on failure.
+pub type WriteCallback = unsafe extern "C" fn(*mut (), *const u8, usize) -> i32;
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum LoadOutcome {
@@ -109,6 +114,9 @@ pub struct StringManifest {
 pub struct ResourceManifest {
     pub fn_from_bytes: String,
     pub fn_dump: String,
+    /// This symbol is optional. If absent, `fn_dump` is used to write the resource.
+    #[serde(default)]
+    pub fn_dump_to: Option<String>,
     pub fn_size: String,
     pub fn_get_method_def: String,
     pub fn_drop: String,
@@ -124,6 +132,18 @@ pub struct ExternalMethod {
     pub input_layout: Struct,
     /// Output layout of the given function.
     pub output_layout: Layout,
+    /// The names of the input fields that are lists of dynamic length, declared with
+    /// length zero in the input layout. Extensions not declaring any dynamic input may
+    /// omit this field.
+    #[serde(default)]
+    pub dynamic_inputs: Vec<String>,
+    /// Whether the output is a list whose length is only known at call time, declared
+    /// with the layout created by [`dynamic_output_layout`]. Extensions not declaring
+    /// any dynamic output may omit this field.
+    ///
+    /// [`dynamic_output_layout`]: crate::resource::dynamic_output_layout
+    #[serde(default)]
+    pub dynamic_output: bool,
 }
 
 /// Checks for nul chars in the provided string and returns a nul-termindated slice.
@@ -239,6 +259,12 @@ pub struct ResourceSymbols {
     pub fn_from_bytes: unsafe extern "C" fn(*const u8, usize) -> Outcome,
     /// Creates a dump, which points to the binary representation of the supplied resource.
     pub fn_dump: unsafe extern "C" fn(RawResource) -> Outcome,
+    /// Optionally, writes the binary representation of the supplied resource, the same
+    /// as the one given by `fn_dump`, by calling the [`WriteCallback`] repeatedly with
+    /// the supplied context pointer and consecutive chunks of data. If the callback
+    /// returns non-zero, writing failed and the extension should stop and return an
+    /// error. The successful value of the returned outcome is ignored.
+    pub fn_dump_to: Option<unsafe extern "C" fn(RawResource, *mut (), WriteCallback) -> Outcome>,
     /// Gets the amount of heap memory (ie RAM) allocated by this resource.
     pub fn_size: unsafe extern "C" fn(RawResource) -> usize,
     /// Given the `name` of a method and its `config` (i.e., aditional parameters) as
@@ -259,11 +285,20 @@ impl ResourceSymbols {
     ) -> Result<ResourceSymbols, Error> {
         /// For building structs that are symbol tables.
         macro_rules! symbol {
-            ($($sym:ident),*) => { Self {$(
+            ($($sym:ident),*; $($optional:ident),*) => { Self {$(
                 $sym: get_symbol(library, &manifest.$sym).context(
                         concat!("getting symbol for ", stringify!($sym)
                     )
                 )?,
+            )* $(
+                $optional: manifest
+                    .$optional
+                    .as_deref()
+                    .map(|name| {
+                        get_symbol(library, name)
+                            .context(concat!("getting symbol for ", stringify!($optional)))
+                    })
+                    .transpose()?,
             )*}}
         }
 
@@ -272,7 +307,8 @@ impl ResourceSymbols {
             fn_dump,
             fn_size,
             fn_get_method_def,
-            fn_drop
+            fn_drop;
+            fn_dump_to
         ))
     }
 }
@@ -388,6 +424,14 @@ impl Extension {
     }
 
     pub(crate) unsafe fn dumped_to_vec(&self, dumped: Dumped) -> Result<Vec<u8>, Error> {
+        self.with_dumped(dumped, <[u8]>::to_vec)
+    }
+
+    /// Calls `f` on the binary data of a dump, dropping the dump afterwards.
+    pub(crate) unsafe fn with_dumped<F, T>(&self, dumped: Dumped, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&[u8]) -> T,
+    {
         unsafe {
             // Safety: supposing that the extension is correctly implmented and observing
             // the contract.
@@ -401,7 +445,7 @@ impl Extension {
             }
             let dump_len = (self.dumped.fn_get_len)(dumped);
 
-            Ok(std::slice::from_raw_parts(dump_ptr, dump_len).to_vec())
+            Ok(f(std::slice::from_raw_parts(dump_ptr, dump_len)))
         }
     }
 
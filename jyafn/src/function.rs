@@ -1,9 +1,10 @@
 use get_size::GetSize;
 use libloading::Library;
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::ffi::{c_char, CStr, CString};
 use std::{
-    cell::RefCell,
+    cell::{RefCell, RefMut},
     fmt::Debug,
     io::{Read, Seek},
     sync::Arc,
@@ -13,53 +14,206 @@ use thread_local::ThreadLocal;
 
 use crate::size::Size;
 
-use super::{layout, Error, Graph};
+use super::graph::ProfileCounters;
+use super::{
+    layout, Context, Error, Graph, NodeProfile, OptStats, MEM_SIZE_ESTIMATE_KEY, OPT_STATS_KEY,
+};
 
 /// The error type returned from the compiled function. If you need to create a new error
 /// from your code, use `String::into`.
-pub struct FnError(Option<Cow<'static, CStr>>);
+pub struct FnError {
+    message: Option<Cow<'static, CStr>>,
+    error_id: Option<usize>,
+}
 
 impl FnError {
     /// Takes the underlying error message from this error. Calling this method more than
     /// once will result in a panic.
     pub fn take(&mut self) -> Cow<'static, CStr> {
-        self.0.take().expect("can only call take once")
+        self.message.take().expect("can only call take once")
+    }
+
+    /// The index of the error in [`Graph::errors`], if this error was raised by the
+    /// graph itself, e.g., by a failed assertion.
+    pub fn error_id(&self) -> Option<usize> {
+        self.error_id
     }
 
     /// This is used from inside jyafn to create an error from static C-style error
     /// messages.
     pub(crate) unsafe extern "C" fn make_static(s: *const c_char) -> *mut FnError {
-        let boxed = Box::new(Self(Some(Cow::Borrowed(CStr::from_ptr(s)))));
+        let boxed = Box::new(Self {
+            message: Some(Cow::Borrowed(CStr::from_ptr(s))),
+            error_id: None,
+        });
+        Box::leak(boxed)
+    }
+
+    /// This is used from inside jyafn to create an error from the static C-style error
+    /// messages of the graph, together with the index of the error in [`Graph::errors`].
+    pub(crate) unsafe extern "C" fn make_static_with_id(
+        s: *const c_char,
+        error_id: u64,
+    ) -> *mut FnError {
+        let boxed = Box::new(Self {
+            message: Some(Cow::Borrowed(CStr::from_ptr(s))),
+            error_id: Some(error_id as usize),
+        });
         Box::leak(boxed)
     }
 
+    /// This is used from inside jyafn to read the index of an error in [`Graph::errors`],
+    /// returning `u64::MAX` if the error has none.
+    pub(crate) unsafe extern "C" fn error_id_of(error: *const FnError) -> u64 {
+        (*error)
+            .error_id
+            .map_or(u64::MAX, |error_id| error_id as u64)
+    }
+
+    /// This is used from inside jyafn to set the index of an error in [`Graph::errors`]
+    /// when it is raised again by another graph, e.g., by the caller of a subgraph.
+    pub(crate) unsafe extern "C" fn set_error_id(
+        error: *mut FnError,
+        error_id: u64,
+    ) -> *mut FnError {
+        (*error).error_id = Some(error_id as usize);
+        error
+    }
+
     /// This is used from inside jyafn to create an error from static C-style error
     /// messages.
     pub(crate) unsafe extern "C" fn make_allocated(s: *mut c_char) -> *mut FnError {
-        let boxed = Box::new(Self(Some(Cow::Owned(CString::from_raw(s)))));
+        let boxed = Box::new(Self {
+            message: Some(Cow::Owned(CString::from_raw(s))),
+            error_id: None,
+        });
         Box::leak(boxed)
     }
 }
 
 impl From<String> for FnError {
     fn from(s: String) -> FnError {
-        FnError(Some(Cow::Owned(crate::utils::make_safe_c_str(s))))
+        FnError {
+            message: Some(Cow::Owned(crate::utils::make_safe_c_str(s))),
+            error_id: None,
+        }
+    }
+}
+
+/// The status raised by a compiled function: its error message and, if it is one of
+/// the errors of the graph (e.g., a failed assertion), the index of the error in
+/// [`Graph::errors`]. This dereferences to the error message.
+pub struct RaisedStatus {
+    message: Cow<'static, CStr>,
+    error_id: Option<usize>,
+}
+
+impl RaisedStatus {
+    /// The index of the error in [`Graph::errors`], if this status was raised by the
+    /// graph itself.
+    pub fn error_id(&self) -> Option<usize> {
+        self.error_id
+    }
+
+    /// Takes the error message out of this status.
+    pub fn into_message(self) -> Cow<'static, CStr> {
+        self.message
+    }
+}
+
+impl std::ops::Deref for RaisedStatus {
+    type Target = CStr;
+
+    fn deref(&self) -> &CStr {
+        &self.message
+    }
+}
+
+impl Debug for RaisedStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.message.fmt(f)
     }
 }
 
+/// Reads one byte of every page of the readable mappings of the shared object containing
+/// `addr`, so that they are faulted in. Mappings are found in `/proc/self/maps`.
+#[cfg(target_os = "linux")]
+fn touch_mapped_pages(addr: usize) {
+    /// The smallest page size around. Touching more often than needed is harmless.
+    const PAGE_SIZE: usize = 4096;
+
+    let Ok(maps) = std::fs::read_to_string("/proc/self/maps") else {
+        return;
+    };
+    // Each line is `start-end perms offset dev inode path`.
+    let mappings = maps
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (start, end) = fields.next()?.split_once('-')?;
+            let perms = fields.next()?;
+            let file = (fields.nth(1)?, fields.next()?);
+            Some((
+                usize::from_str_radix(start, 16).ok()?..usize::from_str_radix(end, 16).ok()?,
+                perms,
+                file,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let Some((_, _, library)) = mappings.iter().find(|(range, ..)| range.contains(&addr)) else {
+        return;
+    };
+    if library.1 == "0" {
+        // Not backed by a file.
+        return;
+    }
+
+    for (range, _, _) in mappings
+        .iter()
+        .filter(|(_, perms, file)| file == library && perms.starts_with('r'))
+    {
+        for page in range.clone().step_by(PAGE_SIZE) {
+            // Safety: the page is in a readable mapping of the shared object, which is
+            // kept loaded by the function calling this.
+            unsafe { std::ptr::read_volatile(page as *const u8) };
+        }
+    }
+}
+
+/// Faulting pages in is only implemented for Linux.
+#[cfg(not(target_os = "linux"))]
+fn touch_mapped_pages(_addr: usize) {}
+
 /// The function signature exposed from jyafn.
 pub type RawFn = unsafe extern "C" fn(*const u8, *mut u8) -> *mut FnError;
 
 /// All the data that a [`Function`] holds on to.
 #[derive(Debug)]
 pub struct FunctionData {
+    /// The graph this function was compiled from. Besides being used for introspection,
+    /// this keeps alive the mappings and resources whose raw pointers are hardcoded in the
+    /// compiled code. Since clones of a graph share the same `Arc`s, the original graph
+    /// (and any other clone) can be safely dropped once the function is created.
     graph: Graph,
     _library: Library,
     library_len: u64,
+    /// The shared object file, kept on disk only when the function was compiled with
+    /// debug information.
+    shared_object: Option<NamedTempFile>,
+    /// The counters written by the compiled code and the optimized graph whose nodes they
+    /// count, only present when the function was compiled with profiling.
+    profile: Option<(Arc<ProfileCounters>, Graph)>,
+    entry: String,
     input_layout: layout::Layout,
     output_layout: layout::Layout,
     input_size: Size,
     output_size: Size,
+    /// The headers of the maps in the input, whose entries come after the input size.
+    map_headers: Vec<layout::MapHeader>,
+    /// Whether the output contains enums. The function can return any integer for them,
+    /// so the output is always validated before being decoded.
+    output_has_enum: bool,
     fn_ptr: RawFn,
     input: ThreadLocal<RefCell<layout::Visitor>>,
     output: ThreadLocal<RefCell<layout::Visitor>>,
@@ -124,11 +278,36 @@ impl Function {
         &self.data.output_layout
     }
 
+    /// A [JSON Schema](https://json-schema.org/) of the JSON values accepted as input by
+    /// this function. See [`layout::Layout::to_json_schema`] for details.
+    pub fn input_schema_json(&self) -> serde_json::Value {
+        self.data.input_layout.to_json_schema()
+    }
+
+    /// A [JSON Schema](https://json-schema.org/) of the JSON values returned by this
+    /// function. See [`layout::Layout::to_json_schema`] for details.
+    pub fn output_schema_json(&self) -> serde_json::Value {
+        self.data.output_layout.to_json_schema()
+    }
+
     /// The computational graph that generated this function.
     pub fn graph(&self) -> &Graph {
         &self.data.graph
     }
 
+    /// The name of the symbol of the compiled function in the shared object. This is
+    /// `run`, unless the function was compiled with [`Graph::compile_named`].
+    pub fn entry(&self) -> &str {
+        &self.data.entry
+    }
+
+    /// The path of the shared object this function was loaded from. This is only
+    /// available if the function was compiled with [`crate::CompileOptions::debug_info`]
+    /// set; otherwise, the file is deleted as soon as it is loaded.
+    pub fn shared_object_path(&self) -> Option<&std::path::Path> {
+        self.data.shared_object.as_ref().map(|file| file.path())
+    }
+
     /// The raw function pointer of the compiled function in memory.
     pub fn fn_ptr(&self) -> RawFn {
         self.data.fn_ptr
@@ -139,6 +318,35 @@ impl Function {
         self.into()
     }
 
+    /// Statistics on the optimizations performed when this function was compiled, if
+    /// they were recorded.
+    pub fn opt_stats(&self) -> Option<OptStats> {
+        self.data
+            .graph
+            .metadata
+            .get(OPT_STATS_KEY)
+            .and_then(|stats| serde_json::from_str(stats).ok())
+    }
+
+    /// How many times each node ran and how long it took, summed over all calls to this
+    /// function so far, keyed by node id. Node ids refer to the nodes of
+    /// [`Function::profiled_graph`], and nodes that never ran are left out. This is only
+    /// available if the function was compiled with [`Graph::compile_profiled`] or with
+    /// [`crate::CompileOptions::profile`] set.
+    pub fn profile_report(&self) -> Option<BTreeMap<usize, NodeProfile>> {
+        self.data
+            .profile
+            .as_ref()
+            .map(|(profile, _)| profile.report())
+    }
+
+    /// The optimized graph that was actually compiled, whose node ids are the ones used
+    /// by [`Function::profile_report`]. Like the report, this is only available if the
+    /// function was compiled with profiling.
+    pub fn profiled_graph(&self) -> Option<&Graph> {
+        self.data.profile.as_ref().map(|(_, graph)| graph)
+    }
+
     /// Loads a computational graph from the provided reader and compiles it, returning
     /// the reulting function.
     pub fn load<R: Read + Seek>(reader: R) -> Result<Function, Error> {
@@ -146,9 +354,56 @@ impl Function {
         graph.compile()
     }
 
+    /// Creates a function from the assembly code of a graph, as rendered by
+    /// [`Graph::render_assembly`], assembling and linking it without invoking QBE. Use
+    /// this to persist the assembly and load it where QBE is not available. The graph
+    /// must be the one the assembly was rendered from; this is checked before the function
+    /// is created.
+    ///
+    /// Graphs that call into the jyafn runtime (graphs that may raise errors or that use
+    /// mappings, resources or pure functions) compile to code that hardcodes memory
+    /// addresses of the process rendering it. Therefore, loading their assembly in any
+    /// other process fails. Only the assembly of graphs that use none of these can be
+    /// loaded in other processes.
+    ///
+    /// # Safety
+    ///
+    /// The assembly code is run as is. It must come from a trusted source.
+    pub unsafe fn from_assembly(graph: &Graph, assembly: &str) -> Result<Function, Error> {
+        let shared_object = crate::graph::assemble_and_link(assembly)?;
+        graph.load_shared_object(shared_object)
+    }
+
+    /// Creates a function from a shared object linked from the assembly code of a graph
+    /// (see [`Function::from_assembly`]) or kept by a function compiled with
+    /// [`CompileOptions::debug_info`](crate::graph::CompileOptions::debug_info), without
+    /// needing a toolchain at all. The shared object is copied, so that it cannot change
+    /// while loaded. The same checks and limitations of [`Function::from_assembly`] apply.
+    ///
+    /// # Safety
+    ///
+    /// Loading a shared object runs its initialization routines. It must come from a
+    /// trusted source.
+    pub unsafe fn from_shared_object(
+        graph: &Graph,
+        path: &std::path::Path,
+    ) -> Result<Function, Error> {
+        let mut shared_object = NamedTempFile::new()?;
+        std::io::copy(&mut std::fs::File::open(path)?, shared_object.as_file_mut())?;
+        graph.load_shared_object(shared_object)
+    }
+
     /// Initializes a function from a given graph and a temporary file, containing the
-    /// shared object obtained from the compilation process.
-    pub(crate) fn init(graph: Graph, shared_object: NamedTempFile) -> Result<Function, Error> {
+    /// shared object obtained from the compilation process. The `entry` is the name of
+    /// the symbol of the function in the shared object. If `keep_shared_object` is set,
+    /// the temporary file is only deleted when the function is dropped.
+    pub(crate) fn init(
+        graph: Graph,
+        shared_object: NamedTempFile,
+        entry: &str,
+        keep_shared_object: bool,
+        profile: Option<(Arc<ProfileCounters>, Graph)>,
+    ) -> Result<Function, Error> {
         let library = unsafe {
             // Safety: shared object was complied straignt from the linker into the
             // temporary file, unless some spooky process was able to change the file
@@ -158,11 +413,11 @@ impl Function {
         let symbol: libloading::Symbol<RawFn> = unsafe {
             // Safety: all jyafn shared objects have this function with this given signature.
             // Also, `library` will be held by the current function until it is dropped.
-            library.get(b"run\0")?
+            library.get(entry.as_bytes())?
         };
         let fn_ptr: RawFn = *symbol;
 
-        let input_layout = graph.input_layout.clone();
+        let input_layout: layout::Layout = graph.input_layout.clone().into();
         let output_layout = graph.output_layout.clone();
         let input_size_in_floats = input_layout.size();
         let output_size_in_floats = output_layout.size();
@@ -170,9 +425,14 @@ impl Function {
         let mut data = FunctionData {
             _library: library,
             library_len: std::fs::metadata(shared_object.path())?.len(),
+            shared_object: keep_shared_object.then_some(shared_object),
+            profile,
+            entry: entry.to_string(),
             input_size: input_size_in_floats,
-            input_layout: input_layout.into(),
+            map_headers: input_layout.map_headers(),
+            input_layout,
             output_size: output_size_in_floats,
+            output_has_enum: output_layout.contains_enum(),
             output_layout,
             fn_ptr,
             graph,
@@ -183,7 +443,7 @@ impl Function {
         let data_size = data.get_size();
         data.graph
             .metadata_mut()
-            .insert("jyafn.mem_size_estimate".to_string(), data_size.to_string());
+            .insert(MEM_SIZE_ESTIMATE_KEY.to_string(), data_size.to_string());
 
         Ok(Function {
             data: Arc::new(data),
@@ -192,7 +452,8 @@ impl Function {
 
     /// Calls the function on an raw input and returns the result in the output. This
     /// function panics if the input and the output are not of the correct size for this
-    /// function.
+    /// function. If the input layout contains maps, the input may be longer than the input
+    /// size, but the entries of the maps must be inside the input.
     ///
     /// This method is not unsafe in that it does not generate Undefined Behavior if some
     /// contract is not obeyed. However, you should really know what you are doing here.
@@ -205,7 +466,9 @@ impl Function {
         let input = input.as_ref();
         let output = output.as_mut();
 
-        assert_eq!(self.data.input_size.in_bytes(), input.len());
+        if let Err(err) = self.check_input(input) {
+            panic!("bad input for function: {err}");
+        }
         assert_eq!(self.data.output_size.in_bytes(), output.len());
 
         // Safety: input and output sizes are checked and function pinky-promisses not to
@@ -213,6 +476,36 @@ impl Function {
         unsafe { (self.data.fn_ptr)(input.as_ptr(), output.as_mut_ptr()) }
     }
 
+    /// Calls the function on an raw input and returns the result in the output, like
+    /// [`Function::call_raw`]. However, instead of panicking, this returns an
+    /// [`Error::SizeMismatch`] if the input or the output are not of the correct size for
+    /// this function. Errors raised by the function are also returned as errors.
+    pub fn try_call_raw<I, O>(&self, input: I, mut output: O) -> Result<(), Error>
+    where
+        I: AsRef<[u8]>,
+        O: AsMut<[u8]>,
+    {
+        let input = input.as_ref();
+        let output = output.as_mut();
+
+        self.check_input(input).context("checking input")?;
+        check_size(self.data.output_size.in_bytes(), output.len()).context("checking output")?;
+
+        check_status(self.call_raw(input, output))
+    }
+
+    /// Checks that the input has the input size of this function or, if the input layout
+    /// contains maps, that it is at least as long and that the entries of the maps are
+    /// inside of it.
+    fn check_input(&self, input: &[u8]) -> Result<(), Error> {
+        let input_size = self.data.input_size.in_bytes();
+        if self.data.map_headers.is_empty() || input.len() < input_size {
+            return check_size(input_size, input.len());
+        }
+
+        layout::check_map_bounds(&self.data.map_headers, input)
+    }
+
     /// Calls the function on an raw input and returns the result as boxed slice of bytes.
     /// This function panics if the input is not of the correct size for this function.
     ///
@@ -224,26 +517,145 @@ impl Function {
         I: AsRef<[u8]>,
     {
         let mut output = vec![0; self.data.output_size.in_bytes()].into_boxed_slice();
-        let status = self.call_raw(input, &mut output);
-        if status.is_null() {
-            Ok(output)
-        } else {
-            // Safety: null was checked and the function pinky-promisses to return a valid
-            // C string in case of error.
-            let mut error = unsafe { Box::from_raw(status) };
-            Err(Error::StatusRaised(error.take()))
+        check_status(self.call_raw(input, &mut output))?;
+        Ok(output)
+    }
+
+    /// Calls the function on a raw input and builds the return value from the resulting
+    /// binary data using the supplied decoder. This function panics if the input is not
+    /// of the correct size for this function. Symbols in the output are resolved against
+    /// the symbols of the graph only, since the raw input carries no new symbols.
+    pub fn eval_raw_with_decoder<I, D>(&self, input: I, mut decoder: D) -> Result<D::Target, Error>
+    where
+        I: AsRef<[u8]>,
+        D: layout::Decoder,
+    {
+        let (_, mut decode_visitor) = self.buffers();
+        let symbols_view = layout::SymbolsView::new(&self.data.graph.symbols);
+        decode_visitor.reset();
+
+        check_status(self.call_raw(input, &mut decode_visitor.0))?;
+
+        if self.data.output_has_enum {
+            layout::validate(&self.data.output_layout, &symbols_view, &decode_visitor.0)?;
+        }
+        Ok(decoder.build(&self.data.output_layout, &symbols_view, &mut decode_visitor))
+    }
+
+    /// Calls the function on `n` raw inputs laid out contiguously in `inputs` and returns
+    /// the `n` raw outputs, also laid out contiguously, as a boxed slice of bytes. This
+    /// returns an error if `inputs` does not have exactly `n` times the input size of this
+    /// function. If a row raises an error, the error names the index of the row and the
+    /// remaining rows are not evaluated. Since rows must have the same size, this is not
+    /// supported for functions with maps in their input.
+    ///
+    /// This method is not unsafe in that it does not generate Undefined Behavior if some
+    /// contract is not obeyed. However, you should really know what you are doing here.
+    /// Consider using [`Function::eval_batch`] instead.
+    pub fn eval_raw_batch<I>(&self, inputs: I, n: usize) -> Result<Box<[u8]>, Error>
+    where
+        I: AsRef<[u8]>,
+    {
+        let inputs = inputs.as_ref();
+        let input_size = self.data.input_size.in_bytes();
+        let output_size = self.data.output_size.in_bytes();
+
+        if !self.data.map_headers.is_empty() {
+            return Err("raw batches are not supported for inputs with maps"
+                .to_string()
+                .into());
+        }
+
+        if inputs.len() != n * input_size {
+            return Err(format!(
+                "batch of {n} rows should have {} bytes of input, but got {}",
+                n * input_size,
+                inputs.len()
+            )
+            .into());
+        }
+
+        let mut outputs = vec![0; n * output_size].into_boxed_slice();
+        for row in 0..n {
+            let input = &inputs[row * input_size..(row + 1) * input_size];
+            let output = &mut outputs[row * output_size..(row + 1) * output_size];
+            check_status(self.call_raw(input, output))
+                .with_context(|| format!("evaluating row {row} of batch"))?;
         }
+
+        Ok(outputs)
     }
 
     /// Calls this function on an input that can be encoded to jyafn-compatible binary
     /// data and builds the return value from the resulting binary data using the supplied
     /// decoder.
-    pub fn eval_with_decoder<E, D>(&self, input: &E, mut decoder: D) -> Result<D::Target, Error>
+    pub fn eval_with_decoder<E, D>(&self, input: &E, decoder: D) -> Result<D::Target, Error>
+    where
+        E: ?Sized + layout::Encode,
+        D: layout::Decoder,
+    {
+        self.do_eval_with_decoder(input, decoder, false)
+    }
+
+    /// Like [`Function::eval_with_decoder`], but checks that the output is well-formed
+    /// (see [`layout::validate`]) before decoding it, returning an error if it is not.
+    pub fn eval_with_decoder_strict<E, D>(&self, input: &E, decoder: D) -> Result<D::Target, Error>
     where
         E: ?Sized + layout::Encode,
         D: layout::Decoder,
     {
-        // Access buffers:
+        self.do_eval_with_decoder(input, decoder, true)
+    }
+
+    fn do_eval_with_decoder<E, D>(
+        &self,
+        input: &E,
+        mut decoder: D,
+        strict: bool,
+    ) -> Result<D::Target, Error>
+    where
+        E: ?Sized + layout::Encode,
+        D: layout::Decoder,
+    {
+        let (mut encode_visitor, mut decode_visitor) = self.buffers();
+
+        // Define a symbols view (to store symbols present in the input not present in the
+        // graph)
+        let mut symbols_view = layout::SymbolsView::new(&self.data.graph.symbols);
+
+        self.eval_in_buffers(
+            input,
+            &mut decoder,
+            strict,
+            &mut symbols_view,
+            &mut encode_visitor,
+            &mut decode_visitor,
+        )
+    }
+
+    /// Prepares this function to be evaluated in the current thread, so that the first
+    /// evaluation is not slower than the rest. This allocates the encoding and decoding
+    /// buffers of the current thread and faults in the memory pages of the compiled code
+    /// (the latter only on Linux). If `dry_run` is set, the function is also evaluated
+    /// once on an input of all zeros, whose result (or error) is discarded.
+    ///
+    /// Buffers are allocated per thread, so this has to be called in every thread that
+    /// evaluates this function.
+    pub fn warm_up(&self, dry_run: bool) {
+        let (mut encode_visitor, mut decode_visitor) = self.buffers();
+        touch_mapped_pages(self.data.fn_ptr as *const () as usize);
+
+        if dry_run {
+            encode_visitor.buffer_mut().fill(0);
+            if let Err(err) = check_status(self.call_raw(&encode_visitor.0, &mut decode_visitor.0))
+            {
+                log::debug!("dry run of function warm-up failed: {err}");
+            }
+        }
+    }
+
+    /// Borrows the encoding and decoding buffers of this function for the current thread.
+    fn buffers(&self) -> (RefMut<'_, layout::Visitor>, RefMut<'_, layout::Visitor>) {
         let local_input = self
             .data
             .input
@@ -252,35 +664,103 @@ impl Function {
             .data
             .output
             .get_or(|| RefCell::new(layout::Visitor::new(self.data.output_size)));
-        let mut encode_visitor = local_input.borrow_mut();
+
+        (local_input.borrow_mut(), local_output.borrow_mut())
+    }
+
+    /// Evaluates a single input using the supplied buffers.
+    fn eval_in_buffers<E, D>(
+        &self,
+        input: &E,
+        decoder: &mut D,
+        strict: bool,
+        symbols_view: &mut layout::SymbolsView,
+        encode_visitor: &mut layout::Visitor,
+        decode_visitor: &mut layout::Visitor,
+    ) -> Result<D::Target, Error>
+    where
+        E: ?Sized + layout::Encode,
+        D: layout::Decoder,
+    {
         encode_visitor.reset();
-        let mut decode_visitor = local_output.borrow_mut();
         decode_visitor.reset();
 
-        // Define a symbols view (to store symbols present in the input not present in the
-        // graph)
-        let mut symbols_view = layout::SymbolsView::new(&self.data.graph.symbols);
-
         // Serialization dance:
         input
-            .visit(
-                &self.data.input_layout,
-                &mut symbols_view,
-                &mut encode_visitor,
-            )
+            .visit(&self.data.input_layout, symbols_view, encode_visitor)
             .map_err(|err| Error::EncodeError(Box::new(err)))?;
 
         // Call:
-        let status = self.call_raw(&encode_visitor.0, &mut decode_visitor.0);
-        if !status.is_null() {
-            // Safety: null was checked and the function pinky-promisses to return a valid
-            // C string in case of error.
-            let mut error = unsafe { Box::from_raw(status) };
-            return Err(Error::StatusRaised(error.take()));
-        }
+        check_status(self.call_raw(&encode_visitor.0, &mut decode_visitor.0))?;
 
         // Deserialization dance:
-        Ok(decoder.build(&self.data.output_layout, &symbols_view, &mut decode_visitor))
+        if strict || self.data.output_has_enum {
+            layout::validate(&self.data.output_layout, symbols_view, &decode_visitor.0)?;
+        }
+        Ok(decoder.build(&self.data.output_layout, symbols_view, decode_visitor))
+    }
+
+    /// Runs this function on an input given as JSON text and writes the output as JSON
+    /// text to `out`. This gives the same result as evaluating into a
+    /// [`serde_json::Value`] and serializing it, but the input is encoded straight from
+    /// the text and the output is serialized straight from the output buffer, without
+    /// building any intermediate value.
+    pub fn eval_json_streaming<W: std::io::Write>(&self, input: &str, out: W) -> Result<(), Error> {
+        let (mut encode_visitor, mut decode_visitor) = self.buffers();
+        let mut symbols_view = layout::SymbolsView::new(&self.data.graph.symbols);
+        encode_visitor.reset();
+        decode_visitor.reset();
+
+        layout::encode_json(
+            input,
+            &self.data.input_layout,
+            &mut symbols_view,
+            &mut encode_visitor,
+        )
+        .map_err(|err| Error::EncodeError(Box::new(err)))?;
+
+        check_status(self.call_raw(&encode_visitor.0, &mut decode_visitor.0))?;
+
+        layout::write_json(
+            &self.data.output_layout,
+            &symbols_view,
+            &decode_visitor,
+            out,
+        )
+    }
+
+    /// Runs this function on each of the `inputs`, building each return value using the
+    /// supplied decoder. The results are returned in the same order as the inputs. If a
+    /// row fails, the error names the index of the row and the remaining rows are not
+    /// evaluated.
+    pub fn eval_batch_with_decoder<E, D>(
+        &self,
+        inputs: &[E],
+        mut decoder: D,
+    ) -> Result<Vec<D::Target>, Error>
+    where
+        E: layout::Encode,
+        D: layout::Decoder,
+    {
+        let (mut encode_visitor, mut decode_visitor) = self.buffers();
+        let mut symbols_view = layout::SymbolsView::new(&self.data.graph.symbols);
+        let mut outputs = Vec::with_capacity(inputs.len());
+
+        for (row, input) in inputs.iter().enumerate() {
+            let output = self
+                .eval_in_buffers(
+                    input,
+                    &mut decoder,
+                    false,
+                    &mut symbols_view,
+                    &mut encode_visitor,
+                    &mut decode_visitor,
+                )
+                .with_context(|| format!("evaluating row {row} of batch"))?;
+            outputs.push(output);
+        }
+
+        Ok(outputs)
     }
 
     /// Runs this function on an input value and returns the the computation result or an
@@ -293,4 +773,246 @@ impl Function {
         let zero = layout::ZeroDecoder::new();
         self.eval_with_decoder(input, zero)
     }
+
+    /// Runs this function on each of the `inputs` and returns the results in the same
+    /// order. This is equivalent to calling [`Function::eval`] in a loop, but cheaper,
+    /// since the encoding and decoding buffers are acquired only once for the whole batch.
+    /// If a row fails, the error names the index of the row and the remaining rows are not
+    /// evaluated.
+    pub fn eval_batch<E, D>(&self, inputs: &[E]) -> Result<Vec<D>, Error>
+    where
+        E: layout::Encode,
+        D: layout::Decode,
+    {
+        let zero = layout::ZeroDecoder::new();
+        self.eval_batch_with_decoder(inputs, zero)
+    }
+
+    /// Runs this function on an input value, but decodes only the values at the given
+    /// `paths` of the output, returning them in the same order as the paths. The whole
+    /// output is still computed, but the values not selected are never decoded. This
+    /// fails if any of the paths is not present in the output layout.
+    pub fn eval_select<E, D>(
+        &self,
+        input: &E,
+        paths: &[layout::OutputPath],
+    ) -> Result<Vec<D>, Error>
+    where
+        E: ?Sized + layout::Encode,
+        D: layout::Decode,
+    {
+        let select = layout::SelectDecoder::new(
+            &self.data.output_layout,
+            paths,
+            layout::ZeroDecoder::new(),
+        )?;
+        self.eval_with_decoder(input, select)
+    }
+
+    /// Like [`Function::eval`], but checks that the output is well-formed (see
+    /// [`layout::validate`]) before decoding it, returning an error if it is not.
+    pub fn eval_strict<E, D>(&self, input: &E) -> Result<D, Error>
+    where
+        E: ?Sized + layout::Encode,
+        D: layout::Decode,
+    {
+        let zero = layout::ZeroDecoder::new();
+        self.eval_with_decoder_strict(input, zero)
+    }
+}
+
+/// Checks that a buffer handed to a raw function call has the expected size in bytes.
+fn check_size(expected: usize, got: usize) -> Result<(), Error> {
+    if expected == got {
+        Ok(())
+    } else {
+        Err(Error::SizeMismatch { expected, got })
+    }
+}
+
+/// Converts the status returned by a raw function call into a result.
+fn check_status(status: *mut FnError) -> Result<(), Error> {
+    if status.is_null() {
+        Ok(())
+    } else {
+        // Safety: null was checked and the function pinky-promisses to return a valid C
+        // string in case of error.
+        let mut error = unsafe { Box::from_raw(status) };
+        Err(Error::StatusRaised(RaisedStatus {
+            message: error.take(),
+            error_id: error.error_id(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::layout::{Layout, RefValue};
+
+    #[test]
+    fn test_warm_up() {
+        let mut graph = Graph::new();
+        let RefValue::Scalar(a) = graph.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let zero = graph.r#const(0.0);
+        let positive = graph.insert(crate::op::Gt, vec![a, zero]).unwrap();
+        graph
+            .assert(positive, "a is not positive".to_string())
+            .unwrap();
+        graph.output(RefValue::Scalar(a), Layout::Scalar).unwrap();
+        let func = graph.compile().unwrap();
+
+        assert!(func.data.input.get().is_none());
+        // Errors on a zero input are ignored.
+        func.warm_up(true);
+        let buffer_ptr = || {
+            let input = func.data.input.get().expect("warm-up allocates buffers");
+            let ptr = input.borrow().buffer().as_ptr();
+            ptr
+        };
+        let warm = buffer_ptr();
+
+        let out: f64 = func.eval(&serde_json::json!({ "a": 2.0 })).unwrap();
+        assert_eq!(out, 2.0);
+        assert_eq!(buffer_ptr(), warm);
+
+        // Buffers are per thread.
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert!(func.data.input.get().is_none()));
+        });
+    }
+
+    /// Checks `value` against the subset of JSON Schema produced by
+    /// [`Layout::to_json_schema`] for the layouts used in the tests.
+    fn conforms(schema: &serde_json::Value, value: &serde_json::Value) -> bool {
+        let type_ok = match schema["type"].as_str() {
+            Some("number") => value.is_number(),
+            Some("integer") => value.is_i64() || value.is_u64(),
+            Some("boolean") => value.is_boolean(),
+            Some("string") => value.is_string(),
+            Some("null") => value.is_null(),
+            Some("object") => value.is_object(),
+            Some("array") => value.is_array(),
+            _ => true,
+        };
+        if !type_ok {
+            return false;
+        }
+
+        if let Some(variants) = schema["anyOf"].as_array() {
+            return variants.iter().any(|variant| conforms(variant, value));
+        }
+        if let (Some(properties), Some(object)) =
+            (schema["properties"].as_object(), value.as_object())
+        {
+            let required = schema["required"].as_array().into_iter().flatten();
+            if !required
+                .filter_map(serde_json::Value::as_str)
+                .all(|name| object.contains_key(name))
+            {
+                return false;
+            }
+            if !object.iter().all(|(name, field)| {
+                properties
+                    .get(name)
+                    .is_none_or(|field_schema| conforms(field_schema, field))
+            }) {
+                return false;
+            }
+        }
+        if let Some(elements) = value.as_array() {
+            let len = elements.len() as u64;
+            if schema["minItems"].as_u64().is_some_and(|min| len < min)
+                || schema["maxItems"].as_u64().is_some_and(|max| len > max)
+            {
+                return false;
+            }
+            if schema["items"].is_object()
+                && !elements
+                    .iter()
+                    .all(|element| conforms(&schema["items"], element))
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    #[test]
+    fn test_schema_json() {
+        let mut graph = Graph::new();
+        let item = Layout::Struct(crate::r#struct!("price": scalar, "tags": [symbol; 2]));
+        let RefValue::List(items) =
+            graph.input("items".to_string(), Layout::List(Box::new(item), 2))
+        else {
+            unreachable!()
+        };
+        graph.input(
+            "at".to_string(),
+            Layout::DateTime(crate::layout::ISOFORMAT.to_string()),
+        );
+        graph.input("discount".to_string(), crate::layout!(?scalar));
+        let RefValue::Struct(first) = &items[0] else {
+            unreachable!()
+        };
+        graph
+            .output(
+                RefValue::Tuple(vec![first["price"].clone(), RefValue::Bool(true.into())]),
+                Layout::Tuple(vec![Layout::Scalar, Layout::Bool]),
+            )
+            .unwrap();
+        let func = graph.compile().unwrap();
+
+        let input = func.input_schema_json();
+        assert_eq!(input["required"], serde_json::json!(["items", "at"]));
+        let items = &input["properties"]["items"];
+        assert_eq!(items["type"], "array");
+        assert_eq!(
+            (&items["minItems"], &items["maxItems"]),
+            (&2.into(), &2.into())
+        );
+        assert_eq!(
+            items["items"]["required"],
+            serde_json::json!(["price", "tags"])
+        );
+        assert_eq!(
+            items["items"]["properties"]["tags"]["items"],
+            serde_json::json!({ "type": "string" })
+        );
+        assert_eq!(input["properties"]["at"]["format"], "date-time");
+
+        let payload = serde_json::json!({
+            "items": [
+                { "price": 1.5, "tags": ["a", "b"] },
+                { "price": 2, "tags": ["c", "d"] },
+            ],
+            "at": "2024-01-01T00:00:00",
+            "discount": null,
+        });
+        assert!(conforms(&input, &payload));
+        let output = func.eval::<_, serde_json::Value>(&payload).unwrap();
+        assert!(conforms(&func.output_schema_json(), &output));
+
+        for bad in [
+            serde_json::json!({ "items": [], "at": "2024-01-01T00:00:00" }),
+            serde_json::json!({ "items": payload["items"] }),
+            serde_json::json!({
+                "items": [{ "price": "1.5", "tags": ["a", "b"] }, payload["items"][1]],
+                "at": "2024-01-01T00:00:00",
+            }),
+            serde_json::json!({
+                "items": [{ "price": 1.5, "tags": ["a"] }, payload["items"][1]],
+                "at": "2024-01-01T00:00:00",
+            }),
+        ] {
+            assert!(!conforms(&input, &bad), "{bad}");
+        }
+        assert!(!conforms(
+            &func.output_schema_json(),
+            &serde_json::json!([1.0])
+        ));
+    }
 }
@@ -8,7 +8,8 @@ use super::{Graph, Ref, Type};
 
 /// This function mutates the graph because some checks fix the state of the graph.
 pub fn run_checks(graph: &mut Graph) -> Result<(), Error> {
-    topsort(graph)?;
+    graph.check_subgraph_depth()?;
+    graph.assert_topologically_sorted()?;
     types(graph)?;
     pointers(graph)?;
     mappings_initialized(graph)?;
@@ -28,10 +29,8 @@ fn types(graph: &mut Graph) -> Result<(), Error> {
             .iter()
             .map(|&r| graph.type_of(r))
             .collect::<Vec<_>>();
-        if let Some(ty) = node.op.annotate(node_id, graph, &arg_types) {
-            if ty != node.ty {
-                return Err(Error::Type(node.op, arg_types));
-            }
+        if node.op.annotate(node_id, graph, &arg_types) != Some(node.ty) {
+            return Err(Error::Type(node.op, arg_types));
         }
 
         graph.nodes[node_id] = node;
@@ -40,22 +39,37 @@ fn types(graph: &mut Graph) -> Result<(), Error> {
     Ok(())
 }
 
-/// Checks whether the nodes are ordered in topological order.
-fn topsort(graph: &Graph) -> Result<(), Error> {
-    for (node_id, node) in graph.nodes.iter().enumerate() {
-        for arg in &node.args {
-            if let &Ref::Node(arg_id) = arg {
-                if arg_id >= node_id {
-                    return Err(format!(
-                        "graph topsort violated: node {node_id} references node {arg_id}"
-                    )
-                    .into());
+impl Graph {
+    /// Checks that the nodes are in topological order, i.e., that every node only
+    /// references constants, existing inputs and nodes declared before it. This is
+    /// guaranteed for graphs built with [`Graph::insert`], but not for graphs
+    /// deserialized from an external source. Compilation relies on this order.
+    pub fn assert_topologically_sorted(&self) -> Result<(), Error> {
+        for (node_id, node) in self.nodes.iter().enumerate() {
+            for arg in &node.args {
+                match *arg {
+                    Ref::Node(arg_id) if arg_id >= node_id => {
+                        return Err(Error::Other(format!(
+                            "graph topsort violated: node {node_id} ({:?}) references node \
+                            {arg_id}",
+                            node.op
+                        )));
+                    }
+                    Ref::Input(input_id) if input_id >= self.inputs.len() => {
+                        return Err(Error::Other(format!(
+                            "graph topsort violated: node {node_id} ({:?}) references input \
+                            {input_id}, but graph has {} inputs",
+                            node.op,
+                            self.inputs.len()
+                        )));
+                    }
+                    _ => {}
                 }
             }
         }
-    }
 
-    Ok(())
+        Ok(())
+    }
 }
 
 /// Checks that no pointers are present in the output.
@@ -85,6 +99,42 @@ fn pointers(graph: &Graph) -> Result<(), Error> {
     Ok(())
 }
 
+/// Checks that the outputs of the graph match its output layout, slot by slot.
+pub fn output_layout(graph: &Graph) -> Result<(), Error> {
+    if graph.output_layout.contains_map() {
+        return Err(format!(
+            "output layout {} contains maps, which can only be inputs",
+            graph.output_layout
+        )
+        .into());
+    }
+
+    let slots = graph.output_layout.slots();
+    if slots.len() != graph.outputs.len() {
+        return Err(format!(
+            "output layout {} has {} slots, but graph has {} outputs",
+            graph.output_layout,
+            slots.len(),
+            graph.outputs.len()
+        )
+        .into());
+    }
+
+    for (i, (&output, expected)) in graph.outputs.iter().zip(slots).enumerate() {
+        let ty = graph.type_of(output);
+        if ty != expected {
+            return Err(format!(
+                "output slot {i} should be {expected} according to output layout {}, but \
+                got {ty}",
+                graph.output_layout
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
 /// Checks whether all mappings were all initialized.
 fn mappings_initialized(graph: &Graph) -> Result<(), Error> {
     for (name, mapping) in &graph.mappings {
@@ -0,0 +1,149 @@
+//! Export of graphs as relocatable objects with a C header, to be linked into programs
+//! that do not use the jyafn runtime.
+
+use serde_derive::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::layout::{Layout, Symbols};
+use crate::Function;
+
+use super::{assemble, create_assembly, Error, Graph};
+
+/// The name of the function exported by a C artifact.
+const ENTRY: &str = "run";
+
+/// The files written by [`Graph::export_c_artifact`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CArtifact {
+    /// The relocatable object containing the compiled function, `run.o`.
+    pub object: PathBuf,
+    /// The C header declaring the function and the sizes of its buffers, `run.h`.
+    pub header: PathBuf,
+    /// The JSON sidecar describing the layouts of the function, `run.json`.
+    pub sidecar: PathBuf,
+}
+
+/// The contents of the JSON sidecar of a C artifact. Symbols are needed to encode and
+/// decode symbol values to and from their ids.
+#[derive(Serialize)]
+struct Sidecar<'a> {
+    name: &'a str,
+    entry: &'a str,
+    input_size: usize,
+    output_size: usize,
+    input_layout: Layout,
+    output_layout: &'a Layout,
+    symbols: &'a Symbols,
+}
+
+/// Escapes text to be put inside a C block comment.
+fn comment(text: &str) -> String {
+    text.replace("*/", "* /")
+}
+
+impl Graph {
+    /// Compiles this graph to a relocatable object that can be linked into a C or C++
+    /// program without the jyafn runtime, writing it to `out_dir` as `run.o`, together
+    /// with a C header `run.h` and a JSON sidecar `run.json`. The header declares
+    ///
+    /// ```c
+    /// const void* run(const uint8_t* in, uint8_t* out);
+    /// ```
+    ///
+    /// and defines `RUN_INPUT_SIZE` and `RUN_OUTPUT_SIZE`, the sizes of the buffers, in
+    /// bytes. The function always returns `NULL`. The sidecar describes the input and
+    /// output layouts and the symbols, so that callers can encode and decode the
+    /// buffers.
+    ///
+    /// Since the compiled code reaches the jyafn runtime by hardcoded memory addresses,
+    /// this fails for graphs that need it: graphs that may raise errors or that use
+    /// mappings, resources or pure functions. This also fails for graphs with maps in
+    /// their input, whose buffers have no fixed size.
+    pub fn export_c_artifact(&self, out_dir: &Path) -> Result<CArtifact, Error> {
+        if self.input_layout.contains_map() {
+            return Err(format!(
+                "cannot export graph {:?} as a C artifact: maps in the input have no fixed \
+                size in bytes",
+                self.name()
+            )
+            .into());
+        }
+
+        let graph = self.optimized()?;
+        if graph.calls_runtime() {
+            return Err(format!(
+                "cannot export graph {:?} as a C artifact: it calls into the jyafn runtime \
+                (graphs that may raise errors or that use mappings, resources or pure \
+                functions need it)",
+                graph.name()
+            )
+            .into());
+        }
+        let ir = graph.render_named(ENTRY)?.to_string();
+        let object = assemble(&create_assembly(&ir, None)?)?;
+
+        let input_size = graph.input_layout.size().in_bytes();
+        let output_size = graph.output_layout.size().in_bytes();
+        let guard = format!("JYAFN_{}_H", ENTRY.to_uppercase());
+        let prefix = ENTRY.to_uppercase();
+        let header = format!(
+            "/* Generated by jyafn from the graph {name:?}. Do not edit. */\n\
+            #ifndef {guard}\n\
+            #define {guard}\n\
+            \n\
+            #include <stdint.h>\n\
+            \n\
+            #ifdef __cplusplus\n\
+            extern \"C\" {{\n\
+            #endif\n\
+            \n\
+            /* The size of the input buffer, in bytes. Input layout: {input_layout} */\n\
+            #define {prefix}_INPUT_SIZE {input_size}\n\
+            \n\
+            /* The size of the output buffer, in bytes. Output layout: {output_layout} */\n\
+            #define {prefix}_OUTPUT_SIZE {output_size}\n\
+            \n\
+            /* Reads {prefix}_INPUT_SIZE bytes from `in` and writes {prefix}_OUTPUT_SIZE\n\
+            \x20* bytes to `out`. Always returns NULL. */\n\
+            const void* {ENTRY}(const uint8_t* in, uint8_t* out);\n\
+            \n\
+            #ifdef __cplusplus\n\
+            }}\n\
+            #endif\n\
+            \n\
+            #endif\n",
+            name = comment(graph.name()),
+            input_layout = comment(&graph.input_layout.to_string()),
+            output_layout = comment(&graph.output_layout.to_string()),
+        );
+        let sidecar = Sidecar {
+            name: graph.name(),
+            entry: ENTRY,
+            input_size,
+            output_size,
+            input_layout: Layout::Struct(graph.input_layout.clone()),
+            output_layout: &graph.output_layout,
+            symbols: graph.symbols(),
+        };
+
+        let artifact = CArtifact {
+            object: out_dir.join(format!("{ENTRY}.o")),
+            header: out_dir.join(format!("{ENTRY}.h")),
+            sidecar: out_dir.join(format!("{ENTRY}.json")),
+        };
+        std::fs::create_dir_all(out_dir)?;
+        std::fs::write(&artifact.object, object)?;
+        std::fs::write(&artifact.header, header)?;
+        std::fs::write(&artifact.sidecar, serde_json::to_vec_pretty(&sidecar)?)?;
+
+        Ok(artifact)
+    }
+}
+
+impl Function {
+    /// Exports the graph of this function as a C artifact. See
+    /// [`Graph::export_c_artifact`].
+    pub fn export_c_artifact(&self, out_dir: &Path) -> Result<CArtifact, Error> {
+        self.graph().export_c_artifact(out_dir)
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::utils::murmur::murmur_hash64a;
+
+use super::{create_assembly, Error};
+
+const HASH_SEED: u64 = 0x6a79_6166_6e2e_6363;
+
+/// The prefix QBE uses for local assembly labels, such as basic blocks and floating
+/// point constants.
+#[cfg(target_os = "macos")]
+const LOCAL_LABEL_PREFIX: &str = "L";
+#[cfg(not(target_os = "macos"))]
+const LOCAL_LABEL_PREFIX: &str = ".L";
+
+/// An incremental compile cache, reusing the assembly generated for the regions of a graph
+/// that did not change between compilations. A region is the main function of a graph,
+/// or of one of its subgraphs, together with its error messages and mapping accessors.
+/// Regions are keyed by the content of their rendered QBE IR, so changing a single node
+/// only invalidates the region containing it.
+///
+/// Use it through [`CompileOptions::cache`](super::CompileOptions::cache). The same cache
+/// can be shared among many graphs and threads. The cache is not used for compilations
+/// with debug information.
+#[derive(Debug, Default)]
+pub struct CompileCache {
+    regions: Mutex<HashMap<u64, CachedRegion>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct CachedRegion {
+    ir: String,
+    assembly: String,
+}
+
+impl CompileCache {
+    /// Creates a new empty cache.
+    pub fn new() -> CompileCache {
+        CompileCache::default()
+    }
+
+    /// The number of regions currently stored in the cache.
+    pub fn len(&self) -> usize {
+        self.regions.lock().expect("poisoned").len()
+    }
+
+    /// Whether the cache has no regions stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of regions whose assembly was reused from the cache so far.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of regions whose assembly had to be generated so far.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Removes all regions from the cache. The hit and miss counters are kept.
+    pub fn clear(&self) {
+        self.regions.lock().expect("poisoned").clear();
+    }
+
+    /// Creates the assembly for the supplied regions of a graph, reusing the assembly of
+    /// the regions already in the cache.
+    pub(crate) fn create_assembly(
+        &self,
+        regions: &[qbe::Module<'static>],
+    ) -> Result<String, Error> {
+        let mut assembly = String::new();
+
+        for (region_id, region) in regions.iter().enumerate() {
+            let region_assembly = self.get_or_create(&region.to_string())?;
+            assembly.push_str(&localize_labels(&region_assembly, region_id));
+        }
+
+        Ok(assembly)
+    }
+
+    /// Gets the assembly for the rendered QBE IR of a region, invoking QBE if it is not
+    /// cached yet.
+    fn get_or_create(&self, ir: &str) -> Result<String, Error> {
+        let hash = murmur_hash64a(ir.as_bytes(), HASH_SEED);
+
+        if let Some(cached) = self.regions.lock().expect("poisoned").get(&hash) {
+            if cached.ir == ir {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(cached.assembly.clone());
+            }
+        }
+
+        // QBE is invoked without holding the lock, so that other compilations can proceed.
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let assembly = create_assembly(ir, None)?;
+        self.regions.lock().expect("poisoned").insert(
+            hash,
+            CachedRegion {
+                ir: ir.to_string(),
+                assembly: assembly.clone(),
+            },
+        );
+
+        Ok(assembly)
+    }
+}
+
+/// QBE numbers local labels per invocation, so the assembly of different regions uses
+/// the same labels. This makes the labels of a region unique by tagging them with the
+/// region id. Lines with string data are left untouched, since they may contain error
+/// messages with anything in them.
+fn localize_labels(assembly: &str, region_id: usize) -> String {
+    let mut localized = String::with_capacity(assembly.len());
+
+    for line in assembly.lines() {
+        if line.trim_start().starts_with(".ascii") {
+            localized.push_str(line);
+        } else {
+            localize_line(line, region_id, &mut localized);
+        }
+        localized.push('\n');
+    }
+
+    localized
+}
+
+/// Whether a character can be part of a symbol or of a label in the assembly.
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$')
+}
+
+/// Tags the local labels in a line of assembly with the region id. Only whole tokens of
+/// the form `<prefix><kind><number>` are local labels, so that symbols which merely
+/// contain such text (e.g., `$run.Lbb1`) are left untouched.
+fn localize_line(line: &str, region_id: usize, localized: &mut String) {
+    let mut rest = line;
+
+    while !rest.is_empty() {
+        let token_len = match rest.find(|c| !is_symbol_char(c)) {
+            Some(0) => rest.chars().next().map_or(0, char::len_utf8),
+            Some(len) => len,
+            None => rest.len(),
+        };
+        let (token, tail) = rest.split_at(token_len);
+        rest = tail;
+
+        let label = token.strip_prefix(LOCAL_LABEL_PREFIX).and_then(|label| {
+            ["bb", "fp"].into_iter().find_map(|kind| {
+                let number = label.strip_prefix(kind)?;
+                (!number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+                    .then_some((kind, number))
+            })
+        });
+        if let Some((kind, number)) = label {
+            localized.push_str(&format!("{LOCAL_LABEL_PREFIX}r{region_id}{kind}{number}"));
+        } else {
+            localized.push_str(token);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_localize_labels_matches_whole_tokens() {
+        let p = LOCAL_LABEL_PREFIX;
+        let assembly = format!(
+            "{p}bb2:\n\tjmp {p}bb12\n\tmovsd {p}fp3(%rip), %xmm0\n\tcall $run{p}bb1\n\
+            \tleaq {p}bbx(%rip), %rax\n\t.ascii \"{p}bb4\"\n"
+        );
+        assert_eq!(
+            localize_labels(&assembly, 7),
+            format!(
+                "{p}r7bb2:\n\tjmp {p}r7bb12\n\tmovsd {p}r7fp3(%rip), %xmm0\n\
+                \tcall $run{p}bb1\n\tleaq {p}bbx(%rip), %rax\n\t.ascii \"{p}bb4\"\n"
+            )
+        );
+    }
+}
@@ -1,26 +1,295 @@
+mod c_artifact;
+mod cache;
+mod object_cache;
+mod observer;
 mod optimize;
+mod profile;
 mod qbe_app;
+mod strict_fp;
 
 use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
     io::Write,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
+    sync::{Arc, OnceLock},
 };
 use tempfile::NamedTempFile;
 
 use crate::Function;
 
-use super::{Error, Graph, Node, SLOT_SIZE};
+use super::{check, Error, Graph, Node, Ref, SLOT_SIZE};
+
+pub use c_artifact::CArtifact;
+pub use cache::CompileCache;
+pub use object_cache::COMPILE_CACHE_ENV;
+pub use observer::{CompileObserver, CompileStage};
+pub use optimize::{OptStats, Pruned};
+pub use profile::NodeProfile;
+
+pub(super) use optimize::find_reachable;
+pub(crate) use profile::ProfileCounters;
+
+/// The metadata key under which the mappings, resources and subgraphs pruned from a
+/// compiled graph are listed, as a JSON [`Pruned`] object. This key is only set if
+/// something was actually pruned. See [`Graph::prune_unused`].
+pub const PRUNED_KEY: &str = "jyafn.pruned";
+
+/// The metadata key under which the [`OptStats`] of the compilation that produced a
+/// function are stored, as a JSON object. See [`Function::opt_stats`].
+pub const OPT_STATS_KEY: &str = "jyafn.opt_stats";
+
+/// Options controlling how a graph is compiled into a [`Function`]. The default options
+/// produce the leanest artifacts.
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// Whether to emit debug information in the compiled shared object. When set, each
+    /// region of the graph (the main function, subgraphs and mapping accessors) gets its
+    /// own source file in the line table, where the line number of an instruction is the
+    /// id of the node that generated it plus one. The shared object is also kept on disk
+    /// for the lifetime of the function (see [`Function::shared_object_path`]), so that
+    /// profilers and debuggers can resolve the region symbols.
+    pub debug_info: bool,
+    /// A cache to reuse the assembly of the regions of the graph that did not change
+    /// since a previous compilation (see [`CompileCache`]). This is useful when
+    /// recompiling a graph many times with small changes. The cache is not used when
+    /// `debug_info` is set.
+    pub cache: Option<Arc<CompileCache>>,
+    /// A directory in which to store the linked shared objects, so that compiling the
+    /// same graph again, even in another process, loads the stored shared object instead
+    /// of invoking QBE, the assembler and the linker. Shared objects are keyed by the
+    /// content of the rendered QBE IR. Since the IR contains the memory addresses of
+    /// mappings, resources and pure functions, graphs using these will rarely hit the
+    /// cache across processes. If unset, the directory in the [`COMPILE_CACHE_ENV`]
+    /// environment variable is used, if any. The directory is not used when `debug_info`
+    /// is set.
+    pub cache_dir: Option<PathBuf>,
+    /// An observer to be notified of the time each stage of the compilation takes and of
+    /// the size of what it produces. Setting it does not change how graphs are compiled.
+    pub observer: Option<Arc<dyn CompileObserver>>,
+    /// Whether to instrument each node of the main function of the graph to count how
+    /// many times it runs and how long it takes (see [`Function::profile_report`]). This
+    /// slows the function down considerably, so use it only to find hot spots. Neither
+    /// the compile cache nor the cache directory are used when `profile` is set.
+    pub profile: bool,
+    /// Whether to guarantee that the compiled function produces bit-for-bit the same
+    /// results on every machine of the same architecture and operating system, and on
+    /// both x86_64 and aarch64, given the same inputs.
+    ///
+    /// QBE never fuses operations (e.g., into FMA instructions), never reassociates
+    /// floating point arithmetic and maps each arithmetic operation to a single IEEE 754
+    /// double precision instruction: SSE2 on x86_64 (never the x87 unit) and scalar FP
+    /// on aarch64. Constant folding, both in jyafn and in QBE, is also done in IEEE 754
+    /// double precision. In strict mode, QBE is additionally invoked with an explicit
+    /// target, instead of the one it was built with, and the compile cache is not used.
+    ///
+    /// What is _not_ guaranteed are the results of pure functions implemented by the
+    /// system math library, such as `exp` or `sin`, which may differ in the last bits
+    /// between platforms and library versions. A warning is logged listing these if the
+    /// graph calls any. On other architectures, strict mode is not supported: a warning
+    /// is logged and the graph is compiled as usual.
+    pub strict_fp: bool,
+}
 
 impl Graph {
     /// Renders this graph as a QBE module. This fails if the graph contains illegal
     /// operations that cannot be optimized away (e.g., unconditional errors).
     pub fn render(&self) -> Result<qbe::Module<'static>, Error> {
+        self.render_named("run")
+    }
+
+    /// Renders this graph as a QBE module, using `entry` as the name of the public
+    /// function symbol. This fails if the graph contains illegal operations that cannot be
+    /// optimized away (e.g., unconditional errors) or if `entry` is not a valid symbol
+    /// name.
+    pub fn render_named(&self, entry: &str) -> Result<qbe::Module<'static>, Error> {
+        self.render_profiled(entry, None)
+    }
+
+    /// Renders this graph as a single QBE module, instrumenting the main function with
+    /// the supplied profile counters, if any.
+    fn render_profiled(
+        &self,
+        entry: &str,
+        profile: Option<&profile::ProfileCounters>,
+    ) -> Result<qbe::Module<'static>, Error> {
         let mut module = qbe::Module::new();
+        self.render_each_region(entry, |graph, namespace| {
+            let profile = profile.filter(|_| namespace == entry);
+            graph.do_render(&mut module, namespace, profile)
+        })?;
+
+        Ok(module)
+    }
+
+    /// Renders each region of this graph as its own QBE module, using `entry` as the name
+    /// of the public function symbol. A region is the main function of the graph or of
+    /// one of its subgraphs, together with its error messages and mapping accessors. The
+    /// first region is the one of the graph itself.
+    pub fn render_regions(&self, entry: &str) -> Result<Vec<qbe::Module<'static>>, Error> {
+        let mut regions = vec![];
+        self.render_each_region(entry, |graph, namespace| {
+            let mut module = qbe::Module::new();
+            graph.do_render(&mut module, namespace, None)?;
+            regions.push(module);
+            Ok(())
+        })?;
+
+        Ok(regions)
+    }
+
+    /// Optimizes this graph and calls `render` on the graph and on each of its
+    /// subgraphs, together with the namespace to render them in.
+    fn render_each_region<F>(&self, entry: &str, mut render: F) -> Result<(), Error>
+    where
+        F: FnMut(&Graph, &str) -> Result<(), Error>,
+    {
+        check_entry_name(entry)?;
+        self.check_subgraph_depth()?;
+
         let mut graph = self.clone();
         graph.do_check_optimize()?;
-        graph.do_render(&mut module, "run");
 
-        Ok(module)
+        // Subgraphs are rendered iteratively to avoid blowing the stack on deeply nested
+        // graphs.
+        let mut to_render = vec![(&graph, entry.to_string())];
+        while let Some((graph, namespace)) = to_render.pop() {
+            render(graph, &namespace)?;
+            to_render.extend(
+                graph
+                    .subgraphs
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(i, subgraph)| (subgraph, format!("{namespace}.graph.{i}"))),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Checks that this graph would compile, without rendering it. This runs type
+    /// annotation on all nodes of the graph and of its subgraphs, checks that the outputs
+    /// match the output layout and looks for illegal instructions that cannot be
+    /// optimized away, returning the same errors [`Graph::compile`] would. Since QBE, the
+    /// assembler and the linker are never invoked, this can be used to validate graphs on
+    /// machines without a toolchain.
+    pub fn verify(&self) -> Result<(), Error> {
+        self.check_subgraph_depth()?;
+
+        let mut graph = self.clone();
+        let mut to_check = vec![&mut graph];
+        while let Some(graph) = to_check.pop() {
+            check::run_checks(graph)?;
+            check::output_layout(graph)?;
+            to_check.extend(graph.subgraphs.iter_mut());
+        }
+
+        graph.input_constraints()?;
+        graph.do_check_optimize()?;
+
+        Ok(())
+    }
+
+    /// Optimizes this graph and drops all mappings, resources and subgraphs that are not
+    /// referenced by any node that is still reachable after optimization, returning what
+    /// was dropped. Unused items are otherwise embedded in the compiled function and in
+    /// its serialized form for nothing. This is done automatically by
+    /// [`Graph::compile`], which records the result under the [`PRUNED_KEY`] metadata key.
+    pub fn prune_unused(&mut self) -> Result<Pruned, Error> {
+        self.do_prune_unused().map(|(_, pruned)| pruned)
+    }
+
+    /// Removes the inputs of this graph that feed no node reachable from the outputs,
+    /// returning their names. The input layout shrinks accordingly, so that callers don't
+    /// need to supply values that are never used. An input is kept if any of its slots is
+    /// used. Inputs used only by operations that must always be run, such as assertions,
+    /// and inputs with a constraint (see [`Graph::constrain_input`]) are also kept.
+    ///
+    /// Unreachable nodes are kept, so that existing [`Ref`]s to nodes remain
+    /// valid. The references of these nodes to removed inputs are replaced by constants.
+    pub fn prune_unused_inputs(&mut self) -> Vec<String> {
+        let Ok(constraints) = self.input_constraints() else {
+            // Can't tell which inputs are constrained. Better not to touch anything.
+            return vec![];
+        };
+        let reachable = optimize::find_reachable(&self.outputs, &self.nodes);
+        let used =
+            optimize::find_used_inputs(&self.outputs, &self.nodes, &reachable, self.inputs.len());
+
+        let mut removed = vec![];
+        let mut removed_slots = vec![false; self.inputs.len()];
+        let mut start = 0;
+        for (name, layout) in &self.input_layout.0 {
+            let slots = start..start + layout.size().in_slots();
+            start = slots.end;
+            if !constraints.contains_key(name) && !slots.clone().any(|slot| used[slot]) {
+                removed.push(name.clone());
+                slots.for_each(|slot| removed_slots[slot] = true);
+            }
+        }
+
+        if removed.is_empty() {
+            return removed;
+        }
+
+        let mut remaining = 0;
+        let replacements = (0..self.inputs.len())
+            .map(|slot| {
+                if removed_slots[slot] {
+                    Ref::Const(self.inputs[slot], 0)
+                } else {
+                    remaining += 1;
+                    Ref::Input(remaining - 1)
+                }
+            })
+            .collect::<Vec<_>>();
+        self.replace_inputs(&replacements);
+        self.inputs = (0..self.inputs.len())
+            .filter(|&slot| !removed_slots[slot])
+            .map(|slot| self.inputs[slot])
+            .collect();
+        self.input_layout
+            .0
+            .retain(|(name, _)| !removed.contains(name));
+
+        if let Ok(mut pinnable) = self.pinnable_inputs() {
+            if removed.iter().any(|name| pinnable.remove(name)) {
+                self.metadata.insert(
+                    super::PINNABLE_INPUTS_KEY.to_string(),
+                    serde_json::to_string(&pinnable).expect("can always serialize names"),
+                );
+            }
+        }
+
+        removed
+    }
+
+    /// Merges the nodes of this graph that perform the same operation on the same
+    /// arguments into the first of them, returning the number of nodes removed. Nodes
+    /// whose operations must always be used, such as assertions, are never merged. This
+    /// is also done when the graph is compiled.
+    ///
+    /// Since the remaining nodes are renumbered, existing [`Ref`]s to nodes of this graph
+    /// are invalidated if anything is merged.
+    pub fn eliminate_common_subexpressions(&mut self) -> usize {
+        optimize::eliminate_common_subexpressions(self)
+    }
+
+    /// Does the work of [`Graph::prune_unused`], also returning statistics on the
+    /// optimizations performed.
+    fn do_prune_unused(&mut self) -> Result<(OptStats, Pruned), Error> {
+        self.check_subgraph_depth()?;
+        let mut stats = self.do_check_optimize()?;
+
+        let mut pruned = Pruned::default();
+        optimize::prune_unreferenced(self, "", &mut pruned, true);
+        stats.mappings_pruned = pruned.mappings.len();
+        stats.resources_pruned = pruned.resources.len();
+        stats.subgraphs_pruned = pruned.subgraphs.len();
+
+        Ok((stats, pruned))
     }
 
     /// Finds illegal instructions in graphs.
@@ -32,28 +301,48 @@ impl Graph {
 
     /// Performs optimizations in the current graph. These optimizations currently are,
     /// in this order:
-    /// 1. Constant evaluation: things like `1 * x` or `2 + 2`, which we already know the
+    /// 1. Common subexpression elimination: computing the same thing only once (see
+    ///    [`Graph::eliminate_common_subexpressions`]).
+    /// 2. Constant evaluation: things like `1 * x` or `2 + 2`, which we already know the
     ///    result beforehand.
-    /// 2. Reachability eliminations: remove nodes that will never be computed.
-    /// 3. Finds illegal instructions that remain: thigs that are not allowed, such as
+    /// 3. Reachability eliminations: remove nodes that will never be computed.
+    /// 4. Finds illegal instructions that remain: thigs that are not allowed, such as
     ///    unconditionally failing assertions.
-    fn do_check_optimize(&mut self) -> Result<(), Error> {
+    fn do_check_optimize(&mut self) -> Result<OptStats, Error> {
+        self.assert_topologically_sorted()?;
+
+        let mut stats = OptStats {
+            nodes_before: self.nodes.len(),
+            ..OptStats::default()
+        };
+
+        // Common subexpressions (before const eval, so that it runs on fewer nodes):
+        stats.subexpressions_merged = optimize::eliminate_common_subexpressions(self);
+
         // Constant evaluation:
-        optimize::const_eval(self);
+        stats.const_folded = optimize::const_eval(self);
 
         // Reachability (needs to be after const eval):
         let reachable = optimize::find_reachable(&self.outputs, &self.nodes);
         optimize::remap_reachable(self, &reachable);
+        stats.nodes_after = self.nodes.len();
+        stats.unreachable_removed =
+            stats.nodes_before - stats.subexpressions_merged - stats.nodes_after;
 
         // Find illegal (needs to be after reachability):
         if let Some(node) = self.find_illegal() {
             return Err(Error::IllegalInstruction(format!("{node:?}")));
         }
 
-        Ok(())
+        Ok(stats)
     }
 
-    fn do_render(&self, module: &mut qbe::Module<'static>, namespace: &str) {
+    fn do_render(
+        &self,
+        module: &mut qbe::Module<'static>,
+        namespace: &str,
+        profile: Option<&profile::ProfileCounters>,
+    ) -> Result<(), Error> {
         // Rendering main:
         let main = module.add_function(qbe::Function::new(
             qbe::Linkage::public(),
@@ -66,6 +355,15 @@ impl Graph {
         ));
         main.add_block("start".to_string());
 
+        // The entries of maps are found relative to the start of the input:
+        if self.input_layout.contains_map() {
+            main.assign_instr(
+                qbe::Value::Temporary("in.base".to_string()),
+                qbe::Type::Long,
+                qbe::Instr::Copy(qbe::Value::Temporary("in".to_string())),
+            );
+        }
+
         for (id, input) in self.inputs.iter().enumerate() {
             main.assign_instr(
                 qbe::Value::Temporary(format!("i{id}")),
@@ -81,6 +379,10 @@ impl Graph {
                 ),
             );
         }
+
+        // Input constraints are checked before anything else is computed:
+        let constraint_errors = self.render_input_constraints(main, namespace)?;
+
         // This is the old naive implementation, kept here in case you need a quick
         // rollback...
         // // Supposes that the nodes were already declared in topological order:
@@ -92,7 +394,8 @@ impl Graph {
         // }
 
         // optimize::Statements::build(&self.nodes).render_into(self, &reachable, main, namespace);
-        optimize::Statements::build(&self.nodes).render_into(self, main, namespace);
+        optimize::Statements::build(&self.nodes, &self.outputs)
+            .render_into(self, main, namespace, profile);
 
         for output in &self.outputs {
             main.add_instr(qbe::Instr::Store(
@@ -113,13 +416,16 @@ impl Graph {
         main.add_instr(qbe::Instr::Ret(Some(qbe::Value::Const(0))));
 
         // Render error messages:
-        for (error_id, error) in self.errors.iter().enumerate() {
+        let errors = (0..self.errors.len())
+            .map(|error_id| self.error_text(error_id))
+            .chain(constraint_errors);
+        for (error_id, error) in errors.enumerate() {
             module.add_data(qbe::DataDef::new(
                 qbe::Linkage::private(),
                 format!("{namespace}.error.{error_id}"),
                 None,
                 vec![
-                    (qbe::Type::Byte, qbe::DataItem::Str(error.to_string())),
+                    (qbe::Type::Byte, qbe::DataItem::Str(error)),
                     (qbe::Type::Byte, qbe::DataItem::Const(0)),
                 ],
             ));
@@ -130,10 +436,7 @@ impl Graph {
             module.add_function(mapping.render(format!("{namespace}.mapping.{name}")));
         }
 
-        // Render sub-graphs:
-        for (i, subgraph) in self.subgraphs.iter().enumerate() {
-            subgraph.do_render(module, &format!("{namespace}.graph.{i}"))
-        }
+        Ok(())
     }
 
     // fn render(&self) -> &'static str {
@@ -155,30 +458,485 @@ impl Graph {
     //     "#
     // }
 
+    /// Renders this graph as QBE IR code, like [`Graph::render`], with a comment before
+    /// the instructions computing each node, giving the id of the node and the name of
+    /// its op, as in `# n3: Abs`. Node ids are those of the graph (or subgraph) after
+    /// optimization. Comments are ignored by QBE, so this compiles to the same code.
+    pub fn render_annotated(&self) -> Result<String, Error> {
+        let entry = "run";
+        let mut ops = HashMap::new();
+        self.render_each_region(entry, |graph, namespace| {
+            ops.insert(
+                namespace.to_string(),
+                graph.nodes.iter().map(Node::op_name).collect(),
+            );
+            Ok(())
+        })?;
+
+        Ok(annotate_nodes(&self.render_named(entry)?.to_string(), &ops))
+    }
+
     /// Renders this graph as assembly code for the current machine's architecture,
-    /// using a standard assembler under the hood.
+    /// using a standard assembler under the hood. The assembly can be persisted and
+    /// loaded later with [`Function::from_assembly`].
     pub fn render_assembly(&self) -> Result<String, Error> {
-        let rendered = self.render()?;
-        create_assembly(rendered)
+        self.render_assembly_with("run", &CompileOptions::default())
+    }
+
+    /// Renders this graph as assembly code for the current machine's architecture, as
+    /// it would be compiled by [`Graph::compile_with`] with the same `entry` and
+    /// `options`. Only [`CompileOptions::debug_info`] and [`CompileOptions::strict_fp`]
+    /// affect the result.
+    pub fn render_assembly_with(
+        &self,
+        entry: &str,
+        options: &CompileOptions,
+    ) -> Result<String, Error> {
+        let target = options.strict_fp.then(|| strict_fp::check(self)).flatten();
+        let graph = self.optimized()?;
+        let mut module = graph.render_named(entry)?;
+        graph.sign(entry, &mut module);
+        let ir = module.to_string();
+        let ir = if options.debug_info {
+            annotate_debug_info(&ir)
+        } else {
+            ir
+        };
+
+        create_assembly(ir, target)
     }
 
     /// Compiles this graph to machine code and loads the resulting shared object into
     /// the current process.
     pub fn compile(&self) -> Result<Function, Error> {
-        let assembly = self.render_assembly()?;
+        self.compile_named("run")
+    }
+
+    /// Compiles this graph to machine code and loads the resulting shared object into
+    /// the current process, using `entry` as the name of the exported function symbol.
+    /// Use this if you need to combine many functions without symbol collisions.
+    pub fn compile_named(&self, entry: &str) -> Result<Function, Error> {
+        self.compile_with(entry, &CompileOptions::default())
+    }
+
+    /// Compiles this graph to machine code and loads the resulting shared object into
+    /// the current process, storing the shared object in `cache_dir` and reusing the one
+    /// stored by a previous compilation of the same graph, if any. See
+    /// [`CompileOptions::cache_dir`].
+    pub fn compile_cached(&self, cache_dir: &Path) -> Result<Function, Error> {
+        self.compile_with(
+            "run",
+            &CompileOptions {
+                cache_dir: Some(cache_dir.to_owned()),
+                ..CompileOptions::default()
+            },
+        )
+    }
+
+    /// Compiles this graph to machine code and loads the resulting shared object into
+    /// the current process, reporting the time each stage of the compilation takes to
+    /// `observer`. See [`CompileObserver`].
+    pub fn compile_with_observer(
+        &self,
+        observer: Arc<dyn CompileObserver>,
+    ) -> Result<Function, Error> {
+        self.compile_with(
+            "run",
+            &CompileOptions {
+                observer: Some(observer),
+                ..CompileOptions::default()
+            },
+        )
+    }
+
+    /// Compiles this graph to machine code and loads the resulting shared object into
+    /// the current process, instrumenting each node to count how many times it runs and
+    /// how long it takes. See [`CompileOptions::profile`].
+    pub fn compile_profiled(&self) -> Result<Function, Error> {
+        self.compile_with(
+            "run",
+            &CompileOptions {
+                profile: true,
+                ..CompileOptions::default()
+            },
+        )
+    }
+
+    /// Compiles this graph to machine code and loads the resulting shared object into
+    /// the current process, using `entry` as the name of the exported function symbol
+    /// and the supplied [`CompileOptions`].
+    pub fn compile_with(&self, entry: &str, options: &CompileOptions) -> Result<Function, Error> {
+        let mut timer = observer::StageTimer::new(options.observer.as_deref());
+        let graph = self.optimized()?;
+
+        // The counters are indexed by the ids of the nodes of the optimized graph, which
+        // is kept by the function alongside the counters.
+        let profile = options
+            .profile
+            .then(|| Arc::new(profile::ProfileCounters::new(graph.nodes.len())));
+        let target = options
+            .strict_fp
+            .then(|| strict_fp::check(&graph))
+            .flatten();
+
+        let object_cache = match &options.cache_dir {
+            _ if options.debug_info || options.profile => None,
+            Some(dir) => Some(object_cache::ObjectCache::new(dir)),
+            None => object_cache::ObjectCache::from_env(),
+        };
+        // The whole IR is only needed if there are no regions to be compiled separately
+        // or to look the shared object up in the object cache.
+        let regions = match &options.cache {
+            Some(cache) if !options.debug_info && !options.profile && target.is_none() => {
+                Some((cache, graph.render_regions(entry)?))
+            }
+            _ => None,
+        };
+        let ir = if regions.is_none() || object_cache.is_some() {
+            let mut module = graph.render_profiled(entry, profile.as_deref())?;
+            // Only the shared objects that are kept need to be loadable elsewhere:
+            if options.debug_info {
+                graph.sign(entry, &mut module);
+            }
+            let ir = module.to_string();
+            Some(if options.debug_info {
+                annotate_debug_info(&ir)
+            } else {
+                ir
+            })
+        } else {
+            None
+        };
+        let ir_size = match (&ir, &regions) {
+            (Some(ir), _) => ir.len(),
+            (None, Some((_, regions))) if timer.is_observed() => {
+                regions.iter().map(|region| region.to_string().len()).sum()
+            }
+            _ => 0,
+        };
+        timer.finish(CompileStage::Optimize, ir_size);
+
+        let mut key = None;
+        if let Some((object_cache, ir)) = object_cache.as_ref().zip(ir.as_ref()) {
+            let ir_key = object_cache::ObjectCache::key(ir);
+            if let Some(shared_object) = object_cache.get(&ir_key, &graph, entry)? {
+                return Function::init(self.kept(&graph), shared_object, entry, false, None);
+            }
+            key = Some(ir_key);
+        }
+
+        let assembly = if let Some((cache, regions)) = &regions {
+            cache.create_assembly(regions)?
+        } else {
+            create_assembly(
+                ir.as_ref()
+                    .expect("IR is rendered when there are no regions"),
+                target,
+            )?
+        };
+        timer.finish(CompileStage::Qbe, assembly.len());
         let unlinked = assemble(&assembly)?;
+        timer.finish(CompileStage::Assemble, unlinked.len());
         let shared_object = link(&unlinked)?;
+        let shared_object_size = if timer.is_observed() {
+            std::fs::metadata(shared_object.path())?.len() as usize
+        } else {
+            0
+        };
+        timer.finish(CompileStage::Link, shared_object_size);
+
+        if let Some((object_cache, key)) = object_cache.zip(key) {
+            object_cache.put(&key, &graph, entry, shared_object.path());
+        }
+
+        let profile = profile.map(|profile| (profile, graph.clone()));
+        Function::init(
+            self.kept(&graph),
+            shared_object,
+            entry,
+            options.debug_info,
+            profile,
+        )
+    }
+}
+
+impl Graph {
+    /// Creates the optimized and pruned copy of this graph that is rendered into the
+    /// function compiled from it, recording what was done in its metadata.
+    fn optimized(&self) -> Result<Graph, Error> {
+        let mut graph = self.clone();
+        let (stats, pruned) = graph.do_prune_unused()?;
+        if !pruned.is_empty() {
+            graph
+                .metadata
+                .insert(PRUNED_KEY.to_string(), serde_json::to_string(&pruned)?);
+        }
+        graph
+            .metadata
+            .insert(OPT_STATS_KEY.to_string(), serde_json::to_string(&stats)?);
+
+        Ok(graph)
+    }
+
+    /// Creates the copy of this graph that is kept by the function compiled from it. This
+    /// is the graph as it was built, so that node ids stay the same as the original ones,
+    /// minus the mappings, resources and subgraphs that no node references, with the
+    /// metadata recording what was done to `optimized`. Items referenced only by nodes
+    /// that were optimized away are kept, so that the graph can still be compiled again.
+    fn kept(&self, optimized: &Graph) -> Graph {
+        let mut graph = self.clone();
+        optimize::prune_unreferenced(&mut graph, "", &mut Pruned::default(), false);
+        for key in [PRUNED_KEY, OPT_STATS_KEY] {
+            match optimized.metadata.get(key) {
+                Some(value) => graph.metadata.insert(key.to_string(), value.clone()),
+                None => graph.metadata.remove(key),
+            };
+        }
+
+        graph
+    }
+
+    /// Whether the code rendered for this graph calls into the jyafn runtime, either
+    /// from its operations (see [`Op::calls_runtime`](crate::op::Op::calls_runtime)),
+    /// from its input constraints or from its mapping accessors. Such code hardcodes
+    /// memory addresses of the current process.
+    pub(crate) fn calls_runtime(&self) -> bool {
+        !self.mappings.is_empty()
+            || self.metadata.contains_key(super::INPUT_CONSTRAINTS_KEY)
+            || self.nodes.iter().any(|node| node.op.calls_runtime())
+            || self.subgraphs.iter().any(Graph::calls_runtime)
+    }
+
+    /// Exports the signature (see [`Signature`]) of the function rendered from this graph
+    /// in `module`.
+    fn sign(&self, entry: &str, module: &mut qbe::Module<'static>) {
+        let signature = Signature::new(self, &module.to_string());
+        module.add_data(signature.render(entry));
+    }
+
+    /// Creates a function from a shared object that was compiled from this graph
+    /// elsewhere, checking its signature (see [`Signature`]) first. The shared object
+    /// must export the function as `run`.
+    pub(crate) fn load_shared_object(
+        &self,
+        shared_object: NamedTempFile,
+    ) -> Result<Function, Error> {
+        let entry = "run";
+        let graph = self.optimized()?;
+        let expected = Signature::new(&graph, &graph.render_named(entry)?.to_string());
+        let found = Signature::read(shared_object.path(), entry)?;
+
+        if found.input_size != expected.input_size || found.output_size != expected.output_size {
+            return Err(format!(
+                "shared object takes {} bytes of input and returns {} bytes of output, but \
+                the graph takes {} bytes and returns {} bytes",
+                found.input_size, found.output_size, expected.input_size, expected.output_size
+            )
+            .into());
+        }
+        if found.process != expected.process && graph.calls_runtime() {
+            return Err(
+                "shared object was compiled in another process. Since this graph calls \
+                into the jyafn runtime (e.g., it may raise errors or it uses mappings, \
+                resources or pure functions), the compiled code hardcodes memory addresses \
+                that are only valid in the process where the graph was rendered"
+                    .to_string()
+                    .into(),
+            );
+        }
+        if found.ir_hash != expected.ir_hash {
+            return Err("shared object was not compiled from this graph"
+                .to_string()
+                .into());
+        }
+
+        Function::init(self.kept(&graph), shared_object, entry, false, None)
+    }
+}
 
-        Function::init(self.clone(), shared_object)
+/// Assembles and links assembly code into a shared object, without invoking QBE.
+pub(crate) fn assemble_and_link(assembly: &str) -> Result<NamedTempFile, Error> {
+    link(&assemble(assembly)?)
+}
+
+/// What a shared object compiled from a graph exports about the function it contains,
+/// under the symbol `<entry>.signature`: the sizes of its input and output, in bytes, a
+/// token identifying the process that rendered it and a hash of the QBE IR it was
+/// compiled from. Code that calls into the jyafn runtime is only valid in the process
+/// that rendered it, since the IR hardcodes memory addresses; otherwise, matching hashes
+/// mean that the code is valid for a given graph.
+#[derive(Debug)]
+struct Signature {
+    input_size: u64,
+    output_size: u64,
+    process: u64,
+    ir_hash: u64,
+}
+
+impl Signature {
+    const HASH_SEED: u64 = 0x6a79_6166_6e2e_7369;
+
+    fn new(graph: &Graph, ir: &str) -> Signature {
+        Signature {
+            input_size: graph.input_layout.size().in_bytes() as u64,
+            output_size: graph.output_layout.size().in_bytes() as u64,
+            process: process_token(),
+            ir_hash: crate::utils::murmur::murmur_hash64a(ir.as_bytes(), Self::HASH_SEED),
+        }
+    }
+
+    fn render(&self, entry: &str) -> qbe::DataDef<'static> {
+        qbe::DataDef::new(
+            qbe::Linkage::public(),
+            format!("{entry}.signature"),
+            None,
+            [
+                self.input_size,
+                self.output_size,
+                self.process,
+                self.ir_hash,
+            ]
+            .into_iter()
+            .map(|value| (qbe::Type::Long, qbe::DataItem::Const(value)))
+            .collect(),
+        )
+    }
+
+    /// Reads the signature exported by a shared object.
+    fn read(path: &Path, entry: &str) -> Result<Signature, Error> {
+        let library = unsafe {
+            // Safety: the caller vouches for the shared object.
+            libloading::Library::new(path)?
+        };
+        let symbol: libloading::Symbol<*const [u64; 4]> = unsafe {
+            // Safety: the signature is four longs, if the symbol is there at all.
+            library
+                .get(format!("{entry}.signature").as_bytes())
+                .map_err(|err| format!("shared object has no jyafn signature: {err}"))?
+        };
+        let [input_size, output_size, process, ir_hash] = unsafe { **symbol };
+
+        Ok(Signature {
+            input_size,
+            output_size,
+            process,
+            ir_hash,
+        })
+    }
+}
+
+/// A random token identifying the current process. It fits in an `i64`, so that it is
+/// rendered the same way in the QBE IR and in the assembly.
+pub(crate) fn process_token() -> u64 {
+    static TOKEN: OnceLock<u64> = OnceLock::new();
+    *TOKEN.get_or_init(|| RandomState::new().hash_one(std::process::id()) >> 1)
+}
+
+/// Checks whether the supplied name can be used as the entry symbol of a function.
+fn check_entry_name(entry: &str) -> Result<(), Error> {
+    let mut chars = entry.chars();
+    let is_valid = chars
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if !is_valid {
+        return Err(Error::Other(format!(
+            "invalid entry symbol name {entry:?}: must be a non-empty sequence of ascii \
+            alphanumeric characters or underscores, not starting with a digit"
+        )));
+    }
+
+    Ok(())
+}
+
+/// The name of the function declared by a line of rendered QBE IR code, if any.
+fn declared_function(trimmed: &str) -> Option<&str> {
+    if trimmed.starts_with("function") || trimmed.starts_with("export function") {
+        let (_, name) = trimmed.split_once('$')?;
+        Some(name.split('(').next().unwrap_or(name))
+    } else {
+        None
+    }
+}
+
+/// The id of the node computed by a line of rendered QBE IR code, if any. Auxiliary
+/// temporaries are named `{prefix}_n{id}` (see `op::unique_for`), so they are attributed
+/// to the node they belong to as well.
+fn assigned_node(trimmed: &str) -> Option<usize> {
+    let (temp, _) = trimmed.strip_prefix('%')?.split_once(" =")?;
+    let suffix = temp.rsplit_once('_').map_or(temp, |(_, suffix)| suffix);
+    suffix.strip_prefix('n')?.parse().ok()
+}
+
+/// Adds QBE comments to rendered QBE IR code, marking where the instructions computing
+/// each node start with the id of the node and the name of its op. `ops` has the op
+/// names of the nodes of each function, by function name. Functions not in `ops` are
+/// left as they are.
+fn annotate_nodes(rendered: &str, ops: &HashMap<String, Vec<&'static str>>) -> String {
+    let mut annotated = String::with_capacity(rendered.len());
+    let mut current_ops = None;
+    let mut current_node = None;
+
+    for line in rendered.lines() {
+        let trimmed = line.trim_start();
+
+        if let Some(name) = declared_function(trimmed) {
+            current_ops = ops.get(name);
+            current_node = None;
+        } else if let Some((node_id, op)) =
+            assigned_node(trimmed).and_then(|node_id| Some((node_id, current_ops?.get(node_id)?)))
+        {
+            if current_node != Some(node_id) {
+                annotated.push_str(&format!("\t# n{node_id}: {op}\n"));
+                current_node = Some(node_id);
+            }
+        }
+
+        annotated.push_str(line);
+        annotated.push('\n');
+    }
+
+    annotated
+}
+
+/// Adds QBE debug directives to rendered QBE IR code. Each function is declared as its
+/// own `dbgfile` and every instruction computing a node is tagged with a `dbgloc` whose
+/// line is the node id plus one. Auxiliary temporaries are named `{prefix}_n{id}` (see
+/// `op::unique_for`), so they are attributed to the node they belong to as well.
+fn annotate_debug_info(rendered: &str) -> String {
+    let mut annotated = String::with_capacity(rendered.len());
+    let mut current_line = None;
+
+    for line in rendered.lines() {
+        let trimmed = line.trim_start();
+
+        if let Some(name) = declared_function(trimmed) {
+            annotated.push_str(&format!("dbgfile \"{name}\"\n"));
+            current_line = None;
+        } else if let Some(node_id) = assigned_node(trimmed) {
+            if current_line != Some(node_id + 1) {
+                annotated.push_str(&format!("\tdbgloc {}\n", node_id + 1));
+                current_line = Some(node_id + 1);
+            }
+        }
+
+        annotated.push_str(line);
+        annotated.push('\n');
     }
+
+    annotated
 }
 
-/// Invokes QBE over some rendered QBE IR code. The result is assembly code.
-fn create_assembly<R>(rendered: R) -> Result<String, Error>
+/// Invokes QBE over some rendered QBE IR code, for the given QBE target or for the
+/// default one. The result is assembly code.
+fn create_assembly<R>(rendered: R, target: Option<&str>) -> Result<String, Error>
 where
     R: std::fmt::Display,
 {
     let mut qbe = Command::new(qbe_app::get_qbe()?)
+        .args(target.map(|target| ["-t", target]).into_iter().flatten())
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
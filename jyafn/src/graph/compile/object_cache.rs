@@ -0,0 +1,146 @@
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+use super::{Error, Graph};
+
+/// The environment variable with the directory in which to store compiled shared
+/// objects, when [`CompileOptions::cache_dir`](super::CompileOptions::cache_dir) is not
+/// set.
+pub const COMPILE_CACHE_ENV: &str = "JYAFN_COMPILE_CACHE";
+
+/// A directory storing the shared objects linked for graphs, so that compiling the same
+/// graph again, even in another process, skips QBE, the assembler and the linker. Shared
+/// objects are keyed by the SHA-256 hash of the rendered QBE IR of the graph, so that
+/// different code never shares an entry. Since the IR
+/// hardcodes the memory addresses of mappings, resources and pure functions, graphs
+/// using these only hit the cache within the same process, or when the addresses happen
+/// to coincide.
+///
+/// Each entry is stored as `<key>.so`, together with a `<key>.json` file describing the
+/// function it contains, which is checked against the graph before the shared object is
+/// used.
+#[derive(Debug)]
+pub(crate) struct ObjectCache {
+    dir: PathBuf,
+}
+
+/// What is known about the function in a cached shared object.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct CachedObject {
+    entry: String,
+    input_size: usize,
+    output_size: usize,
+}
+
+impl CachedObject {
+    fn for_graph(graph: &Graph, entry: &str) -> CachedObject {
+        CachedObject {
+            entry: entry.to_string(),
+            input_size: graph.input_layout.size().in_bytes(),
+            output_size: graph.output_layout.size().in_bytes(),
+        }
+    }
+}
+
+impl ObjectCache {
+    /// Creates a cache on top of the given directory. The directory is created when the
+    /// first entry is stored.
+    pub(crate) fn new(dir: impl Into<PathBuf>) -> ObjectCache {
+        ObjectCache { dir: dir.into() }
+    }
+
+    /// Creates a cache on the directory set in [`COMPILE_CACHE_ENV`], if any.
+    pub(crate) fn from_env() -> Option<ObjectCache> {
+        std::env::var_os(COMPILE_CACHE_ENV)
+            .filter(|dir| !dir.is_empty())
+            .map(ObjectCache::new)
+    }
+
+    /// The key of the shared object for the given rendered QBE IR.
+    pub(crate) fn key(ir: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!(
+            "{}-{}\n",
+            std::env::consts::ARCH,
+            std::env::consts::OS
+        ));
+        hasher.update(ir);
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    fn paths(&self, key: &str) -> (PathBuf, PathBuf) {
+        (
+            self.dir.join(format!("{key}.so")),
+            self.dir.join(format!("{key}.json")),
+        )
+    }
+
+    /// Gets a copy of the shared object stored under `key`, if it exists and contains
+    /// the function of `entry` for the given graph.
+    pub(crate) fn get(
+        &self,
+        key: &str,
+        graph: &Graph,
+        entry: &str,
+    ) -> Result<Option<NamedTempFile>, Error> {
+        let (object_path, info_path) = self.paths(key);
+        let Ok(info) = std::fs::read(&info_path) else {
+            return Ok(None);
+        };
+        match serde_json::from_slice::<CachedObject>(&info) {
+            Ok(info) if info == CachedObject::for_graph(graph, entry) => {}
+            _ => return Ok(None),
+        }
+
+        // Copies the shared object, so that it cannot change while it is loaded.
+        let mut shared_object = NamedTempFile::new()?;
+        let Ok(mut cached) = std::fs::File::open(&object_path) else {
+            return Ok(None);
+        };
+        std::io::copy(&mut cached, shared_object.as_file_mut())?;
+
+        Ok(Some(shared_object))
+    }
+
+    /// Stores the shared object for the given graph under `key`. This is best-effort:
+    /// since the function is already compiled, failures are only logged.
+    pub(crate) fn put(&self, key: &str, graph: &Graph, entry: &str, shared_object: &Path) {
+        if let Err(err) = self.try_put(key, graph, entry, shared_object) {
+            log::warn!(
+                "failed to store shared object in {}: {err}",
+                self.dir.display()
+            );
+        }
+    }
+
+    fn try_put(
+        &self,
+        key: &str,
+        graph: &Graph,
+        entry: &str,
+        shared_object: &Path,
+    ) -> Result<(), Error> {
+        let (object_path, info_path) = self.paths(key);
+        let info = serde_json::to_vec(&CachedObject::for_graph(graph, entry))?;
+        std::fs::create_dir_all(&self.dir)?;
+
+        // Files are written in full before being moved in place, so that concurrent
+        // readers never see partial entries. The info goes last, since it is what marks
+        // the entry as present.
+        let object = NamedTempFile::new_in(&self.dir)?;
+        std::fs::copy(shared_object, object.path())?;
+        object.persist(&object_path).map_err(|err| err.error)?;
+
+        let mut info_file = NamedTempFile::new_in(&self.dir)?;
+        std::io::Write::write_all(&mut info_file, &info)?;
+        info_file.persist(&info_path).map_err(|err| err.error)?;
+
+        Ok(())
+    }
+}
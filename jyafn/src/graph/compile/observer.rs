@@ -0,0 +1,80 @@
+use std::fmt::{self, Display};
+use std::time::{Duration, Instant};
+
+/// A stage in the compilation of a graph into a [`Function`](crate::Function), as
+/// reported to a [`CompileObserver`]. Stages are always run in the order they are
+/// declared here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CompileStage {
+    /// Optimizing the graph, which includes constant evaluation and pruning, and
+    /// rendering it as QBE IR. The size is the one of the rendered IR.
+    Optimize,
+    /// Generating assembly from the QBE IR. The size is the one of the assembly.
+    Qbe,
+    /// Assembling the object file. The size is the one of the object.
+    Assemble,
+    /// Linking the object into a shared object. The size is the one of the shared object.
+    Link,
+}
+
+impl CompileStage {
+    /// The name of this stage, in lowercase.
+    pub fn name(self) -> &'static str {
+        match self {
+            CompileStage::Optimize => "optimize",
+            CompileStage::Qbe => "qbe",
+            CompileStage::Assemble => "assemble",
+            CompileStage::Link => "link",
+        }
+    }
+}
+
+impl Display for CompileStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Receives timing and size information on each stage of the compilation of a graph. Use
+/// it through [`CompileOptions::observer`](super::CompileOptions::observer) or
+/// [`Graph::compile_with_observer`](crate::Graph::compile_with_observer).
+///
+/// Stages are reported as they finish. Stages that fail are not reported and stages
+/// skipped because their result was found in a cache (see
+/// [`CompileOptions::cache_dir`](super::CompileOptions::cache_dir)) are not run, and
+/// therefore not reported either.
+pub trait CompileObserver: fmt::Debug + Send + Sync {
+    /// Called when a compilation stage finishes, with the time it took and the size in
+    /// bytes of what it produced.
+    fn on_stage(&self, stage: CompileStage, elapsed: Duration, bytes: usize);
+}
+
+/// Times the stages of a compilation, reporting them to an optional observer.
+pub(crate) struct StageTimer<'a> {
+    observer: Option<&'a dyn CompileObserver>,
+    start: Instant,
+}
+
+impl<'a> StageTimer<'a> {
+    pub(crate) fn new(observer: Option<&'a dyn CompileObserver>) -> StageTimer<'a> {
+        StageTimer {
+            observer,
+            start: Instant::now(),
+        }
+    }
+
+    /// Whether there is anyone to report to. Use this to avoid computing sizes for
+    /// nothing.
+    pub(crate) fn is_observed(&self) -> bool {
+        self.observer.is_some()
+    }
+
+    /// Reports that `stage` finished, producing `bytes` bytes, and starts timing the
+    /// next stage.
+    pub(crate) fn finish(&mut self, stage: CompileStage, bytes: usize) {
+        if let Some(observer) = self.observer {
+            observer.on_stage(stage, self.start.elapsed(), bytes);
+        }
+        self.start = Instant::now();
+    }
+}
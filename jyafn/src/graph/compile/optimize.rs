@@ -1,8 +1,15 @@
 //! Graph optimizations (those not covered by qbe).
 
-use std::collections::{BTreeMap, BTreeSet};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use crate::{Graph, Node, Ref};
+use crate::op::{
+    CallGraph, CallMapping, CallResource, LoadMappingValue, LoadOrDefaultMappingValue,
+    LoadSubgraphOutput,
+};
+use crate::{Graph, Node, Ref, Type};
+
+use super::profile::ProfileCounters;
 
 /// Even though QBE can make a good job of finding unused data, sometimes it cannot
 /// optimize everything out. One example are pfuncs. Since, fot QBE, the call might as
@@ -44,6 +51,31 @@ pub fn find_reachable(outputs: &[Ref], nodes: &[Node]) -> Vec<bool> {
     reachable
 }
 
+/// Finds the input slots used by the outputs or by the reachable nodes of a graph, given
+/// the result of [`find_reachable`]. Since operations that must always be used are
+/// reachable, so are the inputs they depend on.
+pub fn find_used_inputs(
+    outputs: &[Ref],
+    nodes: &[Node],
+    reachable: &[bool],
+    n_inputs: usize,
+) -> Vec<bool> {
+    let mut used = vec![false; n_inputs];
+    let reachable_args = nodes
+        .iter()
+        .zip(reachable)
+        .filter(|(_, &is_reachable)| is_reachable)
+        .flat_map(|(node, _)| &node.args);
+
+    for &arg in outputs.iter().chain(reachable_args) {
+        if let Ref::Input(input_id) = arg {
+            used[input_id] = true;
+        }
+    }
+
+    used
+}
+
 /// Remaps the nodes of this graph to exclude unreachable nodes.
 pub fn remap_reachable(graph: &mut Graph, reachable: &[bool]) {
     // Create new ids:
@@ -71,6 +103,9 @@ pub fn remap_reachable(graph: &mut Graph, reachable: &[bool]) {
                 *id = id_map[id];
             }
         }
+        if let Type::Ptr { origin } = &mut node.ty {
+            *origin = id_map[origin];
+        }
     }
 
     // Rewrite references in output:
@@ -81,19 +116,214 @@ pub fn remap_reachable(graph: &mut Graph, reachable: &[bool]) {
     }
 }
 
-/// Runs constant evaluation optimization on the graph.
-pub fn const_eval(graph: &mut Graph) {
-    let mut visited = vec![false; graph.nodes.len()];
+/// Merges every node into the first node performing the same operation on the same
+/// arguments, rewriting the references to it, and drops the merged nodes. This is a
+/// value numbering pass over the node list: since nodes are in topological order, the
+/// arguments of a node are already rewritten when it is visited, so that whole repeated
+/// subtrees are merged. Nodes whose operations must always be used are never merged.
+/// Returns the number of merged nodes.
+pub fn eliminate_common_subexpressions(graph: &mut Graph) -> usize {
+    let mut replacements = (0..graph.nodes.len()).collect::<Vec<_>>();
+    // Candidates for each node, bucketed by operation name and arguments, since
+    // operations can only be compared for equality.
+    let mut seen = HashMap::<_, Vec<usize>>::new();
+    let mut keep = vec![true; graph.nodes.len()];
+    let mut merged = 0;
+
+    for node_id in 0..graph.nodes.len() {
+        let node = &mut graph.nodes[node_id];
+        for arg in &mut node.args {
+            if let Ref::Node(id) = arg {
+                *id = replacements[*id];
+            }
+        }
+
+        if node.op.must_use() {
+            continue;
+        }
+
+        let candidates = seen.entry((node.op_name(), node.args.clone())).or_default();
+        let nodes = &graph.nodes;
+        if let Some(&first) = candidates
+            .iter()
+            .find(|&&other| nodes[other].op.is_eq(nodes[node_id].op.as_ref()))
+        {
+            replacements[node_id] = first;
+            keep[node_id] = false;
+            merged += 1;
+        } else {
+            candidates.push(node_id);
+        }
+    }
+
+    for output in &mut graph.outputs {
+        if let Ref::Node(id) = output {
+            *id = replacements[*id];
+        }
+    }
+
+    if merged > 0 {
+        remap_reachable(graph, &keep);
+    }
+
+    merged
+}
+
+/// The mappings, resources and subgraphs dropped from a graph because no reachable node
+/// references them. Items of subgraphs are qualified by the path of the subgraph, e.g.,
+/// `graph.0.my_mapping` is the mapping `my_mapping` of the first subgraph. Subgraph ids
+/// are the ones _before_ pruning.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Pruned {
+    /// The mappings that were dropped.
+    pub mappings: Vec<String>,
+    /// The resources that were dropped.
+    pub resources: Vec<String>,
+    /// The subgraphs that were dropped.
+    pub subgraphs: Vec<String>,
+}
+
+impl Pruned {
+    /// Whether nothing was dropped.
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty() && self.resources.is_empty() && self.subgraphs.is_empty()
+    }
+}
+
+/// Drops the mappings, resources and subgraphs of the graph (and, recursively, of its
+/// subgraphs) that are not referenced by any node. This needs to run after reachability
+/// elimination, so that only nodes that will actually be computed are taken into account.
+/// Subgraphs are also trimmed of their unreachable nodes in the process if `trim` is set.
+/// Otherwise, the nodes of the graph and of its subgraphs are left untouched, so that node
+/// ids remain valid, and only items that no node at all references are dropped.
+pub fn prune_unreferenced(graph: &mut Graph, prefix: &str, pruned: &mut Pruned, trim: bool) {
+    let mut mappings = BTreeSet::new();
+    let mut resources = BTreeSet::new();
+    let mut subgraphs = BTreeSet::new();
+
+    for node in &graph.nodes {
+        let op = node.op.as_any();
+        if let Some(op) = op.downcast_ref::<CallMapping>() {
+            mappings.insert(op.name.clone());
+        } else if let Some(op) = op.downcast_ref::<LoadMappingValue>() {
+            mappings.insert(op.mapping.clone());
+        } else if let Some(op) = op.downcast_ref::<LoadOrDefaultMappingValue>() {
+            mappings.insert(op.mapping.clone());
+        } else if let Some(op) = op.downcast_ref::<CallResource>() {
+            resources.insert(op.name.clone());
+        } else if let Some(op) = op.downcast_ref::<CallGraph>() {
+            subgraphs.insert(op.0);
+        }
+    }
+
+    let mut unused_mappings = graph
+        .mappings
+        .keys()
+        .filter(|name| !mappings.contains(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+    unused_mappings.sort();
+    for name in unused_mappings {
+        graph.mappings.remove(&name);
+        pruned.mappings.push(format!("{prefix}{name}"));
+    }
+
+    let mut unused_resources = graph
+        .resources
+        .keys()
+        .filter(|name| !resources.contains(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+    unused_resources.sort();
+    for name in unused_resources {
+        graph.resources.remove(&name);
+        pruned.resources.push(format!("{prefix}{name}"));
+    }
+
+    // Create new subgraph ids:
+    let id_map = subgraphs
+        .iter()
+        .enumerate()
+        .map(|(new_id, &old_id)| (old_id, new_id))
+        .collect::<BTreeMap<_, _>>();
+
+    // Retain only referenced subgraphs:
+    let mut subgraph_id = 0;
+    graph.subgraphs.retain_mut(|subgraph| {
+        let path = format!("{prefix}graph.{subgraph_id}");
+        subgraph_id += 1;
+
+        if !id_map.contains_key(&(subgraph_id - 1)) {
+            pruned.subgraphs.push(path);
+            return false;
+        }
+
+        if trim {
+            let reachable = find_reachable(&subgraph.outputs, &subgraph.nodes);
+            remap_reachable(subgraph, &reachable);
+        }
+        prune_unreferenced(subgraph, &format!("{path}."), pruned, trim);
+
+        true
+    });
+
+    // Rewrite references in nodes:
+    for node in &mut graph.nodes {
+        if let Some(call) = node.op.downcast_mut::<CallGraph>() {
+            call.0 = id_map[&call.0];
+        } else if let Some(load) = node.op.downcast_mut::<LoadSubgraphOutput>() {
+            load.subgraph = id_map[&load.subgraph];
+        }
+    }
+}
+
+/// Statistics on the optimizations performed on a graph when it was compiled. See
+/// [`crate::Function::opt_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OptStats {
+    /// The number of nodes in the graph before optimization.
+    pub nodes_before: usize,
+    /// The number of nodes merged into an earlier node computing the same thing. See
+    /// [`Graph::eliminate_common_subexpressions`].
+    #[serde(default)]
+    pub subexpressions_merged: usize,
+    /// The number of nodes simplified by constant evaluation, such as `2 + 2` or `x * 1`.
+    pub const_folded: usize,
+    /// The number of nodes removed because they were never used. This includes the nodes
+    /// left unused by constant evaluation, but not the merged nodes.
+    pub unreachable_removed: usize,
+    /// The number of nodes in the graph after optimization.
+    pub nodes_after: usize,
+    /// The number of mappings dropped for being unused (see [`Pruned`]).
+    pub mappings_pruned: usize,
+    /// The number of resources dropped for being unused (see [`Pruned`]).
+    pub resources_pruned: usize,
+    /// The number of subgraphs dropped for being unused (see [`Pruned`]).
+    pub subgraphs_pruned: usize,
+}
 
-    fn search(graph: &mut Graph, visited: &mut [bool], node_id: usize) -> Ref {
-        visited[node_id] = true;
+/// Runs constant evaluation optimization on the graph, returning the number of nodes
+/// that were simplified.
+pub fn const_eval(graph: &mut Graph) -> usize {
+    // What each node visited so far evaluates to, so that all the nodes using a node that
+    // was folded get the folded value.
+    let mut evaluated = vec![None; graph.nodes.len()];
+    let mut folded = 0;
+
+    fn search(
+        graph: &mut Graph,
+        evaluated: &mut [Option<Ref>],
+        folded: &mut usize,
+        node_id: usize,
+    ) -> Ref {
         let mut new_args = graph.nodes[node_id].args.clone();
 
         for r#ref in &mut new_args {
             if let Ref::Node(other) = *r#ref {
-                if !visited[other] {
-                    *r#ref = search(graph, visited, other);
-                }
+                *r#ref = match evaluated[other] {
+                    Some(evald) => evald,
+                    None => search(graph, evaluated, folded, other),
+                };
             }
         }
 
@@ -101,22 +331,30 @@ pub fn const_eval(graph: &mut Graph) {
         node.args = new_args;
 
         let node = &graph.nodes[node_id]; // reborrow as immutable
-        if let Some(evald) = node.op.const_eval(graph, &node.args) {
+        let evald = if let Some(evald) = node.op.const_eval(graph, &node.args) {
+            *folded += 1;
             evald
         } else {
             Ref::Node(node_id)
-        }
+        };
+        evaluated[node_id] = Some(evald);
+
+        evald
     }
 
     let mut new_outputs = graph.outputs.clone();
 
     for output in &mut new_outputs {
         if let Ref::Node(node_id) = *output {
-            *output = search(graph, &mut visited, node_id);
+            *output = match evaluated[node_id] {
+                Some(evald) => evald,
+                None => search(graph, &mut evaluated, &mut folded, node_id),
+            };
         }
     }
 
     graph.outputs = new_outputs;
+    folded
 }
 
 /// The adjacency list of the reverse graph, with everything indexed only by node ids.
@@ -156,6 +394,12 @@ fn find_branches(
             .iter()
             .any(|&other| other >= choose_node_id)
     };
+    // Arguments are always used by the choice itself.
+    let is_argument_accessible_later = |node_id: usize| {
+        reversed[node_id]
+            .iter()
+            .any(|&other| other > choose_node_id)
+    };
 
     // The search queue
     let mut queue = BTreeSet::new();
@@ -183,11 +427,19 @@ fn find_branches(
         queue.insert(t_node);
         true_nodes.insert(t_node);
         true_in_queue += 1;
+
+        if is_argument_accessible_later(t_node) && false_nodes.insert(t_node) {
+            false_in_queue += 1;
+        }
     }
     if let Ref::Node(f_node) = nodes[choose_node_id].args[2] {
         queue.insert(f_node);
         false_nodes.insert(f_node);
         false_in_queue += 1;
+
+        if is_argument_accessible_later(f_node) && true_nodes.insert(f_node) {
+            true_in_queue += 1;
+        }
     }
 
     while let Some(node_id) = queue.pop_last() {
@@ -243,6 +495,78 @@ fn find_branches(
     )
 }
 
+/// Finds the nodes needed only by each one of the values of an [`op::Select`], which
+/// thus need only be computed when that value is selected. Only nodes in `node_ids` are
+/// considered. See also [`find_branches`].
+///
+/// [`op::Select`]: crate::op::Select
+fn find_cases(
+    nodes: &[Node],
+    reversed: &[Vec<usize>],
+    node_ids: &BTreeSet<usize>,
+    select_node_id: usize,
+) -> Vec<BTreeSet<usize>> {
+    let ancestors = |start: Ref| {
+        let mut found = BTreeSet::new();
+        let mut stack = vec![start];
+        while let Some(Ref::Node(node_id)) = stack.pop() {
+            if node_ids.contains(&node_id) && found.insert(node_id) {
+                stack.extend(nodes[node_id].args.iter().copied());
+            }
+        }
+        found
+    };
+
+    let args = &nodes[select_node_id].args;
+    let index_nodes = ancestors(args[0]);
+    let reached = args[1..]
+        .iter()
+        .map(|&arg| ancestors(arg))
+        .collect::<Vec<_>>();
+    let mut cases = reached
+        .iter()
+        .enumerate()
+        .map(|(i, case)| {
+            case.iter()
+                .copied()
+                .filter(|node_id| {
+                    !index_nodes.contains(node_id)
+                        && reached
+                            .iter()
+                            .enumerate()
+                            .all(|(j, other)| i == j || !other.contains(node_id))
+                })
+                .collect::<BTreeSet<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // Nodes also used outside of their case must be computed before the select. Taking
+    // a node out of a case may make its arguments used outside of the case as well, so
+    // this goes on until nothing changes.
+    loop {
+        let mut changed = false;
+        for case in &mut cases {
+            let escaping = case
+                .iter()
+                .copied()
+                .filter(|&node_id| {
+                    reversed[node_id]
+                        .iter()
+                        .any(|user| *user != select_node_id && !case.contains(user))
+                })
+                .collect::<Vec<_>>();
+            for node_id in escaping {
+                case.remove(&node_id);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return cases;
+        }
+    }
+}
+
 /// A restructuring of your good old plain list of instructions into a cool tree structure
 /// that looks a lot like you averaged program written in a structured programming language.
 pub enum StatementOrConditional {
@@ -259,15 +583,31 @@ pub enum StatementOrConditional {
         /// Statements on the `else` side.
         false_side: Statements,
     },
+    /// A choice among many cases.
+    Switch {
+        /// The id of the node that contains the [`op::Select`] operation.
+        ///
+        /// [`op::Select`]: crate::op::Select
+        node_id: usize,
+        /// Statements of each case.
+        cases: Vec<Statements>,
+    },
 }
 
 /// Statements are a list of statements or conditionals.
 pub struct Statements(Vec<StatementOrConditional>);
 
 impl Statements {
-    /// Build the nested conditional structure out of a list of topologically sorted nodes.
-    pub fn build(nodes: &[Node]) -> Statements {
-        let reversed = reverse(nodes);
+    /// Build the nested conditional structure out of a list of topologically sorted nodes
+    /// and the outputs of the graph.
+    pub fn build(nodes: &[Node], outputs: &[Ref]) -> Statements {
+        let mut reversed = reverse(nodes);
+        // Outputs are used after everything else, so they can never be inside a branch.
+        for output in outputs {
+            if let &Ref::Node(node_id) = output {
+                reversed[node_id].push(nodes.len());
+            }
+        }
         let all_node_ids = (0..nodes.len()).collect::<BTreeSet<_>>();
 
         return do_build(all_node_ids, &reversed, nodes);
@@ -298,6 +638,19 @@ impl Statements {
                         true_side: do_build(true_side, reversed, nodes),
                         false_side: do_build(false_side, reversed, nodes),
                     });
+                } else if nodes[node_id].op.as_any().is::<crate::op::Select>() {
+                    let cases = find_cases(nodes, reversed, &node_ids, node_id);
+                    cases.iter().flatten().for_each(|n| {
+                        node_ids.remove(n);
+                    });
+
+                    buffer.push(StatementOrConditional::Switch {
+                        node_id,
+                        cases: cases
+                            .into_iter()
+                            .map(|case| do_build(case, reversed, nodes))
+                            .collect(),
+                    });
                 } else {
                     // Meh! just a plain old normal statement. Add it to the list.
                     buffer.push(StatementOrConditional::Statement(node_id));
@@ -310,19 +663,32 @@ impl Statements {
         }
     }
 
-    /// Render the resulting nested structure into the provided QBE function builder.
-    pub fn render_into(&self, graph: &Graph, func: &mut qbe::Function, namespace: &str) {
+    /// Render the resulting nested structure into the provided QBE function builder. If
+    /// `profile` is set, each node also updates its counters.
+    pub fn render_into(
+        &self,
+        graph: &Graph,
+        func: &mut qbe::Function,
+        namespace: &str,
+        profile: Option<&ProfileCounters>,
+    ) {
         for statement in &self.0 {
             match statement {
                 &StatementOrConditional::Statement(node_id) => {
                     let node = &graph.nodes[node_id];
+                    if let Some(profile) = profile {
+                        profile.render_enter(func, node_id);
+                    }
                     node.op.render_into(
                         graph,
                         Ref::Node(node_id).render(),
                         &node.args,
                         func,
                         namespace,
-                    )
+                    );
+                    if let Some(profile) = profile {
+                        profile.render_exit(func, node_id);
+                    }
                 }
                 StatementOrConditional::Conditional {
                     node_id,
@@ -336,6 +702,9 @@ impl Statements {
                     let false_label = format!("if.false_n{node_id}");
                     let end_label = format!("if.end_n{node_id}");
 
+                    if let Some(profile) = profile {
+                        profile.render_enter(func, *node_id);
+                    }
                     func.add_instr(qbe::Instr::Jnz(
                         condition.render(),
                         true_label.clone(),
@@ -343,7 +712,7 @@ impl Statements {
                     ));
 
                     func.add_block(true_label);
-                    true_side.render_into(graph, func, namespace);
+                    true_side.render_into(graph, func, namespace, profile);
                     func.assign_instr(
                         output.clone(),
                         node.ty.render(),
@@ -352,7 +721,7 @@ impl Statements {
                     func.add_instr(qbe::Instr::Jmp(end_label.clone()));
 
                     func.add_block(false_label);
-                    false_side.render_into(graph, func, namespace);
+                    false_side.render_into(graph, func, namespace, profile);
                     func.assign_instr(
                         output,
                         node.ty.render(),
@@ -360,6 +729,50 @@ impl Statements {
                     );
 
                     func.add_block(end_label);
+                    if let Some(profile) = profile {
+                        profile.render_exit(func, *node_id);
+                    }
+                }
+                StatementOrConditional::Switch { node_id, cases } => {
+                    let output = Ref::Node(*node_id).render();
+                    let node = &graph.nodes[*node_id];
+                    let select = node
+                        .op
+                        .downcast_ref::<crate::op::Select>()
+                        .expect("switch node is a select");
+                    let case_labels = (0..cases.len())
+                        .map(|i| format!("select.case{i}_n{node_id}"))
+                        .collect::<Vec<_>>();
+                    let end_label = format!("select.end_n{node_id}");
+
+                    if let Some(profile) = profile {
+                        profile.render_enter(func, *node_id);
+                    }
+                    select.render_dispatch(
+                        graph,
+                        output.clone(),
+                        &node.args,
+                        &case_labels,
+                        func,
+                        namespace,
+                    );
+
+                    for ((case, label), value) in cases.iter().zip(case_labels).zip(&node.args[1..])
+                    {
+                        func.add_block(label);
+                        case.render_into(graph, func, namespace, profile);
+                        func.assign_instr(
+                            output.clone(),
+                            node.ty.render(),
+                            qbe::Instr::Copy(value.render()),
+                        );
+                        func.add_instr(qbe::Instr::Jmp(end_label.clone()));
+                    }
+
+                    func.add_block(end_label);
+                    if let Some(profile) = profile {
+                        profile.render_exit(func, *node_id);
+                    }
                 }
             }
         }
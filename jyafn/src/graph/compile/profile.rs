@@ -0,0 +1,121 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::SLOT_SIZE;
+
+/// What was measured for a single node of a function compiled with
+/// [`Graph::compile_profiled`](crate::Graph::compile_profiled).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeProfile {
+    /// The number of times the node was run.
+    pub calls: u64,
+    /// The total time spent running the node. This is measured with the timestamp
+    /// counter of the CPU on `x86_64` and in nanoseconds on other architectures. The
+    /// instrumentation itself is included in the measurement, so only compare this
+    /// between nodes of the same function.
+    pub cycles: u64,
+}
+
+/// The counters written by a profiled function. These are two slots per node, with the
+/// number of calls and the cycles spent, whose addresses are hardcoded in the compiled
+/// code. The compiled code updates the counters with plain loads and stores, so counts
+/// are only approximate if the function is called from many threads at once.
+#[derive(Debug)]
+pub(crate) struct ProfileCounters(Box<[AtomicU64]>);
+
+impl ProfileCounters {
+    /// Creates zeroed counters for a graph with `n_nodes` nodes.
+    pub(crate) fn new(n_nodes: usize) -> ProfileCounters {
+        ProfileCounters((0..2 * n_nodes).map(|_| AtomicU64::new(0)).collect())
+    }
+
+    /// The address of the number of calls of a node. The cycles come in the next slot.
+    fn address(&self, node_id: usize) -> u64 {
+        self.0[2 * node_id].as_ptr() as u64
+    }
+
+    /// Reads the counters, keyed by node id. Nodes that were never run are left out.
+    pub(crate) fn report(&self) -> BTreeMap<usize, NodeProfile> {
+        self.0
+            .chunks(2)
+            .enumerate()
+            .map(|(node_id, slots)| {
+                let profile = NodeProfile {
+                    calls: slots[0].load(Ordering::Relaxed),
+                    cycles: slots[1].load(Ordering::Relaxed),
+                };
+                (node_id, profile)
+            })
+            .filter(|(_, profile)| profile.calls > 0)
+            .collect()
+    }
+
+    /// Renders the start of the measurement of a node.
+    pub(crate) fn render_enter(&self, func: &mut qbe::Function, node_id: usize) {
+        func.assign_instr(
+            qbe::Value::Temporary(format!("prof.start_n{node_id}")),
+            qbe::Type::Long,
+            qbe::Instr::Call(qbe::Value::Const(clock as *const () as u64), vec![]),
+        );
+    }
+
+    /// Renders the end of the measurement of a node, which updates its counters.
+    pub(crate) fn render_exit(&self, func: &mut qbe::Function, node_id: usize) {
+        let temp = |name: &str| qbe::Value::Temporary(format!("prof.{name}_n{node_id}"));
+
+        func.assign_instr(
+            temp("end"),
+            qbe::Type::Long,
+            qbe::Instr::Call(qbe::Value::Const(clock as *const () as u64), vec![]),
+        );
+        func.assign_instr(
+            temp("elapsed"),
+            qbe::Type::Long,
+            qbe::Instr::Sub(temp("end"), temp("start")),
+        );
+
+        let counters = [("calls", qbe::Value::Const(1)), ("cycles", temp("elapsed"))];
+        for (slot, (name, increment)) in counters.into_iter().enumerate() {
+            let address = self.address(node_id) + (slot * SLOT_SIZE.in_bytes()) as u64;
+            func.assign_instr(
+                temp(&format!("{name}.ptr")),
+                qbe::Type::Long,
+                qbe::Instr::Copy(qbe::Value::Const(address)),
+            );
+            func.assign_instr(
+                temp(name),
+                qbe::Type::Long,
+                qbe::Instr::Load(qbe::Type::Long, temp(&format!("{name}.ptr"))),
+            );
+            func.assign_instr(
+                temp(name),
+                qbe::Type::Long,
+                qbe::Instr::Add(temp(name), increment),
+            );
+            func.add_instr(qbe::Instr::Store(
+                qbe::Type::Long,
+                temp(&format!("{name}.ptr")),
+                temp(name),
+            ));
+        }
+    }
+}
+
+/// Reads the clock used to measure the time spent in each node.
+extern "C" fn clock() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // Safety: `rdtsc` is available on all `x86_64` processors.
+        unsafe { std::arch::x86_64::_rdtsc() }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        START
+            .get_or_init(std::time::Instant::now)
+            .elapsed()
+            .as_nanos() as u64
+    }
+}
@@ -0,0 +1,123 @@
+//! Support for [`CompileOptions::strict_fp`](super::CompileOptions::strict_fp).
+
+use std::collections::BTreeSet;
+
+use crate::op;
+
+use super::Graph;
+
+/// The pure functions whose results are the same in every machine: they are either
+/// correctly rounded by IEEE 754 or exact.
+const EXACT_PFUNCS: &[&str] = &[
+    "floor",
+    "ceil",
+    "round",
+    "trunc",
+    "sqrt",
+    "rem",
+    "modf",
+    "is_nan",
+    "is_finite",
+    "is_infinite",
+    "fromtimestamp",
+    "timestamp",
+    "year",
+    "month",
+    "day",
+    "hour",
+    "minute",
+    "second",
+    "microsecond",
+    "weekday",
+    "week",
+    "dayofyear",
+];
+
+/// The QBE target for which strict floating point semantics hold in the current machine,
+/// if any.
+pub(super) fn target() -> Option<&'static str> {
+    if cfg!(all(target_arch = "x86_64", target_os = "macos")) {
+        Some("amd64_apple")
+    } else if cfg!(target_arch = "x86_64") {
+        Some("amd64_sysv")
+    } else if cfg!(all(target_arch = "aarch64", target_os = "macos")) {
+        Some("arm64_apple")
+    } else if cfg!(target_arch = "aarch64") {
+        Some("arm64")
+    } else {
+        None
+    }
+}
+
+/// Checks whether the graph can be compiled with strict floating point semantics,
+/// logging a warning for everything that prevents it. Returns the QBE target to compile
+/// for, if strict semantics hold for the current machine.
+pub(super) fn check(graph: &Graph) -> Option<&'static str> {
+    let Some(target) = target() else {
+        log::warn!(
+            "strict floating point semantics are not supported on {}; compiling graph {} \
+            without them",
+            std::env::consts::ARCH,
+            graph.name()
+        );
+        return None;
+    };
+
+    let mut inexact = BTreeSet::new();
+    find_inexact_calls(graph, &mut inexact);
+    if !inexact.is_empty() {
+        log::warn!(
+            "graph {} calls functions whose results may differ between machines: {}",
+            graph.name(),
+            inexact.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+
+    Some(target)
+}
+
+/// Finds the functions called by the graph and its subgraphs whose results may differ
+/// between machines: the pure functions not in [`EXACT_PFUNCS`] and the ones reported by
+/// [`op::Op::inexact_calls`].
+fn find_inexact_calls(graph: &Graph, found: &mut BTreeSet<String>) {
+    for node in &graph.nodes {
+        if let Some(op::Call(name)) = node.op.downcast_ref::<op::Call>() {
+            if !EXACT_PFUNCS.contains(&name.as_str()) {
+                found.insert(name.clone());
+            }
+        }
+        found.extend(node.op.inexact_calls().iter().map(|name| name.to_string()));
+    }
+
+    for subgraph in &graph.subgraphs {
+        find_inexact_calls(subgraph, found);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::layout::{Layout, RefValue};
+
+    #[test]
+    fn test_finds_inexact_op_calls() {
+        let mut graph = Graph::new();
+        let RefValue::Scalar(x) = graph.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let root = graph.insert(op::Call("sqrt".to_string()), vec![x]).unwrap();
+        let mut found = BTreeSet::new();
+        find_inexact_calls(&graph, &mut found);
+        assert!(found.is_empty());
+
+        let root = graph.insert(op::Sigmoid, vec![root]).unwrap();
+        graph
+            .output(RefValue::Scalar(root), Layout::Scalar)
+            .unwrap();
+        find_inexact_calls(&graph, &mut found);
+        assert_eq!(
+            found.into_iter().collect::<Vec<_>>(),
+            vec!["exp".to_string()]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use super::{Error, Graph, Ref, Type};
+
+/// The metadata key under which the input constraints of a graph are stored, as a JSON
+/// object mapping input names to [`InputConstraint`]s. Since they live in the metadata,
+/// the constraints can be inspected without loading the whole graph (see
+/// [`Graph::load_metadata`]).
+pub const INPUT_CONSTRAINTS_KEY: &str = "jyafn.input_constraints";
+
+/// A range constraint on the values of an input of a graph. Bounds are inclusive and an
+/// unset bound is unconstrained. See [`Graph::constrain_input`].
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct InputConstraint {
+    /// The minimum value allowed for the input.
+    pub min: Option<f64>,
+    /// The maximum value allowed for the input.
+    pub max: Option<f64>,
+}
+
+impl Display for InputConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => write!(f, "between {min} and {max}"),
+            (Some(min), None) => write!(f, "at least {min}"),
+            (None, Some(max)) => write!(f, "at most {max}"),
+            (None, None) => write!(f, "anything"),
+        }
+    }
+}
+
+impl InputConstraint {
+    /// Whether `value` satisfies this constraint. NaNs never do.
+    pub fn contains(&self, value: f64) -> bool {
+        self.min.map_or(!value.is_nan(), |min| value >= min)
+            && self.max.map_or(!value.is_nan(), |max| value <= max)
+    }
+}
+
+impl Graph {
+    /// Constrains all the scalar values of the input `name` to be within `min` and `max`,
+    /// inclusive. The compiled function checks the constraint before any other
+    /// computation, raising an error naming the input if it is not satisfied. NaNs never
+    /// satisfy a constraint. Constraining the same input again replaces the previous
+    /// constraint.
+    ///
+    /// Constraints are only checked when the graph is compiled as the entry point, not
+    /// when it is called as a subgraph.
+    pub fn constrain_input(
+        &mut self,
+        name: &str,
+        min: Option<f64>,
+        max: Option<f64>,
+    ) -> Result<(), Error> {
+        let constraint = InputConstraint { min, max };
+        if min.is_none() && max.is_none() {
+            return Err(
+                format!("constraint on input {name:?} must have a minimum or a maximum").into(),
+            );
+        }
+        if min.into_iter().chain(max).any(|bound| !bound.is_finite()) {
+            return Err(format!("bounds of constraint on input {name:?} must be finite").into());
+        }
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                return Err(format!(
+                    "constraint on input {name:?} has minimum {min} greater than maximum {max}"
+                )
+                .into());
+            }
+        }
+
+        let slots = self.input_slots(name)?;
+        if let Some(ty) = slots
+            .map(|slot| self.inputs[slot])
+            .find(|&ty| ty != Type::Float)
+        {
+            return Err(format!(
+                "can only constrain inputs made of scalars, but input {name:?} contains {ty}"
+            )
+            .into());
+        }
+
+        let mut constraints = self.input_constraints()?;
+        constraints.insert(name.to_string(), constraint);
+        self.metadata.insert(
+            INPUT_CONSTRAINTS_KEY.to_string(),
+            serde_json::to_string(&constraints)?,
+        );
+
+        Ok(())
+    }
+
+    /// Gets the input constraints declared for this graph, by input name. See
+    /// [`Graph::constrain_input`].
+    pub fn input_constraints(&self) -> Result<BTreeMap<String, InputConstraint>, Error> {
+        self.metadata
+            .get(INPUT_CONSTRAINTS_KEY)
+            .map(|constraints| serde_json::from_str(constraints))
+            .transpose()
+            .map(Option::unwrap_or_default)
+            .map_err(Error::Json)
+    }
+
+    /// The ids of the input slots associated with the input of a given name.
+    pub(super) fn input_slots(&self, name: &str) -> Result<std::ops::Range<usize>, Error> {
+        let mut start = 0;
+        for (field, layout) in &self.input_layout.0 {
+            let end = start + layout.size().in_slots();
+            if field == name {
+                return Ok(start..end);
+            }
+            start = end;
+        }
+
+        Err(format!("graph has no input named {name:?}").into())
+    }
+
+    /// Renders the checks for the input constraints of this graph in `func`, returning
+    /// the error messages used. These messages are to be rendered after the errors of the
+    /// graph, i.e., the first message has id `self.errors.len()`.
+    pub(crate) fn render_input_constraints(
+        &self,
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) -> Result<Vec<String>, Error> {
+        let mut messages = vec![];
+
+        for (name, constraint) in self.input_constraints()? {
+            let error_id = self.errors.len() + messages.len();
+            messages.push(format!(
+                "input `{name}` is out of range: must be {constraint}"
+            ));
+
+            for slot in self.input_slots(&name)? {
+                let bounds = [
+                    (constraint.min, qbe::Cmp::Ge, "min"),
+                    (constraint.max, qbe::Cmp::Le, "max"),
+                ];
+                for (bound, cmp, kind) in bounds {
+                    let Some(bound) = bound else {
+                        continue;
+                    };
+                    let test = format!("constraint.{kind}.i{slot}");
+                    func.assign_instr(
+                        qbe::Value::Temporary(test.clone()),
+                        Type::Bool.render(),
+                        qbe::Instr::Cmp(
+                            Type::Float.render(),
+                            cmp,
+                            Ref::Input(slot).render(),
+                            Ref::from(bound).render(),
+                        ),
+                    );
+                    func.add_instr(qbe::Instr::Jnz(
+                        qbe::Value::Temporary(test.clone()),
+                        format!("{test}.ok"),
+                        format!("{test}.failed"),
+                    ));
+                    func.add_block(format!("{test}.failed"));
+                    crate::op::render_return_error(
+                        func,
+                        qbe::Value::Global(format!("{namespace}.error.{error_id}")),
+                        None,
+                    );
+                    func.add_block(format!("{test}.ok"));
+                }
+            }
+        }
+
+        Ok(messages)
+    }
+}
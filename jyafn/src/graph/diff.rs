@@ -0,0 +1,123 @@
+//! Structural comparison of graphs.
+
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::Graph;
+
+/// A value that is different between two graphs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Changed<T> {
+    /// The value in the original graph.
+    pub before: T,
+    /// The value in the other graph.
+    pub after: T,
+}
+
+impl<T: PartialEq> Changed<T> {
+    /// A change from `before` to `after`, if they are actually different.
+    fn between(before: T, after: T) -> Option<Changed<T>> {
+        (before != after).then_some(Changed { before, after })
+    }
+}
+
+/// The differences between two graphs, as reported by [`Graph::diff`]. Layouts are
+/// described by their display representation. An empty diff does not mean that the
+/// graphs are equal, since the nodes themselves are only compared by their count.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GraphDiff {
+    /// The inputs only in the other graph.
+    pub added_inputs: Vec<String>,
+    /// The inputs only in the original graph.
+    pub removed_inputs: Vec<String>,
+    /// The layouts of the inputs in both graphs that have changed, by input name.
+    pub changed_inputs: BTreeMap<String, Changed<String>>,
+    /// The positions in the input layout of the inputs in both graphs that have moved, by
+    /// input name. Inputs are laid out in order, so moving them changes the input layout.
+    pub moved_inputs: BTreeMap<String, Changed<usize>>,
+    /// The output layout, if it has changed.
+    pub output_layout: Option<Changed<String>>,
+    /// The number of nodes, if it has changed.
+    pub node_count: Option<Changed<usize>>,
+    /// The metadata keys only in the other graph.
+    pub added_metadata: Vec<String>,
+    /// The metadata keys only in the original graph.
+    pub removed_metadata: Vec<String>,
+    /// The metadata keys in both graphs whose values have changed.
+    pub changed_metadata: Vec<String>,
+}
+
+impl GraphDiff {
+    /// Whether no difference was found.
+    pub fn is_empty(&self) -> bool {
+        *self == GraphDiff::default()
+    }
+}
+
+impl Graph {
+    /// Compares this graph to `other`, reporting what changed from this graph to the
+    /// other one. Unlike comparing graphs for equality, this describes the differences.
+    /// All the lists in the diff are sorted.
+    pub fn diff(&self, other: &Graph) -> GraphDiff {
+        let before = self
+            .input_layout
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(position, (name, layout))| (name, (position, layout.to_string())))
+            .collect::<BTreeMap<_, _>>();
+        let after = other
+            .input_layout
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(position, (name, layout))| (name, (position, layout.to_string())))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut diff = GraphDiff {
+            output_layout: Changed::between(
+                self.output_layout.to_string(),
+                other.output_layout.to_string(),
+            ),
+            node_count: Changed::between(self.nodes.len(), other.nodes.len()),
+            ..GraphDiff::default()
+        };
+
+        for (&name, (position, layout)) in &before {
+            match after.get(name) {
+                None => diff.removed_inputs.push(name.clone()),
+                Some((other_position, other_layout)) => {
+                    if let Some(changed) = Changed::between(layout.clone(), other_layout.clone()) {
+                        diff.changed_inputs.insert(name.clone(), changed);
+                    }
+                    if let Some(moved) = Changed::between(*position, *other_position) {
+                        diff.moved_inputs.insert(name.clone(), moved);
+                    }
+                }
+            }
+        }
+        diff.added_inputs = after
+            .keys()
+            .filter(|name| !before.contains_key(*name))
+            .map(|&name| name.clone())
+            .collect();
+
+        let keys = self
+            .metadata
+            .keys()
+            .chain(other.metadata.keys())
+            .collect::<BTreeSet<_>>();
+        for key in keys {
+            match (self.metadata.get(key), other.metadata.get(key)) {
+                (None, _) => diff.added_metadata.push(key.clone()),
+                (_, None) => diff.removed_metadata.push(key.clone()),
+                (Some(value), Some(other_value)) if value != other_value => {
+                    diff.changed_metadata.push(key.clone())
+                }
+                _ => {}
+            }
+        }
+
+        diff
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt::Write;
+
+use super::{compile::find_reachable, Graph, Ref};
+
+impl Graph {
+    /// Renders this graph in the GraphViz DOT language, for debugging purposes. There is
+    /// one vertex per input, node, constant argument and output of the graph. Nodes are
+    /// labelled with their operation and their type and edges are labelled with the
+    /// position of the argument they feed. Nodes that are not reachable from the
+    /// outputs, and are therefore removed when compiling, are dashed.
+    pub fn to_dot(&self) -> String {
+        let reachable = find_reachable(&self.outputs, &self.nodes);
+        let mut dot = String::new();
+
+        // Writing to a string never fails.
+        let _ = writeln!(dot, "digraph {} {{", escape(&self.name));
+
+        for (input_id, ty) in self.inputs.iter().enumerate() {
+            let _ = writeln!(
+                dot,
+                "    i{input_id} [label={}, shape=box, style=filled, fillcolor=lightblue];",
+                escape(&format!("input {input_id}\n{ty}"))
+            );
+        }
+
+        // Constants are drawn once per use, so as not to tangle the graph.
+        let write_arg = |dot: &mut String, arg: Ref, target: &str, label: &str| {
+            let source = match arg {
+                Ref::Input(input_id) => format!("i{input_id}"),
+                Ref::Node(node_id) => format!("n{node_id}"),
+                Ref::Const(ty, _) => {
+                    let source = format!("c_{target}_{label}");
+                    let _ = writeln!(
+                        dot,
+                        "    {source} [label={}, shape=plaintext];",
+                        escape(&format!("{arg}\n{ty}"))
+                    );
+                    source
+                }
+            };
+            let _ = writeln!(dot, "    {source} -> {target} [label={}];", escape(label));
+        };
+
+        for (node_id, (node, &is_reachable)) in self.nodes.iter().zip(&reachable).enumerate() {
+            let _ = writeln!(
+                dot,
+                "    n{node_id} [label={}{}];",
+                escape(&format!("#{node_id} {:?}\n{}", node.op, node.ty)),
+                if is_reachable { "" } else { ", style=dashed" },
+            );
+            for (arg_id, &arg) in node.args.iter().enumerate() {
+                write_arg(&mut dot, arg, &format!("n{node_id}"), &arg_id.to_string());
+            }
+        }
+
+        for (output_id, &output) in self.outputs.iter().enumerate() {
+            let _ = writeln!(
+                dot,
+                "    o{output_id} [label={}, shape=doublecircle];",
+                escape(&format!("output {output_id}"))
+            );
+            write_arg(&mut dot, output, &format!("o{output_id}"), "");
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Quotes a string as a DOT identifier.
+fn escape(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use crate::layout::{Layout, RefValue};
+use crate::{Context, Error};
+
+use super::{Graph, Node, Ref};
+
+impl Graph {
+    /// Inlines the subgraph of the given id into this graph, wiring its inputs to the
+    /// provided ref value and returning the ref value of its output. Unlike
+    /// [`Graph::call_graph`], no call is made in runtime: the nodes of the subgraph are
+    /// copied into this graph, so that they can be optimized together with the rest of
+    /// it. This produces the same results as calling the subgraph.
+    ///
+    /// The errors, symbols, mappings, resources and subgraphs of the subgraph are merged
+    /// into this graph. This fails if the subgraph has a mapping or a resource with the
+    /// same name as a different one in this graph, or if a graph tries to inline itself.
+    pub fn inline_subgraph(&mut self, graph_id: usize, args: RefValue) -> Result<RefValue, Error> {
+        let subgraph = self
+            .subgraphs
+            .get(graph_id)
+            .ok_or_else(|| format!("no subgraph of id {graph_id}"))?
+            .clone();
+        if &subgraph == self {
+            return Err(format!(
+                "tried to inline graph {} into itself. Recursion in jyafn is disallowed.",
+                self.name
+            )
+            .into());
+        }
+
+        self.do_inline(&subgraph, args)
+            .with_context(|| format!("inlining subgraph {}", subgraph.name()))
+    }
+
+    fn do_inline(&mut self, subgraph: &Graph, args: RefValue) -> Result<RefValue, Error> {
+        let input_layout = Layout::Struct(subgraph.input_layout.clone());
+        let Some(args) = args.output_vec(&input_layout) else {
+            return Err(Error::BadValue {
+                expected: input_layout,
+                got: args,
+            });
+        };
+        for (input_id, (&arg, &ty)) in args.iter().zip(&subgraph.inputs).enumerate() {
+            if self.type_of(arg) != ty {
+                return Err(format!(
+                    "input {input_id} has type {ty}, but got {} for it",
+                    self.type_of(arg)
+                )
+                .into());
+            }
+        }
+
+        // Merge the tables of the subgraph first, since nodes are type-checked against
+        // them when they are copied.
+        for (name, mapping) in &subgraph.mappings {
+            match self.mappings.get(name) {
+                Some(existing) if !Arc::ptr_eq(existing, mapping) => {
+                    return Err(format!("graph already has a different mapping {name}").into())
+                }
+                Some(_) => {}
+                None => {
+                    self.mappings.insert(name.clone(), mapping.clone());
+                }
+            }
+        }
+        for (name, resource) in &subgraph.resources {
+            match self.resources.get(name) {
+                Some(existing) if !Arc::ptr_eq(existing, resource) => {
+                    return Err(format!("graph already has a different resource {name}").into())
+                }
+                Some(_) => {}
+                None => {
+                    self.resources.insert(name.clone(), resource.clone());
+                }
+            }
+        }
+        for symbol in subgraph.symbols.as_vec() {
+            self.symbols.push(symbol);
+        }
+        let error_map = subgraph
+            .errors
+            .iter()
+            .enumerate()
+            .map(|(error_id, error)| {
+                let origin = subgraph.error_origins.get(&error_id).cloned();
+                self.push_error_with_origin(error.clone(), origin)
+            })
+            .collect::<Vec<_>>();
+        let subgraph_map = subgraph
+            .subgraphs
+            .iter()
+            .map(|nested| self.insert_subgraph(nested.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let remap = |node_map: &[Ref], arg: Ref| match arg {
+            Ref::Input(input_id) => args[input_id],
+            Ref::Node(node_id) => node_map[node_id],
+            Ref::Const(..) => arg,
+        };
+
+        let mut node_map = Vec::with_capacity(subgraph.nodes.len());
+        for node in &subgraph.nodes {
+            let mut op = node.op.clone();
+            op.remap_tables(&error_map, &subgraph_map);
+
+            let node_args = node
+                .args
+                .iter()
+                .map(|&arg| remap(&node_map, arg))
+                .collect::<Vec<_>>();
+            let arg_types = node_args
+                .iter()
+                .map(|&arg| self.type_of(arg))
+                .collect::<Vec<_>>();
+            let node_id = self.nodes.len();
+            let Some(ty) = op.annotate(node_id, self, &arg_types) else {
+                return Err(Error::Type(op, arg_types));
+            };
+
+            self.nodes.push(Node {
+                op,
+                args: node_args,
+                ty,
+            });
+            node_map.push(Ref::Node(node_id));
+        }
+
+        let outputs = subgraph
+            .outputs
+            .iter()
+            .map(|&output| remap(&node_map, output))
+            .collect::<Vec<_>>();
+
+        Ok(subgraph
+            .output_layout
+            .build_ref_value(outputs)
+            .ok_or_else(|| "building ref-value for the output".to_string())?)
+    }
+}
@@ -1,19 +1,36 @@
 mod check;
 mod compile;
+mod constraint;
+mod diff;
+mod dot;
+mod inline;
 mod node;
+mod pin;
+mod rename;
 mod serde;
 mod r#type;
 
 pub mod size;
 
+#[cfg(test)]
+pub(crate) use compile::process_token;
+pub(crate) use compile::{assemble_and_link, ProfileCounters};
+pub use compile::{
+    CArtifact, CompileCache, CompileObserver, CompileOptions, CompileStage, NodeProfile, OptStats,
+    Pruned, COMPILE_CACHE_ENV, OPT_STATS_KEY, PRUNED_KEY,
+};
+pub use constraint::{InputConstraint, INPUT_CONSTRAINTS_KEY};
+pub use diff::{Changed, GraphDiff};
 pub use node::{Node, Ref};
+pub use pin::PINNABLE_INPUTS_KEY;
 pub use r#type::{Type, SLOT_SIZE};
+pub use serde::{DUMP_FORMAT_VERSION, MEM_SIZE_ESTIMATE_KEY};
 
 use get_size::GetSize;
 use serde_derive::{Deserialize, Serialize};
 use std::{
     cmp::PartialEq,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     error::Error as StdError,
     fmt::Debug,
     pin::Pin,
@@ -25,15 +42,40 @@ use super::{
     layout::{Encode, Layout, RefValue, Struct, Symbols, Visitor},
     mapping,
     op::{self, Op},
+    pfunc,
     r#const::Const,
-    resource::{Resource, ResourceContainer},
+    resource::{Resource, ResourceContainer, ResourceMethod},
     Context, Error,
 };
 
+/// The kinds of vector norms that can be computed with [`Graph::norm`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Norm {
+    /// The sum of the absolute values of the components.
+    L1,
+    /// The Euclidean norm, i.e., the square root of the sum of the squares of the
+    /// components.
+    L2,
+    /// The maximum of the absolute values of the components. This is NaN if any of the
+    /// components is NaN.
+    Inf,
+}
+
 /// A global variable used to create new unique names for graphs. This might change in the
 /// future in favor of using random ids.
 static GRAPH_ID: AtomicUsize = AtomicUsize::new(0);
 
+/// The maximum nesting depth of subgraphs allowed in a graph, unless set otherwise with
+/// [`Graph::set_max_subgraph_depth`]. Deeper chains of subgraphs are rejected, since they
+/// can blow the stack when processing the graph. Note that QBE limits the length of
+/// identifiers and subgraph symbols grow with the nesting depth, so not much more than
+/// this default is possible in practice.
+pub const DEFAULT_MAX_SUBGRAPH_DEPTH: usize = 8;
+
+/// The metadata key under which the maximum nesting depth of subgraphs allowed in a graph
+/// is stored, as a JSON number. See [`Graph::set_max_subgraph_depth`].
+pub const MAX_SUBGRAPH_DEPTH_KEY: &str = "jyafn.max_subgraph_depth";
+
 /// A computational graph.
 ///
 /// This structure records all the necessary data to run the computation of a computational
@@ -42,6 +84,11 @@ static GRAPH_ID: AtomicUsize = AtomicUsize::new(0);
 pub struct Graph {
     pub(crate) name: String,
     pub(crate) metadata: HashMap<String, String>,
+    /// Metadata whose values are arbitrary JSON. This is stored separately from the rest
+    /// of the graph when dumping, since `bincode` cannot represent JSON values.
+    #[serde(skip)]
+    #[get_size(size_fn = json_metadata_size)]
+    pub(crate) metadata_json: HashMap<String, serde_json::Value>,
     pub(crate) input_layout: Struct,
     pub(crate) output_layout: Layout,
     pub(crate) inputs: Vec<Type>,
@@ -49,15 +96,43 @@ pub struct Graph {
     pub(crate) outputs: Vec<Ref>,
     pub(crate) symbols: Symbols,
     pub(crate) errors: Vec<String>,
+    /// Where in the user code each error was raised from, by error id, if known. This is
+    /// stored separately from the rest of the graph when dumping.
+    #[serde(skip)]
+    pub(crate) error_origins: HashMap<usize, String>,
+    /// The mappings of this graph. These are shared among all clones of this graph and
+    /// their pointers are hardcoded in the compiled code. See [`FunctionData`] for the
+    /// contract on how they are kept alive.
+    ///
+    /// [`FunctionData`]: crate::FunctionData
     pub(crate) mappings: HashMap<String, Arc<mapping::Mapping>>,
+    /// The resources of this graph. These are shared among all clones of this graph and
+    /// their pointers are hardcoded in the compiled code. See [`FunctionData`] for the
+    /// contract on how they are kept alive.
+    ///
+    /// [`FunctionData`]: crate::FunctionData
     pub(crate) resources: HashMap<String, Arc<ResourceContainer>>,
     pub(crate) subgraphs: Vec<Graph>,
+    /// The source code that generated this graph, if any. This is stored separately from
+    /// the rest of the graph when dumping.
+    #[serde(skip)]
+    pub(crate) source: Option<String>,
+}
+
+/// The heap size of the JSON metadata of a graph, approximated by the size of its
+/// serialized values.
+fn json_metadata_size(metadata: &HashMap<String, serde_json::Value>) -> usize {
+    metadata
+        .iter()
+        .map(|(key, value)| key.len() + value.to_string().len())
+        .sum()
 }
 
 impl PartialEq for Graph {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name
             && self.metadata == other.metadata
+            && self.metadata_json == other.metadata_json
             && self.input_layout == other.input_layout
             && self.output_layout == other.output_layout
             && self.inputs == other.inputs
@@ -65,6 +140,7 @@ impl PartialEq for Graph {
             && self.outputs == other.outputs
             && self.symbols == other.symbols
             && self.errors == other.errors
+            && self.error_origins == other.error_origins
             && (self.mappings.len() == other.mappings.len()
                 && self.mappings.iter().all(|(k, v)| {
                     other
@@ -82,6 +158,7 @@ impl PartialEq for Graph {
                         .unwrap_or(false)
                 }))
             && self.subgraphs == other.subgraphs
+            && self.source == other.source
     }
 }
 
@@ -105,6 +182,49 @@ impl Graph {
         &self.name
     }
 
+    /// Gets a name for this graph derived only from its contents, i.e., its inputs,
+    /// nodes, outputs, layouts, symbols, errors and subgraphs. Two graphs built
+    /// independently with the same structure get the same canonical name, regardless of
+    /// their names or metadata, across runs and machines. This makes it suitable as a
+    /// cache key or for deduplicating functions.
+    ///
+    /// The contents of mappings and resources are _not_ taken into account, only the
+    /// names under which they are referenced.
+    pub fn canonical_name(&self) -> String {
+        format!("g{:016x}", self.content_hash())
+    }
+
+    fn content_hash(&self) -> u64 {
+        /// The seed for the content hash. Changing this changes all canonical names.
+        const CONTENT_HASH_SEED: u64 = 0x6a79_6166_6e2e_6767;
+
+        #[derive(Serialize)]
+        struct Contents<'a> {
+            input_layout: &'a Struct,
+            output_layout: &'a Layout,
+            inputs: &'a [Type],
+            nodes: &'a [Node],
+            outputs: &'a [Ref],
+            symbols: &'a Symbols,
+            errors: &'a [String],
+            subgraphs: Vec<u64>,
+        }
+
+        let contents = Contents {
+            input_layout: &self.input_layout,
+            output_layout: &self.output_layout,
+            inputs: &self.inputs,
+            nodes: &self.nodes,
+            outputs: &self.outputs,
+            symbols: &self.symbols,
+            errors: &self.errors,
+            subgraphs: self.subgraphs.iter().map(Graph::content_hash).collect(),
+        };
+        let serialized = serde_json::to_vec(&contents).expect("can always serialize graph");
+
+        crate::utils::murmur::murmur_hash64a(&serialized, CONTENT_HASH_SEED)
+    }
+
     /// Gets the metadata associated with the graph. These are user- and system- defined
     /// pairs of keys and values.
     pub fn metadata(&self) -> &HashMap<String, String> {
@@ -129,12 +249,40 @@ impl Graph {
         &self.inputs
     }
 
+    /// The names of the inputs of this graph, in declaration order. See
+    /// [`Graph::input_layout`] for their layouts.
+    pub fn input_names(&self) -> impl Iterator<Item = &str> {
+        self.input_layout
+            .fields()
+            .iter()
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Gets the nodes of this graph, in the order they were inserted. The id of each node
+    /// is its position in this slice (see [`Ref::Node`]).
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
     /// Gets the metadata associated with the graph. These are user- and system- defined
     /// pairs of keys and values.
     pub fn metadata_mut(&mut self) -> &mut HashMap<String, String> {
         &mut self.metadata
     }
 
+    /// Gets the metadata associated with the graph whose values are arbitrary JSON, such
+    /// as lists or nested objects. This is independent of [`Graph::metadata`], which
+    /// only holds strings.
+    pub fn metadata_json(&self) -> &HashMap<String, serde_json::Value> {
+        &self.metadata_json
+    }
+
+    /// Gets the metadata associated with the graph whose values are arbitrary JSON. See
+    /// [`Graph::metadata_json`].
+    pub fn metadata_json_mut(&mut self) -> &mut HashMap<String, serde_json::Value> {
+        &mut self.metadata_json
+    }
+
     /// Gets the type of a given reference in this graph. This function panics if the
     /// reference is invalid.
     pub fn type_of(&self, reference: Ref) -> Type {
@@ -174,7 +322,7 @@ impl Graph {
             Layout::Unit => RefValue::Unit,
             Layout::Scalar => RefValue::Scalar(self.push_input(Type::Float)),
             Layout::Bool => RefValue::Bool(self.push_input(Type::Bool)),
-            Layout::DateTime(_) => RefValue::Bool(self.push_input(Type::DateTime)),
+            Layout::DateTime(_) => RefValue::DateTime(self.push_input(Type::DateTime)),
             Layout::Symbol => RefValue::Symbol(self.push_input(Type::Symbol)),
             Layout::Struct(fields) => RefValue::Struct(
                 fields
@@ -189,6 +337,22 @@ impl Graph {
             Layout::List(element, size) => {
                 RefValue::List((0..*size).map(|_| self.alloc_input(element)).collect())
             }
+            Layout::Int => RefValue::Int(self.push_input(Type::Int)),
+            Layout::Optional(inner) => RefValue::Optional(
+                self.push_input(Type::Bool),
+                Box::new(self.alloc_input(inner)),
+            ),
+            Layout::Enum(_) => RefValue::Int(self.push_input(Type::Int)),
+            Layout::Bytes(n_bytes) => RefValue::List(
+                (0..n_bytes.div_ceil(8))
+                    .map(|_| RefValue::Int(self.push_input(Type::Int)))
+                    .collect(),
+            ),
+            Layout::Map(value) => RefValue::Map(
+                self.push_input(Type::Int),
+                self.push_input(Type::Int),
+                (**value).clone(),
+            ),
         }
     }
 
@@ -203,25 +367,63 @@ impl Graph {
     /// references while the layout contains the interpretation of the ref value. If you
     /// want the layout to be inferred from the value, you may use
     /// [`RefValue::putative_layout`].
+    ///
+    /// This fails if any of the output references does not exist in this graph, e.g., if
+    /// it was created in another graph.
     pub fn output(&mut self, value: RefValue, layout: Layout) -> Result<(), Error> {
-        self.outputs = value.output_vec(&layout).ok_or_else(|| Error::BadValue {
+        let outputs = value.output_vec(&layout).ok_or_else(|| Error::BadValue {
             expected: layout.clone(),
             got: value,
         })?;
+
+        if let Some(reference) = outputs.iter().find(|&&r| !self.contains(r)) {
+            return Err(format!(
+                "cannot output {reference}: graph has {} inputs and {} nodes. Was it created \
+                in another graph?",
+                self.inputs.len(),
+                self.nodes.len(),
+            )
+            .into());
+        }
+
+        self.outputs = outputs;
         self.output_layout = layout;
         Ok(())
     }
 
+    /// Whether a reference exists in this graph.
+    fn contains(&self, reference: Ref) -> bool {
+        match reference {
+            Ref::Input(input_id) => input_id < self.inputs.len(),
+            Ref::Node(node_id) => node_id < self.nodes.len(),
+            Ref::Const(..) => true,
+        }
+    }
+
     fn push_error(&mut self, error: String) -> usize {
-        if let Some(error_id) = self.errors.iter().position(|e| e == &error) {
+        self.push_error_with_origin(error, None)
+    }
+
+    fn push_error_with_origin(&mut self, error: String, origin: Option<String>) -> usize {
+        if let Some(error_id) = self.find_error(&error, origin.as_deref()) {
             error_id
         } else {
             let error_id = self.errors.len();
             self.errors.push(error);
+            if let Some(origin) = origin {
+                self.error_origins.insert(error_id, origin);
+            }
             error_id
         }
     }
 
+    /// The id of the error with the given message and origin, if this graph has one.
+    pub(crate) fn find_error(&self, error: &str, origin: Option<&str>) -> Option<usize> {
+        self.errors.iter().enumerate().position(|(error_id, e)| {
+            e == error && self.error_origins.get(&error_id).map(String::as_str) == origin
+        })
+    }
+
     /// Inserts a new assertion into the graph. If the supplied reference turns out to be
     /// false in runtime, the supplied error message will be raised.
     pub fn assert(&mut self, test: Ref, error_msg: String) -> Result<Ref, Error> {
@@ -229,6 +431,34 @@ impl Graph {
         self.insert(op::Assert(error_id as u64), vec![test])
     }
 
+    /// Inserts a new assertion into the graph, like [`Graph::assert`], tagged with where
+    /// in the user code it was created (e.g., `file.py:12`). If the assertion fails in
+    /// runtime, the raised error shows both the error message and the origin.
+    pub fn assert_with_origin(
+        &mut self,
+        test: Ref,
+        error_msg: String,
+        origin: String,
+    ) -> Result<Ref, Error> {
+        let error_id = self.push_error_with_origin(error_msg, Some(origin));
+        self.insert(op::Assert(error_id as u64), vec![test])
+    }
+
+    /// Where in the user code the error of the given id was raised from, if known. See
+    /// [`Graph::assert_with_origin`].
+    pub fn error_origin(&self, error_id: usize) -> Option<&str> {
+        self.error_origins.get(&error_id).map(String::as_str)
+    }
+
+    /// The text of the error of the given id, as raised in runtime. This includes the
+    /// origin of the error, if known.
+    pub(crate) fn error_text(&self, error_id: usize) -> String {
+        match self.error_origins.get(&error_id) {
+            Some(origin) => format!("{} (at {origin})", self.errors[error_id]),
+            None => self.errors[error_id].clone(),
+        }
+    }
+
     /// All the user-defined errors for this graph.
     ///
     /// # Note
@@ -239,6 +469,225 @@ impl Graph {
         &self.errors
     }
 
+    /// Inserts a new feature hash operation in the graph. This computes the murmur hash
+    /// of all the slots of `value`, using the supplied `seed`, and returns the bucket in
+    /// `[0, k)` associated with that hash as a scalar.
+    pub fn feature_hash(&mut self, value: RefValue, k: u64, seed: u64) -> Result<Ref, Error> {
+        if k == 0 {
+            return Err("number of buckets for feature hash must be positive"
+                .to_string()
+                .into());
+        }
+
+        let layout = value.putative_layout();
+        let Some(args) = value.output_vec(&layout) else {
+            return Err(Error::BadValue {
+                expected: layout,
+                got: value,
+            })
+            .context("getting arguments for feature hash");
+        };
+
+        if args.is_empty() {
+            return Err("cannot compute feature hash of an empty value"
+                .to_string()
+                .into());
+        }
+
+        self.insert(op::FeatureHash { k, seed }, args)
+    }
+
+    /// Inserts the computation of the `norm` of a vector of scalars in the graph. The
+    /// Euclidean norm is computed by chaining calls to `hypot`, so that large components
+    /// do not overflow the intermediate sum of squares. The norm of an empty vector is
+    /// zero.
+    pub fn norm(&mut self, list: &[Ref], norm: Norm) -> Result<Ref, Error> {
+        if let Some(&arg) = list.iter().find(|&&arg| self.type_of(arg) != Type::Float) {
+            return Err(format!(
+                "cannot compute the norm of a vector containing a {}",
+                self.type_of(arg)
+            )
+            .into());
+        }
+
+        let Some((&first, rest)) = list.split_first() else {
+            return Ok(self.r#const(0.0));
+        };
+
+        let mut acc = self.insert(op::Abs, vec![first])?;
+        for &item in rest {
+            acc = match norm {
+                Norm::L1 => {
+                    let abs = self.insert(op::Abs, vec![item])?;
+                    self.insert(op::Add, vec![acc, abs])?
+                }
+                Norm::L2 => self.insert(op::Call("hypot".to_string()), vec![acc, item])?,
+                Norm::Inf => {
+                    // Comparisons with NaN are always false, so NaNs have to be picked
+                    // explicitly for them to propagate.
+                    let abs = self.insert(op::Abs, vec![item])?;
+                    let gt = self.insert(op::Gt, vec![abs, acc])?;
+                    let is_nan = self.insert(op::IsNan, vec![abs])?;
+                    let pick = self.insert(op::Or, vec![gt, is_nan])?;
+                    self.insert(op::Choose, vec![pick, abs, acc])?
+                }
+            };
+        }
+
+        Ok(acc)
+    }
+
+    /// Inserts a fallback chain in the graph: the value of the first candidate whose
+    /// presence flag (a boolean, such as the result of [`Graph::mapping_contains`]) is
+    /// true is selected, or `default` if no candidate is present. All values must have the
+    /// same layout as `default`.
+    pub fn coalesce(
+        &mut self,
+        candidates: Vec<(Ref, RefValue)>,
+        default: RefValue,
+    ) -> Result<RefValue, Error> {
+        let layout = default.putative_layout();
+        let mut selected = default
+            .output_vec(&layout)
+            .expect("putative layout should work on the value it corresponds to");
+
+        // Build the chain of `Choose` from the last candidate to the first, so that the
+        // first candidate present takes precedence.
+        for (present, value) in candidates.into_iter().rev() {
+            if self.type_of(present) != Type::Bool {
+                return Err(format!(
+                    "presence flag of a candidate must be a bool, got {}",
+                    self.type_of(present)
+                )
+                .into());
+            }
+
+            let Some(candidate) = value.output_vec(&layout) else {
+                return Err(Error::WrongLayout {
+                    expected: layout,
+                    got: value.putative_layout(),
+                });
+            };
+
+            selected = candidate
+                .into_iter()
+                .zip(selected)
+                .map(|(if_present, otherwise)| {
+                    self.insert(op::Choose, vec![present, if_present, otherwise])
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+        }
+
+        Ok(layout
+            .build_ref_value(selected)
+            .expect("can build ref-value from layout here"))
+    }
+
+    /// Inserts a multi-way choice in the graph: the value in `values` at position `index`
+    /// is selected. The index may be an int or a float, which is truncated. Evaluating the
+    /// function raises an error if the index is out of range. All values must have the
+    /// same type.
+    ///
+    /// Nodes used only by one of the values are computed only when that value is
+    /// selected. However, nodes shared with anything else are computed regardless of the
+    /// index. So, do not count on an unselected value not being computed.
+    pub fn select(&mut self, index: Ref, values: Vec<Ref>) -> Result<Ref, Error> {
+        if values.is_empty() {
+            return Err("cannot select from an empty list of values"
+                .to_string()
+                .into());
+        }
+
+        let error = self.push_error("Select index out of range".to_string());
+        let mut args = vec![index];
+        args.extend(values);
+
+        self.insert(
+            op::Select {
+                n: args.len() - 1,
+                error,
+            },
+            args,
+        )
+    }
+
+    /// Reduces a list of floats into one, with a single node instead of a chain of binary
+    /// operations. See [`op::Reduce`] for details.
+    pub fn reduce(&mut self, kind: op::ReduceKind, values: Vec<Ref>) -> Result<Ref, Error> {
+        self.insert(
+            op::Reduce {
+                kind,
+                n: values.len(),
+            },
+            values,
+        )
+    }
+
+    /// Looks up the value of `key`, a symbol, in a map from the input of this graph (see
+    /// [`Layout::Map`]). The result is an optional value, absent if the key is not in
+    /// the map. Finding the key takes time proportional to the number of entries in the
+    /// map.
+    pub fn map_get(&mut self, map: &RefValue, key: Ref) -> Result<RefValue, Error> {
+        let RefValue::Map(n_entries, start, value) = map else {
+            return Err(format!("can only look keys up in maps, got {map}").into());
+        };
+        if value.contains_map() {
+            return Err(format!("map values cannot contain maps, got {value}").into());
+        }
+
+        let entries = self.insert(op::InputAddress, vec![*start])?;
+        let entry = self.insert(
+            op::MapFind {
+                stride: 1 + value.size().in_slots(),
+            },
+            vec![*n_entries, entries, key],
+        )?;
+        let is_found = self.insert(op::MapFound, vec![entry])?;
+        let slots = value
+            .slots()
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| self.insert(op::LoadMapValue { slot: 1 + i, ty }, vec![entry]))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(RefValue::Optional(
+            is_found,
+            Box::new(
+                value
+                    .build_ref_value(slots)
+                    .expect("slots were built from the layout"),
+            ),
+        ))
+    }
+
+    /// The softmax of a list of floats, as a list of the same length. See
+    /// [`op::Softmax`] for details.
+    pub fn softmax(&mut self, values: Vec<Ref>) -> Result<Vec<Ref>, Error> {
+        let n = values.len();
+        let list = self.insert(op::Softmax { n }, values)?;
+
+        (0..n)
+            .map(|slot| self.insert(op::LoadSoftmaxOutput { slot }, vec![list]))
+            .collect()
+    }
+
+    /// The dot product of two lists of floats of the same length.
+    pub fn dot(&mut self, a: Vec<Ref>, b: Vec<Ref>) -> Result<Ref, Error> {
+        if a.len() != b.len() {
+            return Err(format!(
+                "cannot take the dot product of lists of lengths {} and {}",
+                a.len(),
+                b.len()
+            )
+            .into());
+        }
+
+        let n = a.len();
+        let mut args = a;
+        args.extend(b);
+        self.insert(op::Dot { n }, args)
+    }
+
     /// Adds a new symbol to the graph, returning a reference associated with it.
     pub fn push_symbol(&mut self, name: String) -> Ref {
         Ref::Const(Type::Symbol, self.symbols.push(name))
@@ -255,7 +704,58 @@ impl Graph {
         &self.symbols
     }
 
-    /// Adds a new mapping to the current graph.
+    /// Breaks down the memory used by this graph by component, to find out what makes
+    /// a graph large. Mappings and resources are shared between a graph and the
+    /// subgraphs that use them, so each one of them is counted only once, in the first
+    /// graph where it is found.
+    pub fn size_breakdown(&self) -> size::SizeBreakdown {
+        self.size_breakdown_with(&mut HashSet::new())
+    }
+
+    /// Does the heavy lifting for [`Graph::size_breakdown`]. The `seen` set has the
+    /// pointers of the mappings and resources already counted.
+    fn size_breakdown_with(&self, seen: &mut HashSet<usize>) -> size::SizeBreakdown {
+        let nodes = self.nodes.get_heap_size();
+        let mappings = self.mappings.get_heap_size();
+        let resources = self.resources.get_heap_size();
+        let subgraphs = self.subgraphs.get_heap_size();
+        let symbols = self.symbols.get_heap_size();
+        let other = self.get_size() - nodes - mappings - resources - subgraphs - symbols;
+
+        let shared_mappings = self
+            .mappings
+            .values()
+            .filter(|mapping| !seen.insert(Arc::as_ptr(mapping) as usize))
+            .map(|mapping| mapping.get_size())
+            .sum::<usize>();
+        let shared_resources = self
+            .resources
+            .values()
+            .filter(|resource| !seen.insert(Arc::as_ptr(resource) as usize))
+            .map(|resource| resource.get_size())
+            .sum::<usize>();
+        // Parents are visited before their subgraphs, so that the first graph to have a
+        // mapping or a resource is the one to count it.
+        let unused_capacity = self.subgraphs.capacity() - self.subgraphs.len();
+        let subgraphs = unused_capacity * std::mem::size_of::<Graph>()
+            + self
+                .subgraphs
+                .iter()
+                .map(|subgraph| subgraph.size_breakdown_with(seen).total())
+                .sum::<usize>();
+
+        size::SizeBreakdown {
+            nodes,
+            mappings: mappings - shared_mappings,
+            resources: resources - shared_resources,
+            subgraphs,
+            symbols,
+            other,
+        }
+    }
+
+    /// Adds a new mapping to the current graph. Errors of the storage, such as failing to
+    /// store a value, are converted to the error type `E` of the items.
     pub fn insert_mapping<S, I, K, V, E>(
         &mut self,
         name: String,
@@ -266,13 +766,35 @@ impl Graph {
     ) -> Result<(), E>
     where
         S: 'static + mapping::StorageType,
-        K: Encode<Err = E>,
-        V: Encode<Err = E>,
-        E: 'static + StdError + Send,
+        K: Encode,
+        V: Encode,
+        E: 'static + StdError + Send + From<K::Err> + From<V::Err> + From<Error>,
+        I: IntoIterator<Item = Result<(K, V), E>>,
+    {
+        self.insert_mapping_with_seed(name, key_layout, value_layout, storage_type, 0, items)
+    }
+
+    /// Adds a new mapping to the current graph, like [`Graph::insert_mapping`], whose
+    /// keys are hashed starting from `seed`. Storages shared between mappings with
+    /// different seeds will not see each other's keys.
+    pub fn insert_mapping_with_seed<S, I, K, V, E>(
+        &mut self,
+        name: String,
+        key_layout: Layout,
+        value_layout: Layout,
+        storage_type: S,
+        seed: u64,
+        items: I,
+    ) -> Result<(), E>
+    where
+        S: 'static + mapping::StorageType,
+        K: Encode,
+        V: Encode,
+        E: 'static + StdError + Send + From<K::Err> + From<V::Err> + From<Error>,
         I: IntoIterator<Item = Result<(K, V), E>>,
     {
-        let mut mapping = mapping::Mapping::new(key_layout, value_layout, storage_type)
-            .expect("didn't find a good way to treat this error yet");
+        let mut mapping =
+            mapping::Mapping::new_with_seed(key_layout, value_layout, storage_type, seed)?;
         let mut key_visitor = Visitor::new(mapping.key_layout().size());
         let mut value_visitor = Visitor::new(mapping.value_layout().size());
 
@@ -290,14 +812,107 @@ impl Graph {
             mapping.insert(
                 key_visitor.clone().into_inner(),
                 value_visitor.clone().into_inner(),
-            );
+            )?;
         }
 
+        mapping.finish();
         self.mappings.insert(name, Arc::new(mapping));
 
         Ok(())
     }
 
+    /// Adds a new mapping to the current graph, read from CSV data and backed by a
+    /// [`mapping::CsvStorage`]. The header of the CSV declares the columns as `name:type`,
+    /// where the type is one of `scalar` (the default, if omitted), `int`, `bool`,
+    /// `symbol` or `datetime` (in ISO 8601). The first `n_key_columns` columns are the key
+    /// and the remaining ones are the value of the mapping, both laid out as structs.
+    ///
+    /// The CSV is read line by line. Quoted fields cannot span multiple lines.
+    pub fn insert_mapping_from_csv<R: std::io::Read>(
+        &mut self,
+        name: String,
+        csv: R,
+        n_key_columns: usize,
+    ) -> Result<(), Error> {
+        let mut lines = std::io::BufRead::lines(std::io::BufReader::new(csv));
+        let header = lines
+            .next()
+            .ok_or_else(|| "CSV has no header".to_string())??;
+        let columns = mapping::parse_csv_line(&header)?
+            .into_iter()
+            .map(|column| {
+                let (name, ty) = column.split_once(':').unwrap_or((&column, "scalar"));
+                let layout = match ty.trim() {
+                    "scalar" => Layout::Scalar,
+                    "int" => Layout::Int,
+                    "bool" => Layout::Bool,
+                    "symbol" => Layout::Symbol,
+                    "datetime" => Layout::DateTime(crate::layout::ISOFORMAT.to_string()),
+                    other => return Err(format!("unknown type {other:?} for CSV column {name:?}")),
+                };
+                Ok((name.trim().to_string(), layout))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if n_key_columns == 0 || n_key_columns >= columns.len() {
+            return Err(format!(
+                "CSV must have at least one key and one value column, but has {} columns and \
+                {n_key_columns} key columns",
+                columns.len()
+            )
+            .into());
+        }
+
+        let key_layout = Layout::Struct(Struct(columns[..n_key_columns].to_vec()));
+        let value_layout = Layout::Struct(Struct(columns[n_key_columns..].to_vec()));
+        let parse_row = |line: String| -> Result<_, Error> {
+            let fields = mapping::parse_csv_line(&line)?;
+            if fields.len() != columns.len() {
+                return Err(
+                    format!("expected {} columns, got {}", columns.len(), fields.len()).into(),
+                );
+            }
+
+            let mut row = columns
+                .iter()
+                .zip(fields)
+                .map(|((name, layout), field)| {
+                    let value = match layout {
+                        Layout::Int => serde_json::Value::from(
+                            field
+                                .parse::<i64>()
+                                .map_err(|_| format!("cannot parse {field:?} as int"))?,
+                        ),
+                        Layout::Bool => serde_json::Value::from(
+                            field
+                                .parse::<bool>()
+                                .map_err(|_| format!("cannot parse {field:?} as bool"))?,
+                        ),
+                        _ => serde_json::Value::String(field),
+                    };
+                    Ok((name.clone(), value))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            let value = row.split_off(n_key_columns);
+
+            Ok((
+                serde_json::Value::Object(row.into_iter().collect()),
+                serde_json::Value::Object(value.into_iter().collect()),
+            ))
+        };
+        let items = lines.enumerate().map(|(i, line)| {
+            parse_row(line?).with_context(|| format!("reading line {} of CSV", i + 2))
+        });
+
+        self.insert_mapping(
+            name,
+            key_layout,
+            value_layout.clone(),
+            mapping::CsvStorage::new(value_layout),
+            items,
+        )
+    }
+
     /// Lists all mappings associated with this graph.
     pub fn mappings(&self) -> &HashMap<String, Arc<mapping::Mapping>> {
         &self.mappings
@@ -467,6 +1082,18 @@ impl Graph {
             .insert(name, Arc::new(ResourceContainer::new_boxed(resource)));
     }
 
+    /// Gets the method of the given name of a resource of this graph.
+    pub fn resource_method(&self, name: &str, method_name: &str) -> Result<ResourceMethod, Error> {
+        let resource = self
+            .resources
+            .get(name)
+            .ok_or_else(|| format!("no such resource {name}"))?;
+
+        Ok(resource
+            .get_method(method_name)
+            .ok_or_else(|| format!("resource {name} has no method {method_name}"))?)
+    }
+
     /// Inserts a new `resource.method(**params)` in the graph.
     pub fn call_resource(
         &mut self,
@@ -474,25 +1101,35 @@ impl Graph {
         method_name: &str,
         args: RefValue,
     ) -> Result<RefValue, Error> {
-        let resource = self
-            .resources
-            .get(name)
-            .ok_or_else(|| format!("no such resource {name}"))?
-            .clone();
-        let method = resource
-            .get_method(method_name)
-            .ok_or_else(|| format!("resource {name} has no method {method_name}"))?;
-        let Some(args) = args.output_vec(&Layout::Struct(method.input_layout.clone())) else {
+        let method = self.resource_method(name, method_name)?;
+        let input_layout = method.input_layout_for(&args);
+        let Some(args) = args.output_vec(&Layout::Struct(input_layout.clone())) else {
             return Err(Error::BadValue {
-                expected: Layout::Struct(method.input_layout.clone()),
+                expected: Layout::Struct(input_layout),
                 got: args,
             })
             .with_context(|| format!("calling {method_name} on {name}"));
         };
+        let length_error = if method.dynamic_output {
+            let max_len = method.max_output_len().ok_or_else(|| {
+                format!(
+                    "method {method_name} on {name} has a dynamic output, but its output \
+                    layout {} is not a dynamic output layout",
+                    method.output_layout
+                )
+            })?;
+            Some(self.push_error(format!(
+                "method {method_name} on {name} returned more than {max_len} elements"
+            )))
+        } else {
+            None
+        };
         let output_pointer = self.insert(
             op::CallResource {
                 name: name.to_string(),
                 method: method_name.to_string(),
+                input_layout: (!method.dynamic_inputs.is_empty()).then_some(input_layout),
+                length_error,
                 resolved: None,
             },
             args,
@@ -520,15 +1157,115 @@ impl Graph {
             .ok_or_else(|| "building ref-value for call {method_name} on {name}".to_string())?)
     }
 
-    /// Inserts a new subgraph in the graph, returning the id associated with it.
-    pub fn insert_subgraph(&mut self, subgraph: Graph) -> usize {
+    /// The maximum nesting depth of subgraphs allowed in this graph. This is
+    /// [`DEFAULT_MAX_SUBGRAPH_DEPTH`] unless set with [`Graph::set_max_subgraph_depth`].
+    pub fn max_subgraph_depth(&self) -> Result<usize, Error> {
+        self.metadata
+            .get(MAX_SUBGRAPH_DEPTH_KEY)
+            .map(|depth| serde_json::from_str(depth))
+            .transpose()
+            .map(|depth| depth.unwrap_or(DEFAULT_MAX_SUBGRAPH_DEPTH))
+            .map_err(Error::Json)
+    }
+
+    /// Sets the maximum nesting depth of subgraphs allowed in this graph, which is kept in
+    /// its metadata under the [`MAX_SUBGRAPH_DEPTH_KEY`] key. This graph will then fail to
+    /// have subgraphs inserted and to compile if it exceeds this depth. Only the limit of
+    /// the outermost graph is taken into account.
+    pub fn set_max_subgraph_depth(&mut self, depth: usize) {
+        self.metadata.insert(
+            MAX_SUBGRAPH_DEPTH_KEY.to_string(),
+            serde_json::to_string(&depth).expect("can always serialize a number"),
+        );
+    }
+
+    /// The nesting depth of the subgraphs of this graph. A graph without subgraphs has
+    /// depth `0`, a graph whose subgraphs have no subgraphs has depth `1` and so on.
+    pub fn subgraph_depth(&self) -> usize {
+        // This is done iteratively to avoid blowing the stack on pathological graphs.
+        let mut max_depth = 0;
+        let mut stack = vec![(self, 0)];
+
+        while let Some((graph, depth)) = stack.pop() {
+            max_depth = max_depth.max(depth);
+            stack.extend(graph.subgraphs.iter().map(|subgraph| (subgraph, depth + 1)));
+        }
+
+        max_depth
+    }
+
+    /// Checks whether the subgraph nesting depth of this graph is within its maximum
+    /// allowed depth.
+    pub(crate) fn check_subgraph_depth(&self) -> Result<(), Error> {
+        let depth = self.subgraph_depth();
+        let max_depth = self.max_subgraph_depth()?;
+        if depth > max_depth {
+            return Err(Error::Other(format!(
+                "graph {} has subgraph depth {depth}, but the maximum allowed is {max_depth}",
+                self.name
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Inserts a new subgraph in the graph, returning the id associated with it. This fails
+    /// if the insertion would make this graph exceed the maximum subgraph nesting depth
+    /// (see [`Graph::set_max_subgraph_depth`]) or if the subgraph has maps in its inputs.
+    pub fn insert_subgraph(&mut self, subgraph: Graph) -> Result<usize, Error> {
+        if subgraph.input_layout.contains_map() {
+            return Err(Error::Other(format!(
+                "cannot insert subgraph {}: maps are not allowed in subgraph inputs",
+                subgraph.name
+            )));
+        }
+
         if let Some(exitsting) = self.subgraphs.iter().position(|g| g == &subgraph) {
-            return exitsting;
+            return Ok(exitsting);
+        }
+
+        let depth = subgraph.subgraph_depth() + 1;
+        let max_depth = self.max_subgraph_depth()?;
+        if depth > max_depth {
+            return Err(Error::Other(format!(
+                "inserting subgraph {} would make graph {} have subgraph depth {depth}, but \
+                the maximum allowed is {max_depth}",
+                subgraph.name, self.name
+            )));
         }
 
         let graph_id = self.subgraphs.len();
         self.subgraphs.push(subgraph);
-        graph_id
+        Ok(graph_id)
+    }
+
+    /// Inserts a masked select in the graph: returns, for each position, the value in
+    /// `values` if the bool in `mask` is true, or `default` otherwise. The mask and the
+    /// values must have the same length, and all values must have the type of `default`.
+    pub fn masked_select(
+        &mut self,
+        mask: Vec<Ref>,
+        values: Vec<Ref>,
+        default: Ref,
+    ) -> Result<Vec<Ref>, Error> {
+        if mask.len() != values.len() {
+            return Err(format!(
+                "mask has {} elements, but there are {} values",
+                mask.len(),
+                values.len()
+            )
+            .into());
+        }
+
+        let n = values.len();
+        let mut args = values;
+        args.extend(mask);
+        args.push(default);
+        let list = self.insert(op::MaskedSelect { n }, args)?;
+
+        (0..n)
+            .map(|slot| self.insert(op::LoadMaskedSelectOutput { slot }, vec![list]))
+            .collect()
     }
 
     /// Inserts a new graph call operation in the graph. This operation will call the
@@ -547,6 +1284,12 @@ impl Graph {
             })
             .with_context(|| format!("calling subgraph {}", subgraph.name()));
         };
+        // The errors of the subgraph may be raised by this graph, so they are also among the
+        // errors of this graph.
+        for (error_id, error) in subgraph.errors.iter().enumerate() {
+            let origin = subgraph.error_origins.get(&error_id).cloned();
+            self.push_error_with_origin(error.clone(), origin);
+        }
         let output_pointer = self.insert(op::CallGraph(graph_id), args)?;
 
         let values = subgraph
@@ -576,6 +1319,58 @@ impl Graph {
             })?)
     }
 
+    /// Wires `output_value`, the output of a previous stage of a pipeline, into the graph
+    /// `next`, returning the output of `next`. The value must be a struct containing all
+    /// the inputs of `next`, which are matched by name. Extra fields are ignored. The
+    /// graph `next` is inserted as a subgraph of this graph (see
+    /// [`Graph::insert_subgraph`]).
+    pub fn pipe(&mut self, next: &Graph, output_value: RefValue) -> Result<RefValue, Error> {
+        let RefValue::Struct(fields) = &output_value else {
+            return Err(format!(
+                "can only pipe a struct into graph {}, but got {output_value}",
+                next.name
+            )
+            .into());
+        };
+
+        for (name, layout) in &next.input_layout.0 {
+            let Some(field) = fields.get(name) else {
+                return Err(format!(
+                    "graph {} has input {name:?}, which is missing from {output_value}",
+                    next.name
+                )
+                .into());
+            };
+            if field.output_vec(layout).is_none() {
+                return Err(Error::BadValue {
+                    expected: layout.clone(),
+                    got: field.clone(),
+                })
+                .with_context(|| format!("piping field {name:?} into graph {}", next.name));
+            }
+        }
+
+        let graph_id = self.insert_subgraph(next.clone())?;
+        self.call_graph(graph_id, output_value)
+    }
+
+    /// Inserts a call to the pure function `name` in the graph, returning one reference
+    /// per output of the function. Multi-output functions (see
+    /// [`pfunc::PFunc::outputs`]) are called only once, no matter how many of
+    /// their outputs are used.
+    pub fn call_pfunc(&mut self, name: &str, args: Vec<Ref>) -> Result<Vec<Ref>, Error> {
+        let pfunc = pfunc::get(name).ok_or_else(|| format!("no pure function named {name:?}"))?;
+        let call = self.insert(op::Call(name.to_string()), args)?;
+
+        if !pfunc.is_multi_output() {
+            return Ok(vec![call]);
+        }
+
+        (0..pfunc.outputs().len())
+            .map(|slot| self.insert(op::LoadCallOutput { slot }, vec![call]))
+            .collect()
+    }
+
     /// Creates a new indexed list in the graph.
     pub fn indexed_list(&mut self, list: Vec<Ref>) -> Result<IndexedList, Error> {
         let element = list
@@ -611,11 +1406,31 @@ pub struct IndexedList {
 
 impl IndexedList {
     pub fn get(&self, graph: &mut Graph, idx: Ref) -> Result<Ref, Error> {
+        self.get_with_mode(graph, idx, op::IndexMode::Strict)
+    }
+
+    /// Gets the element at `idx`, where negative indices count from the end of the list,
+    /// like in Python: `-1` is the last element. Indices below `-n_elements` or at or
+    /// above `n_elements` raise an error. This is the same as [`IndexedList::get_with_mode`]
+    /// with [`op::IndexMode::NegativeWrap`].
+    pub fn get_wrapping(&self, graph: &mut Graph, idx: Ref) -> Result<Ref, Error> {
+        self.get_with_mode(graph, idx, op::IndexMode::NegativeWrap)
+    }
+
+    /// Gets the element at `idx`, interpreting the index according to `mode`. Indices out
+    /// of bounds after normalization raise an error.
+    pub fn get_with_mode(
+        &self,
+        graph: &mut Graph,
+        idx: Ref,
+        mode: op::IndexMode,
+    ) -> Result<Ref, Error> {
         graph.insert(
             op::Index {
                 element: self.element,
                 n_elements: self.n_elements,
                 error: self.error,
+                mode,
             },
             vec![self.list, idx],
         )
@@ -3,13 +3,13 @@ use serde_derive::{Deserialize, Serialize};
 use std::cmp::PartialEq;
 use std::fmt::{self, Display};
 
-use crate::{Error, Op};
+use crate::{Context, Error, Op};
 
 use super::Graph;
 use super::Type;
 
 /// A reference to a value in a graph.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, GetSize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, GetSize)]
 pub enum Ref {
     /// A reference to the input of a given id.
     Input(usize),
@@ -41,6 +41,12 @@ impl From<bool> for Ref {
     }
 }
 
+impl From<i64> for Ref {
+    fn from(v: i64) -> Ref {
+        Ref::Const(Type::Int, v as u64)
+    }
+}
+
 impl Ref {
     pub(crate) fn render(self) -> qbe::Value {
         match self {
@@ -59,6 +65,15 @@ impl Ref {
         }
     }
 
+    /// Represents this ref as an i64, if it is a constant.
+    pub fn as_i64(self) -> Option<i64> {
+        if let Self::Const(Type::Int, c) = self {
+            Some(c as i64)
+        } else {
+            None
+        }
+    }
+
     /// Represents this ref as an f64, if it is a constant.
     pub fn as_bool(self) -> Option<bool> {
         if let Self::Const(Type::Bool, c) = self {
@@ -93,6 +108,26 @@ impl GetSize for Node {
 }
 
 impl Node {
+    /// The operation that this node performs.
+    pub fn op(&self) -> &dyn Op {
+        self.op.as_ref()
+    }
+
+    /// The name under which the operation of this node is serialized, e.g. `"Add"`.
+    pub fn op_name(&self) -> &'static str {
+        self.op.typetag_name()
+    }
+
+    /// The inputs of the operation.
+    pub fn args(&self) -> &[Ref] {
+        &self.args
+    }
+
+    /// The type of the output of the operation.
+    pub fn ty(&self) -> Type {
+        self.ty
+    }
+
     /// Creates a new node.
     pub(crate) fn init<O: Op>(
         node_id: usize,
@@ -102,7 +137,14 @@ impl Node {
     ) -> Result<Node, Error> {
         let arg_types = args.iter().map(|r| graph.type_of(*r)).collect::<Vec<_>>();
         let Some(ty) = op.annotate(node_id, graph, &arg_types) else {
-            return Err(Error::Type(Box::new(op), arg_types));
+            let explanation = op.explain_type_error(graph, &arg_types);
+            let error = Err(Error::Type(Box::new(op), arg_types));
+
+            return if let Some(explanation) = explanation {
+                error.with_context(|| explanation)
+            } else {
+                error
+            };
         };
 
         Ok(Node {
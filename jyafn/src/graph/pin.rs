@@ -0,0 +1,196 @@
+use byte_slice_cast::*;
+use std::collections::{BTreeSet, HashMap};
+
+use super::{
+    Context, Encode, Error, Graph, Layout, Ref, RefValue, Struct, Visitor, INPUT_CONSTRAINTS_KEY,
+};
+
+/// The metadata key under which the names of the pinnable inputs of a graph are stored,
+/// as a JSON list. See [`Graph::pinnable_input`].
+pub const PINNABLE_INPUTS_KEY: &str = "jyafn.pinnable_inputs";
+
+impl Graph {
+    /// Adds a new pinnable key to the input struct of this graph. Pinnable inputs are
+    /// inputs that are constant for the lifetime of a served function, but may vary
+    /// across deployments, such as feature flags or configuration. They behave like any
+    /// other input unless a value is pinned for them with [`Graph::pin`].
+    pub fn pinnable_input(&mut self, name: String, layout: Layout) -> RefValue {
+        let mut pinnable = self.pinnable_inputs().unwrap_or_default();
+        pinnable.insert(name.clone());
+        self.metadata.insert(
+            PINNABLE_INPUTS_KEY.to_string(),
+            serde_json::to_string(&pinnable).expect("can always serialize names"),
+        );
+
+        self.input(name, layout)
+    }
+
+    /// Gets the names of the pinnable inputs of this graph. See
+    /// [`Graph::pinnable_input`].
+    pub fn pinnable_inputs(&self) -> Result<BTreeSet<String>, Error> {
+        self.metadata
+            .get(PINNABLE_INPUTS_KEY)
+            .map(|pinnable| serde_json::from_str(pinnable))
+            .transpose()
+            .map(Option::unwrap_or_default)
+            .map_err(Error::Json)
+    }
+
+    /// Creates a new graph specialized for the supplied values of some of its pinnable
+    /// inputs. The pinned inputs are removed from the input layout of the new graph and
+    /// every use of them is replaced by a constant, so that the computations depending
+    /// only on them are folded away when the graph is compiled. Inputs that are not
+    /// pinned are kept as they are.
+    ///
+    /// This fails if an input is not pinnable, if it contains maps or if its value does
+    /// not fit its layout or its input constraint.
+    pub fn pin<'a, E>(
+        &self,
+        values: impl IntoIterator<Item = (&'a str, &'a E)>,
+    ) -> Result<Graph, Error>
+    where
+        E: 'a + ?Sized + Encode,
+    {
+        self.with_constant_inputs(values, true)
+    }
+
+    /// Specializes this graph for a fixed value of the input `name`, which can be any
+    /// input. The input is removed from the input layout and every use of it is replaced
+    /// by a constant, as in [`Graph::pin`], and the remaining inputs are renumbered.
+    ///
+    /// This fails if there is no such input, if it contains maps or if `value` does not
+    /// fit its layout or its input constraint. The graph is left untouched on failure.
+    pub fn bind_input<E>(&mut self, name: &str, value: &E) -> Result<(), Error>
+    where
+        E: ?Sized + Encode,
+    {
+        *self = self.with_constant_inputs([(name, value)], false)?;
+        Ok(())
+    }
+
+    /// Creates a new graph where the given inputs are replaced by constants. If
+    /// `pinning`, only pinnable inputs are accepted.
+    fn with_constant_inputs<'a, E>(
+        &self,
+        values: impl IntoIterator<Item = (&'a str, &'a E)>,
+        pinning: bool,
+    ) -> Result<Graph, Error>
+    where
+        E: 'a + ?Sized + Encode,
+    {
+        let (verb, participle, gerund) = if pinning {
+            ("pin", "pinned", "pinning")
+        } else {
+            ("bind", "bound", "binding")
+        };
+        let mut pinned = self.clone();
+        let mut pinnable = self.pinnable_inputs()?;
+        let mut constraints = self.input_constraints()?;
+        let mut constants = HashMap::new();
+        let mut pinned_names = BTreeSet::new();
+
+        for (name, value) in values {
+            if !pinnable.remove(name) && pinning {
+                return Err(format!("input {name:?} is not pinnable").into());
+            }
+            pinned_names.insert(name.to_string());
+
+            let slots = self.input_slots(name)?;
+            let layout = &self.input_layout.0[self.input_field(name)].1;
+            if layout.contains_map() {
+                return Err(
+                    format!("cannot {verb} input {name:?}: maps cannot be {participle}").into(),
+                );
+            }
+            let mut visitor = Visitor::new(layout.size());
+            value
+                .visit(layout, &mut pinned.symbols, &mut visitor)
+                .map_err(|err| Error::EncodeError(Box::new(err)))
+                .with_context(|| format!("{gerund} input {name:?}"))?;
+            let encoded = visitor
+                .buffer()
+                .as_slice_of::<u64>()
+                .expect("visitor buffer is made of slots");
+
+            if let Some(constraint) = constraints.remove(name) {
+                if let Some(value) = encoded
+                    .iter()
+                    .map(|&slot| f64::from_bits(slot))
+                    .find(|&value| !constraint.contains(value))
+                {
+                    return Err(format!(
+                        "cannot {verb} input {name:?} to {value}: must be {constraint}"
+                    )
+                    .into());
+                }
+            }
+
+            for (slot, &repr) in slots.zip(encoded) {
+                constants.insert(slot, Ref::Const(self.inputs[slot], repr));
+            }
+        }
+
+        // Renumbers the remaining inputs, replacing the fixed ones by constants.
+        let mut remaining = 0;
+        let replacements = (0..self.inputs.len())
+            .map(|slot| {
+                constants.get(&slot).copied().unwrap_or_else(|| {
+                    remaining += 1;
+                    Ref::Input(remaining - 1)
+                })
+            })
+            .collect::<Vec<_>>();
+        pinned.replace_inputs(&replacements);
+        pinned.inputs = (0..self.inputs.len())
+            .filter(|slot| !constants.contains_key(slot))
+            .map(|slot| self.inputs[slot])
+            .collect();
+        pinned.input_layout = Struct(
+            self.input_layout
+                .0
+                .iter()
+                .filter(|(name, _)| !pinned_names.contains(name))
+                .cloned()
+                .collect(),
+        );
+
+        if self.metadata.contains_key(PINNABLE_INPUTS_KEY) {
+            pinned.metadata.insert(
+                PINNABLE_INPUTS_KEY.to_string(),
+                serde_json::to_string(&pinnable)?,
+            );
+        }
+        if self.metadata.contains_key(INPUT_CONSTRAINTS_KEY) {
+            pinned.metadata.insert(
+                INPUT_CONSTRAINTS_KEY.to_string(),
+                serde_json::to_string(&constraints)?,
+            );
+        }
+
+        Ok(pinned)
+    }
+
+    /// Replaces every reference to an input slot in the nodes and outputs of this graph
+    /// by the reference at the same position in `replacements`.
+    pub(super) fn replace_inputs(&mut self, replacements: &[Ref]) {
+        let replace = |r: &mut Ref| {
+            if let Ref::Input(slot) = *r {
+                *r = replacements[slot];
+            }
+        };
+
+        for node in &mut self.nodes {
+            node.args.iter_mut().for_each(replace);
+        }
+        self.outputs.iter_mut().for_each(replace);
+    }
+
+    /// The position of the input of a given name in the input layout.
+    fn input_field(&self, name: &str) -> usize {
+        self.input_layout
+            .0
+            .iter()
+            .position(|(field, _)| field == name)
+            .expect("input was already checked to exist")
+    }
+}
@@ -0,0 +1,62 @@
+//! Renaming of the fields of the input and output layouts of a graph.
+
+use super::{Error, Graph, Layout, Struct, INPUT_CONSTRAINTS_KEY, PINNABLE_INPUTS_KEY};
+
+/// Renames the field `old` of `fields` to `new`, keeping its position.
+fn rename_field(fields: &mut Struct, old: &str, new: &str, what: &str) -> Result<(), Error> {
+    let Some(position) = fields.0.iter().position(|(name, _)| name == old) else {
+        return Err(format!("no {what} named {old:?}").into());
+    };
+    if old != new && fields.0.iter().any(|(name, _)| name == new) {
+        return Err(
+            format!("cannot rename {what} {old:?} to {new:?}: {new:?} already exists").into(),
+        );
+    }
+    fields.0[position].0 = new.to_string();
+
+    Ok(())
+}
+
+impl Graph {
+    /// Renames the input `old` of this graph to `new`. The input keeps its position and
+    /// its slots in the input layout, so no node needs to change. Input constraints and
+    /// pinnable inputs are renamed along. This fails if there is no input named `old` or
+    /// if there is already an input named `new`.
+    pub fn rename_input(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        let mut constraints = self.input_constraints()?;
+        let mut pinnable = self.pinnable_inputs()?;
+        rename_field(&mut self.input_layout, old, new, "input")?;
+
+        if let Some(constraint) = constraints.remove(old) {
+            constraints.insert(new.to_string(), constraint);
+            self.metadata.insert(
+                INPUT_CONSTRAINTS_KEY.to_string(),
+                serde_json::to_string(&constraints)?,
+            );
+        }
+        if pinnable.remove(old) {
+            pinnable.insert(new.to_string());
+            self.metadata.insert(
+                PINNABLE_INPUTS_KEY.to_string(),
+                serde_json::to_string(&pinnable)?,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Renames the field `old` of the output struct of this graph to `new`, keeping its
+    /// position. This fails if the output is not a struct, if it has no field named `old`
+    /// or if it already has a field named `new`.
+    pub fn rename_output_field(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        let Layout::Struct(fields) = &mut self.output_layout else {
+            return Err(format!(
+                "cannot rename output field {old:?}: output layout {} is not a struct",
+                self.output_layout
+            )
+            .into());
+        };
+
+        rename_field(fields, old, new, "output field")
+    }
+}
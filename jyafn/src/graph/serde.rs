@@ -1,16 +1,198 @@
+use get_size::GetSize;
+use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::{Read, Seek, Write};
+use std::sync::Arc;
+use zip::read::ZipFile;
 use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
 
 use crate::Error;
 
-use super::{check, Graph};
+use super::{check, Graph, SLOT_SIZE};
+
+/// The metadata key under which an estimate of the memory used by a graph or function is
+/// stored, in bytes. This depends on the architecture where the graph is loaded, so it is
+/// recomputed by [`Graph::load`] instead of being trusted from the archive.
+pub const MEM_SIZE_ESTIMATE_KEY: &str = "jyafn.mem_size_estimate";
+
+/// The version of the format of graph archives written by [`Graph::dump`]. This is
+/// stored in the `VERSION` entry of the archive and is bumped whenever the serialization
+/// of graphs or mappings changes in a way that older builds would misread. Archives with
+/// a newer version than this are rejected on load; archives without a version predate it
+/// and are read as version `0`.
+///
+/// Version `1` is the first versioned format. It includes the hash seeds of mappings in
+/// the `mapping_seeds.json` entry, which builds that ignore the entry would misread.
+pub const DUMP_FORMAT_VERSION: u32 = 1;
+
+/// The sizes, in bytes, assumed by the build that dumped a graph. These are checked
+/// against the current build when loading, so that a graph is never silently read with
+/// a different memory layout than the one it was created with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct StoredSizes {
+    slot_size: usize,
+    input_size: usize,
+    output_size: usize,
+}
+
+impl StoredSizes {
+    fn of(graph: &Graph) -> StoredSizes {
+        StoredSizes {
+            slot_size: SLOT_SIZE.in_bytes(),
+            input_size: graph.input_layout.size().in_bytes(),
+            output_size: graph.output_layout.size().in_bytes(),
+        }
+    }
+
+    fn check(self, graph: &Graph) -> Result<(), Error> {
+        let current = StoredSizes::of(graph);
+
+        if self.slot_size != current.slot_size {
+            return Err(Error::Other(format!(
+                "graph was dumped assuming slots of {} bytes, but this build uses slots of \
+                 {} bytes",
+                self.slot_size, current.slot_size
+            )));
+        }
+
+        if self.input_size != current.input_size || self.output_size != current.output_size {
+            return Err(Error::Other(format!(
+                "graph was dumped with input and output of {} and {} bytes, but this build \
+                 lays them out in {} and {} bytes",
+                self.input_size, self.output_size, current.input_size, current.output_size
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// The JSON metadata of a graph and of each of its subgraphs, as stored in the
+/// `metadata_json.json` entry of an archive.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredMetadataJson {
+    #[serde(default)]
+    metadata: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    subgraphs: Vec<StoredMetadataJson>,
+}
+
+impl StoredMetadataJson {
+    fn of(graph: &Graph) -> StoredMetadataJson {
+        StoredMetadataJson {
+            metadata: graph.metadata_json.clone(),
+            subgraphs: graph.subgraphs.iter().map(StoredMetadataJson::of).collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.metadata.is_empty() && self.subgraphs.iter().all(StoredMetadataJson::is_empty)
+    }
+
+    /// Sets the JSON metadata of `graph` and of its subgraphs.
+    fn set(self, graph: &mut Graph) {
+        graph.metadata_json = self.metadata;
+        for (subgraph, metadata) in graph.subgraphs.iter_mut().zip(self.subgraphs) {
+            metadata.set(subgraph);
+        }
+    }
+}
+
+/// The origins of the errors of a graph and of each of its subgraphs, as stored in the
+/// `error_origins.json` entry of an archive. They are kept out of the `graph` entry, so
+/// that archives dumped before errors had origins can still be read.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredErrorOrigins {
+    #[serde(default)]
+    origins: HashMap<usize, String>,
+    #[serde(default)]
+    subgraphs: Vec<StoredErrorOrigins>,
+}
+
+impl StoredErrorOrigins {
+    fn of(graph: &Graph) -> StoredErrorOrigins {
+        StoredErrorOrigins {
+            origins: graph.error_origins.clone(),
+            subgraphs: graph.subgraphs.iter().map(StoredErrorOrigins::of).collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.origins.is_empty() && self.subgraphs.iter().all(StoredErrorOrigins::is_empty)
+    }
+
+    /// Sets the origins of the errors of `graph` and of its subgraphs.
+    fn set(self, graph: &mut Graph) {
+        graph.error_origins = self.origins;
+        for (subgraph, origins) in graph.subgraphs.iter_mut().zip(self.subgraphs) {
+            origins.set(subgraph);
+        }
+    }
+}
+
+/// The seeds of the mappings of a graph and of each of its subgraphs, as stored in the
+/// `mapping_seeds.json` entry of an archive. Mappings not listed there have a seed of `0`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredMappingSeeds {
+    #[serde(default)]
+    seeds: HashMap<String, u64>,
+    #[serde(default)]
+    subgraphs: Vec<StoredMappingSeeds>,
+}
+
+impl StoredMappingSeeds {
+    fn of(graph: &Graph) -> StoredMappingSeeds {
+        StoredMappingSeeds {
+            seeds: graph
+                .mappings
+                .iter()
+                .filter(|(_, mapping)| mapping.seed() != 0)
+                .map(|(name, mapping)| (name.clone(), mapping.seed()))
+                .collect(),
+            subgraphs: graph.subgraphs.iter().map(StoredMappingSeeds::of).collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.seeds.is_empty() && self.subgraphs.iter().all(StoredMappingSeeds::is_empty)
+    }
+
+    /// Sets the seeds of the mappings of a freshly deserialized `graph` and of its
+    /// subgraphs. This has to be done before the data of the mappings is read.
+    fn set(self, graph: &mut Graph) -> Result<(), Error> {
+        for (name, seed) in self.seeds {
+            let Some(mapping) = graph.mappings.get_mut(&name) else {
+                return Err(Error::Other(format!(
+                    "seed found for mapping {name:?}, which does not exist"
+                )));
+            };
+            let Some(mapping) = Arc::get_mut(mapping) else {
+                return Err(Error::Other(format!("mapping {name:?} is shared")));
+            };
+
+            mapping.set_seed(seed);
+        }
+
+        for (subgraph, seeds) in graph.subgraphs.iter_mut().zip(self.subgraphs) {
+            seeds.set(subgraph)?;
+        }
+
+        Ok(())
+    }
+}
 
 impl Graph {
+    /// The maximum size of the source code annotation of a graph, in bytes.
+    pub const MAX_SOURCE_SIZE: usize = 1 << 20;
+
     /// Writes a binary representation of the graph to the supplied writer.
     pub fn dump<W: Write + Seek>(&self, writer: W) -> Result<(), Error> {
         let mut writer = zip::ZipWriter::new(writer);
 
+        writer.start_file("VERSION", SimpleFileOptions::default())?;
+        write!(writer, "{DUMP_FORMAT_VERSION}")?;
+
         writer.start_file("graph", SimpleFileOptions::default())?;
         bincode::serialize_into(&mut writer, self).map_err(Error::Bincode)?;
 
@@ -19,6 +201,32 @@ impl Graph {
         writer.start_file("metadata.json", SimpleFileOptions::default())?;
         serde_json::to_writer(&mut writer, &self.metadata).map_err(Error::Json)?;
 
+        writer.start_file("sizes.json", SimpleFileOptions::default())?;
+        serde_json::to_writer(&mut writer, &StoredSizes::of(self)).map_err(Error::Json)?;
+
+        let metadata_json = StoredMetadataJson::of(self);
+        if !metadata_json.is_empty() {
+            writer.start_file("metadata_json.json", SimpleFileOptions::default())?;
+            serde_json::to_writer(&mut writer, &metadata_json).map_err(Error::Json)?;
+        }
+
+        let error_origins = StoredErrorOrigins::of(self);
+        if !error_origins.is_empty() {
+            writer.start_file("error_origins.json", SimpleFileOptions::default())?;
+            serde_json::to_writer(&mut writer, &error_origins).map_err(Error::Json)?;
+        }
+
+        if let Some(source) = &self.source {
+            writer.start_file("source.txt", SimpleFileOptions::default())?;
+            writer.write_all(source.as_bytes())?;
+        }
+
+        let mapping_seeds = StoredMappingSeeds::of(self);
+        if !mapping_seeds.is_empty() {
+            writer.start_file("mapping_seeds.json", SimpleFileOptions::default())?;
+            serde_json::to_writer(&mut writer, &mapping_seeds).map_err(Error::Json)?;
+        }
+
         for (name, mapping) in &self.mappings {
             writer.start_file(format!("{name}.mapping"), SimpleFileOptions::default())?;
             writer.write_all(&mapping.dump())?;
@@ -26,7 +234,7 @@ impl Graph {
 
         for (name, resources) in &self.resources {
             writer.start_file(format!("{name}.resource"), SimpleFileOptions::default())?;
-            writer.write_all(&resources.dump()?)?;
+            resources.dump_to(&mut writer)?;
         }
 
         writer.finish()?;
@@ -38,6 +246,7 @@ impl Graph {
     /// loading the whole graph and reading its metadata.
     pub fn load_metadata<R: Read + Seek>(reader: R) -> Result<HashMap<String, String>, Error> {
         let mut archive = zip::ZipArchive::new(reader)?;
+        check_version(&mut archive)?;
         let file = archive.by_name("metadata.json")?;
         let metadata: HashMap<String, String> =
             serde_json::from_reader(file).map_err(Error::Json)?;
@@ -45,10 +254,19 @@ impl Graph {
         Ok(metadata)
     }
 
+    /// Loads only the source code annotation of a graph, if there is any. See
+    /// [`Graph::set_source`] for more information.
+    pub fn load_source<R: Read + Seek>(reader: R) -> Result<Option<String>, Error> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        check_version(&mut archive)?;
+        read_source(&mut archive)
+    }
+
     /// Loads a graph in an unintialized state. This is quicker, since extra resources are
     /// not loader. However, you will not be able to compile the resulting graph.
     pub fn load_uninitialized<R: Read + Seek>(reader: R) -> Result<Self, Error> {
         let mut archive = zip::ZipArchive::new(reader)?;
+        check_version(&mut archive)?;
 
         let file = archive.by_name("graph")?;
         let mut graph: Graph = bincode::deserialize_from(file).map_err(Error::Bincode)?;
@@ -57,13 +275,42 @@ impl Graph {
         let metadata: HashMap<String, String> =
             serde_json::from_reader(file).map_err(Error::Json)?;
         graph.metadata = metadata;
+        read_metadata_json(&mut archive)?.set(&mut graph);
+        read_error_origins(&mut archive)?.set(&mut graph);
+        graph.source = read_source(&mut archive)?;
+
+        if let Some(sizes) = read_sizes(&mut archive)? {
+            sizes.check(&graph)?;
+        }
+        read_mapping_seeds(&mut archive)?.set(&mut graph)?;
 
         Ok(graph)
     }
 
     /// Loads a graph from the supplied reader.
     pub fn load<R: Read + Seek>(reader: R) -> Result<Self, Error> {
+        Self::load_archive(reader, None)
+    }
+
+    /// Loads a graph from a dump in memory, such as a memory-mapped file. Unlike
+    /// [`Graph::load`] over a [`Cursor`](std::io::Cursor), resources stored uncompressed
+    /// in the dump are built directly from `bytes`, without first being copied to an
+    /// intermediate buffer.
+    ///
+    /// Note that this only applies to entries with the `Stored` compression method.
+    /// [`Graph::dump`] compresses resources, so the resources of its dumps are still
+    /// decompressed into a buffer, just like with [`Graph::load`]. Archives can be
+    /// repacked with stored entries to avoid the copy. The returned graph does not borrow
+    /// from `bytes`.
+    pub fn load_from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        Self::load_archive(std::io::Cursor::new(bytes), Some(bytes))
+    }
+
+    /// Loads a graph from the supplied reader. If `bytes` is set, it must be the whole
+    /// content of the reader.
+    fn load_archive<R: Read + Seek>(reader: R, bytes: Option<&[u8]>) -> Result<Self, Error> {
         let mut archive = zip::ZipArchive::new(reader)?;
+        check_version(&mut archive)?;
 
         let file = archive.by_name("graph")?;
         let mut graph: Graph = bincode::deserialize_from(file).map_err(Error::Bincode)?;
@@ -72,6 +319,14 @@ impl Graph {
         let metadata: HashMap<String, String> =
             serde_json::from_reader(file).map_err(Error::Json)?;
         graph.metadata = metadata;
+        read_metadata_json(&mut archive)?.set(&mut graph);
+        read_error_origins(&mut archive)?.set(&mut graph);
+        graph.source = read_source(&mut archive)?;
+
+        if let Some(sizes) = read_sizes(&mut archive)? {
+            sizes.check(&graph)?;
+        }
+        read_mapping_seeds(&mut archive)?.set(&mut graph)?;
 
         for id in 0..archive.len() {
             let file = archive.by_index(id)?;
@@ -94,14 +349,49 @@ impl Graph {
                 continue;
             };
 
-            *resource = resource.read(file)?.into();
+            *resource = match bytes.and_then(|bytes| stored_contents(&file, bytes)) {
+                Some(contents) => resource.read_bytes(contents)?,
+                None => resource.read(file)?,
+            }
+            .into();
         }
 
         check::run_checks(&mut graph)?;
 
+        // The estimate in the archive was made by the build that dumped the graph, which
+        // might not even be on the same architecture.
+        if graph.metadata.contains_key(MEM_SIZE_ESTIMATE_KEY) {
+            let estimate = graph.get_size();
+            graph
+                .metadata
+                .insert(MEM_SIZE_ESTIMATE_KEY.to_string(), estimate.to_string());
+        }
+
         Ok(graph)
     }
 
+    /// Annotates this graph with the source code that generated it. This is stored in a
+    /// separate entry when dumping the graph, so that it can be retrieved later for
+    /// auditing purposes, but it has no effect on compilation. The source code cannot be
+    /// larger than [`Graph::MAX_SOURCE_SIZE`] bytes.
+    pub fn set_source(&mut self, source: String) -> Result<(), Error> {
+        if source.len() > Self::MAX_SOURCE_SIZE {
+            return Err(Error::Other(format!(
+                "source has {} bytes, but the maximum allowed is {} bytes",
+                source.len(),
+                Self::MAX_SOURCE_SIZE
+            )));
+        }
+
+        self.source = Some(source);
+        Ok(())
+    }
+
+    /// The source code that generated this graph, if it was set.
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
     /// Creates a JSON representation of this graph.
     ///
     /// # Note
@@ -110,6 +400,138 @@ impl Graph {
     /// does not fully serialize all the data necessary to recreate the graph. This is
     /// for inspection purposes only.
     pub fn to_json(&self) -> String {
-        serde_json::to_string(&self).expect("can always serialize")
+        let mut json = serde_json::to_value(self).expect("can always serialize");
+        insert_metadata_json(self, &mut json);
+        json.to_string()
+    }
+}
+
+/// Inserts the JSON metadata of a graph and of its subgraphs, which is skipped by
+/// `serde`, in the JSON representation of the graph.
+fn insert_metadata_json(graph: &Graph, json: &mut serde_json::Value) {
+    json["metadata_json"] =
+        serde_json::to_value(&graph.metadata_json).expect("can always serialize");
+    if let Some(subgraphs) = json["subgraphs"].as_array_mut() {
+        for (subgraph, json) in graph.subgraphs.iter().zip(subgraphs) {
+            insert_metadata_json(subgraph, json);
+        }
+    }
+}
+
+/// The contents of an uncompressed zip file entry, sliced from the bytes of the whole
+/// archive.
+fn stored_contents<'a>(file: &ZipFile<'_>, bytes: &'a [u8]) -> Option<&'a [u8]> {
+    if file.compression() != CompressionMethod::Stored {
+        return None;
+    }
+
+    let start = usize::try_from(file.data_start()).ok()?;
+    let size = usize::try_from(file.size()).ok()?;
+    bytes.get(start..start.checked_add(size)?)
+}
+
+/// Checks that the format version of a graph archive can be read by this build, before
+/// anything else is deserialized from it. Archives dumped by older versions have no
+/// version and are always read.
+fn check_version<R: Read + Seek>(archive: &mut zip::ZipArchive<R>) -> Result<(), Error> {
+    let mut file = match archive.by_name("VERSION") {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut version = String::new();
+    file.read_to_string(&mut version)?;
+    let version: u32 = version.trim().parse().map_err(|_| {
+        Error::Other(format!(
+            "invalid format version {version:?} in graph archive"
+        ))
+    })?;
+
+    if version > DUMP_FORMAT_VERSION {
+        return Err(Error::Other(format!(
+            "graph archive has format version {version}, but this build of jyafn only \
+            reads versions up to {DUMP_FORMAT_VERSION}. Upgrade jyafn to load it"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Reads the JSON metadata from a graph archive. Archives dumped without JSON metadata
+/// have none.
+fn read_metadata_json<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+) -> Result<StoredMetadataJson, Error> {
+    match archive.by_name("metadata_json.json") {
+        Ok(file) => serde_json::from_reader(file).map_err(Error::Json),
+        Err(zip::result::ZipError::FileNotFound) => Ok(StoredMetadataJson::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Reads the origins of the errors from a graph archive. Archives dumped without error
+/// origins have none.
+fn read_error_origins<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+) -> Result<StoredErrorOrigins, Error> {
+    match archive.by_name("error_origins.json") {
+        Ok(file) => serde_json::from_reader(file).map_err(Error::Json),
+        Err(zip::result::ZipError::FileNotFound) => Ok(StoredErrorOrigins::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Reads the sizes assumed by the build that dumped a graph archive. Archives dumped by
+/// older versions do not record them.
+fn read_sizes<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+) -> Result<Option<StoredSizes>, Error> {
+    match archive.by_name("sizes.json") {
+        Ok(file) => serde_json::from_reader(file).map_err(Error::Json),
+        Err(zip::result::ZipError::FileNotFound) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Reads the seeds of the mappings from a graph archive. Archives dumped without seeds
+/// have none.
+fn read_mapping_seeds<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+) -> Result<StoredMappingSeeds, Error> {
+    match archive.by_name("mapping_seeds.json") {
+        Ok(file) => serde_json::from_reader(file).map_err(Error::Json),
+        Err(zip::result::ZipError::FileNotFound) => Ok(StoredMappingSeeds::default()),
+        Err(err) => Err(err.into()),
     }
 }
+
+/// Reads the source code annotation from a graph archive, if it exists. Sources larger
+/// than [`Graph::MAX_SOURCE_SIZE`] are rejected without being read whole, whatever size
+/// the archive claims them to have.
+fn read_source<R: Read + Seek>(archive: &mut zip::ZipArchive<R>) -> Result<Option<String>, Error> {
+    let file = match archive.by_name("source.txt") {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let too_large = || {
+        Error::Other(format!(
+            "source in graph archive is larger than the maximum allowed of {} bytes",
+            Graph::MAX_SOURCE_SIZE
+        ))
+    };
+    let max_size = Graph::MAX_SOURCE_SIZE as u64;
+    if file.size() > max_size {
+        return Err(too_large());
+    }
+
+    let mut source = String::new();
+    file.take(max_size + 1).read_to_string(&mut source)?;
+    if source.len() > Graph::MAX_SOURCE_SIZE {
+        return Err(too_large());
+    }
+
+    Ok(Some(source))
+}
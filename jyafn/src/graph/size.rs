@@ -70,3 +70,33 @@ const SLOT_SIZE: usize = 8;
 impl Unit for InSlots {
     const UNIT: Size = Size(SLOT_SIZE);
 }
+
+/// The memory used by a graph, in bytes, broken down by component. See
+/// [`Graph::size_breakdown`](crate::Graph::size_breakdown).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeBreakdown {
+    /// The heap memory used by the nodes of the graph.
+    pub nodes: usize,
+    /// The memory used by the mappings of the graph.
+    pub mappings: usize,
+    /// The memory used by the resources of the graph. This is only as good as the
+    /// estimate each resource gives of its own size.
+    pub resources: usize,
+    /// The memory used by the subgraphs of the graph, not including mappings and
+    /// resources already counted elsewhere.
+    pub subgraphs: usize,
+    /// The heap memory used by the symbols of the graph.
+    pub symbols: usize,
+    /// Everything else, including the graph struct itself, its name, metadata and
+    /// layouts.
+    pub other: usize,
+}
+
+impl SizeBreakdown {
+    /// The total memory used by the graph. This is the same as
+    /// [`GetSize::get_size`](get_size::GetSize::get_size), except that mappings and
+    /// resources shared between the graph and its subgraphs are counted only once.
+    pub fn total(&self) -> usize {
+        self.nodes + self.mappings + self.resources + self.subgraphs + self.symbols + self.other
+    }
+}
@@ -7,7 +7,7 @@ use crate::Error;
 use super::size::{InSlots, Size, Unit};
 
 /// The primitive types of data that can be represented in the computational graph.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, GetSize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, GetSize)]
 #[repr(u8)]
 pub enum Type {
     /// A floating point number.
@@ -21,6 +21,8 @@ pub enum Type {
     Ptr { origin: usize },
     /// An integer timestamp in microseconds.
     DateTime,
+    /// A signed 64-bit integer.
+    Int,
 }
 
 impl TryFrom<u8> for Type {
@@ -33,6 +35,7 @@ impl TryFrom<u8> for Type {
             2 => Ok(Type::Symbol),
             3 => Ok(Type::Ptr { origin: usize::MAX }),
             4 => Ok(Type::DateTime),
+            5 => Ok(Type::Int),
             _ => Err(format!("{v} is not a valid type id"))?,
         }
     }
@@ -46,6 +49,7 @@ impl Display for Type {
             Type::Symbol => write!(f, "symbol"),
             Type::Ptr { origin } => write!(f, "ptr@{origin}"),
             Type::DateTime => write!(f, "datetime"),
+            Type::Int => write!(f, "int"),
         }
     }
 }
@@ -61,6 +65,7 @@ impl Type {
             Type::Symbol => qbe::Type::Long,
             Type::Ptr { .. } => qbe::Type::Long,
             Type::DateTime => qbe::Type::Long,
+            Type::Int => qbe::Type::Long,
         }
     }
 
@@ -79,6 +84,7 @@ impl Type {
                     "<invalid datetime>".to_string()
                 }
             }
+            Type::Int => format!("{}", val as i64),
         }
     }
 }
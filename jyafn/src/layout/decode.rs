@@ -1,10 +1,12 @@
+use base64::Engine;
 use std::collections::BTreeMap;
+use std::fmt::{self, Display};
 use std::rc::Rc;
 use std::sync::Arc;
 
 use hashbrown::HashMap;
 
-use crate::utils;
+use crate::{utils, Error};
 
 use super::symbols::Sym;
 use super::{Layout, Visitor};
@@ -22,7 +24,18 @@ pub trait Decoder {
     fn build(&mut self, layout: &Layout, symbols: &dyn Sym, visitor: &mut Visitor) -> Self::Target;
 }
 
+impl<D: ?Sized + Decoder> Decoder for &mut D {
+    type Target = D::Target;
+    fn build(&mut self, layout: &Layout, symbols: &dyn Sym, visitor: &mut Visitor) -> Self::Target {
+        (**self).build(layout, symbols, visitor)
+    }
+}
+
 /// A type that can be decoded from a `layout`, `symbols` and a visitor.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be decoded from a jyafn layout",
+    note = "structs with named fields can derive `JyafnDecode` from the `jyafn-derive` crate"
+)]
 pub trait Decode {
     /// Creates a value of `Self` corresponding to the supplied information.
     ///
@@ -30,6 +43,321 @@ pub trait Decode {
     /// no decode errors are expected from this function. If necessary, this code should
     /// panic, indicating a bug in the caller code.
     fn build(layout: &Layout, symbols: &dyn Sym, visitor: &mut Visitor) -> Self;
+
+    /// Creates a value of `Self` from one of the bytes of a [`Layout::Bytes`], which is
+    /// how `Vec<Self>` is decoded from bytes. The default implementation panics, since
+    /// only integers can hold bytes.
+    #[allow(unused_variables)]
+    fn from_byte(byte: u8) -> Self
+    where
+        Self: Sized,
+    {
+        panic!("Bad layout for {}: bytes", std::any::type_name::<Self>())
+    }
+}
+
+/// Checks that the integer-backed slots of the binary data in `buffer` hold valid values
+/// for the given `layout`, before anything is decoded from it. Bools must be exactly `0`
+/// or `1`, symbols must be indices present in `symbols` and date-times must be
+/// representable timestamps. Scalars are not checked.
+///
+/// Decoders assume that their input is well-formed and may panic or silently decode
+/// garbage otherwise. Use this (or [`crate::Function::eval_strict`]) to get a descriptive
+/// error instead, pointing to the offending value.
+///
+/// If the layout contains maps, the buffer may be longer than the layout, to hold the
+/// map entries. These must be inside the buffer and are also checked.
+pub fn validate(layout: &Layout, symbols: &dyn Sym, buffer: &[u8]) -> Result<(), Error> {
+    let size = layout.size().in_bytes();
+    let has_maps = layout.contains_map();
+    if buffer.len() != size && !(has_maps && buffer.len() > size && buffer.len().is_multiple_of(8))
+    {
+        return Err(Error::DecodeError(format!(
+            "buffer has {} bytes, but layout {layout} needs {size}",
+            buffer.len(),
+        )));
+    }
+
+    let all_slots = buffer
+        .chunks_exact(8)
+        .map(|slot| i64::from_ne_bytes(slot.try_into().expect("slot has 8 bytes")))
+        .collect::<Vec<_>>();
+    validate_recursive(
+        layout,
+        symbols,
+        &all_slots,
+        &mut all_slots.iter().copied(),
+        &mut String::new(),
+    )
+}
+
+fn validate_recursive(
+    layout: &Layout,
+    symbols: &dyn Sym,
+    all_slots: &[i64],
+    slots: &mut impl Iterator<Item = i64>,
+    path: &mut String,
+) -> Result<(), Error> {
+    let display_path = |path: &str| {
+        if path.is_empty() {
+            ".".to_string()
+        } else {
+            path.to_string()
+        }
+    };
+
+    match layout {
+        Layout::Unit => {}
+        Layout::Scalar => {
+            slots.next();
+        }
+        Layout::Bool => match slots.next() {
+            Some(0 | 1) => {}
+            Some(value) => {
+                return Err(Error::DecodeError(format!(
+                    "bool at {} has value {value}, expected 0 or 1",
+                    display_path(path)
+                )))
+            }
+            None => {}
+        },
+        Layout::DateTime(_) => {
+            if let Some(value) = slots.next() {
+                if utils::int_to_datetime(value).is_none() {
+                    return Err(Error::DecodeError(format!(
+                        "datetime at {} has out of range timestamp {value}",
+                        display_path(path)
+                    )));
+                }
+            }
+        }
+        Layout::Symbol => {
+            if let Some(value) = slots.next() {
+                let index = u64::from_ne_bytes(value.to_ne_bytes());
+                if symbols.get(index).is_none() {
+                    return Err(Error::DecodeError(format!(
+                        "symbol at {} has unknown index {index}",
+                        display_path(path)
+                    )));
+                }
+            }
+        }
+        Layout::Int => {
+            slots.next();
+        }
+        Layout::Bytes(n_bytes) => {
+            for _ in 0..n_bytes.div_ceil(8) {
+                slots.next();
+            }
+        }
+        Layout::Struct(fields) => {
+            for (name, field) in &fields.0 {
+                let len = path.len();
+                path.push('.');
+                path.push_str(name);
+                validate_recursive(field, symbols, all_slots, slots, path)?;
+                path.truncate(len);
+            }
+        }
+        Layout::Tuple(fields) => {
+            for (i, field) in fields.iter().enumerate() {
+                let len = path.len();
+                path.push_str(&format!(".{i}"));
+                validate_recursive(field, symbols, all_slots, slots, path)?;
+                path.truncate(len);
+            }
+        }
+        Layout::List(element, size) => {
+            for i in 0..*size {
+                let len = path.len();
+                path.push_str(&format!("[{i}]"));
+                validate_recursive(element, symbols, all_slots, slots, path)?;
+                path.truncate(len);
+            }
+        }
+        Layout::Optional(inner) => match slots.next() {
+            Some(0) => {
+                // Absent values are zeroed and not meant to be decoded.
+                for _ in 0..inner.size().in_slots() {
+                    slots.next();
+                }
+            }
+            Some(1) => validate_recursive(inner, symbols, all_slots, slots, path)?,
+            Some(value) => {
+                return Err(Error::DecodeError(format!(
+                    "presence flag at {} has value {value}, expected 0 or 1",
+                    display_path(path)
+                )))
+            }
+            None => {}
+        },
+        Layout::Map(value) => {
+            let (Some(n_entries), Some(start)) = (slots.next(), slots.next()) else {
+                return Ok(());
+            };
+            if n_entries == 0 {
+                return Ok(());
+            }
+            let entry_size = 1 + value.size().in_slots();
+            let entries = usize::try_from(n_entries)
+                .ok()
+                .zip(usize::try_from(start).ok())
+                .and_then(|(n_entries, start)| {
+                    all_slots.get(start..start.checked_add(n_entries.checked_mul(entry_size)?)?)
+                });
+            let Some(entries) = entries else {
+                return Err(Error::DecodeError(format!(
+                    "map at {} has {n_entries} entries starting at slot {start}, which are \
+                    out of the buffer",
+                    display_path(path)
+                )));
+            };
+
+            for (i, entry) in entries.chunks_exact(entry_size).enumerate() {
+                let len = path.len();
+                path.push_str(&format!("{{{i}}}"));
+                let mut entry_slots = entry.iter().copied();
+                validate_recursive(&Layout::Symbol, symbols, all_slots, &mut entry_slots, path)?;
+                validate_recursive(value, symbols, all_slots, &mut entry_slots, path)?;
+                path.truncate(len);
+            }
+        }
+        Layout::Enum(variants) => {
+            if let Some(value) = slots.next() {
+                if usize::try_from(value).map_or(true, |index| index >= variants.len()) {
+                    return Err(Error::DecodeError(format!(
+                        "enum at {} has value {value}, expected an index below {}",
+                        display_path(path),
+                        variants.len()
+                    )));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A path to a value inside a layout, such as `heads.score` or `items.2`. Each segment
+/// of the path is interpreted according to the layout it is applied to: a field name for
+/// structs and an index for tuples and lists. The empty path points to the whole value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct OutputPath(pub Vec<String>);
+
+impl Display for OutputPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join("."))
+    }
+}
+
+impl From<&str> for OutputPath {
+    fn from(path: &str) -> Self {
+        OutputPath::parse(path)
+    }
+}
+
+impl OutputPath {
+    /// Parses a path from its dot-separated representation.
+    pub fn parse(path: &str) -> OutputPath {
+        if path.is_empty() {
+            OutputPath(vec![])
+        } else {
+            OutputPath(path.split('.').map(str::to_string).collect())
+        }
+    }
+
+    /// Finds the value this path points to in `layout`, returning its offset in slots
+    /// from the start of the layout and its layout.
+    pub fn locate<'a>(&self, layout: &'a Layout) -> Result<(usize, &'a Layout), Error> {
+        let mut offset = 0;
+        let mut current = layout;
+
+        for segment in &self.0 {
+            let index = || {
+                segment.parse::<usize>().map_err(|_| {
+                    Error::Other(format!(
+                        "path `{self}` has bad index {segment:?} for {current}"
+                    ))
+                })
+            };
+            let not_found =
+                || Error::Other(format!("path `{self}` has no {segment:?} in {current}"));
+
+            current = match current {
+                Layout::Struct(fields) => {
+                    let position = fields
+                        .0
+                        .iter()
+                        .position(|(name, _)| name == segment)
+                        .ok_or_else(not_found)?;
+                    offset += fields.0[..position]
+                        .iter()
+                        .map(|(_, field)| field.size().in_slots())
+                        .sum::<usize>();
+                    &fields.0[position].1
+                }
+                Layout::Tuple(fields) => {
+                    let position = index()?;
+                    let field = fields.get(position).ok_or_else(not_found)?;
+                    offset += fields[..position]
+                        .iter()
+                        .map(|field| field.size().in_slots())
+                        .sum::<usize>();
+                    field
+                }
+                Layout::List(element, size) => {
+                    let position = index()?;
+                    if position >= *size {
+                        return Err(not_found());
+                    }
+                    offset += position * element.size().in_slots();
+                    element
+                }
+                _ => return Err(not_found()),
+            };
+        }
+
+        Ok((offset, current))
+    }
+}
+
+/// A decoder that decodes only the values at some given paths of the output, using an
+/// inner decoder for each of them and skipping everything else. The target is the list
+/// of decoded values, in the same order as the paths.
+#[derive(Debug, Clone)]
+pub struct SelectDecoder<D> {
+    selected: Vec<(usize, Layout)>,
+    decoder: D,
+}
+
+impl<D> SelectDecoder<D> {
+    /// Creates a new decoder selecting `paths` from values of the given `layout`. This
+    /// fails if any of the paths does not exist in the layout.
+    pub fn new(layout: &Layout, paths: &[OutputPath], decoder: D) -> Result<Self, Error> {
+        let selected = paths
+            .iter()
+            .map(|path| {
+                path.locate(layout)
+                    .map(|(offset, layout)| (offset, layout.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(SelectDecoder { selected, decoder })
+    }
+}
+
+impl<D: Decoder> Decoder for SelectDecoder<D> {
+    type Target = Vec<D::Target>;
+    fn build(&mut self, _: &Layout, symbols: &dyn Sym, visitor: &mut Visitor) -> Self::Target {
+        self.selected
+            .iter()
+            .map(|(offset, layout)| {
+                visitor.reset();
+                visitor.skip(*offset);
+                self.decoder.build(layout, symbols, visitor)
+            })
+            .collect()
+    }
 }
 
 /// A decoder for types that implement [`Decode`].
@@ -55,6 +383,34 @@ impl<D: Decode> Decoder for ZeroDecoder<D> {
     }
 }
 
+/// Decodes the header and the entries of a [`Layout::Map`] whose values have the layout
+/// `value`, using `build` to decode each value. The visitor ends up right after the
+/// header.
+fn build_map_entries<T>(
+    value: &Layout,
+    symbols: &dyn Sym,
+    visitor: &mut Visitor,
+    mut build: impl FnMut(&Layout, &dyn Sym, &mut Visitor) -> T,
+) -> Vec<(String, T)> {
+    let (n_entries, start) = visitor.pop_map();
+    let end = visitor.position();
+    let entry_size = 1 + value.size().in_slots();
+
+    let entries = (0..n_entries)
+        .map(|i| {
+            visitor.seek(start + i * entry_size);
+            let index = visitor.pop_uint();
+            let Some(key) = symbols.get(index) else {
+                panic!("Symbol of index {index} not found")
+            };
+            (key.to_owned(), build(value, symbols, visitor))
+        })
+        .collect();
+    visitor.seek(end);
+
+    entries
+}
+
 impl Decode for () {
     fn build(layout: &Layout, _: &dyn Sym, _: &mut Visitor) -> Self {
         match layout {
@@ -70,6 +426,10 @@ macro_rules! impl_decode_container {
             fn build(layout: &Layout, symbols: &dyn Sym, visitor: &mut Visitor) -> Self {
                 Self::from(T::build(layout, symbols, visitor))
             }
+
+            fn from_byte(byte: u8) -> Self {
+                Self::from(T::from_byte(byte))
+            }
         }
     };
 }
@@ -91,16 +451,34 @@ macro_rules! impl_decode_scalar {
     };
 }
 
-impl_decode_scalar!(i8);
-impl_decode_scalar!(u8);
-impl_decode_scalar!(i16);
-impl_decode_scalar!(u16);
-impl_decode_scalar!(i32);
-impl_decode_scalar!(u32);
-impl_decode_scalar!(i64);
-impl_decode_scalar!(u64);
-impl_decode_scalar!(isize);
-impl_decode_scalar!(usize);
+macro_rules! impl_decode_int {
+    ($int:ty) => {
+        impl Decode for $int {
+            fn build(layout: &Layout, _: &dyn Sym, visitor: &mut Visitor) -> Self {
+                match layout {
+                    Layout::Scalar => visitor.pop() as $int,
+                    Layout::Int => visitor.pop_int() as $int,
+                    _ => panic!("Bad layout for {}: {layout:?}", stringify!($int)),
+                }
+            }
+
+            fn from_byte(byte: u8) -> Self {
+                byte as $int
+            }
+        }
+    };
+}
+
+impl_decode_int!(i8);
+impl_decode_int!(u8);
+impl_decode_int!(i16);
+impl_decode_int!(u16);
+impl_decode_int!(i32);
+impl_decode_int!(u32);
+impl_decode_int!(i64);
+impl_decode_int!(u64);
+impl_decode_int!(isize);
+impl_decode_int!(usize);
 impl_decode_scalar!(f64);
 impl_decode_scalar!(f32);
 
@@ -127,17 +505,68 @@ impl Decode for String {
                 };
                 string.to_owned()
             }
+            Layout::Enum(variants) => {
+                let index = visitor.pop_uint();
+                let Some(string) = variants.get(index as usize) else {
+                    panic!("Enum variant of index {index} not found")
+                };
+                string.to_owned()
+            }
             _ => panic!("Bad layout for String: {layout:?}"),
         }
     }
 }
 
+impl Decode for chrono::DateTime<chrono::Utc> {
+    fn build(layout: &Layout, _: &dyn Sym, visitor: &mut Visitor) -> Self {
+        match layout {
+            Layout::DateTime(_) => utils::Timestamp::from(visitor.pop_int()).into(),
+            _ => panic!("Bad layout for DateTime<Utc>: {layout:?}"),
+        }
+    }
+}
+
+impl Decode for chrono::NaiveDateTime {
+    fn build(layout: &Layout, symbols: &dyn Sym, visitor: &mut Visitor) -> Self {
+        chrono::DateTime::<chrono::Utc>::build(layout, symbols, visitor).naive_utc()
+    }
+}
+
+/// Dates are decoded as the day of the instant in UTC, discarding the time of day.
+impl Decode for chrono::NaiveDate {
+    fn build(layout: &Layout, symbols: &dyn Sym, visitor: &mut Visitor) -> Self {
+        chrono::DateTime::<chrono::Utc>::build(layout, symbols, visitor).date_naive()
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn build(layout: &Layout, symbols: &dyn Sym, visitor: &mut Visitor) -> Self {
+        match layout {
+            Layout::Optional(inner) => {
+                if visitor.pop_int() != 0 {
+                    Some(T::build(inner, symbols, visitor))
+                } else {
+                    visitor.skip(inner.size().in_slots());
+                    None
+                }
+            }
+            _ => panic!("Bad layout for Option<_>: {layout:?}"),
+        }
+    }
+}
+
 impl<T: Decode> Decode for Vec<T> {
     fn build(layout: &Layout, symbols: &dyn Sym, visitor: &mut Visitor) -> Self {
         match layout {
             Layout::List(layout, size) => (0..*size)
                 .map(|_| T::build(layout, symbols, visitor))
                 .collect(),
+            // Each byte is decoded as an integer, so that `Vec<u8>` holds the bytes.
+            Layout::Bytes(n_bytes) => visitor
+                .pop_bytes(*n_bytes)
+                .iter()
+                .map(|&byte| T::from_byte(byte))
+                .collect(),
             _ => panic!("Bad layout for Vec<_>: {layout:?}"),
         }
     }
@@ -189,6 +618,9 @@ impl<T: Decode> Decode for HashMap<String, T> {
 
                 decoded
             }
+            Layout::Map(value) => build_map_entries(value, symbols, visitor, T::build)
+                .into_iter()
+                .collect(),
             _ => panic!("Bad layout for HashMap<String, _>: {layout:?}"),
         }
     }
@@ -206,6 +638,9 @@ impl<T: Decode> Decode for BTreeMap<String, T> {
 
                 decoded
             }
+            Layout::Map(value) => build_map_entries(value, symbols, visitor, T::build)
+                .into_iter()
+                .collect(),
             _ => panic!("Bad layout for BTreeMap<String, _>: {layout:?}"),
         }
     }
@@ -239,6 +674,391 @@ impl Decode for serde_json::Value {
                 .map(|_| Self::build(element, symbols, visitor))
                 .collect::<Vec<_>>()
                 .into(),
+            Layout::Int => visitor.pop_int().into(),
+            Layout::Optional(inner) => {
+                if visitor.pop_int() != 0 {
+                    Self::build(inner, symbols, visitor)
+                } else {
+                    visitor.skip(inner.size().in_slots());
+                    Self::Null
+                }
+            }
+            // Functions validate enums before decoding them, so this is never null for
+            // their outputs.
+            Layout::Enum(variants) => variants
+                .get(visitor.pop_uint() as usize)
+                .map_or(Self::Null, |variant| Self::String(variant.clone())),
+            Layout::Bytes(n_bytes) => Self::String(
+                base64::engine::general_purpose::STANDARD.encode(visitor.pop_bytes(*n_bytes)),
+            ),
+            Layout::Map(value) => build_map_entries(value, symbols, visitor, Self::build)
+                .into_iter()
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::layout::{Struct, Symbols};
+
+    fn to_buffer(slots: &[i64]) -> Vec<u8> {
+        slots.iter().flat_map(|slot| slot.to_ne_bytes()).collect()
+    }
+
+    #[test]
+    fn test_validate_corrupt_buffer() {
+        let mut symbols = Symbols::default();
+        let a = symbols.find("a") as i64;
+        let layout = Layout::Struct(Struct(vec![
+            ("x".to_string(), Layout::Scalar),
+            ("flags".to_string(), Layout::List(Box::new(Layout::Bool), 2)),
+            ("name".to_string(), Layout::Symbol),
+        ]));
+
+        let good = to_buffer(&[f64::to_bits(1.5) as i64, 0, 1, a]);
+        validate(&layout, &symbols, &good).unwrap();
+
+        let bad_bool = to_buffer(&[0, 1, 2, a]);
+        let err = validate(&layout, &symbols, &bad_bool).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "decode error: bool at .flags[1] has value 2, expected 0 or 1"
+        );
+
+        let bad_symbol = to_buffer(&[0, 1, 0, a + 100]);
+        assert!(validate(&layout, &symbols, &bad_symbol).is_err());
+
+        let bad_datetime = to_buffer(&[i64::MAX]);
+        assert!(validate(&Layout::DateTime(String::new()), &symbols, &bad_datetime).is_err());
+
+        assert!(validate(&layout, &symbols, &good[..8]).is_err());
+    }
+
+    fn visitor(encoded: Box<[u8]>) -> Visitor {
+        let mut visitor = Visitor::from(encoded);
+        visitor.reset();
+        visitor
+    }
+
+    #[test]
+    fn test_optional_round_trip() {
+        let mut symbols = Symbols::default();
+        let layout = Layout::Struct(crate::layout!({
+            "x": (?scalar),
+            "name": (?symbol),
+            "ys": [(?int); 2]
+        }));
+        assert_eq!(
+            layout.to_string(),
+            "{ x: ?scalar, name: ?symbol, ys: [?int; 2] }"
+        );
+        assert_eq!(layout.size().in_slots(), 8);
+
+        let round_trip = |value: serde_json::Value, symbols: &mut Symbols| {
+            let encoded = layout.encode(&value, symbols).unwrap();
+            validate(&layout, symbols, &encoded).unwrap();
+            serde_json::Value::build(&layout, symbols, &mut visitor(encoded))
+        };
+
+        let full = serde_json::json!({ "x": 1.5, "name": "a", "ys": [1, 2] });
+        assert_eq!(round_trip(full.clone(), &mut symbols), full);
+
+        let empty = serde_json::json!({ "x": null, "name": null, "ys": [null, 3] });
+        assert_eq!(round_trip(empty.clone(), &mut symbols), empty);
+
+        let missing = serde_json::json!({ "ys": [null, null] });
+        assert_eq!(
+            round_trip(missing, &mut symbols),
+            serde_json::json!({ "x": null, "name": null, "ys": [null, null] })
+        );
+
+        let layout = crate::layout!(?scalar);
+        let encoded = layout.encode(&Some(2.5), &mut symbols).unwrap();
+        let decoded = Option::<f64>::build(&layout, &symbols, &mut visitor(encoded));
+        assert_eq!(decoded, Some(2.5));
+        let encoded = layout.encode(&None::<f64>, &mut symbols).unwrap();
+        let decoded = Option::<f64>::build(&layout, &symbols, &mut visitor(encoded));
+        assert_eq!(decoded, None);
+
+        assert!(validate(&layout, &symbols, &to_buffer(&[2, 0])).is_err());
+        assert!(Layout::Optional(Box::new(Layout::Scalar)).is_superset(&Layout::Scalar));
+        assert!(!Layout::Scalar.is_superset(&Layout::Optional(Box::new(Layout::Scalar))));
+    }
+
+    #[test]
+    fn test_datetime_round_trip() {
+        use chrono::{NaiveDate, TimeZone, Utc};
+
+        let mut symbols = Symbols::default();
+        let layout = Layout::DateTime(crate::layout::ISOFORMAT.to_string());
+
+        let instant = Utc.with_ymd_and_hms(2024, 2, 29, 13, 45, 30).unwrap()
+            + chrono::Duration::microseconds(123_456);
+        let encoded = layout.encode(&instant, &mut symbols).unwrap();
+        assert_eq!(&*encoded, &to_buffer(&[instant.timestamp_micros()])[..]);
+        let decoded = chrono::DateTime::<Utc>::build(&layout, &symbols, &mut visitor(encoded));
+        assert_eq!(decoded, instant);
+
+        let date = NaiveDate::from_ymd_opt(1969, 7, 20).unwrap();
+        let encoded = layout.encode(&date, &mut symbols).unwrap();
+        let decoded = chrono::DateTime::<Utc>::build(&layout, &symbols, &mut visitor(encoded));
+        assert_eq!(decoded, Utc.with_ymd_and_hms(1969, 7, 20, 0, 0, 0).unwrap());
+        let encoded = layout.encode(&instant, &mut symbols).unwrap();
+        let decoded = NaiveDate::build(&layout, &symbols, &mut visitor(encoded));
+        assert_eq!(decoded, NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+
+        let err = Layout::Scalar
+            .encode(&date, &mut symbols)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("expected datetime layout, got scalar"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_enum_round_trip() {
+        let mut symbols = Symbols::default();
+        let layout = crate::layout!(enum["red", "green", "blue"]);
+        assert_eq!(layout.to_string(), r#"enum["red", "green", "blue"]"#);
+        assert_eq!(layout.slots(), vec![crate::Type::Int]);
+
+        let encoded = layout.encode(&"green".to_string(), &mut symbols).unwrap();
+        assert_eq!(&*encoded, &to_buffer(&[1])[..]);
+        validate(&layout, &symbols, &encoded).unwrap();
+        let decoded = String::build(&layout, &symbols, &mut visitor(encoded));
+        assert_eq!(decoded, "green");
+
+        let value = serde_json::json!("blue");
+        let encoded = layout.encode(&value, &mut symbols).unwrap();
+        let decoded = serde_json::Value::build(&layout, &symbols, &mut visitor(encoded));
+        assert_eq!(decoded, value);
+
+        let err = layout
+            .encode(&"purple".to_string(), &mut symbols)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(r#""purple" is not one of the enum variants"#),
+            "{err}"
+        );
+        assert!(layout
+            .encode(&serde_json::json!("Red"), &mut symbols)
+            .is_err());
+        assert!(validate(&layout, &symbols, &to_buffer(&[3])).is_err());
+        let decoded =
+            serde_json::Value::build(&layout, &symbols, &mut visitor(to_buffer(&[3]).into()));
+        assert_eq!(decoded, serde_json::Value::Null);
+        assert!(validate(&layout, &symbols, &to_buffer(&[-1])).is_err());
+
+        let json = serde_json::to_string(&layout).unwrap();
+        assert_eq!(serde_json::from_str::<Layout>(&json).unwrap(), layout);
+    }
+
+    #[test]
+    fn test_enum_superset() {
+        let small = crate::layout!(enum["a", "b"]);
+        let large = crate::layout!(enum["a", "b", "c"]);
+        let reordered = crate::layout!(enum["b", "a", "c"]);
+
+        assert!(large.is_superset(&small));
+        assert!(large.is_superset(&large));
+        assert!(!small.is_superset(&large));
+        assert!(!reordered.is_superset(&small));
+        assert!(!Layout::Symbol.is_superset(&small));
+        assert!(!small.is_superset(&Layout::Symbol));
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let mut symbols = Symbols::default();
+        let layout = crate::layout!(bytes 11);
+        assert_eq!(layout.to_string(), "bytes 11");
+        assert_eq!(layout.size().in_slots(), 2);
+        assert_eq!(layout.slots(), vec![crate::Type::Int; 2]);
+
+        let bytes = (1..=11).collect::<Vec<u8>>();
+        let encoded = layout.encode(&bytes, &mut symbols).unwrap();
+        assert_eq!(&encoded[..11], &bytes[..]);
+        assert_eq!(&encoded[11..], &[0; 5]);
+        validate(&layout, &symbols, &encoded).unwrap();
+        let decoded = Vec::<u8>::build(&layout, &symbols, &mut visitor(encoded.clone()));
+        assert_eq!(decoded, bytes);
+        let decoded = Vec::<Box<i64>>::build(&layout, &symbols, &mut visitor(encoded.clone()));
+        assert_eq!(
+            decoded,
+            bytes
+                .iter()
+                .map(|&b| Box::new(b as i64))
+                .collect::<Vec<_>>()
+        );
+        let items = bytes.iter().map(|&b| b as i64).collect::<Vec<_>>();
+        assert_eq!(layout.encode(&items, &mut symbols).unwrap(), encoded);
+
+        let base64 = serde_json::json!("AQIDBAUGBwgJCgs=");
+        let decoded = serde_json::Value::build(&layout, &symbols, &mut visitor(encoded.clone()));
+        assert_eq!(decoded, base64);
+        assert_eq!(layout.encode(&base64, &mut symbols).unwrap(), encoded);
+        let array = serde_json::json!([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(layout.encode(&array, &mut symbols).unwrap(), encoded);
+
+        let mut streamed = Visitor::new(layout.size());
+        crate::layout::encode_json(
+            r#""AQIDBAUGBwgJCgs=""#,
+            &layout,
+            &mut symbols,
+            &mut streamed,
+        )
+        .unwrap();
+        assert_eq!(streamed.buffer(), &encoded[..]);
+        let mut streamed = Visitor::new(layout.size());
+        crate::layout::encode_json(&array.to_string(), &layout, &mut symbols, &mut streamed)
+            .unwrap();
+        assert_eq!(streamed.buffer(), &encoded[..]);
+        let mut written = vec![];
+        crate::layout::write_json(&layout, &symbols, &visitor(encoded), &mut written).unwrap();
+        assert_eq!(written, br#""AQIDBAUGBwgJCgs=""#);
+
+        assert!(layout.encode(&vec![1u8; 10], &mut symbols).is_err());
+        assert!(layout
+            .encode(&serde_json::json!("AQID"), &mut symbols)
+            .is_err());
+        assert!(layout
+            .encode(&serde_json::json!(vec![256; 11]), &mut symbols)
+            .is_err());
+
+        let layout = Layout::Struct(crate::layout!({ "token": (bytes 16) }));
+        let value = serde_json::json!({ "token": "AAECAwQFBgcICQoLDA0ODw==" });
+        let encoded = layout.encode(&value, &mut symbols).unwrap();
+        assert_eq!(
+            serde_json::Value::build(&layout, &symbols, &mut visitor(encoded)),
+            value
+        );
+
+        assert!(Layout::Bytes(8).is_superset(&Layout::Bytes(8)));
+        assert!(!Layout::Bytes(8).is_superset(&Layout::Bytes(4)));
+        assert!(!Layout::Bytes(8).is_superset(&Layout::Int));
+        let json = serde_json::to_string(&Layout::Bytes(8)).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Layout>(&json).unwrap(),
+            Layout::Bytes(8)
+        );
+    }
+
+    #[test]
+    fn test_map_round_trip() {
+        let mut symbols = Symbols::default();
+        let layout = Layout::Struct(crate::layout!({
+            "id": int,
+            "features": (map[scalar]),
+            "tags": (?(map[(?symbol)]))
+        }));
+        assert_eq!(
+            layout.to_string(),
+            "{ id: int, features: map[scalar], tags: ?map[?symbol] }"
+        );
+        assert_eq!(layout.size().in_slots(), 6);
+        assert!(layout.contains_map());
+        assert!(!Layout::Scalar.contains_map());
+
+        let round_trip = |value: serde_json::Value, symbols: &mut Symbols| {
+            let encoded = layout.encode(&value, symbols).unwrap();
+            validate(&layout, symbols, &encoded).unwrap();
+            let mut streamed = Visitor::new(layout.size());
+            crate::layout::encode_json(&value.to_string(), &layout, symbols, &mut streamed)
+                .unwrap();
+            assert_eq!(streamed.buffer(), &encoded[..]);
+            let mut written = vec![];
+            crate::layout::write_json(&layout, symbols, &visitor(encoded.clone()), &mut written)
+                .unwrap();
+            let decoded = serde_json::Value::build(&layout, symbols, &mut visitor(encoded.clone()));
+            assert_eq!(
+                serde_json::from_slice::<serde_json::Value>(&written).unwrap(),
+                decoded
+            );
+            (encoded.len(), decoded)
+        };
+
+        let empty = serde_json::json!({ "id": 1, "features": {}, "tags": null });
+        assert_eq!(round_trip(empty.clone(), &mut symbols), (6 * 8, empty));
+
+        let one = serde_json::json!({ "id": 2, "features": { "a": 1.5 }, "tags": {} });
+        assert_eq!(round_trip(one.clone(), &mut symbols), ((6 + 2) * 8, one));
+
+        let features = (0..100)
+            .map(|i| (format!("f{i}"), serde_json::json!(i as f64 / 2.0)))
+            .collect::<serde_json::Map<_, _>>();
+        let many = serde_json::json!({
+            "id": 3,
+            "features": features,
+            "tags": { "x": "a", "y": null, "z": "b" },
+        });
+        assert_eq!(
+            round_trip(many.clone(), &mut symbols),
+            ((6 + 100 * 2 + 3 * 3) * 8, many)
+        );
+
+        // Rust maps are encoded and decoded just the same.
+        let layout = crate::layout!(map[int]);
+        let btree = (0..10)
+            .map(|i| (format!("k{i}"), i))
+            .collect::<BTreeMap<_, _>>();
+        let encoded = layout.encode(&btree, &mut symbols).unwrap();
+        assert_eq!(encoded.len(), (2 + 10 * 2) * 8);
+        let decoded = BTreeMap::<String, i64>::build(&layout, &symbols, &mut visitor(encoded));
+        assert_eq!(decoded, btree);
+        let hash = std::collections::HashMap::from([("k".to_string(), 7)]);
+        let encoded = layout.encode(&hash, &mut symbols).unwrap();
+        let decoded = HashMap::<String, i64>::build(&layout, &symbols, &mut visitor(encoded));
+        assert_eq!(decoded, HashMap::from([("k".to_string(), 7)]));
+
+        // Repeated keys in JSON text keep the last value.
+        let mut streamed = Visitor::new(layout.size());
+        crate::layout::encode_json(
+            r#"{"a": 1, "b": 2, "a": 3}"#,
+            &layout,
+            &mut symbols,
+            &mut streamed,
+        )
+        .unwrap();
+        let decoded =
+            serde_json::Value::build(&layout, &symbols, &mut visitor(streamed.into_inner()));
+        assert_eq!(decoded, serde_json::json!({ "a": 3, "b": 2 }));
+
+        let nested = crate::layout!(map[(map[scalar])]);
+        let err = nested
+            .encode(&serde_json::json!({ "a": { "b": 1 } }), &mut symbols)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("map values cannot contain maps"), "{err}");
+        assert!(layout
+            .encode(&serde_json::json!([1]), &mut symbols)
+            .is_err());
+
+        let a = symbols.find("a") as i64;
+        validate(&layout, &symbols, &to_buffer(&[1, 2, a, 5])).unwrap();
+        validate(&layout, &symbols, &to_buffer(&[0, 1000])).unwrap();
+        let err = validate(&layout, &symbols, &to_buffer(&[2, 2, a, 5])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "decode error: map at . has 2 entries starting at slot 2, which are out of the \
+            buffer"
+        );
+        assert!(validate(&layout, &symbols, &to_buffer(&[1, 2, a + 100, 5])).is_err());
+        assert!(validate(&layout, &symbols, &to_buffer(&[-1, 2])).is_err());
+        assert!(validate(&Layout::Int, &symbols, &to_buffer(&[1, 2])).is_err());
+
+        assert!(crate::layout!(map[(?scalar)]).is_superset(&crate::layout!(map[scalar])));
+        assert!(!crate::layout!(map[scalar]).is_superset(&crate::layout!(map[int])));
+        assert_eq!(
+            layout.to_json_schema(),
+            serde_json::json!({ "type": "object", "additionalProperties": { "type": "integer" } })
+        );
+        let json = serde_json::to_string(&layout).unwrap();
+        assert_eq!(serde_json::from_str::<Layout>(&json).unwrap(), layout);
+    }
+}
@@ -1,3 +1,4 @@
+use base64::Engine;
 use std::collections::{BTreeMap, HashMap};
 use std::error::Error as StdError;
 use std::rc::Rc;
@@ -66,6 +67,128 @@ impl_encode_container!(Box<T>);
 impl_encode_container!(Rc<T>);
 impl_encode_container!(Arc<T>);
 
+/// Encodes a piece of text as the index of one of the variants of a [`Layout::Enum`].
+pub(super) fn visit_enum(
+    variants: &[String],
+    value: &str,
+    visitor: &mut Visitor,
+) -> Result<(), String> {
+    let Some(index) = variants.iter().position(|variant| variant == value) else {
+        return Err(format!(
+            "{value:?} is not one of the enum variants {variants:?}"
+        ));
+    };
+    visitor.push_int(index as i64);
+    Ok(())
+}
+
+/// Encodes a sequence of bytes as a [`Layout::Bytes`] of the given size.
+pub(super) fn visit_bytes(
+    n_bytes: usize,
+    bytes: &[u8],
+    visitor: &mut Visitor,
+) -> Result<(), String> {
+    if bytes.len() != n_bytes {
+        return Err(format!(
+            "expected {n_bytes} bytes, got {} bytes",
+            bytes.len()
+        ));
+    }
+    visitor.push_bytes(bytes);
+    Ok(())
+}
+
+/// Encodes base64 text as a [`Layout::Bytes`] of the given size.
+pub(super) fn visit_base64(
+    n_bytes: usize,
+    text: &str,
+    visitor: &mut Visitor,
+) -> Result<(), String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .map_err(|err| format!("{text:?} is not valid base64: {err}"))?;
+    visit_bytes(n_bytes, &bytes, visitor)
+}
+
+/// Encodes a list of integers, each one between 0 and 255, as a [`Layout::Bytes`] of the
+/// given size.
+fn visit_byte_items<T: Encode<Err = Error>>(
+    n_bytes: usize,
+    items: &[T],
+    symbols: &mut dyn Sym,
+    visitor: &mut Visitor,
+) -> Result<(), Error> {
+    // Each item is encoded as an integer in the same one-slot scratch visitor.
+    let mut byte_visitor = Visitor::new(Layout::Int.size());
+    let bytes = items
+        .iter()
+        .map(|item| {
+            byte_visitor.reset();
+            item.visit(&Layout::Int, symbols, &mut byte_visitor)?;
+            byte_visitor.reset();
+            let byte = byte_visitor.pop_int();
+            u8::try_from(byte).map_err(|_| format!("{byte} is not a valid byte").into())
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(visit_bytes(n_bytes, &bytes, visitor)?)
+}
+
+/// Encodes an absent value of a [`Layout::Optional`] with the given inner layout.
+pub(super) fn visit_absent(inner: &Layout, visitor: &mut Visitor) {
+    visitor.push_int(0);
+    for _ in 0..inner.size().in_slots() {
+        visitor.push_int(0);
+    }
+}
+
+/// Encodes the entries of a [`Layout::Map`] whose values have the layout `value`,
+/// appending them to the end of the visitor and pushing the header of the map.
+pub(super) fn visit_map_entries<'a, T: 'a + ?Sized + Encode<Err = Error>>(
+    value: &Layout,
+    entries: impl IntoIterator<Item = (&'a str, &'a T)>,
+    symbols: &mut dyn Sym,
+    visitor: &mut Visitor,
+) -> Result<(), Error> {
+    if value.contains_map() {
+        return Err(format!("map values cannot contain maps, got {value}").into());
+    }
+
+    let entry_size = Layout::Symbol.size() + value.size();
+    let mut encoded = vec![];
+    let mut n_entries = 0;
+    for (key, item) in entries {
+        let mut entry = Visitor::new(entry_size);
+        entry.push_uint(symbols.find(key));
+        item.visit(value, symbols, &mut entry)?;
+        encoded.extend_from_slice(entry.buffer());
+        n_entries += 1;
+    }
+    visitor.push_map(n_entries, &encoded);
+
+    Ok(())
+}
+
+impl<T: Encode<Err = Error>> Encode for Option<T> {
+    type Err = T::Err;
+    fn visit(
+        &self,
+        layout: &Layout,
+        symbols: &mut dyn Sym,
+        visitor: &mut Visitor,
+    ) -> Result<(), T::Err> {
+        match (self, layout) {
+            (None, Layout::Optional(inner)) => visit_absent(inner, visitor),
+            (Some(value), Layout::Optional(inner)) => {
+                visitor.push_int(1);
+                value.visit(inner, symbols, visitor)?;
+            }
+            _ => return Err("expected optional".to_string().into()),
+        }
+
+        Ok(())
+    }
+}
+
 macro_rules! impl_encode_scalar {
     ($scalar:ty) => {
         impl Encode for $scalar {
@@ -87,16 +210,40 @@ macro_rules! impl_encode_scalar {
     };
 }
 
-impl_encode_scalar!(i8);
-impl_encode_scalar!(u8);
-impl_encode_scalar!(i16);
-impl_encode_scalar!(u16);
-impl_encode_scalar!(i32);
-impl_encode_scalar!(u32);
-impl_encode_scalar!(i64);
-impl_encode_scalar!(u64);
-impl_encode_scalar!(isize);
-impl_encode_scalar!(usize);
+macro_rules! impl_encode_int {
+    ($int:ty) => {
+        impl Encode for $int {
+            type Err = Error;
+            fn visit(
+                &self,
+                layout: &Layout,
+                _: &mut dyn Sym,
+                visitor: &mut Visitor,
+            ) -> Result<(), Error> {
+                match layout {
+                    Layout::Scalar => visitor.push(*self as f64),
+                    Layout::Int => visitor.push_int(i64::try_from(*self).map_err(|_| {
+                        format!("{self} cannot be represented as a 64 bit integer")
+                    })?),
+                    _ => return Err("expected scalar or int".to_string().into()),
+                }
+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_encode_int!(i8);
+impl_encode_int!(u8);
+impl_encode_int!(i16);
+impl_encode_int!(u16);
+impl_encode_int!(i32);
+impl_encode_int!(u32);
+impl_encode_int!(i64);
+impl_encode_int!(u64);
+impl_encode_int!(isize);
+impl_encode_int!(usize);
 impl_encode_scalar!(f64);
 impl_encode_scalar!(f32);
 
@@ -127,7 +274,8 @@ impl Encode for String {
                 let index = symbols.find(self);
                 visitor.push_int(index as i64);
             }
-            _ => return Err("expected symbol".to_string().into()),
+            Layout::Enum(variants) => visit_enum(variants, self, visitor)?,
+            _ => return Err("expected symbol or enum".to_string().into()),
         }
 
         Ok(())
@@ -147,13 +295,54 @@ impl Encode for str {
                 let index = symbols.find(self);
                 visitor.push_int(index as i64);
             }
-            _ => return Err("expected symbol".to_string().into()),
+            Layout::Enum(variants) => visit_enum(variants, self, visitor)?,
+            _ => return Err("expected symbol or enum".to_string().into()),
+        }
+
+        Ok(())
+    }
+}
+
+impl Encode for chrono::DateTime<chrono::Utc> {
+    type Err = Error;
+    fn visit(&self, layout: &Layout, _: &mut dyn Sym, visitor: &mut Visitor) -> Result<(), Error> {
+        match layout {
+            Layout::DateTime(_) => {
+                visitor.push_int(utils::Timestamp::from(*self).into());
+            }
+            _ => return Err(format!("expected datetime layout, got {layout}").into()),
         }
 
         Ok(())
     }
 }
 
+impl Encode for chrono::NaiveDateTime {
+    type Err = Error;
+    fn visit(
+        &self,
+        layout: &Layout,
+        symbols: &mut dyn Sym,
+        visitor: &mut Visitor,
+    ) -> Result<(), Error> {
+        self.and_utc().visit(layout, symbols, visitor)
+    }
+}
+
+/// Dates are encoded as the instant of their midnight in UTC.
+impl Encode for chrono::NaiveDate {
+    type Err = Error;
+    fn visit(
+        &self,
+        layout: &Layout,
+        symbols: &mut dyn Sym,
+        visitor: &mut Visitor,
+    ) -> Result<(), Error> {
+        self.and_time(chrono::NaiveTime::MIN)
+            .visit(layout, symbols, visitor)
+    }
+}
+
 impl<T: Encode<Err = Error>> Encode for [T] {
     type Err = T::Err;
     fn visit(
@@ -175,7 +364,8 @@ impl<T: Encode<Err = Error>> Encode for [T] {
                     item.visit(element, symbols, visitor)?;
                 }
             }
-            _ => return Err("expected list".to_string().into()),
+            Layout::Bytes(n_bytes) => visit_byte_items(*n_bytes, self, symbols, visitor)?,
+            _ => return Err("expected list or bytes".to_string().into()),
         }
 
         Ok(())
@@ -203,7 +393,8 @@ impl<T: Encode<Err = Error>> Encode for Vec<T> {
                     item.visit(element, symbols, visitor)?;
                 }
             }
-            _ => return Err("expected list".to_string().into()),
+            Layout::Bytes(n_bytes) => visit_byte_items(*n_bytes, self, symbols, visitor)?,
+            _ => return Err("expected list or bytes".to_string().into()),
         }
 
         Ok(())
@@ -227,7 +418,13 @@ impl<T: Encode<Err = Error>> Encode for HashMap<String, T> {
                     value.visit(field, symbols, visitor)?;
                 }
             }
-            _ => return Err("expected struct".to_string().into()),
+            Layout::Map(value) => visit_map_entries(
+                value,
+                self.iter().map(|(key, item)| (key.as_str(), item)),
+                symbols,
+                visitor,
+            )?,
+            _ => return Err("expected struct or map".to_string().into()),
         }
 
         Ok(())
@@ -251,7 +448,13 @@ impl<T: Encode<Err = Error>> Encode for BTreeMap<String, T> {
                     value.visit(field, symbols, visitor)?;
                 }
             }
-            _ => return Err("expected struct".to_string().into()),
+            Layout::Map(value) => visit_map_entries(
+                value,
+                self.iter().map(|(key, item)| (key.as_str(), item)),
+                symbols,
+                visitor,
+            )?,
+            _ => return Err("expected struct or map".to_string().into()),
         }
 
         Ok(())
@@ -309,6 +512,11 @@ impl Encode for serde_json::Value {
     ) -> Result<(), Error> {
         match (self, layout) {
             (Self::Null, Layout::Unit) => {}
+            (Self::Null, Layout::Optional(inner)) => visit_absent(inner, visitor),
+            (value, Layout::Optional(inner)) => {
+                visitor.push_int(1);
+                value.visit(inner, symbols, visitor)?;
+            }
             (Self::Bool(b), Layout::Bool) => {
                 visitor.push_int(*b as i64);
             }
@@ -325,6 +533,10 @@ impl Encode for serde_json::Value {
                     )
                 }
             }
+            (Self::Number(num), Layout::Int) => visitor.push_int(
+                num.as_i64()
+                    .ok_or_else(|| format!("{num} cannot be represented as 64 bit integer"))?,
+            ),
             (Self::String(num), Layout::Scalar) if num.parse::<f64>().is_ok() => visitor.push(
                 num.parse::<f64>()
                     .expect("can't fail because precondition was checked"),
@@ -341,6 +553,11 @@ impl Encode for serde_json::Value {
                 let index = symbols.find(e);
                 visitor.push_int(index as i64);
             }
+            (Self::String(e), Layout::Enum(variants)) => visit_enum(variants, e, visitor)?,
+            (Self::String(text), Layout::Bytes(n_bytes)) => visit_base64(*n_bytes, text, visitor)?,
+            (Self::Array(array), Layout::Bytes(n_bytes)) => {
+                visit_byte_items(*n_bytes, array, symbols, visitor)?
+            }
             (Self::Array(array), Layout::List(element, size)) => {
                 if array.len() != *size {
                     return Err(format!(
@@ -353,12 +570,21 @@ impl Encode for serde_json::Value {
                     item.visit(element, symbols, visitor)?;
                 }
             }
+            (Self::Object(map), Layout::Map(value)) => visit_map_entries(
+                value,
+                map.iter().map(|(key, item)| (key.as_str(), item)),
+                symbols,
+                visitor,
+            )?,
             (Self::Object(map), Layout::Struct(fields)) => {
                 for (name, field) in &fields.0 {
-                    let Some(value) = map.get(name) else {
-                        return Err(format!("missing field {name:?} in {self:?}").into());
-                    };
-                    value.visit(field, symbols, visitor)?;
+                    match (map.get(name), field) {
+                        (Some(value), _) => value.visit(field, symbols, visitor)?,
+                        (None, Layout::Optional(inner)) => visit_absent(inner, visitor),
+                        (None, _) => {
+                            return Err(format!("missing field {name:?} in {self:?}").into())
+                        }
+                    }
                 }
             }
             _ => return Err(format!("incompatible layout {layout} for {self:?}").into()),
@@ -0,0 +1,442 @@
+//! Encoding of JSON text straight into the input of a function and decoding of the
+//! output of a function straight into JSON text, without going through
+//! [`serde_json::Value`]. These follow the same rules as the [`Encode`](super::Encode)
+//! and [`Decode`](super::Decode) implementations for [`serde_json::Value`].
+
+use base64::Engine;
+use byte_slice_cast::*;
+use serde::de::{self, DeserializeSeed, IgnoredAny};
+use serde::ser::{self, SerializeMap, SerializeSeq};
+use std::cell::Cell;
+use std::collections::hash_map::{Entry, HashMap};
+use std::fmt;
+use std::io::Write;
+
+use crate::{utils, Error};
+
+use super::encode::{visit_absent, visit_base64, visit_bytes, visit_enum};
+use super::symbols::Sym;
+use super::{Layout, Visitor};
+
+/// Encodes the JSON text `input` into the visitor, according to the given layout.
+pub(crate) fn encode_json(
+    input: &str,
+    layout: &Layout,
+    symbols: &mut dyn Sym,
+    visitor: &mut Visitor,
+) -> Result<(), serde_json::Error> {
+    let mut deserializer = serde_json::Deserializer::from_str(input);
+    LayoutSeed {
+        layout,
+        symbols,
+        visitor,
+    }
+    .deserialize(&mut deserializer)?;
+    deserializer.end()
+}
+
+/// Writes the content of the visitor as JSON text, according to the given layout.
+pub(crate) fn write_json<W: Write>(
+    layout: &Layout,
+    symbols: &dyn Sym,
+    visitor: &Visitor,
+    out: W,
+) -> Result<(), Error> {
+    let slots = visitor
+        .buffer()
+        .as_slice_of::<u64>()
+        .map_err(|err| err.to_string())?;
+    let value = LayoutValue {
+        layout,
+        symbols,
+        slots,
+        position: &Cell::new(0),
+    };
+    serde::Serialize::serialize(&value, &mut serde_json::Serializer::new(out))?;
+
+    Ok(())
+}
+
+/// Pushes the value being deserialized into the visitor, checking it against a layout.
+struct LayoutSeed<'a> {
+    layout: &'a Layout,
+    symbols: &'a mut dyn Sym,
+    visitor: &'a mut Visitor,
+}
+
+impl<'a> LayoutSeed<'a> {
+    fn with<'b>(&'b mut self, layout: &'b Layout) -> LayoutSeed<'b> {
+        LayoutSeed {
+            layout,
+            symbols: &mut *self.symbols,
+            visitor: &mut *self.visitor,
+        }
+    }
+
+    /// Marks the optionals wrapping the layout as present, since the value is not null.
+    fn present(mut self) -> Self {
+        while let Layout::Optional(inner) = self.layout {
+            self.visitor.push_int(1);
+            self.layout = inner;
+        }
+        self
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for LayoutSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> de::Visitor<'de> for LayoutSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a value of layout {}", self.layout)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        match self.layout {
+            Layout::Unit => Ok(()),
+            Layout::Optional(inner) => {
+                visit_absent(inner, self.visitor);
+                Ok(())
+            }
+            _ => Err(E::invalid_type(de::Unexpected::Unit, &self)),
+        }
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<(), E> {
+        let this = self.present();
+        match this.layout {
+            Layout::Bool => this.visitor.push_int(v as i64),
+            _ => return Err(E::invalid_type(de::Unexpected::Bool(v), &this)),
+        }
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<(), E> {
+        let this = self.present();
+        match this.layout {
+            Layout::Scalar => this.visitor.push(v as f64),
+            Layout::Int => this.visitor.push_int(v),
+            _ => return Err(E::invalid_type(de::Unexpected::Signed(v), &this)),
+        }
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<(), E> {
+        let this = self.present();
+        match this.layout {
+            Layout::Scalar => this.visitor.push(v as f64),
+            Layout::Int => {
+                this.visitor.push_int(i64::try_from(v).map_err(|_| {
+                    E::custom(format!("{v} cannot be represented as 64 bit integer"))
+                })?)
+            }
+            _ => return Err(E::invalid_type(de::Unexpected::Unsigned(v), &this)),
+        }
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<(), E> {
+        let this = self.present();
+        match this.layout {
+            Layout::Scalar => this.visitor.push(v),
+            Layout::Int => {
+                return Err(E::custom(format!(
+                    "{v} cannot be represented as 64 bit integer"
+                )))
+            }
+            _ => return Err(E::invalid_type(de::Unexpected::Float(v), &this)),
+        }
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<(), E> {
+        let this = self.present();
+        match this.layout {
+            Layout::Scalar => match v.parse::<f64>() {
+                Ok(num) => this.visitor.push(num),
+                Err(_) => return Err(E::invalid_value(de::Unexpected::Str(v), &this)),
+            },
+            Layout::DateTime(format) => {
+                let datetime = utils::parse_datetime(v, format).map_err(E::custom)?;
+                this.visitor
+                    .push_int(utils::Timestamp::from(datetime).into());
+            }
+            Layout::Symbol => {
+                let id = this.symbols.find(v);
+                this.visitor.push_int(id as i64);
+            }
+            Layout::Enum(variants) => visit_enum(variants, v, this.visitor).map_err(E::custom)?,
+            Layout::Bytes(n_bytes) => visit_base64(*n_bytes, v, this.visitor).map_err(E::custom)?,
+            _ => return Err(E::invalid_type(de::Unexpected::Str(v), &this)),
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut this = self.present();
+        if let Layout::Bytes(n_bytes) = this.layout {
+            let mut bytes = Vec::with_capacity(*n_bytes);
+            while let Some(byte) = seq.next_element::<u8>()? {
+                bytes.push(byte);
+            }
+            return visit_bytes(*n_bytes, &bytes, this.visitor).map_err(de::Error::custom);
+        }
+        let Layout::List(element, size) = this.layout else {
+            return Err(de::Error::invalid_type(de::Unexpected::Seq, &this));
+        };
+
+        let mut len = 0;
+        while len < *size {
+            if seq.next_element_seed(this.with(element))?.is_none() {
+                break;
+            }
+            len += 1;
+        }
+        // Extra elements are only counted, since there is no room for them.
+        while seq.next_element::<IgnoredAny>()?.is_some() {
+            len += 1;
+        }
+
+        if len != *size {
+            return Err(de::Error::custom(format!(
+                "expected array of size {size}, got array of size {len}"
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut this = self.present();
+        if let Layout::Map(value) = this.layout {
+            return visit_map_entries(value, map, this.symbols, this.visitor);
+        }
+        let Layout::Struct(fields) = this.layout else {
+            return Err(de::Error::invalid_type(de::Unexpected::Map, &this));
+        };
+
+        // Keys may come in any order, so each field is written at its own offset.
+        let start = this.visitor.position();
+        let mut offsets = Vec::with_capacity(fields.0.len());
+        let mut offset = start;
+        for (_, field) in &fields.0 {
+            offsets.push(offset);
+            offset += field.size().in_slots();
+        }
+
+        let mut is_present = vec![false; fields.0.len()];
+        while let Some(key) = map.next_key::<std::borrow::Cow<str>>()? {
+            let Some(field_id) = fields.0.iter().position(|(name, _)| *name == key) else {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            };
+            this.visitor.seek(offsets[field_id]);
+            map.next_value_seed(this.with(&fields.0[field_id].1))?;
+            is_present[field_id] = true;
+        }
+
+        for (field_id, (name, field)) in fields.0.iter().enumerate() {
+            match (is_present[field_id], field) {
+                (true, _) => {}
+                (false, Layout::Optional(inner)) => {
+                    this.visitor.seek(offsets[field_id]);
+                    visit_absent(inner, this.visitor);
+                }
+                (false, _) => return Err(de::Error::custom(format!("missing field {name:?}"))),
+            }
+        }
+
+        this.visitor.seek(offset);
+
+        Ok(())
+    }
+}
+
+/// Encodes the entries of a JSON object as a [`Layout::Map`] whose values have the layout
+/// `value`. If a key is repeated, the last value wins, like in [`serde_json::Value`].
+fn visit_map_entries<'de, A: de::MapAccess<'de>>(
+    value: &Layout,
+    mut map: A,
+    symbols: &mut dyn Sym,
+    visitor: &mut Visitor,
+) -> Result<(), A::Error> {
+    if value.contains_map() {
+        return Err(de::Error::custom(format!(
+            "map values cannot contain maps, got {value}"
+        )));
+    }
+
+    let entry_size = Layout::Symbol.size() + value.size();
+    let mut entries = Vec::<Visitor>::new();
+    let mut positions = HashMap::new();
+    while let Some(key) = map.next_key::<std::borrow::Cow<str>>()? {
+        let symbol = symbols.find(&key);
+        let mut entry = Visitor::new(entry_size);
+        entry.push_uint(symbol);
+        map.next_value_seed(LayoutSeed {
+            layout: value,
+            symbols: &mut *symbols,
+            visitor: &mut entry,
+        })?;
+
+        match positions.entry(symbol) {
+            Entry::Occupied(position) => entries[*position.get()] = entry,
+            Entry::Vacant(position) => {
+                position.insert(entries.len());
+                entries.push(entry);
+            }
+        }
+    }
+
+    let encoded = entries
+        .iter()
+        .flat_map(|entry| entry.buffer())
+        .copied()
+        .collect::<Vec<_>>();
+    visitor.push_map(entries.len(), &encoded);
+
+    Ok(())
+}
+
+/// A value laid out in binary data, serialized according to its layout.
+struct LayoutValue<'a> {
+    layout: &'a Layout,
+    symbols: &'a dyn Sym,
+    slots: &'a [u64],
+    position: &'a Cell<usize>,
+}
+
+impl LayoutValue<'_> {
+    fn with<'b>(&'b self, layout: &'b Layout) -> LayoutValue<'b> {
+        LayoutValue {
+            layout,
+            symbols: self.symbols,
+            slots: self.slots,
+            position: self.position,
+        }
+    }
+
+    fn pop(&self) -> u64 {
+        let position = self.position.get();
+        self.position.set(position + 1);
+        self.slots[position]
+    }
+}
+
+impl ser::Serialize for LayoutValue<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.layout {
+            Layout::Unit => serializer.serialize_unit(),
+            Layout::Scalar => serializer.serialize_f64(f64::from_bits(self.pop())),
+            Layout::Bool => serializer.serialize_bool(self.pop() != 0),
+            Layout::DateTime(format) => {
+                let datetime = chrono::DateTime::<chrono::Utc>::from(utils::Timestamp::from(
+                    self.pop() as i64,
+                ));
+                serializer.collect_str(&datetime.format(format))
+            }
+            Layout::Symbol => {
+                let id = self.pop();
+                let symbol = self
+                    .symbols
+                    .get(id)
+                    .ok_or_else(|| ser::Error::custom(format!("unknown symbol id {id}")))?;
+                serializer.serialize_str(symbol)
+            }
+            Layout::Enum(variants) => {
+                let index = self.pop();
+                let variant = variants
+                    .get(index as usize)
+                    .ok_or_else(|| ser::Error::custom(format!("unknown enum index {index}")))?;
+                serializer.serialize_str(variant)
+            }
+            Layout::Struct(fields) => {
+                // Fields are written in alphabetical order, like in `serde_json::Value`.
+                let start = self.position.get();
+                let mut offsets = Vec::with_capacity(fields.0.len());
+                let mut offset = start;
+                for (name, field) in &fields.0 {
+                    offsets.push((name, field, offset));
+                    offset += field.size().in_slots();
+                }
+                offsets.sort_by_key(|&(name, _, _)| name);
+
+                let mut map = serializer.serialize_map(Some(fields.0.len()))?;
+                for (name, field, offset) in offsets {
+                    self.position.set(offset);
+                    map.serialize_entry(name, &self.with(field))?;
+                }
+                self.position.set(offset);
+                map.end()
+            }
+            Layout::Tuple(fields) => {
+                let mut seq = serializer.serialize_seq(Some(fields.len()))?;
+                for field in fields {
+                    seq.serialize_element(&self.with(field))?;
+                }
+                seq.end()
+            }
+            Layout::List(element, size) => {
+                let mut seq = serializer.serialize_seq(Some(*size))?;
+                for _ in 0..*size {
+                    seq.serialize_element(&self.with(element))?;
+                }
+                seq.end()
+            }
+            Layout::Int => serializer.serialize_i64(self.pop() as i64),
+            Layout::Bytes(n_bytes) => {
+                let start = self.position.get();
+                let n_slots = n_bytes.div_ceil(8);
+                let bytes = self.slots[start..start + n_slots].as_byte_slice();
+                self.position.set(start + n_slots);
+                serializer.serialize_str(
+                    &base64::engine::general_purpose::STANDARD.encode(&bytes[..*n_bytes]),
+                )
+            }
+            Layout::Map(value) => {
+                let n_entries = self.pop() as usize;
+                let start = self.pop() as usize;
+                let end = self.position.get();
+                let entry_size = 1 + value.size().in_slots();
+
+                // Entries are also written in alphabetical order.
+                let mut entries = (0..n_entries)
+                    .map(|i| {
+                        let offset = start + i * entry_size;
+                        let id = self.slots[offset];
+                        let key = self
+                            .symbols
+                            .get(id)
+                            .ok_or_else(|| ser::Error::custom(format!("unknown symbol id {id}")))?;
+                        Ok((key, offset + 1))
+                    })
+                    .collect::<Result<Vec<_>, S::Error>>()?;
+                entries.sort_by_key(|&(key, _)| key);
+
+                let mut map = serializer.serialize_map(Some(n_entries))?;
+                for (key, offset) in entries {
+                    self.position.set(offset);
+                    map.serialize_entry(key, &self.with(value))?;
+                }
+                self.position.set(end);
+                map.end()
+            }
+            Layout::Optional(inner) => {
+                if self.pop() != 0 {
+                    serializer.serialize_some(&self.with(inner))
+                } else {
+                    self.position
+                        .set(self.position.get() + inner.size().in_slots());
+                    serializer.serialize_none()
+                }
+            }
+        }
+    }
+}
@@ -3,16 +3,18 @@
 
 mod decode;
 mod encode;
+mod json;
 mod ref_value;
 mod symbols;
 mod visitor;
 
-pub use decode::{Decode, Decoder, ZeroDecoder};
+pub use decode::{validate, Decode, Decoder, OutputPath, SelectDecoder, ZeroDecoder};
 pub use encode::Encode;
 pub use ref_value::RefValue;
 pub use symbols::{symbol_hash, Sym, Symbols};
 pub use visitor::Visitor;
 
+pub(crate) use json::{encode_json, write_json};
 pub(crate) use symbols::SymbolsView;
 
 use get_size::GetSize;
@@ -65,6 +67,11 @@ impl Struct {
         self.0.iter().map(|(_, layout)| layout.size()).sum()
     }
 
+    /// The fields of this struct, in declaration order.
+    pub fn fields(&self) -> &[(String, Layout)] {
+        &self.0
+    }
+
     /// Inserts a new key-value field in this struct.
     pub fn insert(&mut self, name: String, field: Layout) {
         self.0.push((name, field))
@@ -78,6 +85,16 @@ impl Struct {
             .collect::<Vec<_>>()
     }
 
+    /// Whether any of the fields of this struct has a [`Layout::Map`] inside it.
+    pub fn contains_map(&self) -> bool {
+        self.0.iter().any(|(_, field)| field.contains_map())
+    }
+
+    /// Whether any of the fields of this struct contains a [`Layout::Enum`].
+    pub fn contains_enum(&self) -> bool {
+        self.0.iter().any(|(_, field)| field.contains_enum())
+    }
+
     /// Prints this struct in a pretty way (recursive part).
     fn pretty_recursive(&self, buf: &mut String, indent: &mut String) {
         *indent += "    ";
@@ -148,7 +165,7 @@ pub enum Layout {
     /// An empty value.
     #[default]
     Unit,
-    /// A floating point number. Jyafn does not support integers directly.
+    /// A floating point number.
     Scalar,
     /// A boolean. Can be either true or false. This is represented as u64 1 or 0
     /// respectively. All other values are invalid.
@@ -164,6 +181,34 @@ pub enum Layout {
     Tuple(Vec<Layout>),
     /// A layout repeated a given number of times.
     List(Box<Layout>, usize),
+    /// A signed 64-bit integer.
+    Int,
+    /// A value that may be absent. This is layed out as a boolean flag telling whether
+    /// the value is present, followed by the slots of the inner layout. When the value
+    /// is absent, the inner slots are all zeroed.
+    Optional(Box<Layout>),
+    /// One of a fixed set of pieces of text. This is represented as the index of the
+    /// text in the list of variants. Encoding text outside the set is an error.
+    Enum(Vec<String>),
+    /// A fixed number of opaque bytes, packed into as many slots as needed to hold
+    /// them. The padding at the end of the last slot is zeroed.
+    Bytes(usize),
+    /// A collection of any number of text keys, each one associated with a value of the
+    /// given layout. Keys are encoded as symbols, so the key set is only known when a
+    /// value is encoded.
+    ///
+    /// Since its size is not fixed, a map takes only two slots where it appears: the
+    /// number of entries and the position, in slots from the start of the buffer, of
+    /// the first entry. The entries themselves are laid out sequentially _after_ the
+    /// fixed-size part of the buffer, each one being the symbol of the key followed by
+    /// the slots of the value. This has some consequences:
+    /// 1. Maps can only be used in function inputs. They can't be returned from
+    ///    functions, pinned or used in mappings or in subgraphs.
+    /// 2. Maps are read-only: they can only be queried by key (see
+    ///    [`crate::Graph::map_get`]).
+    /// 3. The values of a map cannot contain maps themselves.
+    /// 4. The encoded input of a function with maps is longer than its input size.
+    Map(Box<Layout>),
 }
 
 impl From<Struct> for Layout {
@@ -196,6 +241,19 @@ impl Display for Layout {
                 write!(f, "[{size}]")
             }
             Layout::List(element, size) => write!(f, "[{element}; {size}]"),
+            Layout::Int => write!(f, "int"),
+            Layout::Optional(inner) => write!(f, "?{inner}"),
+            Layout::Enum(variants) => write!(
+                f,
+                "enum[{}]",
+                variants
+                    .iter()
+                    .map(|variant| format!("{variant:?}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Layout::Bytes(n_bytes) => write!(f, "bytes {n_bytes}"),
+            Layout::Map(value) => write!(f, "map[{value}]"),
         }
     }
 }
@@ -213,6 +271,11 @@ impl Layout {
             Layout::Struct(fields) => fields.size(),
             Layout::Tuple(fields) => fields.iter().map(Layout::size).sum(),
             Layout::List(element, size) => *size * element.size(),
+            Layout::Int => 1 * InSlots::UNIT,
+            Layout::Optional(inner) => 1 * InSlots::UNIT + inner.size(),
+            Layout::Enum(_) => 1 * InSlots::UNIT,
+            Layout::Bytes(n_bytes) => n_bytes.div_ceil(8) * InSlots::UNIT,
+            Layout::Map(_) => 2 * InSlots::UNIT,
         }
     }
 
@@ -232,6 +295,11 @@ impl Layout {
                 .take(*size)
                 .flatten()
                 .collect(),
+            Layout::Int => vec![Type::Int],
+            Layout::Optional(inner) => [Type::Bool].into_iter().chain(inner.slots()).collect(),
+            Layout::Enum(_) => vec![Type::Int],
+            Layout::Bytes(n_bytes) => vec![Type::Int; n_bytes.div_ceil(8)],
+            Layout::Map(_) => vec![Type::Int, Type::Int],
         }
     }
 
@@ -265,6 +333,18 @@ impl Layout {
                     .map(|_| element.build_ref_value_inner(it.by_ref()))
                     .collect::<Option<Vec<_>>>()?,
             ),
+            Layout::Int => RefValue::Int(it.next()?),
+            Layout::Optional(inner) => RefValue::Optional(
+                it.next()?,
+                Box::new(inner.build_ref_value_inner(it.by_ref())?),
+            ),
+            Layout::Enum(_) => RefValue::Int(it.next()?),
+            Layout::Bytes(n_bytes) => RefValue::List(
+                (0..n_bytes.div_ceil(8))
+                    .map(|_| Some(RefValue::Int(it.next()?)))
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+            Layout::Map(value) => RefValue::Map(it.next()?, it.next()?, (**value).clone()),
         })
     }
 
@@ -310,10 +390,156 @@ impl Layout {
             {
                 self_item.is_superset(other_item)
             }
+            (Layout::Optional(self_inner), Layout::Optional(other_inner)) => {
+                self_inner.is_superset(other_inner)
+            }
+            (Layout::Optional(self_inner), other) => self_inner.is_superset(other),
+            (Layout::Enum(self_variants), Layout::Enum(other_variants)) => {
+                self_variants.starts_with(other_variants)
+            }
+            (Layout::Map(self_value), Layout::Map(other_value)) => {
+                self_value.is_superset(other_value)
+            }
             _ => self == other,
         }
     }
 
+    /// Creates a [JSON Schema](https://json-schema.org/) describing the JSON values that
+    /// can be encoded to (or are decoded from) this layout. Datetimes are represented as
+    /// strings with a description of the expected format.
+    pub fn to_json_schema(&self) -> serde_json::Value {
+        match self {
+            Layout::Unit => serde_json::json!({ "type": "null" }),
+            Layout::Scalar => serde_json::json!({ "type": "number" }),
+            Layout::Bool => serde_json::json!({ "type": "boolean" }),
+            Layout::DateTime(format) if format == ISOFORMAT => serde_json::json!({
+                "type": "string",
+                "format": "date-time",
+                "description": format!("a datetime in the format {format:?}"),
+            }),
+            Layout::DateTime(format) => serde_json::json!({
+                "type": "string",
+                "description": format!("a datetime in the format {format:?}"),
+            }),
+            Layout::Symbol => serde_json::json!({ "type": "string" }),
+            Layout::Struct(fields) => serde_json::json!({
+                "type": "object",
+                "properties": fields
+                    .0
+                    .iter()
+                    .map(|(name, field)| (name.clone(), field.to_json_schema()))
+                    .collect::<serde_json::Map<_, _>>(),
+                "required": fields
+                    .0
+                    .iter()
+                    .filter(|(_, field)| !matches!(field, Layout::Optional(_)))
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            }),
+            Layout::Tuple(fields) => serde_json::json!({
+                "type": "array",
+                "prefixItems": fields.iter().map(Layout::to_json_schema).collect::<Vec<_>>(),
+                "items": false,
+                "minItems": fields.len(),
+                "maxItems": fields.len(),
+            }),
+            Layout::List(element, size) => serde_json::json!({
+                "type": "array",
+                "items": element.to_json_schema(),
+                "minItems": size,
+                "maxItems": size,
+            }),
+            Layout::Int => serde_json::json!({ "type": "integer" }),
+            Layout::Optional(inner) => serde_json::json!({
+                "anyOf": [inner.to_json_schema(), { "type": "null" }],
+            }),
+            Layout::Enum(variants) => serde_json::json!({ "type": "string", "enum": variants }),
+            Layout::Bytes(n_bytes) => serde_json::json!({
+                "anyOf": [
+                    {
+                        "type": "string",
+                        "contentEncoding": "base64",
+                        "description": format!("{n_bytes} bytes encoded in base64"),
+                    },
+                    {
+                        "type": "array",
+                        "items": { "type": "integer", "minimum": 0, "maximum": 255 },
+                        "minItems": n_bytes,
+                        "maxItems": n_bytes,
+                    },
+                ],
+            }),
+            Layout::Map(value) => serde_json::json!({
+                "type": "object",
+                "additionalProperties": value.to_json_schema(),
+            }),
+        }
+    }
+
+    /// Whether this layout has a [`Layout::Map`] anywhere inside it.
+    pub fn contains_map(&self) -> bool {
+        match self {
+            Layout::Map(_) => true,
+            Layout::Struct(fields) => fields.contains_map(),
+            Layout::Tuple(fields) => fields.iter().any(Layout::contains_map),
+            Layout::List(element, _) => element.contains_map(),
+            Layout::Optional(inner) => inner.contains_map(),
+            _ => false,
+        }
+    }
+
+    /// Whether this layout contains a [`Layout::Enum`].
+    pub fn contains_enum(&self) -> bool {
+        match self {
+            Layout::Enum(_) => true,
+            Layout::Struct(fields) => fields.contains_enum(),
+            Layout::Tuple(fields) => fields.iter().any(Layout::contains_enum),
+            Layout::List(element, _) => element.contains_enum(),
+            Layout::Optional(inner) => inner.contains_enum(),
+            Layout::Map(value) => value.contains_enum(),
+            _ => false,
+        }
+    }
+
+    /// Finds the headers of all the [`Layout::Map`]s inside this layout.
+    pub(crate) fn map_headers(&self) -> Vec<MapHeader> {
+        let mut headers = vec![];
+        self.collect_map_headers(0, &mut headers);
+        headers
+    }
+
+    fn collect_map_headers(&self, start: usize, headers: &mut Vec<MapHeader>) {
+        match self {
+            Layout::Map(value) => headers.push(MapHeader {
+                slot: start,
+                entry_size: 1 + value.size().in_slots(),
+            }),
+            Layout::Struct(fields) => {
+                let mut start = start;
+                for (_, field) in &fields.0 {
+                    field.collect_map_headers(start, headers);
+                    start += field.size().in_slots();
+                }
+            }
+            Layout::Tuple(fields) => {
+                let mut start = start;
+                for field in fields {
+                    field.collect_map_headers(start, headers);
+                    start += field.size().in_slots();
+                }
+            }
+            Layout::List(element, size) => {
+                for i in 0..*size {
+                    element.collect_map_headers(start + i * element.size().in_slots(), headers);
+                }
+            }
+            Layout::Optional(inner) => inner.collect_map_headers(start + 1, headers),
+            _ => {}
+        }
+    }
+
+    /// Encodes `msg` into a new buffer. Unless this layout contains maps, the buffer has
+    /// exactly the size of this layout.
     pub fn encode<E: Encode, S: Sym>(&self, msg: &E, symbols: &mut S) -> Result<Box<[u8]>, Error> {
         let mut visitor = Visitor::new(self.size());
         msg.visit(self, symbols, &mut visitor)
@@ -322,6 +548,47 @@ impl Layout {
     }
 }
 
+/// Where the header of a [`Layout::Map`] is inside of a buffer and how big its entries
+/// are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MapHeader {
+    /// The slot of the number of entries. The position of the first entry is in the next
+    /// slot.
+    pub(crate) slot: usize,
+    /// The size in slots of each entry, including the key.
+    pub(crate) entry_size: usize,
+}
+
+/// Checks that the entries of all the maps with the given `headers` are inside `buffer`,
+/// so that they can be safely read by a function.
+pub(crate) fn check_map_bounds(headers: &[MapHeader], buffer: &[u8]) -> Result<(), Error> {
+    let slot = |index: usize| {
+        buffer
+            .get(index * 8..(index + 1) * 8)
+            .map(|slot| u64::from_ne_bytes(slot.try_into().expect("slot has 8 bytes")))
+    };
+    let n_slots = buffer.len() / 8;
+
+    for header in headers {
+        let (Some(n_entries), Some(start)) = (slot(header.slot), slot(header.slot + 1)) else {
+            return Err(format!("map header at slot {} is out of bounds", header.slot).into());
+        };
+        let end = n_entries
+            .checked_mul(header.entry_size as u64)
+            .and_then(|size| size.checked_add(start));
+        if n_entries != 0 && end.is_none_or(|end| end > n_slots as u64) {
+            return Err(format!(
+                "map at slot {} has {n_entries} entries starting at slot {start}, but buffer \
+                has only {n_slots} slots",
+                header.slot
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
 /// Builds a [`Layout`] usng the jyafn layout display notation.
 ///
 /// # Usage
@@ -333,8 +600,31 @@ impl Layout {
 ///     y: datetime "%Y-%m-%d"
 /// })
 /// ```
+///
+/// Enums list their variants, as in `layout!(enum["a", "b"])`.
+///
+/// Optional layouts are prefixed by `?`, as in `layout!(?scalar)`. Inside structs and
+/// lists, they need to be wrapped in parentheses, as in `layout!({ x: (?scalar) })`.
+///
+/// Opaque binary blobs give their size in bytes, as in `layout!(bytes 32)`. Like optional
+/// layouts, they need to be wrapped in parentheses inside structs and lists.
+///
+/// Maps give the layout of their values, as in `layout!(map[scalar])`. They also need to
+/// be wrapped in parentheses inside structs and lists.
 #[macro_export]
 macro_rules! layout {
+    (?$inner:tt) => {
+        $crate::layout::Layout::Optional(Box::new($crate::layout!($inner)))
+    };
+    ((?$inner:tt)) => {
+        $crate::layout!(?$inner)
+    };
+    ((bytes $n_bytes:expr)) => {
+        $crate::layout!(bytes $n_bytes)
+    };
+    ((map[$value:tt])) => {
+        $crate::layout!(map[$value])
+    };
     ({$($key:literal : $ty:tt),*}) => {
         $crate::r#struct!($($key : $ty),*)
     };
@@ -347,6 +637,9 @@ macro_rules! layout {
     (scalar) => {
         $crate::layout::Layout::Scalar
     };
+    (int) => {
+        $crate::layout::Layout::Int
+    };
     (bool) => {
         $crate::layout::Layout::Bool
     };
@@ -359,6 +652,15 @@ macro_rules! layout {
     (symbol) => {
         $crate::layout::Layout::Symbol
     };
+    (bytes $n_bytes:expr) => {
+        $crate::layout::Layout::Bytes($n_bytes)
+    };
+    (map[$value:tt]) => {
+        $crate::layout::Layout::Map(Box::new($crate::layout!($value)))
+    };
+    (enum[$($variant:expr),*]) => {
+        $crate::layout::Layout::Enum(vec![$($variant.to_string()),*])
+    };
     ([$element:tt; $size:expr]) => {
         $crate::layout::Layout::List(Box::new($crate::layout!($element)), $size)
     }
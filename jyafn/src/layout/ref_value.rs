@@ -6,7 +6,7 @@ use crate::Ref;
 use super::{Layout, Struct, ISOFORMAT};
 
 /// A ref value represents jyafn [`Ref`]s in a structured way, similar to [`serde_json::Value`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RefValue {
     /// An empty value.
     Unit,
@@ -24,6 +24,15 @@ pub enum RefValue {
     Tuple(Vec<RefValue>),
     /// A list of values, all of the same layout.
     List(Vec<RefValue>),
+    /// An integer reference.
+    Int(Ref),
+    /// A value that may be absent, given by a boolean reference telling whether it is
+    /// present and the value itself.
+    Optional(Ref, Box<RefValue>),
+    /// A map from the input, given by the integer references of the number of entries
+    /// and of the slot of the first entry, and by the layout of the values. Maps cannot
+    /// be built in a graph and can only be read with [`crate::Graph::map_get`].
+    Map(Ref, Ref, Layout),
 }
 
 impl Display for RefValue {
@@ -55,6 +64,11 @@ impl Display for RefValue {
                 }
                 write!(f, "]")
             }
+            Self::Int(s) => write!(f, "int {s}"),
+            Self::Optional(present, value) => write!(f, "?({present}) {value}"),
+            Self::Map(n_entries, start, value) => {
+                write!(f, "map[{value}]({n_entries}, {start})")
+            }
         }
     }
 }
@@ -87,6 +101,9 @@ impl RefValue {
                     Layout::List(Box::new(Layout::Scalar), 0)
                 }
             }
+            Self::Int(_) => Layout::Int,
+            Self::Optional(_, value) => Layout::Optional(Box::new(value.putative_layout())),
+            Self::Map(_, _, value) => Layout::Map(Box::new(value.clone())),
         }
     }
 
@@ -106,9 +123,10 @@ impl RefValue {
             (Self::Bool(s), Layout::Bool) => buf.push(*s),
             (Self::DateTime(s), Layout::DateTime(_)) => buf.push(*s),
             (Self::Symbol(s), Layout::Symbol) => buf.push(*s),
+            (Self::Int(s), Layout::Int | Layout::Enum(_)) => buf.push(*s),
             (Self::Struct(vals), Layout::Struct(fields)) => {
                 for (name, field) in &fields.0 {
-                    vals.get(name)?.build_output_vec(field, buf);
+                    vals.get(name)?.build_output_vec(field, buf)?;
                 }
             }
             (Self::Tuple(vals), Layout::Tuple(fields)) => {
@@ -125,6 +143,29 @@ impl RefValue {
                     item.build_output_vec(element, buf)?;
                 }
             }
+            // Bytes are given as a list of the integer slots holding them.
+            (Self::List(list), Layout::Bytes(n_bytes)) if list.len() == n_bytes.div_ceil(8) => {
+                for item in list {
+                    let Self::Int(slot) = item else {
+                        return None;
+                    };
+                    buf.push(*slot);
+                }
+            }
+            (Self::Optional(present, value), Layout::Optional(inner)) => {
+                buf.push(*present);
+                value.build_output_vec(inner, buf)?;
+            }
+            // Optional values may also be given as a tuple of the presence flag and the
+            // value, which is how they are represented in languages without a native
+            // equivalent of this enum.
+            (Self::Tuple(vals), Layout::Optional(inner)) if vals.len() == 2 => {
+                let Self::Bool(present) = vals[0] else {
+                    return None;
+                };
+                buf.push(present);
+                vals[1].build_output_vec(inner, buf)?;
+            }
             _ => return None,
         }
 
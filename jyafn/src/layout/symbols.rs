@@ -81,9 +81,17 @@ impl Symbols {
         SymbolsView::new(self)
     }
 
+    /// All the symbols in this collection, ordered by their ids.
     pub fn as_vec(&self) -> Vec<String> {
         self.0.values().cloned().collect()
     }
+
+    /// Gets the id of a symbol, returning `None` if it is not in this collection. Unlike
+    /// [`Sym::find`], this never creates a new symbol.
+    pub fn lookup(&self, name: &str) -> Option<u64> {
+        let h = symbol_hash(name);
+        self.0.contains_key(&h).then_some(h)
+    }
 }
 
 /// A view on top of an already existing [`Symbols`]. This allows immutable access to the
@@ -4,27 +4,42 @@ use crate::size::Size;
 
 /// A builder of binary data to be sent to and from functions. This represents a sequence
 /// of slots of 64-bit data that can be grown by pushing more 64-bid data into it.
+///
+/// The buffer has a fixed-size part, which is where values are pushed to and popped
+/// from, followed by the entries of the maps in it, if any (see
+/// [`super::Layout::Map`]).
 #[derive(Debug, Clone)]
-pub struct Visitor(pub(crate) Box<[u8]>, isize);
+pub struct Visitor(pub(crate) Box<[u8]>, isize, usize);
 
 impl From<Box<[u8]>> for Visitor {
     fn from(value: Box<[u8]>) -> Self {
         let len = value.len();
-        Visitor(value, len as isize)
+        Visitor(value, len as isize, len)
     }
 }
 
 impl Visitor {
     pub fn new(size: Size) -> Visitor {
-        Visitor(vec![0; size.in_bytes()].into_boxed_slice(), 0)
+        Visitor(
+            vec![0; size.in_bytes()].into_boxed_slice(),
+            0,
+            size.in_bytes(),
+        )
     }
 
     pub fn into_inner(self) -> Box<[u8]> {
         self.0
     }
 
+    /// Moves the visitor back to the first slot. Any map entries pushed into the visitor
+    /// are dropped, so that the visitor can be reused for a new value.
     pub fn reset(&mut self) {
-        self.1 = 0
+        self.1 = 0;
+        if self.0.len() > self.2 {
+            let mut buffer = std::mem::take(&mut self.0).into_vec();
+            buffer.truncate(self.2);
+            self.0 = buffer.into_boxed_slice();
+        }
     }
 
     pub fn set_full(&mut self) {
@@ -45,10 +60,10 @@ impl Visitor {
         self.1 += 1;
     }
 
-    /// Removes a scalar value from the end of the visitor, shrinking it by 1 slot.
+    /// Reads the next scalar value from the visitor, advancing it by 1 slot.
     pub fn pop(&mut self) -> f64 {
         let top = self.0.as_mut_slice_of::<f64>().unwrap()[self.1 as usize];
-        self.1 -= 1;
+        self.1 += 1;
         top
     }
 
@@ -58,10 +73,10 @@ impl Visitor {
         self.1 += 1;
     }
 
-    /// Removes an integer value from the end of the visitor, shrinking it by 1 slot.
+    /// Reads the next integer value from the visitor, advancing it by 1 slot.
     pub fn pop_int(&mut self) -> i64 {
         let top = self.0.as_mut_slice_of::<i64>().unwrap()[self.1 as usize];
-        self.1 -= 1;
+        self.1 += 1;
         top
     }
 
@@ -71,10 +86,67 @@ impl Visitor {
         self.1 += 1;
     }
 
-    /// Removes an integer value from the end of the visitor, shrinking it by 1 slot.
+    /// Reads the next unsigned integer value from the visitor, advancing it by 1 slot.
     pub fn pop_uint(&mut self) -> u64 {
         let top = self.0.as_mut_slice_of::<u64>().unwrap()[self.1 as usize];
-        self.1 -= 1;
+        self.1 += 1;
         top
     }
+
+    /// Pushes a sequence of bytes into the visitor, advancing it by as many slots as
+    /// needed to hold them. The padding at the end of the last slot is zeroed.
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        let start = self.1 as usize * 8;
+        let n_slots = bytes.len().div_ceil(8);
+        let slots = &mut self.0[start..start + n_slots * 8];
+        slots[..bytes.len()].copy_from_slice(bytes);
+        slots[bytes.len()..].fill(0);
+        self.1 += n_slots as isize;
+    }
+
+    /// Reads the next `n_bytes` bytes from the visitor, advancing it by as many slots as
+    /// needed to hold them.
+    pub fn pop_bytes(&mut self, n_bytes: usize) -> &[u8] {
+        let start = self.1 as usize * 8;
+        self.1 += n_bytes.div_ceil(8) as isize;
+        &self.0[start..start + n_bytes]
+    }
+
+    /// Appends the given entries of a map to the end of the buffer and pushes the header
+    /// of the map, that is, the number of entries and the slot of the first entry.
+    pub fn push_map(&mut self, n_entries: usize, entries: &[u8]) {
+        let start = self.0.len() / 8;
+        if !entries.is_empty() {
+            let mut buffer = std::mem::take(&mut self.0).into_vec();
+            buffer.extend_from_slice(entries);
+            self.0 = buffer.into_boxed_slice();
+        }
+        self.push_uint(n_entries as u64);
+        self.push_uint(start as u64);
+    }
+
+    /// Reads the header of a map, returning the number of entries and the slot of the
+    /// first entry, advancing the visitor by 2 slots. Use [`Visitor::seek`] to read the
+    /// entries.
+    pub fn pop_map(&mut self) -> (usize, usize) {
+        let n_entries = self.pop_uint() as usize;
+        let start = self.pop_uint() as usize;
+        (n_entries, start)
+    }
+
+    /// Skips the next `n_slots` slots of the visitor without reading them.
+    pub fn skip(&mut self, n_slots: usize) {
+        self.1 += n_slots as isize;
+    }
+
+    /// The slot the visitor is currently at.
+    pub fn position(&self) -> usize {
+        self.1 as usize
+    }
+
+    /// Moves the visitor to the given slot, so that the next value is pushed to or read
+    /// from it.
+    pub fn seek(&mut self, slot: usize) {
+        self.1 = slot as isize;
+    }
 }
@@ -19,16 +19,19 @@ mod graph;
 
 #[cfg(feature = "map-reduce")]
 pub use dataset::Dataset;
-pub use function::{FnError, Function, FunctionData, RawFn};
-pub use graph::size;
-pub use graph::{Graph, IndexedList, Node, Ref, Type};
+pub use function::{FnError, Function, FunctionData, RaisedStatus, RawFn};
+pub use graph::size::{self, SizeBreakdown};
+pub use graph::{
+    CArtifact, Changed, CompileCache, CompileObserver, CompileOptions, CompileStage, Graph,
+    GraphDiff, IndexedList, InputConstraint, Node, NodeProfile, Norm, OptStats, Pruned, Ref, Type,
+    COMPILE_CACHE_ENV, DEFAULT_MAX_SUBGRAPH_DEPTH, DUMP_FORMAT_VERSION, INPUT_CONSTRAINTS_KEY,
+    MAX_SUBGRAPH_DEPTH_KEY, MEM_SIZE_ESTIMATE_KEY, OPT_STATS_KEY, PINNABLE_INPUTS_KEY, PRUNED_KEY,
+};
 pub use op::Op;
 pub use r#const::Const;
 
 use std::{
-    borrow::Cow,
     error::Error as StdError,
-    ffi::CStr,
     fmt::{self, Debug, Display},
     process::ExitStatus,
 };
@@ -54,9 +57,11 @@ pub enum Error {
     #[error("loader error: {0}")]
     Loader(#[from] libloading::Error),
     #[error("function raised status: {0:?}")]
-    StatusRaised(Cow<'static, CStr>),
+    StatusRaised(RaisedStatus),
     #[error("encode error: {0}")]
     EncodeError(Box<dyn StdError + Send + Sync>),
+    #[error("decode error: {0}")]
+    DecodeError(String),
     #[error("wrong layout: expected {expected}, got {got}")]
     WrongLayout {
         expected: layout::Layout,
@@ -67,12 +72,17 @@ pub enum Error {
         expected: layout::Layout,
         got: layout::RefValue,
     },
+    #[error("size mismatch: expected buffer of {expected} bytes, got {got} bytes")]
+    SizeMismatch { expected: usize, got: usize },
     #[error("bincode error: {0}")]
     Bincode(#[from] bincode::Error),
     #[error("json error: {0}")]
     Json(#[from] serde_json::Error),
     #[error("zip error: {0}")]
     Zip(#[from] zip::result::ZipError),
+    #[cfg(feature = "redis")]
+    #[error("redis error: {0}")]
+    Redis(#[from] redis::RedisError),
     #[error("{0}")]
     Other(String),
     #[error("{error}\n\n{context}")]
@@ -88,6 +98,18 @@ impl From<String> for Error {
     }
 }
 
+impl Error {
+    /// The index in [`Graph::errors`] of the error raised by a compiled function, if this
+    /// is such an error and it is one of the graph's.
+    pub fn error_id(&self) -> Option<usize> {
+        match self {
+            Error::StatusRaised(status) => status.error_id(),
+            Error::WithContext { error, .. } => error.error_id(),
+            _ => None,
+        }
+    }
+}
+
 /// An extension for `Result<T, Error>` providing a way to give context to errors.
 pub trait Context: Sized {
     /// Attaches a context returned by a closure to the error.
@@ -140,6 +162,7 @@ mod test {
     use super::layout::{Layout, RefValue};
     use super::*;
     use byte_slice_cast::*;
+    use std::collections::HashMap;
 
     fn create_simple_graph() -> Graph {
         let mut graph = Graph::new();
@@ -198,6 +221,180 @@ mod test {
         println!("fn({:?}) = {:?}", i, out.as_slice_of::<f64>().unwrap());
     }
 
+    #[test]
+    fn test_eval_strict_simple_graph() {
+        let func = create_simple_graph().compile().unwrap();
+        let out: f64 = func
+            .eval_strict(&serde_json::json!({ "a": 5.0, "b": 6.0 }))
+            .unwrap();
+        assert_eq!(out, 12.0);
+    }
+
+    #[test]
+    fn test_eval_checks_enum_output() {
+        let mut graph = Graph::new();
+        let RefValue::Int(i) = graph.input("i".to_string(), Layout::Int) else {
+            unreachable!()
+        };
+        graph
+            .output(RefValue::Int(i), layout!(enum["red", "green"]))
+            .unwrap();
+        let func = graph.compile().unwrap();
+
+        let out: serde_json::Value = func.eval(&serde_json::json!({ "i": 1 })).unwrap();
+        assert_eq!(out, serde_json::json!("green"));
+        for i in [2, -1] {
+            let input = serde_json::json!({ "i": i });
+            assert!(matches!(
+                func.eval::<_, serde_json::Value>(&input),
+                Err(crate::Error::DecodeError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_run_multiple_slot_output() {
+        let mut graph = Graph::new();
+        let RefValue::Scalar(a) = graph.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let b = graph.insert(op::Add, vec![a, 1.0.into()]).unwrap();
+        graph
+            .output(
+                RefValue::Tuple(vec![RefValue::Scalar(a), RefValue::Scalar(b)]),
+                Layout::Tuple(vec![Layout::Scalar, Layout::Scalar]),
+            )
+            .unwrap();
+        let func = graph.compile().unwrap();
+
+        let out: serde_json::Value = func.eval(&serde_json::json!({ "a": 2.0 })).unwrap();
+        assert_eq!(out, serde_json::json!([2.0, 3.0]));
+    }
+
+    #[test]
+    fn test_call_subgraph_multiple_slot_output() {
+        let mut sub = Graph::new();
+        let RefValue::Scalar(a) = sub.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let b = sub.insert(op::Add, vec![a, 1.0.into()]).unwrap();
+        sub.output(
+            RefValue::Tuple(vec![RefValue::Scalar(a), RefValue::Scalar(b)]),
+            Layout::Tuple(vec![Layout::Scalar, Layout::Scalar]),
+        )
+        .unwrap();
+
+        let mut g = Graph::new();
+        let a = g.input("a".to_string(), Layout::Scalar);
+        let graph_id = g.insert_subgraph(sub).unwrap();
+        let output = g
+            .call_graph(
+                graph_id,
+                RefValue::Struct(HashMap::from([("a".to_string(), a)])),
+            )
+            .unwrap();
+        g.output(output, Layout::Tuple(vec![Layout::Scalar, Layout::Scalar]))
+            .unwrap();
+        let func = g.compile().unwrap();
+
+        let out: serde_json::Value = func.eval(&serde_json::json!({ "a": 2.0 })).unwrap();
+        assert_eq!(out, serde_json::json!([2.0, 3.0]));
+    }
+
+    #[test]
+    fn test_output_vec_nested_struct_mismatch() {
+        let inner = layout::Struct(vec![("x".to_string(), Layout::Scalar)]);
+        let layout = Layout::Struct(layout::Struct(vec![(
+            "outer".to_string(),
+            Layout::Struct(inner),
+        )]));
+        let value = RefValue::Struct(HashMap::from([(
+            "outer".to_string(),
+            RefValue::Struct(HashMap::new()),
+        )]));
+        assert!(value.output_vec(&layout).is_none());
+    }
+
+    #[test]
+    fn test_run_not() {
+        let mut g = Graph::new();
+        let RefValue::Bool(a) = g.input("a".to_string(), Layout::Bool) else {
+            unreachable!()
+        };
+        let not_a = g.insert(op::Not, vec![a]).unwrap();
+        let not_false = g.insert(op::Not, vec![false.into()]).unwrap();
+        let not_true = g.insert(op::Not, vec![true.into()]).unwrap();
+        g.output(
+            RefValue::Tuple(vec![
+                RefValue::Bool(not_a),
+                RefValue::Bool(not_false),
+                RefValue::Bool(not_true),
+            ]),
+            Layout::Tuple(vec![Layout::Bool, Layout::Bool, Layout::Bool]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+        assert_eq!(func.opt_stats().unwrap().const_folded, 2);
+
+        let out: serde_json::Value = func.eval(&serde_json::json!({ "a": true })).unwrap();
+        assert_eq!(out, serde_json::json!([false, true, false]));
+    }
+
+    #[test]
+    fn test_prune_unused_subgraph_before_called_one() {
+        let mut g = Graph::new();
+        let a = g.input("a".to_string(), Layout::Scalar);
+        g.insert_subgraph(create_simple_graph()).unwrap();
+        let graph_id = g.insert_subgraph(create_abs_graph()).unwrap();
+        let output = g
+            .call_graph(
+                graph_id,
+                RefValue::Struct(HashMap::from([("a".to_string(), a)])),
+            )
+            .unwrap();
+        g.output(output, Layout::Scalar).unwrap();
+
+        let func = g.compile().unwrap();
+        let out: f64 = func.eval(&serde_json::json!({ "a": -2.0 })).unwrap();
+        assert_eq!(out, 2.0);
+    }
+
+    #[test]
+    fn test_run_choose_branch_used_by_output() {
+        let mut g = Graph::new();
+        let RefValue::Bool(c) = g.input("c".to_string(), Layout::Bool) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let doubled = g.insert(op::Mul, vec![x, 2.0.into()]).unwrap();
+        let shifted = g.insert(op::Add, vec![doubled, 1.0.into()]).unwrap();
+        let chosen = g.insert(op::Choose, vec![c, shifted, 0.0.into()]).unwrap();
+        let other = g.insert(op::Choose, vec![c, 0.0.into(), shifted]).unwrap();
+        g.output(
+            RefValue::Tuple(vec![
+                RefValue::Scalar(chosen),
+                RefValue::Scalar(other),
+                RefValue::Scalar(doubled),
+                RefValue::Scalar(shifted),
+            ]),
+            Layout::Tuple(vec![Layout::Scalar; 4]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+
+        // Outputs are computed even when the choices do not need them:
+        let out: serde_json::Value = func
+            .eval(&serde_json::json!({ "c": false, "x": 3.0 }))
+            .unwrap();
+        assert_eq!(out, serde_json::json!([0.0, 7.0, 6.0, 7.0]));
+        let out: serde_json::Value = func
+            .eval(&serde_json::json!({ "c": true, "x": 3.0 }))
+            .unwrap();
+        assert_eq!(out, serde_json::json!([7.0, 0.0, 6.0, 7.0]));
+    }
+
     fn create_pfunc_graph() -> Graph {
         let mut g = Graph::new();
         let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
@@ -268,4 +465,3723 @@ mod test {
 
         println!("abs({num}) = {abs}");
     }
+
+    fn create_datetime_graph() -> Graph {
+        let mut g = Graph::new();
+        let RefValue::DateTime(a) = g.input(
+            "a".to_string(),
+            Layout::DateTime(layout::ISOFORMAT.to_string()),
+        ) else {
+            unreachable!()
+        };
+        g.output(
+            RefValue::DateTime(a),
+            Layout::DateTime(layout::ISOFORMAT.to_string()),
+        )
+        .unwrap();
+
+        g
+    }
+
+    #[test]
+    fn test_run_datetime_encode_decode() {
+        let graph = create_datetime_graph();
+        let func = graph.compile().unwrap();
+
+        let datetime = chrono::DateTime::from_timestamp_micros(1_712_345_678_123_456).unwrap();
+        let input = HashMap::from([("a".to_string(), datetime)]);
+        let output: chrono::DateTime<chrono::Utc> = func.eval(&input).unwrap();
+        assert_eq!(output, datetime);
+        assert_eq!(output.timestamp_micros(), 1_712_345_678_123_456);
+
+        let naive = datetime.naive_utc();
+        let input = HashMap::from([("a".to_string(), naive)]);
+        let output: chrono::NaiveDateTime = func.eval(&input).unwrap();
+        assert_eq!(output, naive);
+    }
+
+    #[test]
+    fn test_run_choose_input_symbols() {
+        let mut g = Graph::new();
+        let RefValue::Bool(flag) = g.input("flag".to_string(), Layout::Bool) else {
+            unreachable!()
+        };
+        let RefValue::Symbol(a) = g.input("a".to_string(), Layout::Symbol) else {
+            unreachable!()
+        };
+        let RefValue::Symbol(b) = g.input("b".to_string(), Layout::Symbol) else {
+            unreachable!()
+        };
+        g.push_symbol("known".to_string());
+        let chosen = g.insert(op::Choose, vec![flag, a, b]).unwrap();
+        g.output(RefValue::Symbol(chosen), Layout::Symbol).unwrap();
+        let func = g.compile().unwrap();
+
+        for (flag, expected) in [(true, "known"), (false, "only_at_runtime")] {
+            let input = serde_json::json!({ "flag": flag, "a": "known", "b": "only_at_runtime" });
+            let out: String = func.eval(&input).unwrap();
+            assert_eq!(out, expected);
+            let out: String = func.eval_strict(&input).unwrap();
+            assert_eq!(out, expected);
+        }
+    }
+
+    fn create_feature_hash_graph() -> Graph {
+        let mut g = Graph::new();
+        let x = g.input("x".to_string(), Layout::Scalar);
+        let s = g.input("s".to_string(), Layout::Symbol);
+        let bucket = g.feature_hash(RefValue::Tuple(vec![x, s]), 10, 42).unwrap();
+        g.output(RefValue::Scalar(bucket), Layout::Scalar).unwrap();
+
+        g
+    }
+
+    #[test]
+    fn test_run_feature_hash() {
+        let graph = create_feature_hash_graph();
+        let func = graph.compile().unwrap();
+        let mut counts = [0; 10];
+
+        for i in 0..10_000 {
+            let input = serde_json::json!({ "x": i, "s": "foo" });
+            let bucket: f64 = func.eval(&input).unwrap();
+            assert_eq!(bucket, func.eval::<_, f64>(&input).unwrap());
+            assert_eq!(bucket, bucket.trunc());
+            assert!((0.0..10.0).contains(&bucket));
+            counts[bucket as usize] += 1;
+        }
+
+        for count in counts {
+            assert!((800..1200).contains(&count), "{counts:?}");
+        }
+    }
+
+    #[test]
+    fn test_const_eval_feature_hash() {
+        let mut g = Graph::new();
+        let x = g.input("x".to_string(), Layout::Scalar);
+        let runtime = g.feature_hash(x, 10, 42).unwrap();
+        g.output(RefValue::Scalar(runtime), Layout::Scalar).unwrap();
+        let runtime: f64 = g
+            .compile()
+            .unwrap()
+            .eval(&serde_json::json!({ "x": 3.0 }))
+            .unwrap();
+
+        let mut g = Graph::new();
+        let three = g.r#const(3.0);
+        let r#const = g.feature_hash(RefValue::Scalar(three), 10, 42).unwrap();
+        g.output(RefValue::Scalar(r#const), Layout::Scalar).unwrap();
+        assert!(!g.render().unwrap().to_string().contains("call"));
+        let r#const: f64 = g.compile().unwrap().eval(&serde_json::json!({})).unwrap();
+
+        assert_eq!(runtime, r#const);
+    }
+
+    #[test]
+    fn test_compile_named() {
+        let simple = create_simple_graph().compile_named("simple").unwrap();
+        let abs = create_abs_graph().compile_named("my_abs").unwrap();
+        assert_eq!(simple.entry(), "simple");
+        assert_eq!(abs.entry(), "my_abs");
+        assert!(simple
+            .graph()
+            .render_named("simple")
+            .unwrap()
+            .to_string()
+            .contains("$simple("));
+
+        let out: f64 = simple
+            .eval(&serde_json::json!({ "a": 5.0, "b": 6.0 }))
+            .unwrap();
+        assert_eq!(out, 12.0);
+        let out: f64 = abs.eval(&serde_json::json!({ "a": -4.0 })).unwrap();
+        assert_eq!(out, 4.0);
+
+        assert!(create_simple_graph().compile_named("not a symbol").is_err());
+        assert!(create_simple_graph().compile_named("").is_err());
+    }
+
+    #[test]
+    fn test_source_survives_dump_and_load() {
+        let source = "def simple(a, b):\n    return a + b + 1.0\n";
+        let mut graph = create_simple_graph();
+        assert_eq!(graph.source(), None);
+        graph.set_source(source.to_string()).unwrap();
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        assert_eq!(
+            Graph::load_source(std::io::Cursor::new(dumped.get_ref())).unwrap(),
+            Some(source.to_string())
+        );
+
+        let dumped = dumped.into_inner();
+        let loaded = Graph::load(std::io::Cursor::new(&dumped)).unwrap();
+        assert_eq!(loaded.source(), Some(source));
+        assert_eq!(loaded.metadata(), graph.metadata());
+        assert_eq!(
+            loaded.render().unwrap().to_string(),
+            create_simple_graph().render().unwrap().to_string()
+        );
+
+        let out: f64 = loaded
+            .compile()
+            .unwrap()
+            .eval(&serde_json::json!({ "a": 5.0, "b": 6.0 }))
+            .unwrap();
+        assert_eq!(out, 12.0);
+
+        let too_big = "#".repeat(Graph::MAX_SOURCE_SIZE + 1);
+        assert!(graph.set_source(too_big.clone()).is_err());
+
+        // Crafted archives cannot sneak in larger sources.
+        let crafted = rewrite_archive(&dumped, "source.txt", Some(too_big.as_bytes()));
+        let err = Graph::load_source(std::io::Cursor::new(&crafted)).unwrap_err();
+        assert!(err.to_string().contains("larger than the maximum"), "{err}");
+        assert!(Graph::load(std::io::Cursor::new(&crafted)).is_err());
+    }
+
+    #[test]
+    fn test_run_out_of_range_datetime() {
+        let mut g = Graph::new();
+        let RefValue::DateTime(dt) = g.input(
+            "dt".to_string(),
+            Layout::DateTime(layout::ISOFORMAT.to_string()),
+        ) else {
+            unreachable!()
+        };
+        let year = g.insert(op::Call("year".to_string()), vec![dt]).unwrap();
+        g.output(RefValue::Scalar(year), Layout::Scalar).unwrap();
+        let func = g.compile().unwrap();
+
+        for extreme in [i64::MAX, i64::MIN] {
+            let out = func.eval_raw(&extreme.to_ne_bytes()[..]).unwrap();
+            assert!(out.as_slice_of::<f64>().unwrap()[0].is_nan());
+        }
+
+        let out = func.eval_raw(&0i64.to_ne_bytes()[..]).unwrap();
+        assert_eq!(out.as_slice_of::<f64>().unwrap()[0], 1970.0);
+
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let dt = g
+            .insert(op::Call("fromtimestamp".to_string()), vec![x])
+            .unwrap();
+        g.output(
+            RefValue::DateTime(dt),
+            Layout::DateTime(layout::ISOFORMAT.to_string()),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+
+        let output: chrono::DateTime<chrono::Utc> =
+            func.eval(&serde_json::json!({ "x": 1e300 })).unwrap();
+        assert_eq!(output, chrono::DateTime::<chrono::Utc>::MAX_UTC);
+        let output: chrono::DateTime<chrono::Utc> =
+            func.eval(&serde_json::json!({ "x": -1e300 })).unwrap();
+        assert_eq!(output, chrono::DateTime::<chrono::Utc>::MIN_UTC);
+    }
+
+    fn create_subgraph_chain(depth: usize) -> Result<Graph, super::Error> {
+        let mut graph = create_abs_graph();
+
+        for _ in 0..depth {
+            let mut g = Graph::new();
+            let a = g.input("a".to_string(), Layout::Scalar);
+            let graph_id = g.insert_subgraph(graph)?;
+            let output = g.call_graph(
+                graph_id,
+                RefValue::Struct(HashMap::from([("a".to_string(), a)])),
+            )?;
+            g.output(output, Layout::Scalar)?;
+            graph = g;
+        }
+
+        Ok(graph)
+    }
+
+    #[test]
+    fn test_subgraph_depth_limit() {
+        let max_depth = Graph::new().max_subgraph_depth().unwrap();
+        assert_eq!(max_depth, DEFAULT_MAX_SUBGRAPH_DEPTH);
+        let deepest = create_subgraph_chain(max_depth).unwrap();
+        assert_eq!(deepest.subgraph_depth(), max_depth);
+        let out: f64 = deepest
+            .compile()
+            .unwrap()
+            .eval(&serde_json::json!({ "a": -4.0 }))
+            .unwrap();
+        assert_eq!(out, 4.0);
+
+        assert!(Graph::new().insert_subgraph(deepest.clone()).is_err());
+        assert!(create_subgraph_chain(max_depth + 1).is_err());
+
+        // Graphs that bypass `insert_subgraph` (e.g., loaded ones) are checked on render.
+        let mut too_deep = Graph::new();
+        too_deep.subgraphs.push(deepest);
+        assert!(too_deep.render().is_err());
+    }
+
+    #[test]
+    fn test_configurable_subgraph_depth_limit() {
+        let chain = create_subgraph_chain(3).unwrap();
+
+        // Lowering the limit rejects graphs that were fine with the default:
+        let mut lowered = chain.clone();
+        lowered.set_max_subgraph_depth(2);
+        assert_eq!(lowered.max_subgraph_depth().unwrap(), 2);
+        assert!(lowered.verify().is_err());
+        assert!(lowered.compile().is_err());
+        let mut g = Graph::new();
+        g.set_max_subgraph_depth(3);
+        assert!(g.insert_subgraph(chain.clone()).is_err());
+        g.set_max_subgraph_depth(4);
+        assert!(g.insert_subgraph(chain.clone()).is_ok());
+
+        // The limit of a graph does not affect other graphs:
+        assert!(chain.compile().is_ok());
+        assert_eq!(
+            Graph::new().max_subgraph_depth().unwrap(),
+            DEFAULT_MAX_SUBGRAPH_DEPTH
+        );
+
+        // Raising the limit accepts graphs that were rejected with the default:
+        let deepest = create_subgraph_chain(DEFAULT_MAX_SUBGRAPH_DEPTH).unwrap();
+        let mut g = Graph::new();
+        assert!(g.insert_subgraph(deepest.clone()).is_err());
+        g.set_max_subgraph_depth(DEFAULT_MAX_SUBGRAPH_DEPTH + 1);
+        let graph_id = g.insert_subgraph(deepest).unwrap();
+        let a = g.input("a".to_string(), Layout::Scalar);
+        let output = g
+            .call_graph(
+                graph_id,
+                RefValue::Struct(HashMap::from([("a".to_string(), a)])),
+            )
+            .unwrap();
+        g.output(output, Layout::Scalar).unwrap();
+        assert_eq!(g.subgraph_depth(), DEFAULT_MAX_SUBGRAPH_DEPTH + 1);
+        assert!(g.verify().is_ok());
+
+        // The limit is kept when the graph is serialized:
+        let mut dumped = std::io::Cursor::new(vec![]);
+        g.dump(&mut dumped).unwrap();
+        dumped.set_position(0);
+        let loaded = Graph::load(dumped).unwrap();
+        assert_eq!(
+            loaded.max_subgraph_depth().unwrap(),
+            DEFAULT_MAX_SUBGRAPH_DEPTH + 1
+        );
+    }
+
+    #[test]
+    fn test_compile_with_debug_info() {
+        let graph = create_subgraph_chain(2).unwrap();
+        assert!(graph.compile().unwrap().shared_object_path().is_none());
+
+        let func = graph
+            .compile_with(
+                "run",
+                &CompileOptions {
+                    debug_info: true,
+                    ..CompileOptions::default()
+                },
+            )
+            .unwrap();
+        let path = func.shared_object_path().unwrap().to_owned();
+        let nm = std::process::Command::new("nm")
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(nm.status.success());
+        let symbols = String::from_utf8_lossy(&nm.stdout);
+        for symbol in ["run", "run.graph.0", "run.graph.0.graph.0"] {
+            assert!(
+                symbols
+                    .lines()
+                    .any(|line| line.ends_with(&format!(" {symbol}"))),
+                "symbol {symbol:?} not found in:\n{symbols}"
+            );
+        }
+
+        let out: f64 = func.eval(&serde_json::json!({ "a": -4.0 })).unwrap();
+        assert_eq!(out, 4.0);
+
+        drop(func);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_render_annotated() {
+        let graph = create_abs_graph();
+        let annotated = graph.render_annotated().unwrap();
+        let abs_comment = annotated
+            .lines()
+            .position(|line| line.trim() == "# n0: Abs")
+            .unwrap_or_else(|| panic!("no comment for the abs node in:\n{annotated}"));
+        assert!(annotated
+            .lines()
+            .nth(abs_comment + 1)
+            .unwrap()
+            .contains("_n0 ="));
+
+        // Without the comments, this is the same IR.
+        let stripped = annotated
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+        assert_eq!(stripped, graph.render().unwrap().to_string());
+
+        // Subgraphs are annotated with their own nodes.
+        let chain = create_subgraph_chain(2).unwrap();
+        let annotated = chain.render_annotated().unwrap();
+        let functions = annotated
+            .split("function")
+            .filter(|function| function.contains("# n"))
+            .count();
+        assert_eq!(functions, 3, "{annotated}");
+    }
+
+    #[test]
+    fn test_pfunc_arity_error() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+
+        let err = g
+            .insert(op::Call("atan2".to_string()), vec![a])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("atan2 expects 2 args (scalar, scalar), got 1 arg (scalar)"),
+            "{err}"
+        );
+
+        let err = g
+            .insert(op::Call("no_such_pfunc".to_string()), vec![a])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("no pure function named"), "{err}");
+    }
+
+    fn create_norm_graph(norm: Norm) -> Graph {
+        let mut g = Graph::new();
+        let RefValue::List(xs) =
+            g.input("xs".to_string(), Layout::List(Box::new(Layout::Scalar), 3))
+        else {
+            unreachable!()
+        };
+        let xs = xs
+            .into_iter()
+            .map(|x| match x {
+                RefValue::Scalar(x) => x,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        let norm = g.norm(&xs, norm).unwrap();
+        g.output(RefValue::Scalar(norm), Layout::Scalar).unwrap();
+
+        g
+    }
+
+    #[test]
+    fn test_run_norm() {
+        let input = serde_json::json!({ "xs": [3.0, -4.0, 12.0] });
+        let l1: f64 = create_norm_graph(Norm::L1)
+            .compile()
+            .unwrap()
+            .eval(&input)
+            .unwrap();
+        let l2: f64 = create_norm_graph(Norm::L2)
+            .compile()
+            .unwrap()
+            .eval(&input)
+            .unwrap();
+        let inf: f64 = create_norm_graph(Norm::Inf)
+            .compile()
+            .unwrap()
+            .eval(&input)
+            .unwrap();
+        assert_eq!((l1, l2, inf), (19.0, 13.0, 12.0));
+
+        // NaNs propagate regardless of where they are in the vector:
+        for xs in [
+            [f64::NAN, 1.0, 2.0],
+            [1.0, f64::NAN, 2.0],
+            [1.0, 2.0, f64::NAN],
+        ] {
+            let out = create_norm_graph(Norm::Inf)
+                .compile()
+                .unwrap()
+                .eval_raw(xs.as_byte_slice())
+                .unwrap();
+            assert!(out.as_slice_of::<f64>().unwrap()[0].is_nan(), "{xs:?}");
+        }
+
+        // A naive sum of squares would overflow here:
+        let large = serde_json::json!({ "xs": [3e300, -4e300, 12e300] });
+        let l2: f64 = create_norm_graph(Norm::L2)
+            .compile()
+            .unwrap()
+            .eval(&large)
+            .unwrap();
+        assert!((l2 / 13e300 - 1.0).abs() < 1e-15, "{l2}");
+
+        let mut g = Graph::new();
+        let (a, b) = (g.r#const(3.0), g.r#const(4.0));
+        let hypot = g.insert(op::Call("hypot".to_string()), vec![a, b]).unwrap();
+        let pfunc = crate::pfunc::get("hypot").unwrap();
+        assert_eq!((pfunc.const_eval.0)(&[3.0, 4.0]), Some(5.0));
+        g.output(RefValue::Scalar(hypot), Layout::Scalar).unwrap();
+        assert!(!g.render().unwrap().to_string().contains("call"));
+        let out: f64 = g.compile().unwrap().eval(&serde_json::json!({})).unwrap();
+        assert_eq!(out, 5.0);
+    }
+
+    #[test]
+    fn test_run_input_constraints() {
+        let mut graph = create_simple_graph();
+        graph.constrain_input("a", Some(0.0), Some(10.0)).unwrap();
+        graph.constrain_input("b", None, Some(100.0)).unwrap();
+        assert!(graph.constrain_input("c", Some(0.0), None).is_err());
+        assert!(graph.constrain_input("a", Some(1.0), Some(0.0)).is_err());
+        assert!(graph.constrain_input("a", None, None).is_err());
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        dumped.set_position(0);
+        let constraints = Graph::load_metadata(&mut dumped).unwrap();
+        assert!(constraints.contains_key(INPUT_CONSTRAINTS_KEY));
+        let func = Graph::load(dumped).unwrap().compile().unwrap();
+
+        let out: f64 = func
+            .eval(&serde_json::json!({ "a": 5.0, "b": -60.0 }))
+            .unwrap();
+        assert_eq!(out, -54.0);
+
+        for (a, b, field) in [(11.0, 0.0, "a"), (f64::NAN, 0.0, "a"), (0.0, 101.0, "b")] {
+            let err = func
+                .eval_raw([a, b].as_byte_slice())
+                .unwrap_err()
+                .to_string();
+            assert!(
+                err.contains(&format!("input `{field}` is out of range")),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_coalesce() {
+        let mut g = Graph::new();
+        let mut candidates = vec![];
+        for name in ["a", "b", "c"] {
+            let RefValue::Bool(present) = g.input(format!("has_{name}"), Layout::Bool) else {
+                unreachable!()
+            };
+            let value = g.input(name.to_string(), Layout::Scalar);
+            candidates.push((present, value));
+        }
+        let default = RefValue::Scalar(g.r#const(-1.0));
+        let coalesced = g.coalesce(candidates, default).unwrap();
+        g.output(coalesced, Layout::Scalar).unwrap();
+        let func = g.compile().unwrap();
+
+        let eval = |has_a: bool, has_b: bool, has_c: bool| -> f64 {
+            func.eval(&serde_json::json!({
+                "has_a": has_a, "a": 1.0,
+                "has_b": has_b, "b": 2.0,
+                "has_c": has_c, "c": 3.0,
+            }))
+            .unwrap()
+        };
+        assert_eq!(eval(true, true, true), 1.0);
+        assert_eq!(eval(false, true, true), 2.0);
+        assert_eq!(eval(false, false, true), 3.0);
+        assert_eq!(eval(false, false, false), -1.0);
+
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let default = RefValue::Scalar(g.r#const(0.0));
+        assert!(g.coalesce(vec![(x, RefValue::Scalar(x))], default).is_err());
+    }
+
+    #[test]
+    fn test_compile_prunes_unused_mapping() {
+        let mut graph = create_simple_graph();
+        graph
+            .insert_mapping(
+                "unused".to_string(),
+                Layout::Scalar,
+                Layout::Scalar,
+                mapping::HashMapStorage,
+                (0..1_000).map(|i| Ok::<_, super::Error>((i as f64, 2.0 * i as f64))),
+            )
+            .unwrap();
+        let func = graph.compile().unwrap();
+
+        // The function keeps the nodes of the graph as they were built, without the mapping.
+        assert_eq!(func.graph().nodes(), graph.nodes());
+        assert!(!func.graph().mappings().contains_key("unused"));
+        let pruned: serde_json::Value =
+            serde_json::from_str(&func.graph().metadata()[PRUNED_KEY]).unwrap();
+        assert_eq!(pruned["mappings"], serde_json::json!(["unused"]));
+
+        let mut original = std::io::Cursor::new(vec![]);
+        graph.dump(&mut original).unwrap();
+        let mut compiled = std::io::Cursor::new(vec![]);
+        func.graph().dump(&mut compiled).unwrap();
+        assert!(compiled.into_inner().len() < original.into_inner().len());
+
+        let unpruned = create_simple_graph().compile().unwrap();
+        let input = serde_json::json!({"a": 2.0, "b": 3.0});
+        assert_eq!(
+            func.eval::<_, f64>(&input).unwrap(),
+            unpruned.eval::<_, f64>(&input).unwrap()
+        );
+        assert!(!unpruned.graph().metadata().contains_key(PRUNED_KEY));
+    }
+
+    #[test]
+    fn test_eval_select() {
+        /// Records the layouts it is asked to decode.
+        struct Recorder(Vec<Layout>);
+
+        impl layout::Decoder for Recorder {
+            type Target = serde_json::Value;
+            fn build(
+                &mut self,
+                layout: &Layout,
+                symbols: &dyn layout::Sym,
+                visitor: &mut layout::Visitor,
+            ) -> Self::Target {
+                self.0.push(layout.clone());
+                layout::Decode::build(layout, symbols, visitor)
+            }
+        }
+
+        let mut graph = Graph::new();
+        let RefValue::Scalar(x) = graph.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let one = graph.r#const(1.0);
+        let score = graph.insert(op::Add, vec![x, one]).unwrap();
+        let output_layout = Layout::Struct(layout::Struct(vec![
+            (
+                "embedding".to_string(),
+                Layout::List(Box::new(Layout::Scalar), 2),
+            ),
+            ("score".to_string(), Layout::Scalar),
+        ]));
+        graph
+            .output(
+                RefValue::Struct(HashMap::from([
+                    (
+                        "embedding".to_string(),
+                        RefValue::List(vec![RefValue::Scalar(x), RefValue::Scalar(one)]),
+                    ),
+                    ("score".to_string(), RefValue::Scalar(score)),
+                ])),
+                output_layout,
+            )
+            .unwrap();
+        let func = graph.compile().unwrap();
+        let input = serde_json::json!({"x": 2.0});
+
+        let selected: Vec<f64> = func
+            .eval_select(&input, &["score".into(), "embedding.1".into()])
+            .unwrap();
+        assert_eq!(selected, vec![3.0, 1.0]);
+
+        let mut recorder = Recorder(vec![]);
+        let select =
+            layout::SelectDecoder::new(func.output_layout(), &["score".into()], &mut recorder)
+                .unwrap();
+        let selected = func.eval_with_decoder(&input, select).unwrap();
+        assert_eq!(selected, vec![serde_json::json!(3.0)]);
+        assert_eq!(recorder.0, vec![Layout::Scalar]);
+
+        assert!(func
+            .eval_select::<_, f64>(&input, &["embedding.2".into()])
+            .is_err());
+        assert!(func
+            .eval_select::<_, f64>(&input, &["logits".into()])
+            .is_err());
+    }
+
+    #[test]
+    fn test_run_multi_output_pfunc() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let sincos = g.call_pfunc("sincos", vec![x]).unwrap();
+        assert_eq!(sincos.len(), 2);
+        let sin = g.insert(op::Call("sin".to_string()), vec![x]).unwrap();
+        let cos = g.insert(op::Call("cos".to_string()), vec![x]).unwrap();
+        let modf = g.call_pfunc("modf", vec![x]).unwrap();
+        let outputs = sincos
+            .into_iter()
+            .chain([sin, cos])
+            .chain(modf)
+            .map(RefValue::Scalar)
+            .collect();
+        g.output(
+            RefValue::Tuple(outputs),
+            Layout::Tuple(vec![Layout::Scalar; 6]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+
+        for x in [0.0f64, 1.25, -2.5] {
+            let (sin, cos, expected_sin, expected_cos, frac, int): (f64, f64, f64, f64, f64, f64) =
+                func.eval(&serde_json::json!({"x": x})).unwrap();
+            assert!((sin - expected_sin).abs() < 1e-12);
+            assert!((cos - expected_cos).abs() < 1e-12);
+            assert_eq!(frac, x.fract());
+            assert_eq!(int, x.trunc());
+        }
+
+        assert!(g.call_pfunc("no_such_pfunc", vec![x]).is_err());
+    }
+
+    #[test]
+    fn test_compile_opt_stats() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let two = g.r#const(2.0);
+        let three = g.r#const(3.0);
+        let five = g.insert(op::Add, vec![two, three]).unwrap();
+        let ten = g.insert(op::Mul, vec![five, two]).unwrap();
+        let out = g.insert(op::Add, vec![a, ten]).unwrap();
+        g.insert(op::Mul, vec![a, a]).unwrap();
+        g.output(RefValue::Scalar(out), Layout::Scalar).unwrap();
+        let func = g.compile().unwrap();
+
+        assert_eq!(
+            func.opt_stats().unwrap(),
+            OptStats {
+                nodes_before: 4,
+                const_folded: 2,
+                unreachable_removed: 3,
+                nodes_after: 1,
+                ..OptStats::default()
+            }
+        );
+        let mut optimized = g.clone();
+        optimized.prune_unused().unwrap();
+        assert_eq!(optimized.nodes().len(), 1);
+        let out: f64 = func.eval(&serde_json::json!({ "a": 1.0 })).unwrap();
+        assert_eq!(out, 11.0);
+    }
+
+    #[test]
+    fn test_pipe_graphs() {
+        let mut normalizer = Graph::new();
+        let RefValue::Scalar(x) = normalizer.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(w) = normalizer.input("w".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let ten = normalizer.r#const(10.0);
+        let z = normalizer.insert(op::Div, vec![x, ten]).unwrap();
+        normalizer
+            .output(
+                RefValue::Struct(HashMap::from([
+                    ("z".to_string(), RefValue::Scalar(z)),
+                    ("w".to_string(), RefValue::Scalar(w)),
+                    ("raw".to_string(), RefValue::Scalar(x)),
+                ])),
+                Layout::Struct(layout::Struct(vec![
+                    ("z".to_string(), Layout::Scalar),
+                    ("w".to_string(), Layout::Scalar),
+                    ("raw".to_string(), Layout::Scalar),
+                ])),
+            )
+            .unwrap();
+
+        let mut scorer = Graph::new();
+        let RefValue::Scalar(w) = scorer.input("w".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(z) = scorer.input("z".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let score = scorer.insert(op::Mul, vec![w, z]).unwrap();
+        scorer
+            .output(RefValue::Scalar(score), Layout::Scalar)
+            .unwrap();
+
+        let mut pipeline = Graph::new();
+        let input = RefValue::Struct(HashMap::from([
+            (
+                "x".to_string(),
+                pipeline.input("x".to_string(), Layout::Scalar),
+            ),
+            (
+                "w".to_string(),
+                pipeline.input("w".to_string(), Layout::Scalar),
+            ),
+        ]));
+        let normalized = pipeline.pipe(&normalizer, input).unwrap();
+        let score = pipeline.pipe(&scorer, normalized.clone()).unwrap();
+        pipeline.output(score, Layout::Scalar).unwrap();
+        let func = pipeline.compile().unwrap();
+
+        let out: f64 = func
+            .eval(&serde_json::json!({ "x": 5.0, "w": 3.0 }))
+            .unwrap();
+        assert_eq!(out, 1.5);
+
+        let RefValue::Struct(mut fields) = normalized else {
+            unreachable!()
+        };
+        fields.remove("w");
+        assert!(pipeline
+            .pipe(&scorer, RefValue::Struct(fields.clone()))
+            .is_err());
+        fields.insert("w".to_string(), RefValue::List(vec![]));
+        assert!(pipeline.pipe(&scorer, RefValue::Struct(fields)).is_err());
+    }
+
+    #[test]
+    fn test_run_index_modes() {
+        let compile = |mode: op::IndexMode| {
+            let mut g = Graph::new();
+            let RefValue::Scalar(i) = g.input("i".to_string(), Layout::Scalar) else {
+                unreachable!()
+            };
+            let list = g
+                .indexed_list(vec![10.0.into(), 20.0.into(), 30.0.into()])
+                .unwrap();
+            let element = list.get_with_mode(&mut g, i, mode).unwrap();
+            g.output(RefValue::Scalar(element), Layout::Scalar).unwrap();
+            g.compile().unwrap()
+        };
+        let eval = |func: &Function, i: f64| func.eval::<_, f64>(&serde_json::json!({ "i": i }));
+
+        let strict = compile(op::IndexMode::Strict);
+        assert_eq!(eval(&strict, 1.0).unwrap(), 20.0);
+        assert!(eval(&strict, -1.0).is_err());
+        assert!(eval(&strict, 3.0).is_err());
+
+        let negative = compile(op::IndexMode::NegativeWrap);
+        assert_eq!(eval(&negative, 0.0).unwrap(), 10.0);
+        assert_eq!(eval(&negative, -1.0).unwrap(), 30.0);
+        assert_eq!(eval(&negative, -3.0).unwrap(), 10.0);
+        assert!(eval(&negative, -4.0).is_err());
+        assert!(eval(&negative, 3.0).is_err());
+
+        let modulo = compile(op::IndexMode::Modulo);
+        assert_eq!(eval(&modulo, 2.0).unwrap(), 30.0);
+        assert_eq!(eval(&modulo, 4.0).unwrap(), 20.0);
+        assert_eq!(eval(&modulo, -1.0).unwrap(), 30.0);
+        assert_eq!(eval(&modulo, -7.0).unwrap(), 30.0);
+    }
+
+    #[test]
+    fn test_compile_cache_invalidates_changed_region() {
+        let mut scale = Graph::new();
+        let RefValue::Scalar(x) = scale.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let list = scale
+            .indexed_list(vec![2.5.into(), 3.5.into(), 4.5.into()])
+            .unwrap();
+        let factor = list.get(&mut scale, 1.0.into()).unwrap();
+        let scaled = scale.insert(op::Mul, vec![x, factor]).unwrap();
+        scale
+            .output(RefValue::Scalar(scaled), Layout::Scalar)
+            .unwrap();
+
+        let create = |offset: f64| {
+            let mut g = Graph::new();
+            let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+                unreachable!()
+            };
+            let graph_id = g.insert_subgraph(scale.clone()).unwrap();
+            let RefValue::Scalar(scaled) = g
+                .call_graph(
+                    graph_id,
+                    RefValue::Struct(HashMap::from([("x".to_string(), RefValue::Scalar(a))])),
+                )
+                .unwrap()
+            else {
+                unreachable!()
+            };
+            let list = g.indexed_list(vec![offset.into(), 0.5.into()]).unwrap();
+            let offset = list.get(&mut g, 0.0.into()).unwrap();
+            let out = g.insert(op::Add, vec![scaled, offset]).unwrap();
+            g.output(RefValue::Scalar(out), Layout::Scalar).unwrap();
+            g
+        };
+
+        let cache = std::sync::Arc::new(CompileCache::new());
+        let options = CompileOptions {
+            cache: Some(cache.clone()),
+            ..CompileOptions::default()
+        };
+        let eval = |g: &Graph| -> f64 {
+            g.compile_with("run", &options)
+                .unwrap()
+                .eval(&serde_json::json!({ "a": 2.0 }))
+                .unwrap()
+        };
+
+        assert_eq!(eval(&create(1.0)), 8.0);
+        assert_eq!((cache.hits(), cache.misses()), (0, 2));
+
+        // Only the region of the main graph changes:
+        assert_eq!(eval(&create(10.0)), 17.0);
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+        assert_eq!(cache.len(), 3);
+
+        assert_eq!(eval(&create(1.0)), 8.0);
+        assert_eq!((cache.hits(), cache.misses()), (3, 3));
+    }
+
+    #[test]
+    fn test_neg_dispatch() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let neg_x = g.insert(op::Neg, vec![x]).unwrap();
+        g.output(RefValue::Scalar(neg_x), Layout::Scalar).unwrap();
+        let out: f64 = g
+            .compile()
+            .unwrap()
+            .eval(&serde_json::json!({ "x": 2.5 }))
+            .unwrap();
+        assert_eq!(out, -2.5);
+
+        let b = g.insert(op::Lt, vec![x, 0.0.into()]).unwrap();
+        let err = g.insert(op::Neg, vec![b]).unwrap_err();
+        assert!(matches!(err, super::Error::WithContext { .. }), "{err:?}");
+        assert!(err.to_string().contains(".not_()"), "{err}");
+    }
+
+    #[test]
+    fn test_output_ref_from_other_graph() {
+        let mut other = Graph::new();
+        let RefValue::Scalar(a) = other.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let b = other.insert(op::Add, vec![a, a]).unwrap();
+        let c = other.insert(op::Mul, vec![b, b]).unwrap();
+
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let y = g.insert(op::Neg, vec![x]).unwrap();
+
+        let err = g.output(RefValue::Scalar(c), Layout::Scalar).unwrap_err();
+        assert!(err.to_string().contains("cannot output node 1"), "{err}");
+        let err = g
+            .output(
+                RefValue::List(vec![RefValue::Scalar(y), RefValue::Scalar(Ref::Input(1))]),
+                Layout::List(Box::new(Layout::Scalar), 2),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot output input 1"), "{err}");
+
+        g.output(RefValue::Scalar(y), Layout::Scalar).unwrap();
+    }
+
+    #[test]
+    fn test_run_normal_pfuncs() {
+        // Reference values from Python's `math.erf`, `math.erfc` and
+        // `statistics.NormalDist`, reaching into the tails.
+        let cases: &[(&str, &[(f64, f64)])] = &[
+            (
+                "erf",
+                &[
+                    (-1.0, -0.8427007929497149),
+                    (0.5, 0.5204998778130465),
+                    (3.0, 0.9999779095030014),
+                    (10.0, 1.0),
+                ],
+            ),
+            (
+                "erfc",
+                &[
+                    (1.0, 0.15729920705028513),
+                    (3.0, 2.2090496998585438e-05),
+                    (5.0, 1.5374597944280351e-12),
+                    (10.0, 2.088487583762545e-45),
+                    (26.0, 5.663192408856143e-296),
+                ],
+            ),
+            (
+                "norm_cdf",
+                &[
+                    (-37.0, 5.725571222525139e-300),
+                    (-10.0, 7.619853024160593e-24),
+                    (-5.0, 2.866515718791946e-07),
+                    (0.0, 0.5),
+                    (1.96, 0.9750021048517795),
+                ],
+            ),
+            (
+                "norm_pdf",
+                &[
+                    (0.0, 0.3989422804014327),
+                    (-3.0, 0.0044318484119380075),
+                    (10.0, 7.69459862670642e-23),
+                    (30.0, 1.4736461348785476e-196),
+                ],
+            ),
+            (
+                "norm_inv",
+                &[
+                    (1e-300, -37.0470962993612),
+                    (1e-10, -6.361340902404056),
+                    (0.025, -1.9599639845400538),
+                    (0.5, 0.0),
+                    (0.975, 1.9599639845400536),
+                ],
+            ),
+        ];
+
+        for &(name, values) in cases {
+            let mut g = Graph::new();
+            let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+                unreachable!()
+            };
+            let y = g.insert(op::Call(name.to_string()), vec![x]).unwrap();
+            g.output(RefValue::Scalar(y), Layout::Scalar).unwrap();
+            let func = g.compile().unwrap();
+            let pfunc = pfunc::get(name).unwrap();
+
+            for &(x, expected) in values {
+                let got: f64 = func.eval(&serde_json::json!({ "x": x })).unwrap();
+                let folded = (pfunc.const_eval.0)(&[x]).unwrap();
+                for value in [got, folded] {
+                    assert!(
+                        (value - expected).abs() <= 1e-12 * expected.abs(),
+                        "{name}({x}) = {value}, expected {expected}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pin_input() {
+        let mut g = Graph::new();
+        let RefValue::Bool(flag) = g.pinnable_input("flag".to_string(), Layout::Bool) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let doubled = g.insert(op::Mul, vec![x, 2.0.into()]).unwrap();
+        let shifted = g.insert(op::Add, vec![x, 1.0.into()]).unwrap();
+        let out = g.insert(op::Choose, vec![flag, doubled, shifted]).unwrap();
+        g.output(RefValue::Scalar(out), Layout::Scalar).unwrap();
+
+        // Unpinned, the flag is kept as an input:
+        let func = g.compile().unwrap();
+        let out: f64 = func
+            .eval(&serde_json::json!({ "flag": false, "x": 3.0 }))
+            .unwrap();
+        assert_eq!(out, 4.0);
+
+        // Pinned, the flag is gone and the choice is folded away:
+        let pinned = g.pin([("flag", &serde_json::json!(true))]).unwrap();
+        assert!(pinned.pinnable_inputs().unwrap().is_empty());
+        assert_eq!(pinned.input_layout().0.len(), 1);
+        assert_eq!(pinned.inputs(), &[Type::Float]);
+        let func = pinned.compile().unwrap();
+        assert!(func.opt_stats().unwrap().const_folded > 0);
+        let out: f64 = func.eval(&serde_json::json!({ "x": 3.0 })).unwrap();
+        assert_eq!(out, 6.0);
+
+        let err = g.pin([("x", &serde_json::json!(1.0))]).unwrap_err();
+        assert!(err.to_string().contains("not pinnable"), "{err}");
+    }
+
+    #[test]
+    fn test_bind_input() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(weight) = g.input("weight".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(bias) = g.input("bias".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let scaled = g.insert(op::Mul, vec![x, weight]).unwrap();
+        let out = g.insert(op::Add, vec![scaled, bias]).unwrap();
+        g.output(RefValue::Scalar(out), Layout::Scalar).unwrap();
+
+        g.bind_input("weight", &serde_json::json!(2.0)).unwrap();
+        let names = g
+            .input_layout()
+            .0
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["x", "bias"]);
+        assert_eq!(g.inputs(), &[Type::Float, Type::Float]);
+
+        // The weight is no longer required and the bias moved to its slot:
+        let func = g.compile().unwrap();
+        let out: f64 = func
+            .eval(&serde_json::json!({ "x": 3.0, "bias": 1.0 }))
+            .unwrap();
+        assert_eq!(out, 7.0);
+
+        let err = g.bind_input("x", &serde_json::json!("three")).unwrap_err();
+        assert!(err.to_string().contains("binding input \"x\""), "{err}");
+        let err = g.bind_input("weight", &serde_json::json!(2.0)).unwrap_err();
+        assert!(err.to_string().contains("no input named"), "{err}");
+        assert_eq!(g.input_layout().0.len(), 2);
+    }
+
+    #[test]
+    fn test_run_clamp() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(min) = g.input("min".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let clamped = g.insert(op::Clamp, vec![x, min, 1.0.into()]).unwrap();
+        g.output(RefValue::Scalar(clamped), Layout::Scalar).unwrap();
+        let func = g.compile().unwrap();
+        let eval = |x: f64, min: f64| -> f64 {
+            func.eval(&serde_json::json!({ "x": x, "min": min }))
+                .unwrap()
+        };
+
+        assert_eq!(eval(-3.0, 0.0), 0.0);
+        assert_eq!(eval(0.5, 0.0), 0.5);
+        assert_eq!(eval(3.0, 0.0), 1.0);
+        // The degenerate case follows `max`:
+        assert_eq!(eval(0.5, 2.0), 1.0);
+        assert_eq!(eval(3.0, 2.0), 1.0);
+
+        // Constant folding:
+        let mut g = Graph::new();
+        let folded = g
+            .insert(op::Clamp, vec![5.0.into(), 0.0.into(), 1.0.into()])
+            .unwrap();
+        g.output(RefValue::Scalar(folded), Layout::Scalar).unwrap();
+        let func = g.compile().unwrap();
+        assert_eq!(func.opt_stats().unwrap().const_folded, 1);
+        let out: f64 = func.eval(&serde_json::json!({})).unwrap();
+        assert_eq!(out, 1.0);
+    }
+
+    #[test]
+    fn test_run_min_max() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(b) = g.input("b".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let min = g.insert(op::Min, vec![a, b]).unwrap();
+        let max = g.insert(op::Max, vec![a, b]).unwrap();
+        let folded_min = g
+            .insert(op::Min, vec![2.0.into(), f64::NAN.into()])
+            .unwrap();
+        let folded_max = g
+            .insert(op::Max, vec![f64::NAN.into(), 3.0.into()])
+            .unwrap();
+        g.output(
+            RefValue::List(
+                [min, max, folded_min, folded_max]
+                    .map(RefValue::Scalar)
+                    .to_vec(),
+            ),
+            Layout::List(Box::new(Layout::Scalar), 4),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+        assert_eq!(func.opt_stats().unwrap().const_folded, 2);
+
+        let cases = [
+            (1.0, 2.0),
+            (2.0, 1.0),
+            (-0.5, -0.5),
+            (f64::NAN, 1.0),
+            (1.0, f64::NAN),
+            (f64::NAN, f64::NAN),
+            (f64::NEG_INFINITY, f64::INFINITY),
+        ];
+        for (a, b) in cases {
+            // JSON has no NaNs, so this goes raw:
+            let out = func.eval_raw([a, b].as_byte_slice()).unwrap();
+            let out = out.as_slice_of::<f64>().unwrap();
+            let expected = [a.min(b), a.max(b), 2.0, 3.0];
+            for (&got, expected) in out.iter().zip(expected) {
+                assert!(
+                    got == expected || got.is_nan() && expected.is_nan(),
+                    "min/max({a}, {b}): got {got}, expected {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_int() {
+        let mut g = Graph::new();
+        let RefValue::Int(i) = g.input("i".to_string(), Layout::Int) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let truncated = g.insert(op::ToInt, vec![x]).unwrap();
+        let sum = g.insert(op::IntAdd, vec![i, truncated]).unwrap();
+        let diff = g.insert(op::IntSub, vec![i, truncated]).unwrap();
+        let prod = g.insert(op::IntMul, vec![i, truncated]).unwrap();
+        let as_float = g.insert(op::IntToFloat, vec![diff]).unwrap();
+        let list = g
+            .indexed_list(vec![10.0.into(), 20.0.into(), 30.0.into()])
+            .unwrap();
+        let element = list
+            .get_with_mode(&mut g, i, op::IndexMode::NegativeWrap)
+            .unwrap();
+        g.output(
+            RefValue::Tuple(vec![
+                RefValue::Int(sum),
+                RefValue::Int(prod),
+                RefValue::Scalar(as_float),
+                RefValue::Scalar(element),
+            ]),
+            Layout::Tuple(vec![
+                Layout::Int,
+                Layout::Int,
+                Layout::Scalar,
+                Layout::Scalar,
+            ]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+        let eval = |i: i64, x: f64| -> serde_json::Value {
+            func.eval(&serde_json::json!({ "i": i, "x": x })).unwrap()
+        };
+
+        assert_eq!(eval(2, 3.7), serde_json::json!([5, 6, -1.0, 30.0]));
+        assert_eq!(eval(-1, -3.7), serde_json::json!([-4, 3, 2.0, 30.0]));
+        assert_eq!(eval(-3, 0.0), serde_json::json!([-3, 0, -3.0, 10.0]));
+        assert!(func
+            .eval::<_, serde_json::Value>(&serde_json::json!({ "i": 3, "x": 0.0 }))
+            .is_err());
+        // Ints are not floats:
+        assert!(func
+            .eval::<_, serde_json::Value>(&serde_json::json!({ "i": 1.5, "x": 0.0 }))
+            .is_err());
+
+        // Constant folding:
+        let mut g = Graph::new();
+        let truncated = g.insert(op::ToInt, vec![(-2.5).into()]).unwrap();
+        let folded = g.insert(op::IntMul, vec![truncated, 3i64.into()]).unwrap();
+        g.output(RefValue::Int(folded), Layout::Int).unwrap();
+        let func = g.compile().unwrap();
+        assert_eq!(func.opt_stats().unwrap().const_folded, 2);
+        let out: i64 = func.eval(&serde_json::json!({})).unwrap();
+        assert_eq!(out, -6);
+    }
+
+    #[test]
+    fn test_neg_int() {
+        let mut g = Graph::new();
+        let RefValue::Int(i) = g.input("i".to_string(), Layout::Int) else {
+            unreachable!()
+        };
+        let negated = g.insert(op::Neg, vec![i]).unwrap();
+        let folded = g.insert(op::Neg, vec![i64::MIN.into()]).unwrap();
+        g.output(
+            RefValue::Tuple(vec![RefValue::Int(negated), RefValue::Int(folded)]),
+            Layout::Tuple(vec![Layout::Int, Layout::Int]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+        assert_eq!(func.opt_stats().unwrap().const_folded, 1);
+        let eval =
+            |i: i64| -> serde_json::Value { func.eval(&serde_json::json!({ "i": i })).unwrap() };
+
+        assert_eq!(eval(3), serde_json::json!([-3, i64::MIN]));
+        assert_eq!(eval(-7), serde_json::json!([7, i64::MIN]));
+        assert_eq!(eval(0), serde_json::json!([0, i64::MIN]));
+    }
+
+    #[test]
+    fn test_eval_batch() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let positive = g.insert(op::Ge, vec![x, 0.0.into()]).unwrap();
+        g.assert(positive, "x must be positive".to_string())
+            .unwrap();
+        let doubled = g.insert(op::Add, vec![x, x]).unwrap();
+        g.output(RefValue::Scalar(doubled), Layout::Scalar).unwrap();
+        let func = g.compile().unwrap();
+
+        let inputs = [1.0, 2.5, 0.0].map(|x| serde_json::json!({ "x": x }));
+        let outputs: Vec<f64> = func.eval_batch(&inputs).unwrap();
+        assert_eq!(outputs, vec![2.0, 5.0, 0.0]);
+
+        let inputs = [1.0, -2.5, -1.0].map(|x| serde_json::json!({ "x": x }));
+        let err = func.eval_batch::<_, f64>(&inputs).unwrap_err().to_string();
+        assert!(err.contains("row 1"), "{err}");
+
+        let raw = func
+            .eval_raw_batch([1.0, 2.5, 0.0].as_byte_slice(), 3)
+            .unwrap();
+        assert_eq!(raw.as_slice_of::<f64>().unwrap(), &[2.0, 5.0, 0.0]);
+        assert!(func.eval_raw_batch([1.0, 2.5].as_byte_slice(), 3).is_err());
+        let err = func
+            .eval_raw_batch([1.0, 2.5, -1.0].as_byte_slice(), 3)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("row 2"), "{err}");
+    }
+
+    #[test]
+    fn test_mapping_from_csv() {
+        let csv = "id:int,name:symbol,score,active:bool\n\
+            1,alice,1.5,true\n\
+            2,\"bob, jr.\",-2.0,false\n\
+            1,alice,3.0,true\n";
+        let mut g = Graph::new();
+        g.insert_mapping_from_csv("people".to_string(), csv.as_bytes(), 2)
+            .unwrap();
+        assert_eq!(
+            g.mappings()["people"].value_layout().to_string(),
+            "{ score: scalar, active: bool }"
+        );
+
+        let key = g.input(
+            "key".to_string(),
+            g.mappings()["people"].key_layout().clone(),
+        );
+        let value = g.call_mapping("people", key.clone()).unwrap();
+        let RefValue::Scalar(contains) = g.mapping_contains("people", key).unwrap() else {
+            unreachable!()
+        };
+        g.output(
+            RefValue::Tuple(vec![RefValue::Bool(contains), value]),
+            Layout::Tuple(vec![
+                Layout::Bool,
+                g.mappings()["people"].value_layout().clone(),
+            ]),
+        )
+        .unwrap();
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        g.dump(&mut dumped).unwrap();
+        let loaded = Graph::load(std::io::Cursor::new(dumped.into_inner())).unwrap();
+        let func = loaded.compile().unwrap();
+
+        let out: serde_json::Value = func
+            .eval(&serde_json::json!({"key": {"id": 1, "name": "alice"}}))
+            .unwrap();
+        assert_eq!(
+            out,
+            serde_json::json!([true, {"score": 3.0, "active": true}])
+        );
+        let out: serde_json::Value = func
+            .eval(&serde_json::json!({"key": {"id": 2, "name": "bob, jr."}}))
+            .unwrap();
+        assert_eq!(
+            out,
+            serde_json::json!([true, {"score": -2.0, "active": false}])
+        );
+        assert!(func
+            .eval::<_, serde_json::Value>(&serde_json::json!({"key": {"id": 3, "name": "alice"}}))
+            .is_err());
+
+        let mut g = Graph::new();
+        let err = g
+            .insert_mapping_from_csv("bad".to_string(), "a,b:int\n1,x\n".as_bytes(), 1)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("line 2"), "{err}");
+        assert!(g
+            .insert_mapping_from_csv("bad".to_string(), "a,b\n".as_bytes(), 2)
+            .is_err());
+    }
+
+    #[test]
+    fn test_prune_unused_inputs() {
+        fn scalar_input(g: &mut Graph, name: &str) -> Ref {
+            let RefValue::Scalar(r) = g.input(name.to_string(), Layout::Scalar) else {
+                unreachable!()
+            };
+            r
+        }
+
+        let mut g = Graph::new();
+        let a = scalar_input(&mut g, "a");
+        let dead = scalar_input(&mut g, "dead");
+        let b = scalar_input(&mut g, "b");
+        let asserted = scalar_input(&mut g, "asserted");
+        scalar_input(&mut g, "constrained");
+        g.constrain_input("constrained", Some(0.0), None).unwrap();
+
+        // A diamond where the right branch is dead.
+        let top = g.insert(op::Mul, vec![a, b]).unwrap();
+        let left = g.insert(op::Add, vec![top, 1.0.into()]).unwrap();
+        let right = g.insert(op::Add, vec![top, dead]).unwrap();
+        let _ = g.insert(op::Mul, vec![right, 2.0.into()]).unwrap();
+        let out = g.insert(op::Mul, vec![left, top]).unwrap();
+        let positive = g.insert(op::Ge, vec![asserted, 0.0.into()]).unwrap();
+        g.assert(positive, "must be positive".to_string()).unwrap();
+        g.output(RefValue::Scalar(out), Layout::Scalar).unwrap();
+
+        assert_eq!(g.prune_unused_inputs(), vec!["dead".to_string()]);
+        assert!(g.prune_unused_inputs().is_empty());
+        assert_eq!(
+            g.input_layout()
+                .0
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b", "asserted", "constrained"]
+        );
+
+        let func = g.compile().unwrap();
+        let out: f64 = func
+            .eval(&serde_json::json!({"a": 2.0, "b": 3.0, "asserted": 1.0, "constrained": 1.0}))
+            .unwrap();
+        assert_eq!(out, 42.0);
+        assert!(func
+            .eval::<_, f64>(
+                &serde_json::json!({"a": 2.0, "b": 3.0, "asserted": -1.0, "constrained": 1.0})
+            )
+            .is_err());
+        assert!(func
+            .eval::<_, f64>(
+                &serde_json::json!({"a": 2.0, "b": 3.0, "asserted": 1.0, "constrained": -1.0})
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_canonical_name() {
+        fn build(c: f64) -> Graph {
+            let mut g = Graph::new();
+            let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+                unreachable!()
+            };
+            let RefValue::Scalar(b) = g.input("b".to_string(), Layout::Scalar) else {
+                unreachable!()
+            };
+            let sum = g.insert(op::Add, vec![a, b]).unwrap();
+            let out = g.insert(op::Mul, vec![sum, c.into()]).unwrap();
+            g.metadata_mut()
+                .insert("built_at".to_string(), format!("{c}"));
+            g.output(RefValue::Scalar(out), Layout::Scalar).unwrap();
+            g
+        }
+
+        let g1 = build(2.0);
+        let mut g2 = build(2.0);
+        g2.metadata_mut()
+            .insert("built_at".to_string(), "elsewhere".to_string());
+        assert_ne!(g1.name(), g2.name());
+        assert_eq!(g1.canonical_name(), g2.canonical_name());
+        let mut dumped = std::io::Cursor::new(vec![]);
+        g1.dump(&mut dumped).unwrap();
+        dumped.set_position(0);
+        assert_eq!(
+            g1.canonical_name(),
+            Graph::load(dumped).unwrap().canonical_name()
+        );
+        assert_ne!(g1.canonical_name(), build(3.0).canonical_name());
+    }
+
+    #[test]
+    fn test_powi() {
+        for n in [0, 1, 2, 3, 7, 10, -1, -2, -5, i32::MAX, i32::MIN] {
+            let mut g = Graph::new();
+            let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+                unreachable!()
+            };
+            let powered = g.insert(op::Powi(n), vec![a]).unwrap();
+            let folded = g.insert(op::Powi(n), vec![1.5.into()]).unwrap();
+            let out = RefValue::Tuple(vec![RefValue::Scalar(powered), RefValue::Scalar(folded)]);
+            let layout = Layout::Tuple(vec![Layout::Scalar, Layout::Scalar]);
+            g.output(out, layout).unwrap();
+
+            let func = g.compile().unwrap();
+            for x in [1.5f64, -0.75, 2.0, 0.0] {
+                let (got, folded): (f64, f64) = func.eval(&serde_json::json!({ "a": x })).unwrap();
+                let expected = x.powf(n as f64);
+                let close = |value: f64| {
+                    value == expected
+                        || (value - expected).abs() <= 1e-12 * expected.abs()
+                        || (value.is_nan() && expected.is_nan())
+                };
+                assert!(close(got), "{x}^{n}: got {got}, expected {expected}");
+                if x == 1.5 {
+                    assert_eq!(got, folded, "folding 1.5^{n}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Bool(flag) = g.input("flag".to_string(), Layout::Bool) else {
+            unreachable!()
+        };
+        let sum = g.insert(op::Add, vec![a, 1.0.into()]).unwrap();
+        g.output(RefValue::Scalar(sum), Layout::Scalar).unwrap();
+        g.verify().unwrap();
+
+        // A graph received from elsewhere adding a bool to a scalar.
+        let mut mismatched = g.clone();
+        mismatched.nodes[0].args[0] = flag;
+        assert!(matches!(mismatched.verify(), Err(crate::Error::Type(..))));
+
+        // A graph received from elsewhere claiming that adding scalars gives a bool.
+        let mut mistyped = g.clone();
+        mistyped.nodes[0].ty = Type::Bool;
+        assert!(matches!(mistyped.verify(), Err(crate::Error::Type(..))));
+
+        // The output layout says bool, but a scalar is returned.
+        let mut bad_output = g.clone();
+        bad_output.output_layout = Layout::Bool;
+        assert!(bad_output.verify().is_err());
+
+        // An assertion that always fails.
+        let mut failing = g.clone();
+        failing.assert(false.into(), "never".to_string()).unwrap();
+        assert!(matches!(
+            failing.verify(),
+            Err(crate::Error::IllegalInstruction(_))
+        ));
+        assert!(matches!(
+            failing.compile(),
+            Err(crate::Error::IllegalInstruction(_))
+        ));
+    }
+
+    /// Records the stages of the compilations it observes.
+    #[derive(Debug, Default)]
+    struct StageRecorder(std::sync::Mutex<Vec<(CompileStage, usize)>>);
+
+    impl CompileObserver for StageRecorder {
+        fn on_stage(&self, stage: CompileStage, _: std::time::Duration, bytes: usize) {
+            self.0.lock().unwrap().push((stage, bytes));
+        }
+    }
+
+    impl StageRecorder {
+        fn qbe_runs(&self) -> usize {
+            let events = self.0.lock().unwrap();
+            events
+                .iter()
+                .filter(|&&(stage, _)| stage == CompileStage::Qbe)
+                .count()
+        }
+    }
+
+    #[test]
+    fn test_compile_cached() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let recorder = std::sync::Arc::new(StageRecorder::default());
+        let options = CompileOptions {
+            cache_dir: Some(cache_dir.path().to_owned()),
+            observer: Some(recorder.clone()),
+            ..CompileOptions::default()
+        };
+        let graph = create_simple_graph();
+        let input = serde_json::json!({"a": 2.0, "b": 3.0});
+
+        let first: f64 = graph
+            .compile_with("run", &options)
+            .unwrap()
+            .eval(&input)
+            .unwrap();
+        assert_eq!(recorder.qbe_runs(), 1);
+        assert_eq!(std::fs::read_dir(cache_dir.path()).unwrap().count(), 2);
+
+        // The second compilation loads the stored shared object.
+        let second: f64 = graph
+            .compile_with("run", &options)
+            .unwrap()
+            .eval(&input)
+            .unwrap();
+        assert_eq!(recorder.qbe_runs(), 1);
+        assert_eq!(first, second);
+
+        // Entries that do not match the graph are not used.
+        for entry in std::fs::read_dir(cache_dir.path()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().unwrap() == "json" {
+                std::fs::write(&path, r#"{"entry":"run","input_size":0,"output_size":0}"#).unwrap();
+            }
+        }
+        let third: f64 = graph
+            .compile_with("run", &options)
+            .unwrap()
+            .eval(&input)
+            .unwrap();
+        assert_eq!(recorder.qbe_runs(), 2);
+        assert_eq!(first, third);
+
+        // Failing to store entries does not fail the compilation.
+        let not_a_dir = cache_dir.path().join("file");
+        std::fs::write(&not_a_dir, "").unwrap();
+        let fourth: f64 = graph
+            .compile_cached(&not_a_dir)
+            .unwrap()
+            .eval(&input)
+            .unwrap();
+        assert_eq!(first, fourth);
+    }
+
+    #[test]
+    fn test_compile_with_observer() {
+        let recorder = std::sync::Arc::new(StageRecorder::default());
+        let func = create_simple_graph()
+            .compile_with_observer(recorder.clone())
+            .unwrap();
+        let result: f64 = func.eval(&serde_json::json!({"a": 2.0, "b": 3.0})).unwrap();
+        assert_eq!(result, 6.0);
+
+        let events = recorder.0.lock().unwrap();
+        let stages = events.iter().map(|&(stage, _)| stage).collect::<Vec<_>>();
+        assert_eq!(
+            stages,
+            [
+                CompileStage::Optimize,
+                CompileStage::Qbe,
+                CompileStage::Assemble,
+                CompileStage::Link
+            ]
+        );
+        assert!(events.iter().all(|&(_, bytes)| bytes > 0), "{events:?}");
+    }
+
+    #[test]
+    fn test_run_two_arg_pfuncs() {
+        for (name, f) in [
+            ("atan2", f64::atan2 as fn(f64, f64) -> f64),
+            ("powf", f64::powf),
+            ("hypot", f64::hypot),
+            ("log", f64::log),
+        ] {
+            let mut g = Graph::new();
+            let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+                unreachable!()
+            };
+            let RefValue::Scalar(y) = g.input("y".to_string(), Layout::Scalar) else {
+                unreachable!()
+            };
+            let z = g.insert(op::Call(name.to_string()), vec![x, y]).unwrap();
+            g.output(RefValue::Scalar(z), Layout::Scalar).unwrap();
+            let func = g.compile().unwrap();
+
+            for (x, y) in [(3.0, 4.0), (8.0, 2.0), (0.5, 1.5)] {
+                let z: f64 = func.eval(&serde_json::json!({"x": x, "y": y})).unwrap();
+                let expected = f(x, y);
+                assert!(z == expected, "{name}({x}, {y}) = {z}, expected {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let mut graph = create_simple_graph();
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph "), "{dot}");
+        let (edges, vertices): (Vec<_>, Vec<_>) = dot
+            .lines()
+            .filter(|line| line.contains("[label="))
+            .partition(|line| line.contains(" -> "));
+        // 2 inputs, 2 nodes, 1 constant and 1 output.
+        assert_eq!(vertices.len(), 6, "{dot}");
+        // 2 arguments for each node and the output.
+        assert_eq!(edges.len(), 5, "{dot}");
+        assert!(!dot.contains("dashed"), "{dot}");
+
+        let RefValue::Scalar(a) = graph.input("c".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        graph.insert(op::Neg, vec![a]).unwrap();
+        let dot = graph.to_dot();
+        assert_eq!(
+            dot.lines().filter(|line| line.contains("dashed")).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_run_floor_rem_sign() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(b) = g.input("b".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let floored = g.insert(op::FloorRem, vec![a, b]).unwrap();
+        let truncated = g.insert(op::Rem, vec![a, b]).unwrap();
+        g.output(
+            RefValue::Tuple(vec![RefValue::Scalar(floored), RefValue::Scalar(truncated)]),
+            Layout::Tuple(vec![Layout::Scalar, Layout::Scalar]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+
+        for (a, b, expected) in [
+            (7.0, 3.0, 1.0),
+            (-7.0, 3.0, 2.0),
+            (7.0, -3.0, -2.0),
+            (-7.0, -3.0, -1.0),
+            (-6.0, 3.0, 0.0),
+            (-7.5, 2.0, 0.5),
+        ] {
+            // `Rem` keeps truncating, just like Rust.
+            let (rem, truncated): (f64, f64) =
+                func.eval(&serde_json::json!({"a": a, "b": b})).unwrap();
+            assert_eq!(rem, expected, "{a} mod {b}");
+            assert_eq!(truncated, a % b, "{a} % {b}");
+
+            let mut g = Graph::new();
+            let rem = g.insert(op::FloorRem, vec![a.into(), b.into()]).unwrap();
+            g.output(RefValue::Scalar(rem), Layout::Scalar).unwrap();
+            let folded: f64 = g.compile().unwrap().eval(&serde_json::json!({})).unwrap();
+            assert_eq!(folded, expected, "{a} mod {b} folded");
+        }
+
+        let (rem, truncated): (f64, f64) =
+            func.eval(&serde_json::json!({"a": 1.0, "b": 0.0})).unwrap();
+        assert!(rem.is_nan() && truncated.is_nan());
+    }
+
+    #[test]
+    fn test_metadata_json_survives_dump_and_load() {
+        let mut graph = create_simple_graph();
+        graph
+            .metadata_mut()
+            .insert("model".to_string(), "v1".to_string());
+        let params = serde_json::json!({
+            "features": ["a", "b"],
+            "training": {"epochs": 10, "rate": 0.5, "early_stop": null},
+        });
+        graph
+            .metadata_json_mut()
+            .insert("params".to_string(), params.clone());
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        let loaded = Graph::load(std::io::Cursor::new(dumped.into_inner())).unwrap();
+
+        assert_eq!(loaded.metadata_json()["params"], params);
+        assert_eq!(loaded.metadata()["model"], "v1");
+        assert_eq!(loaded.metadata(), graph.metadata());
+        let json: serde_json::Value = serde_json::from_str(&loaded.to_json()).unwrap();
+        assert_eq!(json["metadata_json"]["params"], params);
+
+        // The JSON metadata of subgraphs is kept too.
+        let mut parent = Graph::new();
+        parent.insert_subgraph(graph).unwrap();
+        let mut dumped = std::io::Cursor::new(vec![]);
+        parent.dump(&mut dumped).unwrap();
+        let loaded = Graph::load(std::io::Cursor::new(dumped.into_inner())).unwrap();
+        assert!(loaded.metadata_json().is_empty());
+        assert_eq!(loaded.subgraphs[0].metadata_json()["params"], params);
+        let json: serde_json::Value = serde_json::from_str(&loaded.to_json()).unwrap();
+        assert_eq!(json["subgraphs"][0]["metadata_json"]["params"], params);
+
+        // Graphs without JSON metadata dump just as before.
+        let mut dumped = std::io::Cursor::new(vec![]);
+        create_simple_graph().dump(&mut dumped).unwrap();
+        let archive = zip::ZipArchive::new(dumped).unwrap();
+        assert!(archive.index_for_name("metadata_json.json").is_none());
+    }
+
+    #[test]
+    fn test_run_div_or() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(b) = g.input("b".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let divided = g.insert(op::DivOr, vec![a, b, (-1.0).into()]).unwrap();
+        g.output(RefValue::Scalar(divided), Layout::Scalar).unwrap();
+        let func = g.compile().unwrap();
+
+        for (a, b, expected) in [
+            (6.0, 3.0, 2.0),
+            (6.0, 0.0, -1.0),
+            (6.0, -0.0, -1.0),
+            (0.0, 0.0, -1.0),
+            (1e-300, 1e-310, 1e-300 / 1e-310),
+        ] {
+            let divided: f64 = func.eval(&serde_json::json!({"a": a, "b": b})).unwrap();
+            assert_eq!(divided, expected, "{a} / {b}");
+        }
+
+        // Constant folding.
+        let g = Graph::new();
+        let a = Ref::Input(0);
+        assert_eq!(op::DivOr.const_eval(&g, &[a, 0.0.into(), a]), Some(a));
+        assert_eq!(op::DivOr.const_eval(&g, &[a, (-0.0).into(), a]), Some(a));
+        assert_eq!(
+            op::DivOr.const_eval(&g, &[a, 1.0.into(), 0.0.into()]),
+            Some(a)
+        );
+        assert_eq!(
+            op::DivOr.const_eval(&g, &[6.0.into(), 4.0.into(), 0.0.into()]),
+            Some(1.5.into())
+        );
+        assert_eq!(op::DivOr.const_eval(&g, &[1.0.into(), a, 0.0.into()]), None);
+    }
+
+    #[test]
+    fn test_compile_profiled() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let is_negative = g.insert(op::Lt, vec![a, 0.0.into()]).unwrap();
+        let negated = g.insert(op::Neg, vec![a]).unwrap();
+        let abs = g.insert(op::Choose, vec![is_negative, negated, a]).unwrap();
+        g.output(RefValue::Scalar(abs), Layout::Scalar).unwrap();
+
+        assert!(g.compile().unwrap().profile_report().is_none());
+
+        let func = g.compile_profiled().unwrap();
+        assert_eq!(func.profile_report(), Some(Default::default()));
+        for a in [-3.0, 2.0, -1.0, 5.0, 0.0, -7.0] {
+            let abs: f64 = func.eval(&serde_json::json!({ "a": a })).unwrap();
+            assert_eq!(abs, f64::abs(a));
+        }
+
+        let find = |name: &str| {
+            func.profiled_graph()
+                .unwrap()
+                .nodes()
+                .iter()
+                .position(|node| format!("{:?}", node.op) == name)
+                .unwrap()
+        };
+        let report = func.profile_report().unwrap();
+        assert_eq!(report[&find("Lt")].calls, 6);
+        assert_eq!(report[&find("Choose")].calls, 6);
+        assert_eq!(report[&find("Neg")].calls, 3);
+    }
+
+    #[test]
+    fn test_inline_subgraph() {
+        let mut scale = Graph::new();
+        let RefValue::Scalar(x) = scale.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let is_positive = scale.insert(op::Ge, vec![x, 0.0.into()]).unwrap();
+        scale
+            .assert(is_positive, "x must be non-negative".to_string())
+            .unwrap();
+        let abs_id = scale.insert_subgraph(create_abs_graph()).unwrap();
+        let RefValue::Scalar(abs) = scale
+            .call_graph(
+                abs_id,
+                RefValue::Struct(HashMap::from([("a".to_string(), RefValue::Scalar(x))])),
+            )
+            .unwrap()
+        else {
+            unreachable!()
+        };
+        let list = scale.indexed_list(vec![3.0.into(), 2.0.into()]).unwrap();
+        let factor = list.get(&mut scale, 1.0.into()).unwrap();
+        let weight = scale
+            .select(x, vec![1.0.into(), 0.5.into(), 0.25.into()])
+            .unwrap();
+        let factor = scale.insert(op::Mul, vec![factor, weight]).unwrap();
+        let scaled = scale.insert(op::Mul, vec![abs, factor]).unwrap();
+        scale
+            .output(RefValue::Scalar(scaled), Layout::Scalar)
+            .unwrap();
+
+        let create = |inline: bool| {
+            let mut g = Graph::new();
+            let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+                unreachable!()
+            };
+            let is_large = g.insert(op::Lt, vec![a, 100.0.into()]).unwrap();
+            g.assert(is_large, "a must be less than 100".to_string())
+                .unwrap();
+            let graph_id = g.insert_subgraph(scale.clone()).unwrap();
+            let args = RefValue::Struct(HashMap::from([("x".to_string(), RefValue::Scalar(a))]));
+            let out = if inline {
+                g.inline_subgraph(graph_id, args).unwrap()
+            } else {
+                g.call_graph(graph_id, args).unwrap()
+            };
+            g.output(out, Layout::Scalar).unwrap();
+            g
+        };
+
+        let called = create(false).compile().unwrap();
+        let inlined = create(true);
+        assert_eq!(inlined.errors().len(), 4);
+        assert!(inlined.nodes().iter().all(|node| node
+            .op
+            .downcast_ref::<op::CallGraph>()
+            .is_none_or(|call| inlined.subgraphs[call.0] != scale)));
+        let inlined = inlined.compile().unwrap();
+
+        for a in [0.0, 1.5, 4.0, -2.0] {
+            let input = serde_json::json!({ "a": a });
+            match (
+                called.eval::<_, f64>(&input),
+                inlined.eval::<_, f64>(&input),
+            ) {
+                (Ok(called), Ok(inlined)) => assert_eq!(called, inlined),
+                (Err(_), Err(inlined)) if a < 0.0 => {
+                    assert!(inlined.to_string().contains("x must be non-negative"))
+                }
+                (Err(_), Err(inlined)) => {
+                    assert!(inlined.to_string().contains("Select index out of range"))
+                }
+                (called, inlined) => panic!("{called:?} != {inlined:?}"),
+            }
+        }
+
+        let mut g = Graph::new();
+        let a = g.input("a".to_string(), Layout::Scalar);
+        let graph_id = g.insert_subgraph(scale).unwrap();
+        assert!(g.inline_subgraph(graph_id, a).is_err());
+        assert!(g.inline_subgraph(graph_id + 1, RefValue::Unit).is_err());
+    }
+
+    #[test]
+    fn test_eval_json_streaming() {
+        let mut g = Graph::new();
+        let fields = vec![
+            ("x".to_string(), Layout::Scalar),
+            ("n".to_string(), Layout::Int),
+            ("flag".to_string(), Layout::Bool),
+            ("name".to_string(), Layout::Symbol),
+            (
+                "at".to_string(),
+                Layout::DateTime(layout::ISOFORMAT.to_string()),
+            ),
+            (
+                "maybe".to_string(),
+                Layout::Optional(Box::new(Layout::Scalar)),
+            ),
+            ("xs".to_string(), Layout::List(Box::new(Layout::Scalar), 2)),
+        ];
+        let values = fields
+            .iter()
+            .map(|(name, field)| (name.clone(), g.input(name.clone(), field.clone())))
+            .collect::<HashMap<_, _>>();
+        let RefValue::Scalar(x) = values["x"] else {
+            unreachable!()
+        };
+        let doubled = g.insert(op::Mul, vec![x, 2.0.into()]).unwrap();
+        let mut output = values.clone();
+        output.insert("x".to_string(), RefValue::Scalar(doubled));
+        g.output(
+            RefValue::Struct(output),
+            Layout::Struct(layout::Struct(fields)),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+
+        let eval_json = |input: &str| -> Result<String, super::Error> {
+            let input: serde_json::Value = serde_json::from_str(input)?;
+            let output: serde_json::Value = func.eval(&input)?;
+            Ok(serde_json::to_string(&output)?)
+        };
+        let eval_json_streaming = |input: &str| -> Result<String, super::Error> {
+            let mut output = vec![];
+            func.eval_json_streaming(input, &mut output)?;
+            Ok(String::from_utf8(output).unwrap())
+        };
+
+        for input in [
+            r#"{"x": 1.5, "n": 3, "flag": true, "name": "a", "at": "2024-01-02T03:04:05.25",
+                "maybe": 2, "xs": [1, 2]}"#,
+            // Out of order, with extra and missing optional fields:
+            r#"{"xs": ["1e3", -2.5], "extra": {"a": [1]}, "at": "2024-01-02T03:04:05.25",
+                "name": "unseen symbol", "flag": false, "n": -7, "x": "4"}"#,
+            r#"{"x": 1, "n": 3, "flag": true, "name": "a", "at": "2024-01-02T03:04:05.25",
+                "maybe": null, "xs": [1, 2]}"#,
+        ] {
+            assert_eq!(
+                eval_json_streaming(input).unwrap(),
+                eval_json(input).unwrap()
+            );
+        }
+
+        for input in [
+            r#"{"x": 1.5, "n": 3, "flag": true, "name": "a", "xs": [1, 2]}"#,
+            r#"{"x": 1.5, "n": 3.5, "flag": true, "name": "a", "at": "2024-01-02T03:04:05.25",
+                "xs": [1, 2]}"#,
+            r#"{"x": 1.5, "n": 3, "flag": true, "name": "a", "at": "2024-01-02T03:04:05.25",
+                "xs": [1, 2, 3]}"#,
+            r#"{"x": 1.5, "n": 3, "flag": true, "name": "a", "at": "2024-01-02T03:04:05.25",
+                "xs": [1]}"#,
+            r#"{"x": 1.5, "n": 3, "flag": true, "name": "a", "at": "2024-01-02T03:04:05.25",
+                "xs": [1, 2]} trailing"#,
+            r#"[1.5, 3]"#,
+        ] {
+            assert!(eval_json_streaming(input).is_err(), "{input}");
+            assert!(eval_json(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_f32_mapping() {
+        let value_layout = Layout::Struct(layout::Struct(vec![
+            (
+                "weights".to_string(),
+                Layout::List(Box::new(Layout::Scalar), 16),
+            ),
+            ("count".to_string(), Layout::Int),
+        ]));
+        let value = |i: i64| {
+            serde_json::json!({
+                "weights": (0..16).map(|j| (i * 16 + j) as f64 / 7.0).collect::<Vec<_>>(),
+                "count": i * 1_000_000_007,
+            })
+        };
+        let build = |f32_values: bool| {
+            let mut g = Graph::new();
+            let items = (0..100).map(|i| Ok::<_, super::Error>((i as f64, value(i))));
+            if f32_values {
+                g.insert_mapping(
+                    "weights".to_string(),
+                    Layout::Scalar,
+                    value_layout.clone(),
+                    mapping::F32HashMapStorage,
+                    items,
+                )
+            } else {
+                g.insert_mapping(
+                    "weights".to_string(),
+                    Layout::Scalar,
+                    value_layout.clone(),
+                    mapping::HashMapStorage,
+                    items,
+                )
+            }
+            .unwrap();
+            let key = g.input("key".to_string(), Layout::Scalar);
+            let value = g.call_mapping("weights", key).unwrap();
+            g.output(value, value_layout.clone()).unwrap();
+            g
+        };
+
+        let full = build(false);
+        let packed = build(true);
+        let full_size = get_size::GetSize::get_size(&*full.mappings()["weights"]);
+        let packed_size = get_size::GetSize::get_size(&*packed.mappings()["weights"]);
+        assert!(
+            packed_size < full_size * 3 / 4,
+            "{packed_size} vs {full_size}"
+        );
+
+        // Slots are widened inline, without calling into the runtime.
+        let rendered = packed.render().unwrap().to_string();
+        assert_eq!(rendered.matches("=d exts ").count(), 16);
+
+        let func = packed.compile().unwrap();
+        for i in [0, 42, 99] {
+            let input = serde_json::json!({"key": i as f64});
+            let output: serde_json::Value = func.eval(&input).unwrap();
+            let expected = value(i);
+            assert_eq!(output["count"], expected["count"]);
+            for j in 0..16 {
+                let expected = expected["weights"][j].as_f64().unwrap();
+                let got = output["weights"][j].as_f64().unwrap();
+                assert_eq!(got, expected as f32 as f64);
+                assert!((got - expected).abs() <= expected.abs() * 1e-7);
+            }
+        }
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        packed.dump(&mut dumped).unwrap();
+        dumped.set_position(0);
+        let func = Graph::load(dumped).unwrap().compile().unwrap();
+        let output: serde_json::Value = func.eval(&serde_json::json!({"key": 3.0})).unwrap();
+        assert_eq!(output["weights"][1], serde_json::json!(49.0f32 / 7.0f32));
+    }
+
+    #[test]
+    fn test_try_call_raw() {
+        let func = create_simple_graph().compile().unwrap();
+        let input = [2.0f64, 3.0];
+        let input = input.as_byte_slice();
+        let mut output = [0.0f64; 1];
+
+        func.try_call_raw(input, output.as_mut_byte_slice())
+            .unwrap();
+        assert_eq!(output, [6.0]);
+
+        let err = func
+            .try_call_raw(&input[..8], output.as_mut_byte_slice())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            super::Error::WithContext { ref error, .. }
+                if matches!(**error, super::Error::SizeMismatch { expected: 16, got: 8 })
+        ));
+        assert!(err.to_string().contains("checking input"), "{err}");
+
+        let err = func.try_call_raw(input, [0u8; 4]).unwrap_err();
+        assert!(err.to_string().contains("checking output"), "{err}");
+    }
+
+    #[test]
+    fn test_strict_fp() {
+        let mut graph = Graph::new();
+        let RefValue::Scalar(a) = graph.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(b) = graph.input("b".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let product = graph.insert(op::Mul, vec![a, b]).unwrap();
+        let sum = graph.insert(op::Add, vec![product, a]).unwrap();
+        let root = graph
+            .insert(op::Call("sqrt".to_string()), vec![sum])
+            .unwrap();
+        graph
+            .output(RefValue::Scalar(root), Layout::Scalar)
+            .unwrap();
+
+        let options = CompileOptions {
+            strict_fp: true,
+            ..CompileOptions::default()
+        };
+        let first = graph.render_assembly_with("run", &options).unwrap();
+        let second = graph.render_assembly_with("run", &options).unwrap();
+        assert_eq!(first, second);
+        assert!(!first.contains("fma") && !first.contains("fmadd"));
+
+        let func = graph.compile_with("run", &options).unwrap();
+        let (a, b) = (0.1f64, 0.7f64);
+        let output: f64 = func.eval(&serde_json::json!({"a": a, "b": b})).unwrap();
+        assert_eq!(output.to_bits(), (a * b + a).sqrt().to_bits());
+    }
+
+    #[test]
+    fn test_index_get_wrapping() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Int(i) = g.input("i".to_string(), Layout::Int) else {
+            unreachable!()
+        };
+        let list = g
+            .indexed_list(vec![10.0.into(), 20.0.into(), 30.0.into(), 40.0.into()])
+            .unwrap();
+        let by_scalar = list.get_wrapping(&mut g, x).unwrap();
+        let by_int = list.get_wrapping(&mut g, i).unwrap();
+        g.output(
+            RefValue::Tuple(vec![RefValue::Scalar(by_scalar), RefValue::Scalar(by_int)]),
+            Layout::Tuple(vec![Layout::Scalar, Layout::Scalar]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+        let eval =
+            |x: f64, i: i64| func.eval::<_, (f64, f64)>(&serde_json::json!({ "x": x, "i": i }));
+
+        assert_eq!(eval(-1.0, -1).unwrap(), (40.0, 40.0));
+        assert_eq!(eval(-4.0, -4).unwrap(), (10.0, 10.0));
+        assert_eq!(eval(-2.0, 1).unwrap(), (30.0, 20.0));
+        // Fractional indices are truncated towards zero.
+        assert_eq!(eval(-0.5, 0).unwrap(), (10.0, 10.0));
+        assert_eq!(eval(-1.5, 3).unwrap(), (40.0, 40.0));
+
+        assert!(eval(-5.0, 0).is_err());
+        assert!(eval(0.0, -5).is_err());
+        assert!(eval(-100.0, 0).is_err());
+        assert!(eval(0.0, i64::MIN).is_err());
+        assert!(eval(4.0, 0).is_err());
+        assert!(eval(0.0, 4).is_err());
+        assert!(eval(f64::NEG_INFINITY, 0).is_err());
+    }
+
+    #[test]
+    fn test_input_names() {
+        let mut g = Graph::new();
+        let inputs = [
+            ("zeta", Layout::Scalar),
+            ("alpha", Layout::Int),
+            ("when", Layout::DateTime(layout::ISOFORMAT.to_string())),
+            ("tags", Layout::List(Box::new(Layout::Symbol), 2)),
+        ];
+        for (name, layout) in &inputs {
+            g.input(name.to_string(), layout.clone());
+        }
+
+        assert_eq!(
+            g.input_names().collect::<Vec<_>>(),
+            ["zeta", "alpha", "when", "tags"]
+        );
+        assert_eq!(
+            g.input_layout().fields(),
+            inputs
+                .iter()
+                .map(|(name, layout)| (name.to_string(), layout.clone()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    /// A resource that only holds some bytes.
+    #[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+    struct Blob;
+
+    #[typetag::serde]
+    impl resource::ResourceType for Blob {
+        fn from_bytes(
+            &self,
+            bytes: &[u8],
+        ) -> Result<std::pin::Pin<Box<dyn resource::Resource>>, super::Error> {
+            Ok(Box::pin(BlobResource(bytes.to_vec())))
+        }
+    }
+
+    #[derive(Debug)]
+    struct BlobResource(Vec<u8>);
+
+    impl resource::Resource for BlobResource {
+        fn r#type(&self) -> std::sync::Arc<dyn resource::ResourceType> {
+            std::sync::Arc::new(Blob)
+        }
+
+        fn dump(&self) -> Result<Vec<u8>, super::Error> {
+            Ok(self.0.clone())
+        }
+
+        fn size(&self) -> usize {
+            self.0.len()
+        }
+
+        fn get_method(&self, _: &str) -> Option<resource::ResourceMethod> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_size_breakdown() {
+        let mut graph = create_simple_graph();
+        graph
+            .insert_mapping(
+                "doubles".to_string(),
+                Layout::Scalar,
+                Layout::Scalar,
+                mapping::HashMapStorage,
+                (0..1_000).map(|i| Ok::<_, super::Error>((i as f64, 2.0 * i as f64))),
+            )
+            .unwrap();
+        graph.insert_resource("blob".to_string(), BlobResource(vec![0; 10_000]));
+
+        let breakdown = graph.size_breakdown();
+        assert_eq!(breakdown.total(), get_size::GetSize::get_size(&graph));
+        assert!(breakdown.nodes > 0);
+        assert!(breakdown.mappings > 1_000 * 16, "{breakdown:?}");
+        assert!(breakdown.resources > 10_000, "{breakdown:?}");
+        assert_eq!(breakdown.subgraphs, 0);
+        assert!(breakdown.other >= std::mem::size_of::<Graph>());
+
+        // The clone shares the mapping and the resource with its subgraph, which are only
+        // counted once.
+        let mut parent = graph.clone();
+        parent.insert_subgraph(graph.clone()).unwrap();
+        let parent_breakdown = parent.size_breakdown();
+        assert_eq!(parent_breakdown.mappings, breakdown.mappings);
+        assert_eq!(parent_breakdown.resources, breakdown.resources);
+        assert!(parent_breakdown.subgraphs > std::mem::size_of::<Graph>());
+        assert!(parent_breakdown.subgraphs < breakdown.total());
+        assert!(parent_breakdown.total() < get_size::GetSize::get_size(&parent));
+    }
+
+    #[test]
+    fn test_run_select() {
+        let mut g = Graph::new();
+        let RefValue::Int(i) = g.input("i".to_string(), Layout::Int) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(f) = g.input("f".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let values = (1..=3)
+            .map(|k| {
+                let k = g.r#const(k as f64);
+                g.insert(op::Mul, vec![x, k])
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let by_int = g.select(i, values.clone()).unwrap();
+        let by_float = g.select(f, values[1..].to_vec()).unwrap();
+        // Also used as an output, so it must be computed outside of the select.
+        let hundred = g.r#const(100.0);
+        let exposed = g.insert(op::Add, vec![x, hundred]).unwrap();
+        let with_exposed = g.select(i, vec![exposed, x, x]).unwrap();
+        g.output(
+            RefValue::Tuple(vec![
+                RefValue::Scalar(by_int),
+                RefValue::Scalar(by_float),
+                RefValue::Scalar(with_exposed),
+                RefValue::Scalar(exposed),
+            ]),
+            Layout::Tuple(vec![Layout::Scalar; 4]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+
+        let eval = |i: i64, f: f64| {
+            func.eval::<_, (f64, f64, f64, f64)>(&serde_json::json!({ "i": i, "f": f, "x": 2.0 }))
+        };
+        assert_eq!(eval(0, 0.0).unwrap(), (2.0, 4.0, 102.0, 102.0));
+        assert_eq!(eval(1, 1.9).unwrap(), (4.0, 6.0, 2.0, 102.0));
+        assert_eq!(eval(2, -0.5).unwrap(), (6.0, 4.0, 2.0, 102.0));
+
+        for (i, f) in [(3, 0.0), (-1, 0.0), (0, 2.0), (0, -1.0)] {
+            let err = eval(i, f).unwrap_err().to_string();
+            assert!(err.contains("Select index out of range"), "{err}");
+        }
+        let err = func
+            .eval_raw([0, f64::NAN.to_bits(), 2.0f64.to_bits()].as_byte_slice())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Select index out of range"), "{err}");
+
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let index = g.r#const(1i64);
+        let one = g.r#const(1.0);
+        assert!(g.select(index, vec![]).is_err());
+        assert!(g.select(index, vec![x, index]).is_err());
+
+        // A constant index is folded away at compile time.
+        let selected = g.select(index, vec![one, x]).unwrap();
+        g.output(RefValue::Scalar(selected), Layout::Scalar)
+            .unwrap();
+        let func = g.compile().unwrap();
+        assert!(!func
+            .graph()
+            .render()
+            .unwrap()
+            .to_string()
+            .contains("select"));
+        let out: f64 = func.eval(&serde_json::json!({ "x": 7.0 })).unwrap();
+        assert_eq!(out, 7.0);
+        // And an out-of-range constant index is caught at compile time as well.
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let index = g.r#const(5i64);
+        let selected = g.select(index, vec![x, x]).unwrap();
+        g.output(RefValue::Scalar(selected), Layout::Scalar)
+            .unwrap();
+        assert!(g.compile().is_err());
+    }
+
+    #[test]
+    fn test_run_compare_symbols() {
+        let mut g = Graph::new();
+        let RefValue::Symbol(a) = g.input("a".to_string(), Layout::Symbol) else {
+            unreachable!()
+        };
+        let RefValue::Symbol(b) = g.input("b".to_string(), Layout::Symbol) else {
+            unreachable!()
+        };
+        g.push_symbol("known".to_string());
+        let eq = g.insert(op::Eq(None), vec![a, b]).unwrap();
+        g.output(RefValue::Bool(eq), Layout::Bool).unwrap();
+        let func = g.compile().unwrap();
+
+        for (a, b, expected) in [
+            ("known", "known", true),
+            ("known", "unknown", false),
+            ("unknown", "unknown", true),
+            ("unknown", "other", false),
+        ] {
+            let out: bool = func.eval(&serde_json::json!({ "a": a, "b": b })).unwrap();
+            assert_eq!(out, expected, "{a} == {b}");
+        }
+    }
+
+    #[test]
+    fn test_run_compare_datetimes() {
+        let mut g = Graph::new();
+        let datetime = Layout::DateTime(layout::ISOFORMAT.to_string());
+        let RefValue::DateTime(a) = g.input("a".to_string(), datetime.clone()) else {
+            unreachable!()
+        };
+        let RefValue::DateTime(b) = g.input("b".to_string(), datetime) else {
+            unreachable!()
+        };
+        let RefValue::Bool(flag) = g.input("flag".to_string(), Layout::Bool) else {
+            unreachable!()
+        };
+        let comparisons = vec![
+            g.insert(op::Eq(None), vec![a, b]).unwrap(),
+            g.insert(op::Lt, vec![a, b]).unwrap(),
+            g.insert(op::Le, vec![a, b]).unwrap(),
+            g.insert(op::Gt, vec![a, b]).unwrap(),
+            g.insert(op::Ge, vec![a, b]).unwrap(),
+            g.insert(op::Eq(None), vec![flag, flag]).unwrap(),
+        ];
+        g.output(
+            RefValue::List(comparisons.into_iter().map(RefValue::Bool).collect()),
+            Layout::List(Box::new(Layout::Bool), 6),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+
+        let eval = |a: &str, b: &str| -> Vec<bool> {
+            func.eval(&serde_json::json!({ "a": a, "b": b, "flag": true }))
+                .unwrap()
+        };
+        let before = "1969-07-20T20:17:40";
+        let after = "2024-01-01T00:00:00";
+        // Dates before the epoch have negative timestamps.
+        assert_eq!(eval(before, after), [false, true, true, false, false, true]);
+        assert_eq!(eval(after, before), [false, false, false, true, true, true]);
+        assert_eq!(eval(after, after), [true, false, true, false, true, true]);
+
+        // Constants are compared at compile time.
+        let mut g = Graph::new();
+        let early = Ref::Const(Type::DateTime, (-1i64) as u64);
+        let late = Ref::Const(Type::DateTime, 1);
+        let lt = g.insert(op::Lt, vec![early, late]).unwrap();
+        let eq = g.insert(op::Eq(None), vec![early, late]).unwrap();
+        g.output(
+            RefValue::Tuple(vec![RefValue::Bool(lt), RefValue::Bool(eq)]),
+            Layout::Tuple(vec![Layout::Bool, Layout::Bool]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+        let mut optimized = g.clone();
+        optimized.prune_unused().unwrap();
+        assert_eq!(optimized.nodes().len(), 0);
+        let out: (bool, bool) = func.eval(&serde_json::json!({})).unwrap();
+        assert_eq!(out, (true, false));
+    }
+
+    fn create_mapping_graph() -> Graph {
+        let mut g = Graph::new();
+        let x = g.input("x".to_string(), Layout::Scalar);
+        g.insert_mapping(
+            "double".to_string(),
+            Layout::Scalar,
+            Layout::Scalar,
+            mapping::HashMapStorage,
+            (0..10).map(|i| Ok::<_, super::Error>((i as f64, 2.0 * i as f64))),
+        )
+        .unwrap();
+        let default = RefValue::Scalar(g.r#const(-1.0));
+        let doubled = g.call_mapping_default("double", x, default).unwrap();
+        g.output(doubled, Layout::Scalar).unwrap();
+
+        g
+    }
+
+    #[test]
+    fn test_function_from_assembly() {
+        let input = serde_json::json!({"a": 2.0, "b": 3.0});
+
+        // Round trip through persisted assembly:
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("simple.s");
+        std::fs::write(&path, create_simple_graph().render_assembly().unwrap()).unwrap();
+        let assembly = std::fs::read_to_string(&path).unwrap();
+
+        let func = unsafe { Function::from_assembly(&create_simple_graph(), &assembly) }.unwrap();
+        assert_eq!(func.eval::<_, f64>(&input).unwrap(), 6.0);
+
+        // Different sizes:
+        let err = unsafe { Function::from_assembly(&create_abs_graph(), &assembly) }
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("bytes of input"), "{err}");
+
+        // Same sizes, different code:
+        let mut other = Graph::new();
+        let RefValue::Scalar(a) = other.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(b) = other.input("b".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let product = other.insert(op::Mul, vec![a, b]).unwrap();
+        other
+            .output(RefValue::Scalar(product), Layout::Scalar)
+            .unwrap();
+        assert!(unsafe { Function::from_assembly(&other, &assembly) }.is_err());
+
+        // Mappings are hardcoded, so the assembly is only valid for the same graph:
+        let graph = create_mapping_graph();
+        let assembly = graph.render_assembly().unwrap();
+        let func = unsafe { Function::from_assembly(&graph, &assembly) }.unwrap();
+        let out: f64 = func.eval(&serde_json::json!({ "x": 4.0 })).unwrap();
+        assert_eq!(out, 8.0);
+        assert!(unsafe { Function::from_assembly(&create_mapping_graph(), &assembly) }.is_err());
+
+        // Code calling into the runtime is refused in other processes; other code is not:
+        let token = crate::graph::process_token().to_string();
+        let from_other_process = |assembly: &str| {
+            assert!(assembly.contains(&token));
+            assembly.replace(&token, &(crate::graph::process_token() ^ 1).to_string())
+        };
+        let err = unsafe { Function::from_assembly(&graph, &from_other_process(&assembly)) }
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("another process"), "{err}");
+        let assembly = from_other_process(&create_simple_graph().render_assembly().unwrap());
+        let func = unsafe { Function::from_assembly(&create_simple_graph(), &assembly) }.unwrap();
+        assert_eq!(func.eval::<_, f64>(&input).unwrap(), 6.0);
+    }
+
+    #[test]
+    fn test_function_from_shared_object() {
+        let graph = create_simple_graph();
+        let options = CompileOptions {
+            debug_info: true,
+            ..CompileOptions::default()
+        };
+        let compiled = graph.compile_with("run", &options).unwrap();
+        let path = compiled.shared_object_path().unwrap();
+
+        let func = unsafe { Function::from_shared_object(&graph, path) }.unwrap();
+        drop(compiled);
+        let input = serde_json::json!({"a": 2.0, "b": 3.0});
+        assert_eq!(func.eval::<_, f64>(&input).unwrap(), 6.0);
+        assert!(func.shared_object_path().is_none());
+
+        let abs = create_abs_graph().compile_with("run", &options).unwrap();
+        let abs_path = abs.shared_object_path().unwrap();
+        assert!(unsafe { Function::from_shared_object(&graph, abs_path) }.is_err());
+        assert!(unsafe {
+            Function::from_shared_object(&graph, std::path::Path::new("/nonexistent"))
+        }
+        .is_err());
+        let not_an_object = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(not_an_object.path(), b"not a shared object").unwrap();
+        assert!(unsafe { Function::from_shared_object(&graph, not_an_object.path()) }.is_err());
+    }
+
+    #[test]
+    fn test_const_mapping_lookup() {
+        let value_layout = Layout::Tuple(vec![Layout::Scalar, Layout::Int]);
+        let build = |size: i64, f32_values: bool| {
+            let mut g = Graph::new();
+            let items = (0..size).map(|i| Ok::<_, super::Error>((i as f64, (i as f64 / 2.0, i))));
+            if f32_values {
+                g.insert_mapping(
+                    "m".to_string(),
+                    Layout::Scalar,
+                    value_layout.clone(),
+                    mapping::F32HashMapStorage,
+                    items,
+                )
+            } else {
+                g.insert_mapping(
+                    "m".to_string(),
+                    Layout::Scalar,
+                    value_layout.clone(),
+                    mapping::HashMapStorage,
+                    items,
+                )
+            }
+            .unwrap();
+            g
+        };
+
+        // Hits are resolved and the lookup goes away, mapping included:
+        for f32_values in [false, true] {
+            let mut g = build(10, f32_values);
+            let key = RefValue::Scalar(g.r#const(3.0));
+            let value = g.call_mapping("m", key).unwrap();
+            g.output(value, value_layout.clone()).unwrap();
+            let func = g.compile().unwrap();
+            let mut optimized = g.clone();
+            optimized.prune_unused().unwrap();
+            assert_eq!(optimized.nodes().len(), 0);
+            assert!(optimized.mappings().is_empty());
+            // The function still needs the mapping to be compiled again from its graph:
+            assert!(func.graph().mappings().contains_key("m"));
+            let out: (f64, i64) = func.eval(&serde_json::json!({})).unwrap();
+            assert_eq!(out, (1.5, 3));
+        }
+
+        // Misses fall back to the default or fail to compile:
+        let mut g = build(10, false);
+        let key = RefValue::Scalar(g.r#const(42.0));
+        let default = RefValue::Tuple(vec![
+            RefValue::Scalar(g.r#const(-1.0)),
+            RefValue::Int(g.r#const(-1i64)),
+        ]);
+        let value = g.call_mapping_default("m", key.clone(), default).unwrap();
+        let RefValue::Scalar(contains) = g.mapping_contains("m", key.clone()).unwrap() else {
+            unreachable!()
+        };
+        g.output(
+            RefValue::Tuple(vec![value, RefValue::Bool(contains)]),
+            Layout::Tuple(vec![value_layout.clone(), Layout::Bool]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+        let mut optimized = g.clone();
+        optimized.prune_unused().unwrap();
+        assert_eq!(optimized.nodes().len(), 0);
+        let out: ((f64, i64), bool) = func.eval(&serde_json::json!({})).unwrap();
+        assert_eq!(out, ((-1.0, -1), false));
+
+        let value = g.call_mapping("m", key).unwrap();
+        g.output(value, value_layout.clone()).unwrap();
+        assert!(matches!(
+            g.compile(),
+            Err(super::Error::IllegalInstruction(_))
+        ));
+
+        // Big mappings are looked up at runtime:
+        let mut g = build(op::MAX_CONST_LOOKUP_LEN as i64 + 1, false);
+        let key = RefValue::Scalar(g.r#const(3.0));
+        let value = g.call_mapping("m", key).unwrap();
+        g.output(value, value_layout.clone()).unwrap();
+        let func = g.compile().unwrap();
+        let mut optimized = g.clone();
+        optimized.prune_unused().unwrap();
+        assert!(!optimized.nodes().is_empty());
+        let out: (f64, i64) = func.eval(&serde_json::json!({})).unwrap();
+        assert_eq!(out, (1.5, 3));
+    }
+
+    #[test]
+    fn test_assert_origin() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let positive = g.insert(op::Ge, vec![x, 0.0.into()]).unwrap();
+        g.assert_with_origin(
+            positive,
+            "x must be positive".to_string(),
+            "model.py:12".to_string(),
+        )
+        .unwrap();
+        let small = g.insert(op::Le, vec![x, 10.0.into()]).unwrap();
+        g.assert(small, "x must be small".to_string()).unwrap();
+        g.output(RefValue::Scalar(x), Layout::Scalar).unwrap();
+        assert_eq!(g.error_origin(0), Some("model.py:12"));
+        assert_eq!(g.error_origin(1), None);
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        g.dump(&mut dumped).unwrap();
+        let g = Graph::load(dumped).unwrap();
+        assert_eq!(g.error_origin(0), Some("model.py:12"));
+
+        let func = g.compile().unwrap();
+        let Err(super::Error::StatusRaised(err)) =
+            func.eval::<_, f64>(&serde_json::json!({ "x": -1.0 }))
+        else {
+            panic!("assertion did not fail")
+        };
+        assert_eq!(err.to_str().unwrap(), "x must be positive (at model.py:12)");
+        let err = func
+            .eval::<_, f64>(&serde_json::json!({ "x": 11.0 }))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, r#"function raised status: "x must be small""#);
+
+        // Origins of the errors of subgraphs are also dumped:
+        let mut outer = Graph::new();
+        let x = outer.input("x".to_string(), Layout::Scalar);
+        let graph_id = outer.insert_subgraph(g).unwrap();
+        let args = RefValue::Struct(HashMap::from([("x".to_string(), x)]));
+        let out = outer.call_graph(graph_id, args).unwrap();
+        outer.output(out, Layout::Scalar).unwrap();
+        let mut dumped = std::io::Cursor::new(vec![]);
+        outer.dump(&mut dumped).unwrap();
+        let outer = Graph::load(dumped).unwrap();
+        assert_eq!(outer.subgraphs[0].error_origin(0), Some("model.py:12"));
+        assert_eq!(outer.subgraphs[0].error_origin(1), None);
+    }
+
+    #[test]
+    fn test_run_reduce() {
+        const N: usize = 100;
+        type Binary = fn(&mut Graph, Ref, Ref) -> Ref;
+        let kinds: [(op::ReduceKind, Binary); 4] = [
+            (op::ReduceKind::Sum, |g, x, y| {
+                g.insert(op::Add, vec![x, y]).unwrap()
+            }),
+            (op::ReduceKind::Product, |g, x, y| {
+                g.insert(op::Mul, vec![x, y]).unwrap()
+            }),
+            (op::ReduceKind::Max, |g, x, y| {
+                g.insert(op::Max, vec![x, y]).unwrap()
+            }),
+            (op::ReduceKind::Min, |g, x, y| {
+                g.insert(op::Min, vec![x, y]).unwrap()
+            }),
+        ];
+        let a = (0..N)
+            .map(|i| 1.0 + (i as f64 - 50.0) / 512.0)
+            .collect::<Vec<_>>();
+        let b = (0..N).map(|i| (i % 7) as f64 - 3.0).collect::<Vec<_>>();
+        let input = a.iter().chain(&b).copied().collect::<Vec<f64>>();
+
+        let build = |constant: bool| {
+            let mut g = Graph::new();
+            let (xs, ys) = if constant {
+                (
+                    a.iter().map(|&x| x.into()).collect::<Vec<Ref>>(),
+                    b.iter().map(|&y| y.into()).collect::<Vec<Ref>>(),
+                )
+            } else {
+                let list = Layout::List(Box::new(Layout::Scalar), N);
+                let RefValue::List(xs) = g.input("a".to_string(), list.clone()) else {
+                    unreachable!()
+                };
+                let RefValue::List(ys) = g.input("b".to_string(), list) else {
+                    unreachable!()
+                };
+                let unwrap = |list: Vec<RefValue>| {
+                    list.into_iter()
+                        .map(|x| match x {
+                            RefValue::Scalar(x) => x,
+                            _ => unreachable!(),
+                        })
+                        .collect::<Vec<_>>()
+                };
+                (unwrap(xs), unwrap(ys))
+            };
+
+            let mut outputs = vec![];
+            for (kind, binary) in &kinds {
+                outputs.push(g.reduce(*kind, xs.clone()).unwrap());
+                let mut chained = xs[0];
+                for &x in &xs[1..] {
+                    chained = binary(&mut g, chained, x);
+                }
+                outputs.push(chained);
+            }
+            outputs.push(g.dot(xs.clone(), ys.clone()).unwrap());
+            let mut chained = Ref::from(0.0);
+            for (&x, &y) in xs.iter().zip(&ys) {
+                let product = g.insert(op::Mul, vec![x, y]).unwrap();
+                chained = g.insert(op::Add, vec![chained, product]).unwrap();
+            }
+            outputs.push(chained);
+
+            let n_outputs = outputs.len();
+            g.output(
+                RefValue::List(outputs.into_iter().map(RefValue::Scalar).collect()),
+                Layout::List(Box::new(Layout::Scalar), n_outputs),
+            )
+            .unwrap();
+            g
+        };
+
+        for constant in [false, true] {
+            let mut g = build(constant);
+            assert!(g.dot(vec![1.0.into()], vec![]).is_err());
+            let func = g.compile().unwrap();
+            if constant {
+                let mut optimized = g.clone();
+                optimized.prune_unused().unwrap();
+                assert!(optimized.nodes().is_empty());
+            }
+
+            let input = if constant { vec![] } else { input.clone() };
+            let output = func.eval_raw(input.as_byte_slice()).unwrap();
+            let output = output.as_slice_of::<f64>().unwrap();
+            for pair in output.chunks(2) {
+                let (reduced, chained) = (pair[0], pair[1]);
+                assert!(
+                    (reduced - chained).abs() <= 1e-12 * chained.abs(),
+                    "{reduced} != {chained}"
+                );
+            }
+        }
+
+        let mut g = Graph::new();
+        let max = g.reduce(op::ReduceKind::Max, vec![]);
+        assert!(max.is_err());
+        let sum = g.reduce(op::ReduceKind::Sum, vec![]).unwrap();
+        g.output(RefValue::Scalar(sum), Layout::Scalar).unwrap();
+        let out: f64 = g.compile().unwrap().eval(&serde_json::json!({})).unwrap();
+        assert_eq!(out, 0.0);
+    }
+
+    /// Rewrites the entry `name` of a dumped graph archive with `contents`, keeping
+    /// everything else. The entry is removed if `contents` is `None`.
+    fn rewrite_archive(dumped: &[u8], name: &str, contents: Option<&[u8]>) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(dumped)).unwrap();
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        for id in 0..archive.len() {
+            let file = archive.by_index(id).unwrap();
+            if file.name() == name {
+                continue;
+            }
+            writer.raw_copy_file(file).unwrap();
+        }
+        if let Some(contents) = contents {
+            writer
+                .start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_load_checks_sizes_and_recomputes_estimate() {
+        let mut graph = create_simple_graph();
+        // As if dumped on another architecture.
+        graph
+            .metadata_mut()
+            .insert(MEM_SIZE_ESTIMATE_KEY.to_string(), "123456789".to_string());
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        let dumped = dumped.into_inner();
+
+        let loaded = Graph::load(std::io::Cursor::new(&dumped)).unwrap();
+        let estimate: usize = loaded.metadata()[MEM_SIZE_ESTIMATE_KEY].parse().unwrap();
+        assert_ne!(estimate, 123456789);
+        assert_eq!(
+            loaded.render().unwrap().to_string(),
+            graph.render().unwrap().to_string()
+        );
+
+        let input = serde_json::json!({ "a": 5.0, "b": 6.0 });
+        let expected: f64 = graph.compile().unwrap().eval(&input).unwrap();
+        let out: f64 = loaded.compile().unwrap().eval(&input).unwrap();
+        assert_eq!(out, expected);
+
+        let with_sizes = |sizes: serde_json::Value| {
+            rewrite_archive(&dumped, "sizes.json", Some(sizes.to_string().as_bytes()))
+        };
+        let small_slots = with_sizes(serde_json::json!({
+            "slot_size": 4,
+            "input_size": 8,
+            "output_size": 4,
+        }));
+        let err = Graph::load(std::io::Cursor::new(small_slots)).unwrap_err();
+        assert!(err.to_string().contains("slots of 4 bytes"), "{err}");
+
+        let wrong_input = with_sizes(serde_json::json!({
+            "slot_size": 8,
+            "input_size": 24,
+            "output_size": 8,
+        }));
+        assert!(Graph::load_uninitialized(std::io::Cursor::new(wrong_input)).is_err());
+    }
+
+    #[test]
+    fn test_dump_format_version() {
+        use std::io::Read;
+
+        let graph = create_simple_graph();
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        let dumped = dumped.into_inner();
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&dumped)).unwrap();
+        let mut version = String::new();
+        archive
+            .by_name("VERSION")
+            .unwrap()
+            .read_to_string(&mut version)
+            .unwrap();
+        assert_eq!(version, DUMP_FORMAT_VERSION.to_string());
+        Graph::load(std::io::Cursor::new(&dumped)).unwrap();
+
+        let with_version =
+            |version: Option<&str>| rewrite_archive(&dumped, "VERSION", version.map(str::as_bytes));
+
+        // Archives from before versioning are still read.
+        let unversioned = with_version(None);
+        let loaded = Graph::load(std::io::Cursor::new(&unversioned)).unwrap();
+        assert_eq!(
+            loaded.render().unwrap().to_string(),
+            graph.render().unwrap().to_string()
+        );
+
+        let future = with_version(Some(&(DUMP_FORMAT_VERSION + 1).to_string()));
+        let err = Graph::load(std::io::Cursor::new(&future)).unwrap_err();
+        assert!(matches!(err, crate::Error::Other(_)), "{err}");
+        assert!(
+            err.to_string().contains(&format!(
+                "format version {}, but this build of jyafn only reads versions up to {}",
+                DUMP_FORMAT_VERSION + 1,
+                DUMP_FORMAT_VERSION
+            )),
+            "{err}"
+        );
+        assert!(Graph::load_uninitialized(std::io::Cursor::new(&future)).is_err());
+        assert!(Graph::load_metadata(std::io::Cursor::new(&future)).is_err());
+        assert!(Graph::load_source(std::io::Cursor::new(&future)).is_err());
+        assert!(Graph::load_from_slice(&future).is_err());
+
+        let garbage = with_version(Some("one"));
+        let err = Graph::load(std::io::Cursor::new(&garbage)).unwrap_err();
+        assert!(err.to_string().contains("invalid format version"), "{err}");
+    }
+
+    #[test]
+    fn test_pfunc_snapshot_restore() {
+        extern "C" fn triple(x: f64) -> f64 {
+            3.0 * x
+        }
+
+        let inscribe = |name| unsafe {
+            pfunc::inscribe(name, triple as *const (), &[Type::Float], Type::Float)
+        };
+
+        let outer = pfunc::snapshot();
+        inscribe("test_snapshot_outer").unwrap();
+        let inner = pfunc::snapshot();
+        inscribe("test_snapshot_inner").unwrap();
+
+        let mut graph = Graph::new();
+        let RefValue::Scalar(x) = graph.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let out = graph.call_pfunc("test_snapshot_inner", vec![x]).unwrap()[0];
+        graph.output(RefValue::Scalar(out), Layout::Scalar).unwrap();
+        let func = graph.compile().unwrap();
+
+        pfunc::restore(inner);
+        assert!(pfunc::get("test_snapshot_inner").is_none());
+        assert!(pfunc::get("test_snapshot_outer").is_some());
+
+        // Compiled functions keep working.
+        let out: f64 = func.eval(&serde_json::json!({ "x": 2.0 })).unwrap();
+        assert_eq!(out, 6.0);
+
+        pfunc::restore(outer);
+        assert!(pfunc::get("test_snapshot_outer").is_none());
+
+        // Restoring again does nothing, not even to what was inscribed outside of scopes.
+        inscribe("test_snapshot_unscoped").unwrap();
+        pfunc::restore(inner);
+        pfunc::restore(outer);
+        assert!(pfunc::get("test_snapshot_unscoped").is_some());
+
+        // Restoring an outer snapshot also restores the inner ones, but only removes
+        // what was inscribed by the current thread.
+        let outer = pfunc::snapshot();
+        let _inner = pfunc::snapshot();
+        inscribe("test_snapshot_inner").unwrap();
+        std::thread::spawn(move || inscribe("test_snapshot_other_thread").unwrap())
+            .join()
+            .unwrap();
+        pfunc::restore(outer);
+        assert!(pfunc::get("test_snapshot_inner").is_none());
+        assert!(pfunc::get("test_snapshot_other_thread").is_some());
+    }
+
+    #[test]
+    fn test_run_isnan_isfinite() {
+        let mut graph = Graph::new();
+        let RefValue::Scalar(x) = graph.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let is_nan = graph.insert(op::IsNan, vec![x]).unwrap();
+        let is_finite = graph.insert(op::IsFinite, vec![x]).unwrap();
+        graph
+            .output(
+                RefValue::Tuple(vec![RefValue::Bool(is_nan), RefValue::Bool(is_finite)]),
+                Layout::Tuple(vec![Layout::Bool, Layout::Bool]),
+            )
+            .unwrap();
+        let func = graph.compile().unwrap();
+
+        for x in [
+            1.5,
+            -0.0,
+            f64::MAX,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ] {
+            let out = func.eval_raw(&x.to_ne_bytes()[..]).unwrap();
+            let out: &[u64] = out.as_slice_of().unwrap();
+            assert_eq!(out, [x.is_nan() as u64, x.is_finite() as u64], "for {x}");
+        }
+
+        for x in [1.5, f64::NAN, f64::INFINITY] {
+            let mut graph = Graph::new();
+            let c = graph.r#const(x);
+            assert_eq!(
+                op::IsNan.const_eval(&graph, &[c]),
+                Some(Ref::from(x.is_nan()))
+            );
+            assert_eq!(
+                op::IsFinite.const_eval(&graph, &[c]),
+                Some(Ref::from(x.is_finite()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_graph_diff() {
+        let graph = create_simple_graph();
+        assert!(graph.diff(&graph.clone()).is_empty());
+
+        let mut other = graph.clone();
+        other.input("c".to_string(), Layout::Scalar);
+        let diff = graph.diff(&other);
+        assert_eq!(
+            diff,
+            GraphDiff {
+                added_inputs: vec!["c".to_string()],
+                ..GraphDiff::default()
+            }
+        );
+        assert_eq!(other.diff(&graph).removed_inputs, vec!["c".to_string()]);
+
+        // Reordering the inputs changes the input layout.
+        let mut swapped = Graph::new();
+        let b = swapped.input("b".to_string(), Layout::Scalar);
+        let a = swapped.input("a".to_string(), Layout::Scalar);
+        let (RefValue::Scalar(a), RefValue::Scalar(b)) = (a, b) else {
+            unreachable!()
+        };
+        let sum = swapped.insert(op::Add, vec![a, b]).unwrap();
+        swapped
+            .output(RefValue::Scalar(sum), Layout::Scalar)
+            .unwrap();
+        let diff = graph.diff(&swapped);
+        assert_eq!(
+            diff.moved_inputs,
+            std::collections::BTreeMap::from([
+                (
+                    "a".to_string(),
+                    Changed {
+                        before: 0,
+                        after: 1
+                    }
+                ),
+                (
+                    "b".to_string(),
+                    Changed {
+                        before: 1,
+                        after: 0
+                    }
+                ),
+            ])
+        );
+        assert!(diff.added_inputs.is_empty() && diff.changed_inputs.is_empty());
+
+        let mut other = create_simple_graph();
+        other
+            .metadata_mut()
+            .insert("version".to_string(), "2".to_string());
+        let two = other.r#const(2.0);
+        let out = other.outputs()[0];
+        let doubled = other.insert(op::Mul, vec![out, two]).unwrap();
+        other
+            .output(
+                RefValue::Tuple(vec![RefValue::Scalar(out), RefValue::Scalar(doubled)]),
+                Layout::Tuple(vec![Layout::Scalar, Layout::Scalar]),
+            )
+            .unwrap();
+        let diff = graph.diff(&other);
+        assert_eq!(diff.added_metadata, vec!["version".to_string()]);
+        assert_eq!(
+            diff.node_count,
+            Some(Changed {
+                before: 2,
+                after: 3
+            })
+        );
+        assert_eq!(diff.output_layout.unwrap().before, "scalar");
+        assert!(diff.added_inputs.is_empty() && diff.changed_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_mapping_seed_survives_dump() {
+        fn seeded_graph(seed: u64) -> Graph {
+            let mut graph = Graph::new();
+            graph
+                .insert_mapping_with_seed(
+                    "m".to_string(),
+                    Layout::Scalar,
+                    Layout::Scalar,
+                    mapping::HashMapStorage,
+                    seed,
+                    (0..100).map(|i| Ok::<_, super::Error>((i as f64, 2.0 * i as f64))),
+                )
+                .unwrap();
+            let x = graph.input("x".to_string(), Layout::Scalar);
+            let value = graph.call_mapping("m", x).unwrap();
+            graph.output(value, Layout::Scalar).unwrap();
+            graph
+        }
+
+        // The hashes stored by the mapping, which depend on its seed.
+        fn stored_hashes(graph: &Graph) -> std::collections::BTreeSet<u64> {
+            let table: HashMap<u64, Box<[u8]>> =
+                bincode::deserialize(&graph.mappings()["m"].dump()).unwrap();
+            table.into_keys().collect()
+        }
+
+        let mut graph = seeded_graph(42);
+        let unseeded = seeded_graph(0);
+        assert_ne!(stored_hashes(&graph), stored_hashes(&unseeded));
+
+        let func = graph.compile().unwrap();
+        assert_eq!(
+            func.eval::<_, f64>(&serde_json::json!({"x": 7.0})).unwrap(),
+            14.0
+        );
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        dumped.set_position(0);
+        let loaded = Graph::load(&mut dumped).unwrap();
+        assert_eq!(loaded.mappings()["m"].seed(), 42);
+        assert_eq!(stored_hashes(&loaded), stored_hashes(&graph));
+        assert_eq!(
+            loaded.mappings()["m"].get(&7.0f64.to_ne_bytes()).unwrap(),
+            graph.mappings()["m"].get(&7.0f64.to_ne_bytes()).unwrap()
+        );
+
+        let func = loaded.compile().unwrap();
+        assert_eq!(
+            func.eval::<_, f64>(&serde_json::json!({"x": 7.0})).unwrap(),
+            14.0
+        );
+        assert_eq!(
+            func.eval::<_, f64>(&serde_json::json!({"x": 99.0}))
+                .unwrap(),
+            198.0
+        );
+
+        dumped.set_position(0);
+        let uninitialized = Graph::load_uninitialized(&mut dumped).unwrap();
+        assert_eq!(uninitialized.mappings()["m"].seed(), 42);
+
+        // Seeds of the mappings of subgraphs are kept too.
+        let subgraph = std::mem::replace(&mut graph, Graph::new());
+        graph.insert_subgraph(subgraph).unwrap();
+        graph.insert_subgraph(unseeded).unwrap();
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        dumped.set_position(0);
+        let uninitialized = Graph::load_uninitialized(&mut dumped).unwrap();
+        assert_eq!(uninitialized.subgraphs[0].mappings()["m"].seed(), 42);
+        assert_eq!(uninitialized.subgraphs[1].mappings()["m"].seed(), 0);
+    }
+
+    #[test]
+    fn test_raised_error_id() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let positive = g.insert(op::Gt, vec![x, 0.0.into()]).unwrap();
+        g.assert(positive, "x must be positive".to_string())
+            .unwrap();
+        let small = g.insert(op::Le, vec![x, 10.0.into()]).unwrap();
+        g.assert(small, "x must be small".to_string()).unwrap();
+        g.output(RefValue::Scalar(x), Layout::Scalar).unwrap();
+        g.constrain_input("x", None, Some(100.0)).unwrap();
+        let func = g.compile().unwrap();
+
+        let err = func
+            .eval::<_, f64>(&serde_json::json!({ "x": 11.0 }))
+            .unwrap_err();
+        let Some(error_id) = err.error_id() else {
+            panic!("no error id in {err}")
+        };
+        assert_eq!(g.errors()[error_id], "x must be small");
+        let err = func
+            .eval::<_, f64>(&serde_json::json!({ "x": -1.0 }))
+            .unwrap_err();
+        assert_eq!(err.error_id(), Some(0));
+        assert_eq!(g.errors()[0], "x must be positive");
+
+        // Constraint violations are not among the errors of the graph.
+        let err = func
+            .eval::<_, f64>(&serde_json::json!({ "x": 200.0 }))
+            .unwrap_err();
+        assert_eq!(err.error_id(), None);
+    }
+
+    #[test]
+    fn test_raised_error_id_in_subgraph() {
+        let mut sub = Graph::new();
+        let RefValue::Scalar(a) = sub.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let positive = sub.insert(op::Gt, vec![a, 0.0.into()]).unwrap();
+        sub.assert(positive, "a must be positive".to_string())
+            .unwrap();
+        let small = sub.insert(op::Le, vec![a, 10.0.into()]).unwrap();
+        sub.assert_with_origin(
+            small,
+            "a must be small".to_string(),
+            "model.py:7".to_string(),
+        )
+        .unwrap();
+        sub.output(RefValue::Scalar(a), Layout::Scalar).unwrap();
+
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let finite = g.insert(op::Lt, vec![x, 1e300.into()]).unwrap();
+        g.assert(finite, "x must be finite".to_string()).unwrap();
+        let graph_id = g.insert_subgraph(sub).unwrap();
+        let output = g
+            .call_graph(
+                graph_id,
+                RefValue::Struct(HashMap::from([("a".to_string(), RefValue::Scalar(x))])),
+            )
+            .unwrap();
+        g.output(output, Layout::Scalar).unwrap();
+        let func = g.compile().unwrap();
+
+        let err = func
+            .eval::<_, f64>(&serde_json::json!({ "x": 11.0 }))
+            .unwrap_err();
+        let Some(error_id) = err.error_id() else {
+            panic!("no error id in {err}")
+        };
+        assert_eq!(g.errors()[error_id], "a must be small");
+        assert_eq!(g.error_origin(error_id), Some("model.py:7"));
+        assert!(err.to_string().contains("a must be small (at model.py:7)"));
+
+        let err = func
+            .eval::<_, f64>(&serde_json::json!({ "x": -1.0 }))
+            .unwrap_err();
+        let Some(error_id) = err.error_id() else {
+            panic!("no error id in {err}")
+        };
+        assert_eq!(g.errors()[error_id], "a must be positive");
+    }
+
+    #[test]
+    fn test_assert_topologically_sorted() {
+        let graph = create_simple_graph();
+        graph.assert_topologically_sorted().unwrap();
+
+        let mut json: serde_json::Value = serde_json::from_str(&graph.to_json()).unwrap();
+        let nodes = json["nodes"].as_array_mut().unwrap();
+        assert_eq!(nodes.len(), 2);
+        nodes.swap(0, 1);
+        let graph: Graph = serde_json::from_value(json).unwrap();
+
+        let err = graph.assert_topologically_sorted().unwrap_err().to_string();
+        assert!(err.contains("node 0"), "{err}");
+        let err = graph.compile().unwrap_err().to_string();
+        assert!(err.contains("topsort violated"), "{err}");
+    }
+
+    #[test]
+    fn test_run_masked_select() {
+        let mut g = Graph::new();
+        let RefValue::List(xs) =
+            g.input("xs".to_string(), Layout::List(Box::new(Layout::Scalar), 3))
+        else {
+            unreachable!()
+        };
+        let mask = xs
+            .iter()
+            .map(|x| {
+                let &RefValue::Scalar(x) = x else {
+                    unreachable!()
+                };
+                g.insert(op::Gt, vec![x, 0.0.into()]).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let values = xs
+            .iter()
+            .map(|x| {
+                let &RefValue::Scalar(x) = x else {
+                    unreachable!()
+                };
+                x
+            })
+            .collect::<Vec<_>>();
+        let selected = g.masked_select(mask, values, (-1.0).into()).unwrap();
+
+        // Partially constant mask: only the runtime positions are left to compute.
+        let constant = g
+            .masked_select(
+                vec![true.into(), false.into()],
+                vec![1.0.into(), 2.0.into()],
+                0.0.into(),
+            )
+            .unwrap();
+        assert!(g
+            .masked_select(vec![true.into()], vec![], 0.0.into())
+            .is_err());
+        assert!(g
+            .masked_select(vec![1.0.into()], vec![1.0.into()], 0.0.into())
+            .is_err());
+
+        let layout = Layout::List(Box::new(Layout::Scalar), 5);
+        g.output(
+            RefValue::List(
+                selected
+                    .into_iter()
+                    .chain(constant)
+                    .map(RefValue::Scalar)
+                    .collect(),
+            ),
+            layout,
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+
+        let out: Vec<f64> = func
+            .eval(&serde_json::json!({ "xs": [2.0, -3.0, 4.0] }))
+            .unwrap();
+        assert_eq!(out, vec![2.0, -1.0, 4.0, 1.0, 0.0]);
+        let out: Vec<f64> = func
+            .eval(&serde_json::json!({ "xs": [-2.0, 3.0, 0.0] }))
+            .unwrap();
+        assert_eq!(out, vec![-1.0, 3.0, -1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_rename_input() {
+        let mut graph = Graph::new();
+        let RefValue::Scalar(a) = graph.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(b) = graph.input("b".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let sum = graph.insert(op::Add, vec![a, b]).unwrap();
+        let diff = graph.insert(op::Sub, vec![a, b]).unwrap();
+        graph
+            .output(
+                RefValue::Struct(HashMap::from([
+                    ("sum".to_string(), RefValue::Scalar(sum)),
+                    ("diff".to_string(), RefValue::Scalar(diff)),
+                ])),
+                Layout::Struct(layout::Struct(vec![
+                    ("sum".to_string(), Layout::Scalar),
+                    ("diff".to_string(), Layout::Scalar),
+                ])),
+            )
+            .unwrap();
+        graph.constrain_input("a", Some(0.0), None).unwrap();
+
+        assert!(graph.rename_input("c", "d").is_err());
+        assert!(graph.rename_input("a", "b").is_err());
+        assert!(graph.rename_output_field("sum", "diff").is_err());
+        graph.rename_input("a", "x").unwrap();
+        graph.rename_output_field("sum", "total").unwrap();
+        assert!(graph.input_constraints().unwrap().contains_key("x"));
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        dumped.set_position(0);
+        let loaded = Graph::load(&mut dumped).unwrap();
+        assert_eq!(loaded.input_layout.fields()[0].0, "x");
+
+        let func = loaded.compile().unwrap();
+        assert_eq!(
+            func.eval::<_, serde_json::Value>(&serde_json::json!({"x": 3.0, "b": 1.0}))
+                .unwrap(),
+            serde_json::json!({"total": 4.0, "diff": 2.0})
+        );
+        assert!(func
+            .eval::<_, serde_json::Value>(&serde_json::json!({"x": -3.0, "b": 1.0}))
+            .is_err());
+    }
+
+    #[test]
+    fn test_run_activations() {
+        let mut g = Graph::new();
+        let RefValue::List(xs) =
+            g.input("xs".to_string(), Layout::List(Box::new(Layout::Scalar), 3))
+        else {
+            unreachable!()
+        };
+        let xs = xs
+            .into_iter()
+            .map(|x| {
+                let RefValue::Scalar(x) = x else {
+                    unreachable!()
+                };
+                x
+            })
+            .collect::<Vec<_>>();
+        let probs = g.softmax(xs.clone()).unwrap();
+        let sigmoid = g.insert(op::Sigmoid, vec![xs[0]]).unwrap();
+        let relu = g.insert(op::Relu, vec![xs[0]]).unwrap();
+        let constant = g.softmax(vec![0.0.into(), 2.0f64.ln().into()]).unwrap();
+        assert!(g.softmax(vec![]).is_err());
+
+        let mut outputs = probs;
+        outputs.extend([sigmoid, relu]);
+        outputs.extend(constant);
+        g.output(
+            RefValue::List(outputs.into_iter().map(RefValue::Scalar).collect()),
+            Layout::List(Box::new(Layout::Scalar), 7),
+        )
+        .unwrap();
+
+        let func = g.compile().unwrap();
+        let eval = |xs: [f64; 3]| {
+            func.eval::<_, Vec<f64>>(&serde_json::json!({ "xs": xs }))
+                .unwrap()
+        };
+
+        let reference = |xs: [f64; 3]| {
+            let max = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let exps = xs.map(|x| (x - max).exp());
+            exps.map(|x| x / exps.iter().sum::<f64>())
+        };
+        for xs in [[1.0, 2.0, 3.0], [0.0, 0.0, 0.0], [1000.0, 1001.0, -1000.0]] {
+            let result = eval(xs);
+            assert!((result[..3].iter().sum::<f64>() - 1.0).abs() < 1e-12);
+            for (got, expected) in result[..3].iter().zip(reference(xs)) {
+                assert!((got - expected).abs() < 1e-12, "{got} != {expected}");
+            }
+        }
+
+        let result = eval([-1000.0, 0.0, 0.0]);
+        assert_eq!(result[3..5], [0.0, 0.0]);
+        let result = eval([2.0, 0.0, 0.0]);
+        assert_eq!(result[3], 1.0 / (1.0 + (-2.0f64).exp()));
+        assert_eq!(result[4], 2.0);
+        assert!((result[5] - 1.0 / 3.0).abs() < 1e-12);
+        assert!((result[6] - 2.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_eliminate_common_subexpressions() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(b) = g.input("b".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let sum = g.insert(op::Add, vec![a, b]).unwrap();
+        let same_sum = g.insert(op::Add, vec![a, b]).unwrap();
+        let other_op = g.insert(op::Sub, vec![a, b]).unwrap();
+        let square = g.insert(op::Mul, vec![sum, sum]).unwrap();
+        let same_square = g.insert(op::Mul, vec![same_sum, same_sum]).unwrap();
+        let positive = g.insert(op::Gt, vec![square, 0.0.into()]).unwrap();
+        g.assert(positive, "must be positive".to_string()).unwrap();
+        g.assert(positive, "must be positive".to_string()).unwrap();
+        let out = g.insert(op::Add, vec![square, same_square]).unwrap();
+        let out = g.insert(op::Add, vec![out, other_op]).unwrap();
+        g.output(RefValue::Scalar(out), Layout::Scalar).unwrap();
+
+        let mut merged = g.clone();
+        assert_eq!(merged.eliminate_common_subexpressions(), 2);
+        assert_eq!(merged.nodes().len(), g.nodes().len() - 2);
+        // The assertions are kept, even though they are the same.
+        assert_eq!(
+            merged
+                .nodes()
+                .iter()
+                .filter(|node| node.op_name() == "Assert")
+                .count(),
+            2
+        );
+        assert_eq!(merged.eliminate_common_subexpressions(), 0);
+
+        let func = g.compile().unwrap();
+        assert_eq!(func.opt_stats().unwrap().subexpressions_merged, 2);
+        let out: f64 = func
+            .eval(&serde_json::json!({ "a": 2.0, "b": 1.0 }))
+            .unwrap();
+        assert_eq!(out, 19.0);
+        assert_eq!(
+            merged
+                .compile()
+                .unwrap()
+                .eval::<_, f64>(&serde_json::json!({ "a": 2.0, "b": 1.0 }))
+                .unwrap(),
+            19.0
+        );
+    }
+
+    #[test]
+    fn test_run_map_get() {
+        let mut g = Graph::new();
+        let features = g.input(
+            "features".to_string(),
+            Layout::Map(Box::new(Layout::Scalar)),
+        );
+        let points = g.input(
+            "points".to_string(),
+            Layout::Map(Box::new(Layout::Struct(
+                crate::r#struct!("x": scalar, "y": int),
+            ))),
+        );
+        let RefValue::Symbol(key) = g.input("key".to_string(), Layout::Symbol) else {
+            unreachable!()
+        };
+        let a = g.push_symbol("a".to_string());
+        let by_name = g.map_get(&features, a).unwrap();
+        let by_input = g.map_get(&features, key).unwrap();
+        let point = g.map_get(&points, a).unwrap();
+        assert!(g.map_get(&RefValue::Scalar(a), a).is_err());
+        assert!(g.map_get(&features, Ref::from(1.0)).is_err());
+        g.output(
+            RefValue::Tuple(vec![by_name, by_input, point]),
+            Layout::Tuple(vec![
+                crate::layout!(?scalar),
+                crate::layout!(?scalar),
+                Layout::Optional(Box::new(Layout::Struct(
+                    crate::r#struct!("x": scalar, "y": int),
+                ))),
+            ]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+
+        let eval = |input: serde_json::Value| func.eval::<_, serde_json::Value>(&input).unwrap();
+        assert_eq!(
+            eval(serde_json::json!({ "features": {}, "points": {}, "key": "a" })),
+            serde_json::json!([null, null, null])
+        );
+        assert_eq!(
+            eval(serde_json::json!({
+                "features": { "a": 1.5 },
+                "points": { "b": { "x": 1, "y": 2 } },
+                "key": "b",
+            })),
+            serde_json::json!([1.5, null, null])
+        );
+        let features = (0..50)
+            .map(|i| (format!("f{i}"), serde_json::json!(i)))
+            .chain([("a".to_string(), serde_json::json!(-1.0))])
+            .collect::<serde_json::Map<_, _>>();
+        assert_eq!(
+            eval(serde_json::json!({
+                "features": features,
+                "points": { "b": { "x": 1, "y": 2 }, "a": { "x": 3, "y": 4 } },
+                "key": "f42",
+            })),
+            serde_json::json!([-1.0, 42.0, { "x": 3.0, "y": 4 }])
+        );
+
+        // Raw inputs are longer than the input size, but entries must be inside them.
+        let mut symbols = func.graph().symbols().clone();
+        let input = serde_json::json!({ "features": { "a": 2.0 }, "points": {}, "key": "a" });
+        let encoded = func.input_layout().encode(&input, &mut symbols).unwrap();
+        assert_eq!(encoded.len(), func.input_size().in_bytes() + 16);
+        let mut output = vec![0; func.output_size().in_bytes()];
+        func.try_call_raw(&encoded, &mut output).unwrap();
+        let err = func
+            .try_call_raw(&encoded[..encoded.len() - 8], &mut output)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("out of bounds") || err.contains("has only"),
+            "{err}"
+        );
+        assert!(func.eval_raw_batch(&encoded, 1).is_err());
+    }
+
+    #[test]
+    fn test_map_constraints() {
+        let mut g = Graph::new();
+        let features = g.pinnable_input(
+            "features".to_string(),
+            Layout::Map(Box::new(Layout::Scalar)),
+        );
+        let err = g
+            .output(features.clone(), Layout::Map(Box::new(Layout::Scalar)))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("map"), "{err}");
+        assert!(g.constrain_input("features", Some(0.0), None).is_err());
+        let err = g
+            .pin([("features", &serde_json::json!({ "a": 1.0 }))])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("maps cannot be pinned"), "{err}");
+
+        let mut caller = Graph::new();
+        let err = caller.insert_subgraph(g).unwrap_err().to_string();
+        assert!(
+            err.contains("maps are not allowed in subgraph inputs"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_export_c_artifact() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let RefValue::List(bs) = g.input("bs".to_string(), crate::layout!([scalar; 3])) else {
+            unreachable!()
+        };
+        let RefValue::Scalar(b) = bs[1] else {
+            unreachable!()
+        };
+        let sum = g.insert(op::Add, vec![a, b]).unwrap();
+        let prod = g.insert(op::Mul, vec![a, b]).unwrap();
+        g.output(
+            RefValue::List(vec![RefValue::Scalar(sum), RefValue::Scalar(prod)]),
+            crate::layout!([scalar; 2]),
+        )
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let artifact = g.export_c_artifact(dir.path()).unwrap();
+        assert_eq!(artifact.header, dir.path().join("run.h"));
+
+        let header = std::fs::read_to_string(&artifact.header).unwrap();
+        assert!(header.contains("#define RUN_INPUT_SIZE 32\n"), "{header}");
+        assert!(header.contains("#define RUN_OUTPUT_SIZE 16\n"), "{header}");
+        assert!(header.contains("const void* run(const uint8_t* in, uint8_t* out);"));
+
+        let sidecar: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&artifact.sidecar).unwrap()).unwrap();
+        assert_eq!(sidecar["input_size"], 32);
+        assert_eq!(sidecar["output_size"], 16);
+        let input_layout: Layout = serde_json::from_value(sidecar["input_layout"].clone()).unwrap();
+        assert_eq!(input_layout, Layout::Struct(g.input_layout.clone()));
+
+        let object = std::fs::read(&artifact.object).unwrap();
+        #[cfg(target_os = "linux")]
+        assert_eq!(&object[..4], b"\x7fELF");
+        assert!(!object.is_empty());
+
+        // Errors are created by the runtime.
+        let mut g = Graph::new();
+        let RefValue::Scalar(a) = g.input("a".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let zero = g.r#const(0.0);
+        let positive = g.insert(op::Gt, vec![a, zero]).unwrap();
+        g.assert(positive, "a is not positive".to_string()).unwrap();
+        g.output(RefValue::Scalar(a), Layout::Scalar).unwrap();
+        let err = g.export_c_artifact(dir.path()).unwrap_err().to_string();
+        assert!(err.contains("calls into the jyafn runtime"), "{err}");
+
+        // Maps have no fixed size in the input buffer.
+        let mut g = Graph::new();
+        g.input(
+            "features".to_string(),
+            Layout::Map(Box::new(Layout::Scalar)),
+        );
+        g.output(RefValue::Scalar(1.0.into()), Layout::Scalar)
+            .unwrap();
+        let err = g.export_c_artifact(dir.path()).unwrap_err().to_string();
+        assert!(err.contains("maps in the input"), "{err}");
+    }
+
+    #[test]
+    fn test_run_rounding() {
+        let mut g = Graph::new();
+        let RefValue::Scalar(x) = g.input("x".to_string(), Layout::Scalar) else {
+            unreachable!()
+        };
+        let floor = g.insert(op::Floor, vec![x]).unwrap();
+        let ceil = g.insert(op::Ceil, vec![x]).unwrap();
+        let round = g.insert(op::Round, vec![x]).unwrap();
+        let trunc = g.insert(op::Trunc, vec![x]).unwrap();
+        g.output(
+            RefValue::List(
+                [floor, ceil, round, trunc]
+                    .into_iter()
+                    .map(RefValue::Scalar)
+                    .collect(),
+            ),
+            crate::layout!([scalar; 4]),
+        )
+        .unwrap();
+        let func = g.compile().unwrap();
+
+        let values = [
+            0.0,
+            -0.0,
+            0.5,
+            -0.5,
+            1.5,
+            -1.5,
+            2.5,
+            -2.5,
+            -0.25,
+            -1.0,
+            -3.7,
+            3.7,
+            0.49999999999999994,
+            -0.49999999999999994,
+            4503599627370495.5,
+            -4503599627370495.5,
+            1e300,
+            -1e300,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+        ];
+        for x in values {
+            let expected = [x.floor(), x.ceil(), x.round_ties_even(), x.trunc()];
+            let mut output = [0u8; 32];
+            func.try_call_raw(x.to_ne_bytes(), &mut output).unwrap();
+            for (found, expected) in output.chunks(8).zip(expected) {
+                let found = f64::from_ne_bytes(found.try_into().unwrap());
+                assert!(
+                    found.to_bits() == expected.to_bits() || (found.is_nan() && expected.is_nan()),
+                    "{x}: {found} != {expected}"
+                );
+            }
+
+            let x = Ref::from(x);
+            for (op, expected) in [
+                (op::Floor.const_eval(&g, &[x]), expected[0]),
+                (op::Ceil.const_eval(&g, &[x]), expected[1]),
+                (op::Round.const_eval(&g, &[x]), expected[2]),
+                (op::Trunc.const_eval(&g, &[x]), expected[3]),
+            ] {
+                let found = op.and_then(Ref::as_f64).unwrap();
+                assert!(found.to_bits() == expected.to_bits() || found.is_nan());
+            }
+        }
+
+        let mut g = Graph::new();
+        let RefValue::Int(i) = g.input("i".to_string(), Layout::Int) else {
+            unreachable!()
+        };
+        assert!(g.insert(op::Floor, vec![i]).is_err());
+    }
 }
@@ -0,0 +1,388 @@
+use byte_slice_cast::*;
+use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{BufRead, BufReader};
+use zip::read::ZipFile;
+
+use crate::layout::Layout;
+use crate::{Error, Type};
+
+use super::{Storage, StorageType};
+
+/// A [`StorageType`] implementation of a compact sorted table, dumped as CSV. All entries
+/// live in a single contiguous buffer of fixed-size records sorted by key hash and are
+/// looked up by binary search. This trades some lookup latency for a much smaller heap
+/// footprint than [`HashMapStorage`](super::HashMapStorage), which allocates every value
+/// separately.
+///
+/// The dump is a CSV file with a header. The first column is the hash of the key and the
+/// other columns are the slots of the value, named after their path in the value layout
+/// and annotated with their type, e.g. `hash,score:scalar,tags.0:symbol`. Reading a dump
+/// fails if its header does not match the value layout of the storage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsvStorage {
+    value_layout: Layout,
+}
+
+impl CsvStorage {
+    /// Creates a new storage type for values of the given layout.
+    pub fn new(value_layout: Layout) -> CsvStorage {
+        CsvStorage { value_layout }
+    }
+
+    /// The expected CSV header for this storage.
+    fn header(&self) -> Vec<String> {
+        let mut header = vec!["hash".to_string()];
+        let mut columns = vec![];
+        value_columns(&self.value_layout, &mut String::new(), &mut columns);
+        header.extend(columns.into_iter().map(|(path, ty)| format!("{path}:{ty}")));
+        header
+    }
+}
+
+#[typetag::serde]
+impl StorageType for CsvStorage {
+    fn init(&self) -> Result<Box<dyn Storage>, Error> {
+        Ok(Box::new(CsvTable::new(self)))
+    }
+
+    fn read(&self, f: ZipFile<'_>) -> Result<Box<dyn Storage>, Error> {
+        // Records are parsed as lines are read, so that the dump is never entirely
+        // buffered in memory alongside the table.
+        let mut table = CsvTable::new(self);
+        let mut lines = BufReader::new(f).lines().enumerate();
+
+        let header = lines
+            .next()
+            .map(|(_, line)| parse_line(&line?))
+            .transpose()?
+            .unwrap_or_default();
+        if header != table.header {
+            return Err(format!(
+                "CSV header {header:?} does not match value layout {}: expected {:?}",
+                self.value_layout, table.header,
+            )
+            .into());
+        }
+
+        for (line_no, line) in lines {
+            let fields = parse_line(&line?)?;
+            if fields.len() != table.header.len() {
+                return Err(format!(
+                    "line {} has {} columns, expected {}",
+                    line_no + 1,
+                    fields.len(),
+                    table.header.len()
+                )
+                .into());
+            }
+
+            table
+                .records
+                .push(parse_field(&fields[0], "hash", |s| s.parse().ok())?);
+            for (field, &ty) in fields[1..].iter().zip(&table.slots) {
+                table.records.push(parse_slot(field, ty)?);
+            }
+        }
+
+        table.finish();
+        Ok(Box::new(table))
+    }
+}
+
+/// Lists the primitive columns of a value layout, together with their paths.
+fn value_columns(layout: &Layout, path: &mut String, columns: &mut Vec<(String, Type)>) {
+    let mut push_field = |name: &str, field: &Layout, path: &mut String| {
+        let len = path.len();
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path.push_str(name);
+        value_columns(field, path, columns);
+        path.truncate(len);
+    };
+
+    match layout {
+        Layout::Struct(fields) => {
+            for (name, field) in &fields.0 {
+                push_field(name, field, path);
+            }
+        }
+        Layout::Tuple(fields) => {
+            for (i, field) in fields.iter().enumerate() {
+                push_field(&i.to_string(), field, path);
+            }
+        }
+        Layout::List(element, size) => {
+            for i in 0..*size {
+                push_field(&i.to_string(), element, path);
+            }
+        }
+        _ => {
+            for ty in layout.slots() {
+                columns.push((path.clone(), ty));
+            }
+        }
+    }
+}
+
+/// The sorted table backing a [`CsvStorage`]. Each record is the hash of the key followed
+/// by the slots of the value. Records are kept in slots (not bytes) so that values are
+/// always aligned.
+#[derive(Debug)]
+struct CsvTable {
+    header: Vec<String>,
+    slots: Vec<Type>,
+    records: Vec<u64>,
+}
+
+impl CsvTable {
+    fn new(storage: &CsvStorage) -> CsvTable {
+        CsvTable {
+            header: storage.header(),
+            slots: storage.value_layout.slots(),
+            records: vec![],
+        }
+    }
+
+    fn record_slots(&self) -> usize {
+        1 + self.slots.len()
+    }
+
+    fn n_records(&self) -> usize {
+        self.records.len() / self.record_slots()
+    }
+
+    fn record(&self, i: usize) -> &[u64] {
+        &self.records[i * self.record_slots()..(i + 1) * self.record_slots()]
+    }
+}
+
+impl Storage for CsvTable {
+    fn try_insert(&mut self, hash: u64, value: Box<[u8]>) -> Result<(), Error> {
+        if value.len() != 8 * self.slots.len() {
+            return Err(Error::SizeMismatch {
+                expected: 8 * self.slots.len(),
+                got: value.len(),
+            });
+        }
+        self.records.push(hash);
+        self.records.extend(
+            value
+                .chunks_exact(8)
+                .map(|slot| u64::from_ne_bytes(slot.try_into().expect("slot has 8 bytes"))),
+        );
+        Ok(())
+    }
+
+    fn finish(&mut self) {
+        // Sorts the records by hash. Later inserts win over earlier ones, just like in a
+        // hash map.
+        let mut order = (0..self.n_records())
+            .map(|i| (self.record(i)[0], std::cmp::Reverse(i)))
+            .collect::<Vec<_>>();
+        order.sort_unstable();
+        order.dedup_by_key(|(hash, _)| *hash);
+
+        let mut sorted = Vec::with_capacity(order.len() * self.record_slots());
+        for (_, std::cmp::Reverse(i)) in order {
+            sorted.extend_from_slice(self.record(i));
+        }
+        self.records = sorted;
+    }
+
+    fn n_entries(&self) -> Option<usize> {
+        Some(self.n_records())
+    }
+
+    fn try_get(&self, hash: u64) -> Result<Option<Cow<'_, [u8]>>, Error> {
+        let (mut low, mut high) = (0, self.n_records());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let record = self.record(mid);
+            match record[0].cmp(&hash) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => {
+                    return Ok(Some(Cow::Borrowed(record[1..].as_byte_slice())))
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + std::mem::size_of::<u64>() * self.records.capacity()
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        let mut dump = self
+            .header
+            .iter()
+            .map(|column| quote(column))
+            .collect::<Vec<_>>()
+            .join(",");
+        dump.push('\n');
+
+        for i in 0..self.n_records() {
+            let record = self.record(i);
+            dump.push_str(&record[0].to_string());
+            for (&slot, ty) in record[1..].iter().zip(&self.slots) {
+                dump.push(',');
+                dump.push_str(&format_slot(slot, *ty));
+            }
+            dump.push('\n');
+        }
+
+        dump.into_bytes()
+    }
+}
+
+/// Quotes a CSV field, if necessary.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Parses a single CSV line into its fields. Fields may be quoted with `"`, in which case
+/// they may contain commas and `""` stands for a literal quote. Quoted fields cannot
+/// span multiple lines.
+pub(crate) fn parse_line(line: &str) -> Result<Vec<String>, Error> {
+    let mut fields = vec![];
+    let mut chars = line.chars().peekable();
+
+    loop {
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err(format!("unterminated quote in CSV line {line:?}").into()),
+                }
+            }
+            if !matches!(chars.peek(), None | Some(',')) {
+                return Err(format!("unexpected character after quote in {line:?}").into());
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == ',' {
+                    break;
+                }
+                field.push(c);
+                chars.next();
+            }
+        }
+
+        fields.push(field);
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
+}
+
+fn parse_field<T>(
+    field: &str,
+    what: &str,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Result<T, Error> {
+    parse(field).ok_or_else(|| format!("cannot parse {field:?} as {what}").into())
+}
+
+/// Formats a slot of the given type, to be parsed back by [`parse_slot`].
+fn format_slot(slot: u64, ty: Type) -> String {
+    match ty {
+        Type::Float => f64::from_bits(slot).to_string(),
+        Type::Bool => (slot == 1).to_string(),
+        Type::Int | Type::DateTime => (slot as i64).to_string(),
+        Type::Symbol | Type::Ptr { .. } => slot.to_string(),
+    }
+}
+
+/// Parses a slot of the given type, as written by [`format_slot`].
+fn parse_slot(field: &str, ty: Type) -> Result<u64, Error> {
+    match ty {
+        Type::Float => parse_field(field, "scalar", |s| s.parse::<f64>().ok()).map(f64::to_bits),
+        Type::Bool => parse_field(field, "bool", |s| s.parse::<bool>().ok()).map(u64::from),
+        Type::Int | Type::DateTime => {
+            parse_field(field, &ty.to_string(), |s| s.parse::<i64>().ok()).map(|i| i as u64)
+        }
+        Type::Symbol => parse_field(field, "symbol", |s| s.parse::<u64>().ok()),
+        Type::Ptr { .. } => Err("pointers cannot be stored in mappings".to_string().into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapping::HashMapStorage;
+
+    fn value(slots: &[f64]) -> Box<[u8]> {
+        slots.as_byte_slice().into()
+    }
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(parse_line("a,,b").unwrap(), vec!["a", "", "b"]);
+        assert_eq!(
+            parse_line(r#""a,b","say ""hi""",c"#).unwrap(),
+            vec!["a,b", "say \"hi\"", "c"]
+        );
+        assert!(parse_line(r#""unterminated"#).is_err());
+        assert!(parse_line(r#""a"b"#).is_err());
+    }
+
+    #[test]
+    fn test_get() {
+        let layout = Layout::List(Box::new(Layout::Scalar), 2);
+        let mut table = CsvStorage::new(layout.clone()).init().unwrap();
+        let mut hash_map = HashMapStorage.init().unwrap();
+        for i in 0..100u64 {
+            table
+                .try_insert(i * 7, value(&[i as f64, -(i as f64)]))
+                .unwrap();
+            hash_map
+                .try_insert(i * 7, value(&[i as f64, -(i as f64)]))
+                .unwrap();
+        }
+        table.try_insert(14, value(&[1.5, 2.5])).unwrap();
+        assert!(table.try_insert(15, value(&[1.5])).is_err());
+        table.finish();
+
+        assert_eq!(table.try_get(21).unwrap().unwrap(), &*value(&[3.0, -3.0]));
+        assert_eq!(table.try_get(14).unwrap().unwrap(), &*value(&[1.5, 2.5]));
+        assert!(table.try_get(22).unwrap().is_none());
+        assert!(table.try_get(7 * 100).unwrap().is_none());
+        assert!(table.size() < hash_map.size());
+
+        // Round trip through the dump.
+        let dump = table.dump();
+        assert!(String::from_utf8_lossy(&dump).starts_with("hash,0:scalar,1:scalar\n"));
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        zip.start_file("mapping", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut zip, &dump).unwrap();
+        let mut archive = zip::ZipArchive::new(zip.finish().unwrap()).unwrap();
+        let loaded = CsvStorage::new(layout)
+            .read(archive.by_name("mapping").unwrap())
+            .unwrap();
+        assert_eq!(loaded.try_get(21).unwrap(), table.try_get(21).unwrap());
+        assert!(loaded.try_get(22).unwrap().is_none());
+
+        let mut archive = zip::ZipArchive::new(archive.into_inner()).unwrap();
+        let err = CsvStorage::new(Layout::Scalar)
+            .read(archive.by_name("mapping").unwrap())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("does not match"), "{err}");
+    }
+}
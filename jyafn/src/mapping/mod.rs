@@ -11,19 +11,32 @@
 //! the implementation, this aspect can change in the future, with changes to the
 //! [`Storage`] API in a future version.
 
+mod csv;
+#[cfg(feature = "redis")]
+mod redis;
+
+#[cfg(feature = "redis")]
+pub use self::redis::RedisStorage;
+pub use csv::CsvStorage;
+
+pub(crate) use csv::parse_line as parse_csv_line;
+
 use get_size::GetSize;
 use hashbrown::HashMap;
 use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::ffi::c_char;
 use std::hash::{BuildHasher, Hasher};
 use std::panic::{RefUnwindSafe, UnwindSafe};
 use std::sync::Arc;
 use zip::read::ZipFile;
 
+use crate::graph::SLOT_SIZE;
 use crate::layout::Layout;
 use crate::utils::murmur;
-use crate::Error;
 #[cfg(doc)]
 use crate::Graph;
+use crate::{Error, Type};
 
 /// A hash that doesn't hash. This is intended for operating on already-hashed values.
 #[derive(Debug, Default, Clone, Copy)]
@@ -53,7 +66,8 @@ impl Hasher for UnHasher {
     }
 }
 
-fn update_hash(hash: i64, value: i64) -> i64 {
+/// Updates the murmur hash `hash` with the raw value of a slot.
+pub(crate) fn update_hash(hash: i64, value: i64) -> i64 {
     let hash = u64::from_ne_bytes(hash.to_ne_bytes());
     let value = u64::from_ne_bytes(value.to_ne_bytes());
     let updated = murmur::murmur_hash64a(&u64::to_le_bytes(value), hash);
@@ -61,8 +75,8 @@ fn update_hash(hash: i64, value: i64) -> i64 {
     i64::from_ne_bytes(updated.to_ne_bytes())
 }
 
-fn hash(line: &[u8]) -> u64 {
-    let mut hash = 0u64;
+fn hash(line: &[u8], seed: u64) -> u64 {
+    let mut hash = seed;
 
     for value in line.chunks(8) {
         hash = u64::from_ne_bytes(
@@ -93,17 +107,59 @@ pub trait StorageType: std::fmt::Debug + Send + Sync + UnwindSafe + RefUnwindSaf
     /// The input data is the same that is generated by the corresponding [`Storage::dump`]
     /// implementation.
     fn read(&self, f: ZipFile<'_>) -> Result<Box<dyn Storage>, Error>;
+    /// Whether the scalar slots of the values are stored as `f32` instead of `f64`,
+    /// halving their size at the cost of precision. If so, the values handed to
+    /// [`Storage::insert`] and returned by [`Storage::get`] are packed as described in
+    /// [`Mapping::get`]. By default, this is `false`.
+    fn packs_f32(&self) -> bool {
+        false
+    }
 }
 
 /// A plugable storage instance. This can be a simple hash table (the default
 /// implementation), or something more fancy, as a key-value database.
 pub trait Storage: std::fmt::Debug + Send + Sync + UnwindSafe + RefUnwindSafe {
-    /// Inserts a new value for a given hash in the mapping.
-    fn insert(&mut self, hash: u64, value: Box<[u8]>);
-    /// Gets the value associated with the given hash, if any.
-    fn get(&self, hash: u64) -> Option<&[u8]>;
+    /// Inserts a new value for a given hash in the mapping. By default, this calls
+    /// [`Storage::try_insert`], panicking on errors. Implement at least one of the two.
+    fn insert(&mut self, hash: u64, value: Box<[u8]>) {
+        self.try_insert(hash, value)
+            .expect("failed to insert value in storage")
+    }
+    /// Inserts a new value for a given hash in the mapping, for storages where this can
+    /// fail. This is what mappings use. By default, this calls [`Storage::insert`].
+    fn try_insert(&mut self, hash: u64, value: Box<[u8]>) -> Result<(), Error> {
+        self.insert(hash, value);
+        Ok(())
+    }
+    /// Gets the value associated with the given hash, if any. By default, this calls
+    /// [`Storage::try_get`], returning `None` on errors and on owned values. Implement at
+    /// least one of the two.
+    fn get(&self, hash: u64) -> Option<&[u8]> {
+        match self.try_get(hash) {
+            Ok(Some(Cow::Borrowed(value))) => Some(value),
+            _ => None,
+        }
+    }
+    /// Gets the value associated with the given hash, if any, for storages where this can
+    /// fail. This is what mappings use. Storages that keep their values in memory can
+    /// return them borrowed. Other storages return an owned copy, which is copied to the
+    /// stack of the function doing the lookup. Errors are raised by that function. By
+    /// default, this calls [`Storage::get`].
+    fn try_get(&self, hash: u64) -> Result<Option<Cow<'_, [u8]>>, Error> {
+        Ok(self.get(hash).map(Cow::Borrowed))
+    }
+    /// Called once all the values of a new mapping have been inserted, before it is used.
+    /// This is a chance for the storage to reorganize its data for lookup. By default,
+    /// this does nothing.
+    fn finish(&mut self) {}
     /// The ammount of heap used by this storage.
     fn size(&self) -> usize;
+    /// The number of values in this storage, if it is known and cannot change once the
+    /// mapping is created. Only mappings whose storage implements this are candidates for
+    /// being looked up at compile time. By default, this is `None`.
+    fn n_entries(&self) -> Option<usize> {
+        None
+    }
     /// Dumps the contents of this storage instance as binary data.
     ///
     /// The returned data must be the same that will be consumed by the corresponding
@@ -140,6 +196,10 @@ impl Storage for HashTable {
         self.0.get(&hash).map(|v| v.as_ref())
     }
 
+    fn n_entries(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+
     fn size(&self) -> usize {
         std::mem::size_of::<Self>()
             + std::mem::size_of::<(u64, Box<[u8]>)>() * self.0.raw_table().capacity()
@@ -155,6 +215,36 @@ impl Storage for HashTable {
     }
 }
 
+/// A [`StorageType`] implementation like [`HashMapStorage`], but which stores the scalar
+/// slots of the values as `f32`. Use this for big mappings whose values don't need the
+/// full precision of `f64`, such as feature weights.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct F32HashMapStorage;
+
+#[typetag::serde]
+impl StorageType for F32HashMapStorage {
+    fn init(&self) -> Result<Box<dyn Storage>, Error> {
+        HashMapStorage.init()
+    }
+
+    fn read(&self, f: ZipFile<'_>) -> Result<Box<dyn Storage>, Error> {
+        HashMapStorage.read(f)
+    }
+
+    fn packs_f32(&self) -> bool {
+        true
+    }
+}
+
+/// Where a slot of the value of a mapping is found in the data of its storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SlotPosition {
+    /// The offset of the slot, in bytes.
+    pub offset: usize,
+    /// Whether the slot is a scalar stored as `f32`.
+    pub is_f32: bool,
+}
+
 /// A mapping. Mappings are key-value pairs that can be randomly accessed in functions.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Mapping {
@@ -165,6 +255,15 @@ pub struct Mapping {
     #[serde(skip_deserializing)]
     #[serde(default)]
     storage: Option<Box<dyn Storage>>,
+    /// The initial value of the hash of the keys. This is not part of the graph
+    /// serialization, for compatibility, and is stored in a separate entry of the dump
+    /// (see [`DUMP_FORMAT_VERSION`]).
+    ///
+    /// [`DUMP_FORMAT_VERSION`]: crate::DUMP_FORMAT_VERSION
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    #[serde(default)]
+    seed: u64,
     /// We need this field because we _hardcode_ the pointer to this struct in the
     /// function code. If this moves anywhere, we get the pleasure of accessing bad
     /// memory and The Most Horrible Things™ ensue.
@@ -190,21 +289,29 @@ impl GetSize for Mapping {
 
 impl Mapping {
     /// Creates a new empty mapping out of the input and output types and the supplied
-    /// storage type.
-    pub(crate) fn new<S>(
+    /// storage type. The keys are hashed starting from `seed`.
+    pub(crate) fn new_with_seed<S>(
         key_layout: Layout,
         value_layout: Layout,
         storage_type: S,
+        seed: u64,
     ) -> Result<Mapping, Error>
     where
         S: 'static + StorageType,
     {
+        if key_layout.contains_map() || value_layout.contains_map() {
+            return Err("mapping keys and values cannot contain maps"
+                .to_string()
+                .into());
+        }
+
         let storage = storage_type.init()?;
         Ok(Mapping {
             key_layout,
             value_layout,
             storage_type: Arc::new(storage_type),
             storage: Some(storage),
+            seed,
             _pin: std::marker::PhantomPinned,
         })
     }
@@ -217,6 +324,7 @@ impl Mapping {
             value_layout: self.value_layout.clone(),
             storage_type: self.storage_type.clone(),
             storage: Some(storage),
+            seed: self.seed,
             _pin: std::marker::PhantomPinned,
         })
     }
@@ -245,34 +353,183 @@ impl Mapping {
         &self.value_layout
     }
 
+    /// The seed of the hash of the keys of this mapping.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Sets the seed of the hash of the keys. This is only meant to be used when loading
+    /// a mapping, before any data is read into it.
+    pub(crate) fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
     /// Inserts a new key-value pair in this mapping.
-    pub(crate) fn insert(&mut self, key: Box<[u8]>, value: Box<[u8]>) {
+    pub(crate) fn insert(&mut self, key: Box<[u8]>, value: Box<[u8]>) -> Result<(), Error> {
+        let value = self.pack(value);
+        self.storage
+            .as_mut()
+            .expect("storage not initialized")
+            .try_insert(hash(&key, self.seed), value)
+    }
+
+    /// Signals the storage that all the values of this mapping have been inserted. See
+    /// [`Storage::finish`].
+    pub(crate) fn finish(&mut self) {
         self.storage
             .as_mut()
             .expect("storage not initialized")
-            .insert(hash(&key), value);
+            .finish();
+    }
+
+    /// Where each slot of the value is found in the data of the storage. If the storage
+    /// doesn't pack scalars as `f32`, this is just one slot after the other. Otherwise,
+    /// all the other slots come first, in order, followed by the packed scalars, so that
+    /// every slot stays aligned to its own size.
+    pub(crate) fn value_positions(&self) -> Vec<SlotPosition> {
+        let slots = self.value_layout.slots();
+        if !self.storage_type.packs_f32() {
+            return (0..slots.len())
+                .map(|slot| SlotPosition {
+                    offset: slot * SLOT_SIZE.in_bytes(),
+                    is_f32: false,
+                })
+                .collect();
+        }
+
+        let n_wide = slots.iter().filter(|&&ty| ty != Type::Float).count();
+        let mut next_wide = 0;
+        let mut next_f32 = n_wide * SLOT_SIZE.in_bytes();
+        slots
+            .iter()
+            .map(|&ty| {
+                if ty == Type::Float {
+                    next_f32 += 4;
+                    SlotPosition {
+                        offset: next_f32 - 4,
+                        is_f32: true,
+                    }
+                } else {
+                    next_wide += SLOT_SIZE.in_bytes();
+                    SlotPosition {
+                        offset: next_wide - SLOT_SIZE.in_bytes(),
+                        is_f32: false,
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Packs the scalars of a value as `f32`, if the storage asks for it.
+    fn pack(&self, value: Box<[u8]>) -> Box<[u8]> {
+        if !self.storage_type.packs_f32() {
+            return value;
+        }
+
+        let positions = self.value_positions();
+        let n_f32 = positions.iter().filter(|position| position.is_f32).count();
+        let mut packed = vec![0; value.len() - 4 * n_f32];
+        for (slot, position) in value.chunks(8).zip(positions) {
+            let offset = position.offset;
+            if position.is_f32 {
+                let scalar = f64::from_ne_bytes(slot.try_into().expect("slot has 8 bytes"));
+                packed[offset..offset + 4].copy_from_slice(&(scalar as f32).to_ne_bytes());
+            } else {
+                packed[offset..offset + 8].copy_from_slice(slot);
+            }
+        }
+
+        packed.into()
+    }
+
+    /// The number of values in this mapping, if it is known and fixed. See
+    /// [`Storage::n_entries`].
+    pub fn n_entries(&self) -> Option<usize> {
+        self.storage.as_ref().and_then(|s| s.n_entries())
+    }
+
+    /// Reads a slot of a value returned by [`Mapping::get`], widening it back to `f64`
+    /// if the storage packs scalars as `f32`. Returns the raw representation of the slot.
+    pub(crate) fn read_slot(&self, value: &[u8], slot: usize) -> Option<u64> {
+        let position = *self.value_positions().get(slot)?;
+        let offset = position.offset;
+        if position.is_f32 {
+            let scalar = f32::from_ne_bytes(value.get(offset..offset + 4)?.try_into().ok()?);
+            Some((scalar as f64).to_bits())
+        } else {
+            Some(u64::from_ne_bytes(
+                value.get(offset..offset + 8)?.try_into().ok()?,
+            ))
+        }
     }
 
     /// Gets the raw data associated with the supplied raw key.
-    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
-        self.storage.as_ref().and_then(|s| s.get(hash(key)))
+    ///
+    /// If the storage packs scalars as `f32`, the slots of the value are not laid out as
+    /// in [`Layout`]. Instead, all non-scalar slots come first, in order, followed by the
+    /// scalar slots, in order, each stored as a 4-byte `f32`.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Error> {
+        match &self.storage {
+            Some(storage) => storage.try_get(hash(key, self.seed)),
+            None => Ok(None),
+        }
     }
 
-    unsafe extern "C" fn call_mapping(mapping: *const Mapping, hash: u64) -> *const u8 {
+    /// The size in bytes of the buffer that a function needs to copy a value into. See
+    /// [`Storage::get`].
+    pub(crate) fn value_buffer_size(&self) -> usize {
+        self.value_layout.size().in_bytes()
+    }
+
+    /// Looks a hash up, returning a pointer to the value or null if it is not found. Owned
+    /// values are copied to `buffer`, which has [`Mapping::value_buffer_size`] bytes. On
+    /// error, this also returns null, but sets `error` to an allocated error message.
+    unsafe extern "C" fn call_mapping(
+        mapping: *const Mapping,
+        hash: u64,
+        buffer: *mut u8,
+        error: *mut *mut c_char,
+    ) -> *const u8 {
         let mapping = &*mapping;
-        if let Some(line) = mapping.storage.as_ref().and_then(|s| s.get(hash)) {
-            line.as_ptr()
-        } else {
+        let Some(storage) = mapping.storage.as_ref() else {
+            return std::ptr::null();
+        };
+
+        let raise = |message: String| {
+            *error = crate::utils::make_safe_c_str(message).into_raw();
             std::ptr::null()
+        };
+
+        match storage.try_get(hash) {
+            Ok(Some(Cow::Borrowed(value))) => value.as_ptr(),
+            Ok(Some(Cow::Owned(value))) => {
+                if value.len() > mapping.value_buffer_size() {
+                    return raise(format!(
+                        "mapping value has {} bytes, but expected at most {}",
+                        value.len(),
+                        mapping.value_buffer_size()
+                    ));
+                }
+                std::ptr::copy_nonoverlapping(value.as_ptr(), buffer, value.len());
+                buffer
+            }
+            Ok(None) => std::ptr::null(),
+            Err(err) => raise(format!("mapping lookup failed: {err}")),
         }
     }
 
     pub fn render(&self, func_name: String) -> qbe::Function<'static> {
         let input_slots = self.key_layout.slots();
+        let buffer = qbe::Value::Temporary("buffer".to_string());
+        let error = qbe::Value::Temporary("error".to_string());
         let args = input_slots
             .iter()
             .enumerate()
             .map(|(i, ty)| (ty.render(), qbe::Value::Temporary(format!("i{i}"))))
+            .chain([
+                (qbe::Type::Long, buffer.clone()),
+                (qbe::Type::Long, error.clone()),
+            ])
             .collect::<Vec<_>>();
         let mut func = qbe::Function::new(
             qbe::Linkage::private(),
@@ -287,7 +544,7 @@ impl Mapping {
         func.assign_instr(
             hash.clone(),
             qbe::Type::Long,
-            qbe::Instr::Copy(qbe::Value::Const(0)),
+            qbe::Instr::Copy(qbe::Value::Const(self.seed)),
         );
 
         for (i, ty) in input_slots.iter().enumerate() {
@@ -323,6 +580,8 @@ impl Mapping {
                 vec![
                     (qbe::Type::Long, qbe::Value::Const(mapping_ptr as u64)),
                     (qbe::Type::Long, hash.clone()),
+                    (qbe::Type::Long, buffer),
+                    (qbe::Type::Long, error),
                 ],
             ),
         );
@@ -0,0 +1,383 @@
+use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zip::read::ZipFile;
+
+use crate::Error;
+
+use super::{Storage, StorageType};
+
+/// How long to wait for Redis to connect or to answer before giving up on a request.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+fn default_lru_size() -> usize {
+    4096
+}
+
+/// A [`StorageType`] implementation that keeps the values of a mapping in Redis, so that
+/// many graphs (and many processes) can share the same large lookup table without
+/// embedding it in each graph archive. Each value is stored under the key
+/// `<key_prefix>:<hash>`, where `hash` is the hash of the mapping key, and inserting
+/// values into the mapping writes them to Redis.
+///
+/// The URL of the Redis server is given as `redis://[:password@]host[:port][/db]`.
+/// References to environment variables in it, written as `${NAME}`, are expanded
+/// whenever the storage is created, so that secrets such as the password can be kept out
+/// of graph dumps. The URL (unexpanded), the key prefix and the cache size are saved when
+/// the graph is dumped; the storage reconnects to Redis when the graph is loaded.
+///
+/// # Latency
+///
+/// Mappings are read while functions run, so every lookup that misses the in-process
+/// cache of the storage costs a round trip to Redis, which is orders of magnitude slower
+/// than a lookup in [`HashMapStorage`](super::HashMapStorage). The cache keeps the
+/// `lru_size` least recently used entries (including keys found to be absent), so size
+/// it to hold the hot part of the table. Lookups are serialized by a lock, so a single
+/// storage serves at most one request to Redis at a time.
+///
+/// If Redis cannot be reached, the function doing the lookup returns an error.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedisStorage {
+    /// The URL of the Redis server, before environment variables are expanded.
+    pub url: String,
+    /// The prefix of the keys of this mapping in Redis.
+    pub key_prefix: String,
+    /// The maximum number of entries kept in the in-process cache.
+    #[serde(default = "default_lru_size")]
+    pub lru_size: usize,
+}
+
+impl RedisStorage {
+    /// Creates a new storage type for the Redis server at `url`, storing values under keys
+    /// starting with `key_prefix`, with the default cache size of 4096 entries.
+    pub fn new(url: impl Into<String>, key_prefix: impl Into<String>) -> RedisStorage {
+        RedisStorage {
+            url: url.into(),
+            key_prefix: key_prefix.into(),
+            lru_size: default_lru_size(),
+        }
+    }
+
+    /// Sets the maximum number of entries kept in the in-process cache.
+    pub fn with_lru_size(mut self, lru_size: usize) -> RedisStorage {
+        self.lru_size = lru_size.max(1);
+        self
+    }
+}
+
+#[typetag::serde]
+impl StorageType for RedisStorage {
+    fn init(&self) -> Result<Box<dyn Storage>, Error> {
+        Ok(Box::new(RedisTable::new(self.clone())?))
+    }
+
+    fn read(&self, _: ZipFile<'_>) -> Result<Box<dyn Storage>, Error> {
+        // The data lives in Redis, so there is nothing to read from the dump.
+        Ok(Box::new(RedisTable::new(self.clone())?))
+    }
+}
+
+/// Expands the references to environment variables, written as `${NAME}`, in `url`.
+fn expand_env(url: &str) -> Result<String, Error> {
+    let mut expanded = String::with_capacity(url.len());
+    let mut rest = url;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let Some(len) = rest[start + 2..].find('}') else {
+            return Err(format!("unterminated environment variable in Redis URL {url:?}").into());
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        let value = std::env::var(name)
+            .map_err(|_| format!("environment variable {name:?} in the Redis URL is not set"))?;
+        expanded.push_str(&value);
+        rest = &rest[start + 3 + len..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// A cached value, or the absence of one, and when it was last used.
+struct Entry {
+    last_used: u64,
+    value: Option<Arc<[u8]>>,
+}
+
+/// The least recently used entries read from Redis.
+#[derive(Default)]
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: hashbrown::HashMap<u64, Entry>,
+    by_use: BTreeMap<u64, u64>,
+    heap_size: usize,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Lru {
+        Lru {
+            capacity: capacity.max(1),
+            ..Lru::default()
+        }
+    }
+
+    /// Gets a cached entry, marking it as used. Returns `None` if the entry is not cached
+    /// and `Some(None)` if the key is known to be absent.
+    fn get(&mut self, hash: u64) -> Option<Option<Arc<[u8]>>> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(&hash)?;
+        self.by_use.remove(&entry.last_used);
+        self.by_use.insert(self.tick, hash);
+        entry.last_used = self.tick;
+        Some(entry.value.clone())
+    }
+
+    fn put(&mut self, hash: u64, value: Option<Arc<[u8]>>) {
+        if let Some(old) = self.entries.remove(&hash) {
+            self.by_use.remove(&old.last_used);
+            self.heap_size -= old.value.map_or(0, |value| value.len());
+        }
+        while self.entries.len() >= self.capacity {
+            let Some((_, evicted)) = self.by_use.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&evicted) {
+                self.heap_size -= entry.value.map_or(0, |value| value.len());
+            }
+        }
+
+        self.tick += 1;
+        self.heap_size += value.as_ref().map_or(0, |value| value.len());
+        self.by_use.insert(self.tick, hash);
+        let last_used = self.tick;
+        self.entries.insert(hash, Entry { last_used, value });
+    }
+}
+
+/// A mapping storage backed by Redis. See [`RedisStorage`].
+struct RedisTable {
+    config: RedisStorage,
+    client: redis::Client,
+    connection: Mutex<Option<redis::Connection>>,
+    cache: Mutex<Lru>,
+}
+
+impl std::fmt::Debug for RedisTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisTable")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl RedisTable {
+    fn new(config: RedisStorage) -> Result<RedisTable, Error> {
+        let url = expand_env(&config.url)?;
+
+        // Connecting is left for the first request, so that graphs can be loaded even if
+        // Redis is momentarily down.
+        Ok(RedisTable {
+            client: redis::Client::open(url)?,
+            cache: Mutex::new(Lru::new(config.lru_size)),
+            connection: Mutex::new(None),
+            config,
+        })
+    }
+
+    fn key(&self, hash: u64) -> String {
+        format!("{}:{hash}", self.config.key_prefix)
+    }
+
+    fn connect(&self) -> Result<redis::Connection, Error> {
+        let connection = self.client.get_connection_with_timeout(TIMEOUT)?;
+        connection.set_read_timeout(Some(TIMEOUT))?;
+        connection.set_write_timeout(Some(TIMEOUT))?;
+        Ok(connection)
+    }
+
+    /// Sends a request, reconnecting if there is no connection or if it broke.
+    fn query<T: redis::FromRedisValue>(&self, cmd: &redis::Cmd) -> Result<T, Error> {
+        let mut connection = self.connection.lock().expect("poisoned");
+        for attempt in 0..2 {
+            if connection.is_none() {
+                *connection = Some(self.connect()?);
+            }
+            match cmd.query(connection.as_mut().expect("connected above")) {
+                Err(err) if attempt == 0 && err.is_connection_dropped() => *connection = None,
+                Err(err) => {
+                    *connection = None;
+                    return Err(err.into());
+                }
+                Ok(reply) => return Ok(reply),
+            }
+        }
+
+        unreachable!("the second attempt always returns")
+    }
+}
+
+impl Storage for RedisTable {
+    fn try_insert(&mut self, hash: u64, value: Box<[u8]>) -> Result<(), Error> {
+        self.query::<()>(redis::cmd("SET").arg(self.key(hash)).arg(&*value))?;
+        self.cache
+            .get_mut()
+            .expect("poisoned")
+            .put(hash, Some(value.into()));
+        Ok(())
+    }
+
+    fn try_get(&self, hash: u64) -> Result<Option<Cow<'_, [u8]>>, Error> {
+        let mut cache = self.cache.lock().expect("poisoned");
+        let value = match cache.get(hash) {
+            Some(value) => value,
+            None => {
+                // Failed requests are not cached, so that they are retried on the next
+                // lookup.
+                let value: Option<Vec<u8>> = self.query(redis::cmd("GET").arg(self.key(hash)))?;
+                let value = value.map(Arc::from);
+                cache.put(hash, value.clone());
+                value
+            }
+        };
+
+        Ok(value.map(|value| Cow::Owned(value.to_vec())))
+    }
+
+    fn size(&self) -> usize {
+        self.cache.lock().expect("poisoned").heap_size
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::layout::{Layout, RefValue};
+    use crate::Graph;
+
+    #[test]
+    fn test_expand_env() {
+        let var = format!("JYAFN_TEST_REDIS_EXPAND_{}", std::process::id());
+        std::env::set_var(&var, "secret");
+        assert_eq!(
+            expand_env(&format!("redis://:${{{var}}}@$host/${{{var}}}")).unwrap(),
+            "redis://:secret@$host/secret"
+        );
+        assert_eq!(expand_env("redis://host").unwrap(), "redis://host");
+        assert!(expand_env(&format!("redis://:${{{var}@host")).is_err());
+        std::env::remove_var(&var);
+        assert!(expand_env(&format!("redis://:${{{var}}}@host")).is_err());
+    }
+
+    #[test]
+    fn test_redis_url() {
+        let password_var = format!("JYAFN_TEST_REDIS_PASSWORD_{}", std::process::id());
+        std::env::set_var(&password_var, "secret");
+        let url = format!("redis://:${{{password_var}}}@example.com:1234/2");
+
+        let mut g = Graph::new();
+        g.insert_mapping(
+            "m".to_string(),
+            Layout::Scalar,
+            Layout::Scalar,
+            RedisStorage::new(&url, "prefix"),
+            std::iter::empty::<Result<(f64, f64), Error>>(),
+        )
+        .unwrap();
+
+        // The password is not dumped.
+        let mut dumped = std::io::Cursor::new(vec![]);
+        g.dump(&mut dumped).unwrap();
+        let dumped = dumped.into_inner();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&dumped)).unwrap();
+        for id in 0..archive.len() {
+            let mut contents = vec![];
+            std::io::copy(&mut archive.by_index(id).unwrap(), &mut contents).unwrap();
+            let contents = String::from_utf8_lossy(&contents);
+            assert!(!contents.contains("secret"));
+        }
+
+        // The variables in the URL are expanded again when loading.
+        assert!(Graph::load(std::io::Cursor::new(&dumped)).is_ok());
+        std::env::remove_var(&password_var);
+        assert!(Graph::load(std::io::Cursor::new(&dumped)).is_err());
+    }
+
+    /// This test needs a Redis server, at the URL in `JYAFN_TEST_REDIS_URL` or, if it is
+    /// not set, at `redis://127.0.0.1:6379`. Run it with
+    /// `cargo test -p jyafn --features redis-tests`.
+    #[cfg(feature = "redis-tests")]
+    #[test]
+    fn test_redis_storage() {
+        if std::env::var("JYAFN_TEST_REDIS_URL").is_err() {
+            std::env::set_var("JYAFN_TEST_REDIS_URL", "redis://127.0.0.1:6379");
+        }
+        let key_prefix = format!(
+            "jyafn-test-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        );
+        let storage = RedisStorage::new("${JYAFN_TEST_REDIS_URL}", key_prefix).with_lru_size(2);
+
+        let mut g = Graph::new();
+        g.insert_mapping(
+            "doubled".to_string(),
+            Layout::Scalar,
+            Layout::Scalar,
+            storage,
+            (0..10).map(|i| Ok::<_, Error>((i as f64, 2.0 * i as f64))),
+        )
+        .unwrap();
+        let x = g.input("x".to_string(), Layout::Scalar);
+        let doubled = g
+            .call_mapping_default("doubled", x, RefValue::Scalar((-1.0).into()))
+            .unwrap();
+        g.output(doubled, Layout::Scalar).unwrap();
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        g.dump(&mut dumped).unwrap();
+        let loaded = Graph::load(std::io::Cursor::new(dumped.into_inner())).unwrap();
+
+        // The loaded graph reconnects to Redis and its cache starts empty.
+        for func in [g.compile().unwrap(), loaded.compile().unwrap()] {
+            for _ in 0..2 {
+                for i in 0..12 {
+                    let doubled: f64 = func.eval(&serde_json::json!({ "x": i })).unwrap();
+                    assert_eq!(doubled, if i < 10 { 2.0 * i as f64 } else { -1.0 });
+                }
+            }
+        }
+    }
+
+    /// Lookups that cannot reach Redis raise an error in the function.
+    #[test]
+    fn test_redis_storage_unreachable() {
+        let mut g = Graph::new();
+        g.insert_mapping(
+            "m".to_string(),
+            Layout::Scalar,
+            Layout::Scalar,
+            RedisStorage::new("redis://127.0.0.1:1", "prefix"),
+            std::iter::empty::<Result<(f64, f64), Error>>(),
+        )
+        .unwrap();
+        let x = g.input("x".to_string(), Layout::Scalar);
+        let value = g
+            .call_mapping_default("m", x, RefValue::Scalar((-1.0).into()))
+            .unwrap();
+        g.output(value, Layout::Scalar).unwrap();
+
+        let func = g.compile().unwrap();
+        assert!(func
+            .eval::<_, f64>(&serde_json::json!({ "x": 1.0 }))
+            .is_err());
+    }
+}
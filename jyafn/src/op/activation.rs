@@ -0,0 +1,295 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{graph::SLOT_SIZE, impl_op, Graph, Ref, Type};
+
+use super::reduce::{render_tree, tree_reduce, ReduceKind};
+use super::{origin_of, render_load_slot, render_slot_addr, unique_for, Op};
+
+/// The logistic function, computed so that it never overflows.
+extern "C" fn sigmoid(x: f64) -> f64 {
+    if x >= 0.0 {
+        1.0 / (1.0 + (-x).exp())
+    } else {
+        let exp = x.exp();
+        exp / (1.0 + exp)
+    }
+}
+
+extern "C" fn exp(x: f64) -> f64 {
+    x.exp()
+}
+
+/// Implements the logistic function `1 / (1 + exp(-a))`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sigmoid;
+
+#[typetag::serde]
+impl Op for Sigmoid {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float] => Type::Float,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        func.assign_instr(
+            output,
+            Type::Float.render(),
+            qbe::Instr::Call(
+                qbe::Value::Const(sigmoid as *const () as usize as u64),
+                vec![(Type::Float.render(), args[0].render())],
+            ),
+        )
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        args[0].as_f64().map(|x| sigmoid(x).into())
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
+
+    fn inexact_calls(&self) -> &'static [&'static str] {
+        &["exp"]
+    }
+}
+
+/// Implements `max(a, 0)`. Unlike [`super::Max`], NaNs are kept.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relu;
+
+#[typetag::serde]
+impl Op for Relu {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float] => Type::Float,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let test = qbe::Value::Temporary(unique_for(output.clone(), "relu.test"));
+        let negative_side = unique_for(output.clone(), "relu.negative");
+        let end_side = unique_for(output.clone(), "relu.end");
+
+        func.assign_instr(
+            output.clone(),
+            Type::Float.render(),
+            qbe::Instr::Copy(args[0].render()),
+        );
+        func.assign_instr(
+            test.clone(),
+            Type::Bool.render(),
+            qbe::Instr::Cmp(
+                Type::Float.render(),
+                qbe::Cmp::Lt,
+                args[0].render(),
+                Ref::from(0.0).render(),
+            ),
+        );
+        func.add_instr(qbe::Instr::Jnz(
+            test,
+            negative_side.clone(),
+            end_side.clone(),
+        ));
+
+        func.add_block(negative_side);
+        func.assign_instr(
+            output,
+            Type::Float.render(),
+            qbe::Instr::Copy(Ref::from(0.0).render()),
+        );
+
+        func.add_block(end_side);
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        let x = args[0].as_f64()?;
+        Some(if x < 0.0 { 0.0 } else { x }.into())
+    }
+}
+
+/// The softmax of `n` floats, `exp(a_i) / sum(exp(a_j))`. The maximum of the values is
+/// subtracted from all of them before exponentiation, so that large values don't
+/// overflow. The resulting list is read with [`LoadSoftmaxOutput`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Softmax {
+    pub n: usize,
+}
+
+impl Softmax {
+    /// Evaluates the softmax of `values`, in the same order of operations as the
+    /// compiled code.
+    fn eval(values: Vec<f64>) -> Option<Vec<f64>> {
+        let max = tree_reduce(values.clone(), |_, x, y| ReduceKind::Max.combine(x, y))?;
+        let exps = values.into_iter().map(|x| exp(x - max)).collect::<Vec<_>>();
+        let sum = tree_reduce(exps.clone(), |_, x, y| x + y)?;
+
+        Some(exps.into_iter().map(|x| x / sum).collect())
+    }
+}
+
+#[typetag::serde]
+impl Op for Softmax {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        if self.n != 0 && args.len() == self.n && args.iter().all(|&arg| arg == Type::Float) {
+            Some(Type::Ptr { origin: self_id })
+        } else {
+            None
+        }
+    }
+
+    fn explain_type_error(&self, graph: &Graph, args: &[Type]) -> Option<String> {
+        if self.n == 0 {
+            Some("cannot take the softmax of no values".to_string())
+        } else {
+            Some(format!("softmax of {} values takes only floats", self.n))
+        }
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let temp = |name: String| qbe::Value::Temporary(unique_for(output.clone(), &name));
+        let max = temp("softmax.max".to_string());
+        let sum = temp("softmax.sum".to_string());
+        let addr = temp("softmax.addr".to_string());
+
+        render_tree(
+            ReduceKind::Max,
+            max.clone(),
+            args.iter().copied().map(Ref::render).collect(),
+            func,
+        );
+
+        let exps = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let shifted = temp(format!("softmax.shifted.{i}"));
+                let exp_value = temp(format!("softmax.exp.{i}"));
+                func.assign_instr(
+                    shifted.clone(),
+                    Type::Float.render(),
+                    qbe::Instr::Sub(arg.render(), max.clone()),
+                );
+                func.assign_instr(
+                    exp_value.clone(),
+                    Type::Float.render(),
+                    qbe::Instr::Call(
+                        qbe::Value::Const(exp as *const () as usize as u64),
+                        vec![(Type::Float.render(), shifted)],
+                    ),
+                );
+                exp_value
+            })
+            .collect::<Vec<_>>();
+
+        render_tree(ReduceKind::Sum, sum.clone(), exps.clone(), func);
+
+        func.assign_instr(
+            output.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Alloc8((self.n * SLOT_SIZE).in_bytes() as u64),
+        );
+
+        for (i, exp_value) in exps.into_iter().enumerate() {
+            let normalized = temp(format!("softmax.normalized.{i}"));
+            func.assign_instr(
+                normalized.clone(),
+                Type::Float.render(),
+                qbe::Instr::Div(exp_value, sum.clone()),
+            );
+            render_slot_addr(addr.clone(), output.clone(), i, func);
+            func.add_instr(qbe::Instr::Store(
+                Type::Float.render(),
+                addr.clone(),
+                normalized,
+            ));
+        }
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
+
+    fn inexact_calls(&self) -> &'static [&'static str] {
+        &["exp"]
+    }
+}
+
+/// Loads one of the elements of the list produced by [`Softmax`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct LoadSoftmaxOutput {
+    pub(crate) slot: usize,
+}
+
+#[typetag::serde]
+impl Op for LoadSoftmaxOutput {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        let [Type::Ptr { origin }] = args else {
+            return None;
+        };
+        let (softmax, _) = origin_of::<Softmax>(graph, Ref::Node(*origin))?;
+
+        (self.slot < softmax.n).then_some(Type::Float)
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        render_load_slot(
+            output,
+            Type::Float.render(),
+            args[0],
+            self.slot,
+            func,
+            "loadsoftmax",
+        );
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        let (_, softmax_args) = origin_of::<Softmax>(graph, args[0])?;
+        let values = softmax_args
+            .iter()
+            .copied()
+            .map(Ref::as_f64)
+            .collect::<Option<Vec<_>>>()?;
+
+        Softmax::eval(values)?.get(self.slot).map(|&x| x.into())
+    }
+}
@@ -184,7 +184,90 @@ impl Op for Div {
     }
 }
 
-/// Implements `a % b`.
+/// Implements `a / b`, unless `b` is zero, in which case the result is `default`. The
+/// arguments are `[a, b, default]`. Zero is tested with the usual float comparison, so
+/// both `0.0` and `-0.0` are zero, but subnormal numbers are not. A NaN `b` gives a NaN
+/// result, just like [`Div`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DivOr;
+
+#[typetag::serde]
+impl Op for DivOr {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float, Type::Float, Type::Float] => Type::Float,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let test_temp = qbe::Value::Temporary(unique_for(output.clone(), "divor.test"));
+        func.assign_instr(
+            test_temp.clone(),
+            Type::Bool.render(),
+            qbe::Instr::Cmp(
+                Type::Float.render(),
+                qbe::Cmp::Eq,
+                args[1].render(),
+                qbe::Value::Const(0),
+            ),
+        );
+
+        let zero_side = unique_for(output.clone(), "divor.zero");
+        let div_side = unique_for(output.clone(), "divor.div");
+        let end_side = unique_for(output.clone(), "divor.end");
+
+        func.add_instr(qbe::Instr::Jnz(
+            test_temp,
+            zero_side.clone(),
+            div_side.clone(),
+        ));
+
+        func.add_block(zero_side);
+        func.assign_instr(
+            output.clone(),
+            Type::Float.render(),
+            qbe::Instr::Copy(args[2].render()),
+        );
+        func.add_instr(qbe::Instr::Jmp(end_side.clone()));
+
+        func.add_block(div_side);
+        func.assign_instr(
+            output,
+            Type::Float.render(),
+            qbe::Instr::Div(args[0].render(), args[1].render()),
+        );
+
+        func.add_block(end_side);
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        let y = args[1].as_f64()?;
+        if y == 0.0 {
+            return Some(args[2]);
+        }
+
+        if Ref::from(1.0) == args[1] {
+            return Some(args[0]);
+        }
+
+        Some((args[0].as_f64()? / y).into())
+    }
+}
+
+/// Implements `a % b` with truncated division semantics, just like the `%` operator in
+/// Rust and C's `fmod`: the result has the sign of `a` (e.g., `-7 % 3 == -1` and
+/// `7 % -3 == 1`). See [`FloorRem`] for the floored remainder of Python. A zero `b` gives
+/// NaN.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Rem;
 
@@ -220,6 +303,216 @@ impl Op for Rem {
     }
 }
 
+/// Implements `a mod b` with floored division semantics, just like `%` in Python: the
+/// result has the sign of `b` (e.g., `-7 mod 3 == 2` and `7 mod -3 == -2`), so that
+/// `a == floor(a / b) * b + a mod b`, up to rounding. This differs from [`Rem`], which
+/// truncates, just like the `%` operator in Rust and C's `fmod`. A zero `b` gives NaN.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FloorRem;
+
+/// Computes `x mod y` the same way as the rendered [`FloorRem`] does: the truncated remainder,
+/// corrected by `y` when its sign differs from the sign of `y`.
+fn floored_rem(x: f64, y: f64) -> f64 {
+    let rem = x % y;
+    if rem != 0.0 && (rem < 0.0) != (y < 0.0) {
+        rem + y
+    } else {
+        rem
+    }
+}
+
+#[typetag::serde]
+impl Op for FloorRem {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float, Type::Float] => Type::Float,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        // `rem` does not work for floats in QBE. So, we need to resort to pfuncs!
+        super::call::Call("rem".to_string()).render_into(
+            graph,
+            output.clone(),
+            args,
+            func,
+            namespace,
+        );
+
+        // The truncated remainder has the sign of `a`. Correct it if it is not zero and
+        // its sign differs from the sign of `b`.
+        let temp = |name: &str| qbe::Value::Temporary(unique_for(output.clone(), name));
+        let tests = [
+            ("rem.rem_neg", qbe::Cmp::Lt, output.clone()),
+            ("rem.b_neg", qbe::Cmp::Lt, args[1].render()),
+            ("rem.nonzero", qbe::Cmp::Ne, output.clone()),
+        ];
+        for (name, cmp, value) in tests {
+            func.assign_instr(
+                temp(name),
+                Type::Bool.render(),
+                qbe::Instr::Cmp(Type::Float.render(), cmp, value, qbe::Value::Const(0)),
+            );
+        }
+        func.assign_instr(
+            temp("rem.sign_differs"),
+            Type::Bool.render(),
+            qbe::Instr::Xor(temp("rem.rem_neg"), temp("rem.b_neg")),
+        );
+        func.assign_instr(
+            temp("rem.test"),
+            Type::Bool.render(),
+            qbe::Instr::And(temp("rem.sign_differs"), temp("rem.nonzero")),
+        );
+
+        let fix_side = unique_for(output.clone(), "rem.fix");
+        let end_side = unique_for(output.clone(), "rem.end");
+        func.add_instr(qbe::Instr::Jnz(
+            temp("rem.test"),
+            fix_side.clone(),
+            end_side.clone(),
+        ));
+
+        func.add_block(fix_side);
+        func.assign_instr(
+            output.clone(),
+            Type::Float.render(),
+            qbe::Instr::Add(output, args[1].render()),
+        );
+
+        func.add_block(end_side);
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        if let Some((x, y)) = args[0].as_f64().zip(args[1].as_f64()) {
+            return Some(floored_rem(x, y).into());
+        }
+
+        None
+    }
+}
+
+/// Implements `a ^ n` for a compile-time integer exponent `n`. This is rendered as a
+/// sequence of multiplications by repeated squaring, avoiding a call to `powf`. Negative
+/// exponents give the reciprocal of the positive power and `a ^ 0` is always `1.0`, even
+/// for NaN `a`, just like [`f64::powi`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Powi(pub i32);
+
+/// Computes `x ^ n` in the same order of operations as the rendered [`Powi`], so that
+/// constant folding gives the same result as the compiled code.
+fn powi(x: f64, n: i32) -> f64 {
+    let mut remaining = n.unsigned_abs();
+    let mut base = x;
+    let mut acc: Option<f64> = None;
+
+    while remaining != 0 {
+        if remaining & 1 == 1 {
+            acc = Some(acc.map_or(base, |acc| acc * base));
+        }
+        remaining >>= 1;
+        if remaining != 0 {
+            base *= base;
+        }
+    }
+
+    match acc {
+        None => 1.0,
+        Some(acc) if n < 0 => 1.0 / acc,
+        Some(acc) => acc,
+    }
+}
+
+#[typetag::serde]
+impl Op for Powi {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float] => Type::Float,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let mut remaining = self.0.unsigned_abs();
+        let mut base = args[0].render();
+        let mut acc: Option<qbe::Value> = None;
+        let mut step = 0;
+
+        while remaining != 0 {
+            if remaining & 1 == 1 {
+                acc = Some(if let Some(acc) = acc {
+                    let temp = qbe::Value::Temporary(unique_for(
+                        output.clone(),
+                        &format!("powi.acc{step}"),
+                    ));
+                    func.assign_instr(
+                        temp.clone(),
+                        Type::Float.render(),
+                        qbe::Instr::Mul(acc, base.clone()),
+                    );
+                    temp
+                } else {
+                    base.clone()
+                });
+            }
+            remaining >>= 1;
+            if remaining != 0 {
+                let temp =
+                    qbe::Value::Temporary(unique_for(output.clone(), &format!("powi.base{step}")));
+                func.assign_instr(
+                    temp.clone(),
+                    Type::Float.render(),
+                    qbe::Instr::Mul(base.clone(), base),
+                );
+                base = temp;
+            }
+            step += 1;
+        }
+
+        let instr = match acc {
+            None => qbe::Instr::Copy(Ref::from(1.0).render()),
+            Some(acc) if self.0 < 0 => qbe::Instr::Div(Ref::from(1.0).render(), acc),
+            Some(acc) => qbe::Instr::Copy(acc),
+        };
+        func.assign_instr(output, Type::Float.render(), instr);
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        if self.0 == 0 {
+            return Some(1.0.into());
+        }
+
+        if self.0 == 1 {
+            return Some(args[0]);
+        }
+
+        if let Some(x) = args[0].as_f64() {
+            return Some(powi(x, self.0).into());
+        }
+
+        None
+    }
+}
+
 /// Implements `-a`.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Neg;
@@ -231,10 +524,20 @@ impl Op for Neg {
     fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
         Some(match args {
             [Type::Float] => Type::Float,
+            [Type::Int] => Type::Int,
             _ => return None,
         })
     }
 
+    fn explain_type_error(&self, graph: &Graph, args: &[Type]) -> Option<String> {
+        match args {
+            [Type::Bool] => {
+                Some("cannot negate a bool: use ~ / .not_() to invert a bool".to_string())
+            }
+            _ => None,
+        }
+    }
+
     fn render_into(
         &self,
         graph: &Graph,
@@ -245,7 +548,7 @@ impl Op for Neg {
     ) {
         func.assign_instr(
             output,
-            Type::Float.render(),
+            graph.type_of(args[0]).render(),
             qbe::Instr::Neg(args[0].render()),
         )
     }
@@ -255,6 +558,10 @@ impl Op for Neg {
             return Some((-x).into());
         }
 
+        if let Some(x) = args[0].as_i64() {
+            return Some(x.wrapping_neg().into());
+        }
+
         None
     }
 }
@@ -330,3 +637,350 @@ impl Op for Abs {
         None
     }
 }
+
+/// Implements `clamp(a, min, max)`, bounding `a` to be between `min` and `max`. If
+/// `min > max`, the result is `max`. A NaN `a` is passed through unchanged.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Clamp;
+
+/// Clamps a value the same way as the rendered [`Clamp`] does: first bounding from below,
+/// then from above.
+fn clamp(x: f64, min: f64, max: f64) -> f64 {
+    let bounded_below = if x < min { min } else { x };
+    if bounded_below > max {
+        max
+    } else {
+        bounded_below
+    }
+}
+
+#[typetag::serde]
+impl Op for Clamp {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float, Type::Float, Type::Float] => Type::Float,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let bounds = [
+            ("min", qbe::Cmp::Lt, args[1]),
+            ("max", qbe::Cmp::Gt, args[2]),
+        ];
+
+        func.assign_instr(
+            output.clone(),
+            Type::Float.render(),
+            qbe::Instr::Copy(args[0].render()),
+        );
+
+        for (kind, cmp, bound) in bounds {
+            let test_temp =
+                qbe::Value::Temporary(unique_for(output.clone(), &format!("clamp.{kind}.test")));
+            let out_side = unique_for(output.clone(), &format!("clamp.{kind}.out"));
+            let end_side = unique_for(output.clone(), &format!("clamp.{kind}.end"));
+
+            func.assign_instr(
+                test_temp.clone(),
+                Type::Bool.render(),
+                qbe::Instr::Cmp(Type::Float.render(), cmp, output.clone(), bound.render()),
+            );
+            func.add_instr(qbe::Instr::Jnz(
+                test_temp,
+                out_side.clone(),
+                end_side.clone(),
+            ));
+
+            func.add_block(out_side);
+            func.assign_instr(
+                output.clone(),
+                Type::Float.render(),
+                qbe::Instr::Copy(bound.render()),
+            );
+
+            func.add_block(end_side);
+        }
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        let x = args[0].as_f64()?;
+        let min = args[1].as_f64()?;
+        let max = args[2].as_f64()?;
+
+        Some(clamp(x, min, max).into())
+    }
+}
+
+/// Renders `a` if `b cmp a` is false, else `b`. When `a` is NaN, `b` is taken instead,
+/// which gives the NaN semantics of [`f64::min`] and [`f64::max`].
+pub(super) fn render_select(
+    output: qbe::Value,
+    [a, b]: [qbe::Value; 2],
+    func: &mut qbe::Function,
+    cmp: qbe::Cmp,
+    kind: &str,
+) {
+    let tests = [
+        ("cmp", cmp, b.clone(), a.clone()),
+        ("nan", qbe::Cmp::Ne, a.clone(), a.clone()),
+    ];
+    let take_b = unique_for(output.clone(), &format!("{kind}.take_b"));
+    let end_side = unique_for(output.clone(), &format!("{kind}.end"));
+
+    func.assign_instr(output.clone(), Type::Float.render(), qbe::Instr::Copy(a));
+
+    for (test, cmp, lhs, rhs) in tests {
+        let test_temp =
+            qbe::Value::Temporary(unique_for(output.clone(), &format!("{kind}.{test}.test")));
+        let next_side = unique_for(output.clone(), &format!("{kind}.{test}.next"));
+
+        func.assign_instr(
+            test_temp.clone(),
+            Type::Bool.render(),
+            qbe::Instr::Cmp(Type::Float.render(), cmp, lhs, rhs),
+        );
+        func.add_instr(qbe::Instr::Jnz(
+            test_temp,
+            take_b.clone(),
+            next_side.clone(),
+        ));
+        func.add_block(next_side);
+    }
+    func.add_instr(qbe::Instr::Jmp(end_side.clone()));
+
+    func.add_block(take_b);
+    func.assign_instr(output, Type::Float.render(), qbe::Instr::Copy(b));
+
+    func.add_block(end_side);
+}
+
+/// Implements `min(a, b)`. If one of the arguments is NaN, the other is returned, just
+/// like [`f64::min`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Min;
+
+#[typetag::serde]
+impl Op for Min {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float, Type::Float] => Type::Float,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        render_select(
+            output,
+            [args[0].render(), args[1].render()],
+            func,
+            qbe::Cmp::Lt,
+            "min",
+        )
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        if let Some((x, y)) = args[0].as_f64().zip(args[1].as_f64()) {
+            return Some(x.min(y).into());
+        }
+
+        None
+    }
+}
+
+/// Implements `max(a, b)`. If one of the arguments is NaN, the other is returned, just
+/// like [`f64::max`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Max;
+
+#[typetag::serde]
+impl Op for Max {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float, Type::Float] => Type::Float,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        render_select(
+            output,
+            [args[0].render(), args[1].render()],
+            func,
+            qbe::Cmp::Gt,
+            "max",
+        )
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        if let Some((x, y)) = args[0].as_f64().zip(args[1].as_f64()) {
+            return Some(x.max(y).into());
+        }
+
+        None
+    }
+}
+
+/// Implements `a + b` for integers. Overflows wrap around.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntAdd;
+
+#[typetag::serde]
+impl Op for IntAdd {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Int, Type::Int] => Type::Int,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        func.assign_instr(
+            output,
+            Type::Int.render(),
+            qbe::Instr::Add(args[0].render(), args[1].render()),
+        )
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        if Ref::from(0i64) == args[0] {
+            return Some(args[1]);
+        }
+
+        if Ref::from(0i64) == args[1] {
+            return Some(args[0]);
+        }
+
+        if let Some((x, y)) = args[0].as_i64().zip(args[1].as_i64()) {
+            return Some(x.wrapping_add(y).into());
+        }
+
+        None
+    }
+}
+
+/// Implements `a - b` for integers. Overflows wrap around.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntSub;
+
+#[typetag::serde]
+impl Op for IntSub {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Int, Type::Int] => Type::Int,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        func.assign_instr(
+            output,
+            Type::Int.render(),
+            qbe::Instr::Sub(args[0].render(), args[1].render()),
+        )
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        if Ref::from(0i64) == args[1] {
+            return Some(args[0]);
+        }
+
+        if let Some((x, y)) = args[0].as_i64().zip(args[1].as_i64()) {
+            return Some(x.wrapping_sub(y).into());
+        }
+
+        None
+    }
+}
+
+/// Implements `a * b` for integers. Overflows wrap around.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntMul;
+
+#[typetag::serde]
+impl Op for IntMul {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Int, Type::Int] => Type::Int,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        func.assign_instr(
+            output,
+            Type::Int.render(),
+            qbe::Instr::Mul(args[0].render(), args[1].render()),
+        )
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        if Ref::from(1i64) == args[0] {
+            return Some(args[1]);
+        }
+
+        if Ref::from(1i64) == args[1] {
+            return Some(args[0]);
+        }
+
+        if let Some((x, y)) = args[0].as_i64().zip(args[1].as_i64()) {
+            return Some(x.wrapping_mul(y).into());
+        }
+
+        None
+    }
+}
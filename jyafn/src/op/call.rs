@@ -1,7 +1,7 @@
 use get_size::GetSize;
 use serde_derive::{Deserialize, Serialize};
 
-use crate::{graph::SLOT_SIZE, impl_is_eq, impl_op, pfunc, Graph, Ref, Type};
+use crate::{graph::SLOT_SIZE, impl_is_eq, impl_op, pfunc, FnError, Graph, Ref, Type};
 
 use super::{unique_for, Op};
 
@@ -14,14 +14,21 @@ impl Op for Call {
     impl_is_eq! {}
 
     fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
-        let pfunc = pfunc::get(&self.0)?;
-        if pfunc.signature() == args {
-            Some(pfunc.returns())
+        let pfunc = pfunc::get_checked(&self.0, args).ok()?;
+        if pfunc.is_multi_output() {
+            // Outputs are read from the returned buffer with `LoadCallOutput`.
+            Some(Type::Ptr { origin: self_id })
         } else {
-            None
+            Some(pfunc.returns())
         }
     }
 
+    fn explain_type_error(&self, graph: &Graph, args: &[Type]) -> Option<String> {
+        pfunc::get_checked(&self.0, args)
+            .err()
+            .map(|err| err.to_string())
+    }
+
     fn render_into(
         &self,
         graph: &Graph,
@@ -31,19 +38,33 @@ impl Op for Call {
         namespace: &str,
     ) {
         let pfunc = pfunc::get(&self.0).expect("pfunc existence already checked");
-        func.assign_instr(
-            output,
-            pfunc.returns().render(),
-            qbe::Instr::Call(
+        let mut call_args = pfunc
+            .signature()
+            .iter()
+            .zip(args)
+            .map(|(ty, arg)| (ty.render(), arg.render()))
+            .collect::<Vec<_>>();
+
+        if pfunc.is_multi_output() {
+            let output_ptr = qbe::Value::Temporary(unique_for(output.clone(), "call.output"));
+            func.assign_instr(
+                output_ptr.clone(),
+                qbe::Type::Long,
+                qbe::Instr::Alloc8((pfunc.outputs().len() * SLOT_SIZE.in_bytes()) as u64),
+            );
+            call_args.push((qbe::Type::Long, output_ptr.clone()));
+            func.add_instr(qbe::Instr::Call(
                 qbe::Value::Const(pfunc.location() as u64),
-                pfunc
-                    .signature()
-                    .iter()
-                    .zip(args)
-                    .map(|(ty, arg)| (ty.render(), arg.render()))
-                    .collect(),
-            ),
-        )
+                call_args,
+            ));
+            func.assign_instr(output, qbe::Type::Long, qbe::Instr::Copy(output_ptr));
+        } else {
+            func.assign_instr(
+                output,
+                pfunc.returns().render(),
+                qbe::Instr::Call(qbe::Value::Const(pfunc.location() as u64), call_args),
+            )
+        }
     }
 
     fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
@@ -59,6 +80,69 @@ impl Op for Call {
     fn get_size(&self) -> usize {
         self.0.get_size()
     }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
+}
+
+/// Loads one of the outputs of a multi-output pure function called with [`Call`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct LoadCallOutput {
+    pub(crate) slot: usize,
+}
+
+#[typetag::serde]
+impl Op for LoadCallOutput {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        if args.len() != 1 {
+            return None;
+        }
+
+        let Type::Ptr { origin } = args[0] else {
+            return None;
+        };
+        let origin_op = graph.nodes.get(origin)?.op.downcast_ref::<Call>()?;
+        let pfunc = pfunc::get(&origin_op.0)?;
+
+        pfunc.outputs().get(self.slot).copied()
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let Ref::Node(origin) = args[0] else {
+            panic!("call output pointer is always a node")
+        };
+        let call = graph.nodes[origin]
+            .op
+            .downcast_ref::<Call>()
+            .expect("origin already checked to be a call");
+        let pfunc = pfunc::get(&call.0).expect("pfunc existence already checked");
+        let ty = pfunc.outputs()[self.slot];
+        let addr = unique_for(output.clone(), "loadcalloutput.addr");
+
+        func.assign_instr(
+            qbe::Value::Temporary(addr.clone()),
+            qbe::Type::Long,
+            qbe::Instr::Add(
+                args[0].render(),
+                qbe::Value::Const((self.slot * SLOT_SIZE.in_bytes()) as u64),
+            ),
+        );
+        func.assign_instr(
+            output,
+            ty.render(),
+            qbe::Instr::Load(ty.render(), qbe::Value::Temporary(addr)),
+        );
+    }
 }
 
 /// Calls a sub-graph by its id.
@@ -151,10 +235,86 @@ impl Op for CallGraph {
             end_side.clone(),
         ));
         func.add_block(raise_side);
-        super::render_return_error(func, status);
+        render_reraise(graph, subgraph, status, func, &output);
         func.add_block(end_side);
         func.assign_instr(output, qbe::Type::Long, qbe::Instr::Copy(output_ptr));
     }
+
+    fn remap_tables(&mut self, error_map: &[usize], subgraph_map: &[usize]) {
+        self.0 = subgraph_map[self.0];
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
+}
+
+/// Renders the return of an error raised by a subgraph. If the error is one of the errors
+/// of the subgraph, its id is translated to the id of the same error in `graph`, so that it
+/// can be looked up in [`Graph::errors`] of the caller.
+fn render_reraise(
+    graph: &Graph,
+    subgraph: &Graph,
+    status: qbe::Value,
+    func: &mut qbe::Function,
+    output: &qbe::Value,
+) {
+    let error_id = qbe::Value::Temporary(unique_for(output.clone(), "callgraph.error_id"));
+    func.assign_instr(
+        error_id.clone(),
+        qbe::Type::Long,
+        qbe::Instr::Call(
+            qbe::Value::Const(FnError::error_id_of as *const () as usize as u64),
+            vec![(qbe::Type::Long, status.clone())],
+        ),
+    );
+
+    for (sub_error_id, error) in subgraph.errors.iter().enumerate() {
+        let origin = subgraph
+            .error_origins
+            .get(&sub_error_id)
+            .map(String::as_str);
+        let Some(caller_error_id) = graph.find_error(error, origin) else {
+            continue;
+        };
+        let is_error = qbe::Value::Temporary(unique_for(
+            output.clone(),
+            &format!("callgraph.is_error.{sub_error_id}"),
+        ));
+        let set_side = unique_for(output.clone(), &format!("callgraph.set.{sub_error_id}"));
+        let next_side = unique_for(output.clone(), &format!("callgraph.next.{sub_error_id}"));
+        func.assign_instr(
+            is_error.clone(),
+            qbe::Type::Word,
+            qbe::Instr::Cmp(
+                qbe::Type::Long,
+                qbe::Cmp::Eq,
+                error_id.clone(),
+                qbe::Value::Const(sub_error_id as u64),
+            ),
+        );
+        func.add_instr(qbe::Instr::Jnz(
+            is_error,
+            set_side.clone(),
+            next_side.clone(),
+        ));
+        func.add_block(set_side);
+        func.assign_instr(
+            status.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Call(
+                qbe::Value::Const(FnError::set_error_id as *const () as usize as u64),
+                vec![
+                    (qbe::Type::Long, status.clone()),
+                    (qbe::Type::Long, qbe::Value::Const(caller_error_id as u64)),
+                ],
+            ),
+        );
+        func.add_instr(qbe::Instr::Ret(Some(status.clone())));
+        func.add_block(next_side);
+    }
+
+    func.add_instr(qbe::Instr::Ret(Some(status)));
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -180,7 +340,7 @@ impl Op for LoadSubgraphOutput {
             return None;
         }
 
-        let subgraph = graph.subgraphs.get(self.slot)?;
+        let subgraph = graph.subgraphs.get(self.subgraph)?;
         let slots = subgraph.output_layout.slots();
 
         slots.get(self.slot).copied()
@@ -212,4 +372,8 @@ impl Op for LoadSubgraphOutput {
     fn must_use(&self) -> bool {
         true
     }
+
+    fn remap_tables(&mut self, error_map: &[usize], subgraph_map: &[usize]) {
+        self.subgraph = subgraph_map[self.subgraph];
+    }
 }
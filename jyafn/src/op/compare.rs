@@ -2,7 +2,7 @@ use serde_derive::{Deserialize, Serialize};
 
 use crate::{impl_op, Graph, Ref, Type};
 
-use super::Op;
+use super::{unique_for, Op};
 
 /// Implements `a == b`.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -22,6 +22,14 @@ impl Op for Eq {
                 self.0 = Some(Type::Symbol);
                 Type::Bool
             }
+            [Type::DateTime, Type::DateTime] => {
+                self.0 = Some(Type::DateTime);
+                Type::Bool
+            }
+            [Type::Bool, Type::Bool] => {
+                self.0 = Some(Type::Bool);
+                Type::Bool
+            }
             [Type::Ptr { origin }, Type::Ptr { .. }] => {
                 self.0 = Some(Type::Ptr { origin: *origin });
                 Type::Bool
@@ -51,14 +59,53 @@ impl Op for Eq {
     }
 
     fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
-        if let Some((x, y)) = args[0].as_f64().zip(args[1].as_f64()) {
-            Some(Ref::from(x == y))
-        } else {
-            None
+        match (args[0], args[1]) {
+            (Ref::Const(Type::Float, _), Ref::Const(Type::Float, _)) => {
+                Some(Ref::from(args[0].as_f64()? == args[1].as_f64()?))
+            }
+            // Everything else is compared bit by bit.
+            (
+                Ref::Const(ty @ (Type::Symbol | Type::DateTime | Type::Bool | Type::Ptr { .. }), x),
+                Ref::Const(other, y),
+            ) if ty == other => Some(Ref::from(x == y)),
+            _ => None,
         }
     }
 }
 
+/// Renders an ordered comparison between two floats or two datetimes. Datetimes are
+/// compared chronologically, i.e., as signed timestamps.
+fn render_ordered(
+    graph: &Graph,
+    output: qbe::Value,
+    args: &[Ref],
+    func: &mut qbe::Function,
+    float_cmp: qbe::Cmp,
+    timestamp_cmp: qbe::Cmp,
+) {
+    let ty = graph.type_of(args[0]);
+    let cmp = if ty == Type::DateTime {
+        timestamp_cmp
+    } else {
+        float_cmp
+    };
+    func.assign_instr(
+        output,
+        Type::Bool.render(),
+        qbe::Instr::Cmp(ty.render(), cmp, args[0].render(), args[1].render()),
+    )
+}
+
+/// The timestamps of two constant datetimes, if both arguments are such.
+fn as_timestamps(args: &[Ref]) -> Option<(i64, i64)> {
+    match (args[0], args[1]) {
+        (Ref::Const(Type::DateTime, x), Ref::Const(Type::DateTime, y)) => {
+            Some((x as i64, y as i64))
+        }
+        _ => None,
+    }
+}
+
 /// Implements `a > b`.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Gt;
@@ -70,6 +117,7 @@ impl Op for Gt {
     fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
         Some(match args {
             [Type::Float, Type::Float] => Type::Bool,
+            [Type::DateTime, Type::DateTime] => Type::Bool,
             _ => return None,
         })
     }
@@ -82,21 +130,14 @@ impl Op for Gt {
         func: &mut qbe::Function,
         namespace: &str,
     ) {
-        func.assign_instr(
-            output,
-            Type::Bool.render(),
-            qbe::Instr::Cmp(
-                Type::Float.render(),
-                qbe::Cmp::Gt,
-                args[0].render(),
-                args[1].render(),
-            ),
-        )
+        render_ordered(graph, output, args, func, qbe::Cmp::Gt, qbe::Cmp::Sgt)
     }
 
     fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
         if let Some((x, y)) = args[0].as_f64().zip(args[1].as_f64()) {
             Some(Ref::from(x > y))
+        } else if let Some((x, y)) = as_timestamps(args) {
+            Some(Ref::from(x > y))
         } else {
             None
         }
@@ -114,6 +155,7 @@ impl Op for Lt {
     fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
         Some(match args {
             [Type::Float, Type::Float] => Type::Bool,
+            [Type::DateTime, Type::DateTime] => Type::Bool,
             _ => return None,
         })
     }
@@ -126,21 +168,14 @@ impl Op for Lt {
         func: &mut qbe::Function,
         namespace: &str,
     ) {
-        func.assign_instr(
-            output,
-            Type::Bool.render(),
-            qbe::Instr::Cmp(
-                Type::Float.render(),
-                qbe::Cmp::Lt,
-                args[0].render(),
-                args[1].render(),
-            ),
-        )
+        render_ordered(graph, output, args, func, qbe::Cmp::Lt, qbe::Cmp::Slt)
     }
 
     fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
         if let Some((x, y)) = args[0].as_f64().zip(args[1].as_f64()) {
             Some(Ref::from(x < y))
+        } else if let Some((x, y)) = as_timestamps(args) {
+            Some(Ref::from(x < y))
         } else {
             None
         }
@@ -158,6 +193,7 @@ impl Op for Ge {
     fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
         Some(match args {
             [Type::Float, Type::Float] => Type::Bool,
+            [Type::DateTime, Type::DateTime] => Type::Bool,
             _ => return None,
         })
     }
@@ -170,21 +206,14 @@ impl Op for Ge {
         func: &mut qbe::Function,
         namespace: &str,
     ) {
-        func.assign_instr(
-            output,
-            Type::Bool.render(),
-            qbe::Instr::Cmp(
-                Type::Float.render(),
-                qbe::Cmp::Ge,
-                args[0].render(),
-                args[1].render(),
-            ),
-        )
+        render_ordered(graph, output, args, func, qbe::Cmp::Ge, qbe::Cmp::Sge)
     }
 
     fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
         if let Some((x, y)) = args[0].as_f64().zip(args[1].as_f64()) {
             Some(Ref::from(x >= y))
+        } else if let Some((x, y)) = as_timestamps(args) {
+            Some(Ref::from(x >= y))
         } else {
             None
         }
@@ -202,6 +231,45 @@ impl Op for Le {
     fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
         Some(match args {
             [Type::Float, Type::Float] => Type::Bool,
+            [Type::DateTime, Type::DateTime] => Type::Bool,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        render_ordered(graph, output, args, func, qbe::Cmp::Le, qbe::Cmp::Sle)
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        if let Some((x, y)) = args[0].as_f64().zip(args[1].as_f64()) {
+            Some(Ref::from(x <= y))
+        } else if let Some((x, y)) = as_timestamps(args) {
+            Some(Ref::from(x <= y))
+        } else {
+            None
+        }
+    }
+}
+
+/// Tests whether a float is NaN. Rendered as `x != x`, which only holds for NaN. Note
+/// that `isnan(NaN)` is `true`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IsNan;
+
+#[typetag::serde]
+impl Op for IsNan {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float] => Type::Bool,
             _ => return None,
         })
     }
@@ -219,18 +287,70 @@ impl Op for Le {
             Type::Bool.render(),
             qbe::Instr::Cmp(
                 Type::Float.render(),
-                qbe::Cmp::Le,
+                qbe::Cmp::Ne,
+                args[0].render(),
                 args[0].render(),
-                args[1].render(),
             ),
         )
     }
 
     fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
-        if let Some((x, y)) = args[0].as_f64().zip(args[1].as_f64()) {
-            Some(Ref::from(x <= y))
-        } else {
-            None
-        }
+        Some(Ref::from(args[0].as_f64()?.is_nan()))
+    }
+}
+
+/// Tests whether a float is neither infinite nor NaN. Rendered as the range check
+/// `-inf < x && x < inf`, which fails for NaN, since all comparisons with NaN are false.
+/// Note that `isfinite(inf)` is `false`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IsFinite;
+
+#[typetag::serde]
+impl Op for IsFinite {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float] => Type::Bool,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let above = qbe::Value::Temporary(unique_for(output.clone(), "isfinite.above"));
+        let below = qbe::Value::Temporary(unique_for(output.clone(), "isfinite.below"));
+
+        func.assign_instr(
+            above.clone(),
+            Type::Bool.render(),
+            qbe::Instr::Cmp(
+                Type::Float.render(),
+                qbe::Cmp::Gt,
+                args[0].render(),
+                Ref::from(f64::NEG_INFINITY).render(),
+            ),
+        );
+        func.assign_instr(
+            below.clone(),
+            Type::Bool.render(),
+            qbe::Instr::Cmp(
+                Type::Float.render(),
+                qbe::Cmp::Lt,
+                args[0].render(),
+                Ref::from(f64::INFINITY).render(),
+            ),
+        );
+        func.assign_instr(output, Type::Bool.render(), qbe::Instr::And(above, below));
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        Some(Ref::from(args[0].as_f64()?.is_finite()))
     }
 }
@@ -2,7 +2,7 @@ use serde_derive::{Deserialize, Serialize};
 
 use crate::{impl_op, Graph, Ref, Type};
 
-use super::Op;
+use super::{unique_for, Op};
 
 /// Converts a float to a boolean. This is equivalent to `a != 0`.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -86,3 +86,137 @@ impl Op for ToFloat {
         None
     }
 }
+
+/// Converts a float to an integer, truncating towards zero. NaNs and floats out of the
+/// range of a 64-bit integer have unspecified results.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToInt;
+
+#[typetag::serde]
+impl Op for ToInt {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Float] => Type::Int,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        render_signed_conversion(
+            output,
+            args[0],
+            func,
+            (Type::Float, qbe::Cmp::Lt),
+            Type::Int,
+            qbe::Instr::Dtoui,
+        )
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        if let Some(x) = args[0].as_f64() {
+            return Some((x as i64).into());
+        }
+
+        None
+    }
+}
+
+/// Converts an integer to a float. Integers with magnitude larger than `2^53` are rounded
+/// to the nearest representable float.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntToFloat;
+
+#[typetag::serde]
+impl Op for IntToFloat {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Int] => Type::Float,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        render_signed_conversion(
+            output,
+            args[0],
+            func,
+            (Type::Int, qbe::Cmp::Slt),
+            Type::Float,
+            qbe::Instr::Ultof,
+        )
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        if let Some(x) = args[0].as_i64() {
+            return Some((x as f64).into());
+        }
+
+        None
+    }
+}
+
+/// The `qbe` crate only exposes the conversions between floats and _unsigned_ integers
+/// (`dtoui` and `ultof`), not the signed ones QBE also has (`dtosi` and `sltof`). This
+/// renders a signed conversion by converting the magnitude of the value and restoring its
+/// sign afterwards. The comparison in `from` is the "less than" comparison for the type
+/// of the input.
+fn render_signed_conversion(
+    output: qbe::Value,
+    arg: Ref,
+    func: &mut qbe::Function,
+    from: (Type, qbe::Cmp),
+    to: Type,
+    convert: fn(qbe::Value) -> qbe::Instr<'static>,
+) {
+    let (from, less_than) = from;
+    let test_temp = qbe::Value::Temporary(unique_for(output.clone(), "convert.test"));
+    let magnitude = qbe::Value::Temporary(unique_for(output.clone(), "convert.magnitude"));
+    let converted = qbe::Value::Temporary(unique_for(output.clone(), "convert.converted"));
+    let negative_side = unique_for(output.clone(), "convert.if.negative");
+    let positive_side = unique_for(output.clone(), "convert.if.positive");
+    let end_side = unique_for(output.clone(), "convert.if.end");
+
+    func.assign_instr(
+        test_temp.clone(),
+        Type::Bool.render(),
+        qbe::Instr::Cmp(from.render(), less_than, arg.render(), qbe::Value::Const(0)),
+    );
+    func.add_instr(qbe::Instr::Jnz(
+        test_temp,
+        negative_side.clone(),
+        positive_side.clone(),
+    ));
+
+    func.add_block(negative_side);
+    func.assign_instr(
+        magnitude.clone(),
+        from.render(),
+        qbe::Instr::Neg(arg.render()),
+    );
+    func.assign_instr(converted.clone(), to.render(), convert(magnitude));
+    func.assign_instr(output.clone(), to.render(), qbe::Instr::Neg(converted));
+    func.add_instr(qbe::Instr::Jmp(end_side.clone()));
+
+    func.add_block(positive_side);
+    func.assign_instr(output, to.render(), convert(arg.render()));
+
+    func.add_block(end_side);
+}
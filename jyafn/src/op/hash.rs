@@ -0,0 +1,109 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::mapping::update_hash;
+use crate::{impl_op, Graph, Ref, Type};
+
+use super::{unique_for, Op};
+
+/// Computes the bucket in `[0, k)` of the values of the arguments using the murmur hash
+/// with a given seed. This implements the "hashing trick" for feature hashing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureHash {
+    /// The number of buckets.
+    pub k: u64,
+    /// The seed of the hash.
+    pub seed: u64,
+}
+
+/// Finds the bucket of a given hash. This is called from jyafn code.
+extern "C" fn hash_bucket(hash: u64, k: u64) -> f64 {
+    (hash % k) as f64
+}
+
+#[typetag::serde]
+impl Op for FeatureHash {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        if self.k == 0 || args.is_empty() {
+            return None;
+        }
+
+        if args.iter().any(|ty| matches!(ty, Type::Ptr { .. })) {
+            return None;
+        }
+
+        Some(Type::Float)
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let hash = qbe::Value::Temporary(unique_for(output.clone(), "featurehash.hash"));
+
+        func.assign_instr(
+            hash.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Copy(qbe::Value::Const(self.seed)),
+        );
+
+        for (i, &arg) in args.iter().enumerate() {
+            let cast =
+                qbe::Value::Temporary(unique_for(output.clone(), &format!("featurehash.cast{i}")));
+
+            func.assign_instr(
+                cast.clone(),
+                qbe::Type::Long,
+                if graph.type_of(arg).render() != qbe::Type::Long && !matches!(arg, Ref::Const(..))
+                {
+                    qbe::Instr::Cast(arg.render())
+                } else {
+                    qbe::Instr::Copy(arg.render())
+                },
+            );
+
+            func.assign_instr(
+                hash.clone(),
+                qbe::Type::Long,
+                qbe::Instr::Call(
+                    qbe::Value::Const(update_hash as *const () as usize as u64),
+                    vec![(qbe::Type::Long, hash.clone()), (qbe::Type::Long, cast)],
+                ),
+            );
+        }
+
+        func.assign_instr(
+            output,
+            Type::Float.render(),
+            qbe::Instr::Call(
+                qbe::Value::Const(hash_bucket as *const () as usize as u64),
+                vec![
+                    (qbe::Type::Long, hash),
+                    (qbe::Type::Long, qbe::Value::Const(self.k)),
+                ],
+            ),
+        );
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        let mut hash = i64::from_ne_bytes(self.seed.to_ne_bytes());
+
+        for &arg in args {
+            let Ref::Const(_, repr) = arg else {
+                return None;
+            };
+            hash = update_hash(hash, i64::from_ne_bytes(repr.to_ne_bytes()));
+        }
+
+        Some(hash_bucket(u64::from_ne_bytes(hash.to_ne_bytes()), self.k).into())
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
+}
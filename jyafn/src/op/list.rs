@@ -60,11 +60,64 @@ impl Op for List {
     }
 }
 
+/// How an index into a list is interpreted before it is checked against the bounds of
+/// the list. Fractional indices are truncated towards zero in all modes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IndexMode {
+    /// The index is an offset from the start of the list. Indices out of bounds raise an
+    /// error.
+    #[default]
+    Strict,
+    /// Negative indices count from the end of the list, i.e., `-1` is the last element.
+    /// Indices still out of bounds raise an error.
+    NegativeWrap,
+    /// The index wraps around the list, i.e., it is taken modulo the length of the list.
+    /// Indexing an empty list still raises an error.
+    Modulo,
+}
+
+impl IndexMode {
+    /// Normalizes `idx` into an offset from the start of a list of `n_elements`
+    /// elements. The result is not guaranteed to be within bounds.
+    pub fn normalize(self, idx: f64, n_elements: usize) -> f64 {
+        let idx = idx.trunc();
+        let n_elements = n_elements as f64;
+        match self {
+            IndexMode::NegativeWrap if idx < 0.0 => idx + n_elements,
+            IndexMode::Modulo if n_elements > 0.0 => idx.rem_euclid(n_elements),
+            _ => idx,
+        }
+    }
+
+    /// Normalizes the integer `idx` into an offset from the start of a list of
+    /// `n_elements` elements. The result is not guaranteed to be within bounds.
+    pub fn normalize_int(self, idx: i64, n_elements: usize) -> i64 {
+        let n_elements = n_elements as i64;
+        match self {
+            IndexMode::NegativeWrap if idx < 0 => idx.wrapping_add(n_elements),
+            IndexMode::Modulo if n_elements > 0 => idx.rem_euclid(n_elements),
+            _ => idx,
+        }
+    }
+}
+
+/// Normalizes an index in modulo mode. This is called from jyafn code.
+extern "C" fn normalize_modulo(idx: f64, n_elements: u64) -> f64 {
+    IndexMode::Modulo.normalize(idx, n_elements as usize)
+}
+
+/// Normalizes an integer index in modulo mode. This is called from jyafn code.
+extern "C" fn normalize_modulo_int(idx: i64, n_elements: u64) -> i64 {
+    IndexMode::Modulo.normalize_int(idx, n_elements as usize)
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) struct Index {
     pub element: Type,
     pub n_elements: usize,
     pub error: usize,
+    #[serde(default)]
+    pub mode: IndexMode,
 }
 
 #[typetag::serde]
@@ -72,7 +125,7 @@ impl Op for Index {
     impl_op! {}
 
     fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
-        if args.len() != 2 || args[1] != Type::Float {
+        if args.len() != 2 || !matches!(args[1], Type::Float | Type::Int) {
             return None;
         }
 
@@ -101,11 +154,51 @@ impl Op for Index {
         let out_of_bounds = unique_for(output.clone(), "index.out_of_bounds");
         let in_bounds = unique_for(output.clone(), "index.in_bounds");
 
-        func.assign_instr(
-            displacement.clone(),
-            qbe::Type::Long,
-            qbe::Instr::Dtoui(args[1].render()),
-        );
+        // Integer indices are used directly, without the round-trip through floats.
+        let index_type = graph.type_of(args[1]);
+        let index = match self.mode {
+            IndexMode::Strict | IndexMode::NegativeWrap => args[1].render(),
+            IndexMode::Modulo => {
+                let normalize = match index_type {
+                    Type::Int => normalize_modulo_int as *const (),
+                    _ => normalize_modulo as *const (),
+                };
+                let normalized =
+                    qbe::Value::Temporary(unique_for(output.clone(), "index.normalized"));
+                func.assign_instr(
+                    normalized.clone(),
+                    index_type.render(),
+                    qbe::Instr::Call(
+                        qbe::Value::Const(normalize as usize as u64),
+                        vec![
+                            (index_type.render(), args[1].render()),
+                            (qbe::Type::Long, qbe::Value::Const(self.n_elements as u64)),
+                        ],
+                    ),
+                );
+                normalized
+            }
+        };
+        if self.mode == IndexMode::NegativeWrap {
+            render_negative_wrap(
+                displacement.clone(),
+                index,
+                index_type,
+                self.n_elements,
+                output.clone(),
+                func,
+            );
+        } else {
+            func.assign_instr(
+                displacement.clone(),
+                qbe::Type::Long,
+                if index_type == Type::Int {
+                    qbe::Instr::Copy(index)
+                } else {
+                    qbe::Instr::Dtoui(index)
+                },
+            );
+        }
         func.assign_instr(
             test_bounds.clone(),
             qbe::Type::Long,
@@ -126,6 +219,7 @@ impl Op for Index {
         super::render_return_error(
             func,
             qbe::Value::Global(format!("{namespace}.error.{}", self.error)),
+            Some(self.error),
         );
 
         func.add_block(in_bounds);
@@ -148,6 +242,98 @@ impl Op for Index {
             qbe::Instr::Load(self.element.render(), displacement),
         );
     }
+
+    fn remap_tables(&mut self, error_map: &[usize], subgraph_map: &[usize]) {
+        self.error = error_map[self.error];
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
+}
+
+/// Renders the displacement of a negative wrap index into a list, adding the number of
+/// elements to negative indices. Indices below `-n_elements` stay negative and thus are
+/// out of bounds when compared as unsigned.
+fn render_negative_wrap(
+    displacement: qbe::Value,
+    index: qbe::Value,
+    index_type: Type,
+    n_elements: usize,
+    output: qbe::Value,
+    func: &mut qbe::Function,
+) {
+    let is_negative = qbe::Value::Temporary(unique_for(output.clone(), "index.is_negative"));
+    let magnitude = qbe::Value::Temporary(unique_for(output.clone(), "index.magnitude"));
+    let truncated = qbe::Value::Temporary(unique_for(output.clone(), "index.truncated"));
+    let negative_side = unique_for(output.clone(), "index.if.negative");
+    let positive_side = unique_for(output.clone(), "index.if.positive");
+    let end_side = unique_for(output, "index.if.end");
+
+    // Fractional indices are truncated towards zero, so only those up to `-1` wrap.
+    func.assign_instr(
+        is_negative.clone(),
+        Type::Bool.render(),
+        if index_type == Type::Int {
+            qbe::Instr::Cmp(
+                qbe::Type::Long,
+                qbe::Cmp::Slt,
+                index.clone(),
+                qbe::Value::Const(0),
+            )
+        } else {
+            qbe::Instr::Cmp(
+                qbe::Type::Double,
+                qbe::Cmp::Le,
+                index.clone(),
+                qbe::Value::Const((-1.0f64).to_bits()),
+            )
+        },
+    );
+    func.add_instr(qbe::Instr::Jnz(
+        is_negative,
+        negative_side.clone(),
+        positive_side.clone(),
+    ));
+
+    func.add_block(negative_side);
+    if index_type == Type::Int {
+        func.assign_instr(
+            displacement.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Add(index.clone(), qbe::Value::Const(n_elements as u64)),
+        );
+    } else {
+        func.assign_instr(
+            magnitude.clone(),
+            qbe::Type::Double,
+            qbe::Instr::Neg(index.clone()),
+        );
+        func.assign_instr(
+            truncated.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Dtoui(magnitude),
+        );
+        func.assign_instr(
+            displacement.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Sub(qbe::Value::Const(n_elements as u64), truncated),
+        );
+    }
+    func.add_instr(qbe::Instr::Jmp(end_side.clone()));
+
+    func.add_block(positive_side);
+    func.assign_instr(
+        displacement,
+        qbe::Type::Long,
+        if index_type == Type::Int {
+            qbe::Instr::Copy(index)
+        } else {
+            qbe::Instr::Dtoui(index)
+        },
+    );
+
+    func.add_block(end_side);
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
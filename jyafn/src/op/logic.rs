@@ -1,8 +1,8 @@
 use serde_derive::{Deserialize, Serialize};
 
-use crate::{impl_op, Graph, Ref, Type};
+use crate::{graph::SLOT_SIZE, impl_op, Graph, Ref, Type};
 
-use super::{unique_for, Op};
+use super::{origin_of, render_load_slot, render_slot_addr, unique_for, Op};
 
 /// Implements an assertion. If the input is `false`, this operation will raise a runtime
 /// error.
@@ -39,6 +39,7 @@ impl Op for Assert {
         super::render_return_error(
             func,
             qbe::Value::Global(format!("{namespace}.error.{}", self.0)),
+            Some(self.0 as usize),
         );
         func.add_block(true_side);
     }
@@ -58,6 +59,14 @@ impl Op for Assert {
     fn is_illegal(&self, graph: &Graph, args: &[Ref]) -> bool {
         matches!(args[0].as_bool(), Some(false))
     }
+
+    fn remap_tables(&mut self, error_map: &[usize], subgraph_map: &[usize]) {
+        self.0 = error_map[self.0 as usize] as u64;
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
 }
 
 /// The ternary operator. This implements `if a then b else c`.
@@ -128,6 +137,367 @@ impl Op for Choose {
     }
 }
 
+/// Chooses one among `n` values by its index, i.e., `[v0, v1, ...][index]`. The first
+/// argument is the index, which is either an int or a float (truncated towards zero), and
+/// the following `n` arguments are the values, all of the same type. Indices out of range
+/// raise an error.
+///
+/// This renders as a sequence of comparisons, jumping straight to the selected value. When
+/// compiled, the nodes needed only by one of the values are only computed if that value
+/// is selected. Since jyafn code has no side effects, this is just an optimization: nodes
+/// that are also needed by something else (e.g., by another value or by the output) are
+/// computed anyway.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Select {
+    pub n: usize,
+    pub error: usize,
+}
+
+impl Select {
+    /// The index selected by a constant index ref, if any and if it is in range.
+    fn const_index(&self, index: Ref) -> Option<usize> {
+        let index = match index {
+            Ref::Const(Type::Int, _) => index.as_i64()?,
+            Ref::Const(Type::Float, _) => {
+                let index = index.as_f64()?.trunc();
+                if index.is_nan() || index < 0.0 {
+                    return None;
+                }
+                index as i64
+            }
+            _ => return None,
+        };
+        usize::try_from(index).ok().filter(|&index| index < self.n)
+    }
+
+    /// Renders the jump to the label of the case selected by the index in `args[0]`,
+    /// returning an error if the index is out of range. The `output` is used to derive
+    /// names for temporaries and labels.
+    pub(crate) fn render_dispatch(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        cases: &[String],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let index = qbe::Value::Temporary(unique_for(output.clone(), "select.index"));
+        let test = qbe::Value::Temporary(unique_for(output.clone(), "select.test"));
+        let out_of_range = unique_for(output.clone(), "select.out_of_range");
+
+        // Floats are range-checked before conversion, so that they can be safely
+        // truncated to an integer. Ints out of range simply match none of the cases and
+        // fall through to the error.
+        if graph.type_of(args[0]) == Type::Int {
+            func.assign_instr(
+                index.clone(),
+                qbe::Type::Long,
+                qbe::Instr::Copy(args[0].render()),
+            );
+        } else {
+            let above_minus_one = unique_for(output.clone(), "select.if.above_minus_one");
+            let below_n = unique_for(output.clone(), "select.if.below_n");
+            func.assign_instr(
+                test.clone(),
+                Type::Bool.render(),
+                qbe::Instr::Cmp(
+                    qbe::Type::Double,
+                    qbe::Cmp::Gt,
+                    args[0].render(),
+                    qbe::Value::Const((-1.0f64).to_bits()),
+                ),
+            );
+            func.add_instr(qbe::Instr::Jnz(
+                test.clone(),
+                above_minus_one.clone(),
+                out_of_range.clone(),
+            ));
+            func.add_block(above_minus_one);
+            func.assign_instr(
+                test.clone(),
+                Type::Bool.render(),
+                qbe::Instr::Cmp(
+                    qbe::Type::Double,
+                    qbe::Cmp::Lt,
+                    args[0].render(),
+                    qbe::Value::Const((self.n as f64).to_bits()),
+                ),
+            );
+            func.add_instr(qbe::Instr::Jnz(
+                test.clone(),
+                below_n.clone(),
+                out_of_range.clone(),
+            ));
+            func.add_block(below_n);
+            func.assign_instr(
+                index.clone(),
+                qbe::Type::Long,
+                qbe::Instr::Dtoui(args[0].render()),
+            );
+        }
+
+        for (i, case) in cases.iter().enumerate() {
+            let next = unique_for(output.clone(), &format!("select.next{i}"));
+            func.assign_instr(
+                test.clone(),
+                Type::Bool.render(),
+                qbe::Instr::Cmp(
+                    qbe::Type::Long,
+                    qbe::Cmp::Eq,
+                    index.clone(),
+                    qbe::Value::Const(i as u64),
+                ),
+            );
+            func.add_instr(qbe::Instr::Jnz(test.clone(), case.clone(), next.clone()));
+            func.add_block(next);
+        }
+
+        func.add_block(out_of_range);
+        super::render_return_error(
+            func,
+            qbe::Value::Global(format!("{namespace}.error.{}", self.error)),
+            Some(self.error),
+        );
+    }
+}
+
+#[typetag::serde]
+impl Op for Select {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        let [Type::Float | Type::Int, first, rest @ ..] = args else {
+            return None;
+        };
+        if rest.len() + 1 != self.n || rest.iter().any(|ty| ty != first) {
+            return None;
+        }
+
+        Some(*first)
+    }
+
+    fn explain_type_error(&self, graph: &Graph, args: &[Type]) -> Option<String> {
+        match args {
+            [] => Some("select needs an index".to_string()),
+            [index, ..] if !matches!(index, Type::Float | Type::Int) => Some(format!(
+                "select index must be an int or a float, got {index}"
+            )),
+            [_, values @ ..] if values.len() != self.n => Some(format!(
+                "select expects {} values, got {}",
+                self.n,
+                values.len()
+            )),
+            [_, first, values @ ..] => values.iter().find(|ty| *ty != first).map(|other| {
+                format!("all values of select must have the same type, got {first} and {other}")
+            }),
+            _ => None,
+        }
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let cases = (0..self.n)
+            .map(|i| unique_for(output.clone(), &format!("select.case{i}")))
+            .collect::<Vec<_>>();
+        let end = unique_for(output.clone(), "select.end");
+        let ty = graph.type_of(args[1]).render();
+
+        self.render_dispatch(graph, output.clone(), args, &cases, func, namespace);
+
+        for (case, value) in cases.into_iter().zip(&args[1..]) {
+            func.add_block(case);
+            func.assign_instr(output.clone(), ty.clone(), qbe::Instr::Copy(value.render()));
+            func.add_instr(qbe::Instr::Jmp(end.clone()));
+        }
+
+        func.add_block(end);
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        self.const_index(args[0]).map(|index| args[1 + index])
+    }
+
+    fn is_illegal(&self, graph: &Graph, args: &[Ref]) -> bool {
+        matches!(args[0], Ref::Const(..)) && self.const_index(args[0]).is_none()
+    }
+
+    fn remap_tables(&mut self, error_map: &[usize], subgraph_map: &[usize]) {
+        self.error = error_map[self.error];
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
+}
+
+/// Replaces the elements of a list of `n` values by a default wherever a mask is false,
+/// i.e., `[if m0 then v0 else default, if m1 then v1 else default, ...]`. The arguments
+/// are the `n` values, all of the same type, followed by the `n` bools of the mask and
+/// by the default, of the same type as the values. The resulting list is read with
+/// [`LoadMaskedSelectOutput`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaskedSelect {
+    pub n: usize,
+}
+
+impl MaskedSelect {
+    /// Splits the arguments into the values, the mask and the default, if they have the
+    /// right number of elements.
+    fn split<'a, T>(&self, args: &'a [T]) -> Option<(&'a [T], &'a [T], &'a T)> {
+        if args.len() != 2 * self.n + 1 {
+            return None;
+        }
+
+        let (values, rest) = args.split_at(self.n);
+        let (mask, default) = rest.split_at(self.n);
+        Some((values, mask, &default[0]))
+    }
+}
+
+#[typetag::serde]
+impl Op for MaskedSelect {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        let (values, mask, default) = self.split(args)?;
+        if matches!(default, Type::Ptr { .. })
+            || values.iter().any(|ty| ty != default)
+            || mask.iter().any(|&ty| ty != Type::Bool)
+        {
+            return None;
+        }
+
+        Some(Type::Ptr { origin: self_id })
+    }
+
+    fn explain_type_error(&self, graph: &Graph, args: &[Type]) -> Option<String> {
+        let Some((values, mask, default)) = self.split(args) else {
+            return Some(format!(
+                "masked select of {} values expects {} arguments, got {}",
+                self.n,
+                2 * self.n + 1,
+                args.len()
+            ));
+        };
+
+        if let Some(other) = values.iter().find(|ty| *ty != default) {
+            return Some(format!(
+                "all values of masked select must have the type of the default {default}, \
+                got {other}"
+            ));
+        }
+
+        mask.iter()
+            .find(|&&ty| ty != Type::Bool)
+            .map(|other| format!("mask of masked select must be of bools, got {other}"))
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let (values, mask, default) = self.split(args).expect("arguments already checked");
+        let ty = graph.type_of(*default).render();
+        let addr = qbe::Value::Temporary(unique_for(output.clone(), "maskedselect.addr"));
+
+        func.assign_instr(
+            output.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Alloc8((self.n * SLOT_SIZE).in_bytes() as u64),
+        );
+
+        for (i, (value, flag)) in values.iter().zip(mask).enumerate() {
+            let true_side = unique_for(output.clone(), &format!("maskedselect.if.true{i}"));
+            let false_side = unique_for(output.clone(), &format!("maskedselect.if.false{i}"));
+            let end_side = unique_for(output.clone(), &format!("maskedselect.if.end{i}"));
+
+            render_slot_addr(addr.clone(), output.clone(), i, func);
+            func.add_instr(qbe::Instr::Jnz(
+                flag.render(),
+                true_side.clone(),
+                false_side.clone(),
+            ));
+
+            func.add_block(true_side);
+            func.add_instr(qbe::Instr::Store(ty.clone(), addr.clone(), value.render()));
+            func.add_instr(qbe::Instr::Jmp(end_side.clone()));
+
+            func.add_block(false_side);
+            func.add_instr(qbe::Instr::Store(
+                ty.clone(),
+                addr.clone(),
+                default.render(),
+            ));
+
+            func.add_block(end_side);
+        }
+    }
+}
+
+/// Loads one of the elements of the list produced by [`MaskedSelect`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct LoadMaskedSelectOutput {
+    pub(crate) slot: usize,
+}
+
+#[typetag::serde]
+impl Op for LoadMaskedSelectOutput {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        let [Type::Ptr { origin }] = args else {
+            return None;
+        };
+        let (select, select_args) = origin_of::<MaskedSelect>(graph, Ref::Node(*origin))?;
+        let (values, _, default) = select.split(select_args)?;
+        values.get(self.slot)?;
+
+        Some(graph.type_of(*default))
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let (select, select_args) = origin_of::<MaskedSelect>(graph, args[0])
+            .expect("origin already checked to be a masked select");
+        let ty = graph.type_of(select_args[2 * select.n]).render();
+
+        render_load_slot(output, ty, args[0], self.slot, func, "loadmaskedselect");
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        let (select, select_args) = origin_of::<MaskedSelect>(graph, args[0])?;
+        let (values, mask, default) = select.split(select_args)?;
+        let (value, flag) = (values[self.slot], mask[self.slot]);
+
+        if Ref::from(true) == flag || value == *default {
+            return Some(value);
+        }
+
+        if Ref::from(false) == flag {
+            return Some(*default);
+        }
+
+        None
+    }
+}
+
 /// Implements `!a`.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Not;
@@ -163,7 +533,7 @@ impl Op for Not {
             return Some(Ref::from(false));
         }
 
-        if Ref::from(false) == args[1] {
+        if Ref::from(false) == args[0] {
             return Some(Ref::from(true));
         }
 
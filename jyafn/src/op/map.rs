@@ -0,0 +1,273 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{graph::SLOT_SIZE, impl_op, Graph, Ref, Type};
+
+use super::{unique_for, Op};
+
+/// The address of a slot of the input of the function, given as an integer number of
+/// slots from the start of the input. This is used to find the entries of the maps in
+/// the input (see [`crate::layout::Layout::Map`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct InputAddress;
+
+#[typetag::serde]
+impl Op for InputAddress {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Int] => Type::Int,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let offset = qbe::Value::Temporary(unique_for(output.clone(), "inputaddress.offset"));
+
+        func.assign_instr(
+            offset.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Mul(
+                args[0].render(),
+                qbe::Value::Const(SLOT_SIZE.in_bytes() as u64),
+            ),
+        );
+        func.assign_instr(
+            output,
+            qbe::Type::Long,
+            qbe::Instr::Add(qbe::Value::Temporary("in.base".to_string()), offset),
+        );
+    }
+}
+
+/// Finds the entry of a key in a map, given the number of entries, the address of the
+/// first entry and the key symbol. The result is the address of the entry, or `0` if the
+/// key is not in the map. Entries are `stride` slots long, including the key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct MapFind {
+    pub(crate) stride: usize,
+}
+
+#[typetag::serde]
+impl Op for MapFind {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Int, Type::Int, Type::Symbol] => Type::Int,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let temp = |name: &str| qbe::Value::Temporary(unique_for(output.clone(), name));
+        let remaining = temp("mapfind.remaining");
+        let entry = temp("mapfind.entry");
+        let has_next = temp("mapfind.has_next");
+        let key = temp("mapfind.key");
+        let is_found = temp("mapfind.is_found");
+        let loop_start = unique_for(output.clone(), "mapfind.loop");
+        let body = unique_for(output.clone(), "mapfind.body");
+        let found = unique_for(output.clone(), "mapfind.found");
+        let next = unique_for(output.clone(), "mapfind.next");
+        let end = unique_for(output.clone(), "mapfind.end");
+
+        func.assign_instr(
+            output.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Copy(qbe::Value::Const(0)),
+        );
+        func.assign_instr(
+            remaining.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Copy(args[0].render()),
+        );
+        func.assign_instr(
+            entry.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Copy(args[1].render()),
+        );
+
+        func.add_block(loop_start.clone());
+        func.assign_instr(
+            has_next.clone(),
+            Type::Bool.render(),
+            qbe::Instr::Cmp(
+                qbe::Type::Long,
+                qbe::Cmp::Sgt,
+                remaining.clone(),
+                qbe::Value::Const(0),
+            ),
+        );
+        func.add_instr(qbe::Instr::Jnz(has_next, body.clone(), end.clone()));
+
+        func.add_block(body);
+        func.assign_instr(
+            key.clone(),
+            Type::Symbol.render(),
+            qbe::Instr::Load(Type::Symbol.render(), entry.clone()),
+        );
+        func.assign_instr(
+            is_found.clone(),
+            Type::Bool.render(),
+            qbe::Instr::Cmp(Type::Symbol.render(), qbe::Cmp::Eq, key, args[2].render()),
+        );
+        func.add_instr(qbe::Instr::Jnz(is_found, found.clone(), next.clone()));
+
+        func.add_block(found);
+        func.assign_instr(output, qbe::Type::Long, qbe::Instr::Copy(entry.clone()));
+        func.add_instr(qbe::Instr::Jmp(end.clone()));
+
+        func.add_block(next);
+        func.assign_instr(
+            entry.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Add(
+                entry,
+                qbe::Value::Const((self.stride * SLOT_SIZE.in_bytes()) as u64),
+            ),
+        );
+        func.assign_instr(
+            remaining.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Sub(remaining, qbe::Value::Const(1)),
+        );
+        func.add_instr(qbe::Instr::Jmp(loop_start));
+
+        func.add_block(end);
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        // Nothing is found in an empty map.
+        (args[0] == Ref::Const(Type::Int, 0)).then_some(Ref::Const(Type::Int, 0))
+    }
+}
+
+/// Tests whether an entry found by [`MapFind`] exists.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct MapFound;
+
+#[typetag::serde]
+impl Op for MapFound {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Int] => Type::Bool,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        func.assign_instr(
+            output,
+            Type::Bool.render(),
+            qbe::Instr::Cmp(
+                qbe::Type::Long,
+                qbe::Cmp::Ne,
+                args[0].render(),
+                qbe::Value::Const(0),
+            ),
+        );
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        let Ref::Const(Type::Int, entry) = args[0] else {
+            return None;
+        };
+        Some(Ref::from(entry != 0))
+    }
+}
+
+/// Loads one of the slots of the value of an entry found by [`MapFind`]. The key is
+/// slot `0`. If the entry does not exist, this is zero.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct LoadMapValue {
+    pub(crate) slot: usize,
+    pub(crate) ty: Type,
+}
+
+#[typetag::serde]
+impl Op for LoadMapValue {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        Some(match args {
+            [Type::Int] => self.ty,
+            _ => return None,
+        })
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let is_found = qbe::Value::Temporary(unique_for(output.clone(), "loadmapvalue.is_found"));
+        let addr = qbe::Value::Temporary(unique_for(output.clone(), "loadmapvalue.addr"));
+        let found = unique_for(output.clone(), "loadmapvalue.found");
+        let end = unique_for(output.clone(), "loadmapvalue.end");
+
+        func.assign_instr(
+            output.clone(),
+            self.ty.render(),
+            qbe::Instr::Copy(Ref::Const(self.ty, 0).render()),
+        );
+        func.assign_instr(
+            is_found.clone(),
+            Type::Bool.render(),
+            qbe::Instr::Cmp(
+                qbe::Type::Long,
+                qbe::Cmp::Ne,
+                args[0].render(),
+                qbe::Value::Const(0),
+            ),
+        );
+        func.add_instr(qbe::Instr::Jnz(is_found, found.clone(), end.clone()));
+
+        func.add_block(found);
+        func.assign_instr(
+            addr.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Add(
+                args[0].render(),
+                qbe::Value::Const((self.slot * SLOT_SIZE.in_bytes()) as u64),
+            ),
+        );
+        func.assign_instr(
+            output,
+            self.ty.render(),
+            qbe::Instr::Load(self.ty.render(), addr),
+        );
+
+        func.add_block(end);
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        (args[0] == Ref::Const(Type::Int, 0)).then_some(Ref::Const(self.ty, 0))
+    }
+}
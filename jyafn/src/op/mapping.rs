@@ -1,6 +1,7 @@
 use byte_slice_cast::AsByteSlice;
 use get_size::GetSize;
 use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
 
 use crate::{impl_is_eq, Graph, Ref, Type};
 
@@ -39,6 +40,26 @@ impl Op for CallMapping {
         func: &mut qbe::Function,
         namespace: &str,
     ) {
+        let buffer = qbe::Value::Temporary(unique_for(output.clone(), "callmapping.buffer"));
+        let error_ptr = qbe::Value::Temporary(unique_for(output.clone(), "callmapping.error"));
+        let error = qbe::Value::Temporary(unique_for(output.clone(), "callmapping.status"));
+        let raise_side = unique_for(output.clone(), "callmapping.raise");
+        let end_side = unique_for(output.clone(), "callmapping.end");
+
+        // Storages that don't keep their values in memory copy them to `buffer`.
+        let buffer_size = graph.mappings[&self.name].value_buffer_size() as u64;
+        func.assign_instr(
+            buffer.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Alloc8(buffer_size.max(8)),
+        );
+        func.assign_instr(error_ptr.clone(), qbe::Type::Long, qbe::Instr::Alloc8(8));
+        func.add_instr(qbe::Instr::Store(
+            qbe::Type::Long,
+            error_ptr.clone(),
+            qbe::Value::Const(0),
+        ));
+
         func.assign_instr(
             output.clone(),
             Type::Ptr { origin: usize::MAX }.render(),
@@ -46,16 +67,56 @@ impl Op for CallMapping {
                 qbe::Value::Global(format!("{namespace}.mapping.{}", self.name)),
                 args.iter()
                     .map(|&r| (graph.type_of(r).render(), r.render()))
+                    .chain([
+                        (qbe::Type::Long, buffer),
+                        (qbe::Type::Long, error_ptr.clone()),
+                    ])
                     .collect(),
             ),
         );
+
+        func.assign_instr(
+            error.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Load(qbe::Type::Long, error_ptr),
+        );
+        func.add_instr(qbe::Instr::Jnz(
+            error.clone(),
+            raise_side.clone(),
+            end_side.clone(),
+        ));
+        func.add_block(raise_side);
+        super::render_return_allocated_error(func, error);
+        func.add_block(end_side);
     }
 
     fn get_size(&self) -> usize {
         GetSize::get_size(self)
     }
 
+    /// A missing key becomes a null pointer, which is how the loads of the value know
+    /// the key was not found. Found keys are left to the loads (see [`const_load_slot`]).
     fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        match self.const_lookup(graph, args)? {
+            Some(_) => None,
+            None => Some(Ref::Const(Type::Ptr { origin: usize::MAX }, 0)),
+        }
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
+}
+
+/// Mappings with at most this many values are looked up at compile time when the key is
+/// a constant.
+pub(crate) const MAX_CONST_LOOKUP_LEN: usize = 256;
+
+impl CallMapping {
+    /// Looks the key up in compile time, if all of it is constant and the mapping is small
+    /// (see [`MAX_CONST_LOOKUP_LEN`]) and static (see [`crate::mapping::Storage::n_entries`]).
+    /// Returns `Some(None)` if the key is missing from the mapping.
+    fn const_lookup<'a>(&self, graph: &'a Graph, args: &[Ref]) -> Option<Option<Cow<'a, [u8]>>> {
         let key = args
             .iter()
             .copied()
@@ -67,13 +128,67 @@ impl Op for CallMapping {
                 }
             })
             .collect::<Option<Vec<_>>>()?;
-        let key_ptr = if let Some(value) = graph.mappings[&self.name].get(key.as_byte_slice()) {
-            value.as_ptr() as usize as u64
-        } else {
-            0
-        };
+        let mapping = graph.mappings.get(&self.name)?;
+        if mapping.n_entries()? > MAX_CONST_LOOKUP_LEN {
+            return None;
+        }
+
+        mapping.get(key.as_byte_slice()).ok()
+    }
+}
+
+/// The value of a slot of a mapping lookup, if the lookup (the node in `call`) can be
+/// done in compile time and the key is found.
+fn const_load_slot(graph: &Graph, mapping: &str, slot: usize, call: Ref) -> Option<Ref> {
+    let Ref::Node(call_id) = call else {
+        return None;
+    };
+    let node = graph.nodes.get(call_id)?;
+    let value = node
+        .op
+        .downcast_ref::<CallMapping>()?
+        .const_lookup(graph, &node.args)??;
+    let mapping = graph.mappings.get(mapping)?;
+    let ty = *mapping.value_layout().slots().get(slot)?;
+
+    Some(Ref::Const(ty, mapping.read_slot(&value, slot)?))
+}
 
-        Some(Ref::Const(Type::Ptr { origin: usize::MAX }, key_ptr))
+/// Loads a slot of the value found by a mapping call, widening it back to `f64` if the
+/// storage packs scalars as `f32`.
+fn render_load_slot(
+    graph: &Graph,
+    mapping: &str,
+    slot: usize,
+    value: Ref,
+    output: qbe::Value,
+    addr: String,
+    func: &mut qbe::Function,
+) {
+    let mapping = &graph.mappings[mapping];
+    let ty = mapping.value_layout().slots()[slot];
+    let position = mapping.value_positions()[slot];
+
+    func.assign_instr(
+        qbe::Value::Temporary(addr.clone()),
+        qbe::Type::Long,
+        qbe::Instr::Add(value.render(), qbe::Value::Const(position.offset as u64)),
+    );
+
+    if position.is_f32 {
+        let single = qbe::Value::Temporary(format!("{addr}.single"));
+        func.assign_instr(
+            single.clone(),
+            qbe::Type::Single,
+            qbe::Instr::Load(qbe::Type::Single, qbe::Value::Temporary(addr)),
+        );
+        func.assign_instr(output, ty.render(), qbe::Instr::Exts(single));
+    } else {
+        func.assign_instr(
+            output,
+            ty.render(),
+            qbe::Instr::Load(ty.render(), qbe::Value::Temporary(addr)),
+        );
     }
 }
 
@@ -119,7 +234,6 @@ impl Op for LoadMappingValue {
         func: &mut qbe::Function,
         namespace: &str,
     ) {
-        let ty = graph.mappings[&self.mapping].value_layout().slots()[self.slot];
         let addr = unique_for(output.clone(), "loadmapping.addr");
 
         let false_side = unique_for(output.clone(), "loadmapping.found.false");
@@ -134,30 +248,34 @@ impl Op for LoadMappingValue {
         super::render_return_error(
             func,
             qbe::Value::Global(format!("{namespace}.error.{}", self.error_code)),
+            Some(self.error_code as usize),
         );
         func.add_block(true_side);
 
-        func.assign_instr(
-            qbe::Value::Temporary(addr.clone()),
-            qbe::Type::Long,
-            qbe::Instr::Add(args[0].render(), qbe::Value::Const((self.slot * 8) as u64)),
-        );
-        func.assign_instr(
-            output,
-            ty.render(),
-            qbe::Instr::Load(ty.render(), qbe::Value::Temporary(addr)),
-        );
+        render_load_slot(graph, &self.mapping, self.slot, args[0], output, addr, func);
     }
 
     fn get_size(&self) -> usize {
         GetSize::get_size(self)
     }
 
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        const_load_slot(graph, &self.mapping, self.slot, args[0])
+    }
+
     fn is_illegal(&self, graph: &Graph, args: &[Ref]) -> bool {
         // If const is zero = value not found.
         // If const not zero = hardcoding pointers?! sus...
         matches!(args[0], Ref::Const(_, _))
     }
+
+    fn remap_tables(&mut self, error_map: &[usize], subgraph_map: &[usize]) {
+        self.error_code = error_map[self.error_code as usize] as u64;
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
 }
 
 /// Loads the value of a mapping call for a given slot or yields an error if none was
@@ -228,16 +346,7 @@ impl Op for LoadOrDefaultMappingValue {
         func.add_instr(qbe::Instr::Jmp(end_if.clone()));
 
         func.add_block(true_side);
-        func.assign_instr(
-            qbe::Value::Temporary(addr.clone()),
-            qbe::Type::Long,
-            qbe::Instr::Add(args[0].render(), qbe::Value::Const((self.slot * 8) as u64)),
-        );
-        func.assign_instr(
-            output,
-            ty.render(),
-            qbe::Instr::Load(ty.render(), qbe::Value::Temporary(addr)),
-        );
+        render_load_slot(graph, &self.mapping, self.slot, args[0], output, addr, func);
 
         func.add_block(end_if);
     }
@@ -250,7 +359,7 @@ impl Op for LoadOrDefaultMappingValue {
         if matches!(args[0], Ref::Const(_, 0)) {
             Some(args[1])
         } else {
-            None
+            const_load_slot(graph, &self.mapping, self.slot, args[0])
         }
     }
 
@@ -264,4 +373,12 @@ impl Op for LoadOrDefaultMappingValue {
 
         false
     }
+
+    fn remap_tables(&mut self, error_map: &[usize], subgraph_map: &[usize]) {
+        self.error_code = error_map[self.error_code as usize] as u64;
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
 }
@@ -3,22 +3,33 @@
 
 #![allow(unused_variables)]
 
+mod activation;
 mod arithmetic;
 mod call;
 mod compare;
 mod convert;
+mod hash;
 mod list;
 mod logic;
+mod map;
 mod mapping;
+mod reduce;
 mod resource;
+mod round;
 
+pub use activation::*;
 pub use arithmetic::*;
 pub use call::*;
 pub use compare::*;
 pub use convert::*;
+pub use hash::*;
+pub use list::IndexMode;
 pub use logic::*;
+pub use reduce::*;
+pub use round::*;
 
 pub(crate) use list::*;
+pub(crate) use map::*;
 pub(crate) use mapping::*;
 pub(crate) use resource::*;
 
@@ -29,7 +40,7 @@ use get_size::GetSize;
 use std::fmt::Debug;
 use std::panic::RefUnwindSafe;
 
-use super::{FnError, Graph, Ref, Type};
+use super::{graph::SLOT_SIZE, FnError, Graph, Ref, Type};
 
 /// The fundamental trait defining an operation in a computational graph.
 #[typetag::serde(tag = "type")]
@@ -38,6 +49,13 @@ pub trait Op: 'static + DynClone + Debug + Send + Sync + RefUnwindSafe + Downcas
     /// from the implementor that this function be idempotent.
     fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type>;
 
+    /// Explains why this operation cannot be applied on arguments of the given types,
+    /// when [`Op::annotate`] returns `None`. This is attached to the resulting type error.
+    /// The default implementation returns `None`, which results in a generic type error.
+    fn explain_type_error(&self, graph: &Graph, args: &[Type]) -> Option<String> {
+        None
+    }
+
     /// Renders the QBE code for this operation into a given function builder.
     fn render_into(
         &self,
@@ -74,6 +92,31 @@ pub trait Op: 'static + DynClone + Debug + Send + Sync + RefUnwindSafe + Downcas
     fn is_illegal(&self, graph: &Graph, args: &[Ref]) -> bool {
         false
     }
+
+    /// Whether the code rendered for this operation calls into the jyafn runtime (e.g., to
+    /// raise an error or to call a mapping, a resource or a pure function). Such code
+    /// hardcodes memory addresses of the current process, so it cannot be run in any
+    /// other process. Operations that render such calls must implement this. The default
+    /// implementation returns `false`.
+    fn calls_runtime(&self) -> bool {
+        false
+    }
+
+    /// The functions called by the code rendered for this operation whose results may
+    /// differ between machines, such as transcendental functions from `libm`. These are
+    /// reported when compiling with strict floating point semantics (see
+    /// [`crate::CompileOptions::strict_fp`]). The default implementation returns none.
+    fn inexact_calls(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Rewrites the ids of the errors and subgraphs of the graph referenced by this
+    /// operation, when it is copied from a subgraph into the graph it is inlined into. The
+    /// error (or subgraph) of id `i` in the subgraph has the id `error_map[i]` (or
+    /// `subgraph_map[i]`) in the new graph. Operations that refer to errors or subgraphs
+    /// must implement this. The default implementation does nothing.
+    #[allow(unused_variables)]
+    fn remap_tables(&mut self, error_map: &[usize], subgraph_map: &[usize]) {}
 }
 
 dyn_clone::clone_trait_object!(Op);
@@ -138,17 +181,75 @@ fn unique_for(v: qbe::Value, prefix: &str) -> String {
     format!("{prefix}_{name}")
 }
 
-/// Renders the call to create an [`FnError`] out of a static C-Style string in jyafn code.
-pub(crate) fn render_return_error(func: &mut qbe::Function, error: qbe::Value) {
-    let error_ptr = qbe::Value::Temporary("__error_ptr".to_string());
+/// The node of op `O` that `ptr` points to, together with its arguments. This is how ops
+/// loading the outputs of list-producing ops find their origin.
+pub(crate) fn origin_of<O: Op>(graph: &Graph, ptr: Ref) -> Option<(&O, &[Ref])> {
+    let Ref::Node(origin) = ptr else {
+        return None;
+    };
+    let node = graph.nodes.get(origin)?;
+    let op = node.op.downcast_ref::<O>()?;
+
+    Some((op, &node.args))
+}
+
+/// Renders the computation of the address of the `slot`-th slot of the list pointed to by
+/// `list` into `addr`.
+pub(crate) fn render_slot_addr(
+    addr: qbe::Value,
+    list: qbe::Value,
+    slot: usize,
+    func: &mut qbe::Function,
+) {
     func.assign_instr(
-        error_ptr.clone(),
+        addr,
         qbe::Type::Long,
-        qbe::Instr::Call(
-            qbe::Value::Const(FnError::make_static as usize as u64),
-            vec![(qbe::Type::Long, error)],
+        qbe::Instr::Add(
+            list,
+            qbe::Value::Const((slot * SLOT_SIZE.in_bytes()) as u64),
         ),
     );
+}
+
+/// Renders the load of the `slot`-th slot of the list pointed to by `ptr` into `output`.
+pub(crate) fn render_load_slot(
+    output: qbe::Value,
+    ty: qbe::Type<'static>,
+    ptr: Ref,
+    slot: usize,
+    func: &mut qbe::Function,
+    prefix: &str,
+) {
+    let addr = qbe::Value::Temporary(unique_for(output.clone(), &format!("{prefix}.addr")));
+
+    render_slot_addr(addr.clone(), ptr.render(), slot, func);
+    func.assign_instr(output, ty.clone(), qbe::Instr::Load(ty, addr));
+}
+
+/// Renders the call to create an [`FnError`] out of a static C-Style string in jyafn code.
+/// If the string is one of the errors of the graph, `error_id` is its index in
+/// [`Graph::errors`], which is reported with the error.
+pub(crate) fn render_return_error(
+    func: &mut qbe::Function,
+    error: qbe::Value,
+    error_id: Option<usize>,
+) {
+    let error_ptr = qbe::Value::Temporary("__error_ptr".to_string());
+    let call = if let Some(error_id) = error_id {
+        qbe::Instr::Call(
+            qbe::Value::Const(FnError::make_static_with_id as *const () as usize as u64),
+            vec![
+                (qbe::Type::Long, error),
+                (qbe::Type::Long, qbe::Value::Const(error_id as u64)),
+            ],
+        )
+    } else {
+        qbe::Instr::Call(
+            qbe::Value::Const(FnError::make_static as *const () as usize as u64),
+            vec![(qbe::Type::Long, error)],
+        )
+    };
+    func.assign_instr(error_ptr.clone(), qbe::Type::Long, call);
     func.add_instr(qbe::Instr::Ret(Some(error_ptr)));
 }
 
@@ -160,7 +261,7 @@ pub(crate) fn render_return_allocated_error(func: &mut qbe::Function, error: qbe
         error_ptr.clone(),
         qbe::Type::Long,
         qbe::Instr::Call(
-            qbe::Value::Const(FnError::make_allocated as usize as u64),
+            qbe::Value::Const(FnError::make_allocated as *const () as usize as u64),
             vec![(qbe::Type::Long, error)],
         ),
     );
@@ -0,0 +1,255 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{impl_op, Graph, Ref, Type};
+
+use super::arithmetic::render_select;
+use super::{unique_for, Op};
+
+/// How the values of a [`Reduce`] are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReduceKind {
+    /// The sum of the values. The sum of no values is `0`.
+    Sum,
+    /// The product of the values. The product of no values is `1`.
+    Product,
+    /// The maximum of the values, ignoring NaNs, like [`f64::max`].
+    Max,
+    /// The minimum of the values, ignoring NaNs, like [`f64::min`].
+    Min,
+}
+
+impl ReduceKind {
+    fn name(self) -> &'static str {
+        match self {
+            ReduceKind::Sum => "sum",
+            ReduceKind::Product => "product",
+            ReduceKind::Max => "max",
+            ReduceKind::Min => "min",
+        }
+    }
+
+    /// The result of reducing no values, if there is one.
+    fn identity(self) -> Option<f64> {
+        match self {
+            ReduceKind::Sum => Some(0.0),
+            ReduceKind::Product => Some(1.0),
+            ReduceKind::Max | ReduceKind::Min => None,
+        }
+    }
+
+    pub(super) fn combine(self, x: f64, y: f64) -> f64 {
+        match self {
+            ReduceKind::Sum => x + y,
+            ReduceKind::Product => x * y,
+            ReduceKind::Max => x.max(y),
+            ReduceKind::Min => x.min(y),
+        }
+    }
+
+    fn render_combine(
+        self,
+        output: qbe::Value,
+        x: qbe::Value,
+        y: qbe::Value,
+        func: &mut qbe::Function,
+    ) {
+        match self {
+            ReduceKind::Sum => {
+                func.assign_instr(output, Type::Float.render(), qbe::Instr::Add(x, y))
+            }
+            ReduceKind::Product => {
+                func.assign_instr(output, Type::Float.render(), qbe::Instr::Mul(x, y))
+            }
+            ReduceKind::Max => render_select(output, [x, y], func, qbe::Cmp::Gt, "max"),
+            ReduceKind::Min => render_select(output, [x, y], func, qbe::Cmp::Lt, "min"),
+        }
+    }
+}
+
+/// Combines `items` pairwise, as a balanced tree, in `log2(n)` levels. Each combination
+/// receives a unique id. Returns `None` if there are no items.
+pub(super) fn tree_reduce<T>(
+    mut items: Vec<T>,
+    mut combine: impl FnMut(usize, T, T) -> T,
+) -> Option<T> {
+    let mut step = 0;
+
+    while items.len() > 1 {
+        let mut level = Vec::with_capacity(items.len().div_ceil(2));
+        let mut it = items.into_iter();
+
+        while let Some(x) = it.next() {
+            if let Some(y) = it.next() {
+                level.push(combine(step, x, y));
+                step += 1;
+            } else {
+                level.push(x);
+            }
+        }
+
+        items = level;
+    }
+
+    items.pop()
+}
+
+/// Renders the reduction of `values` into `output`, as a balanced tree.
+pub(super) fn render_tree(
+    kind: ReduceKind,
+    output: qbe::Value,
+    values: Vec<qbe::Value>,
+    func: &mut qbe::Function,
+) {
+    let step_output = |step| {
+        qbe::Value::Temporary(unique_for(
+            output.clone(),
+            &format!("{}.{step}", kind.name()),
+        ))
+    };
+    let result = tree_reduce(values, |step, x, y| {
+        let combined = step_output(step);
+        kind.render_combine(combined.clone(), x, y, func);
+        combined
+    });
+    let result = result
+        .or_else(|| kind.identity().map(|identity| Ref::from(identity).render()))
+        .expect("empty reduction has no result");
+
+    func.assign_instr(output, Type::Float.render(), qbe::Instr::Copy(result));
+}
+
+/// Reduces `n` floats into one, combining them as a balanced tree. This keeps the
+/// dependency chain of the result short and, for sums, the rounding errors small.
+/// Therefore, results may differ in the last bits from combining the values one after
+/// the other. Minimum and maximum of no values are not defined.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reduce {
+    pub kind: ReduceKind,
+    pub n: usize,
+}
+
+#[typetag::serde]
+impl Op for Reduce {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        if args.len() != self.n || (self.n == 0 && self.kind.identity().is_none()) {
+            return None;
+        }
+
+        if args.iter().all(|&arg| arg == Type::Float) {
+            Some(Type::Float)
+        } else {
+            None
+        }
+    }
+
+    fn explain_type_error(&self, graph: &Graph, args: &[Type]) -> Option<String> {
+        if self.n == 0 && self.kind.identity().is_none() {
+            Some(format!("cannot take the {} of no values", self.kind.name()))
+        } else {
+            Some(format!(
+                "{} of {} values takes only floats",
+                self.kind.name(),
+                self.n
+            ))
+        }
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        render_tree(
+            self.kind,
+            output,
+            args.iter().copied().map(Ref::render).collect(),
+            func,
+        )
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        let values = args
+            .iter()
+            .copied()
+            .map(Ref::as_f64)
+            .collect::<Option<Vec<_>>>()?;
+        let result = tree_reduce(values, |_, x, y| self.kind.combine(x, y))
+            .or_else(|| self.kind.identity())?;
+
+        Some(result.into())
+    }
+}
+
+/// The dot product of two lists of `n` floats. The arguments are the `n` elements of the
+/// first list followed by the `n` elements of the second. The products are summed like
+/// in a [`Reduce`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dot {
+    pub n: usize,
+}
+
+#[typetag::serde]
+impl Op for Dot {
+    impl_op! {}
+
+    fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+        if args.len() == 2 * self.n && args.iter().all(|&arg| arg == Type::Float) {
+            Some(Type::Float)
+        } else {
+            None
+        }
+    }
+
+    fn explain_type_error(&self, graph: &Graph, args: &[Type]) -> Option<String> {
+        Some(format!(
+            "dot product of lists of {} values takes only floats",
+            self.n
+        ))
+    }
+
+    fn render_into(
+        &self,
+        graph: &Graph,
+        output: qbe::Value,
+        args: &[Ref],
+        func: &mut qbe::Function,
+        namespace: &str,
+    ) {
+        let (a, b) = args.split_at(self.n);
+        let products = a
+            .iter()
+            .zip(b)
+            .enumerate()
+            .map(|(i, (x, y))| {
+                let product =
+                    qbe::Value::Temporary(unique_for(output.clone(), &format!("dot.mul.{i}")));
+                func.assign_instr(
+                    product.clone(),
+                    Type::Float.render(),
+                    qbe::Instr::Mul(x.render(), y.render()),
+                );
+                product
+            })
+            .collect();
+
+        render_tree(ReduceKind::Sum, output, products, func)
+    }
+
+    fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+        let values = args
+            .iter()
+            .copied()
+            .map(Ref::as_f64)
+            .collect::<Option<Vec<_>>>()?;
+        let (a, b) = values.split_at(self.n);
+        let products = a.iter().zip(b).map(|(x, y)| x * y).collect();
+        let result = tree_reduce(products, |_, x, y| x + y).unwrap_or(0.0);
+
+        Some(result.into())
+    }
+}
@@ -1,6 +1,7 @@
 use get_size::GetSize;
 use serde_derive::{Deserialize, Serialize};
 
+use crate::layout::Struct;
 use crate::{graph::SLOT_SIZE, impl_is_eq, impl_op, resource::ResourceMethod, Graph, Ref, Type};
 
 use super::{unique_for, Op};
@@ -9,6 +10,15 @@ use super::{unique_for, Op};
 pub(crate) struct CallResource {
     pub name: String,
     pub method: String,
+    /// The input layout of the call, if the method has inputs of dynamic length.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_layout: Option<Struct>,
+    /// The error raised if the method has a dynamic output and writes a length greater
+    /// than the maximum length of its output.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length_error: Option<usize>,
     #[serde(default)]
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
@@ -22,7 +32,8 @@ impl Op for CallResource {
     fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
         let method = graph.resources.get(&self.name)?.get_method(&self.method)?;
 
-        if method.input_layout.slots() == args {
+        let input_layout = self.input_layout.as_ref().unwrap_or(&method.input_layout);
+        if input_layout.slots() == args {
             self.resolved = Some(method);
             Some(Type::Ptr { origin: self_id })
         } else {
@@ -50,7 +61,7 @@ impl Op for CallResource {
         let raise_side = unique_for(output.clone(), "callresource.raise");
         let end_side = unique_for(output.clone(), "callresource.end");
 
-        let input_size = method.input_layout.slots().len() as u64;
+        let input_size = args.len() as u64;
         let output_size = method.output_layout.slots().len() as u64;
 
         func.assign_instr(
@@ -112,12 +123,62 @@ impl Op for CallResource {
         func.add_block(raise_side);
         super::render_return_allocated_error(func, status);
         func.add_block(end_side);
+
+        if let Some(error) = self.length_error {
+            let max_len = method
+                .max_output_len()
+                .expect("dynamic output was checked when the call was inserted");
+            let len = qbe::Value::Temporary(unique_for(output.clone(), "callresource.len"));
+            let too_long =
+                qbe::Value::Temporary(unique_for(output.clone(), "callresource.toolong"));
+            let too_long_side = unique_for(output.clone(), "callresource.toolong.raise");
+            let len_ok_side = unique_for(output.clone(), "callresource.lenok");
+
+            func.assign_instr(
+                len.clone(),
+                qbe::Type::Long,
+                qbe::Instr::Load(qbe::Type::Long, output_ptr.clone()),
+            );
+            func.assign_instr(
+                too_long.clone(),
+                qbe::Type::Long,
+                qbe::Instr::Cmp(
+                    qbe::Type::Long,
+                    qbe::Cmp::Ugt,
+                    len,
+                    qbe::Value::Const(max_len as u64),
+                ),
+            );
+            func.add_instr(qbe::Instr::Jnz(
+                too_long,
+                too_long_side.clone(),
+                len_ok_side.clone(),
+            ));
+            func.add_block(too_long_side);
+            super::render_return_error(
+                func,
+                qbe::Value::Global(format!("{namespace}.error.{error}")),
+                Some(error),
+            );
+            func.add_block(len_ok_side);
+        }
+
         func.assign_instr(output, qbe::Type::Long, qbe::Instr::Copy(output_ptr));
     }
 
     fn get_size(&self) -> usize {
         GetSize::get_size(self)
     }
+
+    fn remap_tables(&mut self, error_map: &[usize], subgraph_map: &[usize]) {
+        if let Some(length_error) = &mut self.length_error {
+            *length_error = error_map[*length_error];
+        }
+    }
+
+    fn calls_runtime(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
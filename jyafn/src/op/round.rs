@@ -0,0 +1,285 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{impl_op, Graph, Ref, Type};
+
+use super::{unique_for, Op};
+
+/// The smallest magnitude from which all floats are integers.
+const ALL_INTEGERS: f64 = 4503599627370496.0; // 2^52
+
+const SIGN_MASK: u64 = 1 << 63;
+
+/// How the integer part of the magnitude of a float is adjusted to round it.
+#[derive(Debug, Clone, Copy)]
+enum Rounding {
+    Floor,
+    Ceil,
+    Round,
+    Trunc,
+}
+
+impl Rounding {
+    fn name(self) -> &'static str {
+        match self {
+            Rounding::Floor => "floor",
+            Rounding::Ceil => "ceil",
+            Rounding::Round => "round",
+            Rounding::Trunc => "trunc",
+        }
+    }
+
+    fn eval(self, x: f64) -> f64 {
+        match self {
+            Rounding::Floor => x.floor(),
+            Rounding::Ceil => x.ceil(),
+            Rounding::Round => x.round_ties_even(),
+            Rounding::Trunc => x.trunc(),
+        }
+    }
+
+    /// Renders the rounding of `arg` into `output`. QBE has no rounding instructions, so
+    /// the magnitude of the value is truncated by a round trip through an unsigned
+    /// integer, adjusted by one depending on the fractional part and then gets the sign
+    /// of the value back, so that the sign of zeros is kept. Magnitudes of `2^52` and
+    /// above, infinities and NaNs are left as they are.
+    fn render(self, output: qbe::Value, arg: qbe::Value, func: &mut qbe::Function) {
+        let name = self.name();
+        let temp = |suffix: &str| {
+            qbe::Value::Temporary(unique_for(output.clone(), &format!("{name}.{suffix}")))
+        };
+        let bits = temp("bits");
+        let sign = temp("sign");
+        let magnitude_bits = temp("magnitude_bits");
+        let magnitude = temp("magnitude");
+        let is_small = temp("is_small");
+        let truncated_int = temp("truncated_int");
+        let truncated = temp("truncated");
+        let fraction = temp("fraction");
+        let bump_int = temp("bump_int");
+        let bump = temp("bump");
+        let rounded = temp("rounded");
+        let rounded_bits = temp("rounded_bits");
+        let signed_bits = temp("signed_bits");
+        let small_side = unique_for(output.clone(), &format!("{name}.small"));
+        let end_side = unique_for(output.clone(), &format!("{name}.end"));
+
+        func.assign_instr(
+            output.clone(),
+            Type::Float.render(),
+            qbe::Instr::Copy(arg.clone()),
+        );
+        func.assign_instr(bits.clone(), qbe::Type::Long, qbe::Instr::Cast(arg.clone()));
+        func.assign_instr(
+            sign.clone(),
+            qbe::Type::Long,
+            qbe::Instr::And(bits.clone(), qbe::Value::Const(SIGN_MASK)),
+        );
+        func.assign_instr(
+            magnitude_bits.clone(),
+            qbe::Type::Long,
+            qbe::Instr::And(bits, qbe::Value::Const(!SIGN_MASK)),
+        );
+        func.assign_instr(
+            magnitude.clone(),
+            Type::Float.render(),
+            qbe::Instr::Cast(magnitude_bits),
+        );
+        func.assign_instr(
+            is_small.clone(),
+            Type::Bool.render(),
+            qbe::Instr::Cmp(
+                Type::Float.render(),
+                qbe::Cmp::Lt,
+                magnitude.clone(),
+                Ref::from(ALL_INTEGERS).render(),
+            ),
+        );
+        func.add_instr(qbe::Instr::Jnz(
+            is_small,
+            small_side.clone(),
+            end_side.clone(),
+        ));
+
+        func.add_block(small_side);
+        func.assign_instr(
+            truncated_int.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Dtoui(magnitude.clone()),
+        );
+        func.assign_instr(
+            truncated.clone(),
+            Type::Float.render(),
+            qbe::Instr::Ultof(truncated_int.clone()),
+        );
+        // Exact, since both are close enough:
+        func.assign_instr(
+            fraction.clone(),
+            Type::Float.render(),
+            qbe::Instr::Sub(magnitude, truncated.clone()),
+        );
+
+        // The magnitude grows by one when rounding away from zero:
+        match self {
+            Rounding::Trunc => func.assign_instr(
+                bump_int.clone(),
+                qbe::Type::Long,
+                qbe::Instr::Copy(qbe::Value::Const(0)),
+            ),
+            Rounding::Round => {
+                let is_above_half = temp("is_above_half");
+                let is_half = temp("is_half");
+                let is_odd = temp("is_odd");
+                func.assign_instr(
+                    is_above_half.clone(),
+                    Type::Bool.render(),
+                    qbe::Instr::Cmp(
+                        Type::Float.render(),
+                        qbe::Cmp::Gt,
+                        fraction.clone(),
+                        Ref::from(0.5).render(),
+                    ),
+                );
+                func.assign_instr(
+                    is_half.clone(),
+                    Type::Bool.render(),
+                    qbe::Instr::Cmp(
+                        Type::Float.render(),
+                        qbe::Cmp::Eq,
+                        fraction,
+                        Ref::from(0.5).render(),
+                    ),
+                );
+                // Ties go to the even neighbor:
+                func.assign_instr(
+                    is_odd.clone(),
+                    qbe::Type::Long,
+                    qbe::Instr::And(truncated_int.clone(), qbe::Value::Const(1)),
+                );
+                func.assign_instr(
+                    is_half.clone(),
+                    Type::Bool.render(),
+                    qbe::Instr::And(is_half.clone(), is_odd),
+                );
+                func.assign_instr(
+                    bump_int.clone(),
+                    Type::Bool.render(),
+                    qbe::Instr::Or(is_above_half, is_half),
+                );
+            }
+            Rounding::Floor | Rounding::Ceil => {
+                let has_fraction = temp("has_fraction");
+                let is_away = temp("is_away");
+                func.assign_instr(
+                    has_fraction.clone(),
+                    Type::Bool.render(),
+                    qbe::Instr::Cmp(
+                        Type::Float.render(),
+                        qbe::Cmp::Gt,
+                        fraction,
+                        Ref::from(0.0).render(),
+                    ),
+                );
+                // Floors of negative values and ceilings of positive values:
+                func.assign_instr(
+                    is_away.clone(),
+                    Type::Bool.render(),
+                    qbe::Instr::Cmp(
+                        qbe::Type::Long,
+                        if let Rounding::Floor = self {
+                            qbe::Cmp::Ne
+                        } else {
+                            qbe::Cmp::Eq
+                        },
+                        sign.clone(),
+                        qbe::Value::Const(0),
+                    ),
+                );
+                func.assign_instr(
+                    bump_int.clone(),
+                    Type::Bool.render(),
+                    qbe::Instr::And(has_fraction, is_away),
+                );
+            }
+        }
+        func.assign_instr(
+            bump.clone(),
+            Type::Float.render(),
+            qbe::Instr::Ultof(bump_int),
+        );
+        func.assign_instr(
+            rounded.clone(),
+            Type::Float.render(),
+            qbe::Instr::Add(truncated, bump),
+        );
+
+        func.assign_instr(
+            rounded_bits.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Cast(rounded),
+        );
+        func.assign_instr(
+            signed_bits.clone(),
+            qbe::Type::Long,
+            qbe::Instr::Or(rounded_bits, sign),
+        );
+        func.assign_instr(output, Type::Float.render(), qbe::Instr::Cast(signed_bits));
+
+        func.add_block(end_side);
+    }
+}
+
+macro_rules! rounding_op {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        pub struct $name;
+
+        #[typetag::serde]
+        impl Op for $name {
+            impl_op! {}
+
+            fn annotate(&mut self, self_id: usize, graph: &Graph, args: &[Type]) -> Option<Type> {
+                Some(match args {
+                    [Type::Float] => Type::Float,
+                    _ => return None,
+                })
+            }
+
+            fn render_into(
+                &self,
+                graph: &Graph,
+                output: qbe::Value,
+                args: &[Ref],
+                func: &mut qbe::Function,
+                namespace: &str,
+            ) {
+                Rounding::$name.render(output, args[0].render(), func)
+            }
+
+            fn const_eval(&self, graph: &Graph, args: &[Ref]) -> Option<Ref> {
+                args[0].as_f64().map(|x| Rounding::$name.eval(x).into())
+            }
+        }
+    };
+}
+
+rounding_op! {
+    /// Implements `floor(a)`, the greatest integer less than or equal to `a`.
+    Floor
+}
+
+rounding_op! {
+    /// Implements `ceil(a)`, the least integer greater than or equal to `a`.
+    Ceil
+}
+
+rounding_op! {
+    /// Implements `round(a)`, the nearest integer to `a`. Half-way cases are rounded to
+    /// the nearest even integer, like Python's `round`.
+    Round
+}
+
+rounding_op! {
+    /// Implements `trunc(a)`, the integer part of `a`.
+    Trunc
+}
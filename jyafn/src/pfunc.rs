@@ -2,8 +2,10 @@
 
 use chrono::prelude::*;
 use special_fun::FloatSpecial;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ops::Rem;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::RwLock;
 
 use super::{utils, Error, Type};
@@ -74,8 +76,13 @@ pub struct PFunc {
     fn_ptr: ThreadsafePointer,
     /// The input types of the function.
     signature: &'static [Type],
-    /// The return type of the function.
+    /// The return type of the function. For multi-output functions, this is the type of
+    /// the first output.
     returns: Type,
+    /// The types of the outputs of the function, if it has more than one. In this case,
+    /// the function takes, as an extra last argument, a pointer to a buffer with one slot
+    /// per output and writes the outputs into it, in order, returning nothing.
+    outputs: &'static [Type],
     /// Provides compile-time evaluation behavior.
     pub(crate) const_eval: ConstEval,
 }
@@ -91,6 +98,18 @@ impl PFunc {
         self.returns
     }
 
+    /// The types of the outputs of the function, if it is a multi-output function.
+    /// Otherwise, this is empty and the output type is given by [`PFunc::returns`].
+    pub fn outputs(self) -> &'static [Type] {
+        self.outputs
+    }
+
+    /// Whether this function writes more than one output to a buffer (see
+    /// [`PFunc::outputs`]) instead of returning a single value.
+    pub fn is_multi_output(self) -> bool {
+        !self.outputs.is_empty()
+    }
+
     /// The memory address of the function. This is what will be harcoded in the jyafn
     /// code.
     pub fn location(self) -> usize {
@@ -103,6 +122,7 @@ impl PFunc {
             fn_ptr: ThreadsafePointer(f as *const ()),
             signature: &[Type::Float],
             returns: Type::Float,
+            outputs: &[],
             const_eval: ConstEval::call1(f),
         }
     }
@@ -113,16 +133,30 @@ impl PFunc {
             fn_ptr: ThreadsafePointer(f as *const ()),
             signature: &[Type::Float, Type::Float],
             returns: Type::Float,
+            outputs: &[],
             const_eval: ConstEval::call2(f),
         }
     }
 
+    /// Creates a [`PFunc`] for a `fn(f64) -> (f64, f64)`, where the outputs are written
+    /// to a buffer.
+    fn call1_to2(f: extern "C" fn(f64, *mut [f64; 2])) -> PFunc {
+        PFunc {
+            fn_ptr: ThreadsafePointer(f as *const ()),
+            signature: &[Type::Float],
+            returns: Type::Float,
+            outputs: &[Type::Float, Type::Float],
+            const_eval: ConstEval::no_eval(),
+        }
+    }
+
     /// Creates a [`PFunc`] for a `fn(f64) -> bool`.
     fn call_bool_to_f64(f: fn(f64) -> bool) -> PFunc {
         PFunc {
             fn_ptr: ThreadsafePointer(f as *const ()),
             signature: &[Type::Float],
             returns: Type::Bool,
+            outputs: &[],
             const_eval: ConstEval::no_eval(),
         }
     }
@@ -133,6 +167,7 @@ impl PFunc {
             fn_ptr: ThreadsafePointer(f as *const ()),
             signature: &[Type::DateTime],
             returns: Type::Float,
+            outputs: &[],
             const_eval: ConstEval::no_eval(),
         }
     }
@@ -143,17 +178,100 @@ impl PFunc {
             fn_ptr: ThreadsafePointer(f as *const ()),
             signature: &[Type::Float],
             returns: Type::DateTime,
+            outputs: &[],
             const_eval: ConstEval::no_eval(),
         }
     }
 }
 
 lazy_static::lazy_static! {
-    /// All the known [`PFunc`]s.
-    static ref P_FUNCS: RwLock<HashMap<&'static str, PFunc>> = RwLock::new(init());
+    /// The standard [`PFunc`]s provided by jyafn. These cannot be shadowed.
+    static ref BUILTIN_P_FUNCS: HashMap<&'static str, PFunc> = init();
+    /// The [`PFunc`]s inscribed at runtime with [`inscribe`].
+    static ref INSCRIBED_P_FUNCS: RwLock<Inscribed> = RwLock::default();
+}
+
+/// The registry of inscribed [`PFunc`]s.
+#[derive(Default)]
+struct Inscribed {
+    /// The inscribed pure functions, by name.
+    pfuncs: HashMap<&'static str, PFunc>,
+    /// The names of the pure functions inscribed inside each open scope, by the id of
+    /// its [`Snapshot`]. Since pure functions cannot be overwritten, this is all that is
+    /// needed to undo the inscriptions of a scope.
+    scopes: HashMap<u64, Vec<&'static str>>,
+}
+
+/// The id of the next [`Snapshot`] to be taken.
+static NEXT_SNAPSHOT: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// The ids of the snapshots taken by the current thread that were not restored yet,
+    /// from the outermost to the innermost.
+    static OPEN_SNAPSHOTS: RefCell<Vec<u64>> = const { RefCell::new(vec![]) };
+}
+
+/// A point in the history of the pure functions inscribed by a thread, to which the
+/// registry can be brought back with [`restore`]. See [`snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    id: u64,
 }
 
-/// Inscribes a new pure function.
+/// Takes a snapshot of the pure functions inscribed by the current thread. This opens a
+/// scope that owns every pure function the current thread inscribes until the snapshot
+/// is restored.
+pub fn snapshot() -> Snapshot {
+    let id = NEXT_SNAPSHOT.fetch_add(1, Ordering::Relaxed);
+    INSCRIBED_P_FUNCS
+        .write()
+        .expect("poisoned")
+        .scopes
+        .insert(id, vec![]);
+    OPEN_SNAPSHOTS.with(|open| open.borrow_mut().push(id));
+
+    Snapshot { id }
+}
+
+/// Removes all pure functions inscribed by the current thread since the `snapshot` was
+/// taken, in time proportional to the number of such functions. Pure functions
+/// inscribed by other threads or outside of the scope of the snapshot are kept.
+/// Snapshots should be restored in the reverse order in which they were taken, like
+/// nested scopes; restoring a snapshot also restores the ones taken after it by the same
+/// thread. Restoring a snapshot that was already restored does nothing.
+///
+/// Functions already compiled keep working after the pure functions they call are
+/// removed, since the functions themselves live for the rest of the process. Only new
+/// calls to them cannot be created.
+pub fn restore(snapshot: Snapshot) {
+    let ids = OPEN_SNAPSHOTS.with(|open| {
+        let mut open = open.borrow_mut();
+        match open.iter().position(|&id| id == snapshot.id) {
+            Some(position) => open.drain(position..).collect(),
+            None => vec![snapshot.id],
+        }
+    });
+
+    let mut guard = INSCRIBED_P_FUNCS.write().expect("poisoned");
+    let Inscribed { pfuncs, scopes } = &mut *guard;
+    for id in ids {
+        for name in scopes.remove(&id).unwrap_or_default() {
+            pfuncs.remove(name);
+        }
+    }
+}
+
+/// Inscribes a new pure function, making it available to [`crate::op::Call`] under the
+/// given name. Arguments and the return value are passed as in the C ABI: scalars as
+/// `double` and all other types as `int64_t` (booleans are `0` or `1`). Pointers are not
+/// allowed in the signature.
+///
+/// This returns an error if the name is empty, if it is the name of a builtin pure
+/// function or of a pure function that was already inscribed, or if the signature
+/// contains pointers.
+///
+/// If the current thread has taken a [`snapshot`] that was not restored yet, the pure
+/// function is removed when the snapshot is restored.
 ///
 /// # Safety
 ///
@@ -161,33 +279,51 @@ lazy_static::lazy_static! {
 /// including stuff that are not a function. Its the caller responsibility to check that
 /// `fn_ptr` is in fact a function pointer and that the arguments match the signature
 /// given and that the function that is being supplied actually obeys all the expectations
-/// on a pure function (see [`PFunc`] for the requirements.)
-///
-/// # Panics
-///
-/// This function panics if a pfunc of the given name has already been inscribed.
+/// on a pure function (see [`PFunc`] for the requirements.) The function must also live
+/// for the rest of the lifetime of the process.
 pub unsafe fn inscribe(
     name: &str,
     fn_ptr: *const (),
     signature: &[Type],
     returns: Type,
 ) -> Result<(), Error> {
-    let mut guard = P_FUNCS.write().expect("poisoned");
-
-    if guard.contains_key(name) {
-        // This avoids poisoning the global lock.
-        drop(guard);
-        return Err("Function of name {name} already inscribed"
-            .to_string()
-            .into());
+    if name.is_empty() {
+        return Err("pure function name cannot be empty".to_string().into());
+    }
+    if fn_ptr.is_null() {
+        return Err(format!("function pointer for pure function {name:?} is null").into());
+    }
+    if let Some(ty) = signature
+        .iter()
+        .chain([&returns])
+        .find(|ty| matches!(ty, Type::Ptr { .. }))
+    {
+        return Err(format!("pure function {name:?} cannot take or return {ty}").into());
+    }
+    if BUILTIN_P_FUNCS.contains_key(name) {
+        return Err(format!("cannot shadow builtin pure function {name:?}").into());
+    }
+
+    let mut guard = INSCRIBED_P_FUNCS.write().expect("poisoned");
+
+    if guard.pfuncs.contains_key(name) {
+        return Err(format!("pure function {name:?} already inscribed").into());
     }
 
-    guard.insert(
-        Box::leak(name.to_string().into_boxed_str()),
+    let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+    if let Some(scope) = OPEN_SNAPSHOTS
+        .with(|open| open.borrow().last().copied())
+        .and_then(|id| guard.scopes.get_mut(&id))
+    {
+        scope.push(name);
+    }
+    guard.pfuncs.insert(
+        name,
         PFunc {
             fn_ptr: ThreadsafePointer(fn_ptr),
             signature: Box::leak(signature.to_vec().into_boxed_slice()),
             returns,
+            outputs: &[],
             const_eval: ConstEval::no_eval(),
         },
     );
@@ -197,11 +333,46 @@ pub unsafe fn inscribe(
 
 /// Gets a pure function by name, returning `None` if none is found.
 pub fn get(name: &str) -> Option<PFunc> {
-    let guard = P_FUNCS.read().expect("poisoned");
-    guard.get(name).copied()
+    if let Some(pfunc) = BUILTIN_P_FUNCS.get(name) {
+        return Some(*pfunc);
+    }
+
+    let guard = INSCRIBED_P_FUNCS.read().expect("poisoned");
+    guard.pfuncs.get(name).copied()
+}
+
+/// Gets a pure function by name, checking that it can be called on arguments of the
+/// given types. If it can't, the error describes the expected signature.
+pub fn get_checked(name: &str, args: &[Type]) -> Result<PFunc, Error> {
+    let pfunc = get(name).ok_or_else(|| format!("no pure function named {name:?}"))?;
+
+    if pfunc.signature() != args {
+        return Err(format!(
+            "{name} expects {}, got {}",
+            describe_args(pfunc.signature()),
+            describe_args(args)
+        )
+        .into());
+    }
+
+    Ok(pfunc)
 }
 
-/// Initalizes the [`P_FUNCS`] static with the standard pure function provided by jyafn.
+/// Describes a list of argument types, e.g. `2 args (scalar, scalar)`.
+fn describe_args(args: &[Type]) -> String {
+    format!(
+        "{} arg{} ({})",
+        args.len(),
+        if args.len() == 1 { "" } else { "s" },
+        args.iter()
+            .map(Type::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Initalizes the [`BUILTIN_P_FUNCS`] static with the standard pure function provided by
+/// jyafn.
 #[allow(unstable_name_collisions)]
 fn init() -> HashMap<&'static str, PFunc> {
     let mut map = HashMap::new();
@@ -218,10 +389,10 @@ fn init() -> HashMap<&'static str, PFunc> {
             sqrt, exp, ln, ln_1p, exp_m1,
             sin, cos, tan, asin, acos, atan, sinh, cosh, tanh, asinh, acosh, atanh,
             gamma, loggamma, factorial, rgamma, digamma,
-            erf, erfc, norm, norm_inv,
+            erf, erfc, norm_inv,
             riemann_zeta;
         call2:
-            powf, rem, atan2,
+            powf, rem, atan2, hypot, log,
             beta, logbeta, gammainc, gammac, gammac_inv,
             besselj, bessely, besseli;
         call_bool_to_f64:
@@ -234,6 +405,18 @@ fn init() -> HashMap<&'static str, PFunc> {
         )*)* }
     }
 
+    pfuncs! {
+        call1:
+            norm_cdf, norm_pdf;
+        call1_to2:
+            sincos, modf
+    }
+
+    // The original name of `norm_cdf`.
+    map.insert("norm", PFunc::call1(norm_cdf));
+
+    // Datetime functions return NaN for timestamps out of the representable range,
+    // since pure functions cannot fail.
     pfuncs! {
         call_f64_to_dt:
             fromtimestamp;
@@ -245,6 +428,31 @@ fn init() -> HashMap<&'static str, PFunc> {
     map
 }
 
+/// The cumulative distribution function of the standard normal distribution. This is
+/// computed from `erfc`, which stays accurate far into the lower tail, where the `ndtr`
+/// of cephes already underflows to zero.
+fn norm_cdf(x: f64) -> f64 {
+    0.5 * FloatSpecial::erfc(-x * std::f64::consts::FRAC_1_SQRT_2)
+}
+
+/// The probability density function of the standard normal distribution.
+fn norm_pdf(x: f64) -> f64 {
+    (-0.5 * x * x).exp() / (2.0 * std::f64::consts::PI).sqrt()
+}
+
+/// Computes the sine and the cosine of `x` in one go.
+extern "C" fn sincos(x: f64, out: *mut [f64; 2]) {
+    let (sin, cos) = x.sin_cos();
+    // Safety: jyafn code always passes a buffer with one slot per output.
+    unsafe { *out = [sin, cos] }
+}
+
+/// Splits `x` into its fractional and integral parts, both with the same sign as `x`.
+extern "C" fn modf(x: f64, out: *mut [f64; 2]) {
+    // Safety: jyafn code always passes a buffer with one slot per output.
+    unsafe { *out = [x.fract(), x.trunc()] }
+}
+
 fn fromtimestamp(x: f64) -> i64 {
     (x * 1e6) as i64
 }
@@ -254,41 +462,41 @@ fn timestamp(dt: i64) -> f64 {
 }
 
 fn year(dt: i64) -> f64 {
-    utils::int_to_datetime(dt).year() as f64
+    utils::int_to_datetime(dt).map_or(f64::NAN, |dt| dt.year() as f64)
 }
 
 fn month(dt: i64) -> f64 {
-    utils::int_to_datetime(dt).month() as f64
+    utils::int_to_datetime(dt).map_or(f64::NAN, |dt| dt.month() as f64)
 }
 
 fn day(dt: i64) -> f64 {
-    utils::int_to_datetime(dt).day() as f64
+    utils::int_to_datetime(dt).map_or(f64::NAN, |dt| dt.day() as f64)
 }
 
 fn hour(dt: i64) -> f64 {
-    utils::int_to_datetime(dt).hour() as f64
+    utils::int_to_datetime(dt).map_or(f64::NAN, |dt| dt.hour() as f64)
 }
 
 fn minute(dt: i64) -> f64 {
-    utils::int_to_datetime(dt).minute() as f64
+    utils::int_to_datetime(dt).map_or(f64::NAN, |dt| dt.minute() as f64)
 }
 
 fn second(dt: i64) -> f64 {
-    utils::int_to_datetime(dt).second() as f64
+    utils::int_to_datetime(dt).map_or(f64::NAN, |dt| dt.second() as f64)
 }
 
 fn microsecond(dt: i64) -> f64 {
-    utils::int_to_datetime(dt).timestamp_subsec_micros() as f64
+    utils::int_to_datetime(dt).map_or(f64::NAN, |dt| dt.timestamp_subsec_micros() as f64)
 }
 
 fn weekday(dt: i64) -> f64 {
-    utils::int_to_datetime(dt).weekday() as i64 as f64
+    utils::int_to_datetime(dt).map_or(f64::NAN, |dt| dt.weekday() as i64 as f64)
 }
 
 fn week(dt: i64) -> f64 {
-    utils::int_to_datetime(dt).iso_week().week() as i64 as f64
+    utils::int_to_datetime(dt).map_or(f64::NAN, |dt| dt.iso_week().week() as i64 as f64)
 }
 
 fn dayofyear(dt: i64) -> f64 {
-    utils::int_to_datetime(dt).ordinal() as f64
+    utils::int_to_datetime(dt).map_or(f64::NAN, |dt| dt.ordinal() as f64)
 }
@@ -7,7 +7,7 @@ use std::sync::Arc;
 use crate::layout::{Layout, Struct};
 use crate::Error;
 
-use super::{Input, OutputBuilder, Resource, ResourceMethod, ResourceType};
+use super::{dynamic_output_layout, Input, OutputBuilder, Resource, ResourceMethod, ResourceType};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Dummy;
@@ -47,16 +47,42 @@ impl Resource for DummyResource {
                 fn_ptr: crate::safe_method!(dummy_get),
                 input_layout: Struct(vec![("x".to_string(), Layout::Scalar)]),
                 output_layout: Layout::Scalar,
+                dynamic_inputs: vec![],
+                dynamic_output: false,
             }),
             "error" => Some(ResourceMethod {
                 fn_ptr: crate::safe_method!(dummy_error),
                 input_layout: Struct(vec![]),
                 output_layout: Layout::Scalar,
+                dynamic_inputs: vec![],
+                dynamic_output: false,
             }),
             "panic" => Some(ResourceMethod {
                 fn_ptr: crate::safe_method!(dummy_panic),
                 input_layout: Struct(vec![]),
                 output_layout: Layout::Scalar,
+                dynamic_inputs: vec![],
+                dynamic_output: false,
+            }),
+            "sum" => Some(ResourceMethod {
+                fn_ptr: crate::safe_method!(dummy_sum),
+                input_layout: Struct(vec![(
+                    "x".to_string(),
+                    Layout::List(Box::new(Layout::Scalar), 0),
+                )]),
+                output_layout: Layout::Scalar,
+                dynamic_inputs: vec!["x".to_string()],
+                dynamic_output: false,
+            }),
+            "top_k" => Some(ResourceMethod {
+                fn_ptr: crate::safe_method!(dynamic dummy_top_k),
+                input_layout: Struct(vec![
+                    ("x".to_string(), Layout::List(Box::new(Layout::Scalar), 4)),
+                    ("k".to_string(), Layout::Scalar),
+                ]),
+                output_layout: dynamic_output_layout(Layout::Scalar, 4),
+                dynamic_inputs: vec![],
+                dynamic_output: true,
             }),
             _ => None,
         }
@@ -76,6 +102,34 @@ fn dummy_get(
     Ok(())
 }
 
+fn dummy_sum(
+    resource: &DummyResource,
+    input: Input,
+    mut output_builder: OutputBuilder,
+) -> Result<(), String> {
+    let sum = input.as_f64_slice().iter().sum::<f64>();
+    output_builder.push_f64(sum / resource.number_to_divide);
+    Ok(())
+}
+
+/// Writes the `k` greatest elements of `x`, in decreasing order. The length is always
+/// reported as `k`, even if `x` has less than `k` elements, so that the check of the
+/// maximum length can be exercised.
+fn dummy_top_k(
+    _resource: &DummyResource,
+    input: Input,
+    mut output_builder: OutputBuilder,
+) -> Result<(), String> {
+    let mut x = input.as_f64_slice()[..4].to_vec();
+    let k = input.get_f64(4) as usize;
+    x.sort_by(|a, b| b.total_cmp(a));
+    for &value in x.iter().take(k) {
+        output_builder.push_f64(value);
+    }
+    output_builder.finish_with_len(k);
+    Ok(())
+}
+
 fn dummy_error(
     _resource: &DummyResource,
     _input: Input,
@@ -91,3 +145,224 @@ fn dummy_panic(
 ) -> Result<(), String> {
     panic!("panic!")
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::layout::RefValue;
+    use crate::Graph;
+
+    fn create_dummy_graph() -> Graph {
+        let mut graph = Graph::new();
+        graph.insert_resource(
+            "dummy".to_string(),
+            DummyResource {
+                number_to_divide: 2.0,
+            },
+        );
+        let x = graph.input("x".to_string(), Layout::Scalar);
+        let output = graph
+            .call_resource(
+                "dummy",
+                "get",
+                RefValue::Struct(HashMap::from([("x".to_string(), x)])),
+            )
+            .unwrap();
+        graph.output(output, Layout::Scalar).unwrap();
+
+        graph
+    }
+
+    #[test]
+    fn test_function_outlives_graph() {
+        // Compile a clone and drop everything else:
+        let graph = create_dummy_graph();
+        let clone = graph.clone();
+        drop(graph);
+        let func = clone.compile().unwrap();
+        drop(clone);
+
+        assert_eq!(Arc::strong_count(&func.graph().resources["dummy"]), 1);
+        let out: f64 = func.eval(&serde_json::json!({ "x": 5.0 })).unwrap();
+        assert_eq!(out, 2.5);
+
+        // Compile the original and drop it:
+        let graph = create_dummy_graph();
+        let func = graph.compile().unwrap();
+        drop(graph);
+
+        let out: f64 = func.eval(&serde_json::json!({ "x": 3.0 })).unwrap();
+        assert_eq!(out, 1.5);
+    }
+
+    #[test]
+    fn test_dynamic_input() {
+        let mut graph = Graph::new();
+        graph.insert_resource(
+            "dummy".to_string(),
+            DummyResource {
+                number_to_divide: 2.0,
+            },
+        );
+        let x = graph.input("x".to_string(), Layout::List(Box::new(Layout::Scalar), 3));
+        let output = graph
+            .call_resource(
+                "dummy",
+                "sum",
+                RefValue::Struct(HashMap::from([("x".to_string(), x)])),
+            )
+            .unwrap();
+        graph.output(output, Layout::Scalar).unwrap();
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        let loaded = Graph::load(std::io::Cursor::new(dumped.into_inner())).unwrap();
+        let func = loaded.compile().unwrap();
+        let out: f64 = func
+            .eval(&serde_json::json!({ "x": [1.0, 2.0, 3.0] }))
+            .unwrap();
+        assert_eq!(out, 3.0);
+
+        // Fixed-length inputs still have to match exactly.
+        let mut graph = Graph::new();
+        graph.insert_resource(
+            "dummy".to_string(),
+            DummyResource {
+                number_to_divide: 2.0,
+            },
+        );
+        let x = graph.input("x".to_string(), Layout::List(Box::new(Layout::Scalar), 3));
+        assert!(graph
+            .call_resource(
+                "dummy",
+                "get",
+                RefValue::Struct(HashMap::from([("x".to_string(), x)])),
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_dynamic_output() {
+        let mut graph = Graph::new();
+        graph.insert_resource(
+            "dummy".to_string(),
+            DummyResource {
+                number_to_divide: 2.0,
+            },
+        );
+        let x = graph.input("x".to_string(), Layout::List(Box::new(Layout::Scalar), 4));
+        let k = graph.input("k".to_string(), Layout::Scalar);
+        let output = graph
+            .call_resource(
+                "dummy",
+                "top_k",
+                RefValue::Struct(HashMap::from([("x".to_string(), x), ("k".to_string(), k)])),
+            )
+            .unwrap();
+        graph
+            .output(output, dynamic_output_layout(Layout::Scalar, 4))
+            .unwrap();
+
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        let loaded = Graph::load(std::io::Cursor::new(dumped.into_inner())).unwrap();
+        let func = loaded.compile().unwrap();
+
+        let out: serde_json::Value = func
+            .eval(&serde_json::json!({ "x": [3.0, 1.0, 4.0, 2.0], "k": 2.0 }))
+            .unwrap();
+        assert_eq!(
+            out,
+            serde_json::json!({ "len": 2, "values": [4.0, 3.0, 0.0, 0.0] })
+        );
+
+        let out: serde_json::Value = func
+            .eval(&serde_json::json!({ "x": [3.0, 1.0, 4.0, 2.0], "k": 0.0 }))
+            .unwrap();
+        assert_eq!(
+            out,
+            serde_json::json!({ "len": 0, "values": [0.0, 0.0, 0.0, 0.0] })
+        );
+
+        let err = func
+            .eval::<_, serde_json::Value>(&serde_json::json!({
+                "x": [3.0, 1.0, 4.0, 2.0],
+                "k": 5.0,
+            }))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("returned more than 4 elements"), "{err}");
+    }
+
+    thread_local! {
+        /// Where the bytes the last [`Probe`] resource was built from were.
+        static PROBED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    /// A resource type recording where the bytes its resources are built from are.
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Probe;
+
+    #[typetag::serde]
+    impl ResourceType for Probe {
+        fn from_bytes(&self, bytes: &[u8]) -> Result<Pin<Box<dyn Resource>>, Error> {
+            PROBED.with(|probed| probed.set(bytes.as_ptr() as usize));
+            Ok(Box::pin(ProbeResource))
+        }
+    }
+
+    #[derive(Debug)]
+    struct ProbeResource;
+
+    impl Resource for ProbeResource {
+        fn r#type(&self) -> Arc<dyn ResourceType> {
+            Arc::new(Probe)
+        }
+
+        fn dump(&self) -> Result<Vec<u8>, Error> {
+            Ok(b"probe".to_vec())
+        }
+
+        fn size(&self) -> usize {
+            0
+        }
+
+        fn get_method(&self, _: &str) -> Option<ResourceMethod> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_load_from_slice() {
+        let mut graph = create_dummy_graph();
+        graph.insert_resource("probe".to_string(), ProbeResource);
+        let mut dumped = std::io::Cursor::new(vec![]);
+        graph.dump(&mut dumped).unwrap();
+        let dumped = dumped.into_inner();
+
+        // Repacks the dump with stored entries, which can be read in place.
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&dumped)).unwrap();
+        let mut stored = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        for id in 0..archive.len() {
+            let mut file = archive.by_index(id).unwrap();
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored);
+            stored.start_file(file.name(), options).unwrap();
+            std::io::copy(&mut file, &mut stored).unwrap();
+        }
+        let stored = stored.finish().unwrap().into_inner();
+
+        // Only stored entries are read in place. Compressed ones are decompressed into a
+        // buffer first.
+        for (dumped, in_place) in [(dumped, false), (stored, true)] {
+            let func = Graph::load_from_slice(&dumped).unwrap().compile().unwrap();
+            let out: f64 = func.eval(&serde_json::json!({ "x": 5.0 })).unwrap();
+            assert_eq!(out, 2.5);
+
+            let probed = PROBED.with(|probed| probed.get()) as *const u8;
+            assert_eq!(dumped.as_ptr_range().contains(&probed), in_place);
+        }
+    }
+}
@@ -3,6 +3,7 @@
 use serde_derive::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use std::ffi::{CStr, CString};
+use std::io::{self, Write};
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 
@@ -143,6 +144,50 @@ impl Resource for ExternalResource {
         }
     }
 
+    fn dump_to(&self, writer: &mut dyn Write) -> Result<(), Error> {
+        let resource = self.r#type.resource();
+        let extension = self.r#type.extension();
+
+        let Some(fn_dump_to) = resource.fn_dump_to else {
+            // Still, write the dump directly from the extension memory, without copying.
+            unsafe {
+                // Safety: extension is correctly implemented.
+                let maybe_outcome = (resource.fn_dump)(self.ptr);
+                if maybe_outcome.0.is_null() {
+                    return Err(format!("dumped resource for {:?} was null", self.r#type).into());
+                }
+                let dumped = Dumped(extension.outcome_to_result(maybe_outcome)?);
+                extension.with_dumped(dumped, |dump| writer.write_all(dump))??;
+            }
+            return Ok(());
+        };
+
+        let mut context = WriteContext {
+            writer,
+            error: None,
+        };
+        let outcome = unsafe {
+            // Safety: extension is correctly implemented and the context outlives the call.
+            let maybe_outcome = fn_dump_to(
+                self.ptr,
+                &mut context as *mut WriteContext as *mut (),
+                write_to_context,
+            );
+            if maybe_outcome.0.is_null() {
+                return Err(format!("dumped resource for {:?} was null", self.r#type).into());
+            }
+            extension.outcome_to_result(maybe_outcome)
+        };
+
+        // The writer error is more informative than whatever the extension made of it.
+        if let Some(error) = context.error {
+            return Err(error.into());
+        }
+        outcome?;
+
+        Ok(())
+    }
+
     fn size(&self) -> usize {
         unsafe {
             // Safety: extension is correctly implemented.
@@ -180,6 +225,37 @@ impl Resource for ExternalResource {
             },
             input_layout: external_method.input_layout,
             output_layout: external_method.output_layout,
+            dynamic_inputs: external_method.dynamic_inputs,
+            dynamic_output: external_method.dynamic_output,
         })
     }
 }
+
+/// The context passed to extensions writing a resource incrementally.
+struct WriteContext<'a> {
+    writer: &'a mut dyn Write,
+    error: Option<io::Error>,
+}
+
+/// Writes a chunk of data into the writer of a [`WriteContext`]. This is called from
+/// extension code.
+unsafe extern "C" fn write_to_context(context: *mut (), ptr: *const u8, len: usize) -> i32 {
+    // Safety: the context is the one passed by `ExternalResource::dump_to`.
+    let context = &mut *(context as *mut WriteContext);
+    if context.error.is_some() {
+        return 1;
+    }
+
+    let chunk = if len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
+    };
+    match context.writer.write_all(chunk) {
+        Ok(()) => 0,
+        Err(error) => {
+            context.error = Some(error);
+            1
+        }
+    }
+}
@@ -55,6 +55,8 @@ impl Resource for SquareMatrixResource {
                 ),
                 output_layout: layout!(scalar),
                 fn_ptr: safe_method!(matix_det),
+                dynamic_inputs: vec![],
+                dynamic_output: false,
             },
             "inv" => ResourceMethod {
                 input_layout: r#struct!(
@@ -62,6 +64,8 @@ impl Resource for SquareMatrixResource {
                 ),
                 output_layout: layout!([[scalar; self.shape]; self.shape]),
                 fn_ptr: safe_method!(matrix_inv),
+                dynamic_inputs: vec![],
+                dynamic_output: false,
             },
             "solve" => ResourceMethod {
                 input_layout: r#struct!(
@@ -70,6 +74,8 @@ impl Resource for SquareMatrixResource {
                 ),
                 output_layout: layout!([scalar; self.shape]),
                 fn_ptr: safe_method!(matrix_solve),
+                dynamic_inputs: vec![],
+                dynamic_output: false,
             },
             "cholesky" => ResourceMethod {
                 input_layout: r#struct!(
@@ -77,6 +83,8 @@ impl Resource for SquareMatrixResource {
                 ),
                 output_layout: layout!([[scalar; self.shape]; self.shape]),
                 fn_ptr: safe_method!(matrix_cholesky),
+                dynamic_inputs: vec![],
+                dynamic_output: false,
             },
             _ => return None,
         })
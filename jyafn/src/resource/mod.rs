@@ -8,14 +8,14 @@ pub mod linalg;
 use byte_slice_cast::*;
 use get_size::GetSize;
 use serde_derive::{Deserialize, Serialize};
-use std::io::Read;
+use std::io::{Read, Write};
 use std::mem::MaybeUninit;
 use std::panic::{RefUnwindSafe, UnwindSafe};
 use std::pin::Pin;
 use std::sync::Arc;
 use zip::read::ZipFile;
 
-use crate::layout::{Layout, Struct};
+use crate::layout::{Layout, RefValue, Struct};
 use crate::Error;
 
 /// The signature of the function that will be invoked from inside the function code.
@@ -36,6 +36,94 @@ pub struct ResourceMethod {
     pub(crate) input_layout: Struct,
     /// The output layout for the method.
     pub(crate) output_layout: Layout,
+    /// The names of the input fields that are lists of dynamic length. These are
+    /// declared with length zero in `input_layout` and take the length of the list
+    /// supplied at each call. See [`ResourceMethod::input_layout_for`].
+    pub(crate) dynamic_inputs: Vec<String>,
+    /// Whether the output of this method is a list whose length is only known at call
+    /// time. The output layout of such methods has the shape created by
+    /// [`dynamic_output_layout`]. See [`ResourceMethod::max_output_len`].
+    pub(crate) dynamic_output: bool,
+}
+
+/// The output layout of a method whose output is a list of at most `max_len` elements of
+/// the given layout, but whose actual length is only known at call time.
+///
+/// This is a struct of two fields. The first, `len`, is an int with the number of
+/// elements actually written by the method. The second, `values`, is the list of elements
+/// with its maximum length. Elements past `len` are zeroed. Since methods get a buffer
+/// with the size of the whole layout, `len` is the first slot of the output buffer. After
+/// each call, jyafn checks that `len` is not greater than `max_len`, raising an error
+/// otherwise.
+pub fn dynamic_output_layout(element: Layout, max_len: usize) -> Layout {
+    Layout::Struct(Struct(vec![
+        ("len".to_string(), Layout::Int),
+        (
+            "values".to_string(),
+            Layout::List(Box::new(element), max_len),
+        ),
+    ]))
+}
+
+impl ResourceMethod {
+    /// The input layout of this method. Dynamic lists are declared with length zero. See
+    /// [`ResourceMethod::input_layout_for`].
+    pub fn input_layout(&self) -> &Struct {
+        &self.input_layout
+    }
+
+    /// The output layout of this method.
+    pub fn output_layout(&self) -> &Layout {
+        &self.output_layout
+    }
+
+    /// The maximum number of elements in the output of this method, if it has a dynamic
+    /// output. This returns `None` if the method has no dynamic output or if its output
+    /// layout was not created by [`dynamic_output_layout`].
+    pub fn max_output_len(&self) -> Option<usize> {
+        if !self.dynamic_output {
+            return None;
+        }
+
+        match &self.output_layout {
+            Layout::Struct(Struct(fields)) => match fields.as_slice() {
+                [(len, Layout::Int), (values, Layout::List(_, max_len))]
+                    if len == "len" && values == "values" =>
+                {
+                    Some(*max_len)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The input layout of this method for a call with the supplied arguments. Lists of
+    /// dynamic length take the length of the corresponding argument, if it is a list.
+    /// Methods without dynamic inputs always have their declared input layout.
+    ///
+    /// Since the method only gets to know the total number of slots in its input, it
+    /// can only tell the length of a dynamic list apart if it is the only one.
+    pub fn input_layout_for(&self, args: &RefValue) -> Struct {
+        let RefValue::Struct(args) = args else {
+            return self.input_layout.clone();
+        };
+
+        Struct(
+            self.input_layout
+                .0
+                .iter()
+                .map(|(name, layout)| match (layout, args.get(name)) {
+                    (Layout::List(element, _), Some(RefValue::List(arg)))
+                        if self.dynamic_inputs.contains(name) =>
+                    {
+                        (name.clone(), Layout::List(element.clone(), arg.len()))
+                    }
+                    _ => (name.clone(), layout.clone()),
+                })
+                .collect(),
+        )
+    }
 }
 
 /// A `ResourceType` creates resources of a given type. Think of this as the "class
@@ -66,6 +154,15 @@ pub trait Resource: 'static + std::fmt::Debug + Send + Sync + UnwindSafe + RefUn
     fn r#type(&self) -> Arc<dyn ResourceType>;
     /// Dumps this resource as binary data.
     fn dump(&self) -> Result<Vec<u8>, Error>;
+    /// Writes the binary data of this resource, the same as returned by
+    /// `Resource::dump`, into the supplied writer.
+    ///
+    /// Override this method if the resource can be written incrementally, so that big
+    /// resources don't need to be entirely buffered in memory when graphs are dumped.
+    fn dump_to(&self, writer: &mut dyn Write) -> Result<(), Error> {
+        writer.write_all(&self.dump()?)?;
+        Ok(())
+    }
     /// The ammount of heap used by this storage.
     fn size(&self) -> usize;
     /// Gets information on a method name for this resource, if it exists.
@@ -87,7 +184,9 @@ pub struct ResourceContainer {
     ///
     /// We need this field because we _hardcode_ this pointer in the function code. If
     /// this moves anywhere, we get the pleasure of accessing bad memory and The Most
-    /// Horrible Things™ ensue.
+    /// Horrible Things™ ensue. Containers live behind an `Arc` in the graph, which the
+    /// compiled function holds on to, so that the pointer stays valid for as long as
+    /// the function lives.
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
     #[serde(default)]
@@ -130,12 +229,22 @@ impl ResourceContainer {
         })
     }
 
-    /// Dumps this resource as binary information.
-    pub(crate) fn dump(&self) -> Result<Vec<u8>, Error> {
+    /// Builds the resource from binary data, such as a zip file entry that is stored
+    /// uncompressed in memory.
+    pub(crate) fn read_bytes(&self, bytes: &[u8]) -> Result<Self, Error> {
+        let resource = self.resource_type.from_bytes(bytes)?;
+        Ok(ResourceContainer {
+            resource_type: self.resource_type.clone(),
+            resource: Some(resource),
+        })
+    }
+
+    /// Writes this resource as binary information into the supplied writer.
+    pub(crate) fn dump_to(&self, writer: &mut dyn Write) -> Result<(), Error> {
         self.resource
             .as_ref()
             .expect("resource not initialized")
-            .dump()
+            .dump_to(writer)
     }
 
     /// Checks whether this container was already initialized with a resource.
@@ -219,14 +328,20 @@ impl<'a> Input<'a> {
 pub struct OutputBuilder<'a> {
     position: usize,
     slice: &'a mut [MaybeUninit<u64>],
+    /// Whether the first slot of the output is the length of a dynamic output.
+    len_prefix: bool,
 }
 
 impl<'a> Drop for OutputBuilder<'a> {
     fn drop(&mut self) {
-        // This prevents any uninitialized memory from ever being read.
-        while self.position < self.slice.len() {
-            self.push_u64(0)
+        // Methods usually write the whole output, so there is nothing left to do.
+        if self.is_fully_written() {
+            return;
         }
+
+        // This prevents any uninitialized memory from ever being read.
+        self.slice[self.position..].fill(MaybeUninit::new(0));
+        self.position = self.slice.len();
     }
 }
 
@@ -241,9 +356,45 @@ impl<'a> OutputBuilder<'a> {
         Self {
             position: 0,
             slice: std::slice::from_raw_parts_mut(output as *mut MaybeUninit<u64>, n_slots),
+            len_prefix: false,
         }
     }
 
+    /// Creates a new output for a method with a dynamic output (see
+    /// [`dynamic_output_layout`]). The first slot is reserved for the length of the
+    /// output, which is zero until [`OutputBuilder::finish_with_len`] is called. Pushed
+    /// values start at the second slot.
+    ///
+    /// # Safety
+    ///
+    /// Make sure that `output` points to a slice with _memory size_ of `8 * n_slots` at
+    /// least, with `n_slots` at least one. Failing to do so, writes to bad memory may
+    /// occur.
+    pub unsafe fn new_dynamic(output: *mut u8, n_slots: usize) -> Self {
+        let mut builder = Self::new(output, n_slots);
+        builder.push_u64(0);
+        builder.len_prefix = true;
+        builder
+    }
+
+    /// Records the number of elements actually written to a dynamic output. The slots
+    /// that were not written are zero-filled.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the builder was not created with
+    /// [`OutputBuilder::new_dynamic`].
+    pub fn finish_with_len(self, len: usize) {
+        assert!(self.len_prefix, "output is not dynamic");
+        self.slice[0].write(len as u64);
+    }
+
+    /// Whether all the slots of the output have been written. Unwritten slots are
+    /// zero-filled when the builder is dropped.
+    pub fn is_fully_written(&self) -> bool {
+        self.position == self.slice.len()
+    }
+
     pub fn push_f64(&mut self, val: f64) {
         self.slice[self.position].write(u64::from_ne_bytes(val.to_ne_bytes()));
         self.position += 1;
@@ -303,9 +454,19 @@ impl<'a> OutputBuilder<'a> {
 /// }
 ///
 /// ```
+///
+/// Methods with a dynamic output (see [`dynamic_output_layout`]) are declared with
+/// `safe_method!(dynamic something_safe)`, which creates the output builder with
+/// [`OutputBuilder::new_dynamic`].
 #[macro_export]
 macro_rules! safe_method {
-    ($safe_interface:ident) => {{
+    ($safe_interface:ident) => {
+        $crate::safe_method!(@with new, $safe_interface)
+    };
+    (dynamic $safe_interface:ident) => {
+        $crate::safe_method!(@with new_dynamic, $safe_interface)
+    };
+    (@with $new:ident, $safe_interface:ident) => {{
         pub unsafe extern "C" fn safe_interface(
             resource_ptr: *const (),
             input_ptr: *const u8,
@@ -324,7 +485,7 @@ macro_rules! safe_method {
                     $safe_interface(
                         resource,
                         $crate::resource::Input::new(input_ptr, input_slots as usize),
-                        $crate::resource::OutputBuilder::new(output_ptr, output_slots as usize),
+                        $crate::resource::OutputBuilder::$new(output_ptr, output_slots as usize),
                     )
                 }
             }) {
@@ -338,3 +499,27 @@ macro_rules! safe_method {
         $crate::resource::RawResourceMethod(safe_interface)
     }};
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_output_builder_zero_fills_unwritten_slots() {
+        let mut output = [u64::MAX; 4];
+
+        unsafe {
+            let mut builder = OutputBuilder::new(output.as_mut_ptr() as *mut u8, 3);
+            builder.push_f64(1.5);
+            assert!(!builder.is_fully_written());
+        }
+        assert_eq!(output, [1.5f64.to_bits(), 0, 0, u64::MAX]);
+
+        unsafe {
+            let mut builder = OutputBuilder::new(output.as_mut_ptr() as *mut u8, 3);
+            builder.copy_from_u64(&[1, 2, 3]);
+            assert!(builder.is_fully_written());
+        }
+        assert_eq!(output, [1, 2, 3, u64::MAX]);
+    }
+}
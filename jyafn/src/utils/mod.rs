@@ -57,9 +57,24 @@ impl From<DateTime<Utc>> for Timestamp {
     }
 }
 
+impl Timestamp {
+    /// Converts this timestamp into a datetime, returning `None` if the timestamp is out
+    /// of the range representable by [`DateTime`].
+    pub fn to_datetime(&self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp_micros(self.0)
+    }
+}
+
+/// This conversion never panics: timestamps out of the range representable by
+/// [`DateTime`] are clamped to [`DateTime::<Utc>::MIN_UTC`] or [`DateTime::<Utc>::MAX_UTC`].
+/// Use [`Timestamp::to_datetime`] if you need to detect out of range values.
 impl From<Timestamp> for DateTime<Utc> {
     fn from(timestamp: Timestamp) -> DateTime<Utc> {
-        DateTime::from_timestamp_micros(timestamp.0).expect("out of range timestamp")
+        timestamp.to_datetime().unwrap_or(if timestamp.0 < 0 {
+            DateTime::<Utc>::MIN_UTC
+        } else {
+            DateTime::<Utc>::MAX_UTC
+        })
     }
 }
 
@@ -75,9 +90,10 @@ impl From<i64> for Timestamp {
     }
 }
 
-/// Tranforms an integer into a datetime in UTC.
-pub fn int_to_datetime(i: i64) -> DateTime<Utc> {
-    DateTime::<Utc>::from(Timestamp::from(i))
+/// Tranforms an integer into a datetime in UTC, returning `None` if the integer is out of
+/// the range representable by [`DateTime`].
+pub fn int_to_datetime(i: i64) -> Option<DateTime<Utc>> {
+    Timestamp::from(i).to_datetime()
 }
 
 /// Creates a C-style string out of a `String` in a way that doesn't produce errors. This
@@ -113,4 +129,19 @@ mod test {
     fn test_parse_datetime() {
         parse_datetime("2024-04-10", "%Y-%m-%d").unwrap();
     }
+
+    #[test]
+    fn test_out_of_range_timestamp() {
+        assert_eq!(int_to_datetime(i64::MAX), None);
+        assert_eq!(int_to_datetime(i64::MIN), None);
+        assert_eq!(
+            DateTime::<Utc>::from(Timestamp::from(i64::MAX)),
+            DateTime::<Utc>::MAX_UTC
+        );
+        assert_eq!(
+            DateTime::<Utc>::from(Timestamp::from(i64::MIN)),
+            DateTime::<Utc>::MIN_UTC
+        );
+        format_datetime(i64::MAX, "%Y-%m-%d");
+    }
 }
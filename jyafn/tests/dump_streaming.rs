@@ -0,0 +1,141 @@
+//! Checks that dumping graphs streams big resources instead of buffering them. This lives
+//! in its own test binary because it needs to replace the global allocator to measure
+//! how much memory is allocated.
+
+use serde_derive::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use jyafn::resource::{Resource, ResourceMethod, ResourceType};
+use jyafn::{Error, Graph};
+
+/// Tracks the peak of heap memory allocated by the current thread, so that tests running
+/// in parallel don't interfere with each other.
+struct PeakAlloc;
+
+thread_local! {
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+    static PEAK: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED.try_with(|allocated| {
+            allocated.set(allocated.get() + layout.size());
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(allocated.get())));
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = ALLOCATED
+            .try_with(|allocated| allocated.set(allocated.get().saturating_sub(layout.size())));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc;
+
+/// Measures the peak of extra heap memory allocated by the current thread while running
+/// `f`.
+fn peak_allocated<F: FnOnce()>(f: F) -> usize {
+    let start = ALLOCATED.with(Cell::get);
+    PEAK.with(|peak| peak.set(start));
+    f();
+    PEAK.with(Cell::get) - start
+}
+
+const CHUNK_SIZE: usize = 1 << 16;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Generated;
+
+#[typetag::serde]
+impl ResourceType for Generated {
+    fn from_bytes(&self, bytes: &[u8]) -> Result<Pin<Box<dyn Resource>>, Error> {
+        Ok(Box::pin(GeneratedResource {
+            len: bytes.len(),
+            streaming: true,
+        }))
+    }
+}
+
+/// A big resource whose binary data is generated on demand.
+#[derive(Debug)]
+struct GeneratedResource {
+    len: usize,
+    streaming: bool,
+}
+
+impl Resource for GeneratedResource {
+    fn r#type(&self) -> Arc<dyn ResourceType> {
+        Arc::new(Generated)
+    }
+
+    fn dump(&self) -> Result<Vec<u8>, Error> {
+        Ok((0..self.len).map(|i| i as u8).collect())
+    }
+
+    fn dump_to(&self, writer: &mut dyn Write) -> Result<(), Error> {
+        if !self.streaming {
+            writer.write_all(&self.dump()?)?;
+            return Ok(());
+        }
+
+        let chunk = (0..CHUNK_SIZE).map(|i| i as u8).collect::<Vec<_>>();
+        for start in (0..self.len).step_by(CHUNK_SIZE) {
+            writer.write_all(&chunk[..CHUNK_SIZE.min(self.len - start)])?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn get_method(&self, _: &str) -> Option<ResourceMethod> {
+        None
+    }
+}
+
+/// The length of the data of the `generated` resource in a dumped graph.
+fn dumped_resource_len(dumped: &[u8]) -> u64 {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(dumped)).unwrap();
+    let mut file = archive.by_name("generated.resource").unwrap();
+    std::io::copy(&mut file, &mut std::io::sink()).unwrap()
+}
+
+#[test]
+fn test_dump_streams_resources() {
+    const LEN: usize = 16 << 20;
+
+    let peak_dumping = |streaming| {
+        let mut graph = Graph::new();
+        graph.insert_resource(
+            "generated".to_string(),
+            GeneratedResource {
+                len: LEN,
+                streaming,
+            },
+        );
+        let mut dumped = std::io::Cursor::new(Vec::new());
+        let peak = peak_allocated(|| graph.dump(&mut dumped).unwrap());
+        (peak, dumped.into_inner())
+    };
+
+    let (buffered_peak, buffered) = peak_dumping(false);
+    let (streamed_peak, streamed) = peak_dumping(true);
+    assert!(buffered_peak >= LEN);
+    assert!(streamed_peak < LEN / 4, "streamed peak was {streamed_peak}");
+
+    for dumped in [buffered, streamed] {
+        let graph = Graph::load(std::io::Cursor::new(dumped)).unwrap();
+        let mut redumped = std::io::Cursor::new(Vec::new());
+        graph.dump(&mut redumped).unwrap();
+        assert_eq!(dumped_resource_len(redumped.get_ref()), LEN as u64);
+    }
+}
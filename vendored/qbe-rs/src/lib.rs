@@ -93,6 +93,8 @@ pub enum Instr<'a> {
     Blit(Value, Value, u64),
     Ultof(Value),
     Dtoui(Value),
+    /// Extends a single precision float to double precision
+    Exts(Value),
 }
 
 impl<'a> fmt::Display for Instr<'a> {
@@ -178,6 +180,7 @@ impl<'a> fmt::Display for Instr<'a> {
             Self::Blit(src, dst, n) => write!(f, "blit {}, {}, {}", src, dst, n),
             Self::Ultof(val) => write!(f, "ultof {val}"),
             Self::Dtoui(val) => write!(f, "dtoui {val}"),
+            Self::Exts(val) => write!(f, "exts {val}"),
         }
     }
 }